@@ -6,8 +6,143 @@
 #include <stdint.h>
 #include <stdlib.h>
 
+/**
+ * The call succeeded
+ */
+#define JASONISNTHAPPY_OK 0
+
+/**
+ * An error without a more specific code; see the message
+ */
+#define JASONISNTHAPPY_ERROR_UNKNOWN -1
+
+/**
+ * A null pointer, invalid UTF-8, malformed JSON or another bad argument
+ */
+#define JASONISNTHAPPY_ERROR_INVALID_ARGUMENT 1
+
+/**
+ * The document, collection, index or savepoint does not exist
+ */
+#define JASONISNTHAPPY_ERROR_NOT_FOUND 2
+
+/**
+ * The document or collection already exists
+ */
+#define JASONISNTHAPPY_ERROR_ALREADY_EXISTS 3
+
+/**
+ * Another transaction changed the same data; retrying may succeed
+ */
+#define JASONISNTHAPPY_ERROR_TX_CONFLICT 4
+
+/**
+ * The document failed validation against the collection schema
+ */
+#define JASONISNTHAPPY_ERROR_VALIDATION 5
+
+/**
+ * A unique index already holds the value
+ */
+#define JASONISNTHAPPY_ERROR_DUPLICATE_KEY 6
+
+/**
+ * A document or bulk operation is over the configured size limit
+ */
+#define JASONISNTHAPPY_ERROR_LIMIT_EXCEEDED 7
+
+/**
+ * The transaction was already committed or rolled back
+ */
+#define JASONISNTHAPPY_ERROR_TX_NOT_ACTIVE 8
+
+/**
+ * The transaction ran past its maximum duration
+ */
+#define JASONISNTHAPPY_ERROR_TX_TIMEOUT 9
+
+/**
+ * The database or transaction is read-only
+ */
+#define JASONISNTHAPPY_ERROR_READ_ONLY 10
+
+/**
+ * The database file or WAL is damaged
+ */
+#define JASONISNTHAPPY_ERROR_CORRUPTION 11
+
+/**
+ * Reading or writing the database files failed
+ */
+#define JASONISNTHAPPY_ERROR_IO 12
+
+/**
+ * A struct passed in was laid out for a different version of this library
+ */
+#define JASONISNTHAPPY_ERROR_ABI_MISMATCH 13
+
+/**
+ * Another process has the database open in a conflicting mode
+ */
+#define JASONISNTHAPPY_ERROR_LOCKED 14
+
+/**
+ * Fsync the WAL and database file before every commit returns
+ */
+#define JASONISNTHAPPY_DURABILITY_FULL 0
+
+/**
+ * Fsync the WAL on every commit and the database file only on checkpoint and close
+ */
+#define JASONISNTHAPPY_DURABILITY_NORMAL 1
+
+/**
+ * Fsync the WAL on every commit and the database file at most every `durability_interval_ms`
+ */
+#define JASONISNTHAPPY_DURABILITY_BATCHED 2
+
+/**
+ * Compare strings exactly as stored
+ */
+#define JASONISNTHAPPY_COLLATION_BINARY 0
+
+/**
+ * Compare ASCII letters without regard to case
+ */
+#define JASONISNTHAPPY_COLLATION_ASCII_CASE_INSENSITIVE 1
+
+/**
+ * Compare letters without regard to case, using Unicode simple case folding
+ */
+#define JASONISNTHAPPY_COLLATION_CASE_INSENSITIVE 2
+
+/**
+ * Version of the C ABI. Bumped whenever a struct layout or function
+ * signature changes incompatibly.
+ */
+#define JASONISNTHAPPY_ABI_VERSION 1
+
+/**
+ * Watch inserts, in jasonisnthappy_collection_watch_start_with_operations
+ */
+#define JASONISNTHAPPY_WATCH_INSERT 1
+
+/**
+ * Watch updates, in jasonisnthappy_collection_watch_start_with_operations
+ */
+#define JASONISNTHAPPY_WATCH_UPDATE 2
+
+/**
+ * Watch deletes, in jasonisnthappy_collection_watch_start_with_operations
+ */
+#define JASONISNTHAPPY_WATCH_DELETE 4
+
 typedef struct Arc_Database Arc_Database;
 
+typedef struct CCursor CCursor;
+
+typedef struct CKvStore CKvStore;
+
 typedef struct CWatchHandle CWatchHandle;
 
 typedef struct Option_WebServer Option_WebServer;
@@ -17,11 +152,19 @@ typedef struct CDatabase {
 } CDatabase;
 
 typedef struct CError {
+  /**
+   * One of the `JASONISNTHAPPY_OK` / `JASONISNTHAPPY_ERROR_*` codes
+   */
   int32_t code;
   char *message;
 } CError;
 
 typedef struct CDatabaseOptions {
+  /**
+   * sizeof(CDatabaseOptions) as the caller was compiled, already set by
+   * `jasonisnthappy_default_database_options`
+   */
+  uint32_t struct_size;
   uintptr_t cache_size;
   uint64_t auto_checkpoint_threshold;
   uint32_t file_permissions;
@@ -29,14 +172,107 @@ typedef struct CDatabaseOptions {
   uintptr_t max_bulk_operations;
   uintptr_t max_document_size;
   uintptr_t max_request_body_size;
+  bool document_metadata;
+  uintptr_t scan_threads;
+  bool enable_id_bloom_filter;
+  /**
+   * Page size for a new database file; 0 keeps an existing file's size
+   */
+  uintptr_t page_size;
+  /**
+   * WAL size in bytes that makes the next commit checkpoint first; 0 for no cap
+   */
+  uint64_t max_wal_bytes;
+  /**
+   * List every schema violation in validation errors, not just the first
+   */
+  bool collect_all_validation_errors;
+  /**
+   * Checksum every page of a new database file; an existing file keeps its setting
+   */
+  bool page_checksums;
+  /**
+   * Milliseconds to wait for another process to release the database; 0 fails straight away
+   */
+  uint64_t lock_timeout_ms;
+  /**
+   * Most documents a collection scan may examine; 0 for no limit
+   */
+  uintptr_t max_scan_documents;
+  /**
+   * Most documents a query may collect as results; 0 for no limit
+   */
+  uintptr_t max_result_documents;
+  /**
+   * Queries running at least this many milliseconds go to the slow query log; 0 disables it
+   */
+  uint64_t slow_query_threshold_ms;
+  /**
+   * Number of slow queries kept, oldest dropped first
+   */
+  uintptr_t slow_query_log_size;
+  /**
+   * When commits fsync: one of the `JASONISNTHAPPY_DURABILITY_*` values
+   */
+  uint32_t durability;
+  /**
+   * Milliseconds between fsyncs with `JASONISNTHAPPY_DURABILITY_BATCHED`
+   */
+  uint64_t durability_interval_ms;
+  /**
+   * Never match a number against a string in query comparisons; when false,
+   * strings holding numbers compare as those numbers
+   */
+  bool strict_query_types;
+  /**
+   * Number of `find` and aggregation results cached until a write to their
+   * collection; 0 disables the cache
+   */
+  uintptr_t query_cache_size;
 } CDatabaseOptions;
 
 typedef struct CTransactionConfig {
+  /**
+   * sizeof(CTransactionConfig) as the caller was compiled, already set by
+   * `jasonisnthappy_default_transaction_config`
+   */
+  uint32_t struct_size;
   uintptr_t max_retries;
   uint64_t retry_backoff_base_ms;
   uint64_t max_retry_backoff_ms;
+  /**
+   * 0 means no limit
+   */
+  uint64_t max_transaction_duration_ms;
+  /**
+   * Shortest wait between retries, and the low end of the jittered range
+   */
+  uint64_t min_retry_backoff_ms;
+  /**
+   * Randomize each retry wait between the minimum and the doubled backoff
+   */
+  bool jitter;
 } CTransactionConfig;
 
+typedef struct CIndexOptions {
+  /**
+   * sizeof(CIndexOptions) as the caller was compiled, already set by
+   * `jasonisnthappy_default_index_options`
+   */
+  uint32_t struct_size;
+  bool unique;
+  bool descending;
+  /**
+   * Leave out documents where any indexed field is missing or null, so they
+   * never conflict in a unique index
+   */
+  bool sparse;
+  /**
+   * How indexed strings compare: one of the `JASONISNTHAPPY_COLLATION_*` values
+   */
+  uint32_t collation;
+} CIndexOptions;
+
 typedef struct CTransaction {
   Transaction inner;
 } CTransaction;
@@ -53,6 +289,14 @@ typedef struct CTransaction {
  */
 typedef int32_t (*TransactionCallback)(struct CTransaction *tx, void *user_data);
 
+/**
+ * Bytes returned to C, freed with `jasonisnthappy_free_buffer`
+ */
+typedef struct CBuffer {
+  uint8_t *data;
+  uintptr_t len;
+} CBuffer;
+
 typedef struct CCollection {
   Collection inner;
 } CCollection;
@@ -65,18 +309,32 @@ typedef struct CCollection {
  * - operation: "insert", "update", or "delete"
  * - doc_id: ID of the document
  * - doc_json: JSON representation of the document (NULL for delete operations)
+ * - old_doc_json: JSON representation of the document before the change
+ *   (NULL unless watching with full_document_before_change for updates and deletes)
  * - user_data: User-provided context pointer passed to watch_start
  */
 typedef void (*WatchCallback)(const char *collection,
                               const char *operation,
                               const char *doc_id,
                               const char *doc_json,
+                              const char *old_doc_json,
                               void *user_data);
 
 typedef struct CWebServer {
   struct Option_WebServer inner;
 } CWebServer;
 
+/**
+ * Version of the library, e.g. "0.1.1". The string is static and must not be freed.
+ */
+const char *jasonisnthappy_version(void);
+
+/**
+ * ABI version of the loaded library. Compare it against
+ * `JASONISNTHAPPY_ABI_VERSION` from the header the program was built with.
+ */
+uint32_t jasonisnthappy_abi_version(void);
+
 struct CDatabase *jasonisnthappy_open(const char *path, struct CError *error_out);
 
 struct CDatabase *jasonisnthappy_open_with_options(const char *path,
@@ -99,11 +357,19 @@ int32_t jasonisnthappy_set_auto_checkpoint_threshold(struct CDatabase *db,
 
 struct CDatabaseOptions jasonisnthappy_default_database_options(void);
 
+struct CIndexOptions jasonisnthappy_default_index_options(void);
+
 struct CTransactionConfig jasonisnthappy_default_transaction_config(void);
 
 struct CTransaction *jasonisnthappy_begin_transaction(struct CDatabase *db,
                                                       struct CError *error_out);
 
+/**
+ * Begin a transaction that can only read. Writes through it fail, and it is
+ * finished with `jasonisnthappy_commit` or `jasonisnthappy_rollback` as usual.
+ */
+struct CTransaction *jasonisnthappy_begin_read_only(struct CDatabase *db, struct CError *error_out);
+
 int32_t jasonisnthappy_commit(struct CTransaction *tx, struct CError *error_out);
 
 void jasonisnthappy_rollback(struct CTransaction *tx);
@@ -144,6 +410,29 @@ int32_t jasonisnthappy_run_transaction(struct CDatabase *db,
  */
 int32_t jasonisnthappy_transaction_is_active(struct CTransaction *tx, struct CError *error_out);
 
+/**
+ * Create a named savepoint in a transaction
+ */
+int32_t jasonisnthappy_savepoint(struct CTransaction *tx,
+                                 const char *name,
+                                 struct CError *error_out);
+
+/**
+ * Discard all writes made in a transaction since the named savepoint
+ *
+ * The savepoint is kept and can be rolled back to again.
+ */
+int32_t jasonisnthappy_rollback_to_savepoint(struct CTransaction *tx,
+                                             const char *name,
+                                             struct CError *error_out);
+
+/**
+ * Remove a named savepoint, keeping the writes made since it was created
+ */
+int32_t jasonisnthappy_release_savepoint(struct CTransaction *tx,
+                                         const char *name,
+                                         struct CError *error_out);
+
 int32_t jasonisnthappy_insert(struct CTransaction *tx,
                               const char *collection_name,
                               const char *json,
@@ -174,6 +463,8 @@ int32_t jasonisnthappy_find_all(struct CTransaction *tx,
 
 void jasonisnthappy_free_string(char *s);
 
+void jasonisnthappy_free_buffer(struct CBuffer buffer);
+
 void jasonisnthappy_free_error(struct CError error);
 
 int32_t jasonisnthappy_count(struct CTransaction *tx,
@@ -194,14 +485,43 @@ int32_t jasonisnthappy_rename_collection(struct CTransaction *tx,
                                          const char *new_name,
                                          struct CError *error_out);
 
+/**
+ * Rename a collection outside of any transaction
+ */
+int32_t jasonisnthappy_database_rename_collection(struct CDatabase *db,
+                                                  const char *old_name,
+                                                  const char *new_name,
+                                                  struct CError *error_out);
+
+/**
+ * Create `dst` as a copy of `src` with its documents, indexes and schema.
+ * On failure `dst` is not left behind.
+ */
+int32_t jasonisnthappy_copy_collection(struct CDatabase *db,
+                                       const char *src,
+                                       const char *dst,
+                                       struct CError *error_out);
+
 int32_t jasonisnthappy_list_collections(struct CDatabase *db,
                                         char **json_out,
                                         struct CError *error_out);
 
+/**
+ * Check whether a collection has an index (regular or text) with this name
+ *
+ * A missing collection is reported as `false`, not as an error.
+ */
+int32_t jasonisnthappy_index_exists(struct CDatabase *db,
+                                    const char *collection_name,
+                                    const char *index_name,
+                                    bool *exists_out,
+                                    struct CError *error_out);
+
 /**
  * List all indexes for a collection
  *
- * Returns JSON array of index objects with: name, fields (array), unique (bool), btree_root
+ * Returns JSON array of index objects with: name, fields (array), unique (bool),
+ * descending (bool), sparse (bool), btree_root
  */
 int32_t jasonisnthappy_list_indexes(struct CDatabase *db,
                                     const char *collection_name,
@@ -215,6 +535,17 @@ int32_t jasonisnthappy_create_index(struct CDatabase *db,
                                     bool unique,
                                     struct CError *error_out);
 
+/**
+ * Create a geo index over a latitude and a longitude field, used by
+ * `near(field, lat, lng, radius_km)` queries
+ */
+int32_t jasonisnthappy_create_geo_index(struct CDatabase *db,
+                                        const char *collection_name,
+                                        const char *index_name,
+                                        const char *lat_field,
+                                        const char *lng_field,
+                                        struct CError *error_out);
+
 int32_t jasonisnthappy_create_compound_index(struct CDatabase *db,
                                              const char *collection_name,
                                              const char *index_name,
@@ -223,6 +554,29 @@ int32_t jasonisnthappy_create_compound_index(struct CDatabase *db,
                                              bool unique,
                                              struct CError *error_out);
 
+/**
+ * Create a single-field or compound index with explicit options. Start from
+ * `jasonisnthappy_default_index_options()` so `struct_size` is filled in.
+ */
+int32_t jasonisnthappy_create_index_with_options(struct CDatabase *db,
+                                                 const char *collection_name,
+                                                 const char *index_name,
+                                                 const char *const *fields,
+                                                 uintptr_t num_fields,
+                                                 struct CIndexOptions options,
+                                                 struct CError *error_out);
+
+/**
+ * Create an index keyed by an expression such as `lower(email)`, used by
+ * queries comparing the same expression with `is`
+ */
+int32_t jasonisnthappy_create_expression_index(struct CDatabase *db,
+                                               const char *collection_name,
+                                               const char *index_name,
+                                               const char *expression,
+                                               struct CIndexOptions options,
+                                               struct CError *error_out);
+
 int32_t jasonisnthappy_create_text_index(struct CDatabase *db,
                                          const char *collection_name,
                                          const char *index_name,
@@ -235,15 +589,44 @@ int32_t jasonisnthappy_drop_index(struct CDatabase *db,
                                   const char *index_name,
                                   struct CError *error_out);
 
+/**
+ * Writes the collection's `CollectionInfo` as JSON to `json_out`: document
+ * count, data size and average document size, and every index with its
+ * entry count and size in bytes.
+ */
 int32_t jasonisnthappy_collection_stats(struct CDatabase *db,
                                         const char *collection_name,
                                         char **json_out,
                                         struct CError *error_out);
 
+/**
+ * Writes the `DatabaseInfo` as JSON to `json_out`, with every collection
+ * described as by `jasonisnthappy_collection_stats`.
+ */
 int32_t jasonisnthappy_database_info(struct CDatabase *db,
                                      char **json_out,
                                      struct CError *error_out);
 
+/**
+ * Check the database for corruption. On success `report_json_out` receives
+ * the integrity report as JSON; the check itself succeeding says nothing
+ * about whether problems were found, so inspect its `problems` array.
+ */
+int32_t jasonisnthappy_check_integrity(struct CDatabase *db,
+                                       char **report_json_out,
+                                       struct CError *error_out);
+
+/**
+ * Read a collection's B-trees, and with `documents` its document pages, into
+ * the page cache. A null `collection_name` warms every collection. On success
+ * `warmup_json_out` receives the pages read as JSON.
+ */
+int32_t jasonisnthappy_warm_cache(struct CDatabase *db,
+                                  const char *collection_name,
+                                  bool documents,
+                                  char **warmup_json_out,
+                                  struct CError *error_out);
+
 int32_t jasonisnthappy_get_path(struct CDatabase *db, char **path_out, struct CError *error_out);
 
 int32_t jasonisnthappy_is_read_only(struct CDatabase *db, struct CError *error_out);
@@ -259,6 +642,26 @@ int32_t jasonisnthappy_set_schema(struct CDatabase *db,
                                   const char *schema_json,
                                   struct CError *error_out);
 
+/**
+ * Set a collection's schema from a standard JSON Schema document, translating
+ * the supported subset. `warnings_json_out` receives a JSON array of strings,
+ * one per keyword that was ignored (empty if everything is enforced).
+ */
+int32_t jasonisnthappy_set_json_schema(struct CDatabase *db,
+                                       const char *collection_name,
+                                       const char *schema_json,
+                                       char **warnings_json_out,
+                                       struct CError *error_out);
+
+/**
+ * Set how a collection generates `_id` for documents inserted without one.
+ * `strategy` is one of "uuid", "object_id", "ulid" or "auto_increment".
+ */
+int32_t jasonisnthappy_set_id_strategy(struct CDatabase *db,
+                                       const char *collection_name,
+                                       const char *strategy,
+                                       struct CError *error_out);
+
 int32_t jasonisnthappy_get_schema(struct CDatabase *db,
                                   const char *collection_name,
                                   char **schema_json_out,
@@ -288,16 +691,123 @@ int32_t jasonisnthappy_collection_upsert(struct CCollection *coll,
                                          char **id_out,
                                          struct CError *error_out);
 
+/**
+ * Like `jasonisnthappy_collection_upsert_by_id`, but merges `json` into an
+ * existing document, recursing into nested objects, instead of setting its
+ * top-level fields wholesale
+ */
+int32_t jasonisnthappy_collection_upsert_merge_by_id(struct CCollection *coll,
+                                                     const char *id,
+                                                     const char *json,
+                                                     int32_t *result_out,
+                                                     char **id_out,
+                                                     struct CError *error_out);
+
+/**
+ * Like `jasonisnthappy_collection_upsert`, but merges `json` into the first
+ * match the same way as `jasonisnthappy_collection_upsert_merge_by_id`
+ */
+int32_t jasonisnthappy_collection_upsert_merge(struct CCollection *coll,
+                                               const char *query,
+                                               const char *json,
+                                               int32_t *result_out,
+                                               char **id_out,
+                                               struct CError *error_out);
+
 int32_t jasonisnthappy_collection_find(struct CCollection *coll,
                                        const char *query,
                                        char **json_out,
                                        struct CError *error_out);
 
+/**
+ * Opens a cursor over the documents matching `query`, written to
+ * `cursor_out`. Documents are read a batch at a time as the cursor
+ * advances, all from the snapshot taken here, which stays open until
+ * `jasonisnthappy_cursor_free`. Documents come in `_id` order.
+ */
+int32_t jasonisnthappy_collection_find_cursor(struct CCollection *coll,
+                                              const char *query,
+                                              struct CCursor **cursor_out,
+                                              struct CError *error_out);
+
+/**
+ * Advances `cursor`, writing the next document's JSON to `doc_json_out`
+ * for the caller to free with `jasonisnthappy_free_string`.
+ * Returns 1 when a document was written, 0 once the cursor is exhausted
+ * and -1 on error.
+ */
+int32_t jasonisnthappy_cursor_next(struct CCursor *cursor,
+                                   char **doc_json_out,
+                                   struct CError *error_out);
+
+/**
+ * Frees a cursor and releases its snapshot
+ */
+void jasonisnthappy_cursor_free(struct CCursor *cursor);
+
+/**
+ * Runs a query and writes a JSON report of how it was executed to `json_out`:
+ * access path, documents examined (estimated and actual), documents returned,
+ * whether sorting happened in memory and elapsed microseconds.
+ */
+int32_t jasonisnthappy_collection_explain(struct CCollection *coll,
+                                          const char *query,
+                                          char **json_out,
+                                          struct CError *error_out);
+
 int32_t jasonisnthappy_collection_find_one(struct CCollection *coll,
                                            const char *query,
                                            char **json_out,
                                            struct CError *error_out);
 
+/**
+ * Like `jasonisnthappy_collection_find`, binding the query's `?1`, `?2`, ...
+ * placeholders to the values in `params_json`, a JSON array. Each value is
+ * used as a whole JSON value, so user input never needs quoting.
+ */
+int32_t jasonisnthappy_collection_find_params(struct CCollection *coll,
+                                              const char *query,
+                                              const char *params_json,
+                                              char **json_out,
+                                              struct CError *error_out);
+
+/**
+ * Like `jasonisnthappy_collection_find_one`, binding the query's
+ * placeholders to `params_json` as `jasonisnthappy_collection_find_params`
+ * does. Returns 1 with `json_out` set to NULL when nothing matches.
+ */
+int32_t jasonisnthappy_collection_find_one_params(struct CCollection *coll,
+                                                  const char *query,
+                                                  const char *params_json,
+                                                  char **json_out,
+                                                  struct CError *error_out);
+
+/**
+ * Like `jasonisnthappy_collection_find`, returning only the fields named in
+ * `include_json` or dropping those in `exclude_json`, each a JSON array of
+ * field names or NULL. Dotted paths name nested fields, inside every object
+ * of an array along the way. `_id` is kept unless excluded. Alongside
+ * `include_json`, `exclude_json` may only name `_id` and paths inside
+ * included fields; `["_id"]` alone as `include_json` returns only `_id`s.
+ */
+int32_t jasonisnthappy_collection_find_with_projection(struct CCollection *coll,
+                                                       const char *query,
+                                                       const char *include_json,
+                                                       const char *exclude_json,
+                                                       char **json_out,
+                                                       struct CError *error_out);
+
+/**
+ * Like `jasonisnthappy_collection_find_one` with the projection arguments of
+ * `jasonisnthappy_collection_find_with_projection`. Returns 1 if nothing matches.
+ */
+int32_t jasonisnthappy_collection_find_one_with_projection(struct CCollection *coll,
+                                                           const char *query,
+                                                           const char *include_json,
+                                                           const char *exclude_json,
+                                                           char **json_out,
+                                                           struct CError *error_out);
+
 int32_t jasonisnthappy_collection_update(struct CCollection *coll,
                                          const char *query,
                                          const char *updates_json,
@@ -310,6 +820,26 @@ int32_t jasonisnthappy_collection_update_one(struct CCollection *coll,
                                              bool *updated_out,
                                              struct CError *error_out);
 
+/**
+ * Update every document matching `query` and write the modified `_id`s to
+ * `ids_json_out` as a JSON array
+ */
+int32_t jasonisnthappy_collection_update_returning(struct CCollection *coll,
+                                                   const char *query,
+                                                   const char *updates_json,
+                                                   char **ids_json_out,
+                                                   struct CError *error_out);
+
+/**
+ * Update the first document matching `query` and write its `_id` to `id_out`.
+ * Returns 1, with `id_out` set to NULL, if nothing matched.
+ */
+int32_t jasonisnthappy_collection_update_one_returning(struct CCollection *coll,
+                                                       const char *query,
+                                                       const char *updates_json,
+                                                       char **id_out,
+                                                       struct CError *error_out);
+
 int32_t jasonisnthappy_collection_delete(struct CCollection *coll,
                                          const char *query,
                                          uintptr_t *count_out,
@@ -320,16 +850,71 @@ int32_t jasonisnthappy_collection_delete_one(struct CCollection *coll,
                                              bool *deleted_out,
                                              struct CError *error_out);
 
+/**
+ * Atomically update the first document matching `query` and return it as
+ * JSON in `json_out`: the document after the update if `return_new`, before
+ * it otherwise. `sort_field` (NULL = no sort) picks which match is first.
+ * Returns 1 with `json_out` set to NULL if nothing matches.
+ */
+int32_t jasonisnthappy_collection_find_one_and_update(struct CCollection *coll,
+                                                      const char *query,
+                                                      const char *updates_json,
+                                                      const char *sort_field,
+                                                      bool sort_ascending,
+                                                      bool return_new,
+                                                      char **json_out,
+                                                      struct CError *error_out);
+
+/**
+ * Atomically delete the first document matching `query` and return it as
+ * JSON in `json_out`. `sort_field` (NULL = no sort) picks which match is
+ * first. Returns 1 with `json_out` set to NULL if nothing matches.
+ */
+int32_t jasonisnthappy_collection_find_one_and_delete(struct CCollection *coll,
+                                                      const char *query,
+                                                      const char *sort_field,
+                                                      bool sort_ascending,
+                                                      char **json_out,
+                                                      struct CError *error_out);
+
 int32_t jasonisnthappy_collection_insert_many(struct CCollection *coll,
                                               const char *docs_json,
                                               char **ids_json_out,
                                               struct CError *error_out);
 
+/**
+ * Insert document `id` holding the `len` bytes at `data` as its binary `data`
+ * field. `data` may be NULL when `len` is 0.
+ */
+int32_t jasonisnthappy_collection_insert_binary(struct CCollection *coll,
+                                                const char *id,
+                                                const uint8_t *data,
+                                                uintptr_t len,
+                                                struct CError *error_out);
+
+/**
+ * Bytes stored by `jasonisnthappy_collection_insert_binary`, written to
+ * `buffer_out`; free them with `jasonisnthappy_free_buffer`
+ */
+int32_t jasonisnthappy_collection_get_binary(struct CCollection *coll,
+                                             const char *id,
+                                             struct CBuffer *buffer_out,
+                                             struct CError *error_out);
+
 int32_t jasonisnthappy_collection_distinct(struct CCollection *coll,
                                            const char *field,
                                            char **json_out,
                                            struct CError *error_out);
 
+/**
+ * Distinct values of `field` among documents matching `query`, as a JSON array
+ */
+int32_t jasonisnthappy_collection_distinct_with_query(struct CCollection *coll,
+                                                      const char *field,
+                                                      const char *query,
+                                                      char **json_out,
+                                                      struct CError *error_out);
+
 int32_t jasonisnthappy_collection_count_distinct(struct CCollection *coll,
                                                  const char *field,
                                                  uintptr_t *count_out,
@@ -350,6 +935,42 @@ int32_t jasonisnthappy_collection_find_by_id(struct CCollection *coll,
                                              char **json_out,
                                              struct CError *error_out);
 
+/**
+ * Look up several documents by ID against one snapshot
+ *
+ * `ids_json` is a JSON array of ID strings. On success `json_out` receives a
+ * JSON array in the same order, with `null` for IDs that don't exist.
+ */
+int32_t jasonisnthappy_collection_find_by_ids(struct CCollection *coll,
+                                              const char *ids_json,
+                                              char **json_out,
+                                              struct CError *error_out);
+
+/**
+ * Find documents with an `_id` from `start_id` up to `end_id`, in `_id` order
+ *
+ * `start_id` is always included; `end_id` only when `inclusive` is true. On
+ * success `json_out` receives a JSON array of the documents.
+ */
+int32_t jasonisnthappy_collection_scan_range(struct CCollection *coll,
+                                             const char *start_id,
+                                             const char *end_id,
+                                             bool inclusive,
+                                             char **json_out,
+                                             struct CError *error_out);
+
+/**
+ * Find up to `limit` documents with an `_id` of `start_id` or later, in `_id`
+ * order
+ *
+ * On success `json_out` receives a JSON array of the documents.
+ */
+int32_t jasonisnthappy_collection_scan_from(struct CCollection *coll,
+                                            const char *start_id,
+                                            uintptr_t limit,
+                                            char **json_out,
+                                            struct CError *error_out);
+
 int32_t jasonisnthappy_collection_update_by_id(struct CCollection *coll,
                                                const char *id,
                                                const char *updates_json,
@@ -371,6 +992,14 @@ int32_t jasonisnthappy_collection_name(struct CCollection *coll,
                                        char **name_out,
                                        struct CError *error_out);
 
+/**
+ * Check whether the collection exists as of the last commit, without
+ * starting a transaction. Getting a handle doesn't create the collection.
+ */
+int32_t jasonisnthappy_collection_exists(struct CCollection *coll,
+                                         bool *exists_out,
+                                         struct CError *error_out);
+
 int32_t jasonisnthappy_collection_count_with_query(struct CCollection *coll,
                                                    const char *query,
                                                    uintptr_t *count_out,
@@ -436,6 +1065,64 @@ int32_t jasonisnthappy_collection_upsert_typed(struct CCollection *coll,
                                                char **id_out,
                                                struct CError *error_out);
 
+/**
+ * Use a collection as a key-value store, with each document's `_id` as its key
+ *
+ * Values are JSON objects: the document without its `_id`. Free the handle
+ * with `jasonisnthappy_kv_free`.
+ */
+struct CKvStore *jasonisnthappy_get_kv(struct CDatabase *db,
+                                       const char *collection_name,
+                                       struct CError *error_out);
+
+void jasonisnthappy_kv_free(struct CKvStore *kv);
+
+/**
+ * Get the value stored under `key`. `json_out` receives the value as JSON,
+ * or `null` if the key isn't set.
+ */
+int32_t jasonisnthappy_kv_get(struct CKvStore *kv,
+                              const char *key,
+                              char **json_out,
+                              struct CError *error_out);
+
+/**
+ * Store `value_json`, a JSON object, under `key`, replacing what was there
+ */
+int32_t jasonisnthappy_kv_set(struct CKvStore *kv,
+                              const char *key,
+                              const char *value_json,
+                              struct CError *error_out);
+
+/**
+ * Get several values against one snapshot
+ *
+ * `keys_json` is a JSON array of keys. On success `json_out` receives a JSON
+ * array of the values in the same order, with `null` for keys that aren't set.
+ */
+int32_t jasonisnthappy_kv_mget(struct CKvStore *kv,
+                               const char *keys_json,
+                               char **json_out,
+                               struct CError *error_out);
+
+/**
+ * Store several values in one transaction
+ *
+ * `pairs_json` is a JSON object mapping each key to its value. Either every
+ * value is written or, on error, none are.
+ */
+int32_t jasonisnthappy_kv_mset(struct CKvStore *kv,
+                               const char *pairs_json,
+                               struct CError *error_out);
+
+/**
+ * Remove `key`. `deleted_out` is set to whether it was set.
+ */
+int32_t jasonisnthappy_kv_delete(struct CKvStore *kv,
+                                 const char *key,
+                                 bool *deleted_out,
+                                 struct CError *error_out);
+
 /**
  * Query with all options in a single call (simplified query builder for FFI)
  *
@@ -448,7 +1135,8 @@ int32_t jasonisnthappy_collection_upsert_typed(struct CCollection *coll,
  * - project_json: Optional JSON array of fields to include (NULL = all fields)
  * - exclude_json: Optional JSON array of fields to exclude (NULL = none)
  *
- * Note: Cannot specify both project_json and exclude_json
+ * Both lists take dotted paths. Given together, exclude_json may only name
+ * `_id` and paths inside fields in project_json.
  */
 int32_t jasonisnthappy_collection_query_with_options(struct CCollection *coll,
                                                      const char *filter,
@@ -486,19 +1174,21 @@ int32_t jasonisnthappy_collection_query_first(struct CCollection *coll,
  *
  * # Parameters
  * - operations_json: JSON array of operations, each with:
- *   - "op": "insert" | "update_one" | "update_many" | "delete_one" | "delete_many"
- *   - "doc": document (for insert)
- *   - "query": query string (for update/delete)
+ *   - "op": "insert" | "update_one" | "update_many" | "delete_one" | "delete_many" | "upsert_one" | "upsert_by_id"
+ *   - "doc": document (for insert/upsert)
+ *   - "query": query string (for update/delete/upsert_one)
+ *   - "id": document ID (for upsert_by_id)
  *   - "updates": updates object (for update)
  * - ordered: if true, stop on first error; if false, continue on errors
- * - result_json_out: BulkWriteResult as JSON (inserted_count, updated_count, deleted_count, errors)
+ * - result_json_out: BulkWriteResult as JSON (inserted_count, updated_count, deleted_count, upserted_count, errors)
  *
  * # Example operations_json:
  * ```json
  * [
  *   {"op": "insert", "doc": {"name": "Alice", "age": 30}},
  *   {"op": "update_one", "query": "name is 'Bob'", "updates": {"age": 31}},
- *   {"op": "delete_many", "query": "age < 18"}
+ *   {"op": "delete_many", "query": "age < 18"},
+ *   {"op": "upsert_by_id", "id": "user_1", "doc": {"name": "Carol"}}
  * ]
  * ```
  */
@@ -514,12 +1204,21 @@ int32_t jasonisnthappy_collection_bulk_write(struct CCollection *coll,
  * # Parameters
  * - pipeline_json: JSON array of pipeline stages, each with:
  *   - "match": query string (filter stage)
- *   - "group_by": {field: "...", accumulators: [{type: "count|sum|avg|min|max", output_field: "...", field: "..."}]}
+ *   - "group_by": {field: "..." | ["...", ...], accumulators: [{type: "count|sum|avg|min|max", output_field: "...", field: "..."}]}
+ *     (an array of fields groups on the compound key; `_id` becomes an object of the key fields)
+ *   - "group_by_date": like group_by plus granularity: "day|month|year"; the field is
+ *     read as an RFC3339 string or epoch seconds and bucketed in UTC
  *   - "sort": {field: "...", ascending: true|false}
  *   - "limit": number
  *   - "skip": number
  *   - "project": ["field1", "field2", ...]
  *   - "exclude": ["field1", "field2", ...]
+ *   - "add_fields": {"output": expression, ...} where an expression is a field name,
+ *     a literal, or one of {"field": "..."}, {"literal": ...}, {"concat": [...]},
+ *     {"add": [...]}, {"sub": [...]}, {"mul": [...]}, {"div": [...]}
+ *   - "out": "collection", with optional "mode": "replace|merge"; must be the last
+ *     stage. Writes the results into that collection in one transaction and
+ *     returns [{"out": "collection", "written": n}] instead of the results
  *
  * # Example pipeline_json:
  * ```json
@@ -548,6 +1247,7 @@ int32_t jasonisnthappy_collection_aggregate(struct CCollection *coll,
  * # Parameters
  * - coll: Collection to watch
  * - filter: Optional query filter (NULL = watch all changes)
+ * - full_document_before_change: Whether to pass the pre-change document to the callback
  * - callback: Function to call for each change event
  * - user_data: Optional user context pointer passed to callback
  * - handle_out: Output pointer for the watch handle (use to stop watching)
@@ -561,11 +1261,33 @@ int32_t jasonisnthappy_collection_aggregate(struct CCollection *coll,
  */
 int32_t jasonisnthappy_collection_watch_start(struct CCollection *coll,
                                               const char *filter,
+                                              bool full_document_before_change,
                                               WatchCallback callback,
                                               void *user_data,
                                               struct CWatchHandle **handle_out,
                                               struct CError *error_out);
 
+/**
+ * Start watching a collection for changes of some operations only
+ *
+ * Same as jasonisnthappy_collection_watch_start, except that only events
+ * for the operations in `operations` are passed to the callback. It is a
+ * mask of JASONISNTHAPPY_WATCH_INSERT, JASONISNTHAPPY_WATCH_UPDATE and
+ * JASONISNTHAPPY_WATCH_DELETE; 0 watches all operations. With a filter as
+ * well, an event has to match both.
+ *
+ * # Returns
+ * 0 on success, -1 on error
+ */
+int32_t jasonisnthappy_collection_watch_start_with_operations(struct CCollection *coll,
+                                                              const char *filter,
+                                                              uint32_t operations,
+                                                              bool full_document_before_change,
+                                                              WatchCallback callback,
+                                                              void *user_data,
+                                                              struct CWatchHandle **handle_out,
+                                                              struct CError *error_out);
+
 /**
  * Stop watching and clean up resources
  *
@@ -583,6 +1305,22 @@ void jasonisnthappy_watch_stop(struct CWatchHandle *handle);
 
 int32_t jasonisnthappy_checkpoint(struct CDatabase *db, struct CError *error_out);
 
+/**
+ * Checkpoint the whole WAL and truncate the WAL file to zero bytes
+ */
+int32_t jasonisnthappy_checkpoint_truncate(struct CDatabase *db, struct CError *error_out);
+
+/**
+ * Fsync the WAL and write out cached pages without checkpointing
+ */
+int32_t jasonisnthappy_sync(struct CDatabase *db, struct CError *error_out);
+
+/**
+ * 1 if every commit has been fsynced and no cached pages await writing,
+ * 0 if not, -1 for a null database pointer
+ */
+int32_t jasonisnthappy_is_fully_synced(struct CDatabase *db);
+
 int32_t jasonisnthappy_backup(struct CDatabase *db,
                               const char *backup_path,
                               struct CError *error_out);
@@ -1,16 +1,17 @@
 use std::ffi::{CStr, CString};
+use std::mem::size_of;
 use std::os::raw::c_char;
 use std::ptr;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
-use jasonisnthappy::core::{Database, Transaction};
+use jasonisnthappy::core::{Collation, Database, DateGranularity, Durability, FieldExpr, IndexOptions, OutMode, TextIndexOptions, Transaction};
 use jasonisnthappy::core::query_builder::SortOrder;
 use jasonisnthappy::core::watch::ChangeOperation;
 use serde_json::Value;
 
 #[cfg(feature = "web-ui")]
-use jasonisnthappy::core::web_server::WebServer;
+use jasonisnthappy::core::web_server::{WebServer, WebServerOptions};
 
 // Opaque pointer for database
 #[repr(C)]
@@ -37,6 +38,17 @@ pub struct CCollection {
     inner: jasonisnthappy::core::collection::Collection,
 }
 
+// Opaque pointer for a collection used as a key-value store
+pub struct CKvStore {
+    inner: jasonisnthappy::KvStore,
+}
+
+// Opaque pointer for a find cursor
+pub struct CCursor {
+    batches: jasonisnthappy::FindBatches,
+    buffered: std::vec::IntoIter<Value>,
+}
+
 // Opaque pointer for watch handle
 pub struct CWatchHandle {
     _watch_handle: jasonisnthappy::core::watch::WatchHandle,
@@ -51,12 +63,15 @@ pub struct CWatchHandle {
 /// - operation: "insert", "update", or "delete"
 /// - doc_id: ID of the document
 /// - doc_json: JSON representation of the document (NULL for delete operations)
+/// - old_doc_json: JSON representation of the document before the change
+///   (NULL unless watching with full_document_before_change for updates and deletes)
 /// - user_data: User-provided context pointer passed to watch_start
 pub type WatchCallback = extern "C" fn(
     collection: *const c_char,
     operation: *const c_char,
     doc_id: *const c_char,
     doc_json: *const c_char,
+    old_doc_json: *const c_char,
     user_data: *mut std::os::raw::c_void,
 );
 
@@ -85,9 +100,96 @@ unsafe impl Send for SendableCallbackContext {}
 // For builders, we'll store the collection pointer and rebuild on each call
 // This avoids lifetime issues with FFI
 
+// Error codes reported in `CError::code`. The values are part of the C API
+// and stay the same across releases; new codes are only ever added.
+
+/// The call succeeded
+pub const JASONISNTHAPPY_OK: i32 = 0;
+/// An error without a more specific code; see the message
+pub const JASONISNTHAPPY_ERROR_UNKNOWN: i32 = -1;
+/// A null pointer, invalid UTF-8, malformed JSON or another bad argument
+pub const JASONISNTHAPPY_ERROR_INVALID_ARGUMENT: i32 = 1;
+/// The document, collection, index or savepoint does not exist
+pub const JASONISNTHAPPY_ERROR_NOT_FOUND: i32 = 2;
+/// The document or collection already exists
+pub const JASONISNTHAPPY_ERROR_ALREADY_EXISTS: i32 = 3;
+/// Another transaction changed the same data; retrying may succeed
+pub const JASONISNTHAPPY_ERROR_TX_CONFLICT: i32 = 4;
+/// The document failed validation against the collection schema
+pub const JASONISNTHAPPY_ERROR_VALIDATION: i32 = 5;
+/// A unique index already holds the value
+pub const JASONISNTHAPPY_ERROR_DUPLICATE_KEY: i32 = 6;
+/// A document or bulk operation is over the configured size limit
+pub const JASONISNTHAPPY_ERROR_LIMIT_EXCEEDED: i32 = 7;
+/// The transaction was already committed or rolled back
+pub const JASONISNTHAPPY_ERROR_TX_NOT_ACTIVE: i32 = 8;
+/// The transaction ran past its maximum duration
+pub const JASONISNTHAPPY_ERROR_TX_TIMEOUT: i32 = 9;
+/// The database or transaction is read-only
+pub const JASONISNTHAPPY_ERROR_READ_ONLY: i32 = 10;
+/// The database file or WAL is damaged
+pub const JASONISNTHAPPY_ERROR_CORRUPTION: i32 = 11;
+/// Reading or writing the database files failed
+pub const JASONISNTHAPPY_ERROR_IO: i32 = 12;
+/// A struct passed in was laid out for a different version of this library
+pub const JASONISNTHAPPY_ERROR_ABI_MISMATCH: i32 = 13;
+/// Another process has the database open in a conflicting mode
+pub const JASONISNTHAPPY_ERROR_LOCKED: i32 = 14;
+
+/// Map a database error to the code that best describes it
+fn error_code(error: &jasonisnthappy::Error) -> i32 {
+    use jasonisnthappy::Error;
+
+    match error {
+        Error::NotFound
+        | Error::DocumentNotFound { .. }
+        | Error::CollectionDoesNotExist { .. }
+        | Error::SavepointNotFound { .. } => JASONISNTHAPPY_ERROR_NOT_FOUND,
+        Error::DocumentAlreadyExists { .. }
+        | Error::CollectionAlreadyExists { .. } => JASONISNTHAPPY_ERROR_ALREADY_EXISTS,
+        Error::TxConflict
+        | Error::Conflict { .. }
+        | Error::RevConflict { .. } => JASONISNTHAPPY_ERROR_TX_CONFLICT,
+        Error::Validation { .. }
+        | Error::SchemaValidation(_) => JASONISNTHAPPY_ERROR_VALIDATION,
+        Error::DuplicateKey { .. } => JASONISNTHAPPY_ERROR_DUPLICATE_KEY,
+        Error::DocumentTooLarge
+        | Error::BulkOperationTooLarge { .. }
+        | Error::QueryTooExpensive { .. } => JASONISNTHAPPY_ERROR_LIMIT_EXCEEDED,
+        Error::TxNotActive
+        | Error::TxAlreadyDone => JASONISNTHAPPY_ERROR_TX_NOT_ACTIVE,
+        Error::TxTimeout => JASONISNTHAPPY_ERROR_TX_TIMEOUT,
+        Error::DatabaseReadOnly { .. }
+        | Error::ReadOnlyTransaction => JASONISNTHAPPY_ERROR_READ_ONLY,
+        Error::CollectionNameEmpty
+        | Error::CollectionNameTooLong
+        | Error::CollectionNameInvalidStart
+        | Error::CollectionNameInvalidChar
+        | Error::CollectionNameReserved
+        | Error::InvalidDocument
+        | Error::InvalidDocumentFormat { .. }
+        | Error::InvalidUpdate { .. }
+        | Error::PageSizeMismatch { .. } => JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+        Error::Corruption { .. }
+        | Error::DataCorruption { .. }
+        | Error::InvalidMagic
+        | Error::InvalidVersion
+        | Error::InvalidPageNum
+        | Error::InvalidPageSize
+        | Error::PageChecksumMismatch { .. }
+        | Error::WALCorrupted
+        | Error::WALInvalidTx
+        | Error::WALChecksumFail => JASONISNTHAPPY_ERROR_CORRUPTION,
+        Error::Io(_) => JASONISNTHAPPY_ERROR_IO,
+        Error::DatabaseLocked => JASONISNTHAPPY_ERROR_LOCKED,
+        _ => JASONISNTHAPPY_ERROR_UNKNOWN,
+    }
+}
+
 // Error structure for C API
 #[repr(C)]
 pub struct CError {
+    /// One of the `JASONISNTHAPPY_OK` / `JASONISNTHAPPY_ERROR_*` codes
     pub code: i32,
     pub message: *mut c_char,
 }
@@ -96,22 +198,41 @@ impl CError {
     fn from_error(err: jasonisnthappy::Error) -> Self {
         let message = CString::new(err.to_string()).unwrap_or_else(|_| CString::new("Unknown error").unwrap());
         CError {
-            code: -1,
+            code: error_code(&err),
             message: message.into_raw(),
         }
     }
 
     fn success() -> Self {
         CError {
-            code: 0,
+            code: JASONISNTHAPPY_OK,
             message: ptr::null_mut(),
         }
     }
 }
 
+/// Bytes returned to C, freed with `jasonisnthappy_free_buffer`
+#[repr(C)]
+pub struct CBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl CBuffer {
+    fn from_vec(bytes: Vec<u8>) -> Self {
+        let len = bytes.len();
+        let data = Box::into_raw(bytes.into_boxed_slice()) as *mut u8;
+        CBuffer { data, len }
+    }
+}
+
 // Database configuration structures
+
 #[repr(C)]
 pub struct CDatabaseOptions {
+    /// sizeof(CDatabaseOptions) as the caller was compiled, already set by
+    /// `jasonisnthappy_default_database_options`
+    pub struct_size: u32,
     pub cache_size: usize,
     pub auto_checkpoint_threshold: u64,
     pub file_permissions: u32,
@@ -119,6 +240,53 @@ pub struct CDatabaseOptions {
     pub max_bulk_operations: usize,
     pub max_document_size: usize,
     pub max_request_body_size: usize,
+    pub document_metadata: bool,
+    pub scan_threads: usize,
+    pub enable_id_bloom_filter: bool,
+    /// Page size for a new database file; 0 keeps an existing file's size
+    pub page_size: usize,
+    /// WAL size in bytes that makes the next commit checkpoint first; 0 for no cap
+    pub max_wal_bytes: u64,
+    /// List every schema violation in validation errors, not just the first
+    pub collect_all_validation_errors: bool,
+    /// Checksum every page of a new database file; an existing file keeps its setting
+    pub page_checksums: bool,
+    /// Milliseconds to wait for another process to release the database; 0 fails straight away
+    pub lock_timeout_ms: u64,
+    /// Most documents a collection scan may examine; 0 for no limit
+    pub max_scan_documents: usize,
+    /// Most documents a query may collect as results; 0 for no limit
+    pub max_result_documents: usize,
+    /// Queries running at least this many milliseconds go to the slow query log; 0 disables it
+    pub slow_query_threshold_ms: u64,
+    /// Number of slow queries kept, oldest dropped first
+    pub slow_query_log_size: usize,
+    /// When commits fsync: one of the `JASONISNTHAPPY_DURABILITY_*` values
+    pub durability: u32,
+    /// Milliseconds between fsyncs with `JASONISNTHAPPY_DURABILITY_BATCHED`
+    pub durability_interval_ms: u64,
+    /// Never match a number against a string in query comparisons; when false,
+    /// strings holding numbers compare as those numbers
+    pub strict_query_types: bool,
+    /// Number of `find` and aggregation results cached until a write to their
+    /// collection; 0 disables the cache
+    pub query_cache_size: usize,
+}
+
+/// Fsync the WAL and database file before every commit returns
+pub const JASONISNTHAPPY_DURABILITY_FULL: u32 = 0;
+/// Fsync the WAL on every commit and the database file only on checkpoint and close
+pub const JASONISNTHAPPY_DURABILITY_NORMAL: u32 = 1;
+/// Fsync the WAL on every commit and the database file at most every `durability_interval_ms`
+pub const JASONISNTHAPPY_DURABILITY_BATCHED: u32 = 2;
+
+fn durability_from_c(code: u32, interval_ms: u64) -> Option<Durability> {
+    match code {
+        JASONISNTHAPPY_DURABILITY_FULL => Some(Durability::Full),
+        JASONISNTHAPPY_DURABILITY_NORMAL => Some(Durability::Normal),
+        JASONISNTHAPPY_DURABILITY_BATCHED => Some(Durability::Batched(std::time::Duration::from_millis(interval_ms))),
+        _ => None,
+    }
 }
 
 impl From<CDatabaseOptions> for jasonisnthappy::core::database::DatabaseOptions {
@@ -131,15 +299,83 @@ impl From<CDatabaseOptions> for jasonisnthappy::core::database::DatabaseOptions
             max_bulk_operations: opts.max_bulk_operations,
             max_document_size: opts.max_document_size,
             max_request_body_size: opts.max_request_body_size,
+            document_metadata: opts.document_metadata,
+            scan_threads: opts.scan_threads,
+            enable_id_bloom_filter: opts.enable_id_bloom_filter,
+            page_size: opts.page_size,
+            max_wal_bytes: opts.max_wal_bytes,
+            collect_all_validation_errors: opts.collect_all_validation_errors,
+            page_checksums: opts.page_checksums,
+            lock_timeout_ms: opts.lock_timeout_ms,
+            max_scan_documents: opts.max_scan_documents,
+            max_result_documents: opts.max_result_documents,
+            slow_query_threshold_ms: opts.slow_query_threshold_ms,
+            slow_query_log_size: opts.slow_query_log_size,
+            // Checked by `jasonisnthappy_open_with_options` before converting
+            durability: durability_from_c(opts.durability, opts.durability_interval_ms)
+                .unwrap_or_default(),
+            strict_query_types: opts.strict_query_types,
+            query_cache_size: opts.query_cache_size,
+        }
+    }
+}
+
+#[repr(C)]
+pub struct CIndexOptions {
+    /// sizeof(CIndexOptions) as the caller was compiled, already set by
+    /// `jasonisnthappy_default_index_options`
+    pub struct_size: u32,
+    pub unique: bool,
+    pub descending: bool,
+    /// Leave out documents where any indexed field is missing or null, so they
+    /// never conflict in a unique index
+    pub sparse: bool,
+    /// How indexed strings compare: one of the `JASONISNTHAPPY_COLLATION_*` values
+    pub collation: u32,
+}
+
+/// Compare strings exactly as stored
+pub const JASONISNTHAPPY_COLLATION_BINARY: u32 = 0;
+/// Compare ASCII letters without regard to case
+pub const JASONISNTHAPPY_COLLATION_ASCII_CASE_INSENSITIVE: u32 = 1;
+/// Compare letters without regard to case, using Unicode simple case folding
+pub const JASONISNTHAPPY_COLLATION_CASE_INSENSITIVE: u32 = 2;
+
+fn collation_from_c(code: u32) -> Option<Collation> {
+    match code {
+        JASONISNTHAPPY_COLLATION_BINARY => Some(Collation::Binary),
+        JASONISNTHAPPY_COLLATION_ASCII_CASE_INSENSITIVE => Some(Collation::AsciiCaseInsensitive),
+        JASONISNTHAPPY_COLLATION_CASE_INSENSITIVE => Some(Collation::CaseInsensitive),
+        _ => None,
+    }
+}
+
+impl From<CIndexOptions> for IndexOptions {
+    fn from(opts: CIndexOptions) -> Self {
+        IndexOptions {
+            unique: opts.unique,
+            descending: opts.descending,
+            sparse: opts.sparse,
+            // Checked by `check_index_options` before converting
+            collation: collation_from_c(opts.collation).unwrap_or_default(),
         }
     }
 }
 
 #[repr(C)]
 pub struct CTransactionConfig {
+    /// sizeof(CTransactionConfig) as the caller was compiled, already set by
+    /// `jasonisnthappy_default_transaction_config`
+    pub struct_size: u32,
     pub max_retries: usize,
     pub retry_backoff_base_ms: u64,
     pub max_retry_backoff_ms: u64,
+    /// 0 means no limit
+    pub max_transaction_duration_ms: u64,
+    /// Shortest wait between retries, and the low end of the jittered range
+    pub min_retry_backoff_ms: u64,
+    /// Randomize each retry wait between the minimum and the doubled backoff
+    pub jitter: bool,
 }
 
 impl From<CTransactionConfig> for jasonisnthappy::core::database::TransactionConfig {
@@ -148,6 +384,9 @@ impl From<CTransactionConfig> for jasonisnthappy::core::database::TransactionCon
             max_retries: cfg.max_retries,
             retry_backoff_base_ms: cfg.retry_backoff_base_ms,
             max_retry_backoff_ms: cfg.max_retry_backoff_ms,
+            min_retry_backoff_ms: cfg.min_retry_backoff_ms,
+            jitter: cfg.jitter,
+            max_transaction_duration_ms: cfg.max_transaction_duration_ms,
         }
     }
 }
@@ -155,9 +394,13 @@ impl From<CTransactionConfig> for jasonisnthappy::core::database::TransactionCon
 impl From<jasonisnthappy::core::database::TransactionConfig> for CTransactionConfig {
     fn from(cfg: jasonisnthappy::core::database::TransactionConfig) -> Self {
         CTransactionConfig {
+            struct_size: size_of::<CTransactionConfig>() as u32,
             max_retries: cfg.max_retries,
             retry_backoff_base_ms: cfg.retry_backoff_base_ms,
             max_retry_backoff_ms: cfg.max_retry_backoff_ms,
+            min_retry_backoff_ms: cfg.min_retry_backoff_ms,
+            jitter: cfg.jitter,
+            max_transaction_duration_ms: cfg.max_transaction_duration_ms,
         }
     }
 }
@@ -165,7 +408,7 @@ impl From<jasonisnthappy::core::database::TransactionConfig> for CTransactionCon
 unsafe fn c_str_to_string(s: *const c_char) -> Result<String, CError> {
     if s.is_null() {
         return Err(CError {
-            code: -1,
+            code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
             message: CString::new("Null pointer provided").unwrap().into_raw(),
         });
     }
@@ -174,11 +417,77 @@ unsafe fn c_str_to_string(s: *const c_char) -> Result<String, CError> {
         .to_str()
         .map(|s| s.to_string())
         .map_err(|_| CError {
-            code: -1,
+            code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
             message: CString::new("Invalid UTF-8 string").unwrap().into_raw(),
         })
 }
 
+/// Field names from an optional JSON array argument; NULL gives none
+unsafe fn c_field_list(json: *const c_char, name: &str) -> Result<Vec<String>, CError> {
+    if json.is_null() {
+        return Ok(Vec::new());
+    }
+
+    let json_str = c_str_to_string(json)?;
+    serde_json::from_str(&json_str).map_err(|e| CError {
+        code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+        message: CString::new(format!("Invalid {} format (expected JSON array of strings): {}", name, e))
+            .unwrap()
+            .into_raw(),
+    })
+}
+
+/// Reject a struct whose `struct_size` doesn't match the layout this library
+/// was built with, rather than reading or writing past the caller's memory
+fn check_struct_size<T>(struct_size: u32, name: &str) -> Result<(), CError> {
+    let expected = size_of::<T>();
+    if struct_size as usize == expected {
+        return Ok(());
+    }
+
+    let message = format!(
+        "{}.struct_size is {} but this library expects {}; rebuild against the matching jasonisnthappy.h",
+        name, struct_size, expected
+    );
+    Err(CError {
+        code: JASONISNTHAPPY_ERROR_ABI_MISMATCH,
+        message: CString::new(message).unwrap().into_raw(),
+    })
+}
+
+/// Reject index options from a mismatched header or with an unknown collation
+fn check_index_options(options: &CIndexOptions) -> Result<(), CError> {
+    check_struct_size::<CIndexOptions>(options.struct_size, "CIndexOptions")?;
+    if collation_from_c(options.collation).is_none() {
+        return Err(CError {
+            code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+            message: CString::new(format!("invalid collation {}", options.collation)).unwrap().into_raw(),
+        });
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Library Version
+// ============================================================================
+
+/// Version of the C ABI. Bumped whenever a struct layout or function
+/// signature changes incompatibly.
+pub const JASONISNTHAPPY_ABI_VERSION: u32 = 1;
+
+/// Version of the library, e.g. "0.1.1". The string is static and must not be freed.
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char
+}
+
+/// ABI version of the loaded library. Compare it against
+/// `JASONISNTHAPPY_ABI_VERSION` from the header the program was built with.
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_abi_version() -> u32 {
+    JASONISNTHAPPY_ABI_VERSION
+}
+
 // ============================================================================
 // Database Management
 // ============================================================================
@@ -225,6 +534,26 @@ pub extern "C" fn jasonisnthappy_open_with_options(
         }
     };
 
+    if let Err(e) = check_struct_size::<CDatabaseOptions>(options.struct_size, "CDatabaseOptions") {
+        if !error_out.is_null() {
+            unsafe { *error_out = e; }
+        }
+        return ptr::null_mut();
+    }
+
+    if durability_from_c(options.durability, options.durability_interval_ms).is_none() {
+        if !error_out.is_null() {
+            let message = format!("invalid durability {}", options.durability);
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new(message).unwrap().into_raw(),
+                };
+            }
+        }
+        return ptr::null_mut();
+    }
+
     let rust_options = jasonisnthappy::core::database::DatabaseOptions::from(options);
 
     match Database::open_with_options(&path_str, rust_options) {
@@ -261,7 +590,7 @@ pub extern "C" fn jasonisnthappy_set_transaction_config(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -269,6 +598,13 @@ pub extern "C" fn jasonisnthappy_set_transaction_config(
         return -1;
     }
 
+    if let Err(e) = check_struct_size::<CTransactionConfig>(config.struct_size, "CTransactionConfig") {
+        if !error_out.is_null() {
+            unsafe { *error_out = e; }
+        }
+        return -1;
+    }
+
     let db_ref = unsafe { &(*db).inner };
     let rust_config = jasonisnthappy::core::database::TransactionConfig::from(config);
 
@@ -290,7 +626,7 @@ pub extern "C" fn jasonisnthappy_get_transaction_config(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -302,6 +638,14 @@ pub extern "C" fn jasonisnthappy_get_transaction_config(
     let rust_config = db_ref.get_transaction_config();
 
     if !config_out.is_null() {
+        // The caller sets struct_size on the struct it wants filled in
+        if let Err(e) = check_struct_size::<CTransactionConfig>(unsafe { (*config_out).struct_size }, "CTransactionConfig") {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+
         unsafe {
             *config_out = CTransactionConfig::from(rust_config);
         }
@@ -323,7 +667,7 @@ pub extern "C" fn jasonisnthappy_set_auto_checkpoint_threshold(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -344,6 +688,7 @@ pub extern "C" fn jasonisnthappy_set_auto_checkpoint_threshold(
 pub extern "C" fn jasonisnthappy_default_database_options() -> CDatabaseOptions {
     let defaults = jasonisnthappy::core::database::DatabaseOptions::default();
     CDatabaseOptions {
+        struct_size: size_of::<CDatabaseOptions>() as u32,
         cache_size: defaults.cache_size,
         auto_checkpoint_threshold: defaults.auto_checkpoint_threshold,
         file_permissions: defaults.file_permissions,
@@ -351,6 +696,34 @@ pub extern "C" fn jasonisnthappy_default_database_options() -> CDatabaseOptions
         max_bulk_operations: defaults.max_bulk_operations,
         max_document_size: defaults.max_document_size,
         max_request_body_size: defaults.max_request_body_size,
+        document_metadata: defaults.document_metadata,
+        scan_threads: defaults.scan_threads,
+        enable_id_bloom_filter: defaults.enable_id_bloom_filter,
+        page_size: defaults.page_size,
+        max_wal_bytes: defaults.max_wal_bytes,
+        collect_all_validation_errors: defaults.collect_all_validation_errors,
+        page_checksums: defaults.page_checksums,
+        lock_timeout_ms: defaults.lock_timeout_ms,
+        max_scan_documents: defaults.max_scan_documents,
+        max_result_documents: defaults.max_result_documents,
+        slow_query_threshold_ms: defaults.slow_query_threshold_ms,
+        slow_query_log_size: defaults.slow_query_log_size,
+        durability: JASONISNTHAPPY_DURABILITY_FULL,
+        durability_interval_ms: 100,
+        strict_query_types: defaults.strict_query_types,
+        query_cache_size: defaults.query_cache_size,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_default_index_options() -> CIndexOptions {
+    let defaults = IndexOptions::default();
+    CIndexOptions {
+        struct_size: size_of::<CIndexOptions>() as u32,
+        unique: defaults.unique,
+        descending: defaults.descending,
+        sparse: defaults.sparse,
+        collation: JASONISNTHAPPY_COLLATION_BINARY,
     }
 }
 
@@ -358,9 +731,13 @@ pub extern "C" fn jasonisnthappy_default_database_options() -> CDatabaseOptions
 pub extern "C" fn jasonisnthappy_default_transaction_config() -> CTransactionConfig {
     let defaults = jasonisnthappy::core::database::TransactionConfig::default();
     CTransactionConfig {
+        struct_size: size_of::<CTransactionConfig>() as u32,
         max_retries: defaults.max_retries,
         retry_backoff_base_ms: defaults.retry_backoff_base_ms,
         max_retry_backoff_ms: defaults.max_retry_backoff_ms,
+        max_transaction_duration_ms: defaults.max_transaction_duration_ms,
+        min_retry_backoff_ms: defaults.min_retry_backoff_ms,
+        jitter: defaults.jitter,
     }
 }
 
@@ -377,7 +754,7 @@ pub extern "C" fn jasonisnthappy_begin_transaction(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -398,6 +775,38 @@ pub extern "C" fn jasonisnthappy_begin_transaction(
     }
 }
 
+/// Begin a transaction that can only read. Writes through it fail, and it is
+/// finished with `jasonisnthappy_commit` or `jasonisnthappy_rollback` as usual.
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_begin_read_only(
+    db: *mut CDatabase,
+    error_out: *mut CError,
+) -> *mut CTransaction {
+    if db.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null database pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return ptr::null_mut();
+    }
+
+    let db_ref = unsafe { &(*db).inner };
+
+    match db_ref.begin_read_only() {
+        Ok(tx) => Box::into_raw(Box::new(CTransaction { inner: tx })),
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            ptr::null_mut()
+        }
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn jasonisnthappy_commit(
     tx: *mut CTransaction,
@@ -407,7 +816,7 @@ pub extern "C" fn jasonisnthappy_commit(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null transaction pointer").unwrap().into_raw(),
                 };
             }
@@ -478,7 +887,7 @@ pub extern "C" fn jasonisnthappy_run_transaction(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -486,96 +895,35 @@ pub extern "C" fn jasonisnthappy_run_transaction(
         return -1;
     }
 
-    unsafe {
-        let db_ref = &(*db).inner;
-
-        // Get transaction config for retry logic
-        let config = db_ref.get_transaction_config();
-        let mut last_err = None;
-
-        // Retry loop (same logic as Database::run_transaction)
-        for attempt in 0..=(config.max_retries) {
-            // Begin new transaction
-            let tx = match db_ref.begin() {
-                Ok(tx) => tx,
-                Err(e) => {
-                    if !error_out.is_null() {
-                        *error_out = CError::from_error(e);
-                    }
-                    return -1;
-                }
-            };
-
-            // Wrap in CTransaction for callback
-            let c_tx = Box::into_raw(Box::new(CTransaction { inner: tx }));
-
-            // Call user callback
-            let callback_result = callback(c_tx, user_data);
-
-            // Get back the transaction
-            let mut tx_obj = Box::from_raw(c_tx);
+    let db_ref = unsafe { &(*db).inner };
 
-            // Handle result
-            if callback_result == 0 {
-                // Callback succeeded - try to commit
-                match tx_obj.inner.commit() {
-                    Ok(_) => {
-                        if !error_out.is_null() {
-                            *error_out = CError::success();
-                        }
-                        return 0;  // Success!
-                    }
-                    Err(e) => {
-                        if matches!(e, jasonisnthappy::Error::TxConflict) {
-                            // Conflict - retry
-                            last_err = Some(e);
-                        } else {
-                            // Other error - fail immediately
-                            if !error_out.is_null() {
-                                *error_out = CError::from_error(e);
-                            }
-                            return -1;
-                        }
-                    }
-                }
-            } else {
-                // Callback returned error - rollback and fail
-                let _ = tx_obj.inner.rollback();
-                if !error_out.is_null() {
-                    *error_out = CError {
-                        code: -1,
-                        message: CString::new("Transaction callback returned error")
-                            .unwrap()
-                            .into_raw(),
-                    };
-                }
-                return -1;
-            }
+    let result = db_ref.run_transaction(|tx| {
+        // CTransaction is a repr(C) wrapper around a single Transaction field,
+        // so the callback can use the borrowed transaction in place
+        let c_tx = tx as *mut Transaction as *mut CTransaction;
+        if callback(c_tx, user_data) == 0 {
+            Ok(())
+        } else {
+            Err(jasonisnthappy::Error::ContextCancelled {
+                operation: "run_transaction".to_string(),
+                error: "Transaction callback returned error".to_string(),
+            })
+        }
+    });
 
-            // Backoff before retry
-            if attempt < config.max_retries {
-                let backoff_ms = config.retry_backoff_base_ms * (1 << attempt);
-                let backoff_ms = backoff_ms.min(config.max_retry_backoff_ms);
-                if backoff_ms > 0 {
-                    std::thread::sleep(std::time::Duration::from_millis(backoff_ms));
-                }
+    match result {
+        Ok(()) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
             }
+            0
         }
-
-        // All retries exhausted
-        if !error_out.is_null() {
-            *error_out = if let Some(err) = last_err {
-                CError::from_error(err)
-            } else {
-                CError {
-                    code: -1,
-                    message: CString::new("Transaction conflict - max retries exhausted")
-                        .unwrap()
-                        .into_raw(),
-                }
-            };
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
         }
-        -1
     }
 }
 
@@ -589,7 +937,7 @@ pub extern "C" fn jasonisnthappy_transaction_is_active(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null transaction pointer").unwrap().into_raw(),
                 };
             }
@@ -607,23 +955,18 @@ pub extern "C" fn jasonisnthappy_transaction_is_active(
     }
 }
 
-// ============================================================================
-// Document Operations (within a transaction)
-// ============================================================================
-
+/// Create a named savepoint in a transaction
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_insert(
+pub extern "C" fn jasonisnthappy_savepoint(
     tx: *mut CTransaction,
-    collection_name: *const c_char,
-    json: *const c_char,
-    id_out: *mut *mut c_char,
+    name: *const c_char,
     error_out: *mut CError,
 ) -> i32 {
     if tx.is_null() {
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null transaction pointer").unwrap().into_raw(),
                 };
             }
@@ -631,17 +974,7 @@ pub extern "C" fn jasonisnthappy_insert(
         return -1;
     }
 
-    let coll_name = match unsafe { c_str_to_string(collection_name) } {
-        Ok(s) => s,
-        Err(e) => {
-            if !error_out.is_null() {
-                unsafe { *error_out = e; }
-            }
-            return -1;
-        }
-    };
-
-    let json_str = match unsafe { c_str_to_string(json) } {
+    let savepoint_name = match unsafe { c_str_to_string(name) } {
         Ok(s) => s,
         Err(e) => {
             if !error_out.is_null() {
@@ -651,34 +984,10 @@ pub extern "C" fn jasonisnthappy_insert(
         }
     };
 
-    let value: Value = match serde_json::from_str(&json_str) {
-        Ok(v) => v,
-        Err(e) => {
-            if !error_out.is_null() {
-                unsafe {
-                    *error_out = CError {
-                        code: -1,
-                        message: CString::new(format!("Invalid JSON: {}", e)).unwrap().into_raw(),
-                    };
-                }
-            }
-            return -1;
-        }
-    };
-
     let tx_ref = unsafe { &mut (*tx).inner };
 
-    let result = (|| -> jasonisnthappy::Result<String> {
-        let mut coll = tx_ref.collection(&coll_name)?;
-        coll.insert(value)
-    })();
-
-    match result {
-        Ok(id) => {
-            if !id_out.is_null() {
-                let c_id = CString::new(id).unwrap();
-                unsafe { *id_out = c_id.into_raw(); }
-            }
+    match tx_ref.savepoint(&savepoint_name) {
+        Ok(_) => {
             if !error_out.is_null() {
                 unsafe { *error_out = CError::success(); }
             }
@@ -693,19 +1002,20 @@ pub extern "C" fn jasonisnthappy_insert(
     }
 }
 
+/// Discard all writes made in a transaction since the named savepoint
+///
+/// The savepoint is kept and can be rolled back to again.
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_find_by_id(
+pub extern "C" fn jasonisnthappy_rollback_to_savepoint(
     tx: *mut CTransaction,
-    collection_name: *const c_char,
-    id: *const c_char,
-    json_out: *mut *mut c_char,
+    name: *const c_char,
     error_out: *mut CError,
 ) -> i32 {
     if tx.is_null() {
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null transaction pointer").unwrap().into_raw(),
                 };
             }
@@ -713,17 +1023,7 @@ pub extern "C" fn jasonisnthappy_find_by_id(
         return -1;
     }
 
-    let coll_name = match unsafe { c_str_to_string(collection_name) } {
-        Ok(s) => s,
-        Err(e) => {
-            if !error_out.is_null() {
-                unsafe { *error_out = e; }
-            }
-            return -1;
-        }
-    };
-
-    let doc_id = match unsafe { c_str_to_string(id) } {
+    let savepoint_name = match unsafe { c_str_to_string(name) } {
         Ok(s) => s,
         Err(e) => {
             if !error_out.is_null() {
@@ -735,56 +1035,34 @@ pub extern "C" fn jasonisnthappy_find_by_id(
 
     let tx_ref = unsafe { &mut (*tx).inner };
 
-    let result = (|| -> jasonisnthappy::Result<Value> {
-        let coll = tx_ref.collection(&coll_name)?;
-        coll.find_by_id(&doc_id)
-    })();
-
-    match result {
-        Ok(doc) => {
-            let json_str = serde_json::to_string(&doc).unwrap();
-            let c_str = CString::new(json_str).unwrap();
-            if !json_out.is_null() {
-                unsafe { *json_out = c_str.into_raw(); }
-            }
+    match tx_ref.rollback_to_savepoint(&savepoint_name) {
+        Ok(_) => {
             if !error_out.is_null() {
                 unsafe { *error_out = CError::success(); }
             }
             0
         }
         Err(e) => {
-            let err_str = e.to_string();
-            if err_str.contains("not found") || err_str.contains("does not exist") {
-                if !json_out.is_null() {
-                    unsafe { *json_out = ptr::null_mut(); }
-                }
-                if !error_out.is_null() {
-                    unsafe { *error_out = CError::success(); }
-                }
-                1
-            } else {
-                if !error_out.is_null() {
-                    unsafe { *error_out = CError::from_error(e); }
-                }
-                -1
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
             }
+            -1
         }
     }
 }
 
+/// Remove a named savepoint, keeping the writes made since it was created
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_update_by_id(
+pub extern "C" fn jasonisnthappy_release_savepoint(
     tx: *mut CTransaction,
-    collection_name: *const c_char,
-    id: *const c_char,
-    json: *const c_char,
+    name: *const c_char,
     error_out: *mut CError,
 ) -> i32 {
     if tx.is_null() {
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null transaction pointer").unwrap().into_raw(),
                 };
             }
@@ -792,7 +1070,7 @@ pub extern "C" fn jasonisnthappy_update_by_id(
         return -1;
     }
 
-    let coll_name = match unsafe { c_str_to_string(collection_name) } {
+    let savepoint_name = match unsafe { c_str_to_string(name) } {
         Ok(s) => s,
         Err(e) => {
             if !error_out.is_null() {
@@ -802,10 +1080,222 @@ pub extern "C" fn jasonisnthappy_update_by_id(
         }
     };
 
-    let doc_id = match unsafe { c_str_to_string(id) } {
-        Ok(s) => s,
-        Err(e) => {
-            if !error_out.is_null() {
+    let tx_ref = unsafe { &mut (*tx).inner };
+
+    match tx_ref.release_savepoint(&savepoint_name) {
+        Ok(_) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
+}
+
+// ============================================================================
+// Document Operations (within a transaction)
+// ============================================================================
+
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_insert(
+    tx: *mut CTransaction,
+    collection_name: *const c_char,
+    json: *const c_char,
+    id_out: *mut *mut c_char,
+    error_out: *mut CError,
+) -> i32 {
+    if tx.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null transaction pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let coll_name = match unsafe { c_str_to_string(collection_name) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let json_str = match unsafe { c_str_to_string(json) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let value: Value = match serde_json::from_str(&json_str) {
+        Ok(v) => v,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe {
+                    *error_out = CError {
+                        code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                        message: CString::new(format!("Invalid JSON: {}", e)).unwrap().into_raw(),
+                    };
+                }
+            }
+            return -1;
+        }
+    };
+
+    let tx_ref = unsafe { &mut (*tx).inner };
+
+    let result = (|| -> jasonisnthappy::Result<String> {
+        let mut coll = tx_ref.collection(&coll_name)?;
+        coll.insert(value)
+    })();
+
+    match result {
+        Ok(id) => {
+            if !id_out.is_null() {
+                let c_id = CString::new(id).unwrap();
+                unsafe { *id_out = c_id.into_raw(); }
+            }
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_find_by_id(
+    tx: *mut CTransaction,
+    collection_name: *const c_char,
+    id: *const c_char,
+    json_out: *mut *mut c_char,
+    error_out: *mut CError,
+) -> i32 {
+    if tx.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null transaction pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let coll_name = match unsafe { c_str_to_string(collection_name) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let doc_id = match unsafe { c_str_to_string(id) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let tx_ref = unsafe { &mut (*tx).inner };
+
+    let result = (|| -> jasonisnthappy::Result<Value> {
+        let coll = tx_ref.collection(&coll_name)?;
+        coll.find_by_id(&doc_id)
+    })();
+
+    match result {
+        Ok(doc) => {
+            let json_str = serde_json::to_string(&doc).unwrap();
+            let c_str = CString::new(json_str).unwrap();
+            if !json_out.is_null() {
+                unsafe { *json_out = c_str.into_raw(); }
+            }
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if error_code(&e) == JASONISNTHAPPY_ERROR_NOT_FOUND {
+                if !json_out.is_null() {
+                    unsafe { *json_out = ptr::null_mut(); }
+                }
+                if !error_out.is_null() {
+                    unsafe { *error_out = CError::success(); }
+                }
+                1
+            } else {
+                if !error_out.is_null() {
+                    unsafe { *error_out = CError::from_error(e); }
+                }
+                -1
+            }
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_update_by_id(
+    tx: *mut CTransaction,
+    collection_name: *const c_char,
+    id: *const c_char,
+    json: *const c_char,
+    error_out: *mut CError,
+) -> i32 {
+    if tx.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null transaction pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let coll_name = match unsafe { c_str_to_string(collection_name) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let doc_id = match unsafe { c_str_to_string(id) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
                 unsafe { *error_out = e; }
             }
             return -1;
@@ -828,7 +1318,7 @@ pub extern "C" fn jasonisnthappy_update_by_id(
             if !error_out.is_null() {
                 unsafe {
                     *error_out = CError {
-                        code: -1,
+                        code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                         message: CString::new(format!("Invalid JSON: {}", e)).unwrap().into_raw(),
                     };
                 }
@@ -871,7 +1361,7 @@ pub extern "C" fn jasonisnthappy_delete_by_id(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null transaction pointer").unwrap().into_raw(),
                 };
             }
@@ -933,7 +1423,7 @@ pub extern "C" fn jasonisnthappy_find_all(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null transaction pointer").unwrap().into_raw(),
                 };
             }
@@ -992,6 +1482,15 @@ pub extern "C" fn jasonisnthappy_free_string(s: *mut c_char) {
     }
 }
 
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_free_buffer(buffer: CBuffer) {
+    if !buffer.data.is_null() {
+        unsafe {
+            let _ = Box::from_raw(ptr::slice_from_raw_parts_mut(buffer.data, buffer.len));
+        }
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn jasonisnthappy_free_error(error: CError) {
     if !error.message.is_null() {
@@ -1035,7 +1534,7 @@ pub extern "C" fn jasonisnthappy_count(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null transaction pointer").unwrap().into_raw(),
                 };
             }
@@ -1102,7 +1601,7 @@ pub extern "C" fn jasonisnthappy_create_collection(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null transaction pointer").unwrap().into_raw(),
                 };
             }
@@ -1148,7 +1647,7 @@ pub extern "C" fn jasonisnthappy_drop_collection(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null transaction pointer").unwrap().into_raw(),
                 };
             }
@@ -1195,7 +1694,7 @@ pub extern "C" fn jasonisnthappy_rename_collection(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null transaction pointer").unwrap().into_raw(),
                 };
             }
@@ -1241,17 +1740,19 @@ pub extern "C" fn jasonisnthappy_rename_collection(
     }
 }
 
+/// Rename a collection outside of any transaction
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_list_collections(
+pub extern "C" fn jasonisnthappy_database_rename_collection(
     db: *mut CDatabase,
-    json_out: *mut *mut c_char,
+    old_name: *const c_char,
+    new_name: *const c_char,
     error_out: *mut CError,
 ) -> i32 {
     if db.is_null() {
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -1259,27 +1760,184 @@ pub extern "C" fn jasonisnthappy_list_collections(
         return -1;
     }
 
-    let db_ref = unsafe { &(*db).inner };
-    let metadata = db_ref.get_metadata();
-    let mut collections: Vec<String> = metadata.collections.keys().cloned().collect();
-    collections.sort();
+    let old_name_str = match unsafe { c_str_to_string(old_name) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
 
-    let json_str = serde_json::to_string(&collections).unwrap_or_else(|_| "[]".to_string());
-    let c_str = CString::new(json_str).unwrap();
+    let new_name_str = match unsafe { c_str_to_string(new_name) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
 
-    if !json_out.is_null() {
-        unsafe { *json_out = c_str.into_raw(); }
-    }
-    if !error_out.is_null() {
-        unsafe { *error_out = CError::success(); }
-    }
-    0
-}
+    let db_ref = unsafe { &(*db).inner };
 
-/// List all indexes for a collection
-///
-/// Returns JSON array of index objects with: name, fields (array), unique (bool), btree_root
-#[no_mangle]
+    match db_ref.rename_collection(&old_name_str, &new_name_str) {
+        Ok(_) => 0,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
+}
+
+/// Create `dst` as a copy of `src` with its documents, indexes and schema.
+/// On failure `dst` is not left behind.
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_copy_collection(
+    db: *mut CDatabase,
+    src: *const c_char,
+    dst: *const c_char,
+    error_out: *mut CError,
+) -> i32 {
+    if db.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null database pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let src_str = match unsafe { c_str_to_string(src) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let dst_str = match unsafe { c_str_to_string(dst) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let db_ref = unsafe { &(*db).inner };
+
+    match db_ref.copy_collection(&src_str, &dst_str) {
+        Ok(_) => 0,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_list_collections(
+    db: *mut CDatabase,
+    json_out: *mut *mut c_char,
+    error_out: *mut CError,
+) -> i32 {
+    if db.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null database pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let db_ref = unsafe { &(*db).inner };
+    let metadata = db_ref.get_metadata();
+    let mut collections: Vec<String> = metadata.collections.keys().cloned().collect();
+    collections.sort();
+
+    let json_str = serde_json::to_string(&collections).unwrap_or_else(|_| "[]".to_string());
+    let c_str = CString::new(json_str).unwrap();
+
+    if !json_out.is_null() {
+        unsafe { *json_out = c_str.into_raw(); }
+    }
+    if !error_out.is_null() {
+        unsafe { *error_out = CError::success(); }
+    }
+    0
+}
+
+/// Check whether a collection has an index (regular or text) with this name
+///
+/// A missing collection is reported as `false`, not as an error.
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_index_exists(
+    db: *mut CDatabase,
+    collection_name: *const c_char,
+    index_name: *const c_char,
+    exists_out: *mut bool,
+    error_out: *mut CError,
+) -> i32 {
+    if db.is_null() || exists_out.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let coll_name = match unsafe { c_str_to_string(collection_name) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let idx_name = match unsafe { c_str_to_string(index_name) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let db_ref = unsafe { &(*db).inner };
+    unsafe { *exists_out = db_ref.index_exists(&coll_name, &idx_name); }
+    if !error_out.is_null() {
+        unsafe { *error_out = CError::success(); }
+    }
+    0
+}
+
+/// List all indexes for a collection
+///
+/// Returns JSON array of index objects with: name, fields (array), unique (bool),
+/// descending (bool), sparse (bool), btree_root
+#[no_mangle]
 pub extern "C" fn jasonisnthappy_list_indexes(
     db: *mut CDatabase,
     collection_name: *const c_char,
@@ -1290,7 +1948,7 @@ pub extern "C" fn jasonisnthappy_list_indexes(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null pointer").unwrap().into_raw(),
                 };
             }
@@ -1306,7 +1964,7 @@ pub extern "C" fn jasonisnthappy_list_indexes(
             Err(e) => {
                 if !error_out.is_null() {
                     *error_out = CError {
-                        code: -1,
+                        code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                         message: CString::new(format!("Invalid collection_name UTF-8: {}", e))
                             .unwrap()
                             .into_raw(),
@@ -1324,6 +1982,10 @@ pub extern "C" fn jasonisnthappy_list_indexes(
                         "name": idx.name,
                         "fields": idx.fields,
                         "unique": idx.unique,
+                        "descending": idx.descending,
+                        "sparse": idx.sparse,
+                        "geo": idx.geo,
+                        "expression": idx.expression,
                         "btree_root": idx.btree_root
                     })
                 }).collect();
@@ -1336,7 +1998,7 @@ pub extern "C" fn jasonisnthappy_list_indexes(
                     Err(e) => {
                         if !error_out.is_null() {
                             *error_out = CError {
-                                code: -1,
+                                code: JASONISNTHAPPY_ERROR_UNKNOWN,
                                 message: CString::new(format!("Failed to serialize indexes: {}", e))
                                     .unwrap()
                                     .into_raw(),
@@ -1373,7 +2035,7 @@ pub extern "C" fn jasonisnthappy_create_index(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -1429,6 +2091,60 @@ pub extern "C" fn jasonisnthappy_create_index(
     }
 }
 
+/// Create a geo index over a latitude and a longitude field, used by
+/// `near(field, lat, lng, radius_km)` queries
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_create_geo_index(
+    db: *mut CDatabase,
+    collection_name: *const c_char,
+    index_name: *const c_char,
+    lat_field: *const c_char,
+    lng_field: *const c_char,
+    error_out: *mut CError,
+) -> i32 {
+    if db.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null database pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let mut args = Vec::with_capacity(4);
+    for arg in [collection_name, index_name, lat_field, lng_field] {
+        match unsafe { c_str_to_string(arg) } {
+            Ok(s) => args.push(s),
+            Err(e) => {
+                if !error_out.is_null() {
+                    unsafe { *error_out = e; }
+                }
+                return -1;
+            }
+        }
+    }
+
+    let db_ref = unsafe { &(*db).inner };
+
+    match db_ref.create_geo_index(&args[0], &args[1], &args[2], &args[3]) {
+        Ok(_) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn jasonisnthappy_create_compound_index(
     db: *mut CDatabase,
@@ -1438,12 +2154,31 @@ pub extern "C" fn jasonisnthappy_create_compound_index(
     num_fields: usize,
     unique: bool,
     error_out: *mut CError,
+) -> i32 {
+    let options = CIndexOptions {
+        unique,
+        ..jasonisnthappy_default_index_options()
+    };
+    jasonisnthappy_create_index_with_options(db, collection_name, index_name, fields, num_fields, options, error_out)
+}
+
+/// Create a single-field or compound index with explicit options. Start from
+/// `jasonisnthappy_default_index_options()` so `struct_size` is filled in.
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_create_index_with_options(
+    db: *mut CDatabase,
+    collection_name: *const c_char,
+    index_name: *const c_char,
+    fields: *const *const c_char,
+    num_fields: usize,
+    options: CIndexOptions,
+    error_out: *mut CError,
 ) -> i32 {
     if db.is_null() {
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -1451,11 +2186,18 @@ pub extern "C" fn jasonisnthappy_create_compound_index(
         return -1;
     }
 
+    if let Err(e) = check_index_options(&options) {
+        if !error_out.is_null() {
+            unsafe { *error_out = e; }
+        }
+        return -1;
+    }
+
     if fields.is_null() {
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null fields array pointer").unwrap().into_raw(),
                 };
             }
@@ -1504,7 +2246,68 @@ pub extern "C" fn jasonisnthappy_create_compound_index(
 
     let db_ref = unsafe { &(*db).inner };
 
-    match db_ref.create_compound_index(&coll_name, &idx_name, &field_refs, unique) {
+    match db_ref.create_index_with_options(&coll_name, &idx_name, &field_refs, options.into()) {
+        Ok(_) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
+}
+
+/// Create an index keyed by an expression such as `lower(email)`, used by
+/// queries comparing the same expression with `is`
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_create_expression_index(
+    db: *mut CDatabase,
+    collection_name: *const c_char,
+    index_name: *const c_char,
+    expression: *const c_char,
+    options: CIndexOptions,
+    error_out: *mut CError,
+) -> i32 {
+    if db.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null database pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    if let Err(e) = check_index_options(&options) {
+        if !error_out.is_null() {
+            unsafe { *error_out = e; }
+        }
+        return -1;
+    }
+
+    let mut args = Vec::with_capacity(3);
+    for arg in [collection_name, index_name, expression] {
+        match unsafe { c_str_to_string(arg) } {
+            Ok(s) => args.push(s),
+            Err(e) => {
+                if !error_out.is_null() {
+                    unsafe { *error_out = e; }
+                }
+                return -1;
+            }
+        }
+    }
+
+    let db_ref = unsafe { &(*db).inner };
+
+    match db_ref.create_expression_index(&args[0], &args[1], &args[2], options.into()) {
         Ok(_) => {
             if !error_out.is_null() {
                 unsafe { *error_out = CError::success(); }
@@ -1533,7 +2336,7 @@ pub extern "C" fn jasonisnthappy_create_text_index(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -1545,7 +2348,7 @@ pub extern "C" fn jasonisnthappy_create_text_index(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null fields array pointer").unwrap().into_raw(),
                 };
             }
@@ -1594,7 +2397,7 @@ pub extern "C" fn jasonisnthappy_create_text_index(
 
     let db_ref = unsafe { &(*db).inner };
 
-    match db_ref.create_text_index(&coll_name, &idx_name, &field_refs) {
+    match db_ref.create_text_index(&coll_name, &idx_name, &field_refs, TextIndexOptions::default()) {
         Ok(_) => {
             if !error_out.is_null() {
                 unsafe { *error_out = CError::success(); }
@@ -1621,7 +2424,7 @@ pub extern "C" fn jasonisnthappy_drop_index(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -1655,12 +2458,7 @@ pub extern "C" fn jasonisnthappy_drop_index(
         Ok(_) => 0,
         Err(e) => {
             if !error_out.is_null() {
-                unsafe {
-                    *error_out = CError {
-                        code: -1,
-                        message: CString::new(e.to_string()).unwrap().into_raw(),
-                    };
-                }
+                unsafe { *error_out = CError::from_error(e); }
             }
             -1
         }
@@ -1671,6 +2469,9 @@ pub extern "C" fn jasonisnthappy_drop_index(
 // Database Info & Stats
 // ============================================================================
 
+/// Writes the collection's `CollectionInfo` as JSON to `json_out`: document
+/// count, data size and average document size, and every index with its
+/// entry count and size in bytes.
 #[no_mangle]
 pub extern "C" fn jasonisnthappy_collection_stats(
     db: *mut CDatabase,
@@ -1682,7 +2483,7 @@ pub extern "C" fn jasonisnthappy_collection_stats(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -1704,22 +2505,8 @@ pub extern "C" fn jasonisnthappy_collection_stats(
 
     match db_ref.collection_stats(&coll_name) {
         Ok(stats) => {
-            let json_obj = serde_json::json!({
-                "name": stats.name,
-                "document_count": stats.document_count,
-                "btree_root": stats.btree_root,
-                "indexes": stats.indexes.iter().map(|idx| {
-                    serde_json::json!({
-                        "name": idx.name,
-                        "fields": idx.fields,
-                        "unique": idx.unique,
-                        "btree_root": idx.btree_root,
-                    })
-                }).collect::<Vec<_>>(),
-            });
-
-            let json_str = serde_json::to_string(&json_obj).unwrap_or_else(|_| "{}".to_string());
-            let c_str = CString::new(json_str).unwrap();
+            let json_str = serde_json::to_string(&stats).unwrap_or_else(|_| "{}".to_string());
+            let c_str = CString::new(json_str).unwrap();
 
             if !json_out.is_null() {
                 unsafe { *json_out = c_str.into_raw(); }
@@ -1738,6 +2525,8 @@ pub extern "C" fn jasonisnthappy_collection_stats(
     }
 }
 
+/// Writes the `DatabaseInfo` as JSON to `json_out`, with every collection
+/// described as by `jasonisnthappy_collection_stats`.
 #[no_mangle]
 pub extern "C" fn jasonisnthappy_database_info(
     db: *mut CDatabase,
@@ -1748,7 +2537,7 @@ pub extern "C" fn jasonisnthappy_database_info(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -1760,31 +2549,7 @@ pub extern "C" fn jasonisnthappy_database_info(
 
     match db_ref.info() {
         Ok(info) => {
-            let json_obj = serde_json::json!({
-                "path": info.path,
-                "version": info.version,
-                "num_pages": info.num_pages,
-                "file_size": info.file_size,
-                "total_documents": info.total_documents,
-                "read_only": info.read_only,
-                "collections": info.collections.iter().map(|coll| {
-                    serde_json::json!({
-                        "name": coll.name,
-                        "document_count": coll.document_count,
-                        "btree_root": coll.btree_root,
-                        "indexes": coll.indexes.iter().map(|idx| {
-                            serde_json::json!({
-                                "name": idx.name,
-                                "fields": idx.fields,
-                                "unique": idx.unique,
-                                "btree_root": idx.btree_root,
-                            })
-                        }).collect::<Vec<_>>(),
-                    })
-                }).collect::<Vec<_>>(),
-            });
-
-            let json_str = serde_json::to_string(&json_obj).unwrap_or_else(|_| "{}".to_string());
+            let json_str = serde_json::to_string(&info).unwrap_or_else(|_| "{}".to_string());
             let c_str = CString::new(json_str).unwrap();
 
             if !json_out.is_null() {
@@ -1804,6 +2569,116 @@ pub extern "C" fn jasonisnthappy_database_info(
     }
 }
 
+/// Check the database for corruption. On success `report_json_out` receives
+/// the integrity report as JSON; the check itself succeeding says nothing
+/// about whether problems were found, so inspect its `problems` array.
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_check_integrity(
+    db: *mut CDatabase,
+    report_json_out: *mut *mut c_char,
+    error_out: *mut CError,
+) -> i32 {
+    if db.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null database pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let db_ref = unsafe { &(*db).inner };
+
+    match db_ref.check_integrity() {
+        Ok(report) => {
+            let json_str = serde_json::to_string(&report).unwrap_or_else(|_| "{}".to_string());
+            let c_str = CString::new(json_str).unwrap();
+
+            if !report_json_out.is_null() {
+                unsafe { *report_json_out = c_str.into_raw(); }
+            }
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
+}
+
+/// Read a collection's B-trees, and with `documents` its document pages, into
+/// the page cache. A null `collection_name` warms every collection. On success
+/// `warmup_json_out` receives the pages read as JSON.
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_warm_cache(
+    db: *mut CDatabase,
+    collection_name: *const c_char,
+    documents: bool,
+    warmup_json_out: *mut *mut c_char,
+    error_out: *mut CError,
+) -> i32 {
+    if db.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null database pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let coll_name = if collection_name.is_null() {
+        None
+    } else {
+        match unsafe { c_str_to_string(collection_name) } {
+            Ok(s) => Some(s),
+            Err(e) => {
+                if !error_out.is_null() {
+                    unsafe { *error_out = e; }
+                }
+                return -1;
+            }
+        }
+    };
+
+    let db_ref = unsafe { &(*db).inner };
+
+    let warmed = match &coll_name {
+        Some(name) => db_ref.warm_cache(name, documents),
+        None => db_ref.warm_cache_all(documents),
+    };
+    match warmed {
+        Ok(warmup) => {
+            let json_str = serde_json::to_string(&warmup).unwrap_or_else(|_| "{}".to_string());
+            let c_str = CString::new(json_str).unwrap();
+
+            if !warmup_json_out.is_null() {
+                unsafe { *warmup_json_out = c_str.into_raw(); }
+            }
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn jasonisnthappy_get_path(
     db: *mut CDatabase,
@@ -1814,7 +2689,7 @@ pub extern "C" fn jasonisnthappy_get_path(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -1844,7 +2719,7 @@ pub extern "C" fn jasonisnthappy_is_read_only(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -1870,7 +2745,7 @@ pub extern "C" fn jasonisnthappy_max_bulk_operations(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -1895,7 +2770,7 @@ pub extern "C" fn jasonisnthappy_max_document_size(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -1920,7 +2795,7 @@ pub extern "C" fn jasonisnthappy_max_request_body_size(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -1951,7 +2826,7 @@ pub extern "C" fn jasonisnthappy_set_schema(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -1986,7 +2861,7 @@ pub extern "C" fn jasonisnthappy_set_schema(
             if !error_out.is_null() {
                 unsafe {
                     *error_out = CError {
-                        code: -1,
+                        code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                         message: CString::new(format!("Invalid schema JSON: {}", e)).unwrap().into_raw(),
                     };
                 }
@@ -2013,6 +2888,160 @@ pub extern "C" fn jasonisnthappy_set_schema(
     }
 }
 
+/// Set a collection's schema from a standard JSON Schema document, translating
+/// the supported subset. `warnings_json_out` receives a JSON array of strings,
+/// one per keyword that was ignored (empty if everything is enforced).
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_set_json_schema(
+    db: *mut CDatabase,
+    collection_name: *const c_char,
+    schema_json: *const c_char,
+    warnings_json_out: *mut *mut c_char,
+    error_out: *mut CError,
+) -> i32 {
+    if db.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null database pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let coll_name = match unsafe { c_str_to_string(collection_name) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let schema_str = match unsafe { c_str_to_string(schema_json) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let schema: Value = match serde_json::from_str(&schema_str) {
+        Ok(v) => v,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe {
+                    *error_out = CError {
+                        code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                        message: CString::new(format!("Invalid schema JSON: {}", e)).unwrap().into_raw(),
+                    };
+                }
+            }
+            return -1;
+        }
+    };
+
+    let db_ref = unsafe { &(*db).inner };
+
+    match db_ref.set_json_schema(&coll_name, &schema) {
+        Ok(warnings) => {
+            if !warnings_json_out.is_null() {
+                let json_str = serde_json::to_string(&warnings).unwrap();
+                unsafe { *warnings_json_out = CString::new(json_str).unwrap().into_raw(); }
+            }
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
+}
+
+/// Set how a collection generates `_id` for documents inserted without one.
+/// `strategy` is one of "uuid", "object_id", "ulid" or "auto_increment".
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_set_id_strategy(
+    db: *mut CDatabase,
+    collection_name: *const c_char,
+    strategy: *const c_char,
+    error_out: *mut CError,
+) -> i32 {
+    if db.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null database pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let coll_name = match unsafe { c_str_to_string(collection_name) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let strategy_str = match unsafe { c_str_to_string(strategy) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let strategy: jasonisnthappy::IdStrategy = match serde_json::from_value(Value::String(strategy_str.clone())) {
+        Ok(s) => s,
+        Err(_) => {
+            if !error_out.is_null() {
+                unsafe {
+                    *error_out = CError {
+                        code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                        message: CString::new(format!("Unknown id strategy: {}", strategy_str)).unwrap().into_raw(),
+                    };
+                }
+            }
+            return -1;
+        }
+    };
+
+    let db_ref = unsafe { &(*db).inner };
+
+    match db_ref.set_id_strategy(&coll_name, strategy) {
+        Ok(_) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn jasonisnthappy_get_schema(
     db: *mut CDatabase,
@@ -2024,7 +3053,7 @@ pub extern "C" fn jasonisnthappy_get_schema(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -2080,7 +3109,7 @@ pub extern "C" fn jasonisnthappy_remove_schema(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -2130,7 +3159,7 @@ pub extern "C" fn jasonisnthappy_get_collection(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -2177,7 +3206,7 @@ pub extern "C" fn jasonisnthappy_collection_upsert_by_id(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null collection pointer").unwrap().into_raw(),
                 };
             }
@@ -2211,7 +3240,7 @@ pub extern "C" fn jasonisnthappy_collection_upsert_by_id(
             if !error_out.is_null() {
                 unsafe {
                     *error_out = CError {
-                        code: -1,
+                        code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                         message: CString::new(format!("Invalid JSON: {}", e)).unwrap().into_raw(),
                     };
                 }
@@ -2271,7 +3300,7 @@ pub extern "C" fn jasonisnthappy_collection_upsert(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null collection pointer").unwrap().into_raw(),
                 };
             }
@@ -2305,7 +3334,7 @@ pub extern "C" fn jasonisnthappy_collection_upsert(
             if !error_out.is_null() {
                 unsafe {
                     *error_out = CError {
-                        code: -1,
+                        code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                         message: CString::new(format!("Invalid JSON: {}", e)).unwrap().into_raw(),
                     };
                 }
@@ -2352,61 +3381,111 @@ pub extern "C" fn jasonisnthappy_collection_upsert(
     }
 }
 
-// Query/find operations
+/// Like `jasonisnthappy_collection_upsert_by_id`, but merges `json` into an
+/// existing document, recursing into nested objects, instead of setting its
+/// top-level fields wholesale
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_find(
+pub extern "C" fn jasonisnthappy_collection_upsert_merge_by_id(
+    coll: *mut CCollection,
+    id: *const c_char,
+    json: *const c_char,
+    result_out: *mut i32,
+    id_out: *mut *mut c_char,
+    error_out: *mut CError,
+) -> i32 {
+    unsafe {
+        upsert_merge(coll, id, json, result_out, id_out, error_out, |coll, id, doc| {
+            coll.upsert_merge_by_id(id, doc)
+        })
+    }
+}
+
+/// Like `jasonisnthappy_collection_upsert`, but merges `json` into the first
+/// match the same way as `jasonisnthappy_collection_upsert_merge_by_id`
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_collection_upsert_merge(
     coll: *mut CCollection,
     query: *const c_char,
-    json_out: *mut *mut c_char,
+    json: *const c_char,
+    result_out: *mut i32,
+    id_out: *mut *mut c_char,
     error_out: *mut CError,
 ) -> i32 {
-    if coll.is_null() {
-        if !error_out.is_null() {
-            unsafe {
-                *error_out = CError {
-                    code: -1,
-                    message: CString::new("Null collection pointer").unwrap().into_raw(),
-                };
-            }
-        }
-        return -1;
+    unsafe {
+        upsert_merge(coll, query, json, result_out, id_out, error_out, |coll, query, doc| {
+            coll.upsert_merge(query, doc)
+        })
     }
+}
 
-    let query_str = match unsafe { c_str_to_string(query) } {
-        Ok(s) => s,
-        Err(e) => {
-            if !error_out.is_null() {
-                unsafe { *error_out = e; }
+/// Read `target` (an ID or query) and the `json` document, run `upsert`, and
+/// report the result as the other upsert functions do
+unsafe fn upsert_merge(
+    coll: *mut CCollection,
+    target: *const c_char,
+    json: *const c_char,
+    result_out: *mut i32,
+    id_out: *mut *mut c_char,
+    error_out: *mut CError,
+    upsert: impl FnOnce(&jasonisnthappy::core::Collection, &str, Value) -> jasonisnthappy::Result<jasonisnthappy::core::collection::UpsertResult>,
+) -> i32 {
+    if coll.is_null() {
+        if !error_out.is_null() {
+            *error_out = CError {
+                code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                message: CString::new("Null collection pointer").unwrap().into_raw(),
+            };
+        }
+        return -1;
+    }
+
+    let parsed = c_str_to_string(target).and_then(|target| {
+        let json_str = c_str_to_string(json)?;
+        let value: Value = serde_json::from_str(&json_str).map_err(|e| CError {
+            code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+            message: CString::new(format!("Invalid JSON: {}", e)).unwrap().into_raw(),
+        })?;
+        Ok((target, value))
+    });
+    let (target, value) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            if !error_out.is_null() {
+                *error_out = e;
             }
             return -1;
         }
     };
 
-    let coll_ref = unsafe { &(*coll).inner };
-
-    match coll_ref.find(&query_str) {
-        Ok(docs) => {
-            let json_str = serde_json::to_string(&docs).unwrap();
-            let c_str = CString::new(json_str).unwrap();
-            if !json_out.is_null() {
-                unsafe { *json_out = c_str.into_raw(); }
+    match upsert(&(*coll).inner, &target, value) {
+        Ok(result) => {
+            let (code, id) = match result {
+                jasonisnthappy::core::collection::UpsertResult::Inserted(id) => (0, id), // 0 = inserted
+                jasonisnthappy::core::collection::UpsertResult::Updated(id) => (1, id),  // 1 = updated
+            };
+            if !result_out.is_null() {
+                *result_out = code;
+            }
+            if !id_out.is_null() {
+                *id_out = CString::new(id).unwrap().into_raw();
             }
             if !error_out.is_null() {
-                unsafe { *error_out = CError::success(); }
+                *error_out = CError::success();
             }
             0
         }
         Err(e) => {
             if !error_out.is_null() {
-                unsafe { *error_out = CError::from_error(e); }
+                *error_out = CError::from_error(e);
             }
             -1
         }
     }
 }
 
+// Query/find operations
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_find_one(
+pub extern "C" fn jasonisnthappy_collection_find(
     coll: *mut CCollection,
     query: *const c_char,
     json_out: *mut *mut c_char,
@@ -2416,7 +3495,7 @@ pub extern "C" fn jasonisnthappy_collection_find_one(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null collection pointer").unwrap().into_raw(),
                 };
             }
@@ -2436,9 +3515,9 @@ pub extern "C" fn jasonisnthappy_collection_find_one(
 
     let coll_ref = unsafe { &(*coll).inner };
 
-    match coll_ref.find_one(&query_str) {
-        Ok(Some(doc)) => {
-            let json_str = serde_json::to_string(&doc).unwrap();
+    match coll_ref.find(&query_str) {
+        Ok(docs) => {
+            let json_str = serde_json::to_string(&docs).unwrap();
             let c_str = CString::new(json_str).unwrap();
             if !json_out.is_null() {
                 unsafe { *json_out = c_str.into_raw(); }
@@ -2448,16 +3527,6 @@ pub extern "C" fn jasonisnthappy_collection_find_one(
             }
             0
         }
-        Ok(None) => {
-            // Not found
-            if !json_out.is_null() {
-                unsafe { *json_out = ptr::null_mut(); }
-            }
-            if !error_out.is_null() {
-                unsafe { *error_out = CError::success(); }
-            }
-            1 // Return 1 to indicate "not found"
-        }
         Err(e) => {
             if !error_out.is_null() {
                 unsafe { *error_out = CError::from_error(e); }
@@ -2467,21 +3536,26 @@ pub extern "C" fn jasonisnthappy_collection_find_one(
     }
 }
 
-// Update/delete operations with queries
+/// Documents a cursor reads from the collection at a time
+const CURSOR_BATCH_SIZE: usize = 64;
+
+/// Opens a cursor over the documents matching `query`, written to
+/// `cursor_out`. Documents are read a batch at a time as the cursor
+/// advances, all from the snapshot taken here, which stays open until
+/// `jasonisnthappy_cursor_free`. Documents come in `_id` order.
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_update(
+pub extern "C" fn jasonisnthappy_collection_find_cursor(
     coll: *mut CCollection,
     query: *const c_char,
-    updates_json: *const c_char,
-    count_out: *mut usize,
+    cursor_out: *mut *mut CCursor,
     error_out: *mut CError,
 ) -> i32 {
-    if coll.is_null() {
+    if coll.is_null() || cursor_out.is_null() {
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
-                    message: CString::new("Null collection pointer").unwrap().into_raw(),
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null collection or cursor_out pointer").unwrap().into_raw(),
                 };
             }
         }
@@ -2498,38 +3572,12 @@ pub extern "C" fn jasonisnthappy_collection_update(
         }
     };
 
-    let updates_str = match unsafe { c_str_to_string(updates_json) } {
-        Ok(s) => s,
-        Err(e) => {
-            if !error_out.is_null() {
-                unsafe { *error_out = e; }
-            }
-            return -1;
-        }
-    };
-
-    let updates: Value = match serde_json::from_str(&updates_str) {
-        Ok(v) => v,
-        Err(e) => {
-            if !error_out.is_null() {
-                unsafe {
-                    *error_out = CError {
-                        code: -1,
-                        message: CString::new(format!("Invalid JSON: {}", e)).unwrap().into_raw(),
-                    };
-                }
-            }
-            return -1;
-        }
-    };
-
     let coll_ref = unsafe { &(*coll).inner };
 
-    match coll_ref.update(&query_str, updates) {
-        Ok(count) => {
-            if !count_out.is_null() {
-                unsafe { *count_out = count; }
-            }
+    match coll_ref.find_batches(&query_str, CURSOR_BATCH_SIZE) {
+        Ok(batches) => {
+            let cursor = CCursor { batches, buffered: Vec::new().into_iter() };
+            unsafe { *cursor_out = Box::into_raw(Box::new(cursor)); }
             if !error_out.is_null() {
                 unsafe { *error_out = CError::success(); }
             }
@@ -2544,94 +3592,84 @@ pub extern "C" fn jasonisnthappy_collection_update(
     }
 }
 
+/// Advances `cursor`, writing the next document's JSON to `doc_json_out`
+/// for the caller to free with `jasonisnthappy_free_string`.
+/// Returns 1 when a document was written, 0 once the cursor is exhausted
+/// and -1 on error.
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_update_one(
-    coll: *mut CCollection,
-    query: *const c_char,
-    updates_json: *const c_char,
-    updated_out: *mut bool,
+pub extern "C" fn jasonisnthappy_cursor_next(
+    cursor: *mut CCursor,
+    doc_json_out: *mut *mut c_char,
     error_out: *mut CError,
 ) -> i32 {
-    if coll.is_null() {
+    if cursor.is_null() || doc_json_out.is_null() {
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
-                    message: CString::new("Null collection pointer").unwrap().into_raw(),
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null cursor or doc_json_out pointer").unwrap().into_raw(),
                 };
             }
         }
         return -1;
     }
 
-    let query_str = match unsafe { c_str_to_string(query) } {
-        Ok(s) => s,
-        Err(e) => {
-            if !error_out.is_null() {
-                unsafe { *error_out = e; }
-            }
-            return -1;
-        }
-    };
+    let cursor_ref = unsafe { &mut *cursor };
 
-    let updates_str = match unsafe { c_str_to_string(updates_json) } {
-        Ok(s) => s,
-        Err(e) => {
-            if !error_out.is_null() {
-                unsafe { *error_out = e; }
-            }
-            return -1;
+    let doc = loop {
+        if let Some(doc) = cursor_ref.buffered.next() {
+            break doc;
         }
-    };
-
-    let updates: Value = match serde_json::from_str(&updates_str) {
-        Ok(v) => v,
-        Err(e) => {
-            if !error_out.is_null() {
-                unsafe {
-                    *error_out = CError {
-                        code: -1,
-                        message: CString::new(format!("Invalid JSON: {}", e)).unwrap().into_raw(),
-                    };
+        match cursor_ref.batches.next() {
+            Some(Ok(batch)) => cursor_ref.buffered = batch.into_iter(),
+            Some(Err(e)) => {
+                if !error_out.is_null() {
+                    unsafe { *error_out = CError::from_error(e); }
                 }
+                return -1;
+            }
+            None => {
+                if !error_out.is_null() {
+                    unsafe { *error_out = CError::success(); }
+                }
+                return 0;
             }
-            return -1;
         }
     };
 
-    let coll_ref = unsafe { &(*coll).inner };
+    let json_str = serde_json::to_string(&doc).unwrap();
+    unsafe { *doc_json_out = CString::new(json_str).unwrap().into_raw(); }
+    if !error_out.is_null() {
+        unsafe { *error_out = CError::success(); }
+    }
+    1
+}
 
-    match coll_ref.update_one(&query_str, updates) {
-        Ok(updated) => {
-            if !updated_out.is_null() {
-                unsafe { *updated_out = updated; }
-            }
-            if !error_out.is_null() {
-                unsafe { *error_out = CError::success(); }
-            }
-            0
-        }
-        Err(e) => {
-            if !error_out.is_null() {
-                unsafe { *error_out = CError::from_error(e); }
-            }
-            -1
+/// Frees a cursor and releases its snapshot
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_cursor_free(cursor: *mut CCursor) {
+    if !cursor.is_null() {
+        unsafe {
+            let _ = Box::from_raw(cursor);
         }
     }
 }
 
+/// Runs a query and writes a JSON report of how it was executed to `json_out`:
+/// access path, documents examined (estimated and actual), documents returned,
+/// whether sorting happened in memory and elapsed microseconds.
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_delete(
+pub extern "C" fn jasonisnthappy_collection_explain(
     coll: *mut CCollection,
     query: *const c_char,
-    count_out: *mut usize,
+    json_out: *mut *mut c_char,
     error_out: *mut CError,
 ) -> i32 {
     if coll.is_null() {
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null collection pointer").unwrap().into_raw(),
                 };
             }
@@ -2651,10 +3689,12 @@ pub extern "C" fn jasonisnthappy_collection_delete(
 
     let coll_ref = unsafe { &(*coll).inner };
 
-    match coll_ref.delete(&query_str) {
-        Ok(count) => {
-            if !count_out.is_null() {
-                unsafe { *count_out = count; }
+    match coll_ref.explain(&query_str) {
+        Ok(explain) => {
+            let json_str = serde_json::to_string(&explain).unwrap();
+            let c_str = CString::new(json_str).unwrap();
+            if !json_out.is_null() {
+                unsafe { *json_out = c_str.into_raw(); }
             }
             if !error_out.is_null() {
                 unsafe { *error_out = CError::success(); }
@@ -2671,17 +3711,17 @@ pub extern "C" fn jasonisnthappy_collection_delete(
 }
 
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_delete_one(
+pub extern "C" fn jasonisnthappy_collection_find_one(
     coll: *mut CCollection,
     query: *const c_char,
-    deleted_out: *mut bool,
+    json_out: *mut *mut c_char,
     error_out: *mut CError,
 ) -> i32 {
     if coll.is_null() {
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null collection pointer").unwrap().into_raw(),
                 };
             }
@@ -2701,16 +3741,28 @@ pub extern "C" fn jasonisnthappy_collection_delete_one(
 
     let coll_ref = unsafe { &(*coll).inner };
 
-    match coll_ref.delete_one(&query_str) {
-        Ok(deleted) => {
-            if !deleted_out.is_null() {
-                unsafe { *deleted_out = deleted; }
+    match coll_ref.find_one(&query_str) {
+        Ok(Some(doc)) => {
+            let json_str = serde_json::to_string(&doc).unwrap();
+            let c_str = CString::new(json_str).unwrap();
+            if !json_out.is_null() {
+                unsafe { *json_out = c_str.into_raw(); }
             }
             if !error_out.is_null() {
                 unsafe { *error_out = CError::success(); }
             }
             0
         }
+        Ok(None) => {
+            // Not found
+            if !json_out.is_null() {
+                unsafe { *json_out = ptr::null_mut(); }
+            }
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            1 // Return 1 to indicate "not found"
+        }
         Err(e) => {
             if !error_out.is_null() {
                 unsafe { *error_out = CError::from_error(e); }
@@ -2720,19 +3772,31 @@ pub extern "C" fn jasonisnthappy_collection_delete_one(
     }
 }
 
-// Bulk insert
+/// Parse the JSON array of values bound to a query's placeholders
+fn parse_query_params(params_json: *const c_char) -> Result<Vec<serde_json::Value>, CError> {
+    let params_str = unsafe { c_str_to_string(params_json) }?;
+    serde_json::from_str(&params_str).map_err(|e| CError {
+        code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+        message: CString::new(format!("Invalid JSON array of query parameters: {}", e)).unwrap().into_raw(),
+    })
+}
+
+/// Like `jasonisnthappy_collection_find`, binding the query's `?1`, `?2`, ...
+/// placeholders to the values in `params_json`, a JSON array. Each value is
+/// used as a whole JSON value, so user input never needs quoting.
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_insert_many(
+pub extern "C" fn jasonisnthappy_collection_find_params(
     coll: *mut CCollection,
-    docs_json: *const c_char,
-    ids_json_out: *mut *mut c_char,
+    query: *const c_char,
+    params_json: *const c_char,
+    json_out: *mut *mut c_char,
     error_out: *mut CError,
 ) -> i32 {
     if coll.is_null() {
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null collection pointer").unwrap().into_raw(),
                 };
             }
@@ -2740,7 +3804,7 @@ pub extern "C" fn jasonisnthappy_collection_insert_many(
         return -1;
     }
 
-    let docs_str = match unsafe { c_str_to_string(docs_json) } {
+    let query_str = match unsafe { c_str_to_string(query) } {
         Ok(s) => s,
         Err(e) => {
             if !error_out.is_null() {
@@ -2750,16 +3814,11 @@ pub extern "C" fn jasonisnthappy_collection_insert_many(
         }
     };
 
-    let docs: Vec<Value> = match serde_json::from_str(&docs_str) {
-        Ok(v) => v,
+    let params = match parse_query_params(params_json) {
+        Ok(params) => params,
         Err(e) => {
             if !error_out.is_null() {
-                unsafe {
-                    *error_out = CError {
-                        code: -1,
-                        message: CString::new(format!("Invalid JSON array: {}", e)).unwrap().into_raw(),
-                    };
-                }
+                unsafe { *error_out = e; }
             }
             return -1;
         }
@@ -2767,12 +3826,12 @@ pub extern "C" fn jasonisnthappy_collection_insert_many(
 
     let coll_ref = unsafe { &(*coll).inner };
 
-    match coll_ref.insert_many(docs) {
-        Ok(ids) => {
-            let json_str = serde_json::to_string(&ids).unwrap();
+    match coll_ref.find_params(&query_str, &params) {
+        Ok(docs) => {
+            let json_str = serde_json::to_string(&docs).unwrap();
             let c_str = CString::new(json_str).unwrap();
-            if !ids_json_out.is_null() {
-                unsafe { *ids_json_out = c_str.into_raw(); }
+            if !json_out.is_null() {
+                unsafe { *json_out = c_str.into_raw(); }
             }
             if !error_out.is_null() {
                 unsafe { *error_out = CError::success(); }
@@ -2788,11 +3847,14 @@ pub extern "C" fn jasonisnthappy_collection_insert_many(
     }
 }
 
-// Distinct operations
+/// Like `jasonisnthappy_collection_find_one`, binding the query's
+/// placeholders to `params_json` as `jasonisnthappy_collection_find_params`
+/// does. Returns 1 with `json_out` set to NULL when nothing matches.
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_distinct(
+pub extern "C" fn jasonisnthappy_collection_find_one_params(
     coll: *mut CCollection,
-    field: *const c_char,
+    query: *const c_char,
+    params_json: *const c_char,
     json_out: *mut *mut c_char,
     error_out: *mut CError,
 ) -> i32 {
@@ -2800,7 +3862,7 @@ pub extern "C" fn jasonisnthappy_collection_distinct(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null collection pointer").unwrap().into_raw(),
                 };
             }
@@ -2808,7 +3870,7 @@ pub extern "C" fn jasonisnthappy_collection_distinct(
         return -1;
     }
 
-    let field_str = match unsafe { c_str_to_string(field) } {
+    let query_str = match unsafe { c_str_to_string(query) } {
         Ok(s) => s,
         Err(e) => {
             if !error_out.is_null() {
@@ -2818,11 +3880,21 @@ pub extern "C" fn jasonisnthappy_collection_distinct(
         }
     };
 
+    let params = match parse_query_params(params_json) {
+        Ok(params) => params,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
     let coll_ref = unsafe { &(*coll).inner };
 
-    match coll_ref.distinct(&field_str) {
-        Ok(values) => {
-            let json_str = serde_json::to_string(&values).unwrap();
+    match coll_ref.find_one_params(&query_str, &params) {
+        Ok(Some(doc)) => {
+            let json_str = serde_json::to_string(&doc).unwrap();
             let c_str = CString::new(json_str).unwrap();
             if !json_out.is_null() {
                 unsafe { *json_out = c_str.into_raw(); }
@@ -2832,6 +3904,15 @@ pub extern "C" fn jasonisnthappy_collection_distinct(
             }
             0
         }
+        Ok(None) => {
+            if !json_out.is_null() {
+                unsafe { *json_out = ptr::null_mut(); }
+            }
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            1
+        }
         Err(e) => {
             if !error_out.is_null() {
                 unsafe { *error_out = CError::from_error(e); }
@@ -2841,18 +3922,26 @@ pub extern "C" fn jasonisnthappy_collection_distinct(
     }
 }
 
+/// Like `jasonisnthappy_collection_find`, returning only the fields named in
+/// `include_json` or dropping those in `exclude_json`, each a JSON array of
+/// field names or NULL. Dotted paths name nested fields, inside every object
+/// of an array along the way. `_id` is kept unless excluded. Alongside
+/// `include_json`, `exclude_json` may only name `_id` and paths inside
+/// included fields; `["_id"]` alone as `include_json` returns only `_id`s.
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_count_distinct(
+pub extern "C" fn jasonisnthappy_collection_find_with_projection(
     coll: *mut CCollection,
-    field: *const c_char,
-    count_out: *mut usize,
+    query: *const c_char,
+    include_json: *const c_char,
+    exclude_json: *const c_char,
+    json_out: *mut *mut c_char,
     error_out: *mut CError,
 ) -> i32 {
     if coll.is_null() {
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null collection pointer").unwrap().into_raw(),
                 };
             }
@@ -2860,8 +3949,15 @@ pub extern "C" fn jasonisnthappy_collection_count_distinct(
         return -1;
     }
 
-    let field_str = match unsafe { c_str_to_string(field) } {
-        Ok(s) => s,
+    let args = unsafe {
+        c_str_to_string(query).and_then(|query| Ok((
+            query,
+            c_field_list(include_json, "include_json")?,
+            c_field_list(exclude_json, "exclude_json")?,
+        )))
+    };
+    let (query_str, include, exclude) = match args {
+        Ok(args) => args,
         Err(e) => {
             if !error_out.is_null() {
                 unsafe { *error_out = e; }
@@ -2869,13 +3965,17 @@ pub extern "C" fn jasonisnthappy_collection_count_distinct(
             return -1;
         }
     };
+    let include: Vec<&str> = include.iter().map(|s| s.as_str()).collect();
+    let exclude: Vec<&str> = exclude.iter().map(|s| s.as_str()).collect();
 
     let coll_ref = unsafe { &(*coll).inner };
 
-    match coll_ref.count_distinct(&field_str) {
-        Ok(count) => {
-            if !count_out.is_null() {
-                unsafe { *count_out = count; }
+    match coll_ref.find_with_projection(&query_str, &include, &exclude) {
+        Ok(docs) => {
+            let json_str = serde_json::to_string(&docs).unwrap();
+            let c_str = CString::new(json_str).unwrap();
+            if !json_out.is_null() {
+                unsafe { *json_out = c_str.into_raw(); }
             }
             if !error_out.is_null() {
                 unsafe { *error_out = CError::success(); }
@@ -2891,11 +3991,14 @@ pub extern "C" fn jasonisnthappy_collection_count_distinct(
     }
 }
 
-// Text search
+/// Like `jasonisnthappy_collection_find_one` with the projection arguments of
+/// `jasonisnthappy_collection_find_with_projection`. Returns 1 if nothing matches.
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_search(
+pub extern "C" fn jasonisnthappy_collection_find_one_with_projection(
     coll: *mut CCollection,
     query: *const c_char,
+    include_json: *const c_char,
+    exclude_json: *const c_char,
     json_out: *mut *mut c_char,
     error_out: *mut CError,
 ) -> i32 {
@@ -2903,7 +4006,7 @@ pub extern "C" fn jasonisnthappy_collection_search(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null collection pointer").unwrap().into_raw(),
                 };
             }
@@ -2911,8 +4014,15 @@ pub extern "C" fn jasonisnthappy_collection_search(
         return -1;
     }
 
-    let query_str = match unsafe { c_str_to_string(query) } {
-        Ok(s) => s,
+    let args = unsafe {
+        c_str_to_string(query).and_then(|query| Ok((
+            query,
+            c_field_list(include_json, "include_json")?,
+            c_field_list(exclude_json, "exclude_json")?,
+        )))
+    };
+    let (query_str, include, exclude) = match args {
+        Ok(args) => args,
         Err(e) => {
             if !error_out.is_null() {
                 unsafe { *error_out = e; }
@@ -2920,20 +4030,14 @@ pub extern "C" fn jasonisnthappy_collection_search(
             return -1;
         }
     };
+    let include: Vec<&str> = include.iter().map(|s| s.as_str()).collect();
+    let exclude: Vec<&str> = exclude.iter().map(|s| s.as_str()).collect();
 
     let coll_ref = unsafe { &(*coll).inner };
 
-    match coll_ref.search(&query_str) {
-        Ok(results) => {
-            // SearchResult has doc_id and score
-            let json_array: Vec<serde_json::Value> = results.iter().map(|r| {
-                serde_json::json!({
-                    "doc_id": r.doc_id,
-                    "score": r.score,
-                })
-            }).collect();
-
-            let json_str = serde_json::to_string(&json_array).unwrap();
+    match coll_ref.find_one_with_projection(&query_str, &include, &exclude) {
+        Ok(Some(doc)) => {
+            let json_str = serde_json::to_string(&doc).unwrap();
             let c_str = CString::new(json_str).unwrap();
             if !json_out.is_null() {
                 unsafe { *json_out = c_str.into_raw(); }
@@ -2943,6 +4047,15 @@ pub extern "C" fn jasonisnthappy_collection_search(
             }
             0
         }
+        Ok(None) => {
+            if !json_out.is_null() {
+                unsafe { *json_out = ptr::null_mut(); }
+            }
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            1
+        }
         Err(e) => {
             if !error_out.is_null() {
                 unsafe { *error_out = CError::from_error(e); }
@@ -2952,19 +4065,20 @@ pub extern "C" fn jasonisnthappy_collection_search(
     }
 }
 
-// Basic Collection CRUD (non-transactional)
+// Update/delete operations with queries
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_insert(
+pub extern "C" fn jasonisnthappy_collection_update(
     coll: *mut CCollection,
-    json: *const c_char,
-    id_out: *mut *mut c_char,
+    query: *const c_char,
+    updates_json: *const c_char,
+    count_out: *mut usize,
     error_out: *mut CError,
 ) -> i32 {
     if coll.is_null() {
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null collection pointer").unwrap().into_raw(),
                 };
             }
@@ -2972,7 +4086,7 @@ pub extern "C" fn jasonisnthappy_collection_insert(
         return -1;
     }
 
-    let json_str = match unsafe { c_str_to_string(json) } {
+    let query_str = match unsafe { c_str_to_string(query) } {
         Ok(s) => s,
         Err(e) => {
             if !error_out.is_null() {
@@ -2982,13 +4096,23 @@ pub extern "C" fn jasonisnthappy_collection_insert(
         }
     };
 
-    let value: Value = match serde_json::from_str(&json_str) {
+    let updates_str = match unsafe { c_str_to_string(updates_json) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let updates: Value = match serde_json::from_str(&updates_str) {
         Ok(v) => v,
         Err(e) => {
             if !error_out.is_null() {
                 unsafe {
                     *error_out = CError {
-                        code: -1,
+                        code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                         message: CString::new(format!("Invalid JSON: {}", e)).unwrap().into_raw(),
                     };
                 }
@@ -2999,11 +4123,10 @@ pub extern "C" fn jasonisnthappy_collection_insert(
 
     let coll_ref = unsafe { &(*coll).inner };
 
-    match coll_ref.insert(value) {
-        Ok(id) => {
-            if !id_out.is_null() {
-                let c_id = CString::new(id).unwrap();
-                unsafe { *id_out = c_id.into_raw(); }
+    match coll_ref.update(&query_str, updates) {
+        Ok(count) => {
+            if !count_out.is_null() {
+                unsafe { *count_out = count; }
             }
             if !error_out.is_null() {
                 unsafe { *error_out = CError::success(); }
@@ -3020,17 +4143,18 @@ pub extern "C" fn jasonisnthappy_collection_insert(
 }
 
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_find_by_id(
+pub extern "C" fn jasonisnthappy_collection_update_one(
     coll: *mut CCollection,
-    id: *const c_char,
-    json_out: *mut *mut c_char,
+    query: *const c_char,
+    updates_json: *const c_char,
+    updated_out: *mut bool,
     error_out: *mut CError,
 ) -> i32 {
     if coll.is_null() {
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null collection pointer").unwrap().into_raw(),
                 };
             }
@@ -3038,7 +4162,17 @@ pub extern "C" fn jasonisnthappy_collection_find_by_id(
         return -1;
     }
 
-    let doc_id = match unsafe { c_str_to_string(id) } {
+    let query_str = match unsafe { c_str_to_string(query) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let updates_str = match unsafe { c_str_to_string(updates_json) } {
         Ok(s) => s,
         Err(e) => {
             if !error_out.is_null() {
@@ -3048,14 +4182,27 @@ pub extern "C" fn jasonisnthappy_collection_find_by_id(
         }
     };
 
+    let updates: Value = match serde_json::from_str(&updates_str) {
+        Ok(v) => v,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe {
+                    *error_out = CError {
+                        code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                        message: CString::new(format!("Invalid JSON: {}", e)).unwrap().into_raw(),
+                    };
+                }
+            }
+            return -1;
+        }
+    };
+
     let coll_ref = unsafe { &(*coll).inner };
 
-    match coll_ref.find_by_id(&doc_id) {
-        Ok(doc) => {
-            let json_str = serde_json::to_string(&doc).unwrap();
-            let c_str = CString::new(json_str).unwrap();
-            if !json_out.is_null() {
-                unsafe { *json_out = c_str.into_raw(); }
+    match coll_ref.update_one(&query_str, updates) {
+        Ok(updated) => {
+            if !updated_out.is_null() {
+                unsafe { *updated_out = updated; }
             }
             if !error_out.is_null() {
                 unsafe { *error_out = CError::success(); }
@@ -3071,18 +4218,21 @@ pub extern "C" fn jasonisnthappy_collection_find_by_id(
     }
 }
 
+/// Update every document matching `query` and write the modified `_id`s to
+/// `ids_json_out` as a JSON array
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_update_by_id(
+pub extern "C" fn jasonisnthappy_collection_update_returning(
     coll: *mut CCollection,
-    id: *const c_char,
+    query: *const c_char,
     updates_json: *const c_char,
+    ids_json_out: *mut *mut c_char,
     error_out: *mut CError,
 ) -> i32 {
     if coll.is_null() {
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null collection pointer").unwrap().into_raw(),
                 };
             }
@@ -3090,7 +4240,7 @@ pub extern "C" fn jasonisnthappy_collection_update_by_id(
         return -1;
     }
 
-    let doc_id = match unsafe { c_str_to_string(id) } {
+    let query_str = match unsafe { c_str_to_string(query) } {
         Ok(s) => s,
         Err(e) => {
             if !error_out.is_null() {
@@ -3116,7 +4266,7 @@ pub extern "C" fn jasonisnthappy_collection_update_by_id(
             if !error_out.is_null() {
                 unsafe {
                     *error_out = CError {
-                        code: -1,
+                        code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                         message: CString::new(format!("Invalid JSON: {}", e)).unwrap().into_raw(),
                     };
                 }
@@ -3127,8 +4277,13 @@ pub extern "C" fn jasonisnthappy_collection_update_by_id(
 
     let coll_ref = unsafe { &(*coll).inner };
 
-    match coll_ref.update_by_id(&doc_id, updates) {
-        Ok(_) => {
+    match coll_ref.update_returning(&query_str, updates) {
+        Ok(ids) => {
+            let json_str = serde_json::to_string(&ids).unwrap();
+            let c_str = CString::new(json_str).unwrap();
+            if !ids_json_out.is_null() {
+                unsafe { *ids_json_out = c_str.into_raw(); }
+            }
             if !error_out.is_null() {
                 unsafe { *error_out = CError::success(); }
             }
@@ -3143,17 +4298,21 @@ pub extern "C" fn jasonisnthappy_collection_update_by_id(
     }
 }
 
+/// Update the first document matching `query` and write its `_id` to `id_out`.
+/// Returns 1, with `id_out` set to NULL, if nothing matched.
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_delete_by_id(
+pub extern "C" fn jasonisnthappy_collection_update_one_returning(
     coll: *mut CCollection,
-    id: *const c_char,
+    query: *const c_char,
+    updates_json: *const c_char,
+    id_out: *mut *mut c_char,
     error_out: *mut CError,
 ) -> i32 {
     if coll.is_null() {
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null collection pointer").unwrap().into_raw(),
                 };
             }
@@ -3161,7 +4320,17 @@ pub extern "C" fn jasonisnthappy_collection_delete_by_id(
         return -1;
     }
 
-    let doc_id = match unsafe { c_str_to_string(id) } {
+    let query_str = match unsafe { c_str_to_string(query) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let updates_str = match unsafe { c_str_to_string(updates_json) } {
         Ok(s) => s,
         Err(e) => {
             if !error_out.is_null() {
@@ -3171,15 +4340,43 @@ pub extern "C" fn jasonisnthappy_collection_delete_by_id(
         }
     };
 
+    let updates: Value = match serde_json::from_str(&updates_str) {
+        Ok(v) => v,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe {
+                    *error_out = CError {
+                        code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                        message: CString::new(format!("Invalid JSON: {}", e)).unwrap().into_raw(),
+                    };
+                }
+            }
+            return -1;
+        }
+    };
+
     let coll_ref = unsafe { &(*coll).inner };
 
-    match coll_ref.delete_by_id(&doc_id) {
-        Ok(_) => {
+    match coll_ref.update_one_returning(&query_str, updates) {
+        Ok(Some(id)) => {
+            let c_str = CString::new(id).unwrap();
+            if !id_out.is_null() {
+                unsafe { *id_out = c_str.into_raw(); }
+            }
             if !error_out.is_null() {
                 unsafe { *error_out = CError::success(); }
             }
             0
         }
+        Ok(None) => {
+            if !id_out.is_null() {
+                unsafe { *id_out = ptr::null_mut(); }
+            }
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            1
+        }
         Err(e) => {
             if !error_out.is_null() {
                 unsafe { *error_out = CError::from_error(e); }
@@ -3190,16 +4387,17 @@ pub extern "C" fn jasonisnthappy_collection_delete_by_id(
 }
 
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_find_all(
+pub extern "C" fn jasonisnthappy_collection_delete(
     coll: *mut CCollection,
-    json_out: *mut *mut c_char,
+    query: *const c_char,
+    count_out: *mut usize,
     error_out: *mut CError,
 ) -> i32 {
     if coll.is_null() {
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null collection pointer").unwrap().into_raw(),
                 };
             }
@@ -3207,14 +4405,22 @@ pub extern "C" fn jasonisnthappy_collection_find_all(
         return -1;
     }
 
+    let query_str = match unsafe { c_str_to_string(query) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
     let coll_ref = unsafe { &(*coll).inner };
 
-    match coll_ref.find_all() {
-        Ok(docs) => {
-            let json_str = serde_json::to_string(&docs).unwrap();
-            let c_str = CString::new(json_str).unwrap();
-            if !json_out.is_null() {
-                unsafe { *json_out = c_str.into_raw(); }
+    match coll_ref.delete(&query_str) {
+        Ok(count) => {
+            if !count_out.is_null() {
+                unsafe { *count_out = count; }
             }
             if !error_out.is_null() {
                 unsafe { *error_out = CError::success(); }
@@ -3231,16 +4437,17 @@ pub extern "C" fn jasonisnthappy_collection_find_all(
 }
 
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_count(
+pub extern "C" fn jasonisnthappy_collection_delete_one(
     coll: *mut CCollection,
-    count_out: *mut usize,
+    query: *const c_char,
+    deleted_out: *mut bool,
     error_out: *mut CError,
 ) -> i32 {
     if coll.is_null() {
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null collection pointer").unwrap().into_raw(),
                 };
             }
@@ -3248,12 +4455,22 @@ pub extern "C" fn jasonisnthappy_collection_count(
         return -1;
     }
 
+    let query_str = match unsafe { c_str_to_string(query) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
     let coll_ref = unsafe { &(*coll).inner };
 
-    match coll_ref.count() {
-        Ok(count) => {
-            if !count_out.is_null() {
-                unsafe { *count_out = count; }
+    match coll_ref.delete_one(&query_str) {
+        Ok(deleted) => {
+            if !deleted_out.is_null() {
+                unsafe { *deleted_out = deleted; }
             }
             if !error_out.is_null() {
                 unsafe { *error_out = CError::success(); }
@@ -3269,17 +4486,26 @@ pub extern "C" fn jasonisnthappy_collection_count(
     }
 }
 
+/// Atomically update the first document matching `query` and return it as
+/// JSON in `json_out`: the document after the update if `return_new`, before
+/// it otherwise. `sort_field` (NULL = no sort) picks which match is first.
+/// Returns 1 with `json_out` set to NULL if nothing matches.
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_name(
+pub extern "C" fn jasonisnthappy_collection_find_one_and_update(
     coll: *mut CCollection,
-    name_out: *mut *mut c_char,
+    query: *const c_char,
+    updates_json: *const c_char,
+    sort_field: *const c_char,
+    sort_ascending: bool,
+    return_new: bool,
+    json_out: *mut *mut c_char,
     error_out: *mut CError,
 ) -> i32 {
     if coll.is_null() {
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null collection pointer").unwrap().into_raw(),
                 };
             }
@@ -3287,31 +4513,62 @@ pub extern "C" fn jasonisnthappy_collection_name(
         return -1;
     }
 
+    let updates_str = match unsafe { c_str_to_string(updates_json) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let updates: Value = match serde_json::from_str(&updates_str) {
+        Ok(v) => v,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe {
+                    *error_out = CError {
+                        code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                        message: CString::new(format!("Invalid JSON: {}", e)).unwrap().into_raw(),
+                    };
+                }
+            }
+            return -1;
+        }
+    };
+
     let coll_ref = unsafe { &(*coll).inner };
-    let name = coll_ref.name();
+    let query_builder = match unsafe { find_and_modify_query(coll_ref, query, sort_field, sort_ascending) } {
+        Ok(q) => q,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
 
-    if !name_out.is_null() {
-        let c_str = CString::new(name).unwrap();
-        unsafe { *name_out = c_str.into_raw(); }
-    }
-    if !error_out.is_null() {
-        unsafe { *error_out = CError::success(); }
-    }
-    0
+    find_and_modify_result(query_builder.find_one_and_update(updates, return_new), json_out, error_out)
 }
 
+/// Atomically delete the first document matching `query` and return it as
+/// JSON in `json_out`. `sort_field` (NULL = no sort) picks which match is
+/// first. Returns 1 with `json_out` set to NULL if nothing matches.
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_count_with_query(
+pub extern "C" fn jasonisnthappy_collection_find_one_and_delete(
     coll: *mut CCollection,
     query: *const c_char,
-    count_out: *mut usize,
+    sort_field: *const c_char,
+    sort_ascending: bool,
+    json_out: *mut *mut c_char,
     error_out: *mut CError,
 ) -> i32 {
     if coll.is_null() {
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null collection pointer").unwrap().into_raw(),
                 };
             }
@@ -3320,31 +4577,61 @@ pub extern "C" fn jasonisnthappy_collection_count_with_query(
     }
 
     let coll_ref = unsafe { &(*coll).inner };
-
-    let query_option = if query.is_null() {
-        None
-    } else {
-        match unsafe { c_str_to_string(query) } {
-            Ok(s) => Some(s),
-            Err(e) => {
-                if !error_out.is_null() {
-                    unsafe { *error_out = e; }
-                }
-                return -1;
+    let query_builder = match unsafe { find_and_modify_query(coll_ref, query, sort_field, sort_ascending) } {
+        Ok(q) => q,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
             }
+            return -1;
         }
     };
 
-    match coll_ref.count_with_query(query_option.as_deref()) {
-        Ok(count) => {
-            if !count_out.is_null() {
-                unsafe { *count_out = count; }
+    find_and_modify_result(query_builder.find_one_and_delete(), json_out, error_out)
+}
+
+/// Query builder filtered by `query` and sorted by `sort_field` if not NULL
+unsafe fn find_and_modify_query<'a>(
+    coll: &'a jasonisnthappy::core::Collection,
+    query: *const c_char,
+    sort_field: *const c_char,
+    sort_ascending: bool,
+) -> Result<jasonisnthappy::core::QueryBuilder<'a>, CError> {
+    let mut query_builder = coll.query().filter(&c_str_to_string(query)?);
+
+    if !sort_field.is_null() {
+        let order = if sort_ascending { SortOrder::Asc } else { SortOrder::Desc };
+        query_builder = query_builder.sort_by(&c_str_to_string(sort_field)?, order);
+    }
+
+    Ok(query_builder)
+}
+
+fn find_and_modify_result(
+    result: jasonisnthappy::Result<Option<Value>>,
+    json_out: *mut *mut c_char,
+    error_out: *mut CError,
+) -> i32 {
+    match result {
+        Ok(Some(doc)) => {
+            let json_str = serde_json::to_string(&doc).unwrap();
+            if !json_out.is_null() {
+                unsafe { *json_out = CString::new(json_str).unwrap().into_raw(); }
             }
             if !error_out.is_null() {
                 unsafe { *error_out = CError::success(); }
             }
             0
         }
+        Ok(None) => {
+            if !json_out.is_null() {
+                unsafe { *json_out = ptr::null_mut(); }
+            }
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            1
+        }
         Err(e) => {
             if !error_out.is_null() {
                 unsafe { *error_out = CError::from_error(e); }
@@ -3354,120 +4641,1515 @@ pub extern "C" fn jasonisnthappy_collection_count_with_query(
     }
 }
 
-// Typed method variants - in FFI these use JSON but provide API parity
-#[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_insert_typed(
-    coll: *mut CCollection,
-    json: *const c_char,
-    id_out: *mut *mut c_char,
-    error_out: *mut CError,
-) -> i32 {
-    jasonisnthappy_collection_insert(coll, json, id_out, error_out)
-}
-
+// Bulk insert
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_insert_many_typed(
+pub extern "C" fn jasonisnthappy_collection_insert_many(
     coll: *mut CCollection,
     docs_json: *const c_char,
     ids_json_out: *mut *mut c_char,
     error_out: *mut CError,
 ) -> i32 {
-    jasonisnthappy_collection_insert_many(coll, docs_json, ids_json_out, error_out)
+    if coll.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null collection pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let docs_str = match unsafe { c_str_to_string(docs_json) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let docs: Vec<Value> = match serde_json::from_str(&docs_str) {
+        Ok(v) => v,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe {
+                    *error_out = CError {
+                        code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                        message: CString::new(format!("Invalid JSON array: {}", e)).unwrap().into_raw(),
+                    };
+                }
+            }
+            return -1;
+        }
+    };
+
+    let coll_ref = unsafe { &(*coll).inner };
+
+    match coll_ref.insert_many(docs) {
+        Ok(ids) => {
+            let json_str = serde_json::to_string(&ids).unwrap();
+            let c_str = CString::new(json_str).unwrap();
+            if !ids_json_out.is_null() {
+                unsafe { *ids_json_out = c_str.into_raw(); }
+            }
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
 }
 
+// Binary values
+
+/// Insert document `id` holding the `len` bytes at `data` as its binary `data`
+/// field. `data` may be NULL when `len` is 0.
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_find_by_id_typed(
+pub extern "C" fn jasonisnthappy_collection_insert_binary(
     coll: *mut CCollection,
     id: *const c_char,
-    json_out: *mut *mut c_char,
+    data: *const u8,
+    len: usize,
     error_out: *mut CError,
 ) -> i32 {
-    jasonisnthappy_collection_find_by_id(coll, id, json_out, error_out)
+    if coll.is_null() || (data.is_null() && len > 0) {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null collection or data pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let id_str = match unsafe { c_str_to_string(id) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let bytes = if len == 0 { &[][..] } else { unsafe { std::slice::from_raw_parts(data, len) } };
+    let coll_ref = unsafe { &(*coll).inner };
+
+    match coll_ref.insert_binary(&id_str, bytes) {
+        Ok(_) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
 }
 
+/// Bytes stored by `jasonisnthappy_collection_insert_binary`, written to
+/// `buffer_out`; free them with `jasonisnthappy_free_buffer`
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_find_all_typed(
+pub extern "C" fn jasonisnthappy_collection_get_binary(
     coll: *mut CCollection,
-    json_out: *mut *mut c_char,
+    id: *const c_char,
+    buffer_out: *mut CBuffer,
     error_out: *mut CError,
 ) -> i32 {
-    jasonisnthappy_collection_find_all(coll, json_out, error_out)
+    if coll.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null collection pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let id_str = match unsafe { c_str_to_string(id) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let coll_ref = unsafe { &(*coll).inner };
+
+    match coll_ref.get_binary(&id_str) {
+        Ok(bytes) => {
+            if !buffer_out.is_null() {
+                unsafe { *buffer_out = CBuffer::from_vec(bytes); }
+            }
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
 }
 
+// Distinct operations
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_find_typed(
+pub extern "C" fn jasonisnthappy_collection_distinct(
     coll: *mut CCollection,
-    query: *const c_char,
+    field: *const c_char,
     json_out: *mut *mut c_char,
     error_out: *mut CError,
 ) -> i32 {
-    jasonisnthappy_collection_find(coll, query, json_out, error_out)
+    if coll.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null collection pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let field_str = match unsafe { c_str_to_string(field) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let coll_ref = unsafe { &(*coll).inner };
+
+    match coll_ref.distinct(&field_str) {
+        Ok(values) => {
+            let json_str = serde_json::to_string(&values).unwrap();
+            let c_str = CString::new(json_str).unwrap();
+            if !json_out.is_null() {
+                unsafe { *json_out = c_str.into_raw(); }
+            }
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
 }
 
+/// Distinct values of `field` among documents matching `query`, as a JSON array
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_find_one_typed(
+pub extern "C" fn jasonisnthappy_collection_distinct_with_query(
     coll: *mut CCollection,
+    field: *const c_char,
     query: *const c_char,
     json_out: *mut *mut c_char,
     error_out: *mut CError,
 ) -> i32 {
-    jasonisnthappy_collection_find_one(coll, query, json_out, error_out)
-}
+    if coll.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null collection pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
 
-#[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_update_by_id_typed(
-    coll: *mut CCollection,
-    id: *const c_char,
-    updates_json: *const c_char,
-    error_out: *mut CError,
-) -> i32 {
-    jasonisnthappy_collection_update_by_id(coll, id, updates_json, error_out)
+    let field_str = match unsafe { c_str_to_string(field) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let query_str = match unsafe { c_str_to_string(query) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let coll_ref = unsafe { &(*coll).inner };
+
+    match coll_ref.distinct_with_query(&field_str, &query_str) {
+        Ok(values) => {
+            let json_str = serde_json::to_string(&values).unwrap();
+            let c_str = CString::new(json_str).unwrap();
+            if !json_out.is_null() {
+                unsafe { *json_out = c_str.into_raw(); }
+            }
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
 }
 
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_update_typed(
+pub extern "C" fn jasonisnthappy_collection_count_distinct(
     coll: *mut CCollection,
-    query: *const c_char,
-    updates_json: *const c_char,
+    field: *const c_char,
     count_out: *mut usize,
     error_out: *mut CError,
 ) -> i32 {
-    jasonisnthappy_collection_update(coll, query, updates_json, count_out, error_out)
+    if coll.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null collection pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let field_str = match unsafe { c_str_to_string(field) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let coll_ref = unsafe { &(*coll).inner };
+
+    match coll_ref.count_distinct(&field_str) {
+        Ok(count) => {
+            if !count_out.is_null() {
+                unsafe { *count_out = count; }
+            }
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
 }
 
+// Text search
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_update_one_typed(
+pub extern "C" fn jasonisnthappy_collection_search(
     coll: *mut CCollection,
     query: *const c_char,
-    updates_json: *const c_char,
-    updated_out: *mut bool,
+    json_out: *mut *mut c_char,
     error_out: *mut CError,
 ) -> i32 {
-    jasonisnthappy_collection_update_one(coll, query, updates_json, updated_out, error_out)
+    if coll.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null collection pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let query_str = match unsafe { c_str_to_string(query) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let coll_ref = unsafe { &(*coll).inner };
+
+    match coll_ref.search(&query_str) {
+        Ok(results) => {
+            // SearchResult has doc_id and score
+            let json_array: Vec<serde_json::Value> = results.iter().map(|r| {
+                serde_json::json!({
+                    "doc_id": r.doc_id,
+                    "score": r.score,
+                })
+            }).collect();
+
+            let json_str = serde_json::to_string(&json_array).unwrap();
+            let c_str = CString::new(json_str).unwrap();
+            if !json_out.is_null() {
+                unsafe { *json_out = c_str.into_raw(); }
+            }
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
 }
 
+// Basic Collection CRUD (non-transactional)
 #[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_upsert_by_id_typed(
+pub extern "C" fn jasonisnthappy_collection_insert(
     coll: *mut CCollection,
-    id: *const c_char,
     json: *const c_char,
-    result_out: *mut i32,
     id_out: *mut *mut c_char,
     error_out: *mut CError,
 ) -> i32 {
-    jasonisnthappy_collection_upsert_by_id(coll, id, json, result_out, id_out, error_out)
+    if coll.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null collection pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let json_str = match unsafe { c_str_to_string(json) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let value: Value = match serde_json::from_str(&json_str) {
+        Ok(v) => v,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe {
+                    *error_out = CError {
+                        code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                        message: CString::new(format!("Invalid JSON: {}", e)).unwrap().into_raw(),
+                    };
+                }
+            }
+            return -1;
+        }
+    };
+
+    let coll_ref = unsafe { &(*coll).inner };
+
+    match coll_ref.insert(value) {
+        Ok(id) => {
+            if !id_out.is_null() {
+                let c_id = CString::new(id).unwrap();
+                unsafe { *id_out = c_id.into_raw(); }
+            }
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
 }
 
-#[no_mangle]
-pub extern "C" fn jasonisnthappy_collection_upsert_typed(
-    coll: *mut CCollection,
-    query: *const c_char,
-    json: *const c_char,
-    result_out: *mut i32,
-    id_out: *mut *mut c_char,
-    error_out: *mut CError,
-) -> i32 {
-    jasonisnthappy_collection_upsert(coll, query, json, result_out, id_out, error_out)
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_collection_find_by_id(
+    coll: *mut CCollection,
+    id: *const c_char,
+    json_out: *mut *mut c_char,
+    error_out: *mut CError,
+) -> i32 {
+    if coll.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null collection pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let doc_id = match unsafe { c_str_to_string(id) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let coll_ref = unsafe { &(*coll).inner };
+
+    match coll_ref.find_by_id(&doc_id) {
+        Ok(doc) => {
+            let json_str = serde_json::to_string(&doc).unwrap();
+            let c_str = CString::new(json_str).unwrap();
+            if !json_out.is_null() {
+                unsafe { *json_out = c_str.into_raw(); }
+            }
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
+}
+
+/// Look up several documents by ID against one snapshot
+///
+/// `ids_json` is a JSON array of ID strings. On success `json_out` receives a
+/// JSON array in the same order, with `null` for IDs that don't exist.
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_collection_find_by_ids(
+    coll: *mut CCollection,
+    ids_json: *const c_char,
+    json_out: *mut *mut c_char,
+    error_out: *mut CError,
+) -> i32 {
+    if coll.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null collection pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let ids_str = match unsafe { c_str_to_string(ids_json) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let ids: Vec<String> = match serde_json::from_str(&ids_str) {
+        Ok(v) => v,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe {
+                    *error_out = CError {
+                        code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                        message: CString::new(format!("Invalid JSON array of IDs: {}", e)).unwrap().into_raw(),
+                    };
+                }
+            }
+            return -1;
+        }
+    };
+
+    let coll_ref = unsafe { &(*coll).inner };
+    let id_refs: Vec<&str> = ids.iter().map(|s| s.as_str()).collect();
+
+    match coll_ref.find_by_ids(&id_refs) {
+        Ok(docs) => {
+            let json_str = serde_json::to_string(&docs).unwrap();
+            let c_str = CString::new(json_str).unwrap();
+            if !json_out.is_null() {
+                unsafe { *json_out = c_str.into_raw(); }
+            }
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
+}
+
+/// Find documents with an `_id` from `start_id` up to `end_id`, in `_id` order
+///
+/// `start_id` is always included; `end_id` only when `inclusive` is true. On
+/// success `json_out` receives a JSON array of the documents.
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_collection_scan_range(
+    coll: *mut CCollection,
+    start_id: *const c_char,
+    end_id: *const c_char,
+    inclusive: bool,
+    json_out: *mut *mut c_char,
+    error_out: *mut CError,
+) -> i32 {
+    if coll.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null collection pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let start_str = match unsafe { c_str_to_string(start_id) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let end_str = match unsafe { c_str_to_string(end_id) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let coll_ref = unsafe { &(*coll).inner };
+
+    match coll_ref.scan_range(&start_str, &end_str, inclusive) {
+        Ok(docs) => {
+            let json_str = serde_json::to_string(&docs).unwrap();
+            let c_str = CString::new(json_str).unwrap();
+            if !json_out.is_null() {
+                unsafe { *json_out = c_str.into_raw(); }
+            }
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
+}
+
+/// Find up to `limit` documents with an `_id` of `start_id` or later, in `_id`
+/// order
+///
+/// On success `json_out` receives a JSON array of the documents.
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_collection_scan_from(
+    coll: *mut CCollection,
+    start_id: *const c_char,
+    limit: usize,
+    json_out: *mut *mut c_char,
+    error_out: *mut CError,
+) -> i32 {
+    if coll.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null collection pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let start_str = match unsafe { c_str_to_string(start_id) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let coll_ref = unsafe { &(*coll).inner };
+
+    match coll_ref.scan_from(&start_str, limit) {
+        Ok(docs) => {
+            let json_str = serde_json::to_string(&docs).unwrap();
+            let c_str = CString::new(json_str).unwrap();
+            if !json_out.is_null() {
+                unsafe { *json_out = c_str.into_raw(); }
+            }
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_collection_update_by_id(
+    coll: *mut CCollection,
+    id: *const c_char,
+    updates_json: *const c_char,
+    error_out: *mut CError,
+) -> i32 {
+    if coll.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null collection pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let doc_id = match unsafe { c_str_to_string(id) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let updates_str = match unsafe { c_str_to_string(updates_json) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let updates: Value = match serde_json::from_str(&updates_str) {
+        Ok(v) => v,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe {
+                    *error_out = CError {
+                        code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                        message: CString::new(format!("Invalid JSON: {}", e)).unwrap().into_raw(),
+                    };
+                }
+            }
+            return -1;
+        }
+    };
+
+    let coll_ref = unsafe { &(*coll).inner };
+
+    match coll_ref.update_by_id(&doc_id, updates) {
+        Ok(_) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_collection_delete_by_id(
+    coll: *mut CCollection,
+    id: *const c_char,
+    error_out: *mut CError,
+) -> i32 {
+    if coll.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null collection pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let doc_id = match unsafe { c_str_to_string(id) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let coll_ref = unsafe { &(*coll).inner };
+
+    match coll_ref.delete_by_id(&doc_id) {
+        Ok(_) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_collection_find_all(
+    coll: *mut CCollection,
+    json_out: *mut *mut c_char,
+    error_out: *mut CError,
+) -> i32 {
+    if coll.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null collection pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let coll_ref = unsafe { &(*coll).inner };
+
+    match coll_ref.find_all() {
+        Ok(docs) => {
+            let json_str = serde_json::to_string(&docs).unwrap();
+            let c_str = CString::new(json_str).unwrap();
+            if !json_out.is_null() {
+                unsafe { *json_out = c_str.into_raw(); }
+            }
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_collection_count(
+    coll: *mut CCollection,
+    count_out: *mut usize,
+    error_out: *mut CError,
+) -> i32 {
+    if coll.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null collection pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let coll_ref = unsafe { &(*coll).inner };
+
+    match coll_ref.count() {
+        Ok(count) => {
+            if !count_out.is_null() {
+                unsafe { *count_out = count; }
+            }
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_collection_name(
+    coll: *mut CCollection,
+    name_out: *mut *mut c_char,
+    error_out: *mut CError,
+) -> i32 {
+    if coll.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null collection pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let coll_ref = unsafe { &(*coll).inner };
+    let name = coll_ref.name();
+
+    if !name_out.is_null() {
+        let c_str = CString::new(name).unwrap();
+        unsafe { *name_out = c_str.into_raw(); }
+    }
+    if !error_out.is_null() {
+        unsafe { *error_out = CError::success(); }
+    }
+    0
+}
+
+/// Check whether the collection exists as of the last commit, without
+/// starting a transaction. Getting a handle doesn't create the collection.
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_collection_exists(
+    coll: *mut CCollection,
+    exists_out: *mut bool,
+    error_out: *mut CError,
+) -> i32 {
+    if coll.is_null() || exists_out.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let coll_ref = unsafe { &(*coll).inner };
+    unsafe { *exists_out = coll_ref.exists(); }
+    if !error_out.is_null() {
+        unsafe { *error_out = CError::success(); }
+    }
+    0
+}
+
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_collection_count_with_query(
+    coll: *mut CCollection,
+    query: *const c_char,
+    count_out: *mut usize,
+    error_out: *mut CError,
+) -> i32 {
+    if coll.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null collection pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let coll_ref = unsafe { &(*coll).inner };
+
+    let query_option = if query.is_null() {
+        None
+    } else {
+        match unsafe { c_str_to_string(query) } {
+            Ok(s) => Some(s),
+            Err(e) => {
+                if !error_out.is_null() {
+                    unsafe { *error_out = e; }
+                }
+                return -1;
+            }
+        }
+    };
+
+    match coll_ref.count_with_query(query_option.as_deref()) {
+        Ok(count) => {
+            if !count_out.is_null() {
+                unsafe { *count_out = count; }
+            }
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
+}
+
+// Typed method variants - in FFI these use JSON but provide API parity
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_collection_insert_typed(
+    coll: *mut CCollection,
+    json: *const c_char,
+    id_out: *mut *mut c_char,
+    error_out: *mut CError,
+) -> i32 {
+    jasonisnthappy_collection_insert(coll, json, id_out, error_out)
+}
+
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_collection_insert_many_typed(
+    coll: *mut CCollection,
+    docs_json: *const c_char,
+    ids_json_out: *mut *mut c_char,
+    error_out: *mut CError,
+) -> i32 {
+    jasonisnthappy_collection_insert_many(coll, docs_json, ids_json_out, error_out)
+}
+
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_collection_find_by_id_typed(
+    coll: *mut CCollection,
+    id: *const c_char,
+    json_out: *mut *mut c_char,
+    error_out: *mut CError,
+) -> i32 {
+    jasonisnthappy_collection_find_by_id(coll, id, json_out, error_out)
+}
+
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_collection_find_all_typed(
+    coll: *mut CCollection,
+    json_out: *mut *mut c_char,
+    error_out: *mut CError,
+) -> i32 {
+    jasonisnthappy_collection_find_all(coll, json_out, error_out)
+}
+
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_collection_find_typed(
+    coll: *mut CCollection,
+    query: *const c_char,
+    json_out: *mut *mut c_char,
+    error_out: *mut CError,
+) -> i32 {
+    jasonisnthappy_collection_find(coll, query, json_out, error_out)
+}
+
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_collection_find_one_typed(
+    coll: *mut CCollection,
+    query: *const c_char,
+    json_out: *mut *mut c_char,
+    error_out: *mut CError,
+) -> i32 {
+    jasonisnthappy_collection_find_one(coll, query, json_out, error_out)
+}
+
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_collection_update_by_id_typed(
+    coll: *mut CCollection,
+    id: *const c_char,
+    updates_json: *const c_char,
+    error_out: *mut CError,
+) -> i32 {
+    jasonisnthappy_collection_update_by_id(coll, id, updates_json, error_out)
+}
+
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_collection_update_typed(
+    coll: *mut CCollection,
+    query: *const c_char,
+    updates_json: *const c_char,
+    count_out: *mut usize,
+    error_out: *mut CError,
+) -> i32 {
+    jasonisnthappy_collection_update(coll, query, updates_json, count_out, error_out)
+}
+
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_collection_update_one_typed(
+    coll: *mut CCollection,
+    query: *const c_char,
+    updates_json: *const c_char,
+    updated_out: *mut bool,
+    error_out: *mut CError,
+) -> i32 {
+    jasonisnthappy_collection_update_one(coll, query, updates_json, updated_out, error_out)
+}
+
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_collection_upsert_by_id_typed(
+    coll: *mut CCollection,
+    id: *const c_char,
+    json: *const c_char,
+    result_out: *mut i32,
+    id_out: *mut *mut c_char,
+    error_out: *mut CError,
+) -> i32 {
+    jasonisnthappy_collection_upsert_by_id(coll, id, json, result_out, id_out, error_out)
+}
+
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_collection_upsert_typed(
+    coll: *mut CCollection,
+    query: *const c_char,
+    json: *const c_char,
+    result_out: *mut i32,
+    id_out: *mut *mut c_char,
+    error_out: *mut CError,
+) -> i32 {
+    jasonisnthappy_collection_upsert(coll, query, json, result_out, id_out, error_out)
+}
+
+// ============================================================================
+// Key-Value API
+// ============================================================================
+
+/// Use a collection as a key-value store, with each document's `_id` as its key
+///
+/// Values are JSON objects: the document without its `_id`. Free the handle
+/// with `jasonisnthappy_kv_free`.
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_get_kv(
+    db: *mut CDatabase,
+    collection_name: *const c_char,
+    error_out: *mut CError,
+) -> *mut CKvStore {
+    if db.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null database pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return ptr::null_mut();
+    }
+
+    let coll_name = match unsafe { c_str_to_string(collection_name) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return ptr::null_mut();
+        }
+    };
+
+    let db_ref = unsafe { &(*db).inner };
+    Box::into_raw(Box::new(CKvStore { inner: db_ref.kv(&coll_name) }))
+}
+
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_kv_free(kv: *mut CKvStore) {
+    if !kv.is_null() {
+        unsafe {
+            let _ = Box::from_raw(kv);
+        }
+    }
+}
+
+/// Get the value stored under `key`. `json_out` receives the value as JSON,
+/// or `null` if the key isn't set.
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_kv_get(
+    kv: *mut CKvStore,
+    key: *const c_char,
+    json_out: *mut *mut c_char,
+    error_out: *mut CError,
+) -> i32 {
+    if kv.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null key-value store pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let key_str = match unsafe { c_str_to_string(key) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let kv_ref = unsafe { &(*kv).inner };
+
+    match kv_ref.get(&key_str) {
+        Ok(value) => {
+            let json_str = serde_json::to_string(&value).unwrap();
+            let c_str = CString::new(json_str).unwrap();
+            if !json_out.is_null() {
+                unsafe { *json_out = c_str.into_raw(); }
+            }
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
+}
+
+/// Store `value_json`, a JSON object, under `key`, replacing what was there
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_kv_set(
+    kv: *mut CKvStore,
+    key: *const c_char,
+    value_json: *const c_char,
+    error_out: *mut CError,
+) -> i32 {
+    if kv.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null key-value store pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let key_str = match unsafe { c_str_to_string(key) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let value_str = match unsafe { c_str_to_string(value_json) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let value: Value = match serde_json::from_str(&value_str) {
+        Ok(v) => v,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe {
+                    *error_out = CError {
+                        code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                        message: CString::new(format!("Invalid JSON value: {}", e)).unwrap().into_raw(),
+                    };
+                }
+            }
+            return -1;
+        }
+    };
+
+    let kv_ref = unsafe { &(*kv).inner };
+
+    match kv_ref.set(&key_str, value) {
+        Ok(()) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
+}
+
+/// Get several values against one snapshot
+///
+/// `keys_json` is a JSON array of keys. On success `json_out` receives a JSON
+/// array of the values in the same order, with `null` for keys that aren't set.
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_kv_mget(
+    kv: *mut CKvStore,
+    keys_json: *const c_char,
+    json_out: *mut *mut c_char,
+    error_out: *mut CError,
+) -> i32 {
+    if kv.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null key-value store pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let keys_str = match unsafe { c_str_to_string(keys_json) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let keys: Vec<String> = match serde_json::from_str(&keys_str) {
+        Ok(v) => v,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe {
+                    *error_out = CError {
+                        code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                        message: CString::new(format!("Invalid JSON array of keys: {}", e)).unwrap().into_raw(),
+                    };
+                }
+            }
+            return -1;
+        }
+    };
+
+    let kv_ref = unsafe { &(*kv).inner };
+    let key_refs: Vec<&str> = keys.iter().map(|s| s.as_str()).collect();
+
+    match kv_ref.mget(&key_refs) {
+        Ok(value) => {
+            let json_str = serde_json::to_string(&value).unwrap();
+            let c_str = CString::new(json_str).unwrap();
+            if !json_out.is_null() {
+                unsafe { *json_out = c_str.into_raw(); }
+            }
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
+}
+
+/// Store several values in one transaction
+///
+/// `pairs_json` is a JSON object mapping each key to its value. Either every
+/// value is written or, on error, none are.
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_kv_mset(
+    kv: *mut CKvStore,
+    pairs_json: *const c_char,
+    error_out: *mut CError,
+) -> i32 {
+    if kv.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null key-value store pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let pairs_str = match unsafe { c_str_to_string(pairs_json) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let pairs: serde_json::Map<String, Value> = match serde_json::from_str(&pairs_str) {
+        Ok(v) => v,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe {
+                    *error_out = CError {
+                        code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                        message: CString::new(format!("Invalid JSON object of key-value pairs: {}", e)).unwrap().into_raw(),
+                    };
+                }
+            }
+            return -1;
+        }
+    };
+
+    let kv_ref = unsafe { &(*kv).inner };
+    let pair_refs: Vec<(&str, Value)> = pairs.iter()
+        .map(|(key, value)| (key.as_str(), value.clone()))
+        .collect();
+
+    match kv_ref.mset(&pair_refs) {
+        Ok(()) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
+}
+
+/// Remove `key`. `deleted_out` is set to whether it was set.
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_kv_delete(
+    kv: *mut CKvStore,
+    key: *const c_char,
+    deleted_out: *mut bool,
+    error_out: *mut CError,
+) -> i32 {
+    if kv.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null key-value store pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let key_str = match unsafe { c_str_to_string(key) } {
+        Ok(s) => s,
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = e; }
+            }
+            return -1;
+        }
+    };
+
+    let kv_ref = unsafe { &(*kv).inner };
+
+    match kv_ref.delete(&key_str) {
+        Ok(deleted) => {
+            if !deleted_out.is_null() {
+                unsafe { *deleted_out = deleted; }
+            }
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
 }
 
 // ============================================================================
@@ -3485,7 +6167,8 @@ pub extern "C" fn jasonisnthappy_collection_upsert_typed(
 /// - project_json: Optional JSON array of fields to include (NULL = all fields)
 /// - exclude_json: Optional JSON array of fields to exclude (NULL = none)
 ///
-/// Note: Cannot specify both project_json and exclude_json
+/// Both lists take dotted paths. Given together, exclude_json may only name
+/// `_id` and paths inside fields in project_json.
 #[no_mangle]
 pub extern "C" fn jasonisnthappy_collection_query_with_options(
     coll: *mut CCollection,
@@ -3503,7 +6186,7 @@ pub extern "C" fn jasonisnthappy_collection_query_with_options(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null pointer").unwrap().into_raw(),
                 };
             }
@@ -3526,7 +6209,7 @@ pub extern "C" fn jasonisnthappy_collection_query_with_options(
                 Err(e) => {
                     if !error_out.is_null() {
                         *error_out = CError {
-                            code: -1,
+                            code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                             message: CString::new(format!("Invalid filter UTF-8: {}", e))
                                 .unwrap()
                                 .into_raw(),
@@ -3551,7 +6234,7 @@ pub extern "C" fn jasonisnthappy_collection_query_with_options(
                 Err(e) => {
                     if !error_out.is_null() {
                         *error_out = CError {
-                            code: -1,
+                            code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                             message: CString::new(format!("Invalid sort_field UTF-8: {}", e))
                                 .unwrap()
                                 .into_raw(),
@@ -3573,18 +6256,6 @@ pub extern "C" fn jasonisnthappy_collection_query_with_options(
         }
 
         // Add projection if provided
-        if !project_json.is_null() && !exclude_json.is_null() {
-            if !error_out.is_null() {
-                *error_out = CError {
-                    code: -1,
-                    message: CString::new("Cannot specify both project_json and exclude_json")
-                        .unwrap()
-                        .into_raw(),
-                };
-            }
-            return -1;
-        }
-
         if !project_json.is_null() {
             match CStr::from_ptr(project_json).to_str() {
                 Ok(proj_str) => {
@@ -3596,7 +6267,7 @@ pub extern "C" fn jasonisnthappy_collection_query_with_options(
                         Err(e) => {
                             if !error_out.is_null() {
                                 *error_out = CError {
-                                    code: -1,
+                                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                                     message: CString::new(format!(
                                         "Invalid project_json format (expected JSON array): {}",
                                         e
@@ -3612,7 +6283,7 @@ pub extern "C" fn jasonisnthappy_collection_query_with_options(
                 Err(e) => {
                     if !error_out.is_null() {
                         *error_out = CError {
-                            code: -1,
+                            code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                             message: CString::new(format!("Invalid project_json UTF-8: {}", e))
                                 .unwrap()
                                 .into_raw(),
@@ -3634,7 +6305,7 @@ pub extern "C" fn jasonisnthappy_collection_query_with_options(
                         Err(e) => {
                             if !error_out.is_null() {
                                 *error_out = CError {
-                                    code: -1,
+                                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                                     message: CString::new(format!(
                                         "Invalid exclude_json format (expected JSON array): {}",
                                         e
@@ -3650,7 +6321,7 @@ pub extern "C" fn jasonisnthappy_collection_query_with_options(
                 Err(e) => {
                     if !error_out.is_null() {
                         *error_out = CError {
-                            code: -1,
+                            code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                             message: CString::new(format!("Invalid exclude_json UTF-8: {}", e))
                                 .unwrap()
                                 .into_raw(),
@@ -3672,7 +6343,7 @@ pub extern "C" fn jasonisnthappy_collection_query_with_options(
                     Err(e) => {
                         if !error_out.is_null() {
                             *error_out = CError {
-                                code: -1,
+                                code: JASONISNTHAPPY_ERROR_UNKNOWN,
                                 message: CString::new(format!("Failed to serialize results: {}", e))
                                     .unwrap()
                                     .into_raw(),
@@ -3685,7 +6356,7 @@ pub extern "C" fn jasonisnthappy_collection_query_with_options(
             Err(e) => {
                 if !error_out.is_null() {
                     *error_out = CError {
-                        code: -1,
+                        code: error_code(&e),
                         message: CString::new(format!("Query failed: {}", e))
                             .unwrap()
                             .into_raw(),
@@ -3711,7 +6382,7 @@ pub extern "C" fn jasonisnthappy_collection_query_count(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null pointer").unwrap().into_raw(),
                 };
             }
@@ -3733,7 +6404,7 @@ pub extern "C" fn jasonisnthappy_collection_query_count(
                 Err(e) => {
                     if !error_out.is_null() {
                         *error_out = CError {
-                            code: -1,
+                            code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                             message: CString::new(format!("Invalid filter UTF-8: {}", e))
                                 .unwrap()
                                 .into_raw(),
@@ -3761,7 +6432,7 @@ pub extern "C" fn jasonisnthappy_collection_query_count(
             Err(e) => {
                 if !error_out.is_null() {
                     *error_out = CError {
-                        code: -1,
+                        code: error_code(&e),
                         message: CString::new(format!("Count failed: {}", e))
                             .unwrap()
                             .into_raw(),
@@ -3787,7 +6458,7 @@ pub extern "C" fn jasonisnthappy_collection_query_first(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null pointer").unwrap().into_raw(),
                 };
             }
@@ -3809,7 +6480,7 @@ pub extern "C" fn jasonisnthappy_collection_query_first(
                 Err(e) => {
                     if !error_out.is_null() {
                         *error_out = CError {
-                            code: -1,
+                            code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                             message: CString::new(format!("Invalid filter UTF-8: {}", e))
                                 .unwrap()
                                 .into_raw(),
@@ -3834,7 +6505,7 @@ pub extern "C" fn jasonisnthappy_collection_query_first(
                 Err(e) => {
                     if !error_out.is_null() {
                         *error_out = CError {
-                            code: -1,
+                            code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                             message: CString::new(format!("Invalid sort_field UTF-8: {}", e))
                                 .unwrap()
                                 .into_raw(),
@@ -3856,7 +6527,7 @@ pub extern "C" fn jasonisnthappy_collection_query_first(
                     Err(e) => {
                         if !error_out.is_null() {
                             *error_out = CError {
-                                code: -1,
+                                code: JASONISNTHAPPY_ERROR_UNKNOWN,
                                 message: CString::new(format!("Failed to serialize result: {}", e))
                                     .unwrap()
                                     .into_raw(),
@@ -3874,7 +6545,7 @@ pub extern "C" fn jasonisnthappy_collection_query_first(
             Err(e) => {
                 if !error_out.is_null() {
                     *error_out = CError {
-                        code: -1,
+                        code: error_code(&e),
                         message: CString::new(format!("Query failed: {}", e))
                             .unwrap()
                             .into_raw(),
@@ -3894,19 +6565,21 @@ pub extern "C" fn jasonisnthappy_collection_query_first(
 ///
 /// # Parameters
 /// - operations_json: JSON array of operations, each with:
-///   - "op": "insert" | "update_one" | "update_many" | "delete_one" | "delete_many"
-///   - "doc": document (for insert)
-///   - "query": query string (for update/delete)
+///   - "op": "insert" | "update_one" | "update_many" | "delete_one" | "delete_many" | "upsert_one" | "upsert_by_id"
+///   - "doc": document (for insert/upsert)
+///   - "query": query string (for update/delete/upsert_one)
+///   - "id": document ID (for upsert_by_id)
 ///   - "updates": updates object (for update)
 /// - ordered: if true, stop on first error; if false, continue on errors
-/// - result_json_out: BulkWriteResult as JSON (inserted_count, updated_count, deleted_count, errors)
+/// - result_json_out: BulkWriteResult as JSON (inserted_count, updated_count, deleted_count, upserted_count, errors)
 ///
 /// # Example operations_json:
 /// ```json
 /// [
 ///   {"op": "insert", "doc": {"name": "Alice", "age": 30}},
 ///   {"op": "update_one", "query": "name is 'Bob'", "updates": {"age": 31}},
-///   {"op": "delete_many", "query": "age < 18"}
+///   {"op": "delete_many", "query": "age < 18"},
+///   {"op": "upsert_by_id", "id": "user_1", "doc": {"name": "Carol"}}
 /// ]
 /// ```
 #[no_mangle]
@@ -3921,7 +6594,7 @@ pub extern "C" fn jasonisnthappy_collection_bulk_write(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null pointer").unwrap().into_raw(),
                 };
             }
@@ -3938,7 +6611,7 @@ pub extern "C" fn jasonisnthappy_collection_bulk_write(
             Err(e) => {
                 if !error_out.is_null() {
                     *error_out = CError {
-                        code: -1,
+                        code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                         message: CString::new(format!("Invalid operations_json UTF-8: {}", e))
                             .unwrap()
                             .into_raw(),
@@ -3953,7 +6626,7 @@ pub extern "C" fn jasonisnthappy_collection_bulk_write(
             Err(e) => {
                 if !error_out.is_null() {
                     *error_out = CError {
-                        code: -1,
+                        code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                         message: CString::new(format!(
                             "Invalid operations_json format (expected JSON array): {}",
                             e
@@ -3976,7 +6649,7 @@ pub extern "C" fn jasonisnthappy_collection_bulk_write(
                 None => {
                     if !error_out.is_null() {
                         *error_out = CError {
-                            code: -1,
+                            code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                             message: CString::new(format!(
                                 "Operation at index {} is not an object",
                                 index
@@ -3994,7 +6667,7 @@ pub extern "C" fn jasonisnthappy_collection_bulk_write(
                 None => {
                     if !error_out.is_null() {
                         *error_out = CError {
-                            code: -1,
+                            code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                             message: CString::new(format!(
                                 "Operation at index {} missing 'op' field",
                                 index
@@ -4014,7 +6687,7 @@ pub extern "C" fn jasonisnthappy_collection_bulk_write(
                         None => {
                             if !error_out.is_null() {
                                 *error_out = CError {
-                                    code: -1,
+                                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                                     message: CString::new(format!(
                                         "Insert operation at index {} missing 'doc' field",
                                         index
@@ -4034,7 +6707,7 @@ pub extern "C" fn jasonisnthappy_collection_bulk_write(
                         None => {
                             if !error_out.is_null() {
                                 *error_out = CError {
-                                    code: -1,
+                                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                                     message: CString::new(format!(
                                         "update_one at index {} missing 'query' field",
                                         index
@@ -4051,7 +6724,7 @@ pub extern "C" fn jasonisnthappy_collection_bulk_write(
                         None => {
                             if !error_out.is_null() {
                                 *error_out = CError {
-                                    code: -1,
+                                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                                     message: CString::new(format!(
                                         "update_one at index {} missing 'updates' field",
                                         index
@@ -4071,7 +6744,7 @@ pub extern "C" fn jasonisnthappy_collection_bulk_write(
                         None => {
                             if !error_out.is_null() {
                                 *error_out = CError {
-                                    code: -1,
+                                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                                     message: CString::new(format!(
                                         "update_many at index {} missing 'query' field",
                                         index
@@ -4088,7 +6761,7 @@ pub extern "C" fn jasonisnthappy_collection_bulk_write(
                         None => {
                             if !error_out.is_null() {
                                 *error_out = CError {
-                                    code: -1,
+                                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                                     message: CString::new(format!(
                                         "update_many at index {} missing 'updates' field",
                                         index
@@ -4108,7 +6781,7 @@ pub extern "C" fn jasonisnthappy_collection_bulk_write(
                         None => {
                             if !error_out.is_null() {
                                 *error_out = CError {
-                                    code: -1,
+                                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                                     message: CString::new(format!(
                                         "delete_one at index {} missing 'query' field",
                                         index
@@ -4128,7 +6801,7 @@ pub extern "C" fn jasonisnthappy_collection_bulk_write(
                         None => {
                             if !error_out.is_null() {
                                 *error_out = CError {
-                                    code: -1,
+                                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                                     message: CString::new(format!(
                                         "delete_many at index {} missing 'query' field",
                                         index
@@ -4142,10 +6815,84 @@ pub extern "C" fn jasonisnthappy_collection_bulk_write(
                     };
                     bulk = bulk.delete_many(query);
                 }
+                "upsert_one" => {
+                    let query = match op_obj.get("query").and_then(|v| v.as_str()) {
+                        Some(q) => q,
+                        None => {
+                            if !error_out.is_null() {
+                                *error_out = CError {
+                                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                                    message: CString::new(format!(
+                                        "upsert_one at index {} missing 'query' field",
+                                        index
+                                    ))
+                                    .unwrap()
+                                    .into_raw(),
+                                };
+                            }
+                            return -1;
+                        }
+                    };
+                    let doc = match op_obj.get("doc") {
+                        Some(d) => d.clone(),
+                        None => {
+                            if !error_out.is_null() {
+                                *error_out = CError {
+                                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                                    message: CString::new(format!(
+                                        "upsert_one at index {} missing 'doc' field",
+                                        index
+                                    ))
+                                    .unwrap()
+                                    .into_raw(),
+                                };
+                            }
+                            return -1;
+                        }
+                    };
+                    bulk = bulk.upsert_one(query, doc);
+                }
+                "upsert_by_id" => {
+                    let id = match op_obj.get("id").and_then(|v| v.as_str()) {
+                        Some(id) => id,
+                        None => {
+                            if !error_out.is_null() {
+                                *error_out = CError {
+                                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                                    message: CString::new(format!(
+                                        "upsert_by_id at index {} missing 'id' field",
+                                        index
+                                    ))
+                                    .unwrap()
+                                    .into_raw(),
+                                };
+                            }
+                            return -1;
+                        }
+                    };
+                    let doc = match op_obj.get("doc") {
+                        Some(d) => d.clone(),
+                        None => {
+                            if !error_out.is_null() {
+                                *error_out = CError {
+                                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                                    message: CString::new(format!(
+                                        "upsert_by_id at index {} missing 'doc' field",
+                                        index
+                                    ))
+                                    .unwrap()
+                                    .into_raw(),
+                                };
+                            }
+                            return -1;
+                        }
+                    };
+                    bulk = bulk.upsert_by_id(id, doc);
+                }
                 _ => {
                     if !error_out.is_null() {
                         *error_out = CError {
-                            code: -1,
+                            code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                             message: CString::new(format!(
                                 "Unknown operation type '{}' at index {}",
                                 op_type, index
@@ -4167,6 +6914,7 @@ pub extern "C" fn jasonisnthappy_collection_bulk_write(
                     "inserted_count": result.inserted_count,
                     "updated_count": result.updated_count,
                     "deleted_count": result.deleted_count,
+                    "upserted_count": result.upserted_count,
                     "errors": result.errors.iter().map(|e| {
                         serde_json::json!({
                             "operation_index": e.operation_index,
@@ -4183,7 +6931,7 @@ pub extern "C" fn jasonisnthappy_collection_bulk_write(
                     Err(e) => {
                         if !error_out.is_null() {
                             *error_out = CError {
-                                code: -1,
+                                code: JASONISNTHAPPY_ERROR_UNKNOWN,
                                 message: CString::new(format!("Failed to serialize result: {}", e))
                                     .unwrap()
                                     .into_raw(),
@@ -4196,7 +6944,7 @@ pub extern "C" fn jasonisnthappy_collection_bulk_write(
             Err(e) => {
                 if !error_out.is_null() {
                     *error_out = CError {
-                        code: -1,
+                        code: error_code(&e),
                         message: CString::new(format!("Bulk write failed: {}", e))
                             .unwrap()
                             .into_raw(),
@@ -4217,12 +6965,21 @@ pub extern "C" fn jasonisnthappy_collection_bulk_write(
 /// # Parameters
 /// - pipeline_json: JSON array of pipeline stages, each with:
 ///   - "match": query string (filter stage)
-///   - "group_by": {field: "...", accumulators: [{type: "count|sum|avg|min|max", output_field: "...", field: "..."}]}
+///   - "group_by": {field: "..." | ["...", ...], accumulators: [{type: "count|sum|avg|min|max", output_field: "...", field: "..."}]}
+///     (an array of fields groups on the compound key; `_id` becomes an object of the key fields)
+///   - "group_by_date": like group_by plus granularity: "day|month|year"; the field is
+///     read as an RFC3339 string or epoch seconds and bucketed in UTC
 ///   - "sort": {field: "...", ascending: true|false}
 ///   - "limit": number
 ///   - "skip": number
 ///   - "project": ["field1", "field2", ...]
 ///   - "exclude": ["field1", "field2", ...]
+///   - "add_fields": {"output": expression, ...} where an expression is a field name,
+///     a literal, or one of {"field": "..."}, {"literal": ...}, {"concat": [...]},
+///     {"add": [...]}, {"sub": [...]}, {"mul": [...]}, {"div": [...]}
+///   - "out": "collection", with optional "mode": "replace|merge"; must be the last
+///     stage. Writes the results into that collection in one transaction and
+///     returns [{"out": "collection", "written": n}] instead of the results
 ///
 /// # Example pipeline_json:
 /// ```json
@@ -4247,7 +7004,7 @@ pub extern "C" fn jasonisnthappy_collection_aggregate(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null pointer").unwrap().into_raw(),
                 };
             }
@@ -4264,7 +7021,7 @@ pub extern "C" fn jasonisnthappy_collection_aggregate(
             Err(e) => {
                 if !error_out.is_null() {
                     *error_out = CError {
-                        code: -1,
+                        code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                         message: CString::new(format!("Invalid pipeline_json UTF-8: {}", e))
                             .unwrap()
                             .into_raw(),
@@ -4279,7 +7036,7 @@ pub extern "C" fn jasonisnthappy_collection_aggregate(
             Err(e) => {
                 if !error_out.is_null() {
                     *error_out = CError {
-                        code: -1,
+                        code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                         message: CString::new(format!(
                             "Invalid pipeline_json format (expected JSON array): {}",
                             e
@@ -4294,6 +7051,7 @@ pub extern "C" fn jasonisnthappy_collection_aggregate(
 
         // Build aggregation pipeline
         let mut pipeline = collection.aggregate();
+        let mut out: Option<(&str, OutMode)> = None;
 
         for (index, stage) in stages.iter().enumerate() {
             let stage_obj = match stage.as_object() {
@@ -4301,7 +7059,7 @@ pub extern "C" fn jasonisnthappy_collection_aggregate(
                 None => {
                     if !error_out.is_null() {
                         *error_out = CError {
-                            code: -1,
+                            code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                             message: CString::new(format!(
                                 "Stage at index {} is not an object",
                                 index
@@ -4318,17 +7076,19 @@ pub extern "C" fn jasonisnthappy_collection_aggregate(
             if let Some(query) = stage_obj.get("match").and_then(|v| v.as_str()) {
                 pipeline = pipeline.match_(query);
             }
-            // Group by stage
-            else if let Some(group) = stage_obj.get("group_by") {
+            // Group by stage, optionally bucketing a timestamp field
+            else if let Some((stage_name, group)) = stage_obj.get_key_value("group_by")
+                .or_else(|| stage_obj.get_key_value("group_by_date"))
+            {
                 let group_obj = match group.as_object() {
                     Some(obj) => obj,
                     None => {
                         if !error_out.is_null() {
                             *error_out = CError {
-                                code: -1,
+                                code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                                 message: CString::new(format!(
-                                    "group_by at index {} must be an object",
-                                    index
+                                    "{} at index {} must be an object",
+                                    stage_name, index
                                 ))
                                 .unwrap()
                                 .into_raw(),
@@ -4338,15 +7098,16 @@ pub extern "C" fn jasonisnthappy_collection_aggregate(
                     }
                 };
 
-                let field = match group_obj.get("field").and_then(|v| v.as_str()) {
-                    Some(f) => f,
-                    None => {
+                // A single field name, or an array of them for a compound key (group_by only)
+                let fields = match group_obj.get("field").and_then(group_key_fields) {
+                    Some(f) if f.len() == 1 || stage_name == "group_by" => f,
+                    _ => {
                         if !error_out.is_null() {
                             *error_out = CError {
-                                code: -1,
+                                code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                                 message: CString::new(format!(
-                                    "group_by at index {} missing 'field'",
-                                    index
+                                    "{} at index {} missing or invalid 'field'",
+                                    stage_name, index
                                 ))
                                 .unwrap()
                                 .into_raw(),
@@ -4356,7 +7117,31 @@ pub extern "C" fn jasonisnthappy_collection_aggregate(
                     }
                 };
 
-                pipeline = pipeline.group_by(field);
+                if stage_name == "group_by_date" {
+                    let granularity = match group_obj.get("granularity")
+                        .and_then(|v| v.as_str())
+                        .and_then(DateGranularity::parse)
+                    {
+                        Some(g) => g,
+                        None => {
+                            if !error_out.is_null() {
+                                *error_out = CError {
+                                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                                    message: CString::new(format!(
+                                        "group_by_date at index {} needs 'granularity' of day, month or year",
+                                        index
+                                    ))
+                                    .unwrap()
+                                    .into_raw(),
+                                };
+                            }
+                            return -1;
+                        }
+                    };
+                    pipeline = pipeline.group_by_date(fields[0], granularity);
+                } else {
+                    pipeline = pipeline.group_by_multi(&fields);
+                }
 
                 // Process accumulators
                 if let Some(accumulators) = group_obj.get("accumulators").and_then(|v| v.as_array()) {
@@ -4412,7 +7197,7 @@ pub extern "C" fn jasonisnthappy_collection_aggregate(
                     None => {
                         if !error_out.is_null() {
                             *error_out = CError {
-                                code: -1,
+                                code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                                 message: CString::new(format!(
                                     "sort at index {} must be an object",
                                     index
@@ -4430,7 +7215,7 @@ pub extern "C" fn jasonisnthappy_collection_aggregate(
                     None => {
                         if !error_out.is_null() {
                             *error_out = CError {
-                                code: -1,
+                                code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                                 message: CString::new(format!(
                                     "sort at index {} missing 'field'",
                                     index
@@ -4471,10 +7256,58 @@ pub extern "C" fn jasonisnthappy_collection_aggregate(
                     .collect();
                 let field_refs: Vec<&str> = fields.iter().map(|s| s.as_str()).collect();
                 pipeline = pipeline.exclude(&field_refs);
+            }
+            // Add fields stage
+            else if let Some(add_fields) = stage_obj.get("add_fields").and_then(|v| v.as_object()) {
+                let mut fields = Vec::with_capacity(add_fields.len());
+                for (name, expr_json) in add_fields {
+                    match FieldExpr::from_json(expr_json) {
+                        Ok(expr) => fields.push((name.as_str(), expr)),
+                        Err(e) => {
+                            if !error_out.is_null() {
+                                *error_out = CError {
+                                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                                    message: CString::new(format!(
+                                        "Invalid expression for '{}' at stage {}: {}",
+                                        name, index, e
+                                    ))
+                                    .unwrap()
+                                    .into_raw(),
+                                };
+                            }
+                            return -1;
+                        }
+                    }
+                }
+                pipeline = pipeline.add_fields(&fields);
+            }
+            // Out stage
+            else if let Some(target) = stage_obj.get("out").and_then(|v| v.as_str()) {
+                let mode = match stage_obj.get("mode") {
+                    None => Some(OutMode::Replace),
+                    Some(mode) => mode.as_str().and_then(OutMode::parse),
+                };
+                let message = if index + 1 != stages.len() {
+                    Some(format!("out at index {} must be the last stage", index))
+                } else if mode.is_none() {
+                    Some(format!("out at index {} has invalid 'mode' (expected replace or merge)", index))
+                } else {
+                    None
+                };
+                if let Some(message) = message {
+                    if !error_out.is_null() {
+                        *error_out = CError {
+                            code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                            message: CString::new(message).unwrap().into_raw(),
+                        };
+                    }
+                    return -1;
+                }
+                out = mode.map(|mode| (target, mode));
             } else {
                 if !error_out.is_null() {
                     *error_out = CError {
-                        code: -1,
+                        code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                         message: CString::new(format!(
                             "Unknown or invalid stage at index {}",
                             index
@@ -4487,8 +7320,14 @@ pub extern "C" fn jasonisnthappy_collection_aggregate(
             }
         }
 
-        // Execute the pipeline
-        match pipeline.execute() {
+        // Execute the pipeline, writing the results out if asked to
+        let results = match out {
+            Some((target, mode)) => pipeline
+                .out_with_mode(target, mode)
+                .map(|written| vec![serde_json::json!({"out": target, "written": written})]),
+            None => pipeline.execute(),
+        };
+        match results {
             Ok(results) => {
                 match serde_json::to_string(&results) {
                     Ok(json_str) => {
@@ -4498,7 +7337,7 @@ pub extern "C" fn jasonisnthappy_collection_aggregate(
                     Err(e) => {
                         if !error_out.is_null() {
                             *error_out = CError {
-                                code: -1,
+                                code: JASONISNTHAPPY_ERROR_UNKNOWN,
                                 message: CString::new(format!("Failed to serialize results: {}", e))
                                     .unwrap()
                                     .into_raw(),
@@ -4511,7 +7350,7 @@ pub extern "C" fn jasonisnthappy_collection_aggregate(
             Err(e) => {
                 if !error_out.is_null() {
                     *error_out = CError {
-                        code: -1,
+                        code: error_code(&e),
                         message: CString::new(format!("Aggregation failed: {}", e))
                             .unwrap()
                             .into_raw(),
@@ -4523,10 +7362,43 @@ pub extern "C" fn jasonisnthappy_collection_aggregate(
     }
 }
 
+/// Field names for a group stage: a string, or a non-empty array of strings
+fn group_key_fields(value: &serde_json::Value) -> Option<Vec<&str>> {
+    match value {
+        serde_json::Value::String(field) => Some(vec![field.as_str()]),
+        serde_json::Value::Array(items) if !items.is_empty() => {
+            items.iter().map(|v| v.as_str()).collect()
+        }
+        _ => None,
+    }
+}
+
 // ============================================================================
 // Watch / Change Streams
 // ============================================================================
 
+/// Watch inserts, in jasonisnthappy_collection_watch_start_with_operations
+pub const JASONISNTHAPPY_WATCH_INSERT: u32 = 1;
+/// Watch updates, in jasonisnthappy_collection_watch_start_with_operations
+pub const JASONISNTHAPPY_WATCH_UPDATE: u32 = 2;
+/// Watch deletes, in jasonisnthappy_collection_watch_start_with_operations
+pub const JASONISNTHAPPY_WATCH_DELETE: u32 = 4;
+
+/// Operations selected by a mask of JASONISNTHAPPY_WATCH_* bits, `None` for
+/// an unknown bit
+fn watch_operations_from_c(mask: u32) -> Option<Vec<ChangeOperation>> {
+    let all = JASONISNTHAPPY_WATCH_INSERT | JASONISNTHAPPY_WATCH_UPDATE | JASONISNTHAPPY_WATCH_DELETE;
+    if mask & !all != 0 {
+        return None;
+    }
+    let bits = [
+        (JASONISNTHAPPY_WATCH_INSERT, ChangeOperation::Insert),
+        (JASONISNTHAPPY_WATCH_UPDATE, ChangeOperation::Update),
+        (JASONISNTHAPPY_WATCH_DELETE, ChangeOperation::Delete),
+    ];
+    Some(bits.iter().filter(|(bit, _)| mask & bit != 0).map(|(_, op)| *op).collect())
+}
+
 /// Start watching a collection for changes
 ///
 /// Creates a background thread that monitors changes to the collection and calls
@@ -4535,6 +7407,7 @@ pub extern "C" fn jasonisnthappy_collection_aggregate(
 /// # Parameters
 /// - coll: Collection to watch
 /// - filter: Optional query filter (NULL = watch all changes)
+/// - full_document_before_change: Whether to pass the pre-change document to the callback
 /// - callback: Function to call for each change event
 /// - user_data: Optional user context pointer passed to callback
 /// - handle_out: Output pointer for the watch handle (use to stop watching)
@@ -4549,6 +7422,46 @@ pub extern "C" fn jasonisnthappy_collection_aggregate(
 pub extern "C" fn jasonisnthappy_collection_watch_start(
     coll: *mut CCollection,
     filter: *const c_char,
+    full_document_before_change: bool,
+    callback: WatchCallback,
+    user_data: *mut std::os::raw::c_void,
+    handle_out: *mut *mut CWatchHandle,
+    error_out: *mut CError,
+) -> i32 {
+    watch_start(coll, filter, 0, full_document_before_change, callback, user_data, handle_out, error_out)
+}
+
+/// Start watching a collection for changes of some operations only
+///
+/// Same as jasonisnthappy_collection_watch_start, except that only events
+/// for the operations in `operations` are passed to the callback. It is a
+/// mask of JASONISNTHAPPY_WATCH_INSERT, JASONISNTHAPPY_WATCH_UPDATE and
+/// JASONISNTHAPPY_WATCH_DELETE; 0 watches all operations. With a filter as
+/// well, an event has to match both.
+///
+/// # Returns
+/// 0 on success, -1 on error
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn jasonisnthappy_collection_watch_start_with_operations(
+    coll: *mut CCollection,
+    filter: *const c_char,
+    operations: u32,
+    full_document_before_change: bool,
+    callback: WatchCallback,
+    user_data: *mut std::os::raw::c_void,
+    handle_out: *mut *mut CWatchHandle,
+    error_out: *mut CError,
+) -> i32 {
+    watch_start(coll, filter, operations, full_document_before_change, callback, user_data, handle_out, error_out)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn watch_start(
+    coll: *mut CCollection,
+    filter: *const c_char,
+    operations: u32,
+    full_document_before_change: bool,
     callback: WatchCallback,
     user_data: *mut std::os::raw::c_void,
     handle_out: *mut *mut CWatchHandle,
@@ -4558,7 +7471,7 @@ pub extern "C" fn jasonisnthappy_collection_watch_start(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null pointer").unwrap().into_raw(),
                 };
             }
@@ -4581,7 +7494,7 @@ pub extern "C" fn jasonisnthappy_collection_watch_start(
                 Err(e) => {
                     if !error_out.is_null() {
                         *error_out = CError {
-                            code: -1,
+                            code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                             message: CString::new(format!("Invalid filter UTF-8: {}", e))
                                 .unwrap()
                                 .into_raw(),
@@ -4592,6 +7505,27 @@ pub extern "C" fn jasonisnthappy_collection_watch_start(
             }
         }
 
+        if operations != 0 {
+            match watch_operations_from_c(operations) {
+                Some(ops) => watch_builder = watch_builder.operations(&ops),
+                None => {
+                    if !error_out.is_null() {
+                        *error_out = CError {
+                            code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                            message: CString::new(format!("Invalid watch operations: {}", operations))
+                                .unwrap()
+                                .into_raw(),
+                        };
+                    }
+                    return -1;
+                }
+            }
+        }
+
+        if full_document_before_change {
+            watch_builder = watch_builder.with_full_document_before_change();
+        }
+
         // Subscribe to changes
         match watch_builder.subscribe() {
             Ok((watch_handle, receiver)) => {
@@ -4627,17 +7561,13 @@ pub extern "C" fn jasonisnthappy_collection_watch_start(
                                     Err(_) => continue,
                                 };
 
-                                let doc_json_cstr = if let Some(doc) = &event.document {
-                                    match serde_json::to_string(doc) {
-                                        Ok(json_str) => match CString::new(json_str) {
-                                            Ok(s) => Some(s),
-                                            Err(_) => None,
-                                        },
-                                        Err(_) => None,
-                                    }
-                                } else {
-                                    None
+                                let to_cstring = |doc: &Option<serde_json::Value>| {
+                                    doc.as_ref()
+                                        .and_then(|d| serde_json::to_string(d).ok())
+                                        .and_then(|json_str| CString::new(json_str).ok())
                                 };
+                                let doc_json_cstr = to_cstring(&event.document);
+                                let old_doc_json_cstr = to_cstring(&event.old_document);
 
                                 // Call the callback
                                 (context.callback)(
@@ -4645,6 +7575,7 @@ pub extern "C" fn jasonisnthappy_collection_watch_start(
                                     operation_cstr.as_ptr(),
                                     doc_id_cstr.as_ptr(),
                                     doc_json_cstr.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+                                    old_doc_json_cstr.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
                                     context.user_data_addr as *mut std::os::raw::c_void,
                                 );
                             }
@@ -4673,7 +7604,7 @@ pub extern "C" fn jasonisnthappy_collection_watch_start(
             Err(e) => {
                 if !error_out.is_null() {
                     *error_out = CError {
-                        code: -1,
+                        code: error_code(&e),
                         message: CString::new(format!("Failed to start watching: {}", e))
                             .unwrap()
                             .into_raw(),
@@ -4728,7 +7659,7 @@ pub extern "C" fn jasonisnthappy_checkpoint(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -4754,6 +7685,90 @@ pub extern "C" fn jasonisnthappy_checkpoint(
     }
 }
 
+/// Checkpoint the whole WAL and truncate the WAL file to zero bytes
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_checkpoint_truncate(
+    db: *mut CDatabase,
+    error_out: *mut CError,
+) -> i32 {
+    if db.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null database pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let db_ref = unsafe { &(*db).inner };
+
+    match db_ref.checkpoint_truncate() {
+        Ok(_) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
+}
+
+/// Fsync the WAL and write out cached pages without checkpointing
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_sync(
+    db: *mut CDatabase,
+    error_out: *mut CError,
+) -> i32 {
+    if db.is_null() {
+        if !error_out.is_null() {
+            unsafe {
+                *error_out = CError {
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
+                    message: CString::new("Null database pointer").unwrap().into_raw(),
+                };
+            }
+        }
+        return -1;
+    }
+
+    let db_ref = unsafe { &(*db).inner };
+
+    match db_ref.sync() {
+        Ok(_) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::success(); }
+            }
+            0
+        }
+        Err(e) => {
+            if !error_out.is_null() {
+                unsafe { *error_out = CError::from_error(e); }
+            }
+            -1
+        }
+    }
+}
+
+/// 1 if every commit has been fsynced and no cached pages await writing,
+/// 0 if not, -1 for a null database pointer
+#[no_mangle]
+pub extern "C" fn jasonisnthappy_is_fully_synced(db: *mut CDatabase) -> i32 {
+    if db.is_null() {
+        return -1;
+    }
+
+    let db_ref = unsafe { &(*db).inner };
+    db_ref.is_fully_synced() as i32
+}
+
 #[no_mangle]
 pub extern "C" fn jasonisnthappy_backup(
     db: *mut CDatabase,
@@ -4764,7 +7779,7 @@ pub extern "C" fn jasonisnthappy_backup(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -4811,7 +7826,7 @@ pub extern "C" fn jasonisnthappy_verify_backup(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -4867,7 +7882,7 @@ pub extern "C" fn jasonisnthappy_garbage_collect(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -4914,7 +7929,7 @@ pub extern "C" fn jasonisnthappy_metrics(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -4947,7 +7962,7 @@ pub extern "C" fn jasonisnthappy_frame_count(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -4982,7 +7997,7 @@ pub extern "C" fn jasonisnthappy_start_web_server(
         if !error_out.is_null() {
             unsafe {
                 *error_out = CError {
-                    code: -1,
+                    code: JASONISNTHAPPY_ERROR_INVALID_ARGUMENT,
                     message: CString::new("Null database pointer").unwrap().into_raw(),
                 };
             }
@@ -5003,7 +8018,7 @@ pub extern "C" fn jasonisnthappy_start_web_server(
     let db_ref = unsafe { &(*db).inner };
     let db_arc = Arc::clone(db_ref);
 
-    match WebServer::start(db_arc, &addr_str) {
+    match WebServer::start(db_arc, &addr_str, WebServerOptions::default()) {
         Ok(server) => {
             if !error_out.is_null() {
                 unsafe { *error_out = CError::success(); }
@@ -5014,7 +8029,7 @@ pub extern "C" fn jasonisnthappy_start_web_server(
             if !error_out.is_null() {
                 unsafe {
                     *error_out = CError {
-                        code: -1,
+                        code: JASONISNTHAPPY_ERROR_UNKNOWN,
                         message: CString::new(format!("Failed to start web server: {}", e))
                             .unwrap().into_raw(),
                     };
@@ -34,7 +34,7 @@ func main() {
 
 	// Start watching for changes
 	fmt.Println("Starting watch on 'events' collection...")
-	handle, err := coll.WatchStart("", func(collection, operation, docID, docJSON string) {
+	handle, err := coll.WatchStart("", func(collection, operation, docID, docJSON, oldDocJSON string) {
 		fmt.Printf("[WATCH EVENT] Collection: %s, Operation: %s, DocID: %s\n", collection, operation, docID)
 		fmt.Printf("              Document: %s\n", docJSON)
 	})
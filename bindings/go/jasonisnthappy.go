@@ -17,7 +17,7 @@ package jasonisnthappy
 #include "jasonisnthappy.h"
 
 // Forward declaration for the watch callback bridge
-extern void goWatchCallbackBridge(char *collection, char *operation, char *doc_id, char *doc_json, void *user_data);
+extern void goWatchCallbackBridge(char *collection, char *operation, char *doc_id, char *doc_json, char *old_doc_json, void *user_data);
 */
 import "C"
 import (
@@ -43,11 +43,12 @@ var (
 	nextCallbackID   uintptr = 1
 )
 
-// WatchCallback is called when a change event occurs
-type WatchCallback func(collection, operation, docID, docJSON string)
+// WatchCallback is called when a change event occurs.
+// oldDocJSON is only set for updates and deletes when watching with WatchStartWithBeforeChange.
+type WatchCallback func(collection, operation, docID, docJSON, oldDocJSON string)
 
 //export goWatchCallbackBridge
-func goWatchCallbackBridge(collection *C.char, operation *C.char, docID *C.char, docJSON *C.char, userData unsafe.Pointer) {
+func goWatchCallbackBridge(collection *C.char, operation *C.char, docID *C.char, docJSON *C.char, oldDocJSON *C.char, userData unsafe.Pointer) {
 	callbackID := uintptr(userData)
 
 	watchCallbacksMu.RLock()
@@ -63,6 +64,7 @@ func goWatchCallbackBridge(collection *C.char, operation *C.char, docID *C.char,
 		C.GoString(operation),
 		C.GoString(docID),
 		C.GoString(docJSON),
+		C.GoString(oldDocJSON),
 	)
 }
 
@@ -93,7 +95,55 @@ type DatabaseOptions struct {
 	MaxBulkOperations       uint   `json:"max_bulk_operations"`
 	MaxDocumentSize         uint   `json:"max_document_size"`
 	MaxRequestBodySize      uint   `json:"max_request_body_size"`
-}
+	DocumentMetadata        bool   `json:"document_metadata"`
+	ScanThreads             uint   `json:"scan_threads"`
+	EnableIDBloomFilter     bool   `json:"enable_id_bloom_filter"`
+	PageSize                uint   `json:"page_size"`
+	MaxWALBytes             uint64 `json:"max_wal_bytes"`
+	// CollectAllValidationErrors lists every schema violation in validation
+	// errors instead of only the first
+	CollectAllValidationErrors bool `json:"collect_all_validation_errors"`
+	// PageChecksums checksums every page of a new database file; an existing
+	// file keeps its setting
+	PageChecksums bool `json:"page_checksums"`
+	// LockTimeoutMs is how long Open waits for another process to release the
+	// database; 0 fails straight away
+	LockTimeoutMs uint64 `json:"lock_timeout_ms"`
+	// MaxScanDocuments is the most documents a collection scan may examine
+	// before the query fails; 0 for no limit
+	MaxScanDocuments uint `json:"max_scan_documents"`
+	// MaxResultDocuments is the most documents a query may collect as
+	// results before it fails; 0 for no limit
+	MaxResultDocuments uint `json:"max_result_documents"`
+	// SlowQueryThresholdMs sends queries running at least this many
+	// milliseconds to the slow query log; 0 disables it
+	SlowQueryThresholdMs uint64 `json:"slow_query_threshold_ms"`
+	// SlowQueryLogSize is the number of slow queries kept, oldest dropped first
+	SlowQueryLogSize uint `json:"slow_query_log_size"`
+	// Durability decides when commits fsync: DurabilityFull, DurabilityNormal
+	// or DurabilityBatched
+	Durability uint32 `json:"durability"`
+	// DurabilityIntervalMs is the time between fsyncs with DurabilityBatched
+	DurabilityIntervalMs uint64 `json:"durability_interval_ms"`
+	// StrictQueryTypes never matches a number against a string in query
+	// comparisons; when false, strings holding numbers compare as those numbers
+	StrictQueryTypes bool `json:"strict_query_types"`
+	// QueryCacheSize is the number of find and aggregation results cached
+	// until a write to their collection; 0 disables the cache
+	QueryCacheSize uint `json:"query_cache_size"`
+}
+
+// Durability levels for DatabaseOptions.Durability
+const (
+	// DurabilityFull fsyncs the WAL and database file before every commit returns
+	DurabilityFull uint32 = 0
+	// DurabilityNormal fsyncs the WAL on every commit and the database file
+	// only on checkpoint and close
+	DurabilityNormal uint32 = 1
+	// DurabilityBatched fsyncs the WAL on every commit and the database file
+	// at most every DurabilityIntervalMs
+	DurabilityBatched uint32 = 2
+)
 
 // DefaultDatabaseOptions returns the default database options
 func DefaultDatabaseOptions() DatabaseOptions {
@@ -106,6 +156,22 @@ func DefaultDatabaseOptions() DatabaseOptions {
 		MaxBulkOperations:       uint(cOpts.max_bulk_operations),
 		MaxDocumentSize:         uint(cOpts.max_document_size),
 		MaxRequestBodySize:      uint(cOpts.max_request_body_size),
+		DocumentMetadata:        bool(cOpts.document_metadata),
+		ScanThreads:             uint(cOpts.scan_threads),
+		EnableIDBloomFilter:     bool(cOpts.enable_id_bloom_filter),
+		PageSize:                uint(cOpts.page_size),
+		MaxWALBytes:             uint64(cOpts.max_wal_bytes),
+		CollectAllValidationErrors: bool(cOpts.collect_all_validation_errors),
+		PageChecksums:              bool(cOpts.page_checksums),
+		LockTimeoutMs:              uint64(cOpts.lock_timeout_ms),
+		MaxScanDocuments:           uint(cOpts.max_scan_documents),
+		MaxResultDocuments:         uint(cOpts.max_result_documents),
+		SlowQueryThresholdMs:       uint64(cOpts.slow_query_threshold_ms),
+		SlowQueryLogSize:           uint(cOpts.slow_query_log_size),
+		Durability:                 uint32(cOpts.durability),
+		DurabilityIntervalMs:       uint64(cOpts.durability_interval_ms),
+		StrictQueryTypes:           bool(cOpts.strict_query_types),
+		QueryCacheSize:             uint(cOpts.query_cache_size),
 	}
 }
 
@@ -132,6 +198,7 @@ func OpenWithOptions(path string, opts DatabaseOptions) (*Database, error) {
 	defer C.free(unsafe.Pointer(cPath))
 
 	cOpts := C.CDatabaseOptions{
+		struct_size:              C.uint32_t(C.sizeof_CDatabaseOptions),
 		cache_size:               C.uintptr_t(opts.CacheSize),
 		auto_checkpoint_threshold: C.ulonglong(opts.AutoCheckpointThreshold),
 		file_permissions:         C.uint(opts.FilePermissions),
@@ -139,6 +206,22 @@ func OpenWithOptions(path string, opts DatabaseOptions) (*Database, error) {
 		max_bulk_operations:      C.uintptr_t(opts.MaxBulkOperations),
 		max_document_size:        C.uintptr_t(opts.MaxDocumentSize),
 		max_request_body_size:    C.uintptr_t(opts.MaxRequestBodySize),
+		document_metadata:        C.bool(opts.DocumentMetadata),
+		scan_threads:             C.uintptr_t(opts.ScanThreads),
+		enable_id_bloom_filter:   C.bool(opts.EnableIDBloomFilter),
+		page_size:                C.uintptr_t(opts.PageSize),
+		max_wal_bytes:            C.ulonglong(opts.MaxWALBytes),
+		collect_all_validation_errors: C.bool(opts.CollectAllValidationErrors),
+		page_checksums:           C.bool(opts.PageChecksums),
+		lock_timeout_ms:          C.ulonglong(opts.LockTimeoutMs),
+		max_scan_documents:       C.uintptr_t(opts.MaxScanDocuments),
+		max_result_documents:     C.uintptr_t(opts.MaxResultDocuments),
+		slow_query_threshold_ms:  C.ulonglong(opts.SlowQueryThresholdMs),
+		slow_query_log_size:      C.uintptr_t(opts.SlowQueryLogSize),
+		durability:               C.uint32_t(opts.Durability),
+		durability_interval_ms:   C.ulonglong(opts.DurabilityIntervalMs),
+		strict_query_types:       C.bool(opts.StrictQueryTypes),
+		query_cache_size:         C.uintptr_t(opts.QueryCacheSize),
 	}
 
 	var cErr C.CError
@@ -381,9 +464,12 @@ func cErrorToGoError(cErr *C.CError) error {
 
 // TransactionConfig holds transaction retry configuration
 type TransactionConfig struct {
-	MaxRetries          uint   `json:"max_retries"`
-	RetryBackoffBaseMs  uint64 `json:"retry_backoff_base_ms"`
-	MaxRetryBackoffMs   uint64 `json:"max_retry_backoff_ms"`
+	MaxRetries               uint   `json:"max_retries"`
+	RetryBackoffBaseMs       uint64 `json:"retry_backoff_base_ms"`
+	MaxRetryBackoffMs        uint64 `json:"max_retry_backoff_ms"`
+	MaxTransactionDurationMs uint64 `json:"max_transaction_duration_ms"` // 0 means no limit
+	MinRetryBackoffMs        uint64 `json:"min_retry_backoff_ms"`
+	Jitter                   bool   `json:"jitter"` // randomize each retry wait between the minimum and the backoff
 }
 
 // SetTransactionConfig sets the transaction configuration
@@ -393,9 +479,13 @@ func (d *Database) SetTransactionConfig(config TransactionConfig) error {
 	}
 
 	cConfig := C.CTransactionConfig{
+		struct_size:          C.uint32_t(C.sizeof_CTransactionConfig),
 		max_retries:          C.ulong(config.MaxRetries),
 		retry_backoff_base_ms: C.ulonglong(config.RetryBackoffBaseMs),
 		max_retry_backoff_ms:  C.ulonglong(config.MaxRetryBackoffMs),
+		max_transaction_duration_ms: C.ulonglong(config.MaxTransactionDurationMs),
+		min_retry_backoff_ms:  C.ulonglong(config.MinRetryBackoffMs),
+		jitter:                C.bool(config.Jitter),
 	}
 
 	var cErr C.CError
@@ -416,7 +506,7 @@ func (d *Database) GetTransactionConfig() (*TransactionConfig, error) {
 		return nil, &Error{Code: -1, Message: "Database is closed"}
 	}
 
-	var cConfig C.CTransactionConfig
+	cConfig := C.CTransactionConfig{struct_size: C.uint32_t(C.sizeof_CTransactionConfig)}
 	var cErr C.CError
 	result := C.jasonisnthappy_get_transaction_config(d.db, &cConfig, &cErr)
 
@@ -430,6 +520,9 @@ func (d *Database) GetTransactionConfig() (*TransactionConfig, error) {
 		MaxRetries:         uint(cConfig.max_retries),
 		RetryBackoffBaseMs: uint64(cConfig.retry_backoff_base_ms),
 		MaxRetryBackoffMs:  uint64(cConfig.max_retry_backoff_ms),
+		MaxTransactionDurationMs: uint64(cConfig.max_transaction_duration_ms),
+		MinRetryBackoffMs:  uint64(cConfig.min_retry_backoff_ms),
+		Jitter:             bool(cConfig.jitter),
 	}, nil
 }
 
@@ -616,12 +709,114 @@ func (d *Database) DatabaseInfo() (map[string]interface{}, error) {
 	return info, nil
 }
 
+// IntegrityProblem is a single finding of an integrity check
+type IntegrityProblem struct {
+	Collection *string `json:"collection"`
+	Page       *uint64 `json:"page"`
+	Message    string  `json:"message"`
+}
+
+// IntegrityReport is the result of CheckIntegrity
+type IntegrityReport struct {
+	CollectionsChecked int    `json:"collections_checked"`
+	DocumentsChecked   uint64 `json:"documents_checked"`
+	IndexesChecked     int    `json:"indexes_checked"`
+	TotalPages         uint64 `json:"total_pages"`
+	FreePages          uint64 `json:"free_pages"`
+	// OrphanedPages are wasted space that Vacuum reclaims, not corruption
+	OrphanedPages uint64 `json:"orphaned_pages"`
+	// PagesVerified is 0 for databases without page checksums
+	PagesVerified uint64             `json:"pages_verified"`
+	Problems      []IntegrityProblem `json:"problems"`
+	Warnings      []IntegrityProblem `json:"warnings"`
+}
+
+// CheckIntegrity checks the database for corruption without modifying it.
+// The database is consistent when the report has no Problems.
+func (d *Database) CheckIntegrity() (*IntegrityReport, error) {
+	if d.db == nil {
+		return nil, &Error{Code: -1, Message: "Database is closed"}
+	}
+
+	var cJSON *C.char
+	var cErr C.CError
+	result := C.jasonisnthappy_check_integrity(d.db, &cJSON, &cErr)
+
+	if result != 0 {
+		err := cErrorToGoError(&cErr)
+		C.jasonisnthappy_free_error(cErr)
+		return nil, err
+	}
+
+	jsonStr := C.GoString(cJSON)
+	C.jasonisnthappy_free_string(cJSON)
+
+	var report IntegrityReport
+	if err := json.Unmarshal([]byte(jsonStr), &report); err != nil {
+		return nil, err
+	}
+
+	return &report, nil
+}
+
+// CacheWarmup reports the pages WarmCache read into the page cache.
+// CacheFull is set when warming stopped early because the cache was full.
+type CacheWarmup struct {
+	TreePages     uint64 `json:"tree_pages"`
+	DocumentPages uint64 `json:"document_pages"`
+	CacheFull     bool   `json:"cache_full"`
+}
+
+// WarmCache reads a collection's B-trees, and with documents its document
+// pages, into the page cache so the first queries after opening are fast.
+// An empty collection name warms every collection.
+func (d *Database) WarmCache(collectionName string, documents bool) (*CacheWarmup, error) {
+	if d.db == nil {
+		return nil, &Error{Code: -1, Message: "Database is closed"}
+	}
+
+	var cName *C.char
+	if collectionName != "" {
+		cName = C.CString(collectionName)
+		defer C.free(unsafe.Pointer(cName))
+	}
+
+	var cJSON *C.char
+	var cErr C.CError
+	result := C.jasonisnthappy_warm_cache(d.db, cName, C.bool(documents), &cJSON, &cErr)
+
+	if result != 0 {
+		err := cErrorToGoError(&cErr)
+		C.jasonisnthappy_free_error(cErr)
+		return nil, err
+	}
+
+	jsonStr := C.GoString(cJSON)
+	C.jasonisnthappy_free_string(cJSON)
+
+	var warmup CacheWarmup
+	if err := json.Unmarshal([]byte(jsonStr), &warmup); err != nil {
+		return nil, err
+	}
+
+	return &warmup, nil
+}
+
 // IndexInfo represents index metadata
 type IndexInfo struct {
-	Name      string   `json:"name"`
-	Fields    []string `json:"fields"`
-	Unique    bool     `json:"unique"`
-	BTreeRoot uint64   `json:"btree_root"`
+	Name        string            `json:"name"`
+	Fields      []string          `json:"fields"`
+	Unique      bool              `json:"unique"`
+	BTreeRoot   uint64            `json:"btree_root"`
+	TextOptions *TextIndexOptions `json:"text_options"`
+}
+
+// TextIndexOptions describes how a text index tokenizes documents and queries
+type TextIndexOptions struct {
+	Stopwords []string `json:"stopwords"`
+	Stemming  *string  `json:"stemming"`
+	K1        float32  `json:"k1"`
+	B         float32  `json:"b"`
 }
 
 // ListIndexes returns all indexes for a collection
@@ -814,6 +1009,45 @@ func (d *Database) SetSchema(collectionName string, schema map[string]interface{
 	return nil
 }
 
+// SetJSONSchema sets a collection's schema from a standard JSON Schema document.
+// It returns a warning for each keyword that isn't supported and was ignored.
+func (d *Database) SetJSONSchema(collectionName string, schema map[string]interface{}) ([]string, error) {
+	if d.db == nil {
+		return nil, &Error{Code: -1, Message: "Database is closed"}
+	}
+
+	schemaBytes, err := json.Marshal(schema)
+	if err != nil {
+		return nil, err
+	}
+
+	cCollName := C.CString(collectionName)
+	defer C.free(unsafe.Pointer(cCollName))
+
+	cSchema := C.CString(string(schemaBytes))
+	defer C.free(unsafe.Pointer(cSchema))
+
+	var cWarnings *C.char
+	var cErr C.CError
+	result := C.jasonisnthappy_set_json_schema(d.db, cCollName, cSchema, &cWarnings, &cErr)
+
+	if result != 0 {
+		err := cErrorToGoError(&cErr)
+		C.jasonisnthappy_free_error(cErr)
+		return nil, err
+	}
+
+	warningsStr := C.GoString(cWarnings)
+	C.jasonisnthappy_free_string(cWarnings)
+
+	var warnings []string
+	if err := json.Unmarshal([]byte(warningsStr), &warnings); err != nil {
+		return nil, err
+	}
+
+	return warnings, nil
+}
+
 // GetSchema gets the JSON schema for a collection
 func (d *Database) GetSchema(collectionName string) (map[string]interface{}, error) {
 	if d.db == nil {
@@ -891,6 +1125,24 @@ func (d *Database) Checkpoint() error {
 	return nil
 }
 
+// CheckpointTruncate checkpoints the whole WAL and truncates the WAL file to zero bytes
+func (d *Database) CheckpointTruncate() error {
+	if d.db == nil {
+		return &Error{Code: -1, Message: "Database is closed"}
+	}
+
+	var cErr C.CError
+	result := C.jasonisnthappy_checkpoint_truncate(d.db, &cErr)
+
+	if result != 0 {
+		err := cErrorToGoError(&cErr)
+		C.jasonisnthappy_free_error(cErr)
+		return err
+	}
+
+	return nil
+}
+
 // Backup creates a backup of the database
 func (d *Database) Backup(destPath string) error {
 	if d.db == nil {
@@ -1549,6 +1801,82 @@ func (c *Collection) DeleteOne(filter string) (bool, error) {
 	return bool(deleted), nil
 }
 
+// FindOneAndUpdate atomically updates the first document matching a filter and
+// decodes it into result, as it is after the update if returnNew is true.
+// sortField ("" = no sort) picks which match is first.
+// Returns false if no document matches.
+func (c *Collection) FindOneAndUpdate(filter string, update interface{}, returnNew bool, sortField string, sortAsc bool, result interface{}) (bool, error) {
+	if c.coll == nil {
+		return false, &Error{Code: -1, Message: "Collection is closed"}
+	}
+
+	updateJSON, err := json.Marshal(update)
+	if err != nil {
+		return false, err
+	}
+
+	cFilter := C.CString(filter)
+	defer C.free(unsafe.Pointer(cFilter))
+	cUpdate := C.CString(string(updateJSON))
+	defer C.free(unsafe.Pointer(cUpdate))
+
+	var cSortField *C.char
+	if sortField != "" {
+		cSortField = C.CString(sortField)
+		defer C.free(unsafe.Pointer(cSortField))
+	}
+
+	var cJSON *C.char
+	var cErr C.CError
+	status := C.jasonisnthappy_collection_find_one_and_update(c.coll, cFilter, cUpdate, cSortField, C.bool(sortAsc), C.bool(returnNew), &cJSON, &cErr)
+
+	return findAndModifyResult(status, cJSON, &cErr, result)
+}
+
+// FindOneAndDelete atomically deletes the first document matching a filter and
+// decodes it into result. sortField ("" = no sort) picks which match is first.
+// Returns false if no document matches.
+func (c *Collection) FindOneAndDelete(filter string, sortField string, sortAsc bool, result interface{}) (bool, error) {
+	if c.coll == nil {
+		return false, &Error{Code: -1, Message: "Collection is closed"}
+	}
+
+	cFilter := C.CString(filter)
+	defer C.free(unsafe.Pointer(cFilter))
+
+	var cSortField *C.char
+	if sortField != "" {
+		cSortField = C.CString(sortField)
+		defer C.free(unsafe.Pointer(cSortField))
+	}
+
+	var cJSON *C.char
+	var cErr C.CError
+	status := C.jasonisnthappy_collection_find_one_and_delete(c.coll, cFilter, cSortField, C.bool(sortAsc), &cJSON, &cErr)
+
+	return findAndModifyResult(status, cJSON, &cErr, result)
+}
+
+func findAndModifyResult(status C.int32_t, cJSON *C.char, cErr *C.CError, result interface{}) (bool, error) {
+	if status == 1 {
+		return false, nil
+	}
+	if status != 0 {
+		err := cErrorToGoError(cErr)
+		C.jasonisnthappy_free_error(*cErr)
+		return false, err
+	}
+
+	jsonStr := C.GoString(cJSON)
+	C.jasonisnthappy_free_string(cJSON)
+
+	if err := json.Unmarshal([]byte(jsonStr), result); err != nil {
+		return false, err
+	}
+
+	return true, nil
+}
+
 // ====================
 // Upsert Operations
 // ====================
@@ -1629,6 +1957,70 @@ func (c *Collection) Upsert(filter string, doc interface{}) (*UpsertResult, erro
 	return &result, nil
 }
 
+// UpsertMergeByID upserts a document by ID, merging doc into an existing
+// document (nested objects included) instead of setting its top-level fields wholesale
+func (c *Collection) UpsertMergeByID(id string, doc interface{}) (*UpsertResult, error) {
+	if c.coll == nil {
+		return nil, &Error{Code: -1, Message: "Collection is closed"}
+	}
+
+	docJSON, err := json.Marshal(doc)
+	if err != nil {
+		return nil, err
+	}
+
+	cID := C.CString(id)
+	defer C.free(unsafe.Pointer(cID))
+	cDoc := C.CString(string(docJSON))
+	defer C.free(unsafe.Pointer(cDoc))
+
+	var cResult C.int32_t
+	var cIDOut *C.char
+	var cErr C.CError
+	status := C.jasonisnthappy_collection_upsert_merge_by_id(c.coll, cID, cDoc, &cResult, &cIDOut, &cErr)
+
+	return upsertMergeResult(status, cResult, cIDOut, &cErr)
+}
+
+// UpsertMerge upserts a document matching a filter, merging doc into the
+// first match like UpsertMergeByID
+func (c *Collection) UpsertMerge(filter string, doc interface{}) (*UpsertResult, error) {
+	if c.coll == nil {
+		return nil, &Error{Code: -1, Message: "Collection is closed"}
+	}
+
+	docJSON, err := json.Marshal(doc)
+	if err != nil {
+		return nil, err
+	}
+
+	cFilter := C.CString(filter)
+	defer C.free(unsafe.Pointer(cFilter))
+	cDoc := C.CString(string(docJSON))
+	defer C.free(unsafe.Pointer(cDoc))
+
+	var cResult C.int32_t
+	var cIDOut *C.char
+	var cErr C.CError
+	status := C.jasonisnthappy_collection_upsert_merge(c.coll, cFilter, cDoc, &cResult, &cIDOut, &cErr)
+
+	return upsertMergeResult(status, cResult, cIDOut, &cErr)
+}
+
+func upsertMergeResult(status C.int32_t, cResult C.int32_t, cIDOut *C.char, cErr *C.CError) (*UpsertResult, error) {
+	if status != 0 {
+		err := cErrorToGoError(cErr)
+		C.jasonisnthappy_free_error(*cErr)
+		return nil, err
+	}
+
+	id := C.GoString(cIDOut)
+	C.jasonisnthappy_free_string(cIDOut)
+
+	// cResult: 0 = inserted, 1 = updated
+	return &UpsertResult{ID: id, Inserted: cResult == 0}, nil
+}
+
 // ====================
 // Bulk Operations
 // ====================
@@ -1993,6 +2385,7 @@ func (c *Collection) QueryFirst(filter string, sortField string, sortAsc bool, r
 type BulkWriteOperation struct {
 	Op     string                 `json:"op"`
 	Filter string                 `json:"filter,omitempty"`
+	ID     string                 `json:"id,omitempty"`
 	Doc    map[string]interface{} `json:"doc,omitempty"`
 	Update map[string]interface{} `json:"update,omitempty"`
 }
@@ -2008,6 +2401,7 @@ type BulkWriteResult struct {
 	InsertedCount int              `json:"inserted_count"`
 	UpdatedCount  int              `json:"updated_count"`
 	DeletedCount  int              `json:"deleted_count"`
+	UpsertedCount int              `json:"upserted_count"`
 	InsertedIDs   []string         `json:"inserted_ids,omitempty"`
 	Errors        []BulkWriteError `json:"errors,omitempty"`
 }
@@ -2174,7 +2568,7 @@ type WatchHandle struct {
 //
 // Example:
 //
-//	handle, err := collection.WatchStart("", func(coll, op, docID, docJSON string) {
+//	handle, err := collection.WatchStart("", func(coll, op, docID, docJSON, oldDocJSON string) {
 //	    fmt.Printf("Change: %s %s in %s\n", op, docID, coll)
 //	})
 //	if err != nil {
@@ -2182,6 +2576,16 @@ type WatchHandle struct {
 //	}
 //	defer handle.Stop()
 func (c *Collection) WatchStart(filter string, callback WatchCallback) (*WatchHandle, error) {
+	return c.watchStart(filter, false, callback)
+}
+
+// WatchStartWithBeforeChange starts watching for changes on the collection and
+// passes the document as it was before each update or delete to the callback.
+func (c *Collection) WatchStartWithBeforeChange(filter string, callback WatchCallback) (*WatchHandle, error) {
+	return c.watchStart(filter, true, callback)
+}
+
+func (c *Collection) watchStart(filter string, fullDocumentBeforeChange bool, callback WatchCallback) (*WatchHandle, error) {
 	if c.coll == nil {
 		return nil, &Error{Code: -1, Message: "Collection is freed"}
 	}
@@ -2206,6 +2610,7 @@ func (c *Collection) WatchStart(filter string, callback WatchCallback) (*WatchHa
 	result := C.jasonisnthappy_collection_watch_start(
 		c.coll,
 		cFilter,
+		C.bool(fullDocumentBeforeChange),
 		(C.watch_callback_fn)(C.goWatchCallbackBridge),
 		unsafe.Pointer(callbackID),
 		&cHandle,
@@ -19,6 +19,7 @@ typedef struct CError {
 } CError;
 
 typedef struct CDatabaseOptions {
+  uint32_t struct_size;
   uintptr_t cache_size;
   uint64_t auto_checkpoint_threshold;
   uint32_t file_permissions;
@@ -26,12 +27,32 @@ typedef struct CDatabaseOptions {
   uintptr_t max_bulk_operations;
   uintptr_t max_document_size;
   uintptr_t max_request_body_size;
+  bool document_metadata;
+  uintptr_t scan_threads;
+  bool enable_id_bloom_filter;
+  uintptr_t page_size;
+  uint64_t max_wal_bytes;
+  bool collect_all_validation_errors;
+  bool page_checksums;
+  uint64_t lock_timeout_ms;
+  uintptr_t max_scan_documents;
+  uintptr_t max_result_documents;
+  uint64_t slow_query_threshold_ms;
+  uintptr_t slow_query_log_size;
+  uint32_t durability;
+  uint64_t durability_interval_ms;
+  bool strict_query_types;
+  uintptr_t query_cache_size;
 } CDatabaseOptions;
 
 typedef struct CTransactionConfig {
+  uint32_t struct_size;
   uintptr_t max_retries;
   uint64_t retry_backoff_base_ms;
   uint64_t max_retry_backoff_ms;
+  uint64_t max_transaction_duration_ms;
+  uint64_t min_retry_backoff_ms;
+  bool jitter;
 } CTransactionConfig;
 
 /**
@@ -46,12 +67,15 @@ typedef void (*watch_callback_fn)(const char *collection,
                                   const char *operation,
                                   const char *doc_id,
                                   const char *doc_json,
+                                  const char *old_doc_json,
                                   void *user_data);
 
 /* Database Operations */
 CDatabase *jasonisnthappy_open(const char *path, CError *error_out);
 CDatabase *jasonisnthappy_open_with_options(const char *path, CDatabaseOptions options, CError *error_out);
 void jasonisnthappy_close(CDatabase *db);
+const char *jasonisnthappy_version(void);
+uint32_t jasonisnthappy_abi_version(void);
 CDatabaseOptions jasonisnthappy_default_database_options(void);
 CTransactionConfig jasonisnthappy_default_transaction_config(void);
 int32_t jasonisnthappy_set_transaction_config(CDatabase *db, CTransactionConfig config, CError *error_out);
@@ -65,15 +89,19 @@ uintptr_t jasonisnthappy_max_request_body_size(CDatabase *db, CError *error_out)
 int32_t jasonisnthappy_list_collections(CDatabase *db, char **json_out, CError *error_out);
 int32_t jasonisnthappy_collection_stats(CDatabase *db, const char *collection_name, char **json_out, CError *error_out);
 int32_t jasonisnthappy_database_info(CDatabase *db, char **json_out, CError *error_out);
+int32_t jasonisnthappy_check_integrity(CDatabase *db, char **report_json_out, CError *error_out);
+int32_t jasonisnthappy_warm_cache(CDatabase *db, const char *collection_name, bool documents, char **warmup_json_out, CError *error_out);
 int32_t jasonisnthappy_list_indexes(CDatabase *db, const char *collection_name, char **json_out, CError *error_out);
 int32_t jasonisnthappy_create_index(CDatabase *db, const char *collection_name, const char *index_name, const char *field, bool unique, CError *error_out);
 int32_t jasonisnthappy_create_compound_index(CDatabase *db, const char *collection_name, const char *index_name, const char *fields_json, bool unique, CError *error_out);
 int32_t jasonisnthappy_create_text_index(CDatabase *db, const char *collection_name, const char *index_name, const char *field, CError *error_out);
 int32_t jasonisnthappy_drop_index(CDatabase *db, const char *collection_name, const char *index_name, CError *error_out);
 int32_t jasonisnthappy_set_schema(CDatabase *db, const char *collection_name, const char *schema_json, CError *error_out);
+int32_t jasonisnthappy_set_json_schema(CDatabase *db, const char *collection_name, const char *schema_json, char **warnings_json_out, CError *error_out);
 int32_t jasonisnthappy_get_schema(CDatabase *db, const char *collection_name, char **schema_out, CError *error_out);
 int32_t jasonisnthappy_remove_schema(CDatabase *db, const char *collection_name, CError *error_out);
 int32_t jasonisnthappy_checkpoint(CDatabase *db, CError *error_out);
+int32_t jasonisnthappy_checkpoint_truncate(CDatabase *db, CError *error_out);
 int32_t jasonisnthappy_backup(CDatabase *db, const char *dest_path, CError *error_out);
 int32_t jasonisnthappy_garbage_collect(CDatabase *db, char **result_out, CError *error_out);
 int32_t jasonisnthappy_metrics(CDatabase *db, char **json_out, CError *error_out);
@@ -112,8 +140,12 @@ int64_t jasonisnthappy_collection_update(CCollection *coll, const char *filter,
 int32_t jasonisnthappy_collection_update_one(CCollection *coll, const char *filter, const char *updates_json, bool *updated_out, CError *error_out);
 int64_t jasonisnthappy_collection_delete(CCollection *coll, const char *filter, CError *error_out);
 int32_t jasonisnthappy_collection_delete_one(CCollection *coll, const char *filter, bool *deleted_out, CError *error_out);
+int32_t jasonisnthappy_collection_find_one_and_update(CCollection *coll, const char *filter, const char *updates_json, const char *sort_field, bool sort_ascending, bool return_new, char **json_out, CError *error_out);
+int32_t jasonisnthappy_collection_find_one_and_delete(CCollection *coll, const char *filter, const char *sort_field, bool sort_ascending, char **json_out, CError *error_out);
 int32_t jasonisnthappy_collection_upsert_by_id(CCollection *coll, const char *doc_id, const char *doc_json, char **result_out, CError *error_out);
 int32_t jasonisnthappy_collection_upsert(CCollection *coll, const char *filter, const char *doc_json, char **result_out, CError *error_out);
+int32_t jasonisnthappy_collection_upsert_merge_by_id(CCollection *coll, const char *id, const char *json, int32_t *result_out, char **id_out, CError *error_out);
+int32_t jasonisnthappy_collection_upsert_merge(CCollection *coll, const char *query, const char *json, int32_t *result_out, char **id_out, CError *error_out);
 int32_t jasonisnthappy_collection_insert_many(CCollection *coll, const char *docs_json, char **ids_out, CError *error_out);
 int32_t jasonisnthappy_collection_bulk_write(CCollection *coll, const char *operations_json, bool ordered, char **result_out, CError *error_out);
 int32_t jasonisnthappy_collection_aggregate(CCollection *coll, const char *pipeline_json, char **result_out, CError *error_out);
@@ -126,7 +158,7 @@ int64_t jasonisnthappy_collection_query_count(CCollection *coll, const char *fil
 int32_t jasonisnthappy_collection_query_first(CCollection *coll, const char *filter, const char *sort_field, bool sort_asc, char **doc_out, CError *error_out);
 
 /* Watch Operations */
-int32_t jasonisnthappy_collection_watch_start(CCollection *coll, const char *filter, watch_callback_fn callback, void *user_data, CWatchHandle **handle_out, CError *error_out);
+int32_t jasonisnthappy_collection_watch_start(CCollection *coll, const char *filter, bool full_document_before_change, watch_callback_fn callback, void *user_data, CWatchHandle **handle_out, CError *error_out);
 void jasonisnthappy_watch_stop(CWatchHandle *handle);
 
 /* Web Server */
@@ -12,10 +12,47 @@ export interface Document {
   [key: string]: unknown;
 }
 
+/**
+ * A stored document of type `T`, which always has an `_id`. `T` can be any
+ * object type, including an interface without an index signature:
+ *
+ * ```ts
+ * interface User { _id?: string; name: string; age: number }
+ * const users = db.getCollection<User>('users');
+ * const id: string = users.insert({ name: 'Ann', age: 30 });
+ * const user: WithId<User> | null = users.findById(id);
+ * ```
+ *
+ * Types are only checked at compile time; documents are plain JSON at runtime.
+ */
+export type WithId<T> = Omit<T, '_id'> & { _id: string };
+
+/** A document of type `T` to insert; `_id` is generated when left out */
+export type OptionalId<T> = Omit<T, '_id'> & { _id?: string };
+
+/** Update operators, with dot-notation paths for nested fields */
+export interface UpdateOperators<T> {
+  $set?: Partial<Omit<T, '_id'>> & Record<string, unknown>;
+  $unset?: { [K in Exclude<keyof T, '_id'>]?: '' | true | 1 } & Record<string, unknown>;
+  $inc?: Record<string, number>;
+  $min?: Partial<Omit<T, '_id'>> & Record<string, unknown>;
+  $max?: Partial<Omit<T, '_id'>> & Record<string, unknown>;
+  $rename?: Record<string, string>;
+  $push?: Record<string, unknown>;
+}
+
+/** An update: plain fields replacing those of the same name, or operators */
+export type Update<T> = Partial<Omit<T, '_id'>> | UpdateOperators<T>;
+
 // =============================================================================
 // Configuration Types
 // =============================================================================
 
+export type IdStrategy = 'uuid' | 'object_id' | 'ulid' | 'auto_increment';
+
+/** How strings compare in queries, sorts and indexes */
+export type Collation = 'binary' | 'ascii_case_insensitive' | 'case_insensitive';
+
 export interface DatabaseOptions {
   cacheSize?: number;
   autoCheckpointThreshold?: number;
@@ -24,12 +61,48 @@ export interface DatabaseOptions {
   maxBulkOperations?: number;
   maxDocumentSize?: number;
   maxRequestBodySize?: number;
+  documentMetadata?: boolean;
+  scanThreads?: number;
+  enableIdBloomFilter?: boolean;
+  pageSize?: number;
+  maxWalBytes?: number;
+  collectAllValidationErrors?: boolean;
+  pageChecksums?: boolean;
+  lockTimeoutMs?: number;
+  /** Most documents a collection scan may examine; 0 means no limit */
+  maxScanDocuments?: number;
+  /** Most documents a query may collect as results; 0 means no limit */
+  maxResultDocuments?: number;
+  /**
+   * When commits fsync. Every level fsyncs the WAL on each commit. `full`
+   * (default) also fsyncs the database file every commit; `normal` only on
+   * checkpoint and close; `batched` every `durabilityIntervalMs`. Commits not
+   * yet in the database file are replayed from the WAL on the next open.
+   */
+  durability?: 'full' | 'normal' | 'batched';
+  /** Milliseconds between fsyncs when `durability` is `batched` (default 100) */
+  durabilityIntervalMs?: number;
+  /**
+   * Never match a number against a string in query comparisons (default true).
+   * When false, strings holding numbers, like `"21"`, compare as those numbers.
+   */
+  strictQueryTypes?: boolean;
+  /**
+   * Number of find and aggregation results cached until a write to their
+   * collection (default 0, off)
+   */
+  queryCacheSize?: number;
 }
 
 export interface TransactionConfig {
   maxRetries?: number;
   retryBackoffBaseMs?: number;
   maxRetryBackoffMs?: number;
+  /** 0 means no limit */
+  maxTransactionDurationMs?: number;
+  minRetryBackoffMs?: number;
+  /** Randomize each retry wait between the minimum and the doubled backoff */
+  jitter?: boolean;
 }
 
 // =============================================================================
@@ -41,10 +114,22 @@ export interface UpsertResult {
   inserted: boolean;
 }
 
+/**
+ * Fields to keep or drop, as dotted paths that reach into every object of an
+ * array. `_id` is kept unless excluded, so `{ include: ['_id'] }` returns only
+ * ids. With `include`, `exclude` may only name `_id` and paths inside
+ * included fields, like `{ include: ['address'], exclude: ['address.zip'] }`.
+ */
+export interface Projection {
+  include?: string[];
+  exclude?: string[];
+}
+
 export interface BulkWriteResult {
   inserted_count: number;
   updated_count: number;
   deleted_count: number;
+  upserted_count: number;
   inserted_ids: string[];
   errors: BulkWriteError[];
 }
@@ -64,13 +149,42 @@ export interface CollectionInfo {
   document_count: number;
   btree_root: number;
   indexes: IndexInfo[];
+  metrics: CollectionMetrics;
+  /** Encoded size in bytes of every document's current version */
+  data_size_bytes: number;
+  avg_document_size: number;
+}
+
+export interface CollectionMetrics {
+  inserts: number;
+  updates: number;
+  deletes: number;
+  finds: number;
+  documents_scanned: number;
+  index_hits: number;
+  full_scans: number;
 }
 
 export interface IndexInfo {
   name: string;
   fields: string[];
   unique: boolean;
+  descending: boolean;
+  /** Documents missing an indexed field (or holding null) are left out */
+  sparse: boolean;
   index_type: 'btree' | 'text';
+  text_options: TextIndexOptions | null;
+  /** One per indexed document, or per term for a text index; 0 from `listIndexes` */
+  entry_count: number;
+  /** Bytes of the index's pages; 0 from `listIndexes` */
+  size_bytes: number;
+}
+
+export interface TextIndexOptions {
+  stopwords: string[];
+  stemming: 'english' | null;
+  k1: number;
+  b: number;
 }
 
 export interface DatabaseInfo {
@@ -81,6 +195,54 @@ export interface DatabaseInfo {
   collections: CollectionInfo[];
   total_documents: number;
   read_only: boolean;
+  space: SpaceStats;
+}
+
+export interface CollectionSpace {
+  name: string;
+  pages: number;
+  tree_pages: number;
+  document_pages: number;
+  overflow_pages: number;
+  index_pages: number;
+}
+
+export interface SpaceStats {
+  page_size: number;
+  total_pages: number;
+  pages_in_use: number;
+  free_pages: number;
+  overflow_pages: number;
+  old_version_pages: number;
+  orphaned_pages: number;
+  collections: CollectionSpace[];
+}
+
+export interface IntegrityProblem {
+  collection: string | null;
+  page: number | null;
+  message: string;
+}
+
+export interface CacheWarmup {
+  tree_pages: number;
+  document_pages: number;
+  /** Warming stopped early because the page cache was full */
+  cache_full: boolean;
+}
+
+export interface IntegrityReport {
+  collections_checked: number;
+  documents_checked: number;
+  indexes_checked: number;
+  total_pages: number;
+  free_pages: number;
+  /** Wasted space reclaimed by vacuum, not corruption */
+  orphaned_pages: number;
+  /** Pages whose checksum was verified; 0 without page checksums */
+  pages_verified: number;
+  problems: IntegrityProblem[];
+  warnings: IntegrityProblem[];
 }
 
 export interface GarbageCollectResult {
@@ -95,12 +257,26 @@ export interface BackupInfo {
   total_documents: number;
 }
 
+export interface QueryExplain {
+  collection: string;
+  filter: string | null;
+  access_path: 'collection_scan';
+  filter_fields: string[];
+  candidate_indexes: string[];
+  estimated_docs_examined: number;
+  docs_examined: number;
+  docs_returned: number;
+  in_memory_sort: boolean;
+  elapsed_micros: number;
+}
+
 export interface MetricsSnapshot {
   documents_written: number;
   documents_read: number;
   total_document_operations: number;
   io_errors: number;
   transaction_conflicts: number;
+  queries_over_limit: number;
 }
 
 // =============================================================================
@@ -134,16 +310,48 @@ export interface BulkDeleteManyOp {
   filter: string;
 }
 
+export interface BulkUpsertOneOp<T> {
+  op: 'upsert_one';
+  filter: string;
+  doc: T;
+}
+
+export interface BulkUpsertByIdOp<T> {
+  op: 'upsert_by_id';
+  id: string;
+  doc: T;
+}
+
 export type BulkOperation<T = Record<string, unknown>> =
   | BulkInsertOp<T>
   | BulkUpdateOneOp
   | BulkUpdateManyOp
   | BulkDeleteOneOp
-  | BulkDeleteManyOp;
+  | BulkDeleteManyOp
+  | BulkUpsertOneOp<T>
+  | BulkUpsertByIdOp<T>;
+
+/** A field name, a literal, or a single-operator object */
+export type FieldExpr =
+  | string
+  | number
+  | boolean
+  | null
+  | { field: string }
+  | { literal: unknown }
+  | { concat: FieldExpr[] }
+  | { add: FieldExpr[] }
+  | { sub: FieldExpr[] }
+  | { mul: FieldExpr[] }
+  | { div: FieldExpr[] };
 
 export interface AggregationStage {
+  /** Filters the previous stage's output; after `group_by` it filters groups, like SQL `HAVING` */
   match?: string;
-  group_by?: string;
+  /** An array groups on the compound key; `_id` is then an object of the key fields */
+  group_by?: string | string[];
+  /** Buckets an RFC3339 / epoch-seconds field in UTC */
+  group_by_date?: { field: string; granularity: 'day' | 'month' | 'year' };
   count?: string;
   sum?: { field: string; output: string };
   avg?: { field: string; output: string };
@@ -154,6 +362,14 @@ export interface AggregationStage {
   skip?: number;
   project?: string[];
   exclude?: string[];
+  add_fields?: Record<string, FieldExpr>;
+  /**
+   * Writes the results into this collection in one transaction; must be the
+   * last stage. The pipeline then returns `[{ out, written }]`.
+   */
+  out?: string;
+  /** How `out` writes: `replace` (default) clears the collection first, `merge` upserts by `_id` */
+  mode?: 'replace' | 'merge';
 }
 
 // =============================================================================
@@ -165,9 +381,17 @@ export type ChangeOperation = 'insert' | 'update' | 'delete';
 export type WatchCallback<T> = (
   operation: ChangeOperation,
   docId: string,
-  document: T | null
+  document: T | null,
+  oldDocument: T | null
 ) => void;
 
+export interface WatchOptions {
+  /** Only call back for these operations; both these and the filter must match */
+  operations?: ChangeOperation[];
+  /** Pass the document as it was before updates and deletes */
+  fullDocumentBeforeChange?: boolean;
+}
+
 // =============================================================================
 // Database Class
 // =============================================================================
@@ -193,23 +417,37 @@ export class Database {
   maxDocumentSize(): number;
   maxRequestBodySize(): number;
   listCollections(): string[];
+  renameCollection(oldName: string, newName: string): void;
+  copyCollection(src: string, dst: string): void;
+  collectionExists(collectionName: string): boolean;
   collectionStats(collectionName: string): CollectionInfo;
   databaseInfo(): DatabaseInfo;
+  checkIntegrity(): IntegrityReport;
+  /** Read B-trees and, unless `documents` is false, documents into the page cache */
+  warmCache(collectionName?: string | null, documents?: boolean): CacheWarmup;
 
   // Index Management
   listIndexes(collectionName: string): IndexInfo[];
-  createIndex(collectionName: string, indexName: string, field: string, unique: boolean): void;
-  createCompoundIndex(collectionName: string, indexName: string, fields: string[], unique: boolean): void;
+  indexExists(collectionName: string, indexName: string): boolean;
+  createIndex(collectionName: string, indexName: string, field: string, unique: boolean, descending?: boolean, sparse?: boolean, collation?: Collation): void;
+  createCompoundIndex(collectionName: string, indexName: string, fields: string[], unique: boolean, descending?: boolean, sparse?: boolean, collation?: Collation): void;
   createTextIndex(collectionName: string, indexName: string, field: string): void;
   dropIndex(collectionName: string, indexName: string): void;
 
   // Schema
   setSchema(collectionName: string, schema: Record<string, unknown>): void;
+  setJsonSchema(collectionName: string, schema: Record<string, unknown>): string[];
+  setIdStrategy(collectionName: string, strategy: IdStrategy): void;
   getSchema(collectionName: string): Record<string, unknown> | null;
   removeSchema(collectionName: string): void;
 
   // Maintenance
   checkpoint(): void;
+  checkpointTruncate(): void;
+  /** Fsync the WAL and write out cached pages without checkpointing */
+  sync(): void;
+  /** Whether every commit has been fsynced and no cached pages await writing */
+  isFullySynced(): boolean;
   backup(destPath: string): void;
   garbageCollect(): GarbageCollectResult;
   metrics(): MetricsSnapshot;
@@ -217,7 +455,12 @@ export class Database {
 
   // Collections & Transactions
   beginTransaction(): Transaction;
-  getCollection<T extends Document>(name: string): Collection<T>;
+  beginReadOnly(): Transaction;
+  runTransaction<R = any>(callback: (tx: Transaction) => R): R;
+  snapshot(): Snapshot;
+  getCollection<T extends object = Document>(name: string): Collection<T>;
+  /** Use a collection as a key-value store, with each document's _id as its key */
+  kv<T extends object = Document>(collectionName: string): KvStore<T>;
   startWebUi(addr: string): WebServer;
 }
 
@@ -238,12 +481,17 @@ export class Transaction {
   commit(): void;
   rollback(): void;
 
+  // Savepoints
+  savepoint(name: string): void;
+  rollbackToSavepoint(name: string): void;
+  releaseSavepoint(name: string): void;
+
   // CRUD - use generics for type safety
-  insert<T extends Document>(collectionName: string, doc: Omit<T, '_id'>): string;
-  findById<T extends Document>(collectionName: string, id: string): T | null;
-  updateById<T extends Document>(collectionName: string, id: string, updates: Partial<T>): void;
+  insert<T extends object = Document>(collectionName: string, doc: OptionalId<T>): string;
+  findById<T extends object = Document>(collectionName: string, id: string): WithId<T> | null;
+  updateById<T extends object = Document>(collectionName: string, id: string, updates: Update<T>): void;
   deleteById(collectionName: string, id: string): void;
-  findAll<T extends Document>(collectionName: string): T[];
+  findAll<T extends object = Document>(collectionName: string): WithId<T>[];
   count(collectionName: string): number;
 
   // Collection Management
@@ -252,39 +500,79 @@ export class Transaction {
   renameCollection(oldName: string, newName: string): void;
 }
 
+// =============================================================================
+// Snapshot Class
+// =============================================================================
+
+export class Snapshot {
+  getCollection<T extends object = Document>(name: string): Collection<T>;
+  listCollections(): string[];
+  snapshotId(): number;
+  release(): void;
+}
+
+// =============================================================================
+// KvStore Class
+// =============================================================================
+
+export class KvStore<T extends object = Document> {
+  get(key: string): T | null;
+  /** Replaces the whole value stored under key */
+  set(key: string, value: T): void;
+  mget(keys: string[]): (T | null)[];
+  /** Stores every pair in one transaction */
+  mset(pairs: Record<string, T>): void;
+  /** Returns whether the key was set */
+  delete(key: string): boolean;
+}
+
 // =============================================================================
 // Collection Class
 // =============================================================================
 
-export class Collection<T extends Document> {
+export class Collection<T extends object = Document> {
   name(): string;
+  exists(): boolean;
 
   // Basic CRUD
-  insert(doc: Omit<T, '_id'>): string;
-  findById(id: string): T | null;
-  updateById(id: string, updates: Partial<T>): void;
+  insert(doc: OptionalId<T>): string;
+  findById(id: string): WithId<T> | null;
+  findByIds(ids: string[]): (WithId<T> | null)[];
+  updateById(id: string, updates: Update<T>): void;
   deleteById(id: string): void;
-  findAll(): T[];
+  findAll(): WithId<T>[];
+  scanRange(startId: string, endId: string, inclusive: boolean): WithId<T>[];
+  scanFrom(startId: string, limit: number): WithId<T>[];
   count(): number;
 
   // Query Operations
-  find(filter: string): T[];
-  findOne(filter: string): T | null;
-  update(filter: string, updates: Partial<T>): number;
-  updateOne(filter: string, updates: Partial<T>): boolean;
+  find(filter: string, projection?: Projection): WithId<T>[];
+  findOne(filter: string, projection?: Projection): WithId<T> | null;
+  /** Binds the filter's `?1`, `?2`, ... placeholders to `params` as whole JSON values */
+  findParams(filter: string, params: unknown[]): WithId<T>[];
+  findOneParams(filter: string, params: unknown[]): WithId<T> | null;
+  update(filter: string, updates: Update<T>): number;
+  updateOne(filter: string, updates: Update<T>): boolean;
+  updateReturning(filter: string, updates: Update<T>): string[];
+  updateOneReturning(filter: string, updates: Update<T>): string | null;
   delete(filter: string): number;
   deleteOne(filter: string): boolean;
+  findOneAndUpdate(filter: string, updates: Update<T>, returnNew?: boolean, sortField?: keyof T & string, sortAsc?: boolean): WithId<T> | null;
+  findOneAndDelete(filter: string, sortField?: keyof T & string, sortAsc?: boolean): WithId<T> | null;
+  explain(filter: string): QueryExplain;
 
   // Upsert
-  upsertById(id: string, doc: Omit<T, '_id'>): UpsertResult;
-  upsert(filter: string, doc: Omit<T, '_id'>): UpsertResult;
+  upsertById(id: string, doc: OptionalId<T>): UpsertResult;
+  upsert(filter: string, doc: OptionalId<T>): UpsertResult;
+  upsertMergeById(id: string, doc: Partial<OptionalId<T>>): UpsertResult;
+  upsertMerge(filter: string, doc: Partial<OptionalId<T>>): UpsertResult;
 
   // Bulk Operations
-  insertMany(docs: Omit<T, '_id'>[]): string[];
-  bulkWrite(operations: BulkOperation<Omit<T, '_id'>>[], ordered?: boolean): BulkWriteResult;
+  insertMany(docs: OptionalId<T>[]): string[];
+  bulkWrite(operations: BulkOperation<OptionalId<T>>[], ordered?: boolean): BulkWriteResult;
 
   // Advanced Queries
-  distinct<K extends keyof T>(field: K): T[K][];
+  distinct<K extends keyof T>(field: K, filter?: string): T[K][];
   countDistinct(field: keyof T): number;
   search(query: string): SearchResult[];
   countWithQuery(filter?: string): number;
@@ -297,16 +585,58 @@ export class Collection<T extends Document> {
     limit?: number,
     skip?: number,
     projectFields?: (keyof T & string)[],
-    excludeFields?: (keyof T & string)[]
-  ): T[];
+    excludeFields?: (keyof T & string)[],
+    collation?: Collation
+  ): WithId<T>[];
   queryCount(filter?: string, skip?: number, limit?: number): number;
-  queryFirst(filter?: string, sortField?: keyof T & string, sortAsc?: boolean): T | null;
+  queryFirst(filter?: string, sortField?: keyof T & string, sortAsc?: boolean): WithId<T> | null;
+
+  // Streaming
+  /**
+   * Documents matching `filter`, read in batches of `batchSize` (default 100)
+   * on a worker thread that stays at most one batch ahead:
+   *
+   * ```ts
+   * for await (const doc of users.findStream('age > 18')) { ... }
+   * ```
+   *
+   * Every batch reads from the snapshot taken when the stream starts. Breaking
+   * out of the loop closes the stream.
+   */
+  findStream(filter: string, batchSize?: number): FindStream<T>;
 
   // Aggregation - returns different shape, so use separate generic
-  aggregate<R>(pipeline: AggregationStage[]): R[];
+  aggregate<R = Document>(pipeline: AggregationStage[]): R[];
 
   // Watch
-  watch(filter: string | undefined, callback: WatchCallback<T>): WatchHandle;
+  watch(filter: string | undefined, callback: WatchCallback<WithId<T>>, fullDocumentBeforeChange?: boolean): WatchHandle;
+  watch(filter: string | undefined, options: WatchOptions, callback: WatchCallback<WithId<T>>): WatchHandle;
+
+  // Async variants - run on the libuv thread pool without blocking the event loop
+  insertAsync(doc: OptionalId<T>): Promise<string>;
+  insertManyAsync(docs: OptionalId<T>[]): Promise<string[]>;
+  findByIdAsync(id: string): Promise<WithId<T> | null>;
+  findAllAsync(): Promise<WithId<T>[]>;
+  countAsync(): Promise<number>;
+  findAsync(filter: string, projection?: Projection): Promise<WithId<T>[]>;
+  findOneAsync(filter: string, projection?: Projection): Promise<WithId<T> | null>;
+  updateAsync(filter: string, updates: Update<T>): Promise<number>;
+  updateReturningAsync(filter: string, updates: Update<T>): Promise<string[]>;
+  deleteAsync(filter: string): Promise<number>;
+  bulkWriteAsync(operations: BulkOperation<OptionalId<T>>[], ordered?: boolean): Promise<BulkWriteResult>;
+  aggregateAsync<R = Document>(pipeline: AggregationStage[]): Promise<R[]>;
+}
+
+// =============================================================================
+// FindStream Class
+// =============================================================================
+
+export class FindStream<T extends object = Document> implements AsyncIterable<WithId<T>> {
+  /** The next batch, or null once the stream is exhausted */
+  nextBatch(): Promise<WithId<T>[] | null>;
+  /** Stops reading and releases the stream's snapshot */
+  close(): void;
+  [Symbol.asyncIterator](): AsyncGenerator<WithId<T>, void, undefined>;
 }
 
 // =============================================================================
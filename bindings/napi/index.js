@@ -34,4 +34,30 @@ function loadNativeModule() {
   return require(libPath);
 }
 
-module.exports = loadNativeModule();
+const native = loadNativeModule();
+
+// Yield a findStream's documents one at a time, fetching the next batch only
+// once the previous one has been consumed
+native.FindStream.prototype[Symbol.asyncIterator] = async function* () {
+  try {
+    let batch;
+    while ((batch = await this.nextBatch()) !== null) {
+      yield* batch;
+    }
+  } finally {
+    this.close();
+  }
+};
+
+// Accept watch(filter, { operations, fullDocumentBeforeChange }, callback)
+// alongside watch(filter, callback, fullDocumentBeforeChange)
+const nativeWatch = native.Collection.prototype.watch;
+native.Collection.prototype.watch = function (filter, optionsOrCallback, callbackOrFlag) {
+  if (typeof optionsOrCallback === 'function') {
+    return nativeWatch.call(this, filter, optionsOrCallback, callbackOrFlag);
+  }
+  const options = optionsOrCallback || {};
+  return nativeWatch.call(this, filter, callbackOrFlag, options.fullDocumentBeforeChange, options.operations);
+};
+
+module.exports = native;
@@ -2,16 +2,27 @@ use napi::bindgen_prelude::*;
 use napi::threadsafe_function::ThreadsafeFunctionCallMode;
 use napi_derive::napi;
 use jasonisnthappy::{
+    Collation,
     Database as CoreDatabase,
     Transaction as CoreTransaction,
     Collection as CoreCollection,
+    DateGranularity,
+    Durability,
+    Error as CoreError,
+    FieldExpr,
+    IndexOptions,
+    KvStore as CoreKvStore,
+    OutMode,
+    Snapshot as CoreSnapshot,
     SortOrder,
+    TextIndexOptions,
 };
 use jasonisnthappy::core::database::{DatabaseOptions, TransactionConfig};
 use jasonisnthappy::core::watch::ChangeOperation;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
+use std::time::Duration;
 
 // ==================
 // Database Options
@@ -26,6 +37,26 @@ pub struct JsDatabaseOptions {
     pub max_bulk_operations: Option<u32>,
     pub max_document_size: Option<u32>,
     pub max_request_body_size: Option<u32>,
+    pub document_metadata: Option<bool>,
+    pub scan_threads: Option<u32>,
+    pub enable_id_bloom_filter: Option<bool>,
+    pub page_size: Option<u32>,
+    pub max_wal_bytes: Option<i64>,
+    pub collect_all_validation_errors: Option<bool>,
+    pub page_checksums: Option<bool>,
+    pub lock_timeout_ms: Option<u32>,
+    /// Most documents a collection scan may examine; 0 for no limit
+    pub max_scan_documents: Option<u32>,
+    /// Most documents a query may collect as results; 0 for no limit
+    pub max_result_documents: Option<u32>,
+    /// When commits fsync: "full", "normal" or "batched"
+    pub durability: Option<String>,
+    /// Milliseconds between fsyncs when `durability` is "batched"
+    pub durability_interval_ms: Option<u32>,
+    /// When false, strings holding numbers compare as those numbers in queries
+    pub strict_query_types: Option<bool>,
+    /// Number of find and aggregation results cached until a write to their collection
+    pub query_cache_size: Option<u32>,
 }
 
 impl From<JsDatabaseOptions> for DatabaseOptions {
@@ -52,6 +83,47 @@ impl From<JsDatabaseOptions> for DatabaseOptions {
         if let Some(max_req) = opts.max_request_body_size {
             db_opts.max_request_body_size = max_req as usize;
         }
+        if let Some(doc_meta) = opts.document_metadata {
+            db_opts.document_metadata = doc_meta;
+        }
+        if let Some(threads) = opts.scan_threads {
+            db_opts.scan_threads = threads as usize;
+        }
+        if let Some(bloom) = opts.enable_id_bloom_filter {
+            db_opts.enable_id_bloom_filter = bloom;
+        }
+        if let Some(page_size) = opts.page_size {
+            db_opts.page_size = page_size as usize;
+        }
+        if let Some(max_wal) = opts.max_wal_bytes {
+            db_opts.max_wal_bytes = max_wal.max(0) as u64;
+        }
+        if let Some(collect_all) = opts.collect_all_validation_errors {
+            db_opts.collect_all_validation_errors = collect_all;
+        }
+        if let Some(checksums) = opts.page_checksums {
+            db_opts.page_checksums = checksums;
+        }
+        if let Some(timeout) = opts.lock_timeout_ms {
+            db_opts.lock_timeout_ms = timeout as u64;
+        }
+        if let Some(max_scan) = opts.max_scan_documents {
+            db_opts.max_scan_documents = max_scan as usize;
+        }
+        if let Some(max_results) = opts.max_result_documents {
+            db_opts.max_result_documents = max_results as usize;
+        }
+        if let Some(durability) = opts.durability {
+            let interval = Duration::from_millis(opts.durability_interval_ms.unwrap_or(100) as u64);
+            // Checked by `open_with_options` before converting
+            db_opts.durability = Durability::parse(&durability, interval).unwrap_or_default();
+        }
+        if let Some(strict) = opts.strict_query_types {
+            db_opts.strict_query_types = strict;
+        }
+        if let Some(size) = opts.query_cache_size {
+            db_opts.query_cache_size = size as usize;
+        }
         db_opts
     }
 }
@@ -61,6 +133,11 @@ pub struct JsTransactionConfig {
     pub max_retries: Option<u32>,
     pub retry_backoff_base_ms: Option<u32>,
     pub max_retry_backoff_ms: Option<u32>,
+    /// 0 means no limit
+    pub max_transaction_duration_ms: Option<u32>,
+    pub min_retry_backoff_ms: Option<u32>,
+    /// Randomize each retry wait between the minimum and the doubled backoff
+    pub jitter: Option<bool>,
 }
 
 impl From<JsTransactionConfig> for TransactionConfig {
@@ -75,6 +152,15 @@ impl From<JsTransactionConfig> for TransactionConfig {
         if let Some(max_backoff) = cfg.max_retry_backoff_ms {
             tx_cfg.max_retry_backoff_ms = max_backoff as u64;
         }
+        if let Some(max_duration) = cfg.max_transaction_duration_ms {
+            tx_cfg.max_transaction_duration_ms = max_duration as u64;
+        }
+        if let Some(min_backoff) = cfg.min_retry_backoff_ms {
+            tx_cfg.min_retry_backoff_ms = min_backoff as u64;
+        }
+        if let Some(jitter) = cfg.jitter {
+            tx_cfg.jitter = jitter;
+        }
         tx_cfg
     }
 }
@@ -85,6 +171,9 @@ impl From<TransactionConfig> for JsTransactionConfig {
             max_retries: Some(cfg.max_retries as u32),
             retry_backoff_base_ms: Some(cfg.retry_backoff_base_ms as u32),
             max_retry_backoff_ms: Some(cfg.max_retry_backoff_ms as u32),
+            max_transaction_duration_ms: Some(cfg.max_transaction_duration_ms as u32),
+            min_retry_backoff_ms: Some(cfg.min_retry_backoff_ms as u32),
+            jitter: Some(cfg.jitter),
         }
     }
 }
@@ -95,6 +184,30 @@ pub struct JsUpsertResult {
     pub inserted: bool,
 }
 
+/// Fields to keep or drop from query results. `_id` is kept unless excluded.
+#[napi(object)]
+pub struct JsProjection {
+    pub include: Option<Vec<String>>,
+    pub exclude: Option<Vec<String>>,
+}
+
+impl JsProjection {
+    fn find(&self, coll: &CoreCollection, filter: &str) -> jasonisnthappy::Result<Vec<serde_json::Value>> {
+        let (include, exclude) = self.fields();
+        coll.find_with_projection(filter, &include, &exclude)
+    }
+
+    fn find_one(&self, coll: &CoreCollection, filter: &str) -> jasonisnthappy::Result<Option<serde_json::Value>> {
+        let (include, exclude) = self.fields();
+        coll.find_one_with_projection(filter, &include, &exclude)
+    }
+
+    fn fields(&self) -> (Vec<&str>, Vec<&str>) {
+        let names = |fields: &Option<Vec<String>>| fields.iter().flatten().map(String::as_str).collect();
+        (names(&self.include), names(&self.exclude))
+    }
+}
+
 // ==================
 // Database Class
 // ==================
@@ -119,6 +232,13 @@ impl Database {
     /// Opens a database with custom options
     #[napi(factory)]
     pub fn open_with_options(path: String, options: JsDatabaseOptions) -> Result<Database> {
+        if let Some(durability) = &options.durability {
+            if Durability::parse(durability, Duration::ZERO).is_none() {
+                return Err(Error::from_reason(format!(
+                    "Invalid durability '{}': expected full, normal or batched", durability
+                )));
+            }
+        }
         let db_opts: DatabaseOptions = options.into();
         let db = CoreDatabase::open_with_options(&path, db_opts)
             .map_err(|e| Error::from_reason(e.to_string()))?;
@@ -149,6 +269,20 @@ impl Database {
             max_bulk_operations: Some(opts.max_bulk_operations as u32),
             max_document_size: Some(opts.max_document_size as u32),
             max_request_body_size: Some(opts.max_request_body_size as u32),
+            document_metadata: Some(opts.document_metadata),
+            scan_threads: Some(opts.scan_threads as u32),
+            enable_id_bloom_filter: Some(opts.enable_id_bloom_filter),
+            page_size: Some(opts.page_size as u32),
+            max_wal_bytes: Some(opts.max_wal_bytes as i64),
+            collect_all_validation_errors: Some(opts.collect_all_validation_errors),
+            page_checksums: Some(opts.page_checksums),
+            lock_timeout_ms: Some(opts.lock_timeout_ms as u32),
+            max_scan_documents: Some(opts.max_scan_documents as u32),
+            max_result_documents: Some(opts.max_result_documents as u32),
+            durability: Some(opts.durability.as_str().to_string()),
+            durability_interval_ms: Some(100),
+            strict_query_types: Some(opts.strict_query_types),
+            query_cache_size: Some(opts.query_cache_size as u32),
         }
     }
 
@@ -217,6 +351,26 @@ impl Database {
             .map_err(|e| Error::from_reason(e.to_string()))
     }
 
+    /// Renames a collection in a transaction of its own
+    #[napi]
+    pub fn rename_collection(&self, old_name: String, new_name: String) -> Result<()> {
+        self.inner.rename_collection(&old_name, &new_name)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Creates `dst` as a copy of `src` with its documents, indexes and schema
+    #[napi]
+    pub fn copy_collection(&self, src: String, dst: String) -> Result<()> {
+        self.inner.copy_collection(&src, &dst)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Checks whether a collection exists without starting a transaction
+    #[napi]
+    pub fn collection_exists(&self, collection_name: String) -> bool {
+        self.inner.collection_exists(&collection_name)
+    }
+
     /// Gets statistics for a collection
     #[napi(ts_return_type = "any")]
     pub fn collection_stats(&self, collection_name: String) -> Result<serde_json::Value> {
@@ -233,6 +387,27 @@ impl Database {
             .map_err(|e| Error::from_reason(e.to_string()))
     }
 
+    /// Checks the database for corruption; consistent when `problems` is empty
+    #[napi(ts_return_type = "any")]
+    pub fn check_integrity(&self) -> Result<serde_json::Value> {
+        self.inner.check_integrity()
+            .and_then(|report| serde_json::to_value(report).map_err(|e| e.into()))
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Reads a collection's B-trees, and unless `documents` is false its
+    /// documents, into the page cache; every collection when none is named
+    #[napi(ts_return_type = "any")]
+    pub fn warm_cache(&self, collection_name: Option<String>, documents: Option<bool>) -> Result<serde_json::Value> {
+        let documents = documents.unwrap_or(true);
+        match collection_name {
+            Some(name) => self.inner.warm_cache(&name, documents),
+            None => self.inner.warm_cache_all(documents),
+        }
+            .and_then(|warmup| serde_json::to_value(warmup).map_err(|e| e.into()))
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
     // Index Management
 
     /// Lists all indexes for a collection
@@ -247,30 +422,54 @@ impl Database {
             .map_err(|e| Error::from_reason(e.to_string()))
     }
 
-    /// Creates a single-field index
+    /// Checks whether a collection has an index with this name
     #[napi]
+    pub fn index_exists(&self, collection_name: String, index_name: String) -> bool {
+        self.inner.index_exists(&collection_name, &index_name)
+    }
+
+    /// Creates a single-field index
+    #[napi(ts_args_type = "collectionName: string, indexName: string, field: string, unique: boolean, descending?: boolean, sparse?: boolean, collation?: Collation")]
     pub fn create_index(
         &self,
         collection_name: String,
         index_name: String,
         field: String,
         unique: bool,
+        descending: Option<bool>,
+        sparse: Option<bool>,
+        collation: Option<String>,
     ) -> Result<()> {
-        self.inner.create_index(&collection_name, &index_name, &field, unique)
+        let options = IndexOptions {
+            unique,
+            descending: descending.unwrap_or(false),
+            sparse: sparse.unwrap_or(false),
+            collation: parse_collation(collation)?,
+        };
+        self.inner.create_index_with_options(&collection_name, &index_name, &[&field], options)
             .map_err(|e| Error::from_reason(e.to_string()))
     }
 
     /// Creates a compound index on multiple fields
-    #[napi]
+    #[napi(ts_args_type = "collectionName: string, indexName: string, fields: string[], unique: boolean, descending?: boolean, sparse?: boolean, collation?: Collation")]
     pub fn create_compound_index(
         &self,
         collection_name: String,
         index_name: String,
         fields: Vec<String>,
         unique: bool,
+        descending: Option<bool>,
+        sparse: Option<bool>,
+        collation: Option<String>,
     ) -> Result<()> {
         let field_refs: Vec<&str> = fields.iter().map(|s| s.as_str()).collect();
-        self.inner.create_compound_index(&collection_name, &index_name, &field_refs, unique)
+        let options = IndexOptions {
+            unique,
+            descending: descending.unwrap_or(false),
+            sparse: sparse.unwrap_or(false),
+            collation: parse_collation(collation)?,
+        };
+        self.inner.create_index_with_options(&collection_name, &index_name, &field_refs, options)
             .map_err(|e| Error::from_reason(e.to_string()))
     }
 
@@ -282,7 +481,7 @@ impl Database {
         index_name: String,
         field: String,
     ) -> Result<()> {
-        self.inner.create_text_index(&collection_name, &index_name, &[&field])
+        self.inner.create_text_index(&collection_name, &index_name, &[&field], TextIndexOptions::default())
             .map_err(|e| Error::from_reason(e.to_string()))
     }
 
@@ -308,6 +507,23 @@ impl Database {
             .map_err(|e| Error::from_reason(e.to_string()))
     }
 
+    /// Sets a schema from a standard JSON Schema document, returning a warning
+    /// for each keyword that isn't supported and was ignored
+    #[napi(ts_args_type = "collectionName: string, schema: any")]
+    pub fn set_json_schema(&self, collection_name: String, schema: serde_json::Value) -> Result<Vec<String>> {
+        self.inner.set_json_schema(&collection_name, &schema)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Sets how a collection generates `_id`: "uuid", "object_id", "ulid" or "auto_increment"
+    #[napi(ts_args_type = "collectionName: string, strategy: IdStrategy")]
+    pub fn set_id_strategy(&self, collection_name: String, strategy: String) -> Result<()> {
+        let strategy: jasonisnthappy::IdStrategy = serde_json::from_value(serde_json::Value::String(strategy.clone()))
+            .map_err(|_| Error::from_reason(format!("Unknown id strategy: {}", strategy)))?;
+        self.inner.set_id_strategy(&collection_name, strategy)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
     /// Gets the JSON schema for a collection
     #[napi(ts_return_type = "any | null")]
     pub fn get_schema(&self, collection_name: String) -> Result<Option<serde_json::Value>> {
@@ -337,6 +553,26 @@ impl Database {
             .map_err(|e| Error::from_reason(e.to_string()))
     }
 
+    /// Checkpoints the whole WAL and truncates the WAL file to zero bytes
+    #[napi]
+    pub fn checkpoint_truncate(&self) -> Result<()> {
+        self.inner.checkpoint_truncate()
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Fsyncs the WAL and writes out cached pages without checkpointing
+    #[napi]
+    pub fn sync(&self) -> Result<()> {
+        self.inner.sync()
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Whether every commit has been fsynced and no cached pages await writing
+    #[napi]
+    pub fn is_fully_synced(&self) -> bool {
+        self.inner.is_fully_synced()
+    }
+
     /// Creates a backup of the database
     #[napi]
     pub fn backup(&self, dest_path: String) -> Result<()> {
@@ -384,6 +620,75 @@ impl Database {
         Ok(Transaction { inner: Some(tx) })
     }
 
+    /// Begins a transaction that can only read. Writes through it throw.
+    #[napi]
+    pub fn begin_read_only(&self) -> Result<Transaction> {
+        let tx = self.inner.begin_read_only()
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        Ok(Transaction { inner: Some(tx) })
+    }
+
+    /// Runs a callback in a transaction, committing when it returns and
+    /// retrying on conflict. Throwing from the callback rolls back.
+    #[napi(ts_args_type = "callback: (tx: Transaction) => any", ts_return_type = "any")]
+    pub fn run_transaction(&self, env: Env, callback: Function<Object, serde_json::Value>) -> Result<serde_json::Value> {
+        // JS errors can't travel through the core retry loop, so park them here
+        let mut js_err = None;
+
+        let result = self.inner.retry_on_conflict(|| {
+            let tx = self.inner.begin()?;
+
+            let mut instance = match (Transaction { inner: Some(tx) }).into_instance(&env) {
+                Ok(instance) => instance,
+                Err(e) => {
+                    js_err = Some(e);
+                    return Err(callback_failed());
+                }
+            };
+
+            let value = callback.call(instance.as_object(&env));
+
+            // Take the transaction back so the JS object can't be used past this point
+            let Some(mut tx) = instance.inner.take() else {
+                // The callback committed or rolled back itself
+                return match value {
+                    Ok(value) => Ok(value),
+                    Err(e) => {
+                        js_err = Some(e);
+                        Err(callback_failed())
+                    }
+                };
+            };
+
+            match value {
+                Ok(value) => {
+                    tx.commit()?;
+                    Ok(value)
+                }
+                Err(e) => {
+                    let _ = tx.rollback();
+                    js_err = Some(e);
+                    Err(callback_failed())
+                }
+            }
+        });
+
+        match (result, js_err) {
+            (Ok(value), _) => Ok(value),
+            (Err(_), Some(e)) => Err(e),
+            (Err(e), None) => Err(Error::from_reason(e.to_string())),
+        }
+    }
+
+    /// Freezes a read-only view of the database. Collections taken from it all
+    /// read the same point in time without blocking writers.
+    #[napi]
+    pub fn snapshot(&self) -> Result<Snapshot> {
+        let snapshot = self.inner.snapshot()
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        Ok(Snapshot { inner: Some(snapshot) })
+    }
+
     /// Gets a collection reference for non-transactional operations
     #[napi]
     pub fn get_collection(&self, name: String) -> Collection {
@@ -391,6 +696,12 @@ impl Database {
         Collection { inner: Some(coll) }
     }
 
+    /// Uses a collection as a key-value store, with each document's _id as its key
+    #[napi]
+    pub fn kv(&self, collection_name: String) -> KvStore {
+        KvStore { inner: self.inner.kv(&collection_name) }
+    }
+
     /// Starts the web UI server at the given address
     /// Returns a WebServer handle that can be used to stop the server
     #[napi]
@@ -401,6 +712,14 @@ impl Database {
     }
 }
 
+/// Placeholder error that stops the retry loop when the JS side failed
+fn callback_failed() -> CoreError {
+    CoreError::ContextCancelled {
+        operation: "runTransaction".to_string(),
+        error: "callback failed".to_string(),
+    }
+}
+
 // ==================
 // WebServer Class
 // ==================
@@ -458,6 +777,46 @@ impl Transaction {
         Ok(())
     }
 
+    // Savepoints
+
+    /// Creates a named savepoint
+    #[napi]
+    pub fn savepoint(&mut self, name: String) -> Result<()> {
+        let tx = self.inner.as_mut()
+            .ok_or_else(|| Error::from_reason("Transaction is closed"))?;
+
+        tx.savepoint(&name)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Discards all writes made since the named savepoint
+    #[napi]
+    pub fn rollback_to_savepoint(&mut self, name: String) -> Result<()> {
+        let tx = self.inner.as_mut()
+            .ok_or_else(|| Error::from_reason("Transaction is closed"))?;
+
+        tx.rollback_to_savepoint(&name)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Removes the named savepoint, keeping writes made since it
+    #[napi]
+    pub fn release_savepoint(&mut self, name: String) -> Result<()> {
+        let tx = self.inner.as_mut()
+            .ok_or_else(|| Error::from_reason("Transaction is closed"))?;
+
+        tx.release_savepoint(&name)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Checks whether the collection exists yet
+    #[napi]
+    pub fn exists(&self) -> Result<bool> {
+        self.inner.as_ref()
+            .ok_or_else(|| Error::from_reason("Collection is closed"))
+            .map(|c| c.exists())
+    }
+
     // Basic CRUD
 
     /// Inserts a document into a collection
@@ -572,6 +931,106 @@ impl Transaction {
     }
 }
 
+// ==================
+// Snapshot Class
+// ==================
+
+#[napi]
+pub struct Snapshot {
+    inner: Option<CoreSnapshot>,
+}
+
+#[napi]
+impl Snapshot {
+    /// Gets a read-only collection reference that reads this snapshot
+    #[napi]
+    pub fn get_collection(&self, name: String) -> Result<Collection> {
+        let snapshot = self.inner.as_ref()
+            .ok_or_else(|| Error::from_reason("Snapshot is released"))?;
+
+        Ok(Collection { inner: Some(snapshot.collection(&name)) })
+    }
+
+    /// Lists the collections that existed when the snapshot was taken
+    #[napi]
+    pub fn list_collections(&self) -> Result<Vec<String>> {
+        let snapshot = self.inner.as_ref()
+            .ok_or_else(|| Error::from_reason("Snapshot is released"))?;
+
+        Ok(snapshot.list_collections())
+    }
+
+    /// Gets the ID of the last transaction the snapshot sees
+    #[napi]
+    pub fn snapshot_id(&self) -> Result<i64> {
+        let snapshot = self.inner.as_ref()
+            .ok_or_else(|| Error::from_reason("Snapshot is released"))?;
+
+        Ok(snapshot.snapshot_id() as i64)
+    }
+
+    /// Releases the snapshot without waiting for garbage collection of the JS
+    /// object. Collections already taken from it keep it pinned until they
+    /// are collected.
+    #[napi]
+    pub fn release(&mut self) {
+        self.inner.take();
+    }
+}
+
+// ==================
+// KvStore Class
+// ==================
+
+#[napi]
+pub struct KvStore {
+    inner: CoreKvStore,
+}
+
+#[napi]
+impl KvStore {
+    /// Gets the value stored under key, or null if it isn't set
+    #[napi(ts_return_type = "any")]
+    pub fn get(&self, key: String) -> Result<Option<serde_json::Value>> {
+        self.inner.get(&key)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Stores value, an object, under key, replacing what was there
+    #[napi(ts_args_type = "key: string, value: any")]
+    pub fn set(&self, key: String, value: serde_json::Value) -> Result<()> {
+        self.inner.set(&key, value)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Gets several values against one snapshot, in the order given, with null for keys that aren't set
+    #[napi(ts_return_type = "any[]")]
+    pub fn mget(&self, keys: Vec<String>) -> Result<Vec<Option<serde_json::Value>>> {
+        let key_refs: Vec<&str> = keys.iter().map(|s| s.as_str()).collect();
+        self.inner.mget(&key_refs)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Stores every key-value pair of an object in one transaction
+    #[napi(ts_args_type = "pairs: Record<string, any>")]
+    pub fn mset(&self, pairs: serde_json::Value) -> Result<()> {
+        let pairs = pairs.as_object()
+            .ok_or_else(|| Error::from_reason("pairs must be an object"))?;
+        let pair_refs: Vec<(&str, serde_json::Value)> = pairs.iter()
+            .map(|(key, value)| (key.as_str(), value.clone()))
+            .collect();
+        self.inner.mset(&pair_refs)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Removes key, returning whether it was set
+    #[napi]
+    pub fn delete(&self, key: String) -> Result<bool> {
+        self.inner.delete(&key)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+}
+
 // ==================
 // Collection Class
 // ==================
@@ -613,6 +1072,37 @@ impl Collection {
             .map_err(|e| Error::from_reason(e.to_string()))
     }
 
+    /// Finds several documents by ID, in the order given, with null for missing IDs
+    #[napi(ts_return_type = "any[]")]
+    pub fn find_by_ids(&self, ids: Vec<String>) -> Result<Vec<Option<serde_json::Value>>> {
+        let coll = self.inner.as_ref()
+            .ok_or_else(|| Error::from_reason("Collection is closed"))?;
+
+        let id_refs: Vec<&str> = ids.iter().map(|s| s.as_str()).collect();
+        coll.find_by_ids(&id_refs)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Finds documents with an _id from startId up to endId, in _id order; endId is included only when inclusive is set
+    #[napi(ts_return_type = "any[]")]
+    pub fn scan_range(&self, start_id: String, end_id: String, inclusive: bool) -> Result<Vec<serde_json::Value>> {
+        let coll = self.inner.as_ref()
+            .ok_or_else(|| Error::from_reason("Collection is closed"))?;
+
+        coll.scan_range(&start_id, &end_id, inclusive)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Finds up to limit documents with an _id of startId or later, in _id order
+    #[napi(ts_return_type = "any[]")]
+    pub fn scan_from(&self, start_id: String, limit: u32) -> Result<Vec<serde_json::Value>> {
+        let coll = self.inner.as_ref()
+            .ok_or_else(|| Error::from_reason("Collection is closed"))?;
+
+        coll.scan_from(&start_id, limit as usize)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
     /// Updates a document by ID
     #[napi(ts_args_type = "id: string, doc: any")]
     pub fn update_by_id(&mut self, id: String, doc: serde_json::Value) -> Result<()> {
@@ -656,26 +1146,65 @@ impl Collection {
 
     // Query/Filter Operations
 
-    /// Finds documents matching a filter
+    /// Finds documents matching a filter, optionally projected
     #[napi(ts_return_type = "any[]")]
-    pub fn find(&self, filter: String) -> Result<Vec<serde_json::Value>> {
+    pub fn find(&self, filter: String, projection: Option<JsProjection>) -> Result<Vec<serde_json::Value>> {
+        let coll = self.inner.as_ref()
+            .ok_or_else(|| Error::from_reason("Collection is closed"))?;
+
+        match projection {
+            Some(projection) => projection.find(coll, &filter),
+            None => coll.find(&filter),
+        }
+        .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Finds documents matching a filter whose `?1`, `?2`, ... placeholders
+    /// are bound to `params`
+    #[napi(ts_args_type = "filter: string, params: any[]", ts_return_type = "any[]")]
+    pub fn find_params(&self, filter: String, params: Vec<serde_json::Value>) -> Result<Vec<serde_json::Value>> {
         let coll = self.inner.as_ref()
             .ok_or_else(|| Error::from_reason("Collection is closed"))?;
 
-        coll.find(&filter)
+        coll.find_params(&filter, &params)
             .map_err(|e| Error::from_reason(e.to_string()))
     }
 
-    /// Finds first document matching a filter
-    #[napi(ts_return_type = "any | null")]
-    pub fn find_one(&self, filter: String) -> Result<Option<serde_json::Value>> {
+    /// Finds the first document matching a filter whose placeholders are
+    /// bound to `params`
+    #[napi(ts_args_type = "filter: string, params: any[]", ts_return_type = "any | null")]
+    pub fn find_one_params(&self, filter: String, params: Vec<serde_json::Value>) -> Result<Option<serde_json::Value>> {
         let coll = self.inner.as_ref()
             .ok_or_else(|| Error::from_reason("Collection is closed"))?;
 
-        coll.find_one(&filter)
+        coll.find_one_params(&filter, &params)
             .map_err(|e| Error::from_reason(e.to_string()))
     }
 
+    /// Runs a query and reports how it was executed
+    #[napi(ts_return_type = "any")]
+    pub fn explain(&self, filter: String) -> Result<serde_json::Value> {
+        let coll = self.inner.as_ref()
+            .ok_or_else(|| Error::from_reason("Collection is closed"))?;
+
+        coll.explain(&filter)
+            .and_then(|explain| serde_json::to_value(explain).map_err(|e| e.into()))
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Finds first document matching a filter, optionally projected
+    #[napi(ts_return_type = "any | null")]
+    pub fn find_one(&self, filter: String, projection: Option<JsProjection>) -> Result<Option<serde_json::Value>> {
+        let coll = self.inner.as_ref()
+            .ok_or_else(|| Error::from_reason("Collection is closed"))?;
+
+        match projection {
+            Some(projection) => projection.find_one(coll, &filter),
+            None => coll.find_one(&filter),
+        }
+        .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
     /// Updates all documents matching a filter
     #[napi(ts_args_type = "filter: string, update: any")]
     pub fn update(&mut self, filter: String, update: serde_json::Value) -> Result<u32> {
@@ -697,6 +1226,26 @@ impl Collection {
             .map_err(|e| Error::from_reason(e.to_string()))
     }
 
+    /// Updates all documents matching a filter, returning the modified ids
+    #[napi(ts_args_type = "filter: string, update: any")]
+    pub fn update_returning(&mut self, filter: String, update: serde_json::Value) -> Result<Vec<String>> {
+        let coll = self.inner.as_mut()
+            .ok_or_else(|| Error::from_reason("Collection is closed"))?;
+
+        coll.update_returning(&filter, update)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Updates first document matching a filter, returning its id or null
+    #[napi(ts_args_type = "filter: string, update: any")]
+    pub fn update_one_returning(&mut self, filter: String, update: serde_json::Value) -> Result<Option<String>> {
+        let coll = self.inner.as_mut()
+            .ok_or_else(|| Error::from_reason("Collection is closed"))?;
+
+        coll.update_one_returning(&filter, update)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
     /// Deletes all documents matching a filter
     #[napi]
     pub fn delete(&mut self, filter: String) -> Result<u32> {
@@ -718,6 +1267,43 @@ impl Collection {
             .map_err(|e| Error::from_reason(e.to_string()))
     }
 
+    /// Atomically updates the first document matching a filter and returns it,
+    /// after the update if `return_new` is true. `sort_field` picks which match
+    /// is first. Returns null if nothing matches.
+    #[napi(ts_args_type = "filter: string, update: any, returnNew?: boolean, sortField?: string, sortAsc?: boolean", ts_return_type = "any | null")]
+    pub fn find_one_and_update(
+        &mut self,
+        filter: String,
+        update: serde_json::Value,
+        return_new: Option<bool>,
+        sort_field: Option<String>,
+        sort_asc: Option<bool>,
+    ) -> Result<Option<serde_json::Value>> {
+        let coll = self.inner.as_mut()
+            .ok_or_else(|| Error::from_reason("Collection is closed"))?;
+
+        find_and_modify_query(coll, &filter, sort_field, sort_asc)
+            .find_one_and_update(update, return_new.unwrap_or(false))
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Atomically deletes the first document matching a filter and returns it.
+    /// `sort_field` picks which match is first. Returns null if nothing matches.
+    #[napi(ts_return_type = "any | null")]
+    pub fn find_one_and_delete(
+        &mut self,
+        filter: String,
+        sort_field: Option<String>,
+        sort_asc: Option<bool>,
+    ) -> Result<Option<serde_json::Value>> {
+        let coll = self.inner.as_mut()
+            .ok_or_else(|| Error::from_reason("Collection is closed"))?;
+
+        find_and_modify_query(coll, &filter, sort_field, sort_asc)
+            .find_one_and_delete()
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
     // Upsert Operations
 
     /// Upserts a document by ID
@@ -748,6 +1334,35 @@ impl Collection {
             .map_err(|e| Error::from_reason(e.to_string()))
     }
 
+    /// Upserts a document by ID, merging into an existing document (nested
+    /// objects included) instead of setting its top-level fields wholesale
+    #[napi(ts_args_type = "id: string, doc: any")]
+    pub fn upsert_merge_by_id(&mut self, id: String, doc: serde_json::Value) -> Result<JsUpsertResult> {
+        let coll = self.inner.as_mut()
+            .ok_or_else(|| Error::from_reason("Collection is closed"))?;
+
+        coll.upsert_merge_by_id(&id, doc)
+            .map(|result| match result {
+                jasonisnthappy::UpsertResult::Inserted(id) => JsUpsertResult { id, inserted: true },
+                jasonisnthappy::UpsertResult::Updated(id) => JsUpsertResult { id, inserted: false },
+            })
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Upserts a document matching a filter, merging into the first match
+    #[napi(ts_args_type = "filter: string, doc: any")]
+    pub fn upsert_merge(&mut self, filter: String, doc: serde_json::Value) -> Result<JsUpsertResult> {
+        let coll = self.inner.as_mut()
+            .ok_or_else(|| Error::from_reason("Collection is closed"))?;
+
+        coll.upsert_merge(&filter, doc)
+            .map(|result| match result {
+                jasonisnthappy::UpsertResult::Inserted(id) => JsUpsertResult { id, inserted: true },
+                jasonisnthappy::UpsertResult::Updated(id) => JsUpsertResult { id, inserted: false },
+            })
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
     // Bulk Operations
 
     /// Inserts multiple documents
@@ -762,14 +1377,17 @@ impl Collection {
 
     // Advanced Operations
 
-    /// Gets distinct values for a field
+    /// Gets distinct values for a field, optionally among documents matching a filter
     #[napi(ts_return_type = "any[]")]
-    pub fn distinct(&self, field: String) -> Result<Vec<serde_json::Value>> {
+    pub fn distinct(&self, field: String, filter: Option<String>) -> Result<Vec<serde_json::Value>> {
         let coll = self.inner.as_ref()
             .ok_or_else(|| Error::from_reason("Collection is closed"))?;
 
-        coll.distinct(&field)
-            .map_err(|e| Error::from_reason(e.to_string()))
+        match filter {
+            Some(filter) => coll.distinct_with_query(&field, &filter),
+            None => coll.distinct(&field),
+        }
+        .map_err(|e| Error::from_reason(e.to_string()))
     }
 
     /// Counts distinct values for a field
@@ -822,11 +1440,12 @@ impl Collection {
         skip: Option<u32>,
         project_fields: Option<Vec<String>>,
         exclude_fields: Option<Vec<String>>,
+        collation: Option<String>,
     ) -> Result<Vec<serde_json::Value>> {
         let coll = self.inner.as_ref()
             .ok_or_else(|| Error::from_reason("Collection is closed"))?;
 
-        let mut query = coll.query();
+        let mut query = coll.query().collation(parse_collation(collation)?);
 
         if let Some(f) = filter {
             query = query.filter(&f);
@@ -918,52 +1537,7 @@ impl Collection {
         let coll = self.inner.as_mut()
             .ok_or_else(|| Error::from_reason("Collection is closed"))?;
 
-        let mut bulk = coll.bulk_write();
-
-        if ordered.unwrap_or(true) {
-            bulk = bulk.ordered(true);
-        }
-
-        // Parse operations
-        for op in operations {
-            let op_obj = op.as_object()
-                .ok_or_else(|| Error::from_reason("Invalid operation format"))?;
-
-            let op_type = op_obj.get("op")
-                .and_then(|v| v.as_str())
-                .ok_or_else(|| Error::from_reason("Missing 'op' field"))?;
-
-            match op_type {
-                "insert" => {
-                    let doc = op_obj.get("doc")
-                        .ok_or_else(|| Error::from_reason("Missing 'doc' field for insert"))?
-                        .clone();
-                    bulk = bulk.insert(doc);
-                }
-                "update_one" => {
-                    let filter = op_obj.get("filter")
-                        .and_then(|v| v.as_str())
-                        .ok_or_else(|| Error::from_reason("Missing 'filter' field"))?;
-                    let update = op_obj.get("update")
-                        .ok_or_else(|| Error::from_reason("Missing 'update' field"))?
-                        .clone();
-                    bulk = bulk.update_one(filter, update);
-                }
-                "delete" | "delete_many" => {
-                    let filter = op_obj.get("filter")
-                        .and_then(|v| v.as_str())
-                        .ok_or_else(|| Error::from_reason("Missing 'filter' field"))?;
-                    bulk = bulk.delete_many(filter);
-                }
-                _ => return Err(Error::from_reason(format!("Unknown operation type: {}", op_type))),
-            }
-        }
-
-        let result = bulk.execute()
-            .map_err(|e| Error::from_reason(e.to_string()))?;
-
-        serde_json::to_value(result)
-            .map_err(|e| Error::from_reason(e.to_string()))
+        run_bulk_write(coll, operations, ordered)
     }
 
     // Aggregation
@@ -974,91 +1548,22 @@ impl Collection {
         let coll = self.inner.as_ref()
             .ok_or_else(|| Error::from_reason("Collection is closed"))?;
 
-        let mut agg = coll.aggregate();
-
-        // Parse pipeline stages
-        for stage in pipeline {
-            let stage_obj = stage.as_object()
-                .ok_or_else(|| Error::from_reason("Invalid pipeline stage format"))?;
-
-            if let Some(match_filter) = stage_obj.get("match").and_then(|v| v.as_str()) {
-                agg = agg.match_(match_filter);
-            }
-            if let Some(group_by) = stage_obj.get("group_by").and_then(|v| v.as_str()) {
-                agg = agg.group_by(group_by);
-            }
-            if let Some(count_field) = stage_obj.get("count").and_then(|v| v.as_str()) {
-                agg = agg.count(count_field);
-            }
-            if let Some(sum_obj) = stage_obj.get("sum").and_then(|v| v.as_object()) {
-                let field = sum_obj.get("field").and_then(|v| v.as_str())
-                    .ok_or_else(|| Error::from_reason("Missing 'field' in sum"))?;
-                let output = sum_obj.get("output").and_then(|v| v.as_str())
-                    .ok_or_else(|| Error::from_reason("Missing 'output' in sum"))?;
-                agg = agg.sum(field, output);
-            }
-            if let Some(avg_obj) = stage_obj.get("avg").and_then(|v| v.as_object()) {
-                let field = avg_obj.get("field").and_then(|v| v.as_str())
-                    .ok_or_else(|| Error::from_reason("Missing 'field' in avg"))?;
-                let output = avg_obj.get("output").and_then(|v| v.as_str())
-                    .ok_or_else(|| Error::from_reason("Missing 'output' in avg"))?;
-                agg = agg.avg(field, output);
-            }
-            if let Some(min_obj) = stage_obj.get("min").and_then(|v| v.as_object()) {
-                let field = min_obj.get("field").and_then(|v| v.as_str())
-                    .ok_or_else(|| Error::from_reason("Missing 'field' in min"))?;
-                let output = min_obj.get("output").and_then(|v| v.as_str())
-                    .ok_or_else(|| Error::from_reason("Missing 'output' in min"))?;
-                agg = agg.min(field, output);
-            }
-            if let Some(max_obj) = stage_obj.get("max").and_then(|v| v.as_object()) {
-                let field = max_obj.get("field").and_then(|v| v.as_str())
-                    .ok_or_else(|| Error::from_reason("Missing 'field' in max"))?;
-                let output = max_obj.get("output").and_then(|v| v.as_str())
-                    .ok_or_else(|| Error::from_reason("Missing 'output' in max"))?;
-                agg = agg.max(field, output);
-            }
-            if let Some(sort_obj) = stage_obj.get("sort").and_then(|v| v.as_object()) {
-                let field = sort_obj.get("field").and_then(|v| v.as_str())
-                    .ok_or_else(|| Error::from_reason("Missing 'field' in sort"))?;
-                let asc = sort_obj.get("asc").and_then(|v| v.as_bool()).unwrap_or(true);
-                agg = agg.sort(field, asc);
-            }
-            if let Some(limit_val) = stage_obj.get("limit").and_then(|v| v.as_u64()) {
-                agg = agg.limit(limit_val as usize);
-            }
-            if let Some(skip_val) = stage_obj.get("skip").and_then(|v| v.as_u64()) {
-                agg = agg.skip(skip_val as usize);
-            }
-            if let Some(project_arr) = stage_obj.get("project").and_then(|v| v.as_array()) {
-                let fields: Vec<String> = project_arr.iter()
-                    .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                    .collect();
-                let field_refs: Vec<&str> = fields.iter().map(|s| s.as_str()).collect();
-                agg = agg.project(&field_refs);
-            }
-            if let Some(exclude_arr) = stage_obj.get("exclude").and_then(|v| v.as_array()) {
-                let fields: Vec<String> = exclude_arr.iter()
-                    .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                    .collect();
-                let field_refs: Vec<&str> = fields.iter().map(|s| s.as_str()).collect();
-                agg = agg.exclude(&field_refs);
-            }
-        }
-
-        agg.execute()
-            .map_err(|e| Error::from_reason(e.to_string()))
+        run_aggregate(coll, pipeline)
     }
 
     // Watch / Change Streams
 
     /// Starts watching for changes on the collection
-    /// The callback receives (operation: string, docId: string, document: any | null)
-    #[napi(ts_args_type = "filter: string | undefined, callback: (operation: string, docId: string, document: any) => void")]
+    /// The callback receives (operation: string, docId: string, document: any | null, oldDocument: any | null)
+    /// oldDocument is only populated for updates and deletes when fullDocumentBeforeChange is true
+    /// operations ("insert", "update", "delete") limits the events passed to the callback
+    #[napi(ts_args_type = "filter: string | undefined, callback: (operation: string, docId: string, document: any, oldDocument: any) => void, fullDocumentBeforeChange?: boolean, operations?: string[]")]
     pub fn watch(
         &self,
         filter: Option<String>,
-        callback: Function<(String, String, serde_json::Value), ()>,
+        callback: Function<(String, String, serde_json::Value, serde_json::Value), ()>,
+        full_document_before_change: Option<bool>,
+        operations: Option<Vec<String>>,
     ) -> Result<WatchHandle> {
         let coll = self.inner.as_ref()
             .ok_or_else(|| Error::from_reason("Collection is closed"))?;
@@ -1068,6 +1573,16 @@ impl Collection {
         if let Some(f) = filter {
             builder = builder.filter(&f);
         }
+        if let Some(names) = operations {
+            let ops = names.iter()
+                .map(|name| ChangeOperation::parse(name)
+                    .ok_or_else(|| Error::from_reason(format!("Invalid watch operation: {}", name))))
+                .collect::<Result<Vec<_>>>()?;
+            builder = builder.operations(&ops);
+        }
+        if full_document_before_change.unwrap_or(false) {
+            builder = builder.with_full_document_before_change();
+        }
 
         // Subscribe to get the channel and handle
         let (rust_handle, receiver) = builder.subscribe()
@@ -1094,7 +1609,8 @@ impl Collection {
                             ChangeOperation::Delete => "delete".to_string(),
                         };
                         let doc = event.document.unwrap_or(serde_json::Value::Null);
-                        tsfn.call((op_str, event.doc_id, doc), ThreadsafeFunctionCallMode::NonBlocking);
+                        let old_doc = event.old_document.unwrap_or(serde_json::Value::Null);
+                        tsfn.call((op_str, event.doc_id, doc, old_doc), ThreadsafeFunctionCallMode::NonBlocking);
                     }
                     Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
                         // Continue checking stop flag
@@ -1113,6 +1629,457 @@ impl Collection {
             thread_handle: Some(thread_handle),
         })
     }
+
+    // Streaming
+
+    /// Streams the documents matching `filter` in batches of `batchSize`
+    /// (default 100), read on a worker thread that stays at most one batch
+    /// ahead of the consumer. Use with `for await (const doc of stream)`.
+    #[napi(ts_return_type = "FindStream")]
+    pub fn find_stream(&self, filter: String, batch_size: Option<u32>) -> Result<FindStream> {
+        let coll = self.inner.as_ref()
+            .ok_or_else(|| Error::from_reason("Collection is closed"))?
+            .clone();
+        let batch_size = batch_size.unwrap_or(100).max(1) as usize;
+
+        // Holds one batch, so the worker blocks until the previous one is taken
+        let (sender, receiver) = std::sync::mpsc::sync_channel(1);
+        thread::spawn(move || {
+            let batches = match coll.find_batches(&filter, batch_size) {
+                Ok(batches) => batches,
+                Err(e) => {
+                    let _ = sender.send(Err(e));
+                    return;
+                }
+            };
+            for batch in batches {
+                // The stream was closed
+                if sender.send(batch).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Ok(FindStream {
+            receiver: Arc::new(std::sync::Mutex::new(Some(receiver))),
+        })
+    }
+
+    // Async Operations
+    //
+    // These run on the libuv thread pool and resolve a Promise, leaving the
+    // event loop free during long scans and large writes.
+
+    /// Inserts a document without blocking the event loop
+    #[napi(ts_args_type = "doc: any", ts_return_type = "Promise<string>")]
+    pub fn insert_async(&self, doc: serde_json::Value) -> Result<AsyncTask<CollectionTask<String>>> {
+        self.spawn(move |coll| coll.insert(doc))
+    }
+
+    /// Inserts multiple documents without blocking the event loop
+    #[napi(ts_args_type = "docs: any[]", ts_return_type = "Promise<string[]>")]
+    pub fn insert_many_async(&self, docs: Vec<serde_json::Value>) -> Result<AsyncTask<CollectionTask<Vec<String>>>> {
+        self.spawn(move |coll| coll.insert_many(docs))
+    }
+
+    /// Finds a document by ID without blocking the event loop
+    #[napi(ts_return_type = "Promise<any>")]
+    pub fn find_by_id_async(&self, id: String) -> Result<AsyncTask<CollectionTask<serde_json::Value>>> {
+        self.spawn(move |coll| coll.find_by_id(&id))
+    }
+
+    /// Finds all documents without blocking the event loop
+    #[napi(ts_return_type = "Promise<any[]>")]
+    pub fn find_all_async(&self) -> Result<AsyncTask<CollectionTask<Vec<serde_json::Value>>>> {
+        self.spawn(|coll| coll.find_all())
+    }
+
+    /// Counts all documents without blocking the event loop
+    #[napi(ts_return_type = "Promise<number>")]
+    pub fn count_async(&self) -> Result<AsyncTask<CollectionTask<u32>>> {
+        self.spawn(|coll| coll.count().map(|c| c as u32))
+    }
+
+    /// Finds documents matching a filter without blocking the event loop
+    #[napi(ts_return_type = "Promise<any[]>")]
+    pub fn find_async(&self, filter: String, projection: Option<JsProjection>) -> Result<AsyncTask<CollectionTask<Vec<serde_json::Value>>>> {
+        self.spawn(move |coll| match &projection {
+            Some(projection) => projection.find(coll, &filter),
+            None => coll.find(&filter),
+        })
+    }
+
+    /// Finds first document matching a filter without blocking the event loop
+    #[napi(ts_return_type = "Promise<any | null>")]
+    pub fn find_one_async(&self, filter: String, projection: Option<JsProjection>) -> Result<AsyncTask<CollectionTask<Option<serde_json::Value>>>> {
+        self.spawn(move |coll| match &projection {
+            Some(projection) => projection.find_one(coll, &filter),
+            None => coll.find_one(&filter),
+        })
+    }
+
+    /// Updates all documents matching a filter without blocking the event loop
+    #[napi(ts_args_type = "filter: string, update: any", ts_return_type = "Promise<number>")]
+    pub fn update_async(&self, filter: String, update: serde_json::Value) -> Result<AsyncTask<CollectionTask<u32>>> {
+        self.spawn(move |coll| coll.update(&filter, update).map(|c| c as u32))
+    }
+
+    /// Updates all documents matching a filter without blocking the event loop,
+    /// returning the modified ids
+    #[napi(ts_args_type = "filter: string, update: any", ts_return_type = "Promise<string[]>")]
+    pub fn update_returning_async(&self, filter: String, update: serde_json::Value) -> Result<AsyncTask<CollectionTask<Vec<String>>>> {
+        self.spawn(move |coll| coll.update_returning(&filter, update))
+    }
+
+    /// Deletes all documents matching a filter without blocking the event loop
+    #[napi(ts_return_type = "Promise<number>")]
+    pub fn delete_async(&self, filter: String) -> Result<AsyncTask<CollectionTask<u32>>> {
+        self.spawn(move |coll| coll.delete(&filter).map(|c| c as u32))
+    }
+
+    /// Executes multiple operations in a transaction without blocking the event loop
+    #[napi(ts_args_type = "operations: any[], ordered?: boolean", ts_return_type = "Promise<any>")]
+    pub fn bulk_write_async(&self, operations: Vec<serde_json::Value>, ordered: Option<bool>) -> Result<AsyncTask<CollectionTask<serde_json::Value>>> {
+        self.spawn_napi(move |coll| run_bulk_write(coll, operations, ordered))
+    }
+
+    /// Executes an aggregation pipeline without blocking the event loop
+    #[napi(ts_args_type = "pipeline: any[]", ts_return_type = "Promise<any[]>")]
+    pub fn aggregate_async(&self, pipeline: Vec<serde_json::Value>) -> Result<AsyncTask<CollectionTask<Vec<serde_json::Value>>>> {
+        self.spawn_napi(move |coll| run_aggregate(coll, pipeline))
+    }
+}
+
+impl Collection {
+    /// Runs `work` against this collection on the libuv thread pool
+    fn spawn<T, F>(&self, work: F) -> Result<AsyncTask<CollectionTask<T>>>
+    where
+        T: ToNapiValue + TypeName + Send + 'static,
+        F: FnOnce(&CoreCollection) -> jasonisnthappy::Result<T> + Send + 'static,
+    {
+        self.spawn_napi(move |coll| work(coll).map_err(|e| Error::from_reason(e.to_string())))
+    }
+
+    /// Like `spawn`, for work that already reports napi errors
+    fn spawn_napi<T, F>(&self, work: F) -> Result<AsyncTask<CollectionTask<T>>>
+    where
+        T: ToNapiValue + TypeName + Send + 'static,
+        F: FnOnce(&CoreCollection) -> Result<T> + Send + 'static,
+    {
+        // Collections are cheap handles onto the shared database
+        let coll = self.inner.as_ref()
+            .ok_or_else(|| Error::from_reason("Collection is closed"))?
+            .clone();
+
+        Ok(AsyncTask::new(CollectionTask {
+            coll,
+            work: Some(Box::new(work)),
+        }))
+    }
+}
+
+/// Collection call run off the JS thread by the `*Async` methods
+pub struct CollectionTask<T> {
+    coll: CoreCollection,
+    work: Option<Box<dyn FnOnce(&CoreCollection) -> Result<T> + Send>>,
+}
+
+impl<T> Task for CollectionTask<T>
+where
+    T: ToNapiValue + TypeName + Send + 'static,
+{
+    type Output = T;
+    type JsValue = T;
+
+    fn compute(&mut self) -> Result<T> {
+        let work = self.work.take()
+            .ok_or_else(|| Error::from_reason("Task already ran"))?;
+        work(&self.coll)
+    }
+
+    fn resolve(&mut self, _env: Env, output: T) -> Result<T> {
+        Ok(output)
+    }
+}
+
+// ==================
+// FindStream
+// ==================
+
+type BatchReceiver = std::sync::mpsc::Receiver<jasonisnthappy::Result<Vec<serde_json::Value>>>;
+
+/// Batches of a `findStream` query. `index.js` adds `Symbol.asyncIterator`,
+/// yielding the documents of each batch in turn.
+#[napi]
+pub struct FindStream {
+    receiver: Arc<std::sync::Mutex<Option<BatchReceiver>>>,
+}
+
+#[napi]
+impl FindStream {
+    /// Resolves to the next batch, or null once every batch has been read
+    #[napi(ts_return_type = "Promise<any[] | null>")]
+    pub fn next_batch(&self) -> AsyncTask<NextBatchTask> {
+        AsyncTask::new(NextBatchTask { receiver: self.receiver.clone() })
+    }
+
+    /// Stops the worker thread and releases the query's snapshot
+    #[napi]
+    pub fn close(&self) {
+        if let Ok(mut receiver) = self.receiver.lock() {
+            receiver.take();
+        }
+    }
+}
+
+/// Waits off the JS thread for the next batch of a `FindStream`
+pub struct NextBatchTask {
+    receiver: Arc<std::sync::Mutex<Option<BatchReceiver>>>,
+}
+
+impl Task for NextBatchTask {
+    type Output = Option<Vec<serde_json::Value>>;
+    type JsValue = Option<Vec<serde_json::Value>>;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let mut receiver = self.receiver.lock()
+            .map_err(|_| Error::from_reason("FindStream lock poisoned"))?;
+        let batch = match receiver.as_ref() {
+            Some(batches) => batches.recv(),
+            None => return Ok(None),
+        };
+        match batch {
+            Ok(Ok(batch)) => Ok(Some(batch)),
+            Ok(Err(e)) => {
+                receiver.take();
+                Err(Error::from_reason(e.to_string()))
+            }
+            // The worker finished
+            Err(_) => {
+                receiver.take();
+                Ok(None)
+            }
+        }
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(output)
+    }
+}
+
+/// Collation named "binary", "ascii_case_insensitive" or "case_insensitive",
+/// binary when not given
+fn parse_collation(collation: Option<String>) -> Result<Collation> {
+    match collation {
+        Some(name) => serde_json::from_value(serde_json::Value::String(name.clone()))
+            .map_err(|_| Error::from_reason(format!("Unknown collation: {}", name))),
+        None => Ok(Collation::Binary),
+    }
+}
+
+/// Query for `filter`, sorted by `sort_field` if given, ascending by default
+fn find_and_modify_query<'a>(
+    coll: &'a CoreCollection,
+    filter: &str,
+    sort_field: Option<String>,
+    sort_asc: Option<bool>,
+) -> jasonisnthappy::core::QueryBuilder<'a> {
+    let query = coll.query().filter(filter);
+    match sort_field {
+        Some(field) => {
+            let order = if sort_asc.unwrap_or(true) { SortOrder::Asc } else { SortOrder::Desc };
+            query.sort_by(&field, order)
+        }
+        None => query,
+    }
+}
+
+fn run_bulk_write(coll: &CoreCollection, operations: Vec<serde_json::Value>, ordered: Option<bool>) -> Result<serde_json::Value> {
+    let mut bulk = coll.bulk_write();
+
+    if ordered.unwrap_or(true) {
+        bulk = bulk.ordered(true);
+    }
+
+    // Parse operations
+    for op in operations {
+        let op_obj = op.as_object()
+            .ok_or_else(|| Error::from_reason("Invalid operation format"))?;
+
+        let op_type = op_obj.get("op")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| Error::from_reason("Missing 'op' field"))?;
+
+        match op_type {
+            "insert" => {
+                let doc = op_obj.get("doc")
+                    .ok_or_else(|| Error::from_reason("Missing 'doc' field for insert"))?
+                    .clone();
+                bulk = bulk.insert(doc);
+            }
+            "update_one" => {
+                let filter = op_obj.get("filter")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| Error::from_reason("Missing 'filter' field"))?;
+                let update = op_obj.get("update")
+                    .ok_or_else(|| Error::from_reason("Missing 'update' field"))?
+                    .clone();
+                bulk = bulk.update_one(filter, update);
+            }
+            "delete" | "delete_many" => {
+                let filter = op_obj.get("filter")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| Error::from_reason("Missing 'filter' field"))?;
+                bulk = bulk.delete_many(filter);
+            }
+            "upsert_one" => {
+                let filter = op_obj.get("filter")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| Error::from_reason("Missing 'filter' field"))?;
+                let doc = op_obj.get("doc")
+                    .ok_or_else(|| Error::from_reason("Missing 'doc' field for upsert_one"))?
+                    .clone();
+                bulk = bulk.upsert_one(filter, doc);
+            }
+            "upsert_by_id" => {
+                let id = op_obj.get("id")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| Error::from_reason("Missing 'id' field"))?;
+                let doc = op_obj.get("doc")
+                    .ok_or_else(|| Error::from_reason("Missing 'doc' field for upsert_by_id"))?
+                    .clone();
+                bulk = bulk.upsert_by_id(id, doc);
+            }
+            _ => return Err(Error::from_reason(format!("Unknown operation type: {}", op_type))),
+        }
+    }
+
+    let result = bulk.execute()
+        .map_err(|e| Error::from_reason(e.to_string()))?;
+
+    serde_json::to_value(result)
+        .map_err(|e| Error::from_reason(e.to_string()))
+}
+
+fn run_aggregate(coll: &CoreCollection, pipeline: Vec<serde_json::Value>) -> Result<Vec<serde_json::Value>> {
+    let mut agg = coll.aggregate();
+    let mut out = None;
+
+    // Parse pipeline stages
+    let stage_count = pipeline.len();
+    for (index, stage) in pipeline.into_iter().enumerate() {
+        let stage_obj = stage.as_object()
+            .ok_or_else(|| Error::from_reason("Invalid pipeline stage format"))?;
+
+        if let Some(match_filter) = stage_obj.get("match").and_then(|v| v.as_str()) {
+            agg = agg.match_(match_filter);
+        }
+        match stage_obj.get("group_by") {
+            Some(serde_json::Value::String(group_by)) => agg = agg.group_by(group_by),
+            Some(serde_json::Value::Array(fields)) => {
+                let fields: Vec<&str> = fields.iter()
+                    .map(|v| v.as_str())
+                    .collect::<Option<_>>()
+                    .filter(|f: &Vec<&str>| !f.is_empty())
+                    .ok_or_else(|| Error::from_reason("'group_by' array must be non-empty field names"))?;
+                agg = agg.group_by_multi(&fields);
+            }
+            _ => {}
+        }
+        if let Some(group_obj) = stage_obj.get("group_by_date").and_then(|v| v.as_object()) {
+            let field = group_obj.get("field").and_then(|v| v.as_str())
+                .ok_or_else(|| Error::from_reason("Missing 'field' in group_by_date"))?;
+            let granularity = group_obj.get("granularity").and_then(|v| v.as_str())
+                .and_then(DateGranularity::parse)
+                .ok_or_else(|| Error::from_reason("'granularity' in group_by_date must be day, month or year"))?;
+            agg = agg.group_by_date(field, granularity);
+        }
+        if let Some(count_field) = stage_obj.get("count").and_then(|v| v.as_str()) {
+            agg = agg.count(count_field);
+        }
+        if let Some(sum_obj) = stage_obj.get("sum").and_then(|v| v.as_object()) {
+            let field = sum_obj.get("field").and_then(|v| v.as_str())
+                .ok_or_else(|| Error::from_reason("Missing 'field' in sum"))?;
+            let output = sum_obj.get("output").and_then(|v| v.as_str())
+                .ok_or_else(|| Error::from_reason("Missing 'output' in sum"))?;
+            agg = agg.sum(field, output);
+        }
+        if let Some(avg_obj) = stage_obj.get("avg").and_then(|v| v.as_object()) {
+            let field = avg_obj.get("field").and_then(|v| v.as_str())
+                .ok_or_else(|| Error::from_reason("Missing 'field' in avg"))?;
+            let output = avg_obj.get("output").and_then(|v| v.as_str())
+                .ok_or_else(|| Error::from_reason("Missing 'output' in avg"))?;
+            agg = agg.avg(field, output);
+        }
+        if let Some(min_obj) = stage_obj.get("min").and_then(|v| v.as_object()) {
+            let field = min_obj.get("field").and_then(|v| v.as_str())
+                .ok_or_else(|| Error::from_reason("Missing 'field' in min"))?;
+            let output = min_obj.get("output").and_then(|v| v.as_str())
+                .ok_or_else(|| Error::from_reason("Missing 'output' in min"))?;
+            agg = agg.min(field, output);
+        }
+        if let Some(max_obj) = stage_obj.get("max").and_then(|v| v.as_object()) {
+            let field = max_obj.get("field").and_then(|v| v.as_str())
+                .ok_or_else(|| Error::from_reason("Missing 'field' in max"))?;
+            let output = max_obj.get("output").and_then(|v| v.as_str())
+                .ok_or_else(|| Error::from_reason("Missing 'output' in max"))?;
+            agg = agg.max(field, output);
+        }
+        if let Some(sort_obj) = stage_obj.get("sort").and_then(|v| v.as_object()) {
+            let field = sort_obj.get("field").and_then(|v| v.as_str())
+                .ok_or_else(|| Error::from_reason("Missing 'field' in sort"))?;
+            let asc = sort_obj.get("asc").and_then(|v| v.as_bool()).unwrap_or(true);
+            agg = agg.sort(field, asc);
+        }
+        if let Some(limit_val) = stage_obj.get("limit").and_then(|v| v.as_u64()) {
+            agg = agg.limit(limit_val as usize);
+        }
+        if let Some(skip_val) = stage_obj.get("skip").and_then(|v| v.as_u64()) {
+            agg = agg.skip(skip_val as usize);
+        }
+        if let Some(project_arr) = stage_obj.get("project").and_then(|v| v.as_array()) {
+            let fields: Vec<String> = project_arr.iter()
+                .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                .collect();
+            let field_refs: Vec<&str> = fields.iter().map(|s| s.as_str()).collect();
+            agg = agg.project(&field_refs);
+        }
+        if let Some(exclude_arr) = stage_obj.get("exclude").and_then(|v| v.as_array()) {
+            let fields: Vec<String> = exclude_arr.iter()
+                .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                .collect();
+            let field_refs: Vec<&str> = fields.iter().map(|s| s.as_str()).collect();
+            agg = agg.exclude(&field_refs);
+        }
+        if let Some(add_fields_obj) = stage_obj.get("add_fields").and_then(|v| v.as_object()) {
+            let mut fields = Vec::with_capacity(add_fields_obj.len());
+            for (name, expr_json) in add_fields_obj {
+                let expr = FieldExpr::from_json(expr_json)
+                    .map_err(|e| Error::from_reason(format!("Invalid expression for '{}': {}", name, e)))?;
+                fields.push((name.as_str(), expr));
+            }
+            agg = agg.add_fields(&fields);
+        }
+        if let Some(target) = stage_obj.get("out").and_then(|v| v.as_str()) {
+            if index + 1 != stage_count {
+                return Err(Error::from_reason("'out' must be the last stage"));
+            }
+            let mode = match stage_obj.get("mode") {
+                None => OutMode::Replace,
+                Some(mode) => mode.as_str().and_then(OutMode::parse)
+                    .ok_or_else(|| Error::from_reason("'mode' in out must be replace or merge"))?,
+            };
+            out = Some((target.to_string(), mode));
+        }
+    }
+
+    match out {
+        Some((target, mode)) => agg.out_with_mode(&target, mode)
+            .map(|written| vec![serde_json::json!({"out": target, "written": written})])
+            .map_err(|e| Error::from_reason(e.to_string())),
+        None => agg.execute()
+            .map_err(|e| Error::from_reason(e.to_string())),
+    }
 }
 
 // ==================
@@ -68,8 +68,15 @@ class CError(ctypes.Structure):
     ]
 
 
+# Values for CDatabaseOptions.durability
+DURABILITY_FULL = 0
+DURABILITY_NORMAL = 1
+DURABILITY_BATCHED = 2
+
+
 class CDatabaseOptions(ctypes.Structure):
     _fields_ = [
+        ("struct_size", ctypes.c_uint32),
         ("cache_size", ctypes.c_size_t),
         ("auto_checkpoint_threshold", ctypes.c_uint64),
         ("file_permissions", ctypes.c_uint32),
@@ -77,16 +84,44 @@ class CDatabaseOptions(ctypes.Structure):
         ("max_bulk_operations", ctypes.c_size_t),
         ("max_document_size", ctypes.c_size_t),
         ("max_request_body_size", ctypes.c_size_t),
+        ("document_metadata", ctypes.c_bool),
+        ("scan_threads", ctypes.c_size_t),
+        ("enable_id_bloom_filter", ctypes.c_bool),
+        ("page_size", ctypes.c_size_t),
+        ("max_wal_bytes", ctypes.c_uint64),
+        ("collect_all_validation_errors", ctypes.c_bool),
+        ("page_checksums", ctypes.c_bool),
+        ("lock_timeout_ms", ctypes.c_uint64),
+        ("max_scan_documents", ctypes.c_size_t),
+        ("max_result_documents", ctypes.c_size_t),
+        ("slow_query_threshold_ms", ctypes.c_uint64),
+        ("slow_query_log_size", ctypes.c_size_t),
+        ("durability", ctypes.c_uint32),
+        ("durability_interval_ms", ctypes.c_uint64),
+        ("strict_query_types", ctypes.c_bool),
+        ("query_cache_size", ctypes.c_size_t),
     ]
 
+    def __init__(self, *args, **kwargs):
+        super().__init__(*args, **kwargs)
+        self.struct_size = ctypes.sizeof(self)
+
 
 class CTransactionConfig(ctypes.Structure):
     _fields_ = [
+        ("struct_size", ctypes.c_uint32),
         ("max_retries", ctypes.c_size_t),
         ("retry_backoff_base_ms", ctypes.c_uint64),
         ("max_retry_backoff_ms", ctypes.c_uint64),
+        ("max_transaction_duration_ms", ctypes.c_uint64),
+        ("min_retry_backoff_ms", ctypes.c_uint64),
+        ("jitter", ctypes.c_bool),
     ]
 
+    def __init__(self, *args, **kwargs):
+        super().__init__(*args, **kwargs)
+        self.struct_size = ctypes.sizeof(self)
+
 
 # ==================
 # Function Signatures (85 total)
@@ -141,6 +176,12 @@ _lib.jasonisnthappy_collection_stats.restype = ctypes.c_int32
 _lib.jasonisnthappy_database_info.argtypes = [ctypes.c_void_p, ctypes.POINTER(ctypes.c_char_p), ctypes.POINTER(CError)]
 _lib.jasonisnthappy_database_info.restype = ctypes.c_int32
 
+_lib.jasonisnthappy_check_integrity.argtypes = [ctypes.c_void_p, ctypes.POINTER(ctypes.c_char_p), ctypes.POINTER(CError)]
+_lib.jasonisnthappy_check_integrity.restype = ctypes.c_int32
+
+_lib.jasonisnthappy_warm_cache.argtypes = [ctypes.c_void_p, ctypes.c_char_p, ctypes.c_bool, ctypes.POINTER(ctypes.c_char_p), ctypes.POINTER(CError)]
+_lib.jasonisnthappy_warm_cache.restype = ctypes.c_int32
+
 _lib.jasonisnthappy_list_indexes.argtypes = [ctypes.c_void_p, ctypes.c_char_p, ctypes.POINTER(ctypes.c_char_p), ctypes.POINTER(CError)]
 _lib.jasonisnthappy_list_indexes.restype = ctypes.c_int32
 
@@ -159,6 +200,9 @@ _lib.jasonisnthappy_drop_index.restype = ctypes.c_int32
 _lib.jasonisnthappy_set_schema.argtypes = [ctypes.c_void_p, ctypes.c_char_p, ctypes.c_char_p, ctypes.POINTER(CError)]
 _lib.jasonisnthappy_set_schema.restype = ctypes.c_int32
 
+_lib.jasonisnthappy_set_json_schema.argtypes = [ctypes.c_void_p, ctypes.c_char_p, ctypes.c_char_p, ctypes.POINTER(ctypes.c_char_p), ctypes.POINTER(CError)]
+_lib.jasonisnthappy_set_json_schema.restype = ctypes.c_int32
+
 _lib.jasonisnthappy_get_schema.argtypes = [ctypes.c_void_p, ctypes.c_char_p, ctypes.POINTER(ctypes.c_char_p), ctypes.POINTER(CError)]
 _lib.jasonisnthappy_get_schema.restype = ctypes.c_int32
 
@@ -169,6 +213,9 @@ _lib.jasonisnthappy_remove_schema.restype = ctypes.c_int32
 _lib.jasonisnthappy_checkpoint.argtypes = [ctypes.c_void_p, ctypes.POINTER(CError)]
 _lib.jasonisnthappy_checkpoint.restype = ctypes.c_int32
 
+_lib.jasonisnthappy_checkpoint_truncate.argtypes = [ctypes.c_void_p, ctypes.POINTER(CError)]
+_lib.jasonisnthappy_checkpoint_truncate.restype = ctypes.c_int32
+
 _lib.jasonisnthappy_backup.argtypes = [ctypes.c_void_p, ctypes.c_char_p, ctypes.POINTER(CError)]
 _lib.jasonisnthappy_backup.restype = ctypes.c_int32
 
@@ -198,12 +245,14 @@ WatchCallbackType = ctypes.CFUNCTYPE(
     ctypes.c_char_p,  # operation
     ctypes.c_char_p,  # doc_id
     ctypes.c_char_p,  # doc_json
+    ctypes.c_char_p,  # old_doc_json
     ctypes.c_void_p,  # user_data
 )
 
 _lib.jasonisnthappy_collection_watch_start.argtypes = [
     ctypes.c_void_p,  # coll
     ctypes.c_char_p,  # filter
+    ctypes.c_bool,  # full_document_before_change
     WatchCallbackType,  # callback
     ctypes.c_void_p,  # user_data
     ctypes.POINTER(ctypes.c_void_p),  # handle_out
@@ -300,12 +349,24 @@ _lib.jasonisnthappy_collection_delete.restype = ctypes.c_int32
 _lib.jasonisnthappy_collection_delete_one.argtypes = [ctypes.c_void_p, ctypes.c_char_p, ctypes.POINTER(ctypes.c_bool), ctypes.POINTER(CError)]
 _lib.jasonisnthappy_collection_delete_one.restype = ctypes.c_int32
 
+_lib.jasonisnthappy_collection_find_one_and_update.argtypes = [ctypes.c_void_p, ctypes.c_char_p, ctypes.c_char_p, ctypes.c_char_p, ctypes.c_bool, ctypes.c_bool, ctypes.POINTER(ctypes.c_char_p), ctypes.POINTER(CError)]
+_lib.jasonisnthappy_collection_find_one_and_update.restype = ctypes.c_int32
+
+_lib.jasonisnthappy_collection_find_one_and_delete.argtypes = [ctypes.c_void_p, ctypes.c_char_p, ctypes.c_char_p, ctypes.c_bool, ctypes.POINTER(ctypes.c_char_p), ctypes.POINTER(CError)]
+_lib.jasonisnthappy_collection_find_one_and_delete.restype = ctypes.c_int32
+
 _lib.jasonisnthappy_collection_upsert_by_id.argtypes = [ctypes.c_void_p, ctypes.c_char_p, ctypes.c_char_p, ctypes.POINTER(ctypes.c_int32), ctypes.POINTER(ctypes.c_char_p), ctypes.POINTER(CError)]
 _lib.jasonisnthappy_collection_upsert_by_id.restype = ctypes.c_int32
 
 _lib.jasonisnthappy_collection_upsert.argtypes = [ctypes.c_void_p, ctypes.c_char_p, ctypes.c_char_p, ctypes.POINTER(ctypes.c_int32), ctypes.POINTER(ctypes.c_char_p), ctypes.POINTER(CError)]
 _lib.jasonisnthappy_collection_upsert.restype = ctypes.c_int32
 
+_lib.jasonisnthappy_collection_upsert_merge_by_id.argtypes = [ctypes.c_void_p, ctypes.c_char_p, ctypes.c_char_p, ctypes.POINTER(ctypes.c_int32), ctypes.POINTER(ctypes.c_char_p), ctypes.POINTER(CError)]
+_lib.jasonisnthappy_collection_upsert_merge_by_id.restype = ctypes.c_int32
+
+_lib.jasonisnthappy_collection_upsert_merge.argtypes = [ctypes.c_void_p, ctypes.c_char_p, ctypes.c_char_p, ctypes.POINTER(ctypes.c_int32), ctypes.POINTER(ctypes.c_char_p), ctypes.POINTER(CError)]
+_lib.jasonisnthappy_collection_upsert_merge.restype = ctypes.c_int32
+
 _lib.jasonisnthappy_collection_insert_many.argtypes = [ctypes.c_void_p, ctypes.c_char_p, ctypes.POINTER(ctypes.c_char_p), ctypes.POINTER(CError)]
 _lib.jasonisnthappy_collection_insert_many.restype = ctypes.c_int32
 
@@ -572,6 +633,49 @@ class Database:
         _lib.jasonisnthappy_free_string(json_out)
         return json.loads(json_str)
 
+    def check_integrity(self) -> Dict[str, Any]:
+        """Checks the database for corruption.
+
+        Returns the integrity report; the database is consistent when its
+        "problems" list is empty.
+        """
+        if not self._db:
+            raise RuntimeError("Database is closed")
+
+        json_out = ctypes.c_char_p()
+        error = CError()
+        result = _lib.jasonisnthappy_check_integrity(self._db, ctypes.byref(json_out), ctypes.byref(error))
+
+        if result != 0:
+            _check_error(error)
+            raise RuntimeError("Failed to check integrity")
+
+        json_str = json_out.value.decode("utf-8")
+        _lib.jasonisnthappy_free_string(json_out)
+        return json.loads(json_str)
+
+    def warm_cache(self, collection_name: Optional[str] = None, documents: bool = True) -> Dict[str, Any]:
+        """Reads B-trees and, with documents, document pages into the page cache.
+
+        Warms every collection when no name is given. Returns the pages read
+        and whether the cache filled up first.
+        """
+        if not self._db:
+            raise RuntimeError("Database is closed")
+
+        name = collection_name.encode("utf-8") if collection_name is not None else None
+        json_out = ctypes.c_char_p()
+        error = CError()
+        result = _lib.jasonisnthappy_warm_cache(self._db, name, documents, ctypes.byref(json_out), ctypes.byref(error))
+
+        if result != 0:
+            _check_error(error)
+            raise RuntimeError("Failed to warm cache")
+
+        json_str = json_out.value.decode("utf-8")
+        _lib.jasonisnthappy_free_string(json_out)
+        return json.loads(json_str)
+
     # Index Management
     def list_indexes(self, collection_name: str) -> List[Dict[str, Any]]:
         """Lists all indexes for a collection."""
@@ -688,6 +792,31 @@ class Database:
             _check_error(error)
             raise RuntimeError("Failed to set schema")
 
+    def set_json_schema(self, collection_name: str, schema: Dict[str, Any]) -> List[str]:
+        """Sets a schema from a standard JSON Schema document. Returns a warning
+        for each keyword that isn't supported and was ignored."""
+        if not self._db:
+            raise RuntimeError("Database is closed")
+
+        schema_json = json.dumps(schema)
+        warnings_out = ctypes.c_char_p()
+        error = CError()
+        result = _lib.jasonisnthappy_set_json_schema(
+            self._db,
+            collection_name.encode("utf-8"),
+            schema_json.encode("utf-8"),
+            ctypes.byref(warnings_out),
+            ctypes.byref(error)
+        )
+
+        if result != 0:
+            _check_error(error)
+            raise RuntimeError("Failed to set schema")
+
+        warnings_str = warnings_out.value.decode("utf-8")
+        _lib.jasonisnthappy_free_string(warnings_out)
+        return json.loads(warnings_str)
+
     def get_schema(self, collection_name: str) -> Optional[Dict[str, Any]]:
         """Gets the JSON schema for a collection."""
         if not self._db:
@@ -742,6 +871,18 @@ class Database:
             _check_error(error)
             raise RuntimeError("Failed to checkpoint")
 
+    def checkpoint_truncate(self) -> None:
+        """Checkpoints the whole WAL and truncates the WAL file to zero bytes."""
+        if not self._db:
+            raise RuntimeError("Database is closed")
+
+        error = CError()
+        result = _lib.jasonisnthappy_checkpoint_truncate(self._db, ctypes.byref(error))
+
+        if result != 0:
+            _check_error(error)
+            raise RuntimeError("Failed to checkpoint")
+
     def backup(self, dest_path: str) -> None:
         """Creates a backup of the database."""
         if not self._db:
@@ -1480,6 +1621,74 @@ class Collection:
 
         return deleted.value
 
+    def find_one_and_update(
+        self,
+        filter_str: str,
+        update: Dict[str, Any],
+        return_new: bool = False,
+        sort_field: Optional[str] = None,
+        sort_asc: bool = True,
+    ) -> Optional[Dict[str, Any]]:
+        """Atomically updates the first document matching a filter and returns it,
+        after the update if return_new is True. sort_field picks which match is first.
+        Returns None if nothing matches."""
+        if not self._coll:
+            raise RuntimeError("Collection is closed")
+
+        sort_c = sort_field.encode("utf-8") if sort_field else None
+        json_out = ctypes.c_char_p()
+        error = CError()
+
+        status = _lib.jasonisnthappy_collection_find_one_and_update(
+            self._coll,
+            filter_str.encode("utf-8"),
+            json.dumps(update).encode("utf-8"),
+            sort_c,
+            sort_asc,
+            return_new,
+            ctypes.byref(json_out),
+            ctypes.byref(error),
+        )
+
+        return self._find_and_modify_result(status, json_out, error)
+
+    def find_one_and_delete(
+        self,
+        filter_str: str,
+        sort_field: Optional[str] = None,
+        sort_asc: bool = True,
+    ) -> Optional[Dict[str, Any]]:
+        """Atomically deletes the first document matching a filter and returns it.
+        sort_field picks which match is first. Returns None if nothing matches."""
+        if not self._coll:
+            raise RuntimeError("Collection is closed")
+
+        sort_c = sort_field.encode("utf-8") if sort_field else None
+        json_out = ctypes.c_char_p()
+        error = CError()
+
+        status = _lib.jasonisnthappy_collection_find_one_and_delete(
+            self._coll,
+            filter_str.encode("utf-8"),
+            sort_c,
+            sort_asc,
+            ctypes.byref(json_out),
+            ctypes.byref(error),
+        )
+
+        return self._find_and_modify_result(status, json_out, error)
+
+    def _find_and_modify_result(self, status: int, json_out: ctypes.c_char_p, error: CError) -> Optional[Dict[str, Any]]:
+        if status == 1:
+            return None
+        if status != 0:
+            _check_error(error)
+            raise RuntimeError("Failed to modify document")
+
+        json_str = json_out.value.decode("utf-8")
+        _lib.jasonisnthappy_free_string(json_out)
+        return json.loads(json_str)
+
     # Upsert Operations
     def upsert_by_id(self, doc_id: str, doc: Dict[str, Any]) -> UpsertResult:
         """Upserts a document by ID. Returns UpsertResult with id and inserted flag."""
@@ -1537,6 +1746,43 @@ class Collection:
         # result_code: 0 = Inserted, 1 = Updated
         return UpsertResult(id=result_id, inserted=(result_code.value == 0))
 
+    def upsert_merge_by_id(self, doc_id: str, doc: Dict[str, Any]) -> UpsertResult:
+        """Upserts a document by ID, merging into an existing document (nested
+        objects included) instead of setting its top-level fields wholesale."""
+        return self._upsert_merge(_lib.jasonisnthappy_collection_upsert_merge_by_id, doc_id, doc)
+
+    def upsert_merge(self, filter_str: str, doc: Dict[str, Any]) -> UpsertResult:
+        """Upserts a document matching a filter, merging into the first match
+        like upsert_merge_by_id."""
+        return self._upsert_merge(_lib.jasonisnthappy_collection_upsert_merge, filter_str, doc)
+
+    def _upsert_merge(self, func, target: str, doc: Dict[str, Any]) -> UpsertResult:
+        if not self._coll:
+            raise RuntimeError("Collection is closed")
+
+        doc_json = json.dumps(doc)
+        result_code = ctypes.c_int32()
+        id_out = ctypes.c_char_p()
+        error = CError()
+
+        status = func(
+            self._coll,
+            target.encode("utf-8"),
+            doc_json.encode("utf-8"),
+            ctypes.byref(result_code),
+            ctypes.byref(id_out),
+            ctypes.byref(error),
+        )
+
+        if status != 0:
+            _check_error(error)
+            raise RuntimeError("Failed to upsert document")
+
+        result_id = id_out.value.decode("utf-8")
+        _lib.jasonisnthappy_free_string(id_out)
+        # result_code: 0 = Inserted, 1 = Updated
+        return UpsertResult(id=result_id, inserted=(result_code.value == 0))
+
     # Bulk Operations
     def insert_many(self, docs: List[Dict[str, Any]]) -> List[str]:
         """Inserts multiple documents. Returns list of IDs."""
@@ -1812,8 +2058,9 @@ class Collection:
     # Watch / Change Streams
     def watch(
         self,
-        callback: Callable[[str, str, Optional[Dict[str, Any]]], None],
+        callback: Callable[..., None],
         filter_str: Optional[str] = None,
+        full_document_before_change: bool = False,
     ) -> "WatchHandle":
         """
         Starts watching for changes on the collection.
@@ -1823,6 +2070,9 @@ class Collection:
         - doc_id: The document ID
         - document: The document data (None for delete operations)
 
+        If full_document_before_change is True, the callback receives a fourth
+        argument with the document as it was before the change (None for inserts).
+
         Example:
             >>> def on_change(op, doc_id, doc):
             ...     print(f"{op}: {doc_id}")
@@ -1834,12 +2084,16 @@ class Collection:
             raise RuntimeError("Collection is closed")
 
         # Create the callback wrapper that will be called from C
-        def c_callback(collection, operation, doc_id, doc_json, user_data):
+        def c_callback(collection, operation, doc_id, doc_json, old_doc_json, user_data):
             try:
                 op_str = operation.decode("utf-8") if operation else ""
                 id_str = doc_id.decode("utf-8") if doc_id else ""
                 doc = json.loads(doc_json.decode("utf-8")) if doc_json else None
-                callback(op_str, id_str, doc)
+                if full_document_before_change:
+                    old_doc = json.loads(old_doc_json.decode("utf-8")) if old_doc_json else None
+                    callback(op_str, id_str, doc, old_doc)
+                else:
+                    callback(op_str, id_str, doc)
             except Exception:
                 pass  # Silently ignore callback errors
 
@@ -1853,6 +2107,7 @@ class Collection:
         status = _lib.jasonisnthappy_collection_watch_start(
             self._coll,
             filter_c,
+            full_document_before_change,
             c_callback_wrapped,
             None,  # user_data not needed since we use closure
             ctypes.byref(handle_out),
@@ -1,6 +1,6 @@
 use anyhow::Result;
 use crate::commands::CommandContext;
-use crate::formatter::{print_success, print_info, format_json};
+use crate::formatter::{print_success, print_info, format_json, print_paged};
 use serde_json::json;
 
 pub fn list(ctx: &CommandContext) -> Result<()> {
@@ -16,7 +16,7 @@ pub fn list(ctx: &CommandContext) -> Result<()> {
         }).collect::<Vec<_>>()
     );
 
-    println!("{}", format_json(&collections_json, &ctx.format)?);
+    print_paged(&format_json(&collections_json, &ctx.format)?);
     Ok(())
 }
 
@@ -53,8 +53,9 @@ pub fn info(ctx: &CommandContext, name: &str) -> Result<()> {
                 "unique": idx.unique,
             })
         }).collect::<Vec<_>>(),
+        "metrics": collection_info.metrics,
     });
 
-    println!("{}", format_json(&info_json, &ctx.format)?);
+    print_paged(&format_json(&info_json, &ctx.format)?);
     Ok(())
 }
@@ -1,7 +1,13 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use crate::commands::CommandContext;
-use crate::formatter::{print_success, print_info, format_json};
-use serde_json::json;
+use crate::formatter::{print_success, print_info, print_warning, print_error, format_json, print_paged, OutputFormat};
+use indicatif::{ProgressBar, ProgressStyle};
+use jasonisnthappy::{Collation, Database, IdStrategy, IndexOptions, Schema, TextIndexOptions};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, BufWriter, Write};
 
 pub fn info(ctx: &CommandContext) -> Result<()> {
     let db_info = ctx.db.info()?;
@@ -32,7 +38,7 @@ pub fn info(ctx: &CommandContext) -> Result<()> {
         }
     });
 
-    println!("{}", format_json(&info_json, &ctx.format)?);
+    print_paged(&format_json(&info_json, &ctx.format)?);
     Ok(())
 }
 
@@ -48,8 +54,419 @@ pub fn backup(ctx: &CommandContext, destination: &str) -> Result<()> {
 pub fn compact(ctx: &CommandContext) -> Result<()> {
     print_info("Compacting database...");
 
-    ctx.db.checkpoint()?;
+    let reclaimed = ctx.db.vacuum()?;
+    let size_after = std::fs::metadata(ctx.db.path())?.len();
+    let size_before = size_after + reclaimed;
 
-    print_success("Database compacted successfully");
+    print_info(&format!("File size before: {} bytes", size_before));
+    print_info(&format!("File size after:  {} bytes", size_after));
+    print_success(&format!("Database compacted successfully, reclaimed {} bytes", reclaimed));
     Ok(())
 }
+
+pub fn check(ctx: &CommandContext) -> Result<()> {
+    let report = ctx.db.check_integrity()?;
+
+    if matches!(ctx.format, OutputFormat::Json) {
+        println!("{}", serde_json::to_string(&report)?);
+    } else {
+        print_info(&format!(
+            "Checked {} collection(s), {} document(s) and {} index(es)",
+            report.collections_checked, report.documents_checked, report.indexes_checked
+        ));
+        print_info(&format!(
+            "{} pages: {} free, {} orphaned (reclaimable with 'db compact')",
+            report.total_pages, report.free_pages, report.orphaned_pages
+        ));
+        if ctx.db.page_checksums() {
+            print_info(&format!("{} page checksum(s) verified", report.pages_verified));
+        }
+        for warning in &report.warnings {
+            print_warning(&warning.to_string());
+        }
+        for problem in &report.problems {
+            print_error(&problem.to_string());
+        }
+    }
+
+    if !report.is_ok() {
+        anyhow::bail!("Integrity check found {} problem(s)", report.problems.len());
+    }
+    if !matches!(ctx.format, OutputFormat::Json) {
+        print_success("No problems found");
+    }
+    Ok(())
+}
+
+/// Rebuild a database whose metadata is damaged. Runs without a
+/// `CommandContext`, since the database may not open and must not be open.
+pub fn repair(path: &str, force: bool, format: &OutputFormat) -> Result<()> {
+    if !force {
+        anyhow::bail!(
+            "'db repair' rewrites '{}' from whatever pages can still be read, dropping collection \
+             names, indexes and settings. Copy the file somewhere safe, then run again with --force",
+            path
+        );
+    }
+
+    let report = Database::repair(path)?;
+
+    if matches!(format, OutputFormat::Json) {
+        println!("{}", serde_json::to_string(&report)?);
+        return Ok(());
+    }
+
+    print_info(&format!(
+        "Scanned {} pages: {} B-tree node(s), {} older version(s) and {} tree(s) without documents skipped",
+        report.pages_scanned, report.btree_nodes, report.superseded_trees, report.skipped_trees
+    ));
+    for collection in &report.collections {
+        print_info(&format!("{}: {} document(s)", collection.name, collection.documents));
+    }
+    for problem in &report.unrecoverable {
+        print_error(&problem.to_string());
+    }
+    print_success(&format!(
+        "Recovered {} document(s) in {} collection(s); rename them and recreate their indexes",
+        report.documents_recovered, report.collections.len()
+    ));
+    Ok(())
+}
+
+pub fn space(ctx: &CommandContext) -> Result<()> {
+    let stats = ctx.db.space_stats()?;
+
+    if !matches!(ctx.format, OutputFormat::Pretty) {
+        print_paged(&format_json(&serde_json::to_value(&stats)?, &ctx.format)?);
+        return Ok(());
+    }
+
+    let share = |pages: u64| {
+        if stats.total_pages == 0 { 0.0 } else { pages as f64 * 100.0 / stats.total_pages as f64 }
+    };
+    print_info(&format!(
+        "{} pages of {} bytes ({} bytes)",
+        stats.total_pages, stats.page_size, stats.total_pages * stats.page_size as u64
+    ));
+    for (label, pages) in [
+        ("in use", stats.pages_in_use),
+        ("  overflow", stats.overflow_pages),
+        ("  old versions", stats.old_version_pages),
+        ("free", stats.free_pages),
+        ("orphaned", stats.orphaned_pages),
+    ] {
+        println!("  {:<16} {:>10} ({:.1}%)", label, pages, share(pages));
+    }
+    for c in &stats.collections {
+        print_info(&format!(
+            "{}: {} pages ({} tree, {} documents, {} overflow, {} index)",
+            c.name, c.pages, c.tree_pages, c.document_pages, c.overflow_pages, c.index_pages
+        ));
+    }
+    print_success(&format!(
+        "{} pages ({:.1}%) reclaimable with 'db compact'",
+        stats.reclaimable_pages(), stats.reclaimable_ratio() * 100.0
+    ));
+    Ok(())
+}
+
+pub fn slow_queries(ctx: &CommandContext) -> Result<()> {
+    let threshold = ctx.db.slow_query_threshold_ms();
+    if threshold == 0 {
+        print_warning("The slow query log is off; start with --slow-query-threshold <MS> to turn it on");
+        return Ok(());
+    }
+
+    let mut queries = ctx.db.slow_queries();
+    if !matches!(ctx.format, OutputFormat::Pretty) {
+        print_paged(&format_json(&serde_json::to_value(&queries)?, &ctx.format)?);
+        return Ok(());
+    }
+
+    if queries.is_empty() {
+        print_info(&format!("No queries have taken {}ms or longer", threshold));
+        return Ok(());
+    }
+    // Slowest first
+    queries.sort_by(|a, b| b.elapsed_ms.total_cmp(&a.elapsed_ms));
+    for query in &queries {
+        println!(
+            "  {:>9.1}ms  {:<9} {:<16} {:>8} examined  {}",
+            query.elapsed_ms, query.operation.as_str(), query.collection, query.docs_examined, query.query
+        );
+    }
+    Ok(())
+}
+
+pub fn warm(ctx: &CommandContext, collection: Option<&str>, documents: bool) -> Result<()> {
+    let warmup = match collection {
+        Some(name) => ctx.db.warm_cache(name, documents)?,
+        None => ctx.db.warm_cache_all(documents)?,
+    };
+
+    if !matches!(ctx.format, OutputFormat::Pretty) {
+        print_paged(&format_json(&serde_json::to_value(&warmup)?, &ctx.format)?);
+        return Ok(());
+    }
+
+    print_success(&format!(
+        "Cached {} pages ({} tree, {} documents)",
+        warmup.pages(), warmup.tree_pages, warmup.document_pages
+    ));
+    if warmup.cache_full {
+        print_warning("The page cache filled up before everything was read");
+    }
+    Ok(())
+}
+
+/// Format name and version written at the top of every dump. Bump the version
+/// when the layout changes; `load` refuses archives newer than it understands.
+const DUMP_FORMAT: &str = "jasonisnthappy-dump";
+const DUMP_VERSION: u64 = 1;
+
+/// First line of a dump: everything needed to recreate the collections
+#[derive(Serialize, Deserialize)]
+struct DumpHeader {
+    format: String,
+    version: u64,
+    collections: Vec<CollectionDump>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CollectionDump {
+    name: String,
+    #[serde(default)]
+    indexes: Vec<IndexDump>,
+    #[serde(default)]
+    text_indexes: Vec<TextIndexDump>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    schema: Option<Schema>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id_strategy: Option<IdStrategy>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndexDump {
+    name: String,
+    fields: Vec<String>,
+    #[serde(default)]
+    unique: bool,
+    #[serde(default)]
+    descending: bool,
+    #[serde(default)]
+    sparse: bool,
+    #[serde(default)]
+    geo: bool,
+    #[serde(default, skip_serializing_if = "Collation::is_binary")]
+    collation: Collation,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TextIndexDump {
+    name: String,
+    fields: Vec<String>,
+    options: TextIndexOptions,
+}
+
+/// Every line after the header holds one document
+#[derive(Serialize, Deserialize)]
+struct DocumentLine {
+    collection: String,
+    document: Value,
+}
+
+/// Write every collection with its documents, indexes, schema and id strategy
+/// to a newline-delimited JSON archive. Documents are read from a single
+/// snapshot so the archive is consistent across collections.
+pub fn dump(ctx: &CommandContext, output: &str) -> Result<()> {
+    print_info(&format!("Dumping database to '{}'...", output));
+
+    let names = ctx.db.list_collections()?;
+    let mut collections = Vec::with_capacity(names.len());
+    for name in &names {
+        let mut indexes = Vec::new();
+        let mut text_indexes = Vec::new();
+        for index in ctx.db.list_indexes(name)? {
+            match index.text_options {
+                Some(options) => text_indexes.push(TextIndexDump { name: index.name, fields: index.fields, options }),
+                None => indexes.push(IndexDump {
+                    name: index.name,
+                    fields: index.fields,
+                    unique: index.unique,
+                    descending: index.descending,
+                    sparse: index.sparse,
+                    geo: index.geo,
+                    collation: index.collation,
+                }),
+            }
+        }
+
+        collections.push(CollectionDump {
+            name: name.clone(),
+            indexes,
+            text_indexes,
+            schema: ctx.db.get_schema(name),
+            id_strategy: ctx.db.get_id_strategy(name),
+        });
+    }
+
+    let header = DumpHeader { format: DUMP_FORMAT.to_string(), version: DUMP_VERSION, collections };
+    let mut writer = BufWriter::new(fs::File::create(output)?);
+    serde_json::to_writer(&mut writer, &header)?;
+    writeln!(writer)?;
+
+    let mut tx = ctx.db.begin()?;
+    let mut total = 0;
+    for name in &names {
+        for document in tx.collection(name)?.find_all()? {
+            serde_json::to_writer(&mut writer, &DocumentLine { collection: name.clone(), document })?;
+            writeln!(writer)?;
+            total += 1;
+        }
+    }
+    tx.rollback()?;
+    writer.flush()?;
+
+    print_success(&format!("Dumped {} collection(s) and {} document(s) to '{}'", names.len(), total, output));
+    Ok(())
+}
+
+/// Recreate the collections of an archive written by `dump`. Documents are
+/// inserted in batches, then indexes, schemas and id strategies are restored.
+/// Indexes that already exist in the database are left as they are.
+pub fn load(ctx: &CommandContext, input: &str, batch_size: usize) -> Result<()> {
+    print_info(&format!("Loading database from '{}'...", input));
+
+    if batch_size == 0 {
+        anyhow::bail!("Batch size must be at least 1");
+    }
+
+    let file = fs::File::open(input)?;
+    let pb = ProgressBar::new(file.metadata()?.len());
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("[{elapsed_precise}] {bar:40.cyan/blue} {bytes}/{total_bytes} {msg}")?
+            .progress_chars("=>-"),
+    );
+
+    let mut lines = BufReader::new(file).lines();
+    let header_line = lines.next().transpose()?.unwrap_or_default();
+    pb.inc(header_line.len() as u64 + 1);
+    let header = parse_dump_header(&header_line)?;
+
+    for coll in &header.collections {
+        if !ctx.db.collection_exists(&coll.name) {
+            let mut tx = ctx.db.begin()?;
+            tx.create_collection(&coll.name)?;
+            tx.commit()?;
+        }
+    }
+
+    let mut batch: Vec<Value> = Vec::with_capacity(batch_size);
+    let mut batch_collection = String::new();
+    let mut last_numeric_ids: HashMap<String, u64> = HashMap::new();
+    let mut loaded = 0;
+
+    for (i, line) in lines.enumerate() {
+        let line = line?;
+        pb.inc(line.len() as u64 + 1);
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let entry: DocumentLine = serde_json::from_str(&line)
+            .with_context(|| format!("Malformed document on line {}", i + 2))?;
+        if !header.collections.iter().any(|c| c.name == entry.collection) {
+            anyhow::bail!("Line {} belongs to collection '{}', which is missing from the header", i + 2, entry.collection);
+        }
+
+        if let Some(id) = entry.document.get("_id").and_then(Value::as_str).and_then(|id| id.parse::<u64>().ok()) {
+            let last = last_numeric_ids.entry(entry.collection.clone()).or_default();
+            *last = (*last).max(id);
+        }
+
+        if entry.collection != batch_collection || batch.len() >= batch_size {
+            if !batch.is_empty() {
+                loaded += ctx.db.collection(&batch_collection).insert_many(std::mem::take(&mut batch))?.len();
+                pb.set_message(format!("{} loaded", loaded));
+            }
+            batch_collection = entry.collection;
+        }
+        batch.push(entry.document);
+    }
+
+    if !batch.is_empty() {
+        loaded += ctx.db.collection(&batch_collection).insert_many(batch)?.len();
+    }
+
+    for coll in &header.collections {
+        let existing: Vec<String> = ctx.db.list_indexes(&coll.name)?.into_iter().map(|i| i.name).collect();
+
+        for index in &coll.indexes {
+            if existing.contains(&index.name) {
+                pb.suspend(|| print_warning(&format!("Index '{}' on '{}' already exists, skipping", index.name, coll.name)));
+                continue;
+            }
+            if index.geo {
+                ctx.db.create_geo_index(&coll.name, &index.name, &index.fields[0], &index.fields[1])?;
+                continue;
+            }
+            let fields: Vec<&str> = index.fields.iter().map(|f| f.as_str()).collect();
+            let options = IndexOptions {
+                unique: index.unique,
+                descending: index.descending,
+                sparse: index.sparse,
+                collation: index.collation,
+            };
+            ctx.db.create_index_with_options(&coll.name, &index.name, &fields, options)?;
+        }
+
+        for index in &coll.text_indexes {
+            if existing.contains(&index.name) {
+                pb.suspend(|| print_warning(&format!("Index '{}' on '{}' already exists, skipping", index.name, coll.name)));
+                continue;
+            }
+            let fields: Vec<&str> = index.fields.iter().map(|f| f.as_str()).collect();
+            ctx.db.create_text_index(&coll.name, &index.name, &fields, index.options.clone())?;
+        }
+
+        if let Some(schema) = &coll.schema {
+            ctx.db.set_schema(&coll.name, schema.clone())?;
+        }
+
+        if let Some(strategy) = coll.id_strategy {
+            ctx.db.set_id_strategy(&coll.name, strategy)?;
+            if strategy == IdStrategy::AutoIncrement {
+                if let Some(&last) = last_numeric_ids.get(&coll.name) {
+                    ctx.db.reserve_auto_ids(&coll.name, last)?;
+                }
+            }
+        }
+    }
+    pb.finish_with_message("done");
+
+    print_success(&format!("Loaded {} collection(s) and {} document(s)", header.collections.len(), loaded));
+    Ok(())
+}
+
+/// Check the format name and version before reading the rest of the header,
+/// so archives from newer releases fail with a clear message
+fn parse_dump_header(line: &str) -> Result<DumpHeader> {
+    let value: Value = serde_json::from_str(line).context("Archive header is not valid JSON")?;
+
+    if value.get("format").and_then(Value::as_str) != Some(DUMP_FORMAT) {
+        anyhow::bail!("Not a jasonisnthappy dump: header has no '{}' format marker", DUMP_FORMAT);
+    }
+
+    match value.get("version").and_then(Value::as_u64) {
+        Some(version) if version <= DUMP_VERSION => {}
+        Some(version) => anyhow::bail!(
+            "Dump format version {} is newer than this version of the CLI supports ({})",
+            version, DUMP_VERSION
+        ),
+        None => anyhow::bail!("Archive header has no format version"),
+    }
+
+    serde_json::from_value(value).context("Malformed archive header")
+}
@@ -1,9 +1,12 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use crate::commands::CommandContext;
-use crate::formatter::{print_success, print_info, format_json};
-use crate::utils::parse_json;
+use crate::formatter::{print_success, print_info, print_warning, format_json, print_paged};
+use crate::utils::{flatten_document, parse_json, parse_json_input, unflatten_row, ArgInput};
 use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use jasonisnthappy::{BulkWriteError, Collection};
 use indicatif::{ProgressBar, ProgressStyle};
 
 pub fn insert(ctx: &CommandContext, collection: &str, document: &str) -> Result<()> {
@@ -16,6 +19,21 @@ pub fn insert(ctx: &CommandContext, collection: &str, document: &str) -> Result<
     Ok(())
 }
 
+/// The query held by `input`: query language text, or JSON holding either a
+/// query string or `{}` to match every document
+pub fn query_text(input: &ArgInput) -> Result<String> {
+    let trimmed = input.text.trim();
+    if !trimmed.starts_with(['{', '[', '"']) {
+        return Ok(trimmed.to_string());
+    }
+
+    match parse_json_input(input)? {
+        Value::String(query) => Ok(query),
+        Value::Object(obj) if obj.is_empty() => Ok("{}".to_string()),
+        other => anyhow::bail!("Expected a query string or {{}} in {}, got {}", input.source, other),
+    }
+}
+
 pub fn find(
     ctx: &CommandContext,
     collection: &str,
@@ -58,7 +76,7 @@ pub fn find(
     };
 
     let results_json = Value::Array(results);
-    println!("{}", format_json(&results_json, &ctx.format)?);
+    print_paged(&format_json(&results_json, &ctx.format)?);
 
     Ok(())
 }
@@ -75,7 +93,7 @@ pub fn find_one(ctx: &CommandContext, collection: &str, query: &str) -> Result<(
     };
 
     if let Some(doc) = result {
-        println!("{}", format_json(&doc, &ctx.format)?);
+        print_paged(&format_json(&doc, &ctx.format)?);
     } else {
         print_info("No document found");
     }
@@ -110,11 +128,38 @@ pub fn count(ctx: &CommandContext, collection: &str, query: &str) -> Result<()>
         coll.find(query)?.len()
     };
 
-    println!("{}", format_json(&json!({ "count": count }), &ctx.format)?);
+    print_paged(&format_json(&json!({ "count": count }), &ctx.format)?);
     Ok(())
 }
 
-pub fn export(ctx: &CommandContext, collection: &str, output: &str) -> Result<()> {
+/// Options for `export` and `import` file formats
+pub struct FileFormatOptions {
+    pub format: String,
+    pub delimiter: char,
+    pub no_header: bool,
+}
+
+impl FileFormatOptions {
+    /// Pick the format from the file extension (`.csv`, `.ndjson`/`.jsonl`,
+    /// otherwise JSON) with default CSV settings
+    pub fn for_path(path: &str) -> Self {
+        let extension = std::path::Path::new(path)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase());
+        let format = match extension.as_deref() {
+            Some("csv") => "csv",
+            Some("ndjson") | Some("jsonl") => "ndjson",
+            _ => "json",
+        };
+        Self {
+            format: format.to_string(),
+            delimiter: ',',
+            no_header: false,
+        }
+    }
+}
+
+pub fn export(ctx: &CommandContext, collection: &str, output: &str, opts: &FileFormatOptions) -> Result<()> {
     print_info(&format!("Exporting collection '{}' to '{}'...", collection, output));
 
     let coll = ctx.db.collection(collection);
@@ -131,8 +176,15 @@ pub fn export(ctx: &CommandContext, collection: &str, output: &str) -> Result<()
     );
 
     // Write to file
-    let json_str = serde_json::to_string_pretty(&docs)?;
-    fs::write(output, json_str)?;
+    match opts.format.as_str() {
+        "json" => {
+            let json_str = serde_json::to_string_pretty(&docs)?;
+            fs::write(output, json_str)?;
+        }
+        "ndjson" => write_ndjson(&docs, output, &pb)?,
+        "csv" => write_csv(&docs, output, opts, &pb)?,
+        other => anyhow::bail!("Unknown export format '{}', expected json, ndjson or csv", other),
+    }
 
     pb.finish_with_message("done");
 
@@ -140,11 +192,66 @@ pub fn export(ctx: &CommandContext, collection: &str, output: &str) -> Result<()
     Ok(())
 }
 
-pub fn import(ctx: &CommandContext, collection: &str, input: &str) -> Result<()> {
+/// Options that only apply to `import`
+pub struct ImportOptions {
+    /// Documents inserted per transaction
+    pub batch_size: usize,
+    /// Skip malformed NDJSON lines instead of aborting
+    pub continue_on_error: bool,
+    /// Report the documents that would be rejected instead of importing
+    pub validate_only: bool,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            batch_size: 1000,
+            continue_on_error: false,
+            validate_only: false,
+        }
+    }
+}
+
+pub fn import(
+    ctx: &CommandContext,
+    collection: &str,
+    input: &str,
+    opts: &FileFormatOptions,
+    import_opts: &ImportOptions,
+) -> Result<()> {
     print_info(&format!("Importing from '{}' to collection '{}'...", input, collection));
 
-    let json_str = fs::read_to_string(input)?;
-    let docs: Vec<Value> = serde_json::from_str(&json_str)?;
+    if import_opts.batch_size == 0 {
+        anyhow::bail!("Batch size must be at least 1");
+    }
+
+    let coll = ctx.db.collection(collection);
+
+    if opts.format == "ndjson" && import_opts.validate_only {
+        return validate_ndjson(&coll, input, import_opts);
+    }
+    if opts.format == "ndjson" {
+        return import_ndjson(&coll, input, import_opts);
+    }
+
+    let docs: Vec<Value> = match opts.format.as_str() {
+        "json" => {
+            let json_str = fs::read_to_string(input)?;
+            serde_json::from_str(&json_str)?
+        }
+        "csv" => read_csv(input, opts)?,
+        other => anyhow::bail!("Unknown import format '{}', expected json, ndjson or csv", other),
+    };
+
+    if import_opts.validate_only {
+        let failures = coll.validate_many(&docs)?;
+        return report_validation(docs.len(), &failures, "Document");
+    }
+
+    if import_opts.validate_only {
+        let failures = coll.validate_many(&docs)?;
+        return report_validation(docs.len(), &failures, "Document");
+    }
 
     // Create progress bar
     let pb = ProgressBar::new(docs.len() as u64);
@@ -154,11 +261,219 @@ pub fn import(ctx: &CommandContext, collection: &str, input: &str) -> Result<()>
             .progress_chars("=>-"),
     );
 
-    let coll = ctx.db.collection(collection);
+    let mut imported = 0;
+    let mut docs = docs.into_iter().peekable();
+    while docs.peek().is_some() {
+        let batch: Vec<Value> = docs.by_ref().take(import_opts.batch_size).collect();
+        let count = batch.len();
+        imported += coll.insert_many(batch)?.len();
+        pb.inc(count as u64);
+    }
+    pb.finish_with_message("done");
+
+    print_success(&format!("Imported {} document(s)", imported));
+    Ok(())
+}
+
+/// Print the documents that failed validation, numbered from 1 with `label`,
+/// and fail if there were any
+fn report_validation(checked: usize, failures: &[BulkWriteError], label: &str) -> Result<()> {
+    for failure in failures {
+        print_warning(&format!("{} {}: {}", label, failure.operation_index + 1, failure.message));
+    }
+    if !failures.is_empty() {
+        anyhow::bail!("{} of {} document(s) would be rejected, nothing was imported", failures.len(), checked);
+    }
+    print_success(&format!("All {} document(s) are valid, nothing was imported", checked));
+    Ok(())
+}
+
+/// Check newline-delimited JSON one batch at a time without importing it.
+/// Documents are checked against each other within a batch only.
+fn validate_ndjson(coll: &Collection, input: &str, import_opts: &ImportOptions) -> Result<()> {
+    let file = fs::File::open(input)?;
+    let mut batch = Vec::with_capacity(import_opts.batch_size);
+    let mut failures = Vec::new();
+    let mut checked = 0;
+
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        checked += 1;
+        match parse_ndjson_line(&line) {
+            Ok(doc) => batch.push((i, doc)),
+            Err(e) => failures.push(BulkWriteError { operation_index: i, message: e.to_string() }),
+        }
+        if batch.len() >= import_opts.batch_size {
+            failures.extend(validate_lines(coll, std::mem::take(&mut batch))?);
+        }
+    }
+    failures.extend(validate_lines(coll, batch)?);
+    failures.sort_by_key(|failure| failure.operation_index);
+
+    report_validation(checked, &failures, "Line")
+}
+
+/// Validate documents tagged with their zero-based line numbers, reporting
+/// failures by line
+fn validate_lines(coll: &Collection, batch: Vec<(usize, Value)>) -> Result<Vec<BulkWriteError>> {
+    let (lines, docs): (Vec<usize>, Vec<Value>) = batch.into_iter().unzip();
+    let mut failures = coll.validate_many(&docs)?;
+    for failure in &mut failures {
+        failure.operation_index = lines[failure.operation_index];
+    }
+    Ok(failures)
+}
+
+/// Stream newline-delimited JSON into the collection, one transaction per
+/// batch, so memory use doesn't grow with the file
+fn import_ndjson(coll: &Collection, input: &str, import_opts: &ImportOptions) -> Result<()> {
+    let file = fs::File::open(input)?;
+
+    let pb = ProgressBar::new(file.metadata()?.len());
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("[{elapsed_precise}] {bar:40.cyan/blue} {bytes}/{total_bytes} {msg}")?
+            .progress_chars("=>-"),
+    );
+
+    let mut batch = Vec::with_capacity(import_opts.batch_size);
+    let mut imported = 0;
+    let mut skipped = 0;
+
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        let line_number = i + 1;
+        pb.inc(line.len() as u64 + 1);
+
+        if line.trim().is_empty() {
+            continue;
+        }
 
-    let ids = coll.insert_many(docs)?;
+        match parse_ndjson_line(&line) {
+            Ok(doc) => batch.push(doc),
+            Err(e) if import_opts.continue_on_error => {
+                pb.suspend(|| print_warning(&format!("Skipping line {}: {}", line_number, e)));
+                skipped += 1;
+                continue;
+            }
+            Err(e) => {
+                pb.abandon();
+                anyhow::bail!(
+                    "Malformed JSON on line {}: {} ({} document(s) from earlier batches were imported)",
+                    line_number, e, imported
+                );
+            }
+        }
+
+        if batch.len() >= import_opts.batch_size {
+            imported += coll.insert_many(std::mem::take(&mut batch))?.len();
+            pb.set_message(format!("{} imported", imported));
+        }
+    }
+
+    if !batch.is_empty() {
+        imported += coll.insert_many(batch)?.len();
+    }
     pb.finish_with_message("done");
 
-    print_success(&format!("Imported {} document(s)", ids.len()));
+    print_success(&format!("Imported {} document(s)", imported));
+    if skipped > 0 {
+        print_warning(&format!("Skipped {} malformed line(s)", skipped));
+    }
     Ok(())
 }
+
+fn parse_ndjson_line(line: &str) -> Result<Value> {
+    match serde_json::from_str(line)? {
+        doc @ Value::Object(_) => Ok(doc),
+        other => anyhow::bail!("expected a JSON object, got {}", other),
+    }
+}
+
+fn write_ndjson(docs: &[Value], output: &str, pb: &ProgressBar) -> Result<()> {
+    let mut writer = BufWriter::new(fs::File::create(output)?);
+    for doc in docs {
+        serde_json::to_writer(&mut writer, doc)?;
+        writer.write_all(b"\n")?;
+        pb.inc(1);
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn csv_delimiter(delimiter: char) -> Result<u8> {
+    if !delimiter.is_ascii() {
+        anyhow::bail!("CSV delimiter must be a single ASCII character, got '{}'", delimiter);
+    }
+    Ok(delimiter as u8)
+}
+
+fn write_csv(docs: &[Value], output: &str, opts: &FileFormatOptions, pb: &ProgressBar) -> Result<()> {
+    let rows: Vec<Vec<(String, String)>> = docs.iter().map(flatten_document).collect();
+
+    // Columns in first-seen order, with _id leading
+    let mut columns: Vec<String> = vec!["_id".to_string()];
+    let mut seen: HashSet<String> = columns.iter().cloned().collect();
+    for row in &rows {
+        for (column, _) in row {
+            if seen.insert(column.clone()) {
+                columns.push(column.clone());
+            }
+        }
+    }
+
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(csv_delimiter(opts.delimiter)?)
+        .from_path(output)?;
+
+    if !opts.no_header {
+        writer.write_record(&columns)?;
+    }
+
+    for row in rows {
+        let mut cells: HashMap<String, String> = row.into_iter().collect();
+        let record: Vec<String> = columns
+            .iter()
+            .map(|column| cells.remove(column).unwrap_or_default())
+            .collect();
+        writer.write_record(&record)?;
+        pb.inc(1);
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+fn read_csv(input: &str, opts: &FileFormatOptions) -> Result<Vec<Value>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(csv_delimiter(opts.delimiter)?)
+        .has_headers(!opts.no_header)
+        .from_path(input)?;
+
+    let header: Option<Vec<String>> = if opts.no_header {
+        None
+    } else {
+        Some(reader.headers()?.iter().map(|h| h.to_string()).collect())
+    };
+
+    let mut docs = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let record = record?;
+        let cells: Vec<String> = record.iter().map(|c| c.to_string()).collect();
+
+        // Without a header row, columns are named column1, column2, ...
+        let columns = match &header {
+            Some(columns) => columns.clone(),
+            None => (1..=cells.len()).map(|n| format!("column{}", n)).collect(),
+        };
+
+        let doc = unflatten_row(&columns, &cells)
+            .with_context(|| format!("Invalid CSV row {}", i + 1))?;
+        docs.push(doc);
+    }
+
+    Ok(docs)
+}
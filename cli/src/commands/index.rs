@@ -1,6 +1,8 @@
 use anyhow::Result;
 use crate::commands::CommandContext;
-use crate::formatter::{print_success, format_json};
+use crate::formatter::{print_success, format_json, print_paged};
+use indicatif::{ProgressBar, ProgressStyle};
+use jasonisnthappy::IndexOptions;
 use serde_json::json;
 
 pub fn list(ctx: &CommandContext, collection: &str) -> Result<()> {
@@ -18,21 +20,57 @@ pub fn list(ctx: &CommandContext, collection: &str) -> Result<()> {
                 "name": idx.name,
                 "fields": idx.fields,
                 "unique": idx.unique,
+                "descending": idx.descending,
+                "sparse": idx.sparse,
+                "geo": idx.geo,
+                "collation": idx.collation,
             })
         }).collect::<Vec<_>>()
     );
 
-    println!("{}", format_json(&indexes_json, &ctx.format)?);
+    print_paged(&format_json(&indexes_json, &ctx.format)?);
     Ok(())
 }
 
-pub fn create(ctx: &CommandContext, collection: &str, field: &str, unique: bool) -> Result<()> {
+pub fn create(ctx: &CommandContext, collection: &str, field: &str, options: IndexOptions) -> Result<()> {
     let index_name = format!("{}_idx", field);
 
-    ctx.db.create_index(collection, &index_name, field, unique)?;
+    ctx.db.create_index_with_options(collection, &index_name, &[field], options)?;
 
-    let index_type = if unique { "unique index" } else { "index" };
-    print_success(&format!("Created {} on field '{}' in collection '{}'", index_type, field, collection));
+    let mut kinds = Vec::new();
+    if options.unique {
+        kinds.push("unique");
+    }
+    if options.sparse {
+        kinds.push("sparse");
+    }
+    if options.descending {
+        kinds.push("descending");
+    }
+    if !options.collation.is_binary() {
+        kinds.push("case-insensitive");
+    }
+    kinds.push("index");
+    print_success(&format!("Created {} on field '{}' in collection '{}'", kinds.join(" "), field, collection));
+    Ok(())
+}
+
+pub fn rebuild(ctx: &CommandContext, collection: &str, name: &str) -> Result<()> {
+    let pb = ProgressBar::new(0);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}")?
+            .progress_chars("=>-"),
+    );
+
+    ctx.db.rebuild_index_with_progress(collection, name, |done, total| {
+        pb.set_length(total.max(done));
+        pb.set_position(done);
+    })?;
+
+    pb.finish_with_message("done");
+
+    print_success(&format!("Rebuilt index '{}' on collection '{}'", name, collection));
     Ok(())
 }
 
@@ -42,3 +80,26 @@ pub fn drop(ctx: &CommandContext, collection: &str, name: &str) -> Result<()> {
     print_success(&format!("Dropped index '{}' from collection '{}'", name, collection));
     Ok(())
 }
+
+/// Queries served by each index of the collection since the database was
+/// opened, least used first, so indexes that only cost writes stand out
+pub fn usage(ctx: &CommandContext, collection: &str) -> Result<()> {
+    let mut usage = ctx.db.index_usage(collection)?;
+    usage.sort_by_key(|index| index.uses);
+
+    let usage_json = json!(
+        usage.iter().map(|idx| {
+            json!({
+                "name": idx.name,
+                "fields": idx.fields,
+                "unique": idx.unique,
+                "text": idx.text,
+                "uses": idx.uses,
+                "last_used_millis": idx.last_used_millis,
+            })
+        }).collect::<Vec<_>>()
+    );
+
+    print_paged(&format_json(&usage_json, &ctx.format)?);
+    Ok(())
+}
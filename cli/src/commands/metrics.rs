@@ -1,6 +1,7 @@
 use anyhow::Result;
 use crate::commands::CommandContext;
-use crate::formatter::{print_info, format_json};
+use crate::formatter::{print_info, format_json, print_paged};
+use jasonisnthappy::core::metrics::{MetricsSnapshot, Operation};
 use serde_json::json;
 use std::time::Duration;
 
@@ -28,12 +29,30 @@ pub fn show(ctx: &CommandContext) -> Result<()> {
             "bytes_written": metrics.wal_bytes_written,
             "checkpoints": metrics.checkpoints,
         },
+        "latency_micros": latency_json(&metrics),
     });
 
-    println!("{}", format_json(&metrics_json, &ctx.format)?);
+    print_paged(&format_json(&metrics_json, &ctx.format)?);
     Ok(())
 }
 
+fn latency_json(metrics: &MetricsSnapshot) -> serde_json::Value {
+    let mut latency = serde_json::Map::new();
+    for operation in Operation::ALL {
+        let histogram = metrics.latency(operation);
+        latency.insert(operation.as_str().to_string(), json!({
+            "count": histogram.count,
+            "mean": (histogram.mean_micros() * 10.0).round() / 10.0,
+            "p50": histogram.p50_micros,
+            "p90": histogram.p90_micros,
+            "p95": histogram.p95_micros,
+            "p99": histogram.p99_micros,
+            "max": histogram.max_micros,
+        }));
+    }
+    latency.into()
+}
+
 pub fn watch(ctx: &CommandContext, collection: &str) -> Result<()> {
     print_info(&format!("Watching collection '{}' for changes... (Press Ctrl+C to stop)", collection));
 
@@ -7,12 +7,19 @@ pub mod schema;
 pub mod metrics;
 
 use anyhow::Result;
-use jasonisnthappy::Database;
+use jasonisnthappy::{Database, DatabaseOptions};
 use crate::formatter::OutputFormat;
 
 #[cfg(feature = "web-ui")]
 use jasonisnthappy::WebServer;
 
+/// Web UI settings collected from the command line
+pub struct WebUiConfig {
+    pub address: String,
+    pub auth_token: Option<String>,
+    pub read_only: bool,
+}
+
 pub struct CommandContext {
     pub db: Database,
     pub format: OutputFormat,
@@ -21,9 +28,8 @@ pub struct CommandContext {
 }
 
 impl CommandContext {
-    pub fn new(db_path: &str, format_str: &str) -> Result<Self> {
-        let db = Database::open(db_path)?;
-        let format = OutputFormat::from_str(format_str);
+    pub fn new(db_path: &str, options: DatabaseOptions, format: OutputFormat) -> Result<Self> {
+        let db = Database::open_with_options(db_path, options)?;
 
         Ok(Self {
             db,
@@ -34,17 +40,29 @@ impl CommandContext {
     }
 
     #[cfg(feature = "web-ui")]
-    pub fn start_web_ui(&mut self, address: &str) -> Result<()> {
+    pub fn start_web_ui(&mut self, config: &WebUiConfig) -> Result<()> {
         use crate::formatter::print_info;
+        use jasonisnthappy::WebServerOptions;
 
-        let web_server = self.db.start_web_ui(address)?;
-        print_info(&format!("Web UI started at http://{}", address));
+        let options = WebServerOptions {
+            auth_token: config.auth_token.clone(),
+            read_only: config.read_only,
+            ..Default::default()
+        };
+        let web_server = self.db.start_web_ui_with_options(&config.address, options)?;
+        print_info(&format!("Web UI started at http://{}", config.address));
+        if config.auth_token.is_some() {
+            print_info("API requests require the configured bearer token");
+        }
+        if config.read_only {
+            print_info("Web UI is read-only");
+        }
         self.web_server = Some(web_server);
         Ok(())
     }
 
     #[cfg(not(feature = "web-ui"))]
-    pub fn start_web_ui(&mut self, _address: &str) -> Result<()> {
+    pub fn start_web_ui(&mut self, _config: &WebUiConfig) -> Result<()> {
         anyhow::bail!("Web UI feature not enabled. Rebuild with --features web-ui")
     }
 }
@@ -1,10 +1,29 @@
 use anyhow::Result;
 use crate::commands::CommandContext;
-use crate::formatter::format_json;
-use crate::utils::parse_json_array;
+use crate::formatter::{format_json, print_info, OutputFormat, print_paged};
+use crate::utils::{parse_json_input, ArgInput};
 use serde_json::Value;
-use jasonisnthappy::SortOrder;
+use jasonisnthappy::{IndexHint, SortOrder};
+use jasonisnthappy::core::QueryBuilder;
 
+/// Index choice from the `--hint` and `--no-index` flags
+pub fn index_hint(hint: Option<String>, no_index: bool) -> Option<IndexHint> {
+    if no_index {
+        Some(IndexHint::NoIndex)
+    } else {
+        hint.map(IndexHint::Index)
+    }
+}
+
+fn apply_hint<'a>(builder: QueryBuilder<'a>, hint: &Option<IndexHint>) -> QueryBuilder<'a> {
+    match hint {
+        Some(IndexHint::Index(name)) => builder.hint(name),
+        Some(IndexHint::NoIndex) => builder.hint_none(),
+        None => builder,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn run(
     ctx: &CommandContext,
     collection: &str,
@@ -13,6 +32,7 @@ pub fn run(
     order: &str,
     limit: Option<usize>,
     skip: Option<usize>,
+    hint: Option<IndexHint>,
 ) -> Result<()> {
     let coll = ctx.db.collection(collection);
 
@@ -38,27 +58,84 @@ pub fn run(
         builder = builder.skip(skip);
     }
 
-    let results = builder.execute()?;
+    let results = apply_hint(builder, &hint).execute()?;
 
     let results_json = Value::Array(results);
-    println!("{}", format_json(&results_json, &ctx.format)?);
+    print_paged(&format_json(&results_json, &ctx.format)?);
 
     Ok(())
 }
 
-pub fn aggregate(ctx: &CommandContext, collection: &str, _pipeline: &str) -> Result<()> {
-    // Note: The aggregation pipeline API uses builder methods like match_(), group_by(), etc.
-    // For now, this is a simplified implementation
+pub fn explain(
+    ctx: &CommandContext,
+    collection: &str,
+    filter: &str,
+    sort: Option<&str>,
+    order: &str,
+    hint: Option<IndexHint>,
+) -> Result<()> {
     let coll = ctx.db.collection(collection);
 
-    // Execute a simple aggregation (group all documents)
-    let results = coll.aggregate()
-        .execute()?;
+    let mut builder = coll.query();
+
+    if filter != "{}" {
+        builder = builder.filter(filter);
+    }
+
+    if let Some(sort_field) = sort {
+        let sort_order = match order.to_lowercase().as_str() {
+            "desc" | "descending" => SortOrder::Desc,
+            _ => SortOrder::Asc,
+        };
+        builder = builder.sort_by(sort_field, sort_order);
+    }
+
+    let explain = apply_hint(builder, &hint).explain()?;
+
+    if let OutputFormat::Pretty = ctx.format {
+        let access_path = serde_json::to_value(explain.access_path)?;
+        print_info(&format!("Access path:        {}", access_path.as_str().unwrap_or_default()));
+        if let Some(index) = &explain.index {
+            print_info(&format!("Index:              {} ({} keys examined)", index, explain.keys_examined));
+        }
+        match &explain.hint {
+            Some(IndexHint::Index(name)) => print_info(&format!("Hint:               {}", name)),
+            Some(IndexHint::NoIndex) => print_info("Hint:               no index"),
+            None => {}
+        }
+        print_info(&format!(
+            "Documents examined: {} (estimated {})",
+            explain.docs_examined, explain.estimated_docs_examined
+        ));
+        print_info(&format!("Documents returned: {}", explain.docs_returned));
+        print_info(&format!("In-memory sort:     {}", if explain.in_memory_sort { "yes" } else { "no" }));
+        print_info(&format!("Elapsed:            {:.3} ms", explain.elapsed_micros as f64 / 1000.0));
+        if !explain.filter_fields.is_empty() {
+            print_info(&format!("Filter fields:      {}", explain.filter_fields.join(", ")));
+        }
+        if !explain.candidate_indexes.is_empty() {
+            print_info(&format!("Indexes on filter:  {}", explain.candidate_indexes.join(", ")));
+        }
+    } else {
+        print_paged(&format_json(&serde_json::to_value(&explain)?, &ctx.format)?);
+    }
+
+    Ok(())
+}
+
+pub fn aggregate(ctx: &CommandContext, collection: &str, pipeline: &ArgInput) -> Result<()> {
+    let stages = match parse_json_input(pipeline)? {
+        Value::Array(stages) => stages,
+        other => anyhow::bail!("Expected a JSON array of stages in {}, got {}", pipeline.source, other),
+    };
+
+    let coll = ctx.db.collection(collection);
+    let results = coll.aggregate().stages(&stages)?.execute()?;
 
     let results_json = Value::Array(results);
-    println!("{}", format_json(&results_json, &ctx.format)?);
+    print_paged(&format_json(&results_json, &ctx.format)?);
 
-    anyhow::bail!("Advanced aggregation pipelines are not yet supported in the CLI. Use the database API directly for complex aggregations.")
+    Ok(())
 }
 
 pub fn search(ctx: &CommandContext, collection: &str, text: &str, limit: Option<usize>) -> Result<()> {
@@ -86,7 +163,7 @@ pub fn search(ctx: &CommandContext, collection: &str, text: &str, limit: Option<
             .collect(),
     );
 
-    println!("{}", format_json(&results_json, &ctx.format)?);
+    print_paged(&format_json(&results_json, &ctx.format)?);
 
     Ok(())
 }
@@ -1,6 +1,6 @@
 use anyhow::Result;
 use crate::commands::CommandContext;
-use crate::formatter::{print_success, print_info, format_json};
+use crate::formatter::{print_success, print_info, format_json, print_paged};
 use crate::utils::parse_json;
 use serde_json::json;
 
@@ -21,7 +21,7 @@ pub fn get(ctx: &CommandContext, collection: &str) -> Result<()> {
     if let Some(coll_meta) = metadata.collections.get(collection) {
         if let Some(schema) = &coll_meta.schema {
             let schema_json = serde_json::to_value(schema)?;
-            println!("{}", format_json(&schema_json, &ctx.format)?);
+            print_paged(&format_json(&schema_json, &ctx.format)?);
         } else {
             print_info(&format!("No schema set for collection '{}'", collection));
         }
@@ -75,7 +75,7 @@ pub fn validate(ctx: &CommandContext, collection: &str) -> Result<()> {
         "errors": errors,
     });
 
-    println!("{}", format_json(&result, &ctx.format)?);
+    print_paged(&format_json(&result, &ctx.format)?);
 
     if invalid_count > 0 {
         print_info(&format!("Found {} invalid document(s)", invalid_count));
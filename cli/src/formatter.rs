@@ -2,18 +2,30 @@ use anyhow::Result;
 use colored::Colorize;
 use comfy_table::{Table, Cell, Color, Attribute};
 use serde_json::Value;
+use std::io::{IsTerminal, Read, Write};
 
 pub enum OutputFormat {
     Json,
     Pretty,
-    Table,
+    Table(TableOptions),
+}
+
+/// Column selection and cell truncation for table output
+#[derive(Clone, Default)]
+pub struct TableOptions {
+    /// Fields to show, in this order, or every field when empty. Dotted paths
+    /// reach into nested objects.
+    pub columns: Vec<String>,
+    /// Cut cells longer than this many characters with an ellipsis, or 0 to
+    /// keep them whole
+    pub cell_width: usize,
 }
 
 impl OutputFormat {
-    pub fn from_str(s: &str) -> Self {
+    pub fn new(s: &str, table: TableOptions) -> Self {
         match s.to_lowercase().as_str() {
             "json" => OutputFormat::Json,
-            "table" => OutputFormat::Table,
+            "table" => OutputFormat::Table(table),
             _ => OutputFormat::Pretty,
         }
     }
@@ -26,12 +38,12 @@ pub fn format_json(value: &Value, format: &OutputFormat) -> Result<String> {
             let json_str = serde_json::to_string_pretty(value)?;
             Ok(colored_json::to_colored_json_auto(&json_str)?.to_string())
         }
-        OutputFormat::Table => {
+        OutputFormat::Table(options) => {
             // For table format, treat as array of objects
             if let Value::Array(arr) = value {
-                format_table(arr)
+                format_table(arr, options)
             } else if let Value::Object(_) = value {
-                format_table(&vec![value.clone()])
+                format_table(std::slice::from_ref(value), options)
             } else {
                 Ok(serde_json::to_string_pretty(value)?)
             }
@@ -39,7 +51,7 @@ pub fn format_json(value: &Value, format: &OutputFormat) -> Result<String> {
     }
 }
 
-pub fn format_table(docs: &[Value]) -> Result<String> {
+pub fn format_table(docs: &[Value], options: &TableOptions) -> Result<String> {
     if docs.is_empty() {
         return Ok("No documents found".to_string());
     }
@@ -48,17 +60,22 @@ pub fn format_table(docs: &[Value]) -> Result<String> {
     table.load_preset(comfy_table::presets::UTF8_FULL);
     table.apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS);
 
-    // Get all unique keys from all documents
-    let mut keys = std::collections::HashSet::new();
-    for doc in docs {
-        if let Value::Object(map) = doc {
-            for key in map.keys() {
-                keys.insert(key.clone());
+    let keys = if options.columns.is_empty() {
+        // Get all unique keys from all documents
+        let mut keys = std::collections::HashSet::new();
+        for doc in docs {
+            if let Value::Object(map) = doc {
+                for key in map.keys() {
+                    keys.insert(key.clone());
+                }
             }
         }
-    }
-    let mut keys: Vec<_> = keys.into_iter().collect();
-    keys.sort();
+        let mut keys: Vec<_> = keys.into_iter().collect();
+        keys.sort();
+        keys
+    } else {
+        options.columns.clone()
+    };
 
     // Add header
     let header: Vec<Cell> = keys
@@ -69,12 +86,12 @@ pub fn format_table(docs: &[Value]) -> Result<String> {
 
     // Add rows
     for doc in docs {
-        if let Value::Object(map) = doc {
+        if doc.is_object() {
             let row: Vec<Cell> = keys
                 .iter()
                 .map(|key| {
-                    let val = map.get(key).unwrap_or(&Value::Null);
-                    Cell::new(format_value_compact(val))
+                    let val = lookup_path(doc, key).unwrap_or(&Value::Null);
+                    Cell::new(format_value_compact(val, options.cell_width))
                 })
                 .collect();
             table.add_row(row);
@@ -84,17 +101,84 @@ pub fn format_table(docs: &[Value]) -> Result<String> {
     Ok(table.to_string())
 }
 
-fn format_value_compact(val: &Value) -> String {
+/// Follow a dotted path such as `address.city` into nested objects
+fn lookup_path<'a>(doc: &'a Value, path: &str) -> Option<&'a Value> {
+    if let Some(value) = doc.get(path) {
+        return Some(value);
+    }
+    path.split('.').try_fold(doc, |value, part| value.get(part))
+}
+
+fn format_value_compact(val: &Value, width: usize) -> String {
     match val {
         Value::Null => "null".dimmed().to_string(),
         Value::Bool(b) => b.to_string(),
         Value::Number(n) => n.to_string(),
-        Value::String(s) => s.clone(),
+        Value::String(s) => truncate(s, width),
         Value::Array(arr) => format!("[{} items]", arr.len()),
         Value::Object(obj) => format!("{{{} fields}}", obj.len()),
     }
 }
 
+/// Cut `s` to `width` characters, ending with an ellipsis when anything was
+/// dropped. A width of 0 leaves it whole.
+fn truncate(s: &str, width: usize) -> String {
+    if width == 0 || s.chars().count() <= width {
+        return s.to_string();
+    }
+    let mut cut: String = s.chars().take(width.saturating_sub(1)).collect();
+    cut.push('…');
+    cut
+}
+
+/// Print command output, paging it when stdout is a terminal and the output
+/// is taller than the screen. Space or Enter shows the next page, q stops.
+pub fn print_paged(text: &str) {
+    let height = match crossterm::terminal::size() {
+        Ok((_, rows)) if std::io::stdout().is_terminal() && std::io::stdin().is_terminal() => rows as usize,
+        _ => 0,
+    };
+
+    let lines: Vec<&str> = text.lines().collect();
+    if height < 2 || lines.len() < height {
+        println!("{}", text);
+        return;
+    }
+
+    let page_size = height - 1;
+    let pages = lines.chunks(page_size).count();
+    for (page, chunk) in lines.chunks(page_size).enumerate() {
+        for line in chunk {
+            println!("{}", line);
+        }
+        if page + 1 == pages {
+            break;
+        }
+
+        let shown = (page + 1) * page_size;
+        print!("{}", format!("-- {}/{} lines, space for more, q to quit --", shown, lines.len()).reversed());
+        let _ = std::io::stdout().flush();
+        let key = read_key();
+        print!("\r\x1b[K");
+        if matches!(key, Some(b'q') | Some(b'Q') | Some(0x1b) | Some(0x03) | None) {
+            break;
+        }
+    }
+    let _ = std::io::stdout().flush();
+}
+
+/// Read a single key press without waiting for Enter
+fn read_key() -> Option<u8> {
+    crossterm::terminal::enable_raw_mode().ok()?;
+    let mut byte = [0u8; 1];
+    let read = std::io::stdin().read(&mut byte);
+    let _ = crossterm::terminal::disable_raw_mode();
+    match read {
+        Ok(1) => Some(byte[0]),
+        _ => None,
+    }
+}
+
 pub fn print_success(msg: &str) {
     println!("{} {}", "✓".green().bold(), msg);
 }
@@ -6,7 +6,9 @@ mod formatter;
 mod repl;
 mod utils;
 
-use commands::CommandContext;
+use commands::{CommandContext, WebUiConfig};
+use formatter::{OutputFormat, TableOptions};
+use jasonisnthappy::{Collation, DatabaseOptions, IndexOptions};
 
 #[derive(Parser)]
 #[command(name = "jasonisnthappy")]
@@ -28,10 +30,39 @@ struct Cli {
     #[arg(long, default_value = "127.0.0.1:8080")]
     web_address: String,
 
+    /// Require this bearer token on web UI API requests
+    #[arg(long, value_name = "TOKEN")]
+    web_auth_token: Option<String>,
+
+    /// Serve the web UI without allowing any modifications
+    #[arg(long)]
+    web_read_only: bool,
+
+    /// Open the database read-only, alongside other read-only processes
+    #[arg(long)]
+    read_only: bool,
+
+    /// Milliseconds to wait for another process to release the database
+    #[arg(long, value_name = "MS", default_value_t = 0)]
+    lock_timeout: u64,
+
+    /// Log queries that take at least this many milliseconds, listed by
+    /// 'db slow-queries' in the REPL and at /api/slow-queries (0 for off)
+    #[arg(long, value_name = "MS", default_value_t = 0)]
+    slow_query_threshold: u64,
+
     /// Output format: json, table, pretty
     #[arg(short, long, default_value = "pretty")]
     format: String,
 
+    /// Fields to show in table output, e.g. name,address.city
+    #[arg(long, value_delimiter = ',')]
+    columns: Vec<String>,
+
+    /// Truncate table cells longer than this many characters (0 for no limit)
+    #[arg(long, default_value_t = 40)]
+    cell_width: usize,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -71,23 +102,77 @@ enum Commands {
         collection: String,
     },
 
-    /// Export collection to JSON file
+    /// Export collection to a JSON, NDJSON or CSV file
     Export {
         /// Collection name
         collection: String,
         /// Output file path
         output: String,
+        #[command(flatten)]
+        file_format: FileFormatArgs,
     },
 
-    /// Import collection from JSON file
+    /// Import collection from a JSON, NDJSON or CSV file
     Import {
         /// Collection name
         collection: String,
         /// Input file path
         input: String,
+        #[command(flatten)]
+        file_format: FileFormatArgs,
+        /// Documents inserted per transaction
+        #[arg(long, default_value_t = 1000)]
+        batch_size: usize,
+        /// Skip malformed NDJSON lines instead of aborting the import
+        #[arg(long)]
+        continue_on_error: bool,
+        /// Check every document against the schema, existing IDs and unique
+        /// indexes and report the ones that would fail, without importing
+        #[arg(long)]
+        validate_only: bool,
+    },
+
+    /// Dump every collection with its indexes and schema to an archive file
+    Dump {
+        /// Output file path
+        output: String,
+    },
+
+    /// Load collections, indexes and schemas from an archive written by dump
+    Load {
+        /// Input file path
+        input: String,
+        /// Documents inserted per transaction
+        #[arg(long, default_value_t = 1000)]
+        batch_size: usize,
     },
 }
 
+#[derive(clap::Args)]
+struct FileFormatArgs {
+    /// File format: json, ndjson or csv. NDJSON holds one document per line
+    /// and is streamed on import. CSV nests objects with dotted column headers
+    /// (address.city) and stores arrays as JSON-encoded cells
+    #[arg(long = "format", default_value = "json")]
+    format: String,
+    /// CSV field delimiter
+    #[arg(long, default_value_t = ',')]
+    delimiter: char,
+    /// CSV has no header row. On import, columns are named column1, column2, ...
+    #[arg(long)]
+    no_header: bool,
+}
+
+impl From<FileFormatArgs> for commands::document::FileFormatOptions {
+    fn from(args: FileFormatArgs) -> Self {
+        Self {
+            format: args.format.to_lowercase(),
+            delimiter: args.delimiter,
+            no_header: args.no_header,
+        }
+    }
+}
+
 #[derive(Subcommand)]
 enum DbCommands {
     /// Show database information
@@ -102,6 +187,31 @@ enum DbCommands {
     /// Compact the database
     Compact,
 
+    /// Check the database file for corruption; exits non-zero if any is found
+    Check,
+
+    /// Show how the file's pages are used and how much is reclaimable
+    Space,
+
+    /// Rebuild a database whose metadata is damaged from its pages
+    Repair {
+        /// Confirm rewriting the file; take a copy of it first
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// List the queries that took longer than --slow-query-threshold
+    SlowQueries,
+
+    /// Read B-trees and documents into the page cache, up to its size
+    Warm {
+        /// Collection to warm; all collections if omitted
+        collection: Option<String>,
+        /// Only read B-trees, not document pages
+        #[arg(long)]
+        trees_only: bool,
+    },
+
     /// List all collections
     Collections,
 }
@@ -144,9 +254,12 @@ enum DocumentCommands {
     Find {
         /// Collection name
         collection: String,
-        /// Query filter (JSON)
+        /// Query filter, `@path` to read it from a file or `-` for stdin
         #[arg(default_value = "{}")]
         query: String,
+        /// Read the query filter from this file
+        #[arg(long, conflicts_with = "query")]
+        file: Option<String>,
         /// Limit number of results
         #[arg(short, long)]
         limit: Option<usize>,
@@ -212,14 +325,45 @@ enum QueryCommands {
         /// Skip
         #[arg(long)]
         skip: Option<usize>,
+        /// Answer the query from this index
+        #[arg(long)]
+        hint: Option<String>,
+        /// Scan the whole collection without using any index
+        #[arg(long, conflicts_with = "hint")]
+        no_index: bool,
+    },
+
+    /// Run a query and show how it was executed
+    Explain {
+        /// Collection name
+        collection: String,
+        /// Filter
+        #[arg(short, long, default_value = "{}")]
+        filter: String,
+        /// Sort field
+        #[arg(short, long)]
+        sort: Option<String>,
+        /// Sort order: asc or desc
+        #[arg(long, default_value = "asc")]
+        order: String,
+        /// Answer the query from this index
+        #[arg(long)]
+        hint: Option<String>,
+        /// Scan the whole collection without using any index
+        #[arg(long, conflicts_with = "hint")]
+        no_index: bool,
     },
 
     /// Run an aggregation pipeline
     Aggregate {
         /// Collection name
         collection: String,
-        /// Aggregation pipeline (JSON array)
-        pipeline: String,
+        /// Aggregation pipeline (JSON array), `@path` to read it from a file or `-` for stdin
+        #[arg(required_unless_present = "file")]
+        pipeline: Option<String>,
+        /// Read the aggregation pipeline from this file
+        #[arg(long, conflicts_with = "pipeline")]
+        file: Option<String>,
     },
 
     /// Full-text search
@@ -251,6 +395,24 @@ enum IndexCommands {
         /// Make it unique
         #[arg(short, long)]
         unique: bool,
+        /// Keep entries in descending order
+        #[arg(short, long)]
+        descending: bool,
+        /// Skip documents where the field is missing or null
+        #[arg(short, long)]
+        sparse: bool,
+        /// Compare strings without regard to case, so a unique index
+        /// rejects values differing only in case
+        #[arg(long)]
+        case_insensitive: bool,
+    },
+
+    /// Rebuild an index from the collection's documents
+    Rebuild {
+        /// Collection name
+        collection: String,
+        /// Index name
+        name: String,
     },
 
     /// Drop an index
@@ -260,6 +422,12 @@ enum IndexCommands {
         /// Index name
         name: String,
     },
+
+    /// Show how many queries used each index since the database was opened
+    Usage {
+        /// Collection name
+        collection: String,
+    },
 }
 
 #[derive(Subcommand)]
@@ -288,10 +456,28 @@ enum SchemaCommands {
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
+    let web_ui = cli.web_ui.then(|| WebUiConfig {
+        address: cli.web_address.clone(),
+        auth_token: cli.web_auth_token.clone(),
+        read_only: cli.web_read_only,
+    });
+
+    let format = OutputFormat::new(&cli.format, TableOptions {
+        columns: cli.columns.clone(),
+        cell_width: cli.cell_width,
+    });
+
+    let db_options = DatabaseOptions {
+        read_only: cli.read_only,
+        lock_timeout_ms: cli.lock_timeout,
+        slow_query_threshold_ms: cli.slow_query_threshold,
+        ..DatabaseOptions::default()
+    };
+
     // If interactive mode or no command specified, start REPL
     if cli.interactive || (cli.command.is_none() && cli.database.is_some()) {
         let db_path = cli.database.as_deref().unwrap_or("data.db");
-        return repl::start(db_path, &cli.format, cli.web_ui, &cli.web_address);
+        return repl::start(db_path, db_options, format, web_ui.as_ref());
     }
 
     // Require database path for non-interactive commands
@@ -299,12 +485,17 @@ fn main() -> Result<()> {
         anyhow::anyhow!("Database path required. Usage: jasonisnthappy <DATABASE> [COMMAND]")
     })?;
 
+    // Repair works on databases that may not open at all
+    if let Some(Commands::Db(DbCommands::Repair { force })) = &cli.command {
+        return commands::db::repair(db_path, *force, &format);
+    }
+
     // Create command context
-    let mut ctx = CommandContext::new(db_path, &cli.format)?;
+    let mut ctx = CommandContext::new(db_path, db_options, format)?;
 
     // Start web UI if requested
-    if cli.web_ui {
-        ctx.start_web_ui(&cli.web_address)?;
+    if let Some(config) = &web_ui {
+        ctx.start_web_ui(config)?;
     }
 
     // Execute command
@@ -324,6 +515,13 @@ fn execute_command(ctx: &mut CommandContext, command: Commands) -> Result<()> {
             DbCommands::Info => commands::db::info(ctx),
             DbCommands::Backup { destination } => commands::db::backup(ctx, &destination),
             DbCommands::Compact => commands::db::compact(ctx),
+            DbCommands::Check => commands::db::check(ctx),
+            DbCommands::Space => commands::db::space(ctx),
+            DbCommands::Repair { .. } => unreachable!("repair runs before the database is opened"),
+            DbCommands::SlowQueries => commands::db::slow_queries(ctx),
+            DbCommands::Warm { collection, trees_only } => {
+                commands::db::warm(ctx, collection.as_deref(), !trees_only)
+            }
             DbCommands::Collections => commands::collection::list(ctx),
         },
         Commands::Collection(cmd) => match cmd {
@@ -336,7 +534,9 @@ fn execute_command(ctx: &mut CommandContext, command: Commands) -> Result<()> {
             DocumentCommands::Insert { collection, document } => {
                 commands::document::insert(ctx, &collection, &document)
             }
-            DocumentCommands::Find { collection, query, limit, skip } => {
+            DocumentCommands::Find { collection, query, file, limit, skip } => {
+                let input = utils::read_arg_input(Some(&query), file.as_deref())?;
+                let query = commands::document::query_text(&input)?;
                 commands::document::find(ctx, &collection, &query, limit, skip)
             }
             DocumentCommands::FindOne { collection, query } => {
@@ -353,11 +553,17 @@ fn execute_command(ctx: &mut CommandContext, command: Commands) -> Result<()> {
             }
         },
         Commands::Query(cmd) => match cmd {
-            QueryCommands::Run { collection, filter, sort, order, limit, skip } => {
-                commands::query::run(ctx, &collection, &filter, sort.as_deref(), &order, limit, skip)
+            QueryCommands::Run { collection, filter, sort, order, limit, skip, hint, no_index } => {
+                let hint = commands::query::index_hint(hint, no_index);
+                commands::query::run(ctx, &collection, &filter, sort.as_deref(), &order, limit, skip, hint)
             }
-            QueryCommands::Aggregate { collection, pipeline } => {
-                commands::query::aggregate(ctx, &collection, &pipeline)
+            QueryCommands::Explain { collection, filter, sort, order, hint, no_index } => {
+                let hint = commands::query::index_hint(hint, no_index);
+                commands::query::explain(ctx, &collection, &filter, sort.as_deref(), &order, hint)
+            }
+            QueryCommands::Aggregate { collection, pipeline, file } => {
+                let input = utils::read_arg_input(pipeline.as_deref(), file.as_deref())?;
+                commands::query::aggregate(ctx, &collection, &input)
             }
             QueryCommands::Search { collection, text, limit } => {
                 commands::query::search(ctx, &collection, &text, limit)
@@ -365,12 +571,18 @@ fn execute_command(ctx: &mut CommandContext, command: Commands) -> Result<()> {
         },
         Commands::Index(cmd) => match cmd {
             IndexCommands::List { collection } => commands::index::list(ctx, &collection),
-            IndexCommands::Create { collection, field, unique } => {
-                commands::index::create(ctx, &collection, &field, unique)
+            IndexCommands::Create { collection, field, unique, descending, sparse, case_insensitive } => {
+                let collation = if case_insensitive { Collation::CaseInsensitive } else { Collation::Binary };
+                let options = IndexOptions { unique, descending, sparse, collation };
+                commands::index::create(ctx, &collection, &field, options)
+            }
+            IndexCommands::Rebuild { collection, name } => {
+                commands::index::rebuild(ctx, &collection, &name)
             }
             IndexCommands::Drop { collection, name } => {
                 commands::index::drop(ctx, &collection, &name)
             }
+            IndexCommands::Usage { collection } => commands::index::usage(ctx, &collection),
         },
         Commands::Schema(cmd) => match cmd {
             SchemaCommands::Set { collection, schema } => {
@@ -383,11 +595,14 @@ fn execute_command(ctx: &mut CommandContext, command: Commands) -> Result<()> {
         },
         Commands::Metrics => commands::metrics::show(ctx),
         Commands::Watch { collection } => commands::metrics::watch(ctx, &collection),
-        Commands::Export { collection, output } => {
-            commands::document::export(ctx, &collection, &output)
+        Commands::Export { collection, output, file_format } => {
+            commands::document::export(ctx, &collection, &output, &file_format.into())
         }
-        Commands::Import { collection, input } => {
-            commands::document::import(ctx, &collection, &input)
+        Commands::Import { collection, input, file_format, batch_size, continue_on_error, validate_only } => {
+            let import_opts = commands::document::ImportOptions { batch_size, continue_on_error, validate_only };
+            commands::document::import(ctx, &collection, &input, &file_format.into(), &import_opts)
         }
+        Commands::Dump { output } => commands::db::dump(ctx, &output),
+        Commands::Load { input, batch_size } => commands::db::load(ctx, &input, batch_size),
     }
 }
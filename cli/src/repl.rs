@@ -1,23 +1,123 @@
 use anyhow::Result;
+use rustyline::completion::Completer;
 use rustyline::error::ReadlineError;
-use rustyline::{DefaultEditor, Result as RustyResult};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Context, Editor, Helper};
 use colored::Colorize;
-use crate::commands::CommandContext;
-use crate::formatter::{print_success, print_error, print_info};
+use std::path::PathBuf;
+use crate::commands::{CommandContext, WebUiConfig};
+use crate::commands::document::{FileFormatOptions, ImportOptions};
+use crate::formatter::{print_success, print_error, print_info, OutputFormat};
+use jasonisnthappy::{Collation, Database, DatabaseOptions, IndexOptions};
+
+/// Commands offered by tab-completion at the start of a line
+const COMMANDS: &[&str] = &[
+    "help", "exit", "quit", "info", "collections", "metrics", "backup", "check", "space", "slow-queries", "warm", "use", "create", "drop",
+    "insert", "find", "count", "update", "delete", "indexes", "index-usage", "create-index", "export", "import",
+];
+
+/// Line editor helper: completes commands and collection names, and keeps
+/// reading lines until the braces and brackets of pasted JSON balance
+struct ReplHelper {
+    db: Database,
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        let before = &line[..pos];
+        let start = before.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let word = &before[start..];
+
+        let candidates = match before[..start].split_whitespace().collect::<Vec<_>>().as_slice() {
+            [] => COMMANDS.iter().map(|c| c.to_string()).collect(),
+            ["use"] => self.db.list_collections().unwrap_or_default(),
+            _ => Vec::new(),
+        };
+
+        Ok((start, candidates.into_iter().filter(|c| c.starts_with(word)).collect()))
+    }
+}
+
+impl Validator for ReplHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        if has_open_brackets(ctx.input()) {
+            Ok(ValidationResult::Incomplete)
+        } else {
+            Ok(ValidationResult::Valid(None))
+        }
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+/// Whether `input` has braces or brackets left open, ignoring any inside
+/// JSON strings
+fn has_open_brackets(input: &str) -> bool {
+    let mut depth = 0i32;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for c in input.chars() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_string = true,
+            '{' | '[' => depth += 1,
+            '}' | ']' => depth -= 1,
+            _ => {}
+        }
+    }
+
+    depth > 0
+}
+
+/// History file in the user's home directory, if one can be found
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(".jasonisnthappy_history"))
+}
 
-pub fn start(db_path: &str, format: &str, web_ui: bool, web_address: &str) -> Result<()> {
+pub fn start(db_path: &str, options: DatabaseOptions, format: OutputFormat, web_ui: Option<&WebUiConfig>) -> Result<()> {
     println!("{}", "jasonisnthappy interactive shell".bright_cyan().bold());
     println!("Database: {}", db_path);
     println!("Type 'help' for available commands, 'exit' to quit\n");
 
-    let mut ctx = CommandContext::new(db_path, format)?;
+    let mut ctx = CommandContext::new(db_path, options, format)?;
 
     // Start web UI if requested
-    if web_ui {
-        ctx.start_web_ui(web_address)?;
+    if let Some(config) = web_ui {
+        ctx.start_web_ui(config)?;
+    }
+
+    let mut rl: Editor<ReplHelper, DefaultHistory> = Editor::new()?;
+    rl.set_helper(Some(ReplHelper { db: ctx.db.clone() }));
+
+    let history = history_path();
+    if let Some(ref path) = history {
+        // A missing history file just means this is the first session
+        let _ = rl.load_history(path);
     }
 
-    let mut rl = DefaultEditor::new()?;
     let mut current_collection: Option<String> = None;
 
     loop {
@@ -39,6 +139,10 @@ pub fn start(db_path: &str, format: &str, web_ui: bool, web_address: &str) -> Re
 
                 let _ = rl.add_history_entry(line);
 
+                if line == "exit" || line == "quit" {
+                    break;
+                }
+
                 if let Err(e) = execute_repl_command(&mut ctx, line, &mut current_collection) {
                     print_error(&format!("{}", e));
                 }
@@ -58,6 +162,12 @@ pub fn start(db_path: &str, format: &str, web_ui: bool, web_address: &str) -> Re
         }
     }
 
+    if let Some(ref path) = history {
+        if let Err(e) = rl.save_history(path) {
+            print_error(&format!("Failed to save history to {}: {}", path.display(), e));
+        }
+    }
+
     Ok(())
 }
 
@@ -74,10 +184,13 @@ fn execute_repl_command(
 
     match parts[0] {
         "help" => show_help(),
-        "exit" | "quit" => std::process::exit(0),
         "info" => crate::commands::db::info(ctx)?,
         "collections" | "show collections" => crate::commands::collection::list(ctx)?,
         "metrics" => crate::commands::metrics::show(ctx)?,
+        "check" => crate::commands::db::check(ctx)?,
+        "space" => crate::commands::db::space(ctx)?,
+        "slow-queries" => crate::commands::db::slow_queries(ctx)?,
+        "warm" => crate::commands::db::warm(ctx, parts.get(1).copied(), true)?,
 
         "use" => {
             if parts.len() < 2 {
@@ -210,17 +323,34 @@ fn execute_repl_command(
             }
         }
 
+        "index-usage" => {
+            if current_collection.is_none() {
+                print_error("No collection selected. Use 'use <collection>' first");
+            } else if let Some(ref coll) = current_collection {
+                crate::commands::index::usage(ctx, coll)?;
+            }
+        }
+
         "create-index" | "createIndex" => {
             if current_collection.is_none() {
                 print_error("No collection selected. Use 'use <collection>' first");
             } else if parts.len() < 2 {
-                print_error("Usage: create-index <field> [--unique]");
+                print_error("Usage: create-index <field> [--unique] [--descending] [--sparse] [--case-insensitive]");
             } else {
                 let field = parts[1];
-                let unique = parts.contains(&"--unique");
+                let options = IndexOptions {
+                    unique: parts.contains(&"--unique"),
+                    descending: parts.contains(&"--descending"),
+                    sparse: parts.contains(&"--sparse"),
+                    collation: if parts.contains(&"--case-insensitive") {
+                        Collation::CaseInsensitive
+                    } else {
+                        Collation::Binary
+                    },
+                };
 
                 if let Some(ref coll) = current_collection {
-                    crate::commands::index::create(ctx, coll, field, unique)?;
+                    crate::commands::index::create(ctx, coll, field, options)?;
                 }
             }
         }
@@ -239,7 +369,7 @@ fn execute_repl_command(
             } else if parts.len() < 2 {
                 print_error("Usage: export <file>");
             } else if let Some(ref coll) = current_collection {
-                crate::commands::document::export(ctx, coll, parts[1])?;
+                crate::commands::document::export(ctx, coll, parts[1], &FileFormatOptions::for_path(parts[1]))?;
             }
         }
 
@@ -249,7 +379,7 @@ fn execute_repl_command(
             } else if parts.len() < 2 {
                 print_error("Usage: import <file>");
             } else if let Some(ref coll) = current_collection {
-                crate::commands::document::import(ctx, coll, parts[1])?;
+                crate::commands::document::import(ctx, coll, parts[1], &FileFormatOptions::for_path(parts[1]), &ImportOptions::default())?;
             }
         }
 
@@ -269,6 +399,10 @@ fn show_help() {
     println!("    collections             - List all collections");
     println!("    metrics                 - Show database metrics");
     println!("    backup <dest>           - Create database backup");
+    println!("    check                   - Check the database for corruption");
+    println!("    space                   - Show page usage and reclaimable space");
+    println!("    slow-queries            - List queries slower than --slow-query-threshold");
+    println!("    warm [collection]       - Read collections into the page cache");
     println!();
     println!("{}", "  Collection Commands:".bright_yellow());
     println!("    use <collection>        - Select a collection to work with");
@@ -284,12 +418,16 @@ fn show_help() {
     println!();
     println!("{}", "  Index Commands:".bright_yellow());
     println!("    indexes                 - List indexes");
+    println!("    index-usage             - Queries served by each index this session");
     println!("    create-index <field>    - Create index");
     println!("    create-index <field> --unique - Create unique index");
+    println!("    create-index <field> --descending - Create descending index");
+    println!("    create-index <field> --sparse - Skip documents missing the field");
+    println!("    create-index <field> --case-insensitive - Compare strings without regard to case");
     println!();
     println!("{}", "  Data Commands:".bright_yellow());
-    println!("    export <file>           - Export collection to JSON file");
-    println!("    import <file>           - Import collection from JSON file");
+    println!("    export <file>           - Export collection to JSON file (CSV for .csv, NDJSON for .ndjson)");
+    println!("    import <file>           - Import collection from JSON file (CSV for .csv, NDJSON for .ndjson)");
     println!();
     println!("{}", "  General:".bright_yellow());
     println!("    help                    - Show this help message");
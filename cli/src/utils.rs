@@ -1,5 +1,6 @@
 use anyhow::{Result, Context};
 use serde_json::Value;
+use std::io::Read;
 
 pub fn parse_json(input: &str) -> Result<Value> {
     serde_json::from_str(input).context("Failed to parse JSON")
@@ -20,3 +21,143 @@ pub fn parse_json_array(input: &str) -> Result<Vec<Value>> {
         _ => anyhow::bail!("Expected JSON array, got {}", value),
     }
 }
+
+/// Text of a command argument together with a label naming where it came from
+pub struct ArgInput {
+    pub text: String,
+    pub source: String,
+}
+
+/// Resolve an argument that may point at its input: `@path` reads the file,
+/// `-` reads stdin and anything else is used as given. `file` is the value of
+/// a `--file` flag and takes the place of the argument when set.
+pub fn read_arg_input(arg: Option<&str>, file: Option<&str>) -> Result<ArgInput> {
+    let path = match (file, arg) {
+        (Some(path), _) => path,
+        (None, Some("-")) => {
+            let mut text = String::new();
+            std::io::stdin().read_to_string(&mut text).context("Failed to read stdin")?;
+            return Ok(ArgInput { text, source: "stdin".to_string() });
+        }
+        (None, Some(arg)) => match arg.strip_prefix('@') {
+            Some(path) => path,
+            None => return Ok(ArgInput { text: arg.to_string(), source: "argument".to_string() }),
+        },
+        (None, None) => anyhow::bail!("No input given"),
+    };
+
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path))?;
+    Ok(ArgInput { text, source: path.to_string() })
+}
+
+/// Parse JSON read by `read_arg_input`, naming the source and the line and
+/// column of a syntax error
+pub fn parse_json_input(input: &ArgInput) -> Result<Value> {
+    serde_json::from_str(&input.text).map_err(|e| {
+        anyhow::anyhow!(
+            "Invalid JSON in {} at line {}, column {}: {}",
+            input.source, e.line(), e.column(), json_error_message(&e)
+        )
+    })
+}
+
+/// The error text without serde_json's own " at line N column M" suffix
+fn json_error_message(e: &serde_json::Error) -> String {
+    let message = e.to_string();
+    let suffix = format!(" at line {} column {}", e.line(), e.column());
+    message.strip_suffix(&suffix).unwrap_or(&message).to_string()
+}
+
+/// Flatten a document into `(column, cell)` pairs for CSV export. Nested
+/// objects become dotted columns (`address.city`), arrays and empty objects
+/// are JSON-encoded, `null` is written as `null`.
+pub fn flatten_document(doc: &Value) -> Vec<(String, String)> {
+    let mut cells = Vec::new();
+    if let Value::Object(obj) = doc {
+        for (key, value) in obj {
+            flatten_value(key.clone(), value, &mut cells);
+        }
+    }
+    cells
+}
+
+fn flatten_value(prefix: String, value: &Value, cells: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(obj) if !obj.is_empty() => {
+            for (key, child) in obj {
+                flatten_value(format!("{}.{}", prefix, key), child, cells);
+            }
+        }
+        Value::String(s) => cells.push((prefix, s.clone())),
+        other => cells.push((prefix, other.to_string())),
+    }
+}
+
+/// Rebuild a document from CSV columns and cells, the inverse of
+/// `flatten_document`. Empty cells are left out of the document and other
+/// cells are typed with `infer_cell_value`, except `_id` which stays a string.
+pub fn unflatten_row(columns: &[String], cells: &[String]) -> Result<Value> {
+    let mut doc = serde_json::Map::new();
+
+    for (column, cell) in columns.iter().zip(cells) {
+        if cell.is_empty() {
+            continue;
+        }
+
+        let value = if column == "_id" {
+            Value::String(cell.clone())
+        } else {
+            infer_cell_value(cell)
+        };
+
+        let mut parts: Vec<&str> = column.split('.').collect();
+        let leaf = parts.pop().unwrap_or_default();
+        let mut target = &mut doc;
+        for part in parts {
+            let entry = target
+                .entry(part.to_string())
+                .or_insert_with(|| Value::Object(serde_json::Map::new()));
+            target = match entry {
+                Value::Object(obj) => obj,
+                _ => anyhow::bail!("Column '{}' conflicts with column '{}'", column, part),
+            };
+        }
+        target.insert(leaf.to_string(), value);
+    }
+
+    Ok(Value::Object(doc))
+}
+
+/// Type a CSV cell: `null`, booleans, numbers and JSON arrays/objects are
+/// parsed, anything else is kept as a string. Zero-padded digits such as zip
+/// codes stay strings.
+pub fn infer_cell_value(cell: &str) -> Value {
+    match cell {
+        "null" => return Value::Null,
+        "true" => return Value::Bool(true),
+        "false" => return Value::Bool(false),
+        _ => {}
+    }
+
+    let digits = cell.strip_prefix('-').unwrap_or(cell);
+    if digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.") {
+        return Value::String(cell.to_string());
+    }
+
+    if let Ok(n) = cell.parse::<i64>() {
+        return Value::from(n);
+    }
+    if let Ok(f) = cell.parse::<f64>() {
+        if let Some(n) = serde_json::Number::from_f64(f) {
+            return Value::Number(n);
+        }
+    }
+    if cell.starts_with('[') || cell.starts_with('{') {
+        if let Ok(value) = serde_json::from_str::<Value>(cell) {
+            return value;
+        }
+    }
+
+    Value::String(cell.to_string())
+}
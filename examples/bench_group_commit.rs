@@ -0,0 +1,66 @@
+//! Multi-writer commit throughput with group commit
+//!
+//! Each writer thread inserts small documents one commit at a time. Commits
+//! that arrive together share one WAL fsync, so the number of fsyncs (one
+//! per batch) should fall well below the number of commits as writers are
+//! added, while every commit still returns only once it is durable.
+//!
+//! Run with: cargo run --release --example bench_group_commit
+
+use jasonisnthappy::core::database::Database;
+use serde_json::json;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+const TOTAL_COMMITS: usize = 5000;
+
+fn run(writers: usize) {
+    let temp_dir = TempDir::new().unwrap();
+    let db = Arc::new(Database::open(temp_dir.path().join("bench.db").to_str().unwrap()).unwrap());
+    let per_writer = TOTAL_COMMITS / writers;
+
+    let start = Instant::now();
+    let handles: Vec<_> = (0..writers)
+        .map(|writer| {
+            let db = db.clone();
+            thread::spawn(move || {
+                let items = db.collection("items");
+                let mut latencies = Vec::with_capacity(per_writer);
+                for n in 0..per_writer {
+                    let commit_start = Instant::now();
+                    items.insert(json!({"writer": writer, "n": n})).unwrap();
+                    latencies.push(commit_start.elapsed());
+                }
+                latencies
+            })
+        })
+        .collect();
+
+    let mut latencies: Vec<Duration> = handles.into_iter().flat_map(|h| h.join().unwrap()).collect();
+    let elapsed = start.elapsed();
+    latencies.sort();
+
+    let commits = latencies.len();
+    let metrics = db.metrics();
+    assert_eq!(db.collection("items").count().unwrap(), commits);
+
+    println!("{:<10} {:>12} {:>10} {:>14} {:>12} {:>12}",
+             writers,
+             format!("{:.0}", commits as f64 / elapsed.as_secs_f64()),
+             metrics.batches_committed,
+             format!("{:.1}", metrics.avg_batch_size),
+             format!("{:.3}ms", latencies[commits / 2].as_secs_f64() * 1000.0),
+             format!("{:.3}ms", latencies[commits * 99 / 100].as_secs_f64() * 1000.0));
+}
+
+fn main() {
+    println!("\n{:<10} {:>12} {:>10} {:>14} {:>12} {:>12}",
+             "Writers", "Commits/s", "Fsyncs", "Commits/fsync", "p50", "p99");
+    println!("{}", "=".repeat(75));
+
+    for writers in [1, 10, 50] {
+        run(writers);
+    }
+}
@@ -77,7 +77,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         println!("✓ Web UI started at http://{}", addr);
         println!("\nEndpoints:");
         println!("  - Dashboard:     http://{}/", addr);
-        println!("  - Metrics JSON:  http://{}/metrics", addr);
+        println!("  - Metrics JSON:  http://{}/api/metrics", addr);
+        println!("  - Prometheus:    http://{}/metrics", addr);
         println!("  - Collections:   http://{}/api/collections", addr);
         println!("  - Health Check:  http://{}/health", addr);
         println!("\nPress Ctrl+C to stop...");
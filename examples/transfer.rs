@@ -0,0 +1,47 @@
+use jasonisnthappy::core::database::Database;
+use serde_json::json;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("=== Multi-Collection Transaction Example ===\n");
+
+    let _ = std::fs::remove_file("transfer_example.db");
+    let _ = std::fs::remove_file("transfer_example.db.lock");
+    let _ = std::fs::remove_file("transfer_example.db-wal");
+
+    let db = Database::open("transfer_example.db")?;
+    db.collection("accounts").insert(json!({"_id": "alice", "balance": 100}))?;
+    db.collection("accounts").insert(json!({"_id": "bob", "balance": 50}))?;
+    db.create_index("ledger", "by_from", "from", false)?;
+
+    println!("1. Transferring 30 from alice to bob...");
+    let mut tx = db.begin()?;
+    {
+        let mut accounts = tx.collection("accounts")?;
+        accounts.update_by_id("alice", json!({"$inc": {"balance": -30}}))?;
+        accounts.update_by_id("bob", json!({"$inc": {"balance": 30}}))?;
+    }
+    tx.collection("ledger")?.insert(json!({"from": "alice", "to": "bob", "amount": 30}))?;
+    tx.commit()?;
+
+    println!("   alice: {}", db.collection("accounts").find_by_id("alice")?["balance"]);
+    println!("   bob: {}", db.collection("accounts").find_by_id("bob")?["balance"]);
+    println!("   ledger entries: {}", db.collection("ledger").count()?);
+
+    println!("\n2. Abandoning a second transfer before commit...");
+    let mut tx = db.begin()?;
+    tx.collection("accounts")?.update_by_id("alice", json!({"$inc": {"balance": -500}}))?;
+    tx.collection("ledger")?.insert(json!({"from": "alice", "to": "bob", "amount": 500}))?;
+    tx.rollback()?;
+
+    println!("   alice: {}", db.collection("accounts").find_by_id("alice")?["balance"]);
+    println!("   ledger entries: {}", db.collection("ledger").count()?);
+
+    db.close()?;
+
+    let _ = std::fs::remove_file("transfer_example.db");
+    let _ = std::fs::remove_file("transfer_example.db.lock");
+    let _ = std::fs::remove_file("transfer_example.db-wal");
+
+    println!("\n=== Example Complete ===");
+    Ok(())
+}
@@ -1,8 +1,11 @@
 use crate::core::collection::Collection;
+use crate::core::datetime::{timestamp_millis, DateGranularity};
 use crate::core::errors::*;
-use crate::core::query::parser::parse_query;
+use crate::core::index_key::compare_values;
+use crate::core::slow_query::SlowQueryOperation;
 use serde_json::{json, Value};
 use std::collections::HashMap;
+use std::time::Instant;
 
 /// A stage in an aggregation pipeline
 #[derive(Debug, Clone)]
@@ -11,7 +14,10 @@ enum Stage {
     Match(String),
     /// Group documents by a field and apply aggregation functions
     GroupBy {
-        field: String,
+        /// Key fields; more than one makes a compound key
+        fields: Vec<String>,
+        /// Bucket the field as a timestamp instead of grouping on its raw value
+        granularity: Option<DateGranularity>,
         accumulators: Vec<Accumulator>,
     },
     /// Sort results by a field
@@ -22,6 +28,204 @@ enum Stage {
     Skip(usize),
     /// Select specific fields to include/exclude
     Project { fields: Vec<String>, exclude: bool },
+    /// Set fields to the result of an expression
+    AddFields(Vec<(String, FieldExpr)>),
+}
+
+impl Stage {
+    /// Short form for the slow query log, like `match(age > 30)` or `limit(10)`
+    fn describe(&self) -> String {
+        match self {
+            Stage::Match(query) => format!("match({})", query),
+            Stage::GroupBy { fields, .. } => format!("group_by({})", fields.join(", ")),
+            Stage::Sort { field, ascending } => {
+                format!("sort({} {})", field, if *ascending { "asc" } else { "desc" })
+            }
+            Stage::Limit(n) => format!("limit({})", n),
+            Stage::Skip(n) => format!("skip({})", n),
+            Stage::Project { fields, exclude } => {
+                format!("{}({})", if *exclude { "exclude" } else { "project" }, fields.join(", "))
+            }
+            Stage::AddFields(fields) => {
+                let names: Vec<&str> = fields.iter().map(|(name, _)| name.as_str()).collect();
+                format!("add_fields({})", names.join(", "))
+            }
+        }
+    }
+}
+
+/// An expression evaluated against each document by an `add_fields` stage
+///
+/// Field references accept dot notation for nested fields. Arithmetic yields
+/// `null` when an operand is missing or not a number, and `concat` yields
+/// `null` when a part is missing.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldExpr {
+    /// Value of a field in the document
+    Field(String),
+    /// A constant value
+    Literal(Value),
+    /// String concatenation of the parts (numbers and bools are stringified)
+    Concat(Vec<FieldExpr>),
+    /// Sum of the operands
+    Add(Vec<FieldExpr>),
+    /// First operand minus the rest
+    Sub(Vec<FieldExpr>),
+    /// Product of the operands
+    Mul(Vec<FieldExpr>),
+    /// First operand divided by the rest (`null` on division by zero)
+    Div(Vec<FieldExpr>),
+}
+
+impl FieldExpr {
+    pub fn field(name: &str) -> Self {
+        FieldExpr::Field(name.to_string())
+    }
+
+    pub fn literal(value: impl Into<Value>) -> Self {
+        FieldExpr::Literal(value.into())
+    }
+
+    /// Parse an expression from its JSON form
+    ///
+    /// A string is a field reference and any other scalar is a literal.
+    /// Operators are single-key objects: `{"field": "a.b"}`, `{"literal": v}`,
+    /// `{"concat": [...]}`, `{"add": [...]}`, `{"sub": [...]}`, `{"mul": [...]}`
+    /// and `{"div": [...]}`.
+    ///
+    /// # Example
+    /// ```
+    /// # use jasonisnthappy::FieldExpr;
+    /// # use serde_json::json;
+    /// let expr = FieldExpr::from_json(&json!({"mul": ["price", "qty"]})).unwrap();
+    /// assert_eq!(expr, FieldExpr::Mul(vec![FieldExpr::field("price"), FieldExpr::field("qty")]));
+    /// ```
+    pub fn from_json(value: &Value) -> Result<Self> {
+        match value {
+            Value::String(name) => Ok(FieldExpr::Field(name.clone())),
+            Value::Object(obj) => {
+                let mut entries = obj.iter();
+                let (op, arg) = match (entries.next(), entries.next()) {
+                    (Some(entry), None) => entry,
+                    _ => return Err(Error::Other(
+                        "expression object must have exactly one operator".to_string()
+                    )),
+                };
+
+                match op.as_str() {
+                    "field" => arg.as_str()
+                        .map(FieldExpr::field)
+                        .ok_or_else(|| Error::Other("'field' expects a field name".to_string())),
+                    "literal" => Ok(FieldExpr::Literal(arg.clone())),
+                    "concat" => Ok(FieldExpr::Concat(Self::parse_operands(op, arg)?)),
+                    "add" => Ok(FieldExpr::Add(Self::parse_operands(op, arg)?)),
+                    "sub" => Ok(FieldExpr::Sub(Self::parse_operands(op, arg)?)),
+                    "mul" => Ok(FieldExpr::Mul(Self::parse_operands(op, arg)?)),
+                    "div" => Ok(FieldExpr::Div(Self::parse_operands(op, arg)?)),
+                    other => Err(Error::Other(format!("unknown expression operator '{}'", other))),
+                }
+            }
+            Value::Array(_) => Err(Error::Other(
+                "arrays are only valid as operator operands".to_string()
+            )),
+            scalar => Ok(FieldExpr::Literal(scalar.clone())),
+        }
+    }
+
+    fn parse_operands(op: &str, arg: &Value) -> Result<Vec<FieldExpr>> {
+        let items = arg.as_array()
+            .ok_or_else(|| Error::Other(format!("'{}' expects an array of operands", op)))?;
+        if items.is_empty() {
+            return Err(Error::Other(format!("'{}' needs at least one operand", op)));
+        }
+        items.iter().map(Self::from_json).collect()
+    }
+
+    /// Evaluate the expression against a document
+    pub fn eval(&self, doc: &Value) -> Value {
+        match self {
+            FieldExpr::Field(path) => lookup_path(doc, path).cloned().unwrap_or(Value::Null),
+            FieldExpr::Literal(value) => value.clone(),
+            FieldExpr::Concat(parts) => {
+                let mut out = String::new();
+                for part in parts {
+                    match part.eval(doc) {
+                        Value::String(s) => out.push_str(&s),
+                        Value::Number(n) => out.push_str(&n.to_string()),
+                        Value::Bool(b) => out.push_str(&b.to_string()),
+                        _ => return Value::Null,
+                    }
+                }
+                Value::String(out)
+            }
+            FieldExpr::Add(operands) => eval_arithmetic(operands, doc, i64::checked_add, |a, b| a + b),
+            FieldExpr::Sub(operands) => eval_arithmetic(operands, doc, i64::checked_sub, |a, b| a - b),
+            FieldExpr::Mul(operands) => eval_arithmetic(operands, doc, i64::checked_mul, |a, b| a * b),
+            FieldExpr::Div(operands) => {
+                let mut values = Vec::with_capacity(operands.len());
+                for operand in operands {
+                    match operand.eval(doc).as_f64() {
+                        Some(v) => values.push(v),
+                        None => return Value::Null,
+                    }
+                }
+                let mut acc = values[0];
+                for divisor in &values[1..] {
+                    if *divisor == 0.0 {
+                        return Value::Null;
+                    }
+                    acc /= divisor;
+                }
+                json!(acc)
+            }
+        }
+    }
+}
+
+/// Hash key for a compound group. Numbers are normalized so `1` and `1.0`
+/// land in the same group, matching how queries compare numbers.
+fn compound_group_key(values: &[Value]) -> String {
+    let normalized: Vec<Value> = values
+        .iter()
+        .map(|v| match v {
+            Value::Number(n) => n.as_f64().map(|f| json!(f)).unwrap_or_else(|| v.clone()),
+            other => other.clone(),
+        })
+        .collect();
+    Value::Array(normalized).to_string()
+}
+
+fn lookup_path<'v>(doc: &'v Value, path: &str) -> Option<&'v Value> {
+    path.split('.').try_fold(doc, |current, part| current.get(part))
+}
+
+/// Fold operands left to right, staying in integers while every operand is an
+/// integer and nothing overflows
+fn eval_arithmetic(
+    operands: &[FieldExpr],
+    doc: &Value,
+    int_op: fn(i64, i64) -> Option<i64>,
+    float_op: fn(f64, f64) -> f64,
+) -> Value {
+    let mut values = Vec::with_capacity(operands.len());
+    for operand in operands {
+        match operand.eval(doc) {
+            Value::Number(n) => values.push(n),
+            _ => return Value::Null,
+        }
+    }
+
+    let ints: Option<Vec<i64>> = values.iter().map(|n| n.as_i64()).collect();
+    if let Some(ints) = ints {
+        let folded = ints[1..].iter().try_fold(ints[0], |acc, v| int_op(acc, *v));
+        if let Some(result) = folded {
+            return json!(result);
+        }
+    }
+
+    let floats: Vec<f64> = values.iter().filter_map(|n| n.as_f64()).collect();
+    let result = floats[1..].iter().fold(floats[0], |acc, v| float_op(acc, *v));
+    json!(result)
 }
 
 /// An accumulator function for group operations
@@ -33,6 +237,27 @@ struct Accumulator {
     op: AccumulatorOp,
 }
 
+/// How an `out` stage writes into its destination collection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutMode {
+    /// Delete everything in the collection before writing the results
+    #[default]
+    Replace,
+    /// Keep existing documents, updating those whose `_id` matches a result
+    Merge,
+}
+
+impl OutMode {
+    /// Parse `"replace"` or `"merge"`
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "replace" => Some(OutMode::Replace),
+            "merge" => Some(OutMode::Merge),
+            _ => None,
+        }
+    }
+}
+
 /// Types of accumulator operations
 #[derive(Debug, Clone)]
 enum AccumulatorOp {
@@ -47,6 +272,10 @@ enum AccumulatorOp {
 pub struct AggregationPipeline<'a> {
     collection: &'a Collection,
     stages: Vec<Stage>,
+    /// Destination set by an `out` stage in [`stages`](Self::stages)
+    out: Option<(String, OutMode)>,
+    /// Output field of the first accumulator added with no `group_by` before it
+    stray_accumulator: Option<String>,
 }
 
 impl<'a> AggregationPipeline<'a> {
@@ -54,11 +283,35 @@ impl<'a> AggregationPipeline<'a> {
         Self {
             collection,
             stages: Vec::new(),
+            out: None,
+            stray_accumulator: None,
         }
     }
 
     /// Add a match stage to filter documents
     ///
+    /// Stages run in the order they were added, and a match sees whatever the
+    /// stage before it produced. A leading match filters the collection's
+    /// documents while they are scanned; a match after `group_by` filters the
+    /// grouped results instead, so it can test `_id` and accumulator output
+    /// fields like SQL `HAVING`:
+    ///
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # let db = Database::open("my.db").unwrap();
+    /// # let orders = db.collection("orders");
+    /// let big_cities = orders.aggregate()
+    ///     .group_by("city")
+    ///     .sum("amount", "total")
+    ///     .match_("total > 1000")
+    ///     .execute()?;
+    /// # Ok::<(), jasonisnthappy::Error>(())
+    /// ```
+    ///
+    /// Accumulators attach to the most recent `group_by` wherever they appear,
+    /// so `.group_by("city").match_("total > 1000").sum("amount", "total")`
+    /// filters the same way.
+    ///
     /// # Example
     /// ```no_run
     /// # use jasonisnthappy::Database;
@@ -86,13 +339,20 @@ impl<'a> AggregationPipeline<'a> {
     /// ```
     pub fn group_by(mut self, field: &str) -> Self {
         self.stages.push(Stage::GroupBy {
-            field: field.to_string(),
+            fields: vec![field.to_string()],
+            granularity: None,
             accumulators: Vec::new(),
         });
         self
     }
 
-    /// Add a count accumulator to the last group by stage
+    /// Add a group by stage keyed on several fields at once
+    ///
+    /// Each result has an object `_id` mapping every key field to its value,
+    /// and the key fields are also copied to the top level of the result.
+    /// Missing key fields are treated as `null`, so documents lacking a field
+    /// group together with documents where it is explicitly `null`. Passing a
+    /// single field behaves like `group_by`.
     ///
     /// # Example
     /// ```no_run
@@ -100,20 +360,60 @@ impl<'a> AggregationPipeline<'a> {
     /// # let db = Database::open("my.db").unwrap();
     /// # let collection = db.collection("users");
     /// # let pipeline = collection.aggregate();
-    /// pipeline.group_by("city").count("total")
+    /// pipeline.group_by_multi(&["country", "city"]).count("users")
     /// # ;
     /// ```
-    pub fn count(mut self, output_field: &str) -> Self {
-        if let Some(Stage::GroupBy { accumulators, .. }) = self.stages.last_mut() {
-            accumulators.push(Accumulator {
-                output_field: output_field.to_string(),
-                op: AccumulatorOp::Count,
-            });
-        }
+    pub fn group_by_multi(mut self, fields: &[&str]) -> Self {
+        self.stages.push(Stage::GroupBy {
+            fields: fields.iter().map(|f| f.to_string()).collect(),
+            granularity: None,
+            accumulators: Vec::new(),
+        });
         self
     }
 
-    /// Add a sum accumulator to the last group by stage
+    /// Add a group by stage that buckets a timestamp field by day, month or year
+    ///
+    /// The field may hold an RFC3339 string, a `YYYY-MM-DD` date or Unix epoch
+    /// seconds. Buckets are computed in UTC, so `_id` is `"2024-01-15"`,
+    /// `"2024-01"` or `"2024"` regardless of the offset the timestamp was
+    /// written with. Documents without a parseable timestamp fall into the
+    /// `"null"` group. Accumulators attach the same way as for `group_by`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use jasonisnthappy::{Database, DateGranularity};
+    /// # let db = Database::open("my.db").unwrap();
+    /// # let collection = db.collection("events");
+    /// # let pipeline = collection.aggregate();
+    /// pipeline.group_by_date("createdAt", DateGranularity::Day).count("events")
+    /// # ;
+    /// ```
+    pub fn group_by_date(mut self, field: &str, granularity: DateGranularity) -> Self {
+        self.stages.push(Stage::GroupBy {
+            fields: vec![field.to_string()],
+            granularity: Some(granularity),
+            accumulators: Vec::new(),
+        });
+        self
+    }
+
+    /// Add a count accumulator to the most recent group by stage
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # let db = Database::open("my.db").unwrap();
+    /// # let collection = db.collection("users");
+    /// # let pipeline = collection.aggregate();
+    /// pipeline.group_by("city").count("total")
+    /// # ;
+    /// ```
+    pub fn count(self, output_field: &str) -> Self {
+        self.accumulate(output_field, AccumulatorOp::Count)
+    }
+
+    /// Add a sum accumulator to the most recent group by stage
     ///
     /// # Example
     /// ```no_run
@@ -124,17 +424,11 @@ impl<'a> AggregationPipeline<'a> {
     /// pipeline.group_by("city").sum("age", "total_age")
     /// # ;
     /// ```
-    pub fn sum(mut self, field: &str, output_field: &str) -> Self {
-        if let Some(Stage::GroupBy { accumulators, .. }) = self.stages.last_mut() {
-            accumulators.push(Accumulator {
-                output_field: output_field.to_string(),
-                op: AccumulatorOp::Sum(field.to_string()),
-            });
-        }
-        self
+    pub fn sum(self, field: &str, output_field: &str) -> Self {
+        self.accumulate(output_field, AccumulatorOp::Sum(field.to_string()))
     }
 
-    /// Add an average accumulator to the last group by stage
+    /// Add an average accumulator to the most recent group by stage
     ///
     /// # Example
     /// ```no_run
@@ -145,17 +439,11 @@ impl<'a> AggregationPipeline<'a> {
     /// pipeline.group_by("city").avg("age", "avg_age")
     /// # ;
     /// ```
-    pub fn avg(mut self, field: &str, output_field: &str) -> Self {
-        if let Some(Stage::GroupBy { accumulators, .. }) = self.stages.last_mut() {
-            accumulators.push(Accumulator {
-                output_field: output_field.to_string(),
-                op: AccumulatorOp::Avg(field.to_string()),
-            });
-        }
-        self
+    pub fn avg(self, field: &str, output_field: &str) -> Self {
+        self.accumulate(output_field, AccumulatorOp::Avg(field.to_string()))
     }
 
-    /// Add a min accumulator to the last group by stage
+    /// Add a min accumulator to the most recent group by stage
     ///
     /// # Example
     /// ```no_run
@@ -166,17 +454,11 @@ impl<'a> AggregationPipeline<'a> {
     /// pipeline.group_by("city").min("age", "min_age")
     /// # ;
     /// ```
-    pub fn min(mut self, field: &str, output_field: &str) -> Self {
-        if let Some(Stage::GroupBy { accumulators, .. }) = self.stages.last_mut() {
-            accumulators.push(Accumulator {
-                output_field: output_field.to_string(),
-                op: AccumulatorOp::Min(field.to_string()),
-            });
-        }
-        self
+    pub fn min(self, field: &str, output_field: &str) -> Self {
+        self.accumulate(output_field, AccumulatorOp::Min(field.to_string()))
     }
 
-    /// Add a max accumulator to the last group by stage
+    /// Add a max accumulator to the most recent group by stage
     ///
     /// # Example
     /// ```no_run
@@ -187,17 +469,31 @@ impl<'a> AggregationPipeline<'a> {
     /// pipeline.group_by("city").max("age", "max_age")
     /// # ;
     /// ```
-    pub fn max(mut self, field: &str, output_field: &str) -> Self {
-        if let Some(Stage::GroupBy { accumulators, .. }) = self.stages.last_mut() {
-            accumulators.push(Accumulator {
+    pub fn max(self, field: &str, output_field: &str) -> Self {
+        self.accumulate(output_field, AccumulatorOp::Max(field.to_string()))
+    }
+
+    /// Attach an accumulator to the most recent group by stage, remembering
+    /// it as stray when there is none so running the pipeline fails
+    fn accumulate(mut self, output_field: &str, op: AccumulatorOp) -> Self {
+        let group = self.stages.iter_mut().rev().find_map(|stage| match stage {
+            Stage::GroupBy { accumulators, .. } => Some(accumulators),
+            _ => None,
+        });
+        match group {
+            Some(accumulators) => accumulators.push(Accumulator {
                 output_field: output_field.to_string(),
-                op: AccumulatorOp::Max(field.to_string()),
-            });
+                op,
+            }),
+            None => {
+                self.stray_accumulator.get_or_insert_with(|| output_field.to_string());
+            }
         }
         self
     }
 
-    /// Add a sort stage
+    /// Add a sort stage. Documents missing the field come first, the rest
+    /// follow [`compare_values`].
     ///
     /// # Example
     /// ```no_run
@@ -286,29 +582,302 @@ impl<'a> AggregationPipeline<'a> {
         self
     }
 
+    /// Add an add_fields stage that sets each output field to the value of its expression
+    ///
+    /// Existing fields with the same name are overwritten, so combined with
+    /// `exclude` this also renames fields.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use jasonisnthappy::{Database, FieldExpr};
+    /// # let db = Database::open("my.db").unwrap();
+    /// # let collection = db.collection("orders");
+    /// # let pipeline = collection.aggregate();
+    /// pipeline.add_fields(&[
+    ///     ("total", FieldExpr::Mul(vec![FieldExpr::field("price"), FieldExpr::field("qty")])),
+    /// ])
+    /// # ;
+    /// ```
+    pub fn add_fields(mut self, fields: &[(&str, FieldExpr)]) -> Self {
+        self.stages.push(Stage::AddFields(
+            fields.iter().map(|(name, expr)| (name.to_string(), expr.clone())).collect(),
+        ));
+        self
+    }
+
+    /// Append stages described as JSON
+    ///
+    /// Stages are shaped like `{"match": "age > 30"}`, `{"group_by": "city"}`
+    /// (or `["country", "city"]`),
+    /// `{"group_by_date": {"field": "createdAt", "granularity": "day"}}`,
+    /// `{"count": "total"}`, `{"sum": {"field": "amount", "output": "total"}}`
+    /// (likewise `avg`, `min`, `max`), `{"sort": {"field": "total", "asc": false}}`,
+    /// `{"limit": 10}`, `{"skip": 5}`, `{"project": [...]}` and `{"exclude": [...]}`.
+    /// They run in order, so a `match` after a `group_by` filters the groups
+    /// as [`match_`](Self::match_) describes.
+    ///
+    /// A last stage of `{"out": "totals"}`, optionally with `"mode": "merge"`,
+    /// writes the results into that collection as [`out_with_mode`](Self::out_with_mode)
+    /// does, and `execute` then returns `[{"out": "totals", "written": n}]`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # use serde_json::json;
+    /// # let db = Database::open("my.db").unwrap();
+    /// # let collection = db.collection("users");
+    /// let stages = json!([{"group_by": "city"}, {"count": "users"}]);
+    /// let results = collection.aggregate()
+    ///     .stages(stages.as_array().unwrap())?
+    ///     .execute()?;
+    /// # Ok::<(), jasonisnthappy::Error>(())
+    /// ```
+    pub fn stages(mut self, stages: &[Value]) -> Result<Self> {
+        for (index, stage) in stages.iter().enumerate() {
+            let stage_obj = stage.as_object()
+                .ok_or_else(|| Error::Other(format!("Stage at index {} is not an object", index)))?;
+
+            if let Some(target) = stage_obj.get("out") {
+                if index + 1 != stages.len() {
+                    return Err(Error::Other(format!("'out' at index {} must be the last stage", index)));
+                }
+                let target = stage_str(target, index, "out")?;
+                let mode = match stage_obj.get("mode") {
+                    None => OutMode::Replace,
+                    Some(mode) => mode.as_str()
+                        .and_then(OutMode::parse)
+                        .ok_or_else(|| Error::Other(format!(
+                            "'mode' at index {} must be replace or merge", index
+                        )))?,
+                };
+                self.out = Some((target.to_string(), mode));
+                continue;
+            }
+
+            for (name, value) in stage_obj {
+                self = match name.as_str() {
+                    "match" => self.match_(stage_str(value, index, name)?),
+                    "group_by" => match value.as_array() {
+                        Some(items) => {
+                            let fields: Vec<&str> = items.iter()
+                                .map(|v| v.as_str())
+                                .collect::<Option<_>>()
+                                .filter(|f: &Vec<&str>| !f.is_empty())
+                                .ok_or_else(|| Error::Other(format!(
+                                    "'group_by' at index {} must be a field name or non-empty array of them", index
+                                )))?;
+                            self.group_by_multi(&fields)
+                        }
+                        None => self.group_by(stage_str(value, index, name)?),
+                    },
+                    "group_by_date" => {
+                        let field = stage_field(value, "field", index, name)?;
+                        let granularity = DateGranularity::parse(stage_field(value, "granularity", index, name)?)
+                            .ok_or_else(|| Error::Other(format!(
+                                "'granularity' at index {} must be day, month or year", index
+                            )))?;
+                        self.group_by_date(field, granularity)
+                    }
+                    "count" => self.count(stage_str(value, index, name)?),
+                    "sum" | "avg" | "min" | "max" => {
+                        let field = stage_field(value, "field", index, name)?;
+                        let output = stage_field(value, "output", index, name)?;
+                        match name.as_str() {
+                            "sum" => self.sum(field, output),
+                            "avg" => self.avg(field, output),
+                            "min" => self.min(field, output),
+                            _ => self.max(field, output),
+                        }
+                    }
+                    "sort" => {
+                        let field = stage_field(value, "field", index, name)?;
+                        let asc = value.get("asc").and_then(|v| v.as_bool()).unwrap_or(true);
+                        self.sort(field, asc)
+                    }
+                    "limit" | "skip" => {
+                        let n = value.as_u64()
+                            .ok_or_else(|| Error::Other(format!(
+                                "'{}' at index {} must be a non-negative integer", name, index
+                            )))? as usize;
+                        if name == "limit" { self.limit(n) } else { self.skip(n) }
+                    }
+                    "project" | "exclude" => {
+                        let fields: Vec<&str> = value.as_array()
+                            .ok_or_else(|| Error::Other(format!(
+                                "'{}' at index {} must be an array of field names", name, index
+                            )))?
+                            .iter()
+                            .filter_map(|v| v.as_str())
+                            .collect();
+                        if name == "project" { self.project(&fields) } else { self.exclude(&fields) }
+                    }
+                    other => return Err(Error::Other(format!("Unknown stage '{}' at index {}", other, index))),
+                };
+            }
+        }
+
+        Ok(self)
+    }
+
     /// Execute the aggregation pipeline and return results
     pub fn execute(self) -> Result<Vec<Value>> {
-        // Start with all documents in the collection
-        let mut documents = match self.collection.find_all() {
-            Ok(docs) => docs,
-            Err(Error::Other(msg)) if msg.contains("not found") => Vec::new(),
+        let start = Instant::now();
+        let (documents, examined) = match &self.out {
+            Some(_) => self.run()?,
+            None => {
+                let key = format!("aggregate:{:?}", self.stages);
+                self.collection.cached(Some(key), || self.run())?
+            }
+        };
+
+        let documents = match &self.out {
+            Some((target, mode)) => {
+                let written = self.write_out(target, *mode, documents)?;
+                vec![json!({"out": target, "written": written})]
+            }
+            None => documents,
+        };
+
+        self.record_slow_query(start, examined);
+        Ok(documents)
+    }
+
+    /// Execute the pipeline and replace the contents of `collection_name`
+    /// with the results, returning how many documents were written
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # let db = Database::open("my.db").unwrap();
+    /// let written = db.collection("orders").aggregate()
+    ///     .group_by("customer")
+    ///     .sum("amount", "total")
+    ///     .out("customer_totals")?;
+    /// # Ok::<(), jasonisnthappy::Error>(())
+    /// ```
+    pub fn out(self, collection_name: &str) -> Result<usize> {
+        self.out_with_mode(collection_name, OutMode::Replace)
+    }
+
+    /// Execute the pipeline and write the results into `collection_name`,
+    /// creating it if needed, returning how many documents were written
+    ///
+    /// All writes happen in one transaction. With [`OutMode::Replace`] the
+    /// collection ends up holding exactly the results; with [`OutMode::Merge`]
+    /// a result whose `_id` already exists has its fields set on that document
+    /// and the rest are inserted. Results without an `_id` get a new one, and
+    /// a non-string `_id` such as a compound group key is stored as its JSON text.
+    pub fn out_with_mode(mut self, collection_name: &str, mode: OutMode) -> Result<usize> {
+        let start = Instant::now();
+        self.out = Some((collection_name.to_string(), mode));
+        let (documents, examined) = self.run()?;
+        let written = self.write_out(collection_name, mode, documents)?;
+        self.record_slow_query(start, examined);
+        Ok(written)
+    }
+
+    /// Run every stage, returning the results and how many documents were examined
+    fn run(&self) -> Result<(Vec<Value>, usize)> {
+        if let Some(output_field) = &self.stray_accumulator {
+            return Err(Error::Other(format!(
+                "Accumulator '{}' has no group_by stage before it", output_field
+            )));
+        }
+
+        // A leading match stage filters documents while the collection is scanned
+        let (filter, stages) = match self.stages.split_first() {
+            Some((Stage::Match(query), rest)) => {
+                let ast = self.collection.parse_filter(query)?;
+                (Some(ast), rest)
+            }
+            _ => (None, self.stages.as_slice()),
+        };
+
+        let (mut documents, examined) = match self.collection.find_matching(filter.as_deref()) {
+            Ok(scan) => scan,
+            Err(Error::CollectionDoesNotExist { .. }) => (Vec::new(), 0),
             Err(e) => return Err(e),
         };
 
         // Execute each stage in sequence
-        for stage in &self.stages {
+        for stage in stages {
             documents = self.execute_stage(stage, documents)?;
         }
 
-        Ok(documents)
+        Ok((documents, examined))
+    }
+
+    fn record_slow_query(&self, start: Instant, examined: usize) {
+        self.collection.database().slow_query_log().record(
+            SlowQueryOperation::Aggregate,
+            self.collection.name(),
+            || {
+                let mut stages: Vec<String> = self.stages.iter().map(Stage::describe).collect();
+                if let Some((target, mode)) = &self.out {
+                    stages.push(match mode {
+                        OutMode::Replace => format!("out({})", target),
+                        OutMode::Merge => format!("out({} merge)", target),
+                    });
+                }
+                stages.join(" | ")
+            },
+            start,
+            examined,
+        );
+    }
+
+    /// Write `documents` into the collection `target` in one transaction
+    fn write_out(&self, target: &str, mode: OutMode, mut documents: Vec<Value>) -> Result<usize> {
+        let db = self.collection.database();
+        let target_coll = db.collection(target);
+
+        for doc in &mut documents {
+            if let Some(doc_map) = doc.as_object_mut() {
+                if let Some(id) = doc_map.get("_id").filter(|id| !id.is_string()) {
+                    let id = id.to_string();
+                    doc_map.insert("_id".to_string(), Value::String(id));
+                }
+            }
+            target_coll.validate_schema(doc)?;
+        }
+
+        db.run_transaction(|tx| {
+            let mut coll = tx.collection(target)?;
+
+            if mode == OutMode::Replace {
+                for existing in coll.find_all()? {
+                    if let Some(id) = existing.get("_id").and_then(|id| id.as_str()) {
+                        coll.delete_by_id(id)?;
+                    }
+                }
+            }
+
+            for doc in &documents {
+                let id = doc.get("_id").and_then(|id| id.as_str());
+                match id {
+                    Some(id) if mode == OutMode::Merge => match coll.update_by_id(id, doc.clone()) {
+                        Ok(()) => {}
+                        Err(Error::DocumentNotFound { .. }) => {
+                            coll.insert(doc.clone())?;
+                        }
+                        Err(e) => return Err(e),
+                    },
+                    _ => {
+                        coll.insert(doc.clone())?;
+                    }
+                }
+            }
+
+            Ok(documents.len())
+        })
     }
 
     /// Execute a single stage of the pipeline
     fn execute_stage(&self, stage: &Stage, documents: Vec<Value>) -> Result<Vec<Value>> {
         match stage {
             Stage::Match(query) => self.execute_match(&query, documents),
-            Stage::GroupBy { field, accumulators } => {
-                self.execute_group_by(&field, &accumulators, documents)
+            Stage::GroupBy { fields, granularity, accumulators } => {
+                self.execute_group_by(fields, *granularity, accumulators, documents)
             }
             Stage::Sort { field, ascending } => {
                 self.execute_sort(&field, *ascending, documents)
@@ -318,13 +887,13 @@ impl<'a> AggregationPipeline<'a> {
             Stage::Project { fields, exclude } => {
                 self.execute_project(&fields, *exclude, documents)
             }
+            Stage::AddFields(fields) => Ok(self.execute_add_fields(fields, documents)),
         }
     }
 
     /// Execute a match stage
     fn execute_match(&self, query: &str, documents: Vec<Value>) -> Result<Vec<Value>> {
-        let ast = parse_query(query)
-            .map_err(|e| Error::Other(format!("failed to parse query: {}", e)))?;
+        let ast = self.collection.parse_filter(query)?;
 
         Ok(documents
             .into_iter()
@@ -341,36 +910,64 @@ impl<'a> AggregationPipeline<'a> {
     /// Execute a group by stage
     fn execute_group_by(
         &self,
-        field: &str,
+        fields: &[String],
+        granularity: Option<DateGranularity>,
         accumulators: &[Accumulator],
         documents: Vec<Value>,
     ) -> Result<Vec<Value>> {
-        // Group documents by the field value
-        let mut groups: HashMap<String, Vec<Value>> = HashMap::new();
+        // Group documents by key, remembering the `_id` each key produces
+        let mut groups: HashMap<String, (Value, Vec<Value>)> = HashMap::new();
 
         for doc in documents {
-            let key = match doc.get(field) {
-                Some(Value::String(s)) => s.clone(),
-                Some(Value::Number(n)) => n.to_string(),
-                Some(Value::Bool(b)) => b.to_string(),
-                Some(Value::Null) => "null".to_string(),
-                None => "null".to_string(),
-                Some(other) => other.to_string(),
+            let (key, id) = match (fields, granularity) {
+                ([field], Some(granularity)) => {
+                    let key = lookup_path(&doc, field)
+                        .and_then(timestamp_millis)
+                        .map(|millis| granularity.bucket(millis))
+                        .unwrap_or_else(|| "null".to_string());
+                    (key.clone(), Value::String(key))
+                }
+                ([field], None) => {
+                    let key = match doc.get(field) {
+                        Some(Value::String(s)) => s.clone(),
+                        Some(Value::Number(n)) => n.to_string(),
+                        Some(Value::Bool(b)) => b.to_string(),
+                        Some(Value::Null) => "null".to_string(),
+                        None => "null".to_string(),
+                        Some(other) => other.to_string(),
+                    };
+                    (key.clone(), Value::String(key))
+                }
+                _ => {
+                    let values: Vec<Value> = fields
+                        .iter()
+                        .map(|field| lookup_path(&doc, field).cloned().unwrap_or(Value::Null))
+                        .collect();
+                    let key = compound_group_key(&values);
+                    let id = fields.iter().cloned().zip(values).collect::<serde_json::Map<_, _>>();
+                    (key, Value::Object(id))
+                }
             };
 
-            groups.entry(key).or_insert_with(Vec::new).push(doc);
+            groups.entry(key).or_insert_with(|| (id, Vec::new())).1.push(doc);
         }
 
         // Apply accumulators to each group
         let mut results = Vec::new();
-        for (key, group_docs) in groups {
+        for (_, (id, group_docs)) in groups {
+            // Compound keys are also spread into the result for easy access
+            let key_fields = if fields.len() > 1 { id.as_object().cloned() } else { None };
             let mut result = json!({
-                "_id": key,
+                "_id": id,
             });
 
             let result_obj = result.as_object_mut()
                 .ok_or_else(|| Error::Other("aggregation result must be an object".to_string()))?;
 
+            if let Some(key_fields) = key_fields {
+                result_obj.extend(key_fields);
+            }
+
             for accumulator in accumulators {
                 let value = match &accumulator.op {
                     AccumulatorOp::Count => {
@@ -439,16 +1036,10 @@ impl<'a> AggregationPipeline<'a> {
             let b_val = b.get(field);
 
             let cmp = match (a_val, b_val) {
-                (Some(Value::String(a)), Some(Value::String(b))) => a.cmp(b),
-                (Some(Value::Number(a)), Some(Value::Number(b))) => {
-                    let a_f64 = a.as_f64().unwrap_or(0.0);
-                    let b_f64 = b.as_f64().unwrap_or(0.0);
-                    a_f64.partial_cmp(&b_f64).unwrap_or(std::cmp::Ordering::Equal)
-                }
-                (Some(Value::Bool(a)), Some(Value::Bool(b))) => a.cmp(b),
+                (Some(a), Some(b)) => compare_values(a, b),
                 (Some(_), None) => std::cmp::Ordering::Greater,
                 (None, Some(_)) => std::cmp::Ordering::Less,
-                _ => std::cmp::Ordering::Equal,
+                (None, None) => std::cmp::Ordering::Equal,
             };
 
             if ascending {
@@ -503,11 +1094,40 @@ impl<'a> AggregationPipeline<'a> {
             })
             .collect())
     }
+
+    /// Execute an add_fields stage
+    fn execute_add_fields(&self, fields: &[(String, FieldExpr)], documents: Vec<Value>) -> Vec<Value> {
+        documents
+            .into_iter()
+            .map(|mut doc| {
+                // Evaluate everything against the input so fields can't see each other's output
+                let values: Vec<Value> = fields.iter().map(|(_, expr)| expr.eval(&doc)).collect();
+                if let Some(obj) = doc.as_object_mut() {
+                    for ((name, _), value) in fields.iter().zip(values) {
+                        obj.insert(name.clone(), value);
+                    }
+                }
+                doc
+            })
+            .collect()
+    }
+}
+
+fn stage_str<'v>(value: &'v Value, index: usize, name: &str) -> Result<&'v str> {
+    value.as_str()
+        .ok_or_else(|| Error::Other(format!("'{}' at index {} must be a string", name, index)))
+}
+
+fn stage_field<'v>(value: &'v Value, key: &str, index: usize, name: &str) -> Result<&'v str> {
+    value.get(key)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| Error::Other(format!("Missing '{}' in '{}' at index {}", key, name, index)))
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::Database;
+    use super::FieldExpr;
+    use crate::{Database, DateGranularity};
     use serde_json::json;
 
     #[test]
@@ -682,6 +1302,62 @@ mod tests {
         assert_eq!(la.get("total").unwrap(), 1);
     }
 
+    #[test]
+    fn test_match_after_group_filters_groups() {
+        let path = "/tmp/test_agg_match_after_group.db";
+        let _ = std::fs::remove_file(path);
+        let _ = std::fs::remove_file(format!("{}.lock", path));
+        let _ = std::fs::remove_file(format!("{}-wal", path));
+
+        let db = Database::open(path).unwrap();
+        let orders = db.collection("orders");
+
+        orders.insert(json!({"city": "NYC", "amount": 800})).unwrap();
+        orders.insert(json!({"city": "NYC", "amount": 700})).unwrap();
+        orders.insert(json!({"city": "LA", "amount": 900})).unwrap();
+        orders.insert(json!({"city": "SF", "amount": 1200})).unwrap();
+
+        // No single NYC order exceeds 1000 but the group does, and LA never does
+        let results = orders
+            .aggregate()
+            .group_by("city")
+            .sum("amount", "total")
+            .count("orders")
+            .match_("total > 1000")
+            .sort("_id", true)
+            .execute()
+            .unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["_id"], "NYC");
+        assert_eq!(results[0]["total"], 1500.0);
+        assert_eq!(results[1]["_id"], "SF");
+
+        // Group keys and several accumulator fields can be combined
+        let results = orders
+            .aggregate()
+            .group_by("city")
+            .sum("amount", "total")
+            .count("orders")
+            .match_("total > 1000 and orders > 1")
+            .execute()
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0]["_id"], "NYC");
+
+        // A match before the group still filters rows
+        let results = orders
+            .aggregate()
+            .match_("amount > 750")
+            .group_by("city")
+            .sum("amount", "total")
+            .match_("total > 1000")
+            .execute()
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0]["_id"], "SF");
+    }
+
     #[test]
     fn test_sort_stage() {
         let path = "/tmp/test_agg_sort.db";
@@ -832,4 +1508,183 @@ mod tests {
         assert_eq!(results[0].get("total_price").unwrap(), 1900.0);
         assert_eq!(results[0].get("num_products").unwrap(), 3);
     }
+
+    #[test]
+    fn test_add_fields_stage() {
+        let path = "/tmp/test_agg_add_fields.db";
+        let _ = std::fs::remove_file(path);
+        let _ = std::fs::remove_file(format!("{}.lock", path));
+        let _ = std::fs::remove_file(format!("{}-wal", path));
+
+        let db = Database::open(path).unwrap();
+        let people = db.collection("people");
+
+        people.insert(json!({"first": "Ada", "last": "Lovelace", "price": 3, "qty": 4, "rate": 0.5})).unwrap();
+        people.insert(json!({"first": "Alan", "price": 2.5, "qty": 2, "rate": 0})).unwrap();
+
+        let results = people
+            .aggregate()
+            .add_fields(&[
+                ("fullName", FieldExpr::Concat(vec![
+                    FieldExpr::field("first"),
+                    FieldExpr::literal(" "),
+                    FieldExpr::field("last"),
+                ])),
+                ("total", FieldExpr::from_json(&json!({"mul": ["price", "qty"]})).unwrap()),
+                ("perRate", FieldExpr::from_json(&json!({"div": ["qty", "rate"]})).unwrap()),
+                ("name", FieldExpr::field("first")),
+            ])
+            .exclude(&["first"])
+            .sort("total", false)
+            .execute()
+            .unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["fullName"], "Ada Lovelace");
+        assert_eq!(results[0]["total"], 12);
+        assert_eq!(results[0]["perRate"], 8.0);
+        assert_eq!(results[0]["name"], "Ada");
+        assert!(results[0].get("first").is_none());
+
+        // Missing parts and division by zero produce null
+        assert_eq!(results[1]["fullName"], serde_json::Value::Null);
+        assert_eq!(results[1]["total"], 5.0);
+        assert_eq!(results[1]["perRate"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_field_expr_from_json_rejects_bad_input() {
+        assert!(FieldExpr::from_json(&json!({"pow": ["a", 2]})).is_err());
+        assert!(FieldExpr::from_json(&json!({"add": []})).is_err());
+        assert!(FieldExpr::from_json(&json!({"add": ["a"], "mul": ["b"]})).is_err());
+        assert!(FieldExpr::from_json(&json!(["a", "b"])).is_err());
+        assert_eq!(FieldExpr::from_json(&json!(2)).unwrap(), FieldExpr::literal(2));
+        assert_eq!(
+            FieldExpr::from_json(&json!({"field": "address.city"})).unwrap().eval(&json!({"address": {"city": "Oslo"}})),
+            json!("Oslo")
+        );
+    }
+
+    #[test]
+    fn test_group_by_date_stage() {
+        let path = "/tmp/test_agg_group_by_date.db";
+        let _ = std::fs::remove_file(path);
+        let _ = std::fs::remove_file(format!("{}.lock", path));
+        let _ = std::fs::remove_file(format!("{}-wal", path));
+
+        let db = Database::open(path).unwrap();
+        let events = db.collection("events");
+
+        events.insert(json!({"createdAt": "2024-01-15T08:00:00Z", "amount": 10})).unwrap();
+        // 2024-01-15T22:30:00Z once the offset is applied
+        events.insert(json!({"createdAt": "2024-01-16T01:30:00+03:00", "amount": 5})).unwrap();
+        events.insert(json!({"createdAt": "2024-02-01", "amount": 1})).unwrap();
+        // 2024-02-01T00:00:00Z as epoch seconds
+        events.insert(json!({"createdAt": 1_706_745_600, "amount": 2})).unwrap();
+        events.insert(json!({"createdAt": "someday", "amount": 100})).unwrap();
+
+        let by_day = events
+            .aggregate()
+            .group_by_date("createdAt", DateGranularity::Day)
+            .count("events")
+            .sum("amount", "total")
+            .sort("_id", true)
+            .execute()
+            .unwrap();
+
+        assert_eq!(by_day.len(), 3);
+        assert_eq!(by_day[0]["_id"], "2024-01-15");
+        assert_eq!(by_day[0]["events"], 2);
+        assert_eq!(by_day[0]["total"], 15.0);
+        assert_eq!(by_day[1]["_id"], "2024-02-01");
+        assert_eq!(by_day[1]["events"], 2);
+        assert_eq!(by_day[2]["_id"], "null");
+
+        let by_year = events
+            .aggregate()
+            .match_("createdAt >= date('2024-01-01')")
+            .group_by_date("createdAt", DateGranularity::Year)
+            .count("events")
+            .execute()
+            .unwrap();
+
+        assert_eq!(by_year.len(), 1);
+        assert_eq!(by_year[0]["_id"], "2024");
+        assert_eq!(by_year[0]["events"], 4);
+    }
+
+    #[test]
+    fn test_group_by_multi_stage() {
+        let path = "/tmp/test_agg_group_by_multi.db";
+        let _ = std::fs::remove_file(path);
+        let _ = std::fs::remove_file(format!("{}.lock", path));
+        let _ = std::fs::remove_file(format!("{}-wal", path));
+
+        let db = Database::open(path).unwrap();
+        let users = db.collection("users");
+
+        users.insert(json!({"country": "NO", "city": "Oslo", "age": 30})).unwrap();
+        users.insert(json!({"country": "NO", "city": "Oslo", "age": 40})).unwrap();
+        users.insert(json!({"country": "NO", "city": "Bergen", "age": 20})).unwrap();
+        users.insert(json!({"country": "SE", "city": "Oslo", "age": 50})).unwrap();
+        // Missing and explicit null city share a group
+        users.insert(json!({"country": "SE", "age": 60})).unwrap();
+        users.insert(json!({"country": "SE", "city": null, "age": 70})).unwrap();
+
+        let results = users
+            .aggregate()
+            .group_by_multi(&["country", "city"])
+            .count("users")
+            .avg("age", "avg_age")
+            .sort("avg_age", true)
+            .execute()
+            .unwrap();
+
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0]["_id"], json!({"country": "NO", "city": "Bergen"}));
+        assert_eq!(results[1]["_id"], json!({"country": "NO", "city": "Oslo"}));
+        assert_eq!(results[1]["country"], "NO");
+        assert_eq!(results[1]["city"], "Oslo");
+        assert_eq!(results[1]["users"], 2);
+        assert_eq!(results[1]["avg_age"], 35.0);
+        assert_eq!(results[2]["_id"], json!({"country": "SE", "city": "Oslo"}));
+        assert_eq!(results[3]["_id"], json!({"country": "SE", "city": null}));
+        assert_eq!(results[3]["users"], 2);
+
+        // A single field keeps the plain group_by shape
+        let single = users.aggregate().group_by_multi(&["country"]).count("n").sort("_id", true).execute().unwrap();
+        assert_eq!(single[0]["_id"], "NO");
+        assert!(single[0].get("country").is_none());
+    }
+
+    #[test]
+    fn test_stages_from_json() {
+        let path = "/tmp/test_agg_stages_json.db";
+        let _ = std::fs::remove_file(path);
+        let _ = std::fs::remove_file(format!("{}.lock", path));
+        let _ = std::fs::remove_file(format!("{}-wal", path));
+
+        let db = Database::open(path).unwrap();
+        let users = db.collection("users");
+
+        users.insert(json!({"name": "Alice", "city": "NYC", "age": 30})).unwrap();
+        users.insert(json!({"name": "Bob", "city": "LA", "age": 20})).unwrap();
+        users.insert(json!({"name": "Charlie", "city": "NYC", "age": 40})).unwrap();
+
+        let stages = json!([
+            {"match": "age > 25"},
+            {"group_by": "city"},
+            {"count": "total"},
+            {"sort": {"field": "total", "asc": false}}
+        ]);
+        let results = users.aggregate().stages(stages.as_array().unwrap()).unwrap().execute().unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0]["_id"], "NYC");
+        assert_eq!(results[0]["total"], 2);
+
+        let err = users.aggregate().stages(&[json!({"unwind": "tags"})]).err().unwrap();
+        assert_eq!(err.to_string(), "Unknown stage 'unwind' at index 0");
+        let err = users.aggregate().stages(&[json!({"sum": {"field": "age"}})]).err().unwrap();
+        assert_eq!(err.to_string(), "Missing 'output' in 'sum' at index 0");
+    }
 }
@@ -0,0 +1,248 @@
+//! Async wrappers for use from tokio, behind the `tokio` feature.
+//!
+//! The storage engine is synchronous: every read and write does blocking file
+//! I/O and may wait on locks. Calling it straight from an async task stalls
+//! the executor thread it runs on, so [`AsyncDatabase`] and
+//! [`AsyncCollection`] run each call on tokio's blocking thread pool with
+//! [`tokio::task::spawn_blocking`] and hand back a future of the result.
+//! They're a convenience, not an async engine: each call still occupies a
+//! blocking thread while it runs.
+//!
+//! Both wrap the same `Arc<Database>` that synchronous code uses, so the two
+//! can be mixed freely, e.g. a background thread writing with the blocking
+//! API while request handlers read through the async one. Anything without an
+//! async method can be run with [`AsyncDatabase::run`] or
+//! [`AsyncCollection::run`].
+//!
+//! # Examples
+//!
+//! Sharing a database between axum handlers:
+//!
+//! ```ignore
+//! use axum::{extract::{Path, State}, http::StatusCode, routing::get, Json, Router};
+//! use jasonisnthappy::{AsyncDatabase, Error};
+//! use serde_json::Value;
+//!
+//! async fn get_user(
+//!     State(db): State<AsyncDatabase>,
+//!     Path(id): Path<String>,
+//! ) -> Result<Json<Value>, StatusCode> {
+//!     match db.collection("users").find_by_id(&id).await {
+//!         Ok(user) => Ok(Json(user)),
+//!         Err(Error::NotFound | Error::DocumentNotFound { .. }) => Err(StatusCode::NOT_FOUND),
+//!         Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+//!     }
+//! }
+//!
+//! async fn create_user(
+//!     State(db): State<AsyncDatabase>,
+//!     Json(user): Json<Value>,
+//! ) -> Result<String, StatusCode> {
+//!     db.collection("users").insert(user).await
+//!         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let db = AsyncDatabase::open("app.db").await.unwrap();
+//!     let app = Router::new()
+//!         .route("/users", axum::routing::post(create_user))
+//!         .route("/users/{id}", get(get_user))
+//!         .with_state(db);
+//!     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
+//!     axum::serve(listener, app).await.unwrap();
+//! }
+//! ```
+
+use crate::core::collection::{Collection, UpsertResult};
+use crate::core::database::{Database, DatabaseOptions};
+use crate::core::errors::*;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Run `f` on tokio's blocking thread pool. A panic in `f` is resumed in the
+/// caller, as if `f` had run in place.
+async fn blocking<T, F>(f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(result) => result,
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(e) => Err(Error::Other(format!("blocking task failed: {}", e))),
+    }
+}
+
+/// A [`Database`] whose methods return futures, for async code on tokio
+///
+/// Cloning is cheap and every clone refers to the same database. Methods
+/// must be awaited from within a tokio runtime.
+#[derive(Clone)]
+pub struct AsyncDatabase {
+    db: Arc<Database>,
+}
+
+impl AsyncDatabase {
+    /// Wrap a database that synchronous code may keep using
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db }
+    }
+
+    pub async fn open(path: &str) -> Result<Self> {
+        Self::open_with_options(path, DatabaseOptions::default()).await
+    }
+
+    pub async fn open_with_options(path: &str, opts: DatabaseOptions) -> Result<Self> {
+        let path = path.to_string();
+        let db = blocking(move || Database::open_with_options(&path, opts)).await?;
+        Ok(Self::new(Arc::new(db)))
+    }
+
+    /// The wrapped database, for calling the blocking API directly
+    pub fn database(&self) -> &Arc<Database> {
+        &self.db
+    }
+
+    pub fn collection(&self, name: &str) -> AsyncCollection {
+        AsyncCollection { db: self.db.clone(), name: name.to_string() }
+    }
+
+    /// Run `f` against the database on the blocking thread pool, for
+    /// transactions and anything else without an async method
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # async fn example(db: jasonisnthappy::AsyncDatabase) -> jasonisnthappy::Result<()> {
+    /// use serde_json::json;
+    ///
+    /// db.run(|db| {
+    ///     let mut tx = db.begin()?;
+    ///     let mut accounts = tx.collection("accounts")?;
+    ///     accounts.update_by_id("alice", json!({"$inc": {"balance": -10}}))?;
+    ///     accounts.update_by_id("bob", json!({"$inc": {"balance": 10}}))?;
+    ///     tx.commit()
+    /// }).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn run<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Database) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let db = self.db.clone();
+        blocking(move || f(&db)).await
+    }
+
+    pub async fn list_collections(&self) -> Result<Vec<String>> {
+        self.run(|db| db.list_collections()).await
+    }
+
+    pub async fn create_index(&self, collection_name: &str, index_name: &str, field: &str, unique: bool) -> Result<()> {
+        let (collection_name, index_name, field) =
+            (collection_name.to_string(), index_name.to_string(), field.to_string());
+        self.run(move |db| db.create_index(&collection_name, &index_name, &field, unique)).await
+    }
+
+    pub async fn checkpoint(&self) -> Result<()> {
+        self.run(|db| db.checkpoint()).await
+    }
+
+    pub async fn close(&self) -> Result<()> {
+        self.run(|db| db.close()).await
+    }
+}
+
+impl From<Arc<Database>> for AsyncDatabase {
+    fn from(db: Arc<Database>) -> Self {
+        Self::new(db)
+    }
+}
+
+/// A [`Collection`] whose methods return futures, from
+/// [`AsyncDatabase::collection`]
+#[derive(Clone)]
+pub struct AsyncCollection {
+    db: Arc<Database>,
+    name: String,
+}
+
+impl AsyncCollection {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Run `f` against the collection on the blocking thread pool, for
+    /// anything without an async method
+    pub async fn run<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Collection) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let (db, name) = (self.db.clone(), self.name.clone());
+        blocking(move || f(&db.collection(&name))).await
+    }
+
+    pub async fn insert(&self, doc: Value) -> Result<String> {
+        self.run(move |coll| coll.insert(doc)).await
+    }
+
+    pub async fn insert_many(&self, docs: Vec<Value>) -> Result<Vec<String>> {
+        self.run(move |coll| coll.insert_many(docs)).await
+    }
+
+    pub async fn find_by_id(&self, id: &str) -> Result<Value> {
+        let id = id.to_string();
+        self.run(move |coll| coll.find_by_id(&id)).await
+    }
+
+    pub async fn find_all(&self) -> Result<Vec<Value>> {
+        self.run(|coll| coll.find_all()).await
+    }
+
+    pub async fn find(&self, query: &str) -> Result<Vec<Value>> {
+        let query = query.to_string();
+        self.run(move |coll| coll.find(query.as_str())).await
+    }
+
+    pub async fn find_one(&self, query: &str) -> Result<Option<Value>> {
+        let query = query.to_string();
+        self.run(move |coll| coll.find_one(query.as_str())).await
+    }
+
+    pub async fn count(&self) -> Result<usize> {
+        self.run(|coll| coll.count()).await
+    }
+
+    pub async fn update(&self, query: &str, updates: Value) -> Result<usize> {
+        let query = query.to_string();
+        self.run(move |coll| coll.update(&query, updates)).await
+    }
+
+    pub async fn update_by_id(&self, id: &str, updates: Value) -> Result<()> {
+        let id = id.to_string();
+        self.run(move |coll| coll.update_by_id(&id, updates)).await
+    }
+
+    pub async fn upsert(&self, query: &str, doc: Value) -> Result<UpsertResult> {
+        let query = query.to_string();
+        self.run(move |coll| coll.upsert(&query, doc)).await
+    }
+
+    pub async fn delete(&self, query: &str) -> Result<usize> {
+        let query = query.to_string();
+        self.run(move |coll| coll.delete(&query)).await
+    }
+
+    pub async fn delete_by_id(&self, id: &str) -> Result<()> {
+        let id = id.to_string();
+        self.run(move |coll| coll.delete_by_id(&id)).await
+    }
+
+    /// Run an aggregation pipeline given as JSON stages, in the format of
+    /// [`AggregationPipeline::stages`](crate::AggregationPipeline::stages)
+    pub async fn aggregate(&self, stages: Vec<Value>) -> Result<Vec<Value>> {
+        self.run(move |coll| coll.aggregate().stages(&stages)?.execute()).await
+    }
+}
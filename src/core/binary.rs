@@ -0,0 +1,273 @@
+use crate::core::errors::*;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+/// Key of the object that marks a binary value: `{"$binary": "<base64>"}`
+pub const BINARY_KEY: &str = "$binary";
+
+/// First byte of stored documents that carry binary values. Plain documents
+/// are JSON text, which never starts with a zero byte.
+const BINARY_DOC_MARKER: u8 = 0;
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Wrap `bytes` as a binary value, ready to put in a document
+pub fn binary_value(bytes: &[u8]) -> Value {
+    let mut obj = Map::new();
+    obj.insert(BINARY_KEY.to_string(), Value::String(encode_base64(bytes)));
+    Value::Object(obj)
+}
+
+/// The bytes of a binary value, or `None` if `value` isn't one
+pub fn binary_bytes(value: &Value) -> Option<Vec<u8>> {
+    binary_text(value).and_then(|text| decode_base64(text).ok())
+}
+
+/// Whether `value` has the shape of a binary value
+pub fn is_binary(value: &Value) -> bool {
+    binary_text(value).is_some()
+}
+
+fn binary_text(value: &Value) -> Option<&str> {
+    match value {
+        Value::Object(obj) if obj.len() == 1 => obj.get(BINARY_KEY)?.as_str(),
+        _ => None,
+    }
+}
+
+/// Serialize a document for storage. Documents without binary values are
+/// stored as JSON text, as they always were. Otherwise the bytes of each
+/// binary value follow a marker byte, each as a 4-byte length and the raw
+/// bytes, and the JSON after them refers to them by position:
+/// `{"$binary": 0}`.
+pub(crate) fn encode_document(doc: &Map<String, Value>, collection: &str) -> Result<Vec<u8>> {
+    let mut blobs = Vec::new();
+    let mut stored = Map::new();
+    for (key, value) in doc {
+        stored.insert(key.clone(), extract_blobs(value, collection, &mut blobs)?);
+    }
+
+    if blobs.is_empty() {
+        return Ok(serde_json::to_vec(doc)?);
+    }
+
+    let json = serde_json::to_vec(&stored)?;
+    let blob_bytes: usize = blobs.iter().map(|blob| 4 + blob.len()).sum();
+    let mut data = Vec::with_capacity(1 + 4 + blob_bytes + json.len());
+    data.push(BINARY_DOC_MARKER);
+    data.extend_from_slice(&(blobs.len() as u32).to_le_bytes());
+    for blob in &blobs {
+        data.extend_from_slice(&(blob.len() as u32).to_le_bytes());
+        data.extend_from_slice(blob);
+    }
+    data.extend_from_slice(&json);
+    Ok(data)
+}
+
+/// Replace binary values in `value` by their position in `blobs`, rejecting
+/// objects that use `$binary` without being a valid binary value
+fn extract_blobs(value: &Value, collection: &str, blobs: &mut Vec<Vec<u8>>) -> Result<Value> {
+    match value {
+        Value::Object(obj) if obj.contains_key(BINARY_KEY) => {
+            let invalid = |reason: &str| Error::InvalidDocumentFormat {
+                reason: reason.to_string(),
+                collection: Some(collection.to_string()),
+            };
+            let text = binary_text(value)
+                .ok_or_else(|| invalid("'$binary' must be the only key and hold a base64 string"))?;
+            let bytes = decode_base64(text).map_err(|e| invalid(&format!("'$binary' is not valid base64: {}", e)))?;
+            blobs.push(bytes);
+            let mut obj = Map::new();
+            obj.insert(BINARY_KEY.to_string(), Value::from(blobs.len() - 1));
+            Ok(Value::Object(obj))
+        }
+        Value::Object(obj) => {
+            let mut out = Map::new();
+            for (key, value) in obj {
+                out.insert(key.clone(), extract_blobs(value, collection, blobs)?);
+            }
+            Ok(Value::Object(out))
+        }
+        Value::Array(items) => items.iter()
+            .map(|item| extract_blobs(item, collection, blobs))
+            .collect::<Result<Vec<_>>>()
+            .map(Value::Array),
+        other => Ok(other.clone()),
+    }
+}
+
+/// Parse a stored document written by [`encode_document`]
+pub(crate) fn decode_document<T: DeserializeOwned>(data: &[u8]) -> Result<T> {
+    if data.first() != Some(&BINARY_DOC_MARKER) {
+        return Ok(serde_json::from_slice(data)?);
+    }
+
+    let (blobs, mut doc) = split_blobs(data)?;
+    restore_blobs(&mut doc, &blobs)?;
+    Ok(serde_json::from_value(doc)?)
+}
+
+/// The bytes of the binary value in top-level `field` of a stored document.
+/// Bytes stored raw are copied straight out of the page data without a trip
+/// through base64.
+pub(crate) fn binary_field(data: &[u8], field: &str) -> Result<Option<Vec<u8>>> {
+    if data.first() != Some(&BINARY_DOC_MARKER) {
+        let doc: Value = serde_json::from_slice(data)?;
+        return Ok(doc.get(field).and_then(binary_bytes));
+    }
+
+    let (blobs, doc) = split_blobs(data)?;
+    match doc.get(field).and_then(|value| value.get(BINARY_KEY)) {
+        Some(index) => Ok(Some(blob_at(&blobs, index)?.to_vec())),
+        None => Ok(None),
+    }
+}
+
+fn split_blobs(data: &[u8]) -> Result<(Vec<&[u8]>, Value)> {
+    let truncated = || Error::DataCorruption {
+        details: "binary document is truncated".to_string(),
+    };
+    let read_u32 = |offset: usize| -> Result<u32> {
+        let bytes = data.get(offset..offset + 4).ok_or_else(truncated)?;
+        Ok(u32::from_le_bytes(bytes.try_into()?))
+    };
+
+    let count = read_u32(1)? as usize;
+    let mut offset = 5;
+    let mut blobs = Vec::with_capacity(count.min(data.len()));
+    for _ in 0..count {
+        let len = read_u32(offset)? as usize;
+        offset += 4;
+        blobs.push(data.get(offset..offset + len).ok_or_else(truncated)?);
+        offset += len;
+    }
+
+    Ok((blobs, serde_json::from_slice(&data[offset..])?))
+}
+
+fn blob_at<'a>(blobs: &[&'a [u8]], index: &Value) -> Result<&'a [u8]> {
+    index.as_u64()
+        .and_then(|index| blobs.get(index as usize).copied())
+        .ok_or_else(|| Error::DataCorruption {
+            details: format!("binary document refers to missing value {}", index),
+        })
+}
+
+fn restore_blobs(value: &mut Value, blobs: &[&[u8]]) -> Result<()> {
+    match value {
+        Value::Object(obj) => {
+            if let Some(index) = obj.get_mut(BINARY_KEY) {
+                *index = Value::String(encode_base64(blob_at(blobs, index)?));
+                return Ok(());
+            }
+            for child in obj.values_mut() {
+                restore_blobs(child, blobs)?;
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                restore_blobs(item, blobs)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Standard base64 with padding
+pub(crate) fn encode_base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (chunk.get(1).copied().unwrap_or(0) as u32) << 8
+            | chunk.get(2).copied().unwrap_or(0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Decode standard base64; padding is optional
+pub(crate) fn decode_base64(text: &str) -> std::result::Result<Vec<u8>, String> {
+    let text = text.trim_end_matches('=');
+    if text.len() % 4 == 1 {
+        return Err("wrong length".to_string());
+    }
+
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let mut n = 0u32;
+    let mut bits = 0;
+    for ch in text.bytes() {
+        let digit = match ch {
+            b'A'..=b'Z' => ch - b'A',
+            b'a'..=b'z' => ch - b'a' + 26,
+            b'0'..=b'9' => ch - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return Err(format!("unexpected character '{}'", ch as char)),
+        };
+        n = n << 6 | digit as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((n >> bits) as u8);
+            n &= (1 << bits) - 1;
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_base64_round_trip() {
+        for len in 0..20 {
+            let bytes: Vec<u8> = (0..len).map(|i| (i * 37 + 200) as u8).collect();
+            let text = encode_base64(&bytes);
+            assert_eq!(text.len() % 4, 0);
+            assert_eq!(decode_base64(&text).unwrap(), bytes);
+        }
+        assert_eq!(encode_base64(b"hello"), "aGVsbG8=");
+        assert_eq!(decode_base64("aGVsbG8").unwrap(), b"hello");
+        assert!(decode_base64("aGVsb*8=").is_err());
+        assert!(decode_base64("aGVsb").is_err());
+    }
+
+    #[test]
+    fn test_binary_documents_store_raw_bytes() {
+        let thumb: Vec<u8> = (0..=255).collect();
+        let doc = json!({"_id": "a", "thumb": binary_value(&thumb), "parts": [binary_value(b"xy")]});
+        let data = encode_document(doc.as_object().unwrap(), "files").unwrap();
+
+        assert_eq!(data[0], BINARY_DOC_MARKER);
+        assert!(data.windows(thumb.len()).any(|window| window == thumb.as_slice()));
+        assert_eq!(decode_document::<Value>(&data).unwrap(), doc);
+        assert_eq!(binary_field(&data, "thumb").unwrap(), Some(thumb));
+        assert_eq!(binary_field(&data, "_id").unwrap(), None);
+
+        // Documents without binary values stay plain JSON
+        let plain = json!({"_id": "b", "n": 1});
+        let data = encode_document(plain.as_object().unwrap(), "files").unwrap();
+        assert_eq!(data, serde_json::to_vec(&plain).unwrap());
+    }
+
+    #[test]
+    fn test_malformed_binary_values_are_rejected() {
+        for doc in [
+            json!({"b": {"$binary": 1}}),
+            json!({"b": {"$binary": "AA==", "extra": true}}),
+            json!({"b": [{"$binary": "not base64!"}]}),
+        ] {
+            let err = encode_document(doc.as_object().unwrap(), "files").unwrap_err();
+            assert!(matches!(err, Error::InvalidDocumentFormat { .. }), "{:?}", err);
+        }
+    }
+}
@@ -200,19 +200,45 @@ impl BTree {
         Ok(())
     }
 
+    /// Every entry whose key starts with `prefix`, in key order
+    pub fn scan_prefix(&self, prefix: &str) -> Result<Vec<(String, u64)>> {
+        let mut iter = self.iterator_from(Some(prefix))?;
+
+        let mut matches = Vec::new();
+        while iter.next() {
+            let (key, value) = iter.entry();
+            if key.starts_with(prefix) {
+                matches.push((key.to_string(), value));
+            } else if key > prefix {
+                break;
+            }
+        }
+        Ok(matches)
+    }
+
     pub fn iterator(&self) -> Result<BTreeIterator<'_>> {
-        let root = self.get_root_page();
-        let mut node = self.read_node(root)?;
+        self.iterator_from(None)
+    }
+
+    /// Iterator starting at the leaf that would hold `key`, or at the first
+    /// leaf without one
+    pub(crate) fn iterator_from(&self, key: Option<&str>) -> Result<BTreeIterator<'_>> {
+        let mut path = Vec::new();
+        let mut node = self.read_node(self.get_root_page())?;
 
         while node.node_type == NodeType::InternalNode {
             if node.children.is_empty() {
                 return Err(Error::Other("internal node has no children".to_string()));
             }
-            node = self.read_node(node.children[0])?;
+            let idx = key.map_or(0, |key| child_index(&node, key).min(node.children.len() - 1));
+            let child = self.read_node(node.children[idx])?;
+            path.push((node, idx));
+            node = child;
         }
 
         Ok(BTreeIterator {
             bt: self,
+            path,
             current_leaf: Some(node),
             index: 0,
             started: false,
@@ -282,13 +308,7 @@ impl BTree {
             });
         }
 
-        let mut idx = 0;
-        for (i, node_key) in node.keys.iter().enumerate() {
-            if key < node_key {
-                break;
-            }
-            idx = i + 1;
-        }
+        let idx = child_index(node, key);
 
         if idx >= node.children.len() {
             return Err(Error::Corruption {
@@ -563,7 +583,7 @@ impl BTree {
             }
         }
 
-        let mut data = get_node_serialize_buffer();
+        let mut data = get_node_serialize_buffer(self.pager.page_size());
         serialize_node_into(node, &mut data, page_num);
         self.pager.write_page_transfer(page_num, data)?;
 
@@ -806,8 +826,16 @@ pub(crate) fn deserialize_node(page_num: u64, data: &[u8]) -> Result<BTreeNode>
     Ok(node)
 }
 
+/// Index of the child of internal `node` whose subtree holds `key`
+fn child_index(node: &BTreeNode, key: &str) -> usize {
+    node.keys.iter().take_while(|node_key| key >= node_key.as_str()).count()
+}
+
 pub struct BTreeIterator<'a> {
     bt: &'a BTree,
+    /// Internal nodes above the current leaf, each with the index of the
+    /// child being walked
+    path: Vec<(BTreeNode, usize)>,
     current_leaf: Option<BTreeNode>,
     index: usize,
     started: bool,
@@ -819,30 +847,49 @@ impl<'a> BTreeIterator<'a> {
             return false;
         }
 
-        if !self.started {
+        if self.started {
+            self.index += 1;
+        } else {
             self.started = true;
-            let entries_len = self.current_leaf.as_ref().unwrap().entries.len();
-            return entries_len > 0;
         }
 
-        self.index += 1;
-
-        let current = self.current_leaf.as_ref().unwrap();
-        if self.index >= current.entries.len() {
-            if current.next_leaf == 0 {
-                return false;
+        // Deletes can leave leaves empty, so keep going until one has entries
+        loop {
+            let current = self.current_leaf.as_ref().unwrap();
+            if self.index < current.entries.len() {
+                return true;
             }
 
-            match self.bt.read_node(current.next_leaf) {
-                Ok(next_leaf) => {
+            match self.next_leaf() {
+                Ok(Some(next_leaf)) => {
                     self.current_leaf = Some(next_leaf);
                     self.index = 0;
                 }
-                Err(_) => return false,
+                _ => return false,
             }
         }
+    }
 
-        self.index < self.current_leaf.as_ref().unwrap().entries.len()
+    /// The leaf after the current one, found through the internal nodes above
+    /// it. The stored next_leaf pointer isn't followed: copy-on-write can leave
+    /// it naming a page that is no longer part of the tree.
+    fn next_leaf(&mut self) -> Result<Option<BTreeNode>> {
+        while let Some((node, idx)) = self.path.last_mut() {
+            if *idx + 1 < node.children.len() {
+                *idx += 1;
+                let mut child = self.bt.read_node(node.children[*idx])?;
+                while child.node_type == NodeType::InternalNode {
+                    let first = *child.children.first()
+                        .ok_or_else(|| Error::Other("internal node has no children".to_string()))?;
+                    let grandchild = self.bt.read_node(first)?;
+                    self.path.push((child, 0));
+                    child = grandchild;
+                }
+                return Ok(Some(child));
+            }
+            self.path.pop();
+        }
+        Ok(None)
     }
 
     pub fn entry(&self) -> (&str, u64) {
@@ -1,5 +1,5 @@
 
-use crate::core::constants::PAGE_SIZE;
+use crate::core::constants::{MAX_PAGE_SIZE, MIN_PAGE_SIZE, PAGE_SIZE};
 use std::sync::Mutex;
 
 pub struct BufferPool {
@@ -16,15 +16,21 @@ impl BufferPool {
     }
 
     pub fn get(&self) -> Vec<u8> {
+        self.get_sized(PAGE_SIZE)
+    }
+
+    /// Get a zeroed buffer of `len` bytes, reusing a pooled one of that size
+    pub fn get_sized(&self, len: usize) -> Vec<u8> {
         if let Ok(mut buffers) = self.buffers.lock() {
-            buffers.pop().unwrap_or_else(|| vec![0u8; PAGE_SIZE])
-        } else {
-            vec![0u8; PAGE_SIZE]
+            if let Some(pos) = buffers.iter().rposition(|buf| buf.len() == len) {
+                return buffers.swap_remove(pos);
+            }
         }
+        vec![0u8; len]
     }
 
     pub fn put(&self, mut buf: Vec<u8>) {
-        if buf.len() != PAGE_SIZE {
+        if !(MIN_PAGE_SIZE..=MAX_PAGE_SIZE).contains(&buf.len()) {
             return;
         }
 
@@ -55,8 +61,8 @@ impl BufferPool {
 static NODE_SERIALIZE_POOL: once_cell::sync::Lazy<BufferPool> =
     once_cell::sync::Lazy::new(|| BufferPool::new(128));
 
-pub fn get_node_serialize_buffer() -> Vec<u8> {
-    NODE_SERIALIZE_POOL.get()
+pub fn get_node_serialize_buffer(page_size: usize) -> Vec<u8> {
+    NODE_SERIALIZE_POOL.get_sized(page_size)
 }
 
 pub fn put_node_serialize_buffer(buf: Vec<u8>) {
@@ -66,8 +72,8 @@ pub fn put_node_serialize_buffer(buf: Vec<u8>) {
 static PAGE_BUFFER_POOL: once_cell::sync::Lazy<BufferPool> =
     once_cell::sync::Lazy::new(|| BufferPool::new(256));
 
-pub fn get_page_buffer() -> Vec<u8> {
-    PAGE_BUFFER_POOL.get()
+pub fn get_page_buffer(page_size: usize) -> Vec<u8> {
+    PAGE_BUFFER_POOL.get_sized(page_size)
 }
 
 pub fn put_page_buffer(buf: Vec<u8>) {
@@ -113,12 +119,29 @@ mod tests {
 
     #[test]
     fn test_global_pools() {
-        let buf1 = get_node_serialize_buffer();
+        let buf1 = get_node_serialize_buffer(PAGE_SIZE);
         assert_eq!(buf1.len(), PAGE_SIZE);
         put_node_serialize_buffer(buf1);
 
-        let buf2 = get_page_buffer();
+        let buf2 = get_page_buffer(PAGE_SIZE);
         assert_eq!(buf2.len(), PAGE_SIZE);
         put_page_buffer(buf2);
     }
+
+    #[test]
+    fn test_buffer_pool_mixed_sizes() {
+        let pool = BufferPool::new(10);
+
+        pool.put(vec![1u8; PAGE_SIZE]);
+        pool.put(vec![2u8; 16384]);
+        pool.put(vec![3u8; 100]);
+        assert_eq!(pool.len(), 2, "buffers of unsupported sizes are not pooled");
+
+        let large = pool.get_sized(16384);
+        assert_eq!(large.len(), 16384);
+        assert!(large.iter().all(|&b| b == 0));
+        assert_eq!(pool.get_sized(32768).len(), 32768);
+        assert_eq!(pool.get().len(), PAGE_SIZE);
+        assert!(pool.is_empty());
+    }
 }
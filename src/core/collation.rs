@@ -0,0 +1,148 @@
+//! String collations for queries, sorts and indexes.
+//!
+//! A collation decides which strings compare as equal and how they order.
+//! It folds both sides of a comparison to a canonical form and compares
+//! those forms by code point, like [`Collation::Binary`] compares the
+//! originals:
+//!
+//! - [`Collation::Binary`], the default, compares strings as stored, so
+//!   `"Alice"` and `"alice"` differ and every uppercase ASCII letter sorts
+//!   before every lowercase one.
+//! - [`Collation::AsciiCaseInsensitive`] folds `A`-`Z` to `a`-`z` and leaves
+//!   every other character alone.
+//! - [`Collation::CaseInsensitive`] applies Unicode simple case folding, one
+//!   character to one character, so `"ÉCOLE"` equals `"école"`. Foldings that
+//!   change the length of a string, such as `ß` to `ss`, are not applied.
+//!
+//! Only strings are folded, including strings inside arrays and objects;
+//! other values, binary values among them, compare the same under every
+//! collation.
+//!
+//! A query picks its collation with
+//! [`QueryBuilder::collation`](crate::QueryBuilder::collation) and an index
+//! with [`IndexOptions::collation`](crate::IndexOptions::collation). An index
+//! stores the folded form of the strings it holds, so:
+//!
+//! - A unique index with a case-insensitive collation rejects values that
+//!   differ only in case.
+//! - Only queries with the same collation read their sort order from the
+//!   index. Other queries sort in memory.
+//! - An index with any collation other than binary never covers a query,
+//!   since its keys no longer hold the original strings.
+
+use crate::core::binary::is_binary;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+/// How strings compare, see the [module docs](self)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Collation {
+    /// Strings compare by code point, exactly as stored
+    #[default]
+    Binary,
+    /// ASCII letters compare without regard to case
+    AsciiCaseInsensitive,
+    /// Letters compare without regard to case, using Unicode simple case folding
+    CaseInsensitive,
+}
+
+impl Collation {
+    pub fn is_binary(&self) -> bool {
+        *self == Collation::Binary
+    }
+
+    /// `s` in the form strings are compared in
+    pub fn fold<'a>(&self, s: &'a str) -> Cow<'a, str> {
+        match self {
+            Collation::Binary => Cow::Borrowed(s),
+            Collation::AsciiCaseInsensitive if s.bytes().any(|b| b.is_ascii_uppercase()) => {
+                Cow::Owned(s.to_ascii_lowercase())
+            }
+            Collation::AsciiCaseInsensitive => Cow::Borrowed(s),
+            Collation::CaseInsensitive if s.chars().any(|c| simple_fold(c) != c) => {
+                Cow::Owned(s.chars().map(simple_fold).collect())
+            }
+            Collation::CaseInsensitive => Cow::Borrowed(s),
+        }
+    }
+
+    /// `value` with every string in it folded
+    pub fn fold_value<'a>(&self, value: &'a Value) -> Cow<'a, Value> {
+        if self.is_binary() || !self.changes(value) {
+            return Cow::Borrowed(value);
+        }
+        Cow::Owned(match value {
+            Value::String(s) => Value::String(self.fold(s).into_owned()),
+            Value::Array(items) => Value::Array(items.iter().map(|item| self.fold_value(item).into_owned()).collect()),
+            Value::Object(obj) => Value::Object(
+                obj.iter().map(|(key, item)| (key.clone(), self.fold_value(item).into_owned())).collect(),
+            ),
+            other => other.clone(),
+        })
+    }
+
+    /// Order of `a` and `b` after folding, otherwise as
+    /// [`compare_values`](crate::core::index_key::compare_values)
+    pub fn compare(&self, a: &Value, b: &Value) -> Ordering {
+        crate::core::index_key::compare_values(&self.fold_value(a), &self.fold_value(b))
+    }
+
+    /// Whether folding leaves `value` different
+    fn changes(&self, value: &Value) -> bool {
+        match value {
+            Value::String(s) => matches!(self.fold(s), Cow::Owned(_)),
+            Value::Array(items) => items.iter().any(|item| self.changes(item)),
+            Value::Object(obj) => !is_binary(value) && obj.values().any(|item| self.changes(item)),
+            _ => false,
+        }
+    }
+}
+
+/// The character `c` folds to when its lowercase form is a single character
+fn simple_fold(c: char) -> char {
+    if c.is_ascii() {
+        return c.to_ascii_lowercase();
+    }
+    // Final sigma folds like the other two sigmas
+    if c == 'ς' {
+        return 'σ';
+    }
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(folded), None) => folded,
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_fold() {
+        assert_eq!(Collation::Binary.fold("Alice"), "Alice");
+        assert_eq!(Collation::AsciiCaseInsensitive.fold("Alice ÉCOLE"), "alice École");
+        assert_eq!(Collation::CaseInsensitive.fold("Alice ÉCOLE"), "alice école");
+        assert_eq!(Collation::CaseInsensitive.fold("ΣΟΦΟΣ σοφος"), "σοφοσ σοφοσ");
+        // Length-changing foldings are left out
+        assert_eq!(Collation::CaseInsensitive.fold("Straße"), "straße");
+        assert_eq!(Collation::CaseInsensitive.fold("İ"), "İ");
+        assert!(matches!(Collation::CaseInsensitive.fold("lower"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_compare() {
+        let ci = Collation::CaseInsensitive;
+        assert_eq!(ci.compare(&json!("alice"), &json!("ALICE")), Ordering::Equal);
+        assert_eq!(ci.compare(&json!("Bob"), &json!("alice")), Ordering::Greater);
+        assert_eq!(Collation::Binary.compare(&json!("Bob"), &json!("alice")), Ordering::Less);
+        assert_eq!(ci.compare(&json!(["A", 1]), &json!(["a", 1])), Ordering::Equal);
+        assert_eq!(ci.fold_value(&json!({"k": "V", "n": 1})).into_owned(), json!({"k": "v", "n": 1}));
+        assert_eq!(ci.compare(&json!(1), &json!("a")), Ordering::Less);
+        let bytes = crate::core::binary::binary_value(b"\xff\x00");
+        assert_eq!(ci.fold_value(&bytes).into_owned(), bytes);
+    }
+}
@@ -1,17 +1,31 @@
 
 use crate::core::aggregation::AggregationPipeline;
 use crate::core::btree::BTree;
+use crate::core::collation::Collation;
+use crate::core::constants::PageNum;
 use crate::core::database::Database;
-use crate::core::document::{read_versioned_document, write_versioned_document, delete_document};
+use crate::core::binary::{binary_field, binary_value, decode_document, encode_document};
+use crate::core::document::{read_versioned_document, write_versioned_document, delete_document, VersionedDocument};
 use crate::core::errors::*;
-use crate::core::query::parser::parse_query;
-use crate::core::query_builder::QueryBuilder;
-use crate::core::watch::WatchBuilder;
+use crate::core::metrics::{CollectionMetrics, Operation};
+use crate::core::query::ast::QueryAst;
+use crate::core::query::parser::{field_values, parse_query, Node};
+use crate::core::recycle_bin::{self, DELETED_AT};
+use crate::core::query_builder::{index_for_sort, sort_documents, QueryBuilder, QueryExplain, SortOrder};
+use crate::core::metadata::Metadata;
+use crate::core::slow_query::SlowQueryOperation;
+use crate::core::snapshot::SnapshotView;
+use crate::core::transaction::Transaction;
+use crate::core::tx_btree::TxBTree;
+use crate::core::tx_collection::TxCollection;
+use crate::core::update_ops::{apply_update, document_for_insert};
+use crate::core::validation::validation_errors_to_error;
+use crate::core::watch::{before_change_interest, WatchBuilder};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use serde_json::Value;
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 /// Result of an upsert operation
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -22,6 +36,12 @@ pub enum UpsertResult {
     Updated(String),
 }
 
+/// Change applied by [`Collection::find_one_and_modify`]
+pub(crate) enum FindAndModify {
+    Update { updates: Value, return_new: bool },
+    Delete,
+}
+
 /// Result of a bulk write operation
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
 pub struct BulkWriteResult {
@@ -31,6 +51,9 @@ pub struct BulkWriteResult {
     pub updated_count: usize,
     /// Number of documents successfully deleted
     pub deleted_count: usize,
+    /// Number of documents inserted by upserts that matched nothing. Upserts
+    /// that matched a document count towards `updated_count`
+    pub upserted_count: usize,
     /// Errors that occurred during execution (in unordered mode)
     pub errors: Vec<BulkWriteError>,
 }
@@ -44,6 +67,27 @@ pub struct BulkWriteError {
     pub message: String,
 }
 
+/// Outcome of [`Collection::insert_many_chunked`]
+#[derive(Debug, Clone)]
+pub struct ChunkedInsertResult {
+    /// IDs of the committed documents, in input order
+    pub inserted_ids: Vec<String>,
+    /// Number of chunks committed. A load resumes from the first document of
+    /// chunk `chunks_committed`.
+    pub chunks_committed: usize,
+    /// The chunk whose transaction failed. Neither it nor anything after it
+    /// was inserted.
+    pub failed_chunk: Option<ChunkFailure>,
+}
+
+/// Chunk that stopped an [`Collection::insert_many_chunked`] load
+#[derive(Debug, Clone)]
+pub struct ChunkFailure {
+    /// Zero-based index of the chunk
+    pub chunk_index: usize,
+    pub error: Error,
+}
+
 /// A single operation in a bulk write
 #[derive(Debug, Clone)]
 enum BulkOperation {
@@ -52,6 +96,8 @@ enum BulkOperation {
     UpdateMany { query: String, updates: Value },
     DeleteOne(String),
     DeleteMany(String),
+    UpsertOne { query: String, doc: Value },
+    UpsertById { id: String, doc: Value },
 }
 
 /// Builder for bulk write operations
@@ -106,6 +152,26 @@ impl<'a> BulkWrite<'a> {
         self
     }
 
+    /// Add an upsert operation: update the first document matching the query,
+    /// or insert `doc` if none does
+    pub fn upsert_one(mut self, query: &str, doc: Value) -> Self {
+        self.operations.push(BulkOperation::UpsertOne {
+            query: query.to_string(),
+            doc,
+        });
+        self
+    }
+
+    /// Add an upsert operation: update the document with this ID, or insert
+    /// `doc` under it if there is none
+    pub fn upsert_by_id(mut self, id: &str, doc: Value) -> Self {
+        self.operations.push(BulkOperation::UpsertById {
+            id: id.to_string(),
+            doc,
+        });
+        self
+    }
+
     /// Set whether operations should be executed in order (stop on first error)
     /// or unordered (continue on errors). Default is ordered.
     pub fn ordered(mut self, ordered: bool) -> Self {
@@ -115,13 +181,27 @@ impl<'a> BulkWrite<'a> {
 
     /// Execute all bulk operations in a single transaction
     pub fn execute(self) -> Result<BulkWriteResult> {
+        self.run(false)
+    }
+
+    /// Run the operations without committing them, to see what they would
+    /// do. The counts are what `execute` would report, and every operation
+    /// that would fail is listed in `errors`, even in ordered mode, where
+    /// `execute` would stop at the first of them.
+    pub fn dry_run(self) -> Result<BulkWriteResult> {
+        self.run(true)
+    }
+
+    fn run(self, dry_run: bool) -> Result<BulkWriteResult> {
         let mut result = BulkWriteResult {
             inserted_count: 0,
             updated_count: 0,
             deleted_count: 0,
+            upserted_count: 0,
             errors: Vec::new(),
         };
 
+        self.collection.ensure_writable()?;
         if self.operations.is_empty() {
             return Ok(result);
         }
@@ -151,42 +231,51 @@ impl<'a> BulkWrite<'a> {
             .unwrap_or(0);
 
         let pager = tx.get_pager().clone();
-        let btree = if btree_root == 0 {
-            BTree::new(pager.clone())?
+        let mut btree = if btree_root == 0 {
+            TxBTree::create_empty(pager.clone(), tx.get_writes_arc())?
         } else {
-            BTree::open(pager.clone(), btree_root)
+            TxBTree::new(pager.clone(), btree_root, tx.get_writes_arc())
         };
 
         // Process each operation
         for (index, operation) in operations.into_iter().enumerate() {
             let op_result = match operation {
                 BulkOperation::Insert(doc) => {
-                    execute_insert(collection, &btree, &pager, &mut tx, tx_id, doc)
-                        .map(|_| (1, 0, 0))
+                    execute_insert(collection, &mut btree, &pager, &mut tx, tx_id, doc)
+                        .map(|_| (1, 0, 0, 0))
                 }
                 BulkOperation::UpdateOne { query, updates } => {
-                    execute_update_one(collection, &btree, &pager, &mut tx, tx_id, &query, updates)
-                        .map(|count| (0, count, 0))
+                    execute_update_one(collection, &mut btree, &pager, &mut tx, tx_id, &query, updates)
+                        .map(|count| (0, count, 0, 0))
                 }
                 BulkOperation::UpdateMany { query, updates } => {
-                    execute_update_many(collection, &btree, &pager, &mut tx, tx_id, &query, updates)
-                        .map(|count| (0, count, 0))
+                    execute_update_many(collection, &mut btree, &pager, &mut tx, tx_id, &query, updates)
+                        .map(|count| (0, count, 0, 0))
                 }
                 BulkOperation::DeleteOne(query) => {
-                    execute_delete_one(collection, &btree, &pager, &tx, &query)
-                        .map(|count| (0, 0, count))
+                    execute_delete_one(collection, &mut btree, &pager, &mut tx, &query)
+                        .map(|count| (0, 0, count, 0))
                 }
                 BulkOperation::DeleteMany(query) => {
-                    execute_delete_many(collection, &btree, &pager, &tx, &query)
-                        .map(|count| (0, 0, count))
+                    execute_delete_many(collection, &mut btree, &pager, &mut tx, &query)
+                        .map(|count| (0, 0, count, 0))
+                }
+                BulkOperation::UpsertOne { query, doc } => {
+                    execute_upsert_one(collection, &mut btree, &pager, &mut tx, tx_id, &query, doc)
+                        .map(|upsert| upsert_counts(&upsert))
+                }
+                BulkOperation::UpsertById { id, doc } => {
+                    execute_upsert_by_id(collection, &mut btree, &pager, &mut tx, tx_id, &id, doc)
+                        .map(|upsert| upsert_counts(&upsert))
                 }
             };
 
             match op_result {
-                Ok((inserted, updated, deleted)) => {
+                Ok((inserted, updated, deleted, upserted)) => {
                     result.inserted_count += inserted;
                     result.updated_count += updated;
                     result.deleted_count += deleted;
+                    result.upserted_count += upserted;
                 }
                 Err(e) => {
                     let error = BulkWriteError {
@@ -194,7 +283,7 @@ impl<'a> BulkWrite<'a> {
                         message: e.to_string(),
                     };
 
-                    if ordered {
+                    if ordered && !dry_run {
                         // In ordered mode, stop on first error and rollback
                         return Err(Error::Other(format!(
                             "bulk write failed at operation {}: {}",
@@ -208,16 +297,22 @@ impl<'a> BulkWrite<'a> {
             }
         }
 
+        if dry_run {
+            tx.rollback()?;
+            return Ok(result);
+        }
+
         // Update metadata with new btree root
-        let new_root = btree.root_page();
-        collection.db.update_metadata(|m| {
-            let coll = m.get_collection(&collection.name);
-            coll.btree_root = new_root;
-        })?;
+        let new_root = btree.get_current_root();
+        tx.set_collection_root(&collection.name, new_root);
 
         // Commit the transaction
         tx.commit()?;
 
+        collection.metrics.inserted((result.inserted_count + result.upserted_count) as u64);
+        collection.metrics.updated(result.updated_count as u64);
+        collection.metrics.deleted(result.deleted_count as u64);
+
         Ok(result)
     }
 
@@ -226,7 +321,7 @@ impl<'a> BulkWrite<'a> {
 // Helper functions for bulk operations
 fn execute_insert(
     collection: &Collection,
-    btree: &BTree,
+    btree: &mut TxBTree,
     pager: &Arc<crate::core::pager::Pager>,
     tx: &mut crate::core::transaction::Transaction,
     tx_id: u64,
@@ -241,7 +336,8 @@ fn execute_insert(
                 .ok_or_else(|| Error::Other("_id must be a string".to_string()))?
                 .to_string()
         } else {
-            let id = generate_id();
+            let id = collection.db.next_document_id(&collection.name)?
+                .unwrap_or_else(generate_id);
             doc_map.insert("_id".to_string(), Value::String(id.clone()));
             id
         };
@@ -251,7 +347,15 @@ fn execute_insert(
             return Err(Error::Other(format!("document with ID {} already exists", doc_id)));
         }
 
-        let data = serde_json::to_vec(&doc_map)?;
+        collection.validate_schema(&Value::Object(doc_map.clone()))?;
+
+        if collection.db.document_metadata() {
+            stamp_inserted_document(&mut doc_map);
+        }
+
+        tx.check_unique_indexes(&collection.name, &doc_id, &doc_map)?;
+
+        let data = encode_document(&doc_map, &collection.name)?;
 
         let mut tx_writes = std::collections::HashMap::new();
         let (page_num, _page_data) = write_versioned_document(
@@ -271,13 +375,15 @@ fn execute_insert(
         }
 
         tx.write_document(&collection.name, &doc_id, page_num)?;
+        tx.adjust_document_count(&collection.name, 1);
+        tx.write_index_entries(&collection.name, &doc_id, None, Some((doc_map, page_num)));
 
         Ok(doc_id)
 }
 
 fn execute_update_one(
     collection: &Collection,
-    btree: &BTree,
+    btree: &mut TxBTree,
     pager: &Arc<crate::core::pager::Pager>,
     tx: &mut crate::core::transaction::Transaction,
     tx_id: u64,
@@ -289,7 +395,7 @@ fn execute_update_one(
         }
 
         // Find first matching document
-        let doc = find_one_in_tx(btree, pager, tx, query)?;
+        let doc = find_one_in_tx(collection, btree, pager, tx, query)?;
 
         if let Some(doc) = doc {
             if let Some(id) = doc.get("_id").and_then(|v| v.as_str()) {
@@ -303,7 +409,7 @@ fn execute_update_one(
 
 fn execute_update_many(
     collection: &Collection,
-    btree: &BTree,
+    btree: &mut TxBTree,
     pager: &Arc<crate::core::pager::Pager>,
     tx: &mut crate::core::transaction::Transaction,
     tx_id: u64,
@@ -314,7 +420,7 @@ fn execute_update_many(
             return Err(Error::Other("updates must be an object".to_string()));
         }
 
-        let docs = find_in_tx(btree, pager, tx, query)?;
+        let docs = find_in_tx(collection, btree, pager, tx, query)?;
         let mut count = 0;
 
         for doc in docs {
@@ -329,7 +435,7 @@ fn execute_update_many(
 
 fn execute_update_by_id(
     collection: &Collection,
-    btree: &BTree,
+    btree: &mut TxBTree,
     pager: &Arc<crate::core::pager::Pager>,
     tx: &mut crate::core::transaction::Transaction,
     tx_id: u64,
@@ -345,29 +451,38 @@ fn execute_update_by_id(
             read_versioned_document(pager, old_page_num, &*tx_writes_read)?
         };
 
-        if !vdoc.is_visible(tx.snapshot_id) {
-            return Err(Error::Other("document not found".to_string()));
+        // Earlier operations in the same bulk write are visible
+        let is_own_write = vdoc.xmin == tx.mvcc_tx_id;
+
+        if !is_own_write && !vdoc.is_visible(tx.snapshot_id) {
+            return Err(Error::DocumentNotFound {
+                collection: collection.name.clone(),
+                id: id.to_string(),
+            });
         }
 
-        let mut doc: serde_json::Map<String, Value> = serde_json::from_slice(&vdoc.data)?;
+        tx.track_doc_before_image(&collection.name, id, &vdoc.data);
+
+        let mut doc: serde_json::Map<String, Value> = decode_document(&vdoc.data)?;
+        let before = doc.clone();
+        let stamp = collection.db.document_metadata().then(|| UpdateStamp::of(&doc));
 
         let updates_map = updates.as_object()
             .ok_or_else(|| Error::Other("updates must be an object".to_string()))?;
-        for (key, value) in updates_map {
-            doc.insert(key.clone(), value.clone());
-        }
+        apply_update(&mut doc, updates_map)?;
 
         doc.insert("_id".to_string(), Value::String(id.to_string()));
 
         // Validate against schema if one is set
-        let metadata = collection.db.get_metadata();
-        if let Some(coll_meta) = metadata.collections.get(&collection.name) {
-            if let Some(ref schema) = coll_meta.schema {
-                schema.validate(&Value::Object(doc.clone()))?;
-            }
+        collection.validate_schema(&Value::Object(doc.clone()))?;
+
+        if let Some(stamp) = stamp {
+            stamp.apply(&mut doc);
         }
 
-        let new_data = serde_json::to_vec(&doc)?;
+        tx.check_unique_indexes(&collection.name, id, &doc)?;
+
+        let new_data = encode_document(&doc, &collection.name)?;
 
         let mut tx_writes = std::collections::HashMap::new();
         let (new_page_num, _page_data) = write_versioned_document(
@@ -379,6 +494,7 @@ fn execute_update_by_id(
             &mut tx_writes,
         )?;
 
+        retire_version(collection, pager, tx, id, old_page_num, Some(&vdoc))?;
         btree.delete(id)?;
         btree.insert(id, new_page_num)?;
 
@@ -387,22 +503,95 @@ fn execute_update_by_id(
         }
 
         tx.write_document(&collection.name, id, new_page_num)?;
+        tx.write_index_entries(&collection.name, id, Some(before), Some((doc, new_page_num)));
 
         Ok(())
 }
 
+fn execute_upsert_one(
+    collection: &Collection,
+    btree: &mut TxBTree,
+    pager: &Arc<crate::core::pager::Pager>,
+    tx: &mut crate::core::transaction::Transaction,
+    tx_id: u64,
+    query: &str,
+    doc: Value,
+) -> Result<UpsertResult> {
+        if !doc.is_object() {
+            return Err(Error::Other("document must be an object".to_string()));
+        }
+
+        let existing = find_one_in_tx(collection, btree, pager, tx, query)?;
+
+        if let Some(existing) = existing {
+            if let Some(id) = existing.get("_id").and_then(|v| v.as_str()) {
+                execute_update_by_id(collection, btree, pager, tx, tx_id, id, doc)?;
+                return Ok(UpsertResult::Updated(id.to_string()));
+            }
+        }
+
+        let id = execute_insert(collection, btree, pager, tx, tx_id, document_for_insert(doc)?)?;
+        Ok(UpsertResult::Inserted(id))
+}
+
+fn execute_upsert_by_id(
+    collection: &Collection,
+    btree: &mut TxBTree,
+    pager: &Arc<crate::core::pager::Pager>,
+    tx: &mut crate::core::transaction::Transaction,
+    tx_id: u64,
+    id: &str,
+    doc: Value,
+) -> Result<UpsertResult> {
+        if !doc.is_object() {
+            return Err(Error::Other("document must be an object".to_string()));
+        }
+
+        if btree.search(id).is_ok() {
+            execute_update_by_id(collection, btree, pager, tx, tx_id, id, doc)?;
+            return Ok(UpsertResult::Updated(id.to_string()));
+        }
+
+        let mut doc = document_for_insert(doc)?;
+        if let Some(doc_map) = doc.as_object_mut() {
+            doc_map.insert("_id".to_string(), Value::String(id.to_string()));
+        }
+        execute_insert(collection, btree, pager, tx, tx_id, doc)?;
+        Ok(UpsertResult::Inserted(id.to_string()))
+}
+
+/// Merge `patch` into `target`, recursing into objects present on both sides
+fn merge_fields(target: &mut serde_json::Map<String, Value>, patch: &serde_json::Map<String, Value>) {
+    for (key, value) in patch {
+        match (target.get_mut(key), value) {
+            (Some(Value::Object(existing)), Value::Object(nested)) => merge_fields(existing, nested),
+            _ => {
+                target.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Count an upsert as either an update or an upserted insert
+fn upsert_counts(upsert: &UpsertResult) -> (usize, usize, usize, usize) {
+    match upsert {
+        UpsertResult::Inserted(_) => (0, 0, 0, 1),
+        UpsertResult::Updated(_) => (0, 1, 0, 0),
+    }
+}
+
 fn execute_delete_one(
-    _collection: &Collection,
-    btree: &BTree,
+    collection: &Collection,
+    btree: &mut TxBTree,
     pager: &Arc<crate::core::pager::Pager>,
-    tx: &crate::core::transaction::Transaction,
+    tx: &mut crate::core::transaction::Transaction,
     query: &str,
 ) -> Result<usize> {
-        let doc = find_one_in_tx(btree, pager, tx, query)?;
+        let doc = find_one_in_tx(collection, btree, pager, tx, query)?;
 
         if let Some(doc) = doc {
             if let Some(id) = doc.get("_id").and_then(|v| v.as_str()) {
-                execute_delete_by_id(btree, pager, id)?;
+                execute_delete_by_id(collection, btree, pager, tx, id)?;
                 return Ok(1);
             }
         }
@@ -411,18 +600,18 @@ fn execute_delete_one(
 }
 
 fn execute_delete_many(
-    _collection: &Collection,
-    btree: &BTree,
+    collection: &Collection,
+    btree: &mut TxBTree,
     pager: &Arc<crate::core::pager::Pager>,
-    tx: &crate::core::transaction::Transaction,
+    tx: &mut crate::core::transaction::Transaction,
     query: &str,
 ) -> Result<usize> {
-        let docs = find_in_tx(btree, pager, tx, query)?;
+        let docs = find_in_tx(collection, btree, pager, tx, query)?;
         let mut count = 0;
 
         for doc in docs {
             if let Some(id) = doc.get("_id").and_then(|v| v.as_str()) {
-                execute_delete_by_id(btree, pager, id)?;
+                execute_delete_by_id(collection, btree, pager, tx, id)?;
                 count += 1;
             }
         }
@@ -431,26 +620,137 @@ fn execute_delete_many(
 }
 
 fn execute_delete_by_id(
-    btree: &BTree,
+    collection: &Collection,
+    btree: &mut TxBTree,
     pager: &Arc<crate::core::pager::Pager>,
+    tx: &mut crate::core::transaction::Transaction,
     id: &str,
 ) -> Result<()> {
         let page_num = btree.search(id)?;
-        delete_document(pager, page_num)?;
+        capture_before_delete(collection, pager, tx, id, page_num);
+        let before = indexed_document(collection, pager, tx, page_num)?;
+        let soft_delete = collection.db.soft_delete(&collection.name);
+        let recycled = match (soft_delete, &before) {
+            (false, _) => None,
+            (true, Some(before)) => Some(before.clone()),
+            (true, None) => Some(stored_document(pager, tx, page_num)?),
+        };
+        if !retire_version(collection, pager, tx, id, page_num, None)? {
+            delete_document(pager, page_num)?;
+        }
         btree.delete(id)?;
+        tx.write_document(&collection.name, id, PageNum::MAX)?;
+        tx.adjust_document_count(&collection.name, -1);
+        if let Some(before) = before {
+            tx.write_index_entries(&collection.name, id, Some(before), None);
+        }
+        if let Some(doc) = recycled {
+            recycle_bin::recycle(tx, &collection.db, &collection.name, id, doc)?;
+        }
         Ok(())
 }
 
+/// Keep the committed version at `page_num` readable by older snapshots after
+/// this transaction replaces or deletes it. Garbage collection frees the page
+/// once no snapshot can see it. Returns `false` for a version written earlier
+/// in the same transaction, which nobody else can see.
+fn retire_version(
+    collection: &Collection,
+    pager: &Arc<crate::core::pager::Pager>,
+    tx: &crate::core::transaction::Transaction,
+    id: &str,
+    page_num: PageNum,
+    vdoc: Option<&VersionedDocument>,
+) -> Result<bool> {
+    let read;
+    let vdoc = match vdoc {
+        Some(vdoc) => vdoc,
+        None => {
+            let tx_writes_arc = tx.get_writes_arc();
+            let tx_writes = tx_writes_arc.read()
+                .map_err(|_| Error::LockPoisoned { lock_name: "transaction.writes".to_string() })?;
+            read = read_versioned_document(pager, page_num, &tx_writes)?;
+            &read
+        }
+    };
+
+    if vdoc.xmin == tx.mvcc_tx_id {
+        return Ok(false);
+    }
+
+    tx.add_old_version(&collection.name, id, crate::core::mvcc::DocumentVersion {
+        doc_id: id.to_string(),
+        xmin: vdoc.xmin,
+        xmax: vdoc.xmax,
+        data: vdoc.data.clone(),
+        page_num,
+    });
+    Ok(true)
+}
+
+/// Body of the document at `page_num`, for removing its index entries.
+/// Returns `None` without reading anything if the collection has no indexes.
+fn indexed_document(
+    collection: &Collection,
+    pager: &Arc<crate::core::pager::Pager>,
+    tx: &crate::core::transaction::Transaction,
+    page_num: PageNum,
+) -> Result<Option<serde_json::Map<String, Value>>> {
+    let has_indexes = collection.db.get_metadata().collections
+        .get(&collection.name)
+        .is_some_and(|coll_meta| !coll_meta.indexes.is_empty());
+    if !has_indexes {
+        return Ok(None);
+    }
+
+    stored_document(pager, tx, page_num).map(Some)
+}
+
+/// Body of the document at `page_num`, as this transaction sees it
+fn stored_document(
+    pager: &Arc<crate::core::pager::Pager>,
+    tx: &crate::core::transaction::Transaction,
+    page_num: PageNum,
+) -> Result<serde_json::Map<String, Value>> {
+    let tx_writes_arc = tx.get_writes_arc();
+    let tx_writes = tx_writes_arc.read()
+        .map_err(|_| Error::LockPoisoned { lock_name: "transaction.writes".to_string() })?;
+    let vdoc = read_versioned_document(pager, page_num, &tx_writes)?;
+    decode_document(&vdoc.data)
+}
+
+/// Record the document about to be deleted so watchers can see what was removed.
+/// The document is only read if somebody is watching the collection.
+fn capture_before_delete(
+    collection: &Collection,
+    pager: &Arc<crate::core::pager::Pager>,
+    tx: &crate::core::transaction::Transaction,
+    id: &str,
+    page_num: PageNum,
+) {
+    if before_change_interest(&collection.db.get_watchers(), &collection.name).is_none() {
+        return;
+    }
+
+    let tx_writes_arc = tx.get_writes_arc();
+    let tx_writes = tx_writes_arc.read()
+        .recover_poison();
+    if let Ok(vdoc) = read_versioned_document(pager, page_num, &tx_writes) {
+        tx.track_doc_before_image(&collection.name, id, &vdoc.data);
+    }
+}
+
 fn find_in_tx(
-    btree: &BTree,
+    collection: &Collection,
+    btree: &TxBTree,
     pager: &Arc<crate::core::pager::Pager>,
     tx: &crate::core::transaction::Transaction,
     query: &str,
 ) -> Result<Vec<Value>> {
-        let ast = parse_query(query)
-            .map_err(|e| Error::Other(format!("failed to parse query: {}", e)))?;
+        let ast = collection.parse_filter(query)?;
 
         let all_docs = find_all_in_tx(btree, pager, tx)?;
+        collection.metrics.full_scan(all_docs.len() as u64);
         let mut results = Vec::new();
 
         for doc in all_docs {
@@ -465,17 +765,18 @@ fn find_in_tx(
 }
 
 fn find_one_in_tx(
-    btree: &BTree,
+    collection: &Collection,
+    btree: &TxBTree,
     pager: &Arc<crate::core::pager::Pager>,
     tx: &crate::core::transaction::Transaction,
     query: &str,
 ) -> Result<Option<Value>> {
-    let docs = find_in_tx(btree, pager, tx, query)?;
+    let docs = find_in_tx(collection, btree, pager, tx, query)?;
     Ok(docs.into_iter().next())
 }
 
 fn find_all_in_tx(
-    btree: &BTree,
+    btree: &TxBTree,
     pager: &Arc<crate::core::pager::Pager>,
     tx: &crate::core::transaction::Transaction,
 ) -> Result<Vec<Value>> {
@@ -489,8 +790,10 @@ fn find_all_in_tx(
             let (_doc_id, page_num) = iter.entry();
             match read_versioned_document(pager, page_num, &*tx_writes) {
                 Ok(vdoc) => {
-                    if vdoc.is_visible(tx.snapshot_id) {
-                        if let Ok(doc) = serde_json::from_slice(&vdoc.data) {
+                    let is_own_write = vdoc.xmin == tx.mvcc_tx_id;
+
+                    if is_own_write || vdoc.is_visible(tx.snapshot_id) {
+                        if let Ok(doc) = decode_document(&vdoc.data) {
                             results.push(doc);
                         }
                     }
@@ -502,20 +805,276 @@ fn find_all_in_tx(
         Ok(results)
 }
 
+/// Smallest share of a collection scan worth handing to its own thread
+const MIN_DOCS_PER_SCAN_THREAD: usize = 1024;
+
+/// Distinct values of `field` across `docs`, in first-seen order. Array
+/// values contribute each of their elements, and a path through an array of
+/// objects contributes the field of every element.
+fn distinct_values(docs: &[Value], field: &str) -> Vec<Value> {
+    use std::collections::HashSet;
+
+    let mut seen = HashSet::new();
+    let mut results = Vec::new();
+
+    for doc in docs {
+        let Some(doc_map) = doc.as_object() else {
+            continue;
+        };
+
+        for value in field_values(doc_map, field) {
+            // Array values contribute their elements
+            let items = match value {
+                Value::Array(arr) => arr.iter().collect(),
+                _ => vec![value],
+            };
+            for item in items {
+                let value_str = serde_json::to_string(item).unwrap_or_default();
+                if seen.insert(value_str) {
+                    results.push(item.clone());
+                }
+            }
+        }
+    }
+
+    results
+}
+
+/// Documents walked in secondary index order by [`Collection::index_scan`]
+#[derive(Debug, Default)]
+pub(crate) struct IndexScan {
+    /// Documents in index order
+    pub docs: Vec<Value>,
+    pub keys_examined: usize,
+    /// Documents read from their pages
+    pub docs_examined: usize,
+}
+
+/// Documents matching a query, a batch at a time, from [`Collection::find_batches`]
+pub struct FindBatches {
+    tx: ReadTx,
+    pages: std::vec::IntoIter<PageNum>,
+    filter: Box<dyn Node>,
+    batch_size: usize,
+    metrics: Arc<CollectionMetrics>,
+    examined: usize,
+}
+
+impl Iterator for FindBatches {
+    type Item = Result<Vec<Value>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let pager = self.tx.get_pager();
+        let tx_writes_arc = self.tx.get_writes_arc();
+        let tx_writes = match tx_writes_arc.read() {
+            Ok(tx_writes) => tx_writes,
+            Err(_) => return Some(Err(Error::LockPoisoned { lock_name: "transaction.writes".to_string() })),
+        };
+
+        let mut batch = Vec::new();
+        while batch.len() < self.batch_size {
+            let Some(page_num) = self.pages.next() else { break };
+            let vdoc = match read_versioned_document(pager, page_num, &tx_writes) {
+                Ok(vdoc) if vdoc.is_visible(self.tx.snapshot_id) => vdoc,
+                _ => continue,
+            };
+            let doc: Value = match decode_document(&vdoc.data) {
+                Ok(doc) => doc,
+                Err(_) => continue,
+            };
+            self.examined += 1;
+            if doc.as_object().is_some_and(|doc_map| self.filter.eval(doc_map)) {
+                batch.push(doc);
+            }
+        }
+
+        if batch.is_empty() {
+            if self.examined > 0 {
+                self.metrics.full_scan(std::mem::take(&mut self.examined) as u64);
+            }
+            return None;
+        }
+        Some(Ok(batch))
+    }
+}
+
+/// Writes grouped into transactions of a fixed size, from
+/// [`Collection::with_batch`]
+///
+/// Each write joins the current batch's transaction, which commits once it
+/// holds `size` writes; the next write begins a new one. A batch is atomic,
+/// the writes as a whole are not: when a write or commit fails, the
+/// uncommitted writes of its batch are discarded and earlier batches stay
+/// committed.
+pub struct WriteBatch<'a> {
+    coll: &'a Collection,
+    size: usize,
+    tx: Option<Transaction>,
+    pending: usize,
+    committed: usize,
+}
+
+impl WriteBatch<'_> {
+    pub fn insert(&mut self, doc: Value) -> Result<String> {
+        self.write(|coll| coll.insert(doc))
+    }
+
+    pub fn update_by_id(&mut self, id: &str, updates: Value) -> Result<()> {
+        self.write(|coll| coll.update_by_id(id, updates))
+    }
+
+    pub fn delete_by_id(&mut self, id: &str) -> Result<()> {
+        self.write(|coll| coll.delete_by_id(id))
+    }
+
+    /// Commit the writes of the current batch now rather than waiting for it
+    /// to fill up
+    pub fn flush(&mut self) -> Result<()> {
+        let Some(mut tx) = self.tx.take() else {
+            return Ok(());
+        };
+        let pending = std::mem::take(&mut self.pending);
+        tx.commit()?;
+        self.committed += pending;
+        Ok(())
+    }
+
+    /// Writes committed so far
+    pub fn committed(&self) -> usize {
+        self.committed
+    }
+
+    /// Writes in the current batch, not committed yet
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+
+    fn write<T>(&mut self, op: impl FnOnce(&mut TxCollection<'_>) -> Result<T>) -> Result<T> {
+        let tx = match &mut self.tx {
+            Some(tx) => tx,
+            tx => tx.insert(self.coll.db.begin()?),
+        };
+        match tx.collection(&self.coll.name).and_then(|mut coll| op(&mut coll)) {
+            Ok(result) => {
+                self.pending += 1;
+                if self.pending >= self.size {
+                    self.flush()?;
+                }
+                Ok(result)
+            }
+            Err(e) => {
+                self.discard();
+                Err(e)
+            }
+        }
+    }
+
+    fn discard(&mut self) {
+        if let Some(mut tx) = self.tx.take() {
+            let _ = tx.rollback();
+        }
+        self.pending = 0;
+    }
+}
+
+/// Handle onto one collection of a database. Clones share the same database.
+#[derive(Clone)]
 pub struct Collection {
     db: Arc<Database>,
     name: String,
+    metrics: Arc<CollectionMetrics>,
+    /// Set for collections from a `Snapshot`, which read only from it
+    snapshot: Option<Arc<SnapshotView>>,
+}
+
+/// Transaction a read runs in: a new one, or the snapshot's the collection is bound to
+enum ReadTx {
+    Fresh(Box<Transaction>),
+    Snapshot(Arc<SnapshotView>),
+}
+
+impl std::ops::Deref for ReadTx {
+    type Target = Transaction;
+
+    fn deref(&self) -> &Transaction {
+        match self {
+            ReadTx::Fresh(tx) => tx,
+            ReadTx::Snapshot(view) => &view.tx,
+        }
+    }
 }
 
 impl Collection {
     pub(crate) fn new(db: Arc<Database>, name: String) -> Self {
-        Self { db, name }
+        let metrics = db.metrics_ref().collection(&name);
+        Self { db, name, metrics, snapshot: None }
+    }
+
+    pub(crate) fn with_snapshot(mut self, view: Arc<SnapshotView>) -> Self {
+        self.snapshot = Some(view);
+        self
+    }
+
+    /// Transaction to read through, pinned to the snapshot if there is one
+    fn read_tx(&self) -> Result<ReadTx> {
+        match &self.snapshot {
+            Some(view) => Ok(ReadTx::Snapshot(view.clone())),
+            None => self.db.begin_read_only().map(|tx| ReadTx::Fresh(Box::new(tx))),
+        }
+    }
+
+    /// Metadata to read against: the snapshot's, or the last commit's
+    fn metadata(&self) -> Metadata {
+        match &self.snapshot {
+            Some(view) => view.metadata.clone(),
+            None => self.db.get_metadata(),
+        }
+    }
+
+    /// Writes go through fresh transactions, so a snapshot's collections refuse them
+    fn ensure_writable(&self) -> Result<()> {
+        if self.snapshot.is_some() {
+            return Err(Error::ReadOnlyTransaction);
+        }
+        Ok(())
+    }
+
+    /// Whether `id` may be in the collection. The `_id` filters follow the
+    /// latest commit, so snapshots skip them.
+    fn may_contain_id(&self, id: &str) -> bool {
+        self.snapshot.is_some() || self.db.may_contain_id(&self.name, id)
     }
 
     pub fn name(&self) -> &str {
         &self.name
     }
 
+    /// Whether this collection exists as of the last commit, or the snapshot
+    /// it was taken from. Getting a handle doesn't create the collection; the
+    /// first write does.
+    pub fn exists(&self) -> bool {
+        match &self.snapshot {
+            Some(view) => view.metadata.collections.contains_key(&self.name),
+            None => self.db.collection_exists(&self.name),
+        }
+    }
+
+    /// Order queries return documents in when they don't sort themselves,
+    /// set with [`Database::set_default_sort`]. Empty means primary key order.
+    pub fn default_sort(&self) -> Vec<(String, SortOrder)> {
+        match &self.snapshot {
+            Some(view) => view.metadata.collections
+                .get(&self.name)
+                .map(|coll_meta| coll_meta.default_sort.clone())
+                .unwrap_or_default(),
+            None => self.db.get_default_sort(&self.name),
+        }
+    }
+
+    pub(crate) fn database(&self) -> &Database {
+        &self.db
+    }
+
     /// Create a new query builder for this collection
     pub fn query(&self) -> QueryBuilder<'_> {
         QueryBuilder::new(self)
@@ -532,29 +1091,32 @@ impl Collection {
     }
 
     pub fn insert(&self, doc: Value) -> Result<String> {
+        self.ensure_writable()?;
+        let _timer = self.db.metrics_ref().start_timer(Operation::Insert);
+
         let mut doc_map = doc.as_object()
             .ok_or_else(|| Error::Other("document must be an object".to_string()))?
             .clone();
 
         // Validate against schema if one is set
-        let metadata = self.db.get_metadata();
-        if let Some(coll_meta) = metadata.collections.get(&self.name) {
-            if let Some(ref schema) = coll_meta.schema {
-                schema.validate(&Value::Object(doc_map.clone()))?;
-            }
-        }
+        self.validate_schema(&Value::Object(doc_map.clone()))?;
 
         let doc_id = if let Some(id) = doc_map.get("_id") {
             id.as_str()
                 .ok_or_else(|| Error::Other("_id must be a string".to_string()))?
                 .to_string()
         } else {
-            let id = generate_id();
+            let id = self.db.next_document_id(&self.name)?
+                .unwrap_or_else(generate_id);
             doc_map.insert("_id".to_string(), Value::String(id.clone()));
             id
         };
 
-        let data = serde_json::to_vec(&doc_map)?;
+        if self.db.document_metadata() {
+            stamp_inserted_document(&mut doc_map);
+        }
+
+        let data = encode_document(&doc_map, &self.name)?;
 
         let mut tx = self.db.begin()?;
         let tx_id = tx.mvcc_tx_id;
@@ -566,16 +1128,18 @@ impl Collection {
             .unwrap_or(0);
 
         let pager = tx.get_pager();
-        let btree = if btree_root == 0 {
-            BTree::new(pager.clone())?
+        let mut btree = if btree_root == 0 {
+            TxBTree::create_empty(pager.clone(), tx.get_writes_arc())?
         } else {
-            BTree::open(pager.clone(), btree_root)
+            TxBTree::new(pager.clone(), btree_root, tx.get_writes_arc())
         };
 
         if btree.search(&doc_id).is_ok() {
             return Err(Error::Other(format!("document with ID {} already exists", doc_id)));
         }
 
+        tx.check_unique_indexes(&self.name, &doc_id, &doc_map)?;
+
         let mut tx_writes = std::collections::HashMap::new();
         let (page_num, _page_data) = write_versioned_document(
             &pager,
@@ -594,28 +1158,80 @@ impl Collection {
         }
 
         tx.write_document(&self.name, &doc_id, page_num)?;
+        tx.adjust_document_count(&self.name, 1);
+        tx.write_index_entries(&self.name, &doc_id, None, Some((doc_map, page_num)));
 
-        let new_root = btree.root_page();
+        let new_root = btree.get_current_root();
 
         // Track the root change in the transaction - commit will update metadata
         tx.set_collection_root(&self.name, new_root);
 
         tx.commit()?;
+        self.metrics.inserted(1);
 
         Ok(doc_id)
     }
 
     pub fn find_by_id(&self, id: &str) -> Result<Value> {
-        let tx = self.db.begin()?;
+        let _timer = self.db.metrics_ref().start_timer(Operation::Find);
+        self.metrics.found();
+        self.metrics.index_hit();
+        if !self.may_contain_id(id) {
+            return Err(Error::NotFound);
+        }
+        decode_document(&self.document_data(id)?)
+    }
 
-        let metadata = self.db.get_metadata();
+    /// Insert a document holding `bytes` as the binary value of its `data`
+    /// field. The bytes are stored raw, not as base64 text.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # let db = Database::open("my.db").unwrap();
+    /// let thumbs = db.collection("thumbnails");
+    /// thumbs.insert_binary("cat.png", &[0x89, b'P', b'N', b'G']).unwrap();
+    /// assert_eq!(thumbs.get_binary("cat.png").unwrap(), vec![0x89, b'P', b'N', b'G']);
+    /// ```
+    pub fn insert_binary(&self, id: &str, bytes: &[u8]) -> Result<String> {
+        let mut doc = serde_json::Map::new();
+        doc.insert("_id".to_string(), Value::String(id.to_string()));
+        doc.insert("data".to_string(), binary_value(bytes));
+        self.insert(Value::Object(doc))
+    }
+
+    /// Bytes stored by [`insert_binary`](Self::insert_binary): the binary
+    /// value in the `data` field of document `id`, copied out of the stored
+    /// document without decoding it as JSON text
+    pub fn get_binary(&self, id: &str) -> Result<Vec<u8>> {
+        let _timer = self.db.metrics_ref().start_timer(Operation::Find);
+        self.metrics.found();
+        self.metrics.index_hit();
+        if !self.may_contain_id(id) {
+            return Err(Error::NotFound);
+        }
+
+        binary_field(&self.document_data(id)?, "data")?.ok_or_else(|| Error::InvalidDocumentFormat {
+            reason: format!("document '{}' has no binary 'data' field", id),
+            collection: Some(self.name.clone()),
+        })
+    }
+
+    /// Stored bytes of the visible version of document `id`
+    fn document_data(&self, id: &str) -> Result<Vec<u8>> {
+        let tx = self.read_tx()?;
+
+        let metadata = self.metadata();
         let btree_root = metadata.collections
             .get(&self.name)
-            .ok_or_else(|| Error::Other(format!("collection {} not found", self.name)))?
+            .ok_or_else(|| Error::CollectionDoesNotExist { name: self.name.clone() })?
             .btree_root;
 
         if btree_root == 0 {
-            return Err(Error::Other("document not found".to_string()));
+            return Err(Error::DocumentNotFound {
+                collection: self.name.clone(),
+                id: id.to_string(),
+            });
         }
 
         let pager = tx.get_pager();
@@ -631,73 +1247,635 @@ impl Collection {
         };
 
         if !vdoc.is_visible(tx.snapshot_id) {
-            return Err(Error::Other("document not found".to_string()));
+            return Err(Error::DocumentNotFound {
+                collection: self.name.clone(),
+                id: id.to_string(),
+            });
         }
 
-        let result: Value = serde_json::from_slice(&vdoc.data)?;
-
-        Ok(result)
+        Ok(vdoc.data)
     }
 
-    pub fn find_all(&self) -> Result<Vec<Value>> {
-        let tx = self.db.begin()?;
+    /// Look up several documents by ID against one snapshot
+    ///
+    /// Results come back in the order of `ids`, with `None` for IDs that don't
+    /// exist.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # let db = Database::open("my.db").unwrap();
+    /// let users = db.collection("users");
+    /// let docs = users.find_by_ids(&["alice", "bob"]).unwrap();
+    /// assert_eq!(docs.len(), 2);
+    /// ```
+    pub fn find_by_ids(&self, ids: &[&str]) -> Result<Vec<Option<Value>>> {
+        let _timer = self.db.metrics_ref().start_timer(Operation::Find);
+        self.metrics.found();
+        self.metrics.index_hit();
+        let tx = self.read_tx()?;
 
-        let metadata = self.db.get_metadata();
+        let btree_root = tx.get_snapshot_root(&self.name).unwrap_or(0);
+        if btree_root == 0 {
+            return Ok(vec![None; ids.len()]);
+        }
+
+        let pager = tx.get_pager();
+        let btree = BTree::open(pager.clone(), btree_root);
+        let tx_writes_arc = tx.get_writes_arc();
+        let tx_writes = tx_writes_arc.read()
+            .map_err(|_| Error::LockPoisoned { lock_name: "transaction.writes".to_string() })?;
+
+        let mut results = Vec::with_capacity(ids.len());
+        for id in ids {
+            if !self.may_contain_id(id) {
+                results.push(None);
+                continue;
+            }
+            let page_num = match btree.search(id) {
+                Ok(page_num) => page_num,
+                Err(Error::NotFound) => {
+                    results.push(None);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            let vdoc = read_versioned_document(pager, page_num, &tx_writes)?;
+            if vdoc.is_visible(tx.snapshot_id) {
+                results.push(Some(decode_document(&vdoc.data)?));
+            } else {
+                results.push(None);
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Documents with an `_id` from `start_id` up to `end_id`, in `_id` order
+    ///
+    /// `start_id` is always included; `end_id` only when `inclusive` is set.
+    /// Ids compare as strings, byte by byte. The primary key B-tree is seeked
+    /// to `start_id`, so only the range is read.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # let db = Database::open("my.db").unwrap();
+    /// let events = db.collection("events");
+    /// // Every event from January 2024
+    /// let january = events.scan_range("2024-01", "2024-02", false).unwrap();
+    /// ```
+    pub fn scan_range(&self, start_id: &str, end_id: &str, inclusive: bool) -> Result<Vec<Value>> {
+        self.scan_ids(start_id, Some((end_id, inclusive)), None)
+    }
+
+    /// Up to `limit` documents with an `_id` of `start_id` or later, in `_id`
+    /// order
+    ///
+    /// To page through a collection, start the next page just past the last
+    /// `_id` returned, at `format!("{}\0", last_id)`.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # let db = Database::open("my.db").unwrap();
+    /// let users = db.collection("users");
+    /// let mut page = users.scan_from("", 100).unwrap();
+    /// while let Some(last) = page.last() {
+    ///     let next = format!("{}\0", last["_id"].as_str().unwrap());
+    ///     page = users.scan_from(&next, 100).unwrap();
+    /// }
+    /// ```
+    pub fn scan_from(&self, start_id: &str, limit: usize) -> Result<Vec<Value>> {
+        self.scan_ids(start_id, None, Some(limit))
+    }
+
+    /// Visible documents from `start_id` on, stopping past `end` (an id and
+    /// whether it's included) or after `limit` documents
+    fn scan_ids(&self, start_id: &str, end: Option<(&str, bool)>, limit: Option<usize>) -> Result<Vec<Value>> {
+        let _timer = self.db.metrics_ref().start_timer(Operation::Find);
+        self.metrics.found();
+        self.metrics.index_hit();
+        let tx = self.read_tx()?;
+
+        let btree_root = tx.get_snapshot_root(&self.name).unwrap_or(0);
+        if btree_root == 0 || limit == Some(0) {
+            return Ok(Vec::new());
+        }
+
+        let pager = tx.get_pager();
+        let btree = BTree::open(pager.clone(), btree_root);
+        let tx_writes_arc = tx.get_writes_arc();
+        let tx_writes = tx_writes_arc.read()
+            .map_err(|_| Error::LockPoisoned { lock_name: "transaction.writes".to_string() })?;
+
+        let mut results = Vec::new();
+        let mut iter = btree.iterator_from(Some(start_id))?;
+        while iter.next() {
+            let (key, page_num) = iter.entry();
+            // The iterator starts at the beginning of the leaf holding start_id
+            if key < start_id {
+                continue;
+            }
+            match end {
+                Some((end_id, true)) if key > end_id => break,
+                Some((end_id, false)) if key >= end_id => break,
+                _ => {}
+            }
+
+            let vdoc = read_versioned_document(pager, page_num, &tx_writes)?;
+            if vdoc.is_visible(tx.snapshot_id) {
+                results.push(decode_document(&vdoc.data)?);
+                if limit == Some(results.len()) {
+                    break;
+                }
+            }
+        }
+
+        Ok(results)
+    }
+
+    pub fn find_all(&self) -> Result<Vec<Value>> {
+        let _timer = self.db.metrics_ref().start_timer(Operation::Find);
+        self.metrics.found();
+        self.scan_visible()
+    }
+
+    /// Documents matching `filter` and the number examined, for callers that
+    /// parsed a query themselves
+    pub(crate) fn find_matching(&self, filter: Option<&dyn Node>) -> Result<(Vec<Value>, usize)> {
+        let _timer = self.db.metrics_ref().start_timer(Operation::Find);
+        self.metrics.found();
+        self.scan_matching(filter)
+    }
+
+    /// Every document visible to a fresh snapshot
+    fn scan_visible(&self) -> Result<Vec<Value>> {
+        self.scan_matching(None).map(|(docs, _)| docs)
+    }
+
+    /// Every document visible to a fresh snapshot that `filter` matches, in
+    /// primary key order, along with the number of documents examined
+    ///
+    /// With `DatabaseOptions::scan_threads` above 1, large collections are
+    /// split into contiguous runs of keys that are read and filtered on their
+    /// own threads. Runs are joined in key order, so the result is the same as
+    /// a serial scan.
+    fn scan_matching(&self, filter: Option<&dyn Node>) -> Result<(Vec<Value>, usize)> {
+        let tx = self.read_tx()?;
+
+        let metadata = self.metadata();
         let btree_root = metadata.collections
             .get(&self.name)
-            .ok_or_else(|| Error::Other(format!("collection {} not found", self.name)))?
+            .ok_or_else(|| Error::CollectionDoesNotExist { name: self.name.clone() })?
             .btree_root;
 
         if btree_root == 0 {
-            return Ok(Vec::new());
+            return Ok((Vec::new(), 0));
         }
 
         let pager = tx.get_pager();
         let btree = BTree::open(pager.clone(), btree_root);
 
-        let mut results = Vec::new();
         let tx_writes_arc = tx.get_writes_arc();
         let tx_writes = tx_writes_arc.read()
             .map_err(|_| Error::LockPoisoned { lock_name: "transaction.writes".to_string() })?;
 
+        let mut pages = Vec::new();
         let mut iter = btree.iterator()?;
         while iter.next() {
-            let (_doc_id, page_num) = iter.entry();
-            match read_versioned_document(&pager, page_num, &*tx_writes) {
-                Ok(vdoc) => {
-                    if vdoc.is_visible(tx.snapshot_id) {
-                        if let Ok(doc) = serde_json::from_slice(&vdoc.data) {
-                            results.push(doc);
-                        }
-                    }
+            pages.push(iter.entry().1);
+        }
+
+        let snapshot_id = tx.snapshot_id;
+        let scan_limit = self.db.max_scan_documents();
+        let result_limit = self.db.max_result_documents();
+        let read_run = |run: &[PageNum]| {
+            let mut docs = Vec::new();
+            let mut examined = 0;
+            for &page_num in run {
+                // One document past a limit is enough to fail the query
+                if (scan_limit > 0 && examined > scan_limit) || (result_limit > 0 && docs.len() > result_limit) {
+                    break;
                 }
-                Err(_) => continue,
+                let vdoc = match read_versioned_document(pager, page_num, &tx_writes) {
+                    Ok(vdoc) if vdoc.is_visible(snapshot_id) => vdoc,
+                    _ => continue,
+                };
+                let doc: Value = match decode_document(&vdoc.data) {
+                    Ok(doc) => doc,
+                    Err(_) => continue,
+                };
+                examined += 1;
+                let matches = match filter {
+                    Some(filter) => doc.as_object().is_some_and(|doc_map| filter.eval(doc_map)),
+                    None => true,
+                };
+                if matches {
+                    docs.push(doc);
+                }
+            }
+            (docs, examined)
+        };
+
+        let threads = self.db.scan_threads().min(pages.len() / MIN_DOCS_PER_SCAN_THREAD);
+        let (results, examined) = if threads <= 1 {
+            read_run(&pages)
+        } else {
+            let run_len = pages.len().div_ceil(threads);
+            std::thread::scope(|scope| {
+                let handles: Vec<_> = pages
+                    .chunks(run_len)
+                    .map(|run| scope.spawn(|| read_run(run)))
+                    .collect();
+
+                let mut results = Vec::new();
+                let mut examined = 0;
+                for handle in handles {
+                    let (docs, run_examined) = handle.join()
+                        .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+                    results.extend(docs);
+                    examined += run_examined;
+                }
+                (results, examined)
+            })
+        };
+
+        self.metrics.full_scan(examined as u64);
+        self.check_query_limits(examined, results.len())?;
+
+        Ok((results, examined))
+    }
+
+    /// Documents matching `query`, read `batch_size` at a time as the
+    /// iterator is advanced rather than collected up front
+    ///
+    /// Batches come in primary key order, ignoring any default sort, and all
+    /// read from the snapshot taken when this is called; the snapshot stays
+    /// open until the iterator is dropped. Only the keys of the collection are
+    /// held in memory between batches, so `max_scan_documents` and
+    /// `max_result_documents` don't apply.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # let db = Database::open("my.db").unwrap();
+    /// let events = db.collection("events");
+    /// for batch in events.find_batches("kind is 'click'", 500).unwrap() {
+    ///     for event in batch.unwrap() {
+    ///         println!("{}", event["_id"]);
+    ///     }
+    /// }
+    /// ```
+    pub fn find_batches(&self, query: impl Into<QueryAst>, batch_size: usize) -> Result<FindBatches> {
+        let ast = self.db.apply_query_types(query.into().into_node()?);
+        let tx = self.read_tx()?;
+
+        let btree_root = self.metadata().collections
+            .get(&self.name)
+            .ok_or_else(|| Error::CollectionDoesNotExist { name: self.name.clone() })?
+            .btree_root;
+
+        let mut pages = Vec::new();
+        if btree_root != 0 {
+            let btree = BTree::open(tx.get_pager().clone(), btree_root);
+            let mut iter = btree.iterator()?;
+            while iter.next() {
+                pages.push(iter.entry().1);
             }
         }
 
-        Ok(results)
+        self.metrics.found();
+        Ok(FindBatches {
+            tx,
+            pages: pages.into_iter(),
+            filter: ast,
+            batch_size: batch_size.max(1),
+            metrics: self.metrics.clone(),
+            examined: 0,
+        })
+    }
+
+    /// Fail with `Error::QueryTooExpensive` when a query examined or collected
+    /// more documents than `DatabaseOptions` allow
+    fn check_query_limits(&self, examined: usize, collected: usize) -> Result<()> {
+        let over = |count: usize, limit: usize| limit > 0 && count > limit;
+        let (count, limit) = if over(examined, self.db.max_scan_documents()) {
+            (examined, self.db.max_scan_documents())
+        } else if over(collected, self.db.max_result_documents()) {
+            (collected, self.db.max_result_documents())
+        } else {
+            return Ok(());
+        };
+
+        self.db.metrics_ref().query_over_limit();
+        Err(Error::QueryTooExpensive { examined: count, limit })
+    }
+
+    /// Return every document in the order of a secondary index.
+    ///
+    /// With `covered` set, documents are rebuilt from the index keys, keeping
+    /// only `_id` and the indexed fields, without reading document pages. The
+    /// index stores a missing field and an explicit null the same way, so
+    /// entries holding a null are still answered from the full document.
+    ///
+    /// Entries pointing at a document version that is no longer current are
+    /// skipped. Returns `None` when the number of live entries differs from the
+    /// collection's document count, since the index then can't be trusted to
+    /// hold every document.
+    pub(crate) fn index_scan(&self, index_name: &str, covered: bool) -> Result<Option<IndexScan>> {
+        use crate::core::index_key::{compare_values, deserialize_compound_index_key};
+        use crate::core::query_builder::set_nested_field;
+
+        let tx = self.read_tx()?;
+
+        let metadata = self.metadata();
+        let coll_meta = match metadata.collections.get(&self.name) {
+            Some(coll_meta) => coll_meta,
+            None => return Ok(None),
+        };
+        let (index_meta, expected) = match (coll_meta.indexes.get(index_name), coll_meta.document_count) {
+            (Some(index_meta), Some(expected)) => (index_meta, expected as usize),
+            _ => return Ok(None),
+        };
+        let fields = index_meta.get_fields();
+
+        if coll_meta.btree_root == 0 || index_meta.btree_root == 0 {
+            return Ok(if expected == 0 { Some(IndexScan::default()) } else { None });
+        }
+
+        let pager = tx.get_pager();
+        let primary = BTree::open(pager.clone(), coll_meta.btree_root);
+        let index = BTree::open(pager.clone(), index_meta.btree_root);
+        let no_writes = std::collections::HashMap::new();
+
+        let mut scan = IndexScan::default();
+        let mut entries = Vec::new();
+        let mut iter = index.iterator()?;
+        while iter.next() {
+            scan.keys_examined += 1;
+            self.check_query_limits(scan.keys_examined, entries.len())?;
+            let (key, page_num) = iter.entry();
+            let key = match deserialize_compound_index_key(key, fields.len()) {
+                Ok(key) => key,
+                Err(_) => return Ok(None),
+            };
+
+            // Entries left behind by updates and deletes point at old versions
+            if primary.search(&key.doc_id).ok() != Some(page_num) {
+                continue;
+            }
+
+            if !covered || key.field_values.iter().any(Value::is_null) {
+                scan.docs_examined += 1;
+                let vdoc = read_versioned_document(pager, page_num, &no_writes)?;
+                if !vdoc.is_visible(tx.snapshot_id) {
+                    return Ok(None);
+                }
+                let doc: Value = decode_document(&vdoc.data)?;
+                entries.push((key, doc));
+                continue;
+            }
+
+            let mut doc = serde_json::Map::new();
+            doc.insert("_id".to_string(), Value::String(key.doc_id.clone()));
+            for (field, value) in fields.iter().zip(&key.field_values) {
+                set_nested_field(&mut doc, field, value.clone());
+            }
+            entries.push((key, Value::Object(doc)));
+        }
+
+        if entries.len() != expected {
+            return Ok(None);
+        }
+
+        // Keys are stored as JSON text, which doesn't sort like the values it
+        // encodes, so entries are put in index order after decoding
+        entries.sort_by(|(a, _), (b, _)| {
+            let order = a.field_values.iter()
+                .zip(&b.field_values)
+                .map(|(va, vb)| compare_values(va, vb))
+                .find(|order| order.is_ne())
+                .unwrap_or_else(|| a.doc_id.cmp(&b.doc_id));
+            if index_meta.descending { order.reverse() } else { order }
+        });
+        scan.docs = entries.into_iter().map(|(_, doc)| doc).collect();
+
+        self.metrics.found();
+        self.metrics.index_hit();
+        self.metrics.index_used(index_name);
+
+        Ok(Some(scan))
+    }
+
+    /// Documents matching `filter`, read from the candidates a geo index holds
+    /// for its `near()` condition instead of scanning the collection. Returns
+    /// the index used and the scan, in primary key order like a collection
+    /// scan, or `None` when the filter has no `near()` condition or no geo
+    /// index covers its fields.
+    ///
+    /// Candidates come from the geohash cells covering the search circle and
+    /// are checked against the whole filter, including the exact distance.
+    pub(crate) fn geo_scan(&self, filter: &dyn Node) -> Result<Option<(String, IndexScan)>> {
+        use crate::core::geo::covering_cells;
+        use crate::core::index_key::deserialize_compound_index_key;
+
+        let near = match filter.near() {
+            Some(near) => near,
+            None => return Ok(None),
+        };
+
+        let tx = self.read_tx()?;
+
+        let metadata = self.metadata();
+        let coll_meta = match metadata.collections.get(&self.name) {
+            Some(coll_meta) => coll_meta,
+            None => return Ok(None),
+        };
+        let (lat_field, lng_field) = near.point_fields();
+        let index_meta = match coll_meta.indexes
+            .values()
+            .filter(|index_meta| index_meta.geo && index_meta.get_fields() == [lat_field, lng_field])
+            .min_by_key(|index_meta| index_meta.name.clone())
+        {
+            Some(index_meta) => index_meta,
+            None => return Ok(None),
+        };
+
+        let mut scan = IndexScan::default();
+        if coll_meta.btree_root == 0 || index_meta.btree_root == 0 {
+            return Ok(Some((index_meta.name.clone(), scan)));
+        }
+
+        let pager = tx.get_pager();
+        let primary = BTree::open(pager.clone(), coll_meta.btree_root);
+        let index = BTree::open(pager.clone(), index_meta.btree_root);
+        let no_writes = std::collections::HashMap::new();
+
+        let mut matches = Vec::new();
+        for cell in covering_cells(near.lat, near.lng, near.radius_km) {
+            // Keys start with the geohash as a JSON string
+            for (key, page_num) in index.scan_prefix(&format!("\"{}", cell))? {
+                scan.keys_examined += 1;
+                self.check_query_limits(scan.keys_examined, matches.len())?;
+                let doc_id = match deserialize_compound_index_key(&key, 1) {
+                    Ok(key) => key.doc_id,
+                    Err(_) => return Ok(None),
+                };
+
+                // Entries left behind by updates and deletes point at old versions
+                if primary.search(&doc_id).ok() != Some(page_num) {
+                    continue;
+                }
+
+                scan.docs_examined += 1;
+                let vdoc = read_versioned_document(pager, page_num, &no_writes)?;
+                if !vdoc.is_visible(tx.snapshot_id) {
+                    return Ok(None);
+                }
+                let doc: Value = decode_document(&vdoc.data)?;
+                if doc.as_object().is_some_and(|doc_map| filter.eval(doc_map)) {
+                    matches.push((doc_id, doc));
+                }
+            }
+        }
+
+        matches.sort_by(|(a, _), (b, _)| a.cmp(b));
+        scan.docs = matches.into_iter().map(|(_, doc)| doc).collect();
+
+        self.metrics.found();
+        self.metrics.index_hit();
+        self.metrics.index_used(&index_meta.name);
+
+        Ok(Some((index_meta.name.clone(), scan)))
+    }
+
+    /// Documents matching `filter`, read from an expression index holding the
+    /// value of an `is` comparison on the same expression, such as
+    /// `lower(email) is 'ann@example.com'`. Returns the index used and the
+    /// scan, in primary key order like a collection scan, or `None` when no
+    /// expression index answers a condition of the filter.
+    ///
+    /// `collation` is the one the filter's comparisons are under. An index
+    /// folding strings under another collation only answers a binary filter,
+    /// since its entries for a value then include every document the filter
+    /// could match.
+    pub(crate) fn expression_scan(&self, filter: &dyn Node, collation: Collation) -> Result<Option<(String, IndexScan)>> {
+        use crate::core::expression::integral;
+        use crate::core::index_key::{compound_index_key_prefix, deserialize_compound_index_key};
+
+        let mut conditions = Vec::new();
+        filter.expression_equalities(&mut conditions);
+        if conditions.is_empty() {
+            return Ok(None);
+        }
+
+        let tx = self.read_tx()?;
+
+        let metadata = self.metadata();
+        let coll_meta = match metadata.collections.get(&self.name) {
+            Some(coll_meta) => coll_meta,
+            None => return Ok(None),
+        };
+        let found = conditions.iter().find_map(|(expr, value)| {
+            let text = expr.to_string();
+            coll_meta.indexes
+                .values()
+                .filter(|index_meta| index_meta.expression && index_meta.get_fields() == [text.as_str()])
+                // A sparse index leaves out the documents computing to null
+                .filter(|index_meta| !(index_meta.sparse && value.is_null()))
+                .filter(|index_meta| collation.is_binary() || index_meta.collation == collation)
+                .min_by_key(|index_meta| index_meta.name.clone())
+                .map(|index_meta| (index_meta, index_meta.collation.fold_value(&integral((*value).clone())).into_owned()))
+        });
+        let (index_meta, value) = match found {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+
+        let mut scan = IndexScan::default();
+        if coll_meta.btree_root == 0 || index_meta.btree_root == 0 {
+            return Ok(Some((index_meta.name.clone(), scan)));
+        }
+
+        let pager = tx.get_pager();
+        let primary = BTree::open(pager.clone(), coll_meta.btree_root);
+        let index = BTree::open(pager.clone(), index_meta.btree_root);
+        let no_writes = std::collections::HashMap::new();
+
+        let mut matches = Vec::new();
+        for (key, page_num) in index.scan_prefix(&compound_index_key_prefix(&[value])?)? {
+            scan.keys_examined += 1;
+            self.check_query_limits(scan.keys_examined, matches.len())?;
+            let doc_id = match deserialize_compound_index_key(&key, 1) {
+                Ok(key) => key.doc_id,
+                Err(_) => return Ok(None),
+            };
+
+            // Entries left behind by updates and deletes point at old versions
+            if primary.search(&doc_id).ok() != Some(page_num) {
+                continue;
+            }
+
+            scan.docs_examined += 1;
+            let vdoc = read_versioned_document(pager, page_num, &no_writes)?;
+            if !vdoc.is_visible(tx.snapshot_id) {
+                return Ok(None);
+            }
+            let doc: Value = decode_document(&vdoc.data)?;
+            if doc.as_object().is_some_and(|doc_map| filter.eval(doc_map)) {
+                matches.push((doc_id, doc));
+            }
+        }
+
+        matches.sort_by(|(a, _), (b, _)| a.cmp(b));
+        scan.docs = matches.into_iter().map(|(_, doc)| doc).collect();
+
+        self.metrics.found();
+        self.metrics.index_hit();
+        self.metrics.index_used(&index_meta.name);
+
+        Ok(Some((index_meta.name.clone(), scan)))
     }
 
     pub fn update_by_id(&self, id: &str, updates: Value) -> Result<()> {
+        self.update_by_id_checked(id, None, updates)
+    }
+
+    /// Update a document only if its stored `_rev` equals `expected_rev`,
+    /// failing with `Error::RevConflict` otherwise. Documents without a `_rev`
+    /// count as revision 0. Revisions only advance when the database was opened
+    /// with `DatabaseOptions::document_metadata`.
+    pub fn update_by_id_if_rev(&self, id: &str, expected_rev: u64, updates: Value) -> Result<()> {
+        self.update_by_id_checked(id, Some(expected_rev), updates)
+    }
+
+    fn update_by_id_checked(&self, id: &str, expected_rev: Option<u64>, updates: Value) -> Result<()> {
+        self.ensure_writable()?;
         if !updates.is_object() {
             return Err(Error::Other("updates must be an object".to_string()));
         }
 
+        let _timer = self.db.metrics_ref().start_timer(Operation::Update);
         let mut tx = self.db.begin()?;
         let tx_id = tx.mvcc_tx_id;
 
         let metadata = self.db.get_metadata();
         let btree_root = metadata.collections
             .get(&self.name)
-            .ok_or_else(|| Error::Other(format!("collection {} not found", self.name)))?
+            .ok_or_else(|| Error::CollectionDoesNotExist { name: self.name.clone() })?
             .btree_root;
 
         if btree_root == 0 {
-            return Err(Error::Other("document not found".to_string()));
+            return Err(Error::DocumentNotFound {
+                collection: self.name.clone(),
+                id: id.to_string(),
+            });
         }
 
         let pager = tx.get_pager();
-        let btree = BTree::open(pager.clone(), btree_root);
+        let mut btree = TxBTree::new(pager.clone(), btree_root, tx.get_writes_arc());
 
         let old_page_num = btree.search(id)?;
 
@@ -709,28 +1887,47 @@ impl Collection {
         };
 
         if !vdoc.is_visible(tx.snapshot_id) {
-            return Err(Error::Other("document not found".to_string()));
+            return Err(Error::DocumentNotFound {
+                collection: self.name.clone(),
+                id: id.to_string(),
+            });
         }
 
-        let mut doc: serde_json::Map<String, Value> = serde_json::from_slice(&vdoc.data)?;
+        tx.track_doc_before_image(&self.name, id, &vdoc.data);
+
+        let mut doc: serde_json::Map<String, Value> = decode_document(&vdoc.data)?;
+
+        if let Some(expected) = expected_rev {
+            let actual = stored_rev(&doc);
+            if actual != expected {
+                return Err(Error::RevConflict {
+                    collection: self.name.clone(),
+                    id: id.to_string(),
+                    expected,
+                    actual,
+                });
+            }
+        }
+
+        let before = doc.clone();
+        let stamp = self.db.document_metadata().then(|| UpdateStamp::of(&doc));
 
         let updates_map = updates.as_object()
             .ok_or_else(|| Error::Other("updates must be an object".to_string()))?;
-        for (key, value) in updates_map {
-            doc.insert(key.clone(), value.clone());
-        }
+        apply_update(&mut doc, updates_map)?;
 
         doc.insert("_id".to_string(), Value::String(id.to_string()));
 
         // Validate against schema if one is set
-        let metadata = self.db.get_metadata();
-        if let Some(coll_meta) = metadata.collections.get(&self.name) {
-            if let Some(ref schema) = coll_meta.schema {
-                schema.validate(&Value::Object(doc.clone()))?;
-            }
+        self.validate_schema(&Value::Object(doc.clone()))?;
+
+        if let Some(stamp) = stamp {
+            stamp.apply(&mut doc);
         }
 
-        let new_data = serde_json::to_vec(&doc)?;
+        tx.check_unique_indexes(&self.name, id, &doc)?;
+
+        let new_data = encode_document(&doc, &self.name)?;
 
         let mut tx_writes = std::collections::HashMap::new();
         let (new_page_num, _page_data) = write_versioned_document(
@@ -742,8 +1939,8 @@ impl Collection {
             &mut tx_writes,
         )?;
 
-        btree.delete(id)?;
-        btree.insert(id, new_page_num)?;
+        retire_version(self, pager, &tx, id, old_page_num, Some(&vdoc))?;
+        btree.update(id, new_page_num)?;
 
         // Add all pages (including overflow pages) to transaction write buffer
         for (pg_num, pg_data) in tx_writes {
@@ -751,46 +1948,71 @@ impl Collection {
         }
 
         tx.write_document(&self.name, id, new_page_num)?;
+        tx.write_index_entries(&self.name, id, Some(before), Some((doc, new_page_num)));
 
-        let new_root = btree.root_page();
+        let new_root = btree.get_current_root();
 
         // Track the root change in the transaction - commit will update metadata
         tx.set_collection_root(&self.name, new_root);
 
         tx.commit()?;
+        self.metrics.updated(1);
 
         Ok(())
     }
 
     pub fn delete_by_id(&self, id: &str) -> Result<()> {
+        self.ensure_writable()?;
+        if self.db.soft_delete(&self.name) {
+            let mut tx = self.db.begin()?;
+            tx.collection(&self.name)?.delete_by_id(id)?;
+            return tx.commit();
+        }
+
+        let _timer = self.db.metrics_ref().start_timer(Operation::Delete);
         let mut tx = self.db.begin()?;
         let _tx_id = tx.mvcc_tx_id;
 
         let metadata = self.db.get_metadata();
         let btree_root = metadata.collections
             .get(&self.name)
-            .ok_or_else(|| Error::Other(format!("collection {} not found", self.name)))?
+            .ok_or_else(|| Error::CollectionDoesNotExist { name: self.name.clone() })?
             .btree_root;
 
         if btree_root == 0 {
-            return Err(Error::Other("document not found".to_string()));
+            return Err(Error::DocumentNotFound {
+                collection: self.name.clone(),
+                id: id.to_string(),
+            });
         }
 
         let pager = tx.get_pager();
-        let btree = BTree::open(pager.clone(), btree_root);
+        let mut btree = TxBTree::new(pager.clone(), btree_root, tx.get_writes_arc());
 
         let page_num = btree.search(id)?;
 
-        delete_document(&pager, page_num)?;
+        capture_before_delete(self, pager, &tx, id, page_num);
+        let before = indexed_document(self, pager, &tx, page_num)?;
+
+        if !retire_version(self, pager, &tx, id, page_num, None)? {
+            delete_document(pager, page_num)?;
+        }
 
         btree.delete(id)?;
 
-        let new_root = btree.root_page();
+        tx.write_document(&self.name, id, PageNum::MAX)?;
+        tx.adjust_document_count(&self.name, -1);
+        if let Some(before) = before {
+            tx.write_index_entries(&self.name, id, Some(before), None);
+        }
+
+        let new_root = btree.get_current_root();
 
         // Track the root change in the transaction - commit will update metadata
         tx.set_collection_root(&self.name, new_root);
 
         tx.commit()?;
+        self.metrics.deleted(1);
 
         Ok(())
     }
@@ -804,13 +2026,17 @@ impl Collection {
         }
     }
 
+    /// Exact number of documents visible to a fresh snapshot
+    ///
+    /// Reads every document to check its visibility, so it costs O(n). Use
+    /// [`estimated_count`](Self::estimated_count) when a cheap number is enough.
     pub fn count(&self) -> Result<usize> {
-        let tx = self.db.begin()?;
+        let tx = self.read_tx()?;
 
-        let metadata = self.db.get_metadata();
+        let metadata = self.metadata();
         let btree_root = metadata.collections
             .get(&self.name)
-            .ok_or_else(|| Error::Other(format!("collection {} not found", self.name)))?
+            .ok_or_else(|| Error::CollectionDoesNotExist { name: self.name.clone() })?
             .btree_root;
 
         if btree_root == 0 {
@@ -840,62 +2066,377 @@ impl Collection {
             }
         }
 
+        self.metrics.full_scan(count as u64);
+
         Ok(count)
     }
 
-    pub fn find(&self, query: &str) -> Result<Vec<Value>> {
+    /// Number of documents as of the last commit, read from the collection's metadata
+    ///
+    /// This is O(1) and meant for dashboards and polling. It is not tied to a
+    /// snapshot: it can differ from [`count`](Self::count) while other
+    /// transactions are committing, and it never includes uncommitted writes.
+    /// Returns 0 for a collection that doesn't exist.
+    pub fn estimated_count(&self) -> Result<usize> {
+        match &self.snapshot {
+            Some(view) => Ok(view.metadata.collections
+                .get(&self.name)
+                .and_then(|coll_meta| coll_meta.document_count)
+                .unwrap_or(0) as usize),
+            None => self.db.estimated_document_count(&self.name),
+        }
+    }
+
+    /// Documents matching `query`, given as a query string or as a [`Query`]
+    /// built in Rust
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::{Database, Query};
+    /// # use serde_json::json;
+    /// # let db = Database::open("my.db").unwrap();
+    /// let users = db.collection("users");
+    /// let adults = users.find("age > 18").unwrap();
+    /// let named = users.find(Query::field("name").eq(json!("Alice"))).unwrap();
+    /// ```
+    ///
+    /// [`Query`]: crate::core::query::ast::Query
+    pub fn find(&self, query: impl Into<QueryAst>) -> Result<Vec<Value>> {
+        let start = Instant::now();
+        let query = query.into();
+        let key = query.cache_key().map(|key| format!("find:{}", key));
+        let (ast, text) = query.into_parts()?;
+        let ast = self.db.apply_query_types(ast);
+        let (docs, examined) = self.cached(key, || self.find_examined_ast(ast.as_ref()))?;
+        self.record_slow_query(SlowQueryOperation::Find, &text, start, examined);
+        Ok(docs)
+    }
+
+    /// Results of `run`, or of an earlier run under the same `key` when no
+    /// write to the collection has committed since. Queries without a key,
+    /// and those on a snapshot, always run.
+    pub(crate) fn cached(
+        &self,
+        key: Option<String>,
+        run: impl FnOnce() -> Result<(Vec<Value>, usize)>,
+    ) -> Result<(Vec<Value>, usize)> {
+        let cache = self.db.query_cache();
+        let key = match key {
+            Some(key) if cache.is_enabled() && self.snapshot.is_none() => key,
+            _ => return run(),
+        };
+
+        if let Some(docs) = cache.get(&self.name, &key) {
+            self.db.metrics_ref().query_cache_hit();
+            return Ok((docs, 0));
+        }
+        self.db.metrics_ref().query_cache_miss();
+
+        let generation = cache.generation(&self.name);
+        let (docs, examined) = run()?;
+        cache.put(&self.name, &key, generation, &docs);
+        Ok((docs, examined))
+    }
+
+    /// Documents matching `query` with its `?1`, `?2`, ... placeholders bound
+    /// to `params`
+    ///
+    /// Each parameter is used as a whole JSON value and is never parsed, so
+    /// user input can't break out of its place in the query.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # use serde_json::json;
+    /// # let db = Database::open("my.db").unwrap();
+    /// # let email = "alice@example.com";
+    /// let users = db.collection("users");
+    /// let found = users.find_params("email is ?1 and age > ?2", &[json!(email), json!(18)]).unwrap();
+    /// ```
+    pub fn find_params(&self, query: &str, params: &[Value]) -> Result<Vec<Value>> {
+        self.find(QueryAst::with_params(query, params))
+    }
+
+    /// First document matching `query` with its placeholders bound to
+    /// `params`, like [`find_params`](Self::find_params)
+    pub fn find_one_params(&self, query: &str, params: &[Value]) -> Result<Option<Value>> {
+        self.find_one(QueryAst::with_params(query, params))
+    }
+
+    /// Parse `query`, with its comparisons following
+    /// `DatabaseOptions::strict_query_types`
+    pub(crate) fn parse_filter(&self, query: &str) -> Result<Box<dyn Node>> {
         let ast = parse_query(query)
             .map_err(|e| Error::Other(format!("failed to parse query: {}", e)))?;
+        Ok(self.db.apply_query_types(ast))
+    }
 
-        let all_docs = self.find_all()?;
-        let mut results = Vec::new();
+    /// Documents matching `query` and the number examined
+    fn find_examined(&self, query: &str) -> Result<(Vec<Value>, usize)> {
+        let ast = self.parse_filter(query)?;
+        self.find_examined_ast(ast.as_ref())
+    }
 
-        for doc in all_docs {
-            if let Some(doc_map) = doc.as_object() {
-                if ast.eval(doc_map) {
-                    results.push(doc);
-                }
+    fn find_examined_ast(&self, ast: &dyn Node) -> Result<(Vec<Value>, usize)> {
+        let _timer = self.db.metrics_ref().start_timer(Operation::Find);
+
+        let default_sort = self.default_sort();
+        let indexed = match self.geo_scan(ast)? {
+            Some(found) => Some(found),
+            None => self.expression_scan(ast, Collation::Binary)?,
+        };
+        if let Some((_, mut scan)) = indexed {
+            if !default_sort.is_empty() {
+                sort_documents(&mut scan.docs, &default_sort, Collation::Binary);
             }
+            return Ok((scan.docs, scan.docs_examined));
         }
+        self.metrics.found();
+        if !default_sort.is_empty() {
+            return self.sorted_scan(ast, &default_sort);
+        }
+        self.scan_matching(Some(ast))
+    }
 
-        Ok(results)
+    /// Documents matching `filter` in `sort` order, read from an index in that
+    /// order when one is up to date and sorted after a collection scan otherwise
+    fn sorted_scan(&self, filter: &dyn Node, sort: &[(String, SortOrder)]) -> Result<(Vec<Value>, usize)> {
+        let index = self.db.list_indexes(&self.name).ok()
+            .and_then(|indexes| index_for_sort(indexes, sort, Collation::Binary));
+        if let Some(index) = index {
+            if let Some(scan) = self.index_scan(&index.name, false)? {
+                let docs = scan.docs
+                    .into_iter()
+                    .filter(|doc| doc.as_object().is_some_and(|doc_map| filter.eval(doc_map)))
+                    .collect();
+                return Ok((docs, scan.docs_examined));
+            }
+        }
+
+        let (mut docs, examined) = self.scan_matching(Some(filter))?;
+        sort_documents(&mut docs, sort, Collation::Binary);
+        Ok((docs, examined))
+    }
+
+    /// Add `query`, started at `start`, to the slow query log if it took long enough
+    pub(crate) fn record_slow_query(&self, operation: SlowQueryOperation, query: &str, start: Instant, examined: usize) {
+        self.db.slow_query_log().record(operation, &self.name, || query.to_string(), start, examined);
+    }
+
+    /// Run `query` and report how it was executed. Shorthand for
+    /// `query().filter(query).explain()`.
+    pub fn explain(&self, query: &str) -> Result<QueryExplain> {
+        self.query().filter(query).explain()
     }
 
-    pub fn find_one(&self, query: &str) -> Result<Option<Value>> {
+    pub fn find_one(&self, query: impl Into<QueryAst>) -> Result<Option<Value>> {
         let docs = self.find(query)?;
         Ok(docs.into_iter().next())
     }
 
+    /// Documents matching `query`, keeping only the `include` fields or
+    /// dropping the `exclude` ones. `_id` is kept unless it is excluded.
+    /// Giving neither returns whole documents. Dotted paths select nested
+    /// fields. With both lists, `exclude` may only name `_id` and paths
+    /// inside included fields, as in [`QueryBuilder::project`].
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # let db = Database::open("my.db").unwrap();
+    /// let users = db.collection("users");
+    /// let names = users.find_with_projection("age > 30", &["name", "address.city"], &[]).unwrap();
+    /// let slim = users.find_with_projection("age > 30", &[], &["avatar"]).unwrap();
+    /// let ids = users.find_with_projection("age > 30", &["_id"], &[]).unwrap();
+    /// let no_zip = users.find_with_projection("age > 30", &["address"], &["_id", "address.zip"]).unwrap();
+    /// ```
+    pub fn find_with_projection(&self, query: &str, include: &[&str], exclude: &[&str]) -> Result<Vec<Value>> {
+        self.projected_query(query, include, exclude).execute()
+    }
+
+    /// First document matching `query`, projected like
+    /// [`find_with_projection`](Self::find_with_projection)
+    pub fn find_one_with_projection(&self, query: &str, include: &[&str], exclude: &[&str]) -> Result<Option<Value>> {
+        self.projected_query(query, include, exclude).first()
+    }
+
+    fn projected_query(&self, query: &str, include: &[&str], exclude: &[&str]) -> QueryBuilder<'_> {
+        let mut builder = self.query().filter(query);
+        if !include.is_empty() {
+            builder = builder.project(include);
+        }
+        if !exclude.is_empty() {
+            builder = builder.exclude(exclude);
+        }
+        builder
+    }
+
     pub fn update(&self, query: &str, updates: Value) -> Result<usize> {
+        self.update_returning(query, updates).map(|ids| ids.len())
+    }
+
+    /// Like [`update`](Self::update), but returns the `_id` of every document
+    /// it modified, in the order they were updated
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # use serde_json::json;
+    /// # let db = Database::open("my.db").unwrap();
+    /// let users = db.collection("users");
+    /// let ids = users.update_returning("age > 30", json!({"senior": true})).unwrap();
+    /// ```
+    pub fn update_returning(&self, query: &str, updates: Value) -> Result<Vec<String>> {
         if !updates.is_object() {
             return Err(Error::Other("updates must be an object".to_string()));
         }
 
-        let docs = self.find(query)?;
-        let mut count = 0;
+        let start = Instant::now();
+        let (docs, examined) = self.find_examined(query)?;
+        let mut ids = Vec::with_capacity(docs.len());
 
         for doc in docs {
             if let Some(id) = doc.get("_id").and_then(|v| v.as_str()) {
                 self.update_by_id(id, updates.clone())?;
-                count += 1;
+                ids.push(id.to_string());
             }
         }
 
-        Ok(count)
+        self.record_slow_query(SlowQueryOperation::Update, query, start, examined);
+        Ok(ids)
     }
 
     pub fn update_one(&self, query: &str, updates: Value) -> Result<bool> {
-        if let Some(doc) = self.find_one(query)? {
-            if let Some(id) = doc.get("_id").and_then(|v| v.as_str()) {
-                self.update_by_id(id, updates)?;
-                return Ok(true);
+        self.update_one_returning(query, updates).map(|id| id.is_some())
+    }
+
+    /// Like [`update_one`](Self::update_one), but returns the `_id` of the
+    /// document it modified, or `None` if nothing matched
+    pub fn update_one_returning(&self, query: &str, updates: Value) -> Result<Option<String>> {
+        let start = Instant::now();
+        let (docs, examined) = self.find_examined(query)?;
+        let mut updated = None;
+        if let Some(id) = docs.first().and_then(|doc| doc.get("_id")).and_then(|v| v.as_str()) {
+            self.update_by_id(id, updates)?;
+            updated = Some(id.to_string());
+        }
+
+        self.record_slow_query(SlowQueryOperation::Update, query, start, examined);
+        Ok(updated)
+    }
+
+    /// Atomically update the first document matching `query` and return it,
+    /// as it was before the update or, with `return_new`, as it is after.
+    /// Returns `None` if nothing matches. Concurrent callers never get the same
+    /// document; sort with [`QueryBuilder::find_one_and_update`] to choose which
+    /// match comes first.
+    pub fn find_one_and_update(&self, query: &str, updates: Value, return_new: bool) -> Result<Option<Value>> {
+        self.query().filter(query).find_one_and_update(updates, return_new)
+    }
+
+    /// Atomically delete the first document matching `query` and return it.
+    /// Returns `None` if nothing matches. Concurrent callers never get the same
+    /// document; sort with [`QueryBuilder::find_one_and_delete`] to choose which
+    /// match comes first.
+    pub fn find_one_and_delete(&self, query: &str) -> Result<Option<Value>> {
+        self.query().filter(query).find_one_and_delete()
+    }
+
+    /// Pick the first document matching `query` in `sort` order, after
+    /// skipping `skip`, and apply `change` to it in the same transaction.
+    /// Strings are matched and sorted under `collation`.
+    /// Reading the document through the transaction records the version the
+    /// change is based on, so if another writer changes or removes it first
+    /// the commit conflicts and the whole pick is retried on a fresh snapshot.
+    pub(crate) fn find_one_and_modify(
+        &self,
+        query: Option<&str>,
+        sort: &[(String, SortOrder)],
+        skip: usize,
+        collation: Collation,
+        change: &FindAndModify,
+    ) -> Result<Option<Value>> {
+        let ast = match query {
+            Some(q) => {
+                let mut ast = self.parse_filter(q)?;
+                ast.set_collation(collation);
+                Some(ast)
+            }
+            None => None,
+        };
+
+        if let FindAndModify::Update { updates, .. } = change {
+            if !updates.is_object() {
+                return Err(Error::Other("updates must be an object".to_string()));
             }
         }
-        Ok(false)
+
+        self.ensure_writable()?;
+        if !self.exists() {
+            return Ok(None);
+        }
+
+        self.db.run_transaction(|tx| {
+            let mut coll = tx.collection(&self.name)?;
+
+            let mut docs = coll.find_all()?;
+            if let Some(ast) = &ast {
+                docs.retain(|doc| doc.as_object().is_some_and(|doc_map| ast.eval(doc_map)));
+            }
+            sort_documents(&mut docs, sort, collation);
+
+            let id = match docs.get(skip).and_then(|doc| doc.get("_id")).and_then(|v| v.as_str()) {
+                Some(id) => id.to_string(),
+                None => return Ok(None),
+            };
+            let before = coll.find_by_id(&id)?;
+
+            match change {
+                FindAndModify::Delete => {
+                    coll.delete_by_id(&id)?;
+                    Ok(Some(before))
+                }
+                FindAndModify::Update { updates, return_new } => {
+                    self.validate_update(&before, updates)?;
+                    coll.update_by_id(&id, updates.clone())?;
+                    if *return_new {
+                        coll.find_by_id(&id).map(Some)
+                    } else {
+                        Ok(Some(before))
+                    }
+                }
+            }
+        })
+    }
+
+    /// Check `doc` with `updates` applied against the collection's schema, if any
+    fn validate_update(&self, doc: &Value, updates: &Value) -> Result<()> {
+        let mut updated = doc.clone();
+        if let (Some(doc_map), Some(updates_map)) = (updated.as_object_mut(), updates.as_object()) {
+            apply_update(doc_map, updates_map)?;
+        }
+        self.validate_schema(&updated)
+    }
+
+    /// Check `doc` against the collection's schema, if any, reporting every
+    /// violation when the database collects all validation errors
+    pub(crate) fn validate_schema(&self, doc: &Value) -> Result<()> {
+        let metadata = self.db.get_metadata();
+        let schema = match metadata.collections.get(&self.name).and_then(|c| c.schema.as_ref()) {
+            Some(schema) => schema,
+            None => return Ok(()),
+        };
+
+        if self.db.collect_all_validation_errors() {
+            schema.validate_all(doc).map_err(validation_errors_to_error)
+        } else {
+            schema.validate(doc)
+        }
     }
 
     pub fn delete(&self, query: &str) -> Result<usize> {
-        let docs = self.find(query)?;
+        let start = Instant::now();
+        let (docs, examined) = self.find_examined(query)?;
         let mut count = 0;
 
         for doc in docs {
@@ -905,20 +2446,96 @@ impl Collection {
             }
         }
 
+        self.record_slow_query(SlowQueryOperation::Delete, query, start, examined);
         Ok(count)
     }
 
     pub fn delete_one(&self, query: &str) -> Result<bool> {
-        if let Some(doc) = self.find_one(query)? {
-            if let Some(id) = doc.get("_id").and_then(|v| v.as_str()) {
-                self.delete_by_id(id)?;
-                return Ok(true);
+        let start = Instant::now();
+        let (docs, examined) = self.find_examined(query)?;
+        let mut deleted = false;
+        if let Some(id) = docs.first().and_then(|doc| doc.get("_id")).and_then(|v| v.as_str()) {
+            self.delete_by_id(id)?;
+            deleted = true;
+        }
+
+        self.record_slow_query(SlowQueryOperation::Delete, query, start, examined);
+        Ok(deleted)
+    }
+
+    /// Move a document back out of the recycle bin of a collection with soft
+    /// delete on (see [`Database::set_soft_delete`]), without its
+    /// `_deleted_at` stamp. Fails with `DocumentAlreadyExists` if a document
+    /// with the same `_id` was inserted since the delete.
+    pub fn restore(&self, id: &str) -> Result<()> {
+        self.ensure_writable()?;
+        let mut tx = self.db.begin()?;
+
+        let doc = {
+            let mut bin = tx.collection(&recycle_bin::bin_name(&self.name))?;
+            let doc = bin.find_by_id(id).map_err(|e| match e {
+                Error::DocumentNotFound { .. } => Error::DocumentNotFound {
+                    collection: self.name.clone(),
+                    id: id.to_string(),
+                },
+                e => e,
+            })?;
+            bin.delete_by_id(id)?;
+            doc
+        };
+        let Value::Object(mut doc) = doc else {
+            return Err(Error::Other(format!("deleted document {} is not an object", id)));
+        };
+        doc.remove(DELETED_AT);
+
+        tx.collection(&self.name)?.insert_stored(id.to_string(), doc)?;
+        tx.commit()
+    }
+
+    /// Documents in the collection's recycle bin, each with the `_deleted_at`
+    /// time it was deleted at in milliseconds since the Unix epoch
+    pub fn deleted(&self) -> Result<Vec<Value>> {
+        match self.recycle_bin().find_all() {
+            Err(Error::CollectionDoesNotExist { .. }) => Ok(Vec::new()),
+            result => result,
+        }
+    }
+
+    /// Permanently remove documents that have been in the recycle bin for at
+    /// least `older_than`, returning how many were removed.
+    /// `Duration::ZERO` empties the bin.
+    pub fn purge_deleted(&self, older_than: Duration) -> Result<usize> {
+        self.ensure_writable()?;
+        let cutoff = now_millis().saturating_sub(older_than.as_millis() as u64);
+
+        let mut tx = self.db.begin()?;
+        let purged = {
+            let mut bin = tx.collection(&recycle_bin::bin_name(&self.name))?;
+            let expired: Vec<String> = bin.find_all()?
+                .iter()
+                .filter(|doc| doc.get(DELETED_AT).and_then(|at| at.as_u64()).is_some_and(|at| at <= cutoff))
+                .filter_map(|doc| doc.get("_id").and_then(|id| id.as_str()).map(String::from))
+                .collect();
+            for id in &expired {
+                bin.delete_by_id(id)?;
             }
+            expired.len()
+        };
+        tx.commit()?;
+        Ok(purged)
+    }
+
+    /// The hidden collection holding documents soft deleted from this one
+    pub(crate) fn recycle_bin(&self) -> Collection {
+        let bin = Collection::new(self.db.clone(), recycle_bin::bin_name(&self.name));
+        match &self.snapshot {
+            Some(view) => bin.with_snapshot(view.clone()),
+            None => bin,
         }
-        Ok(false)
     }
 
     pub fn insert_many(&self, docs: Vec<Value>) -> Result<Vec<String>> {
+        self.ensure_writable()?;
         if docs.is_empty() {
             return Ok(Vec::new());
         }
@@ -932,6 +2549,8 @@ impl Collection {
             });
         }
 
+        let _timer = self.db.metrics_ref().start_timer(Operation::Insert);
+
         // Execute all inserts in a single transaction
         let mut tx = self.db.begin()?;
         let tx_id = tx.mvcc_tx_id;
@@ -943,31 +2562,236 @@ impl Collection {
             .unwrap_or(0);
 
         let pager = tx.get_pager().clone();
-        let btree = if btree_root == 0 {
-            BTree::new(pager.clone())?
+        let mut btree = if btree_root == 0 {
+            TxBTree::create_empty(pager.clone(), tx.get_writes_arc())?
         } else {
-            BTree::open(pager.clone(), btree_root)
+            TxBTree::new(pager.clone(), btree_root, tx.get_writes_arc())
         };
 
         let mut ids = Vec::new();
 
         // Insert each document within the same transaction
         for doc in docs {
-            let id = execute_insert(self, &btree, &pager, &mut tx, tx_id, doc)?;
+            let id = execute_insert(self, &mut btree, &pager, &mut tx, tx_id, doc)?;
             ids.push(id);
         }
 
         // Update metadata with new btree root
-        let new_root = btree.root_page();
-        self.db.update_metadata(|m| {
-            let coll = m.get_collection(&self.name);
-            coll.btree_root = new_root;
-        })?;
+        let new_root = btree.get_current_root();
+        tx.set_collection_root(&self.name, new_root);
 
         // Commit the transaction - all or nothing
         tx.commit()?;
+        self.metrics.inserted(ids.len() as u64);
+
+        Ok(ids)
+    }
+
+    /// Check documents the way [`insert_many`](Self::insert_many) would
+    /// without writing anything, and report every one that would be rejected
+    /// with its index in `docs` and the reason. Documents are checked against
+    /// the schema, existing IDs and unique indexes, and against each other.
+    /// An empty list means `insert_many` would accept them all, as long as
+    /// nothing else is written in between.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # use serde_json::json;
+    /// # let db = Database::open("my.db").unwrap();
+    /// let users = db.collection("users");
+    /// let docs = vec![json!({"name": "Alice"}), json!({"name": 42})];
+    /// for failure in users.validate_many(&docs).unwrap() {
+    ///     eprintln!("document {}: {}", failure.operation_index, failure.message);
+    /// }
+    /// ```
+    pub fn validate_many(&self, docs: &[Value]) -> Result<Vec<BulkWriteError>> {
+        // The transaction only gives a snapshot to check against and holds
+        // the index entries of earlier documents. It is never committed.
+        let mut tx = self.db.begin()?;
+        let btree_root = self.db.get_metadata().collections
+            .get(&self.name)
+            .map(|c| c.btree_root)
+            .unwrap_or(0);
+        let btree = (btree_root != 0)
+            .then(|| TxBTree::new(tx.get_pager().clone(), btree_root, tx.get_writes_arc()));
+
+        let mut seen = std::collections::HashSet::new();
+        let mut failures = Vec::new();
+        for (index, doc) in docs.iter().enumerate() {
+            if let Err(e) = self.check_insert(&tx, btree.as_ref(), &mut seen, doc) {
+                failures.push(BulkWriteError {
+                    operation_index: index,
+                    message: e.to_string(),
+                });
+            }
+        }
+
+        tx.rollback()?;
+        Ok(failures)
+    }
+
+    /// The checks `execute_insert` makes before writing, with the IDs
+    /// accepted so far in `seen`
+    fn check_insert(
+        &self,
+        tx: &Transaction,
+        btree: Option<&TxBTree>,
+        seen: &mut std::collections::HashSet<String>,
+        doc: &Value,
+    ) -> Result<()> {
+        let doc_map = doc.as_object()
+            .ok_or_else(|| Error::Other("document must be an object".to_string()))?;
+
+        let doc_id = match doc_map.get("_id") {
+            Some(id) => {
+                let id = id.as_str()
+                    .ok_or_else(|| Error::Other("_id must be a string".to_string()))?;
+                if seen.contains(id) || btree.is_some_and(|btree| btree.search(id).is_ok()) {
+                    return Err(Error::Other(format!("document with ID {} already exists", id)));
+                }
+                id.to_string()
+            }
+            // Generating a real ID could advance an auto-increment counter
+            None => generate_id(),
+        };
+
+        self.validate_schema(doc)?;
+        tx.check_unique_indexes(&self.name, &doc_id, doc_map)?;
+
+        tx.write_index_entries(&self.name, &doc_id, None, Some((doc_map.clone(), 0)));
+        seen.insert(doc_id);
+        Ok(())
+    }
+
+    /// Insert documents in chunks of `chunk_size`, committing each chunk in its
+    /// own transaction. Meant for data loads that can tolerate partial progress.
+    ///
+    /// Unlike [`insert_many`](Self::insert_many), the load as a whole is not
+    /// atomic: each chunk is all or nothing, but chunks committed before a
+    /// failure stay committed and other readers can see them while the load
+    /// runs. The first failing chunk stops the load and is reported in
+    /// [`ChunkedInsertResult::failed_chunk`] rather than as an error, so the
+    /// caller can fix the input and resume from that chunk. Documents are
+    /// pulled from `docs` one chunk at a time, so they needn't all be in memory.
+    ///
+    /// Fails without inserting anything if `chunk_size` is zero or above
+    /// [`Database::max_bulk_operations`].
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # use serde_json::json;
+    /// # let db = Database::open("my.db").unwrap();
+    /// let events = db.collection("events");
+    /// let docs = (0..1_000_000).map(|i| json!({"seq": i}));
+    /// let result = events.insert_many_chunked(docs, 10_000).unwrap();
+    /// if let Some(failure) = result.failed_chunk {
+    ///     eprintln!("chunk {} failed: {}", failure.chunk_index, failure.error);
+    /// }
+    /// ```
+    pub fn insert_many_chunked<I>(&self, docs: I, chunk_size: usize) -> Result<ChunkedInsertResult>
+    where
+        I: IntoIterator<Item = Value>,
+    {
+        self.ensure_writable()?;
+        let max_bulk_ops = self.db.max_bulk_operations();
+        if chunk_size == 0 {
+            return Err(Error::Other("chunk size must be at least 1".to_string()));
+        }
+        if chunk_size > max_bulk_ops {
+            return Err(Error::BulkOperationTooLarge {
+                count: chunk_size,
+                limit: max_bulk_ops,
+            });
+        }
+
+        let mut result = ChunkedInsertResult {
+            inserted_ids: Vec::new(),
+            chunks_committed: 0,
+            failed_chunk: None,
+        };
+
+        let mut docs = docs.into_iter();
+        loop {
+            let chunk: Vec<Value> = docs.by_ref().take(chunk_size).collect();
+            if chunk.is_empty() {
+                break;
+            }
+
+            match self.insert_many(chunk) {
+                Ok(ids) => {
+                    result.inserted_ids.extend(ids);
+                    result.chunks_committed += 1;
+                }
+                Err(error) => {
+                    result.failed_chunk = Some(ChunkFailure {
+                        chunk_index: result.chunks_committed,
+                        error,
+                    });
+                    break;
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Run `f` with a [`WriteBatch`] that commits its writes `size` at a time,
+    /// then commit whatever is left
+    ///
+    /// For producers that can't collect their documents up front for
+    /// `insert_many`: writes share a transaction per batch, which is nearly as
+    /// fast, without holding more than a batch in memory. Atomicity is per
+    /// batch. If a write fails, the uncommitted writes of its batch are
+    /// discarded, and if `f` returns an error the current batch is discarded
+    /// too; batches committed before stay committed, and
+    /// [`WriteBatch::committed`] tells how far it got. A batch commits like any
+    /// transaction, so one that conflicts with a concurrent writer fails with
+    /// `TxConflict`.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// use serde_json::json;
+    ///
+    /// # let db = Database::open("my.db").unwrap();
+    /// let events = db.collection("events");
+    /// # let incoming: Vec<serde_json::Value> = Vec::new();
+    /// let count = events.with_batch(1000, |batch| {
+    ///     for event in incoming {
+    ///         batch.insert(event)?;
+    ///     }
+    ///     Ok(batch.committed() + batch.pending())
+    /// }).unwrap();
+    /// ```
+    pub fn with_batch<F, R>(&self, size: usize, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut WriteBatch<'_>) -> Result<R>,
+    {
+        self.ensure_writable()?;
+        let max_bulk_ops = self.db.max_bulk_operations();
+        if size == 0 {
+            return Err(Error::Other("batch size must be at least 1".to_string()));
+        }
+        if size > max_bulk_ops {
+            return Err(Error::BulkOperationTooLarge {
+                count: size,
+                limit: max_bulk_ops,
+            });
+        }
 
-        Ok(ids)
+        let mut batch = WriteBatch { coll: self, size, tx: None, pending: 0, committed: 0 };
+        match f(&mut batch) {
+            Ok(result) => {
+                batch.flush()?;
+                Ok(result)
+            }
+            Err(e) => {
+                batch.discard();
+                Err(e)
+            }
+        }
     }
 
     /// Upsert a document by ID - update if exists, insert if not
@@ -985,7 +2809,7 @@ impl Collection {
             Ok(UpsertResult::Updated(id.to_string()))
         } else {
             // Insert new document with the specified ID
-            let mut doc_map = doc.as_object()
+            let mut doc_map = document_for_insert(doc)?.as_object()
                 .ok_or_else(|| Error::Other("document must be an object".to_string()))?
                 .clone();
 
@@ -1007,7 +2831,7 @@ impl Collection {
         // Handle case where collection doesn't exist yet
         let existing = match self.find_one(query) {
             Ok(doc) => doc,
-            Err(Error::Other(msg)) if msg.contains("not found") => None,
+            Err(Error::CollectionDoesNotExist { .. }) => None,
             Err(e) => return Err(e),
         };
 
@@ -1020,46 +2844,139 @@ impl Collection {
         }
 
         // No match found - insert new document
-        let id = self.insert(doc)?;
+        let id = self.insert(document_for_insert(doc)?)?;
         Ok(UpsertResult::Inserted(id))
     }
 
+    /// Upsert by ID, merging `doc` into the existing document rather than
+    /// setting its top-level fields wholesale
+    ///
+    /// Nested objects are merged key by key, so `{"address": {"city": "Oslo"}}`
+    /// keeps the rest of `address`; any other value replaces what was there.
+    /// If there is no document with this ID, `doc` is inserted under it.
+    /// The merged document, not just `doc`, is checked against the schema.
+    pub fn upsert_merge_by_id(&self, id: &str, doc: Value) -> Result<UpsertResult> {
+        let mut doc_map = doc.as_object()
+            .ok_or_else(|| Error::Other("document must be an object".to_string()))?
+            .clone();
+        doc_map.insert("_id".to_string(), Value::String(id.to_string()));
+
+        self.upsert_merge_matching(&doc_map, |coll| match coll.find_by_id(id) {
+            Ok(_) => Ok(Some(id.to_string())),
+            Err(Error::DocumentNotFound { .. }) => Ok(None),
+            Err(e) => Err(e),
+        })
+    }
+
+    /// Upsert using a query, merging `doc` into the first match the same way
+    /// as [`Collection::upsert_merge_by_id`], or inserting it if nothing matches
+    pub fn upsert_merge(&self, query: &str, doc: Value) -> Result<UpsertResult> {
+        let doc_map = doc.as_object()
+            .ok_or_else(|| Error::Other("document must be an object".to_string()))?;
+        let ast = self.parse_filter(query)?;
+
+        self.upsert_merge_matching(doc_map, |coll| {
+            Ok(coll.find_all()?
+                .iter()
+                .find(|doc| doc.as_object().is_some_and(|doc_map| ast.eval(doc_map)))
+                .and_then(|doc| doc.get("_id"))
+                .and_then(|id| id.as_str())
+                .map(|id| id.to_string()))
+        })
+    }
+
+    /// Merge `doc` into the document `find_match` picks, or insert it, in one
+    /// transaction so a concurrent write to the match conflicts and retries
+    /// instead of being overwritten by a merge based on the old version
+    fn upsert_merge_matching<F>(&self, doc: &serde_json::Map<String, Value>, find_match: F) -> Result<UpsertResult>
+    where
+        F: Fn(&TxCollection) -> Result<Option<String>>,
+    {
+        self.ensure_writable()?;
+        self.db.run_transaction(|tx| {
+            let mut coll = tx.collection(&self.name)?;
+
+            let id = match find_match(&coll)? {
+                Some(id) => id,
+                None => {
+                    let doc = Value::Object(doc.clone());
+                    self.validate_schema(&doc)?;
+                    return coll.insert(doc).map(UpsertResult::Inserted);
+                }
+            };
+
+            // Read through the transaction so the version we merge into is tracked
+            let mut merged = match coll.find_by_id(&id)? {
+                Value::Object(existing) => existing,
+                _ => serde_json::Map::new(),
+            };
+            merge_fields(&mut merged, doc);
+            merged.insert("_id".to_string(), Value::String(id.clone()));
+
+            let merged = Value::Object(merged);
+            self.validate_schema(&merged)?;
+            coll.update_by_id(&id, merged)?;
+            Ok(UpsertResult::Updated(id))
+        })
+    }
+
     /// Get distinct values for a field across all documents
+    ///
+    /// When a non-sparse index holds the field, values are read from the index
+    /// keys instead of scanning every document, and come back in index order.
     pub fn distinct(&self, field: &str) -> Result<Vec<Value>> {
-        use std::collections::HashSet;
+        if let Some(index_name) = self.distinct_index(field) {
+            if let Some(scan) = self.index_scan(&index_name, true)? {
+                return Ok(distinct_values(&scan.docs, field));
+            }
+        }
 
         let all_docs = match self.find_all() {
             Ok(docs) => docs,
-            Err(Error::Other(msg)) if msg.contains("not found") => Vec::new(),
+            Err(Error::CollectionDoesNotExist { .. }) => Vec::new(),
             Err(e) => return Err(e),
         };
 
-        let mut seen = HashSet::new();
-        let mut results = Vec::new();
+        Ok(distinct_values(&all_docs, field))
+    }
 
-        for doc in all_docs {
-            if let Some(doc_map) = doc.as_object() {
-                let value = get_nested_field_value(doc_map, field);
-
-                // Handle array fields - extract all values from arrays
-                if let Value::Array(arr) = &value {
-                    for item in arr {
-                        let value_str = serde_json::to_string(item).unwrap_or_default();
-                        if seen.insert(value_str.clone()) {
-                            results.push(item.clone());
-                        }
-                    }
-                } else {
-                    // Regular field
-                    let value_str = serde_json::to_string(&value).unwrap_or_default();
-                    if seen.insert(value_str) {
-                        results.push(value);
-                    }
-                }
-            }
-        }
+    /// Get distinct values for a field across documents matching `query`
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # fn main() -> jasonisnthappy::Result<()> {
+    /// let db = Database::open("my.db")?;
+    /// let users = db.collection("users");
+    /// let cities = users.distinct_with_query("city", "country is 'US'")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn distinct_with_query(&self, field: &str, query: &str) -> Result<Vec<Value>> {
+        let ast = self.parse_filter(query)?;
 
-        Ok(results)
+        let matching = match self.find_matching(Some(ast.as_ref())) {
+            Ok((docs, _)) => docs,
+            Err(Error::CollectionDoesNotExist { .. }) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(distinct_values(&matching, field))
+    }
+
+    /// Name of a non-sparse index whose keys hold `field`, preferring the
+    /// smallest one
+    fn distinct_index(&self, field: &str) -> Option<String> {
+        let metadata = self.metadata();
+        metadata.collections
+            .get(&self.name)?
+            .indexes
+            .values()
+            // Keys of an index with a collation hold folded strings
+            .filter(|index_meta| !index_meta.sparse && !index_meta.expression && index_meta.collation.is_binary())
+            .filter(|index_meta| index_meta.get_fields().iter().any(|f| f == field))
+            .min_by_key(|index_meta| (index_meta.get_fields().len(), index_meta.name.clone()))
+            .map(|index_meta| index_meta.name.clone())
     }
 
     /// Count distinct values for a field
@@ -1094,7 +3011,7 @@ impl Collection {
 
     /// Perform full-text search on indexed fields
     ///
-    /// Returns documents sorted by relevance (highest score first).
+    /// Returns documents sorted by BM25 relevance (highest score first).
     /// This method requires a text index to be created on the collection first.
     ///
     /// # Arguments
@@ -1102,11 +3019,11 @@ impl Collection {
     ///
     /// # Example
     /// ```no_run
-    /// # use jasonisnthappy::Database;
+    /// # use jasonisnthappy::{Database, TextIndexOptions};
     /// # use serde_json::json;
     /// # let db = Database::open("my.db").unwrap();
     /// # let posts = db.collection("posts");
-    /// # db.create_text_index("posts", "search_idx", &["title", "body"]).unwrap();
+    /// # db.create_text_index("posts", "search_idx", &["title", "body"], TextIndexOptions::default()).unwrap();
     /// // Search for documents containing "rust database"
     /// let results = posts.search("rust database").unwrap();
     ///
@@ -1117,12 +3034,40 @@ impl Collection {
     /// }
     /// ```
     pub fn search(&self, query: &str) -> Result<Vec<crate::core::text_search::SearchResult>> {
+        self.text_search(query, false)
+    }
+
+    /// Perform full-text search and report how each term contributed to the score
+    ///
+    /// Same as `search`, but every result carries `components` with the BM25
+    /// score of each matching term and field. Useful for debugging relevance.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use jasonisnthappy::{Database, TextIndexOptions};
+    /// # let db = Database::open("my.db").unwrap();
+    /// # let posts = db.collection("posts");
+    /// # db.create_text_index("posts", "search_idx", &["title", "body"], TextIndexOptions::default()).unwrap();
+    /// for result in posts.search_explain("rust database").unwrap() {
+    ///     for part in result.components.unwrap_or_default() {
+    ///         println!("{} in {}: {}", part.term, part.field, part.score);
+    ///     }
+    /// }
+    /// ```
+    pub fn search_explain(&self, query: &str) -> Result<Vec<crate::core::text_search::SearchResult>> {
+        self.text_search(query, true)
+    }
+
+    fn text_search(&self, query: &str, explain: bool) -> Result<Vec<crate::core::text_search::SearchResult>> {
         use crate::core::text_search::TextIndex;
+
+        self.metrics.found();
+        self.metrics.index_hit();
         use crate::core::btree::BTree;
 
         // Find the first text index for this collection
         let (text_index_meta, fields) = {
-            let metadata = self.db.get_metadata();
+            let metadata = self.metadata();
             let coll_meta = metadata.collections.get(&self.name);
 
             let coll_meta = match coll_meta {
@@ -1148,15 +3093,21 @@ impl Collection {
             (text_index_meta.clone(), text_index_meta.fields.clone())
         };
 
+        self.metrics.index_used(&text_index_meta.name);
+
         // Load the text index B-tree
         let index_btree = BTree::open(self.db.get_pager(), text_index_meta.btree_root);
-        let text_index = TextIndex::new(index_btree, fields);
+        let text_index = TextIndex::new(index_btree, fields, text_index_meta.options);
 
         // Get total document count for IDF calculation
         let total_docs = self.count()?;
 
         // Perform search
-        text_index.search(query, total_docs)
+        if explain {
+            text_index.search_explain(query, total_docs)
+        } else {
+            text_index.search(query, total_docs)
+        }
     }
 
     // ========== TYPED DOCUMENT METHODS ==========
@@ -1184,8 +3135,7 @@ impl Collection {
     /// # }
     /// ```
     pub fn insert_typed<T: Serialize>(&self, doc: &T) -> Result<String> {
-        let value = serde_json::to_value(doc)
-            .map_err(|e| Error::Other(format!("Failed to serialize document: {}", e)))?;
+        let value = self.typed_to_value(doc, "insert_typed")?;
         self.insert(value)
     }
 
@@ -1193,10 +3143,7 @@ impl Collection {
     pub fn insert_many_typed<T: Serialize>(&self, docs: Vec<T>) -> Result<Vec<String>> {
         let values: Result<Vec<Value>> = docs
             .iter()
-            .map(|doc| {
-                serde_json::to_value(doc)
-                    .map_err(|e| Error::Other(format!("Failed to serialize document: {}", e)))
-            })
+            .map(|doc| self.typed_to_value(doc, "insert_many_typed"))
             .collect();
         self.insert_many(values?)
     }
@@ -1223,14 +3170,10 @@ impl Collection {
     /// ```
     pub fn find_by_id_typed<T: DeserializeOwned>(&self, id: &str) -> Result<Option<T>> {
         match self.find_by_id(id) {
-            Ok(value) => {
-                let typed = serde_json::from_value(value)
-                    .map_err(|e| Error::Other(format!("Failed to deserialize document: {}", e)))?;
-                Ok(Some(typed))
-            }
+            Ok(value) => Ok(Some(self.value_to_typed(value, "find_by_id_typed")?)),
             Err(Error::NotFound) => Ok(None),
             Err(Error::DocumentNotFound { .. }) => Ok(None),
-            Err(Error::Other(msg)) if msg.contains("not found") => Ok(None),
+            Err(Error::CollectionDoesNotExist { .. }) => Ok(None),
             Err(e) => Err(e),
         }
     }
@@ -1240,10 +3183,7 @@ impl Collection {
         let values = self.find_all()?;
         values
             .into_iter()
-            .map(|value| {
-                serde_json::from_value(value)
-                    .map_err(|e| Error::Other(format!("Failed to deserialize document: {}", e)))
-            })
+            .map(|value| self.value_to_typed(value, "find_all_typed"))
             .collect()
     }
 
@@ -1252,75 +3192,105 @@ impl Collection {
         let values = self.find(query)?;
         values
             .into_iter()
-            .map(|value| {
-                serde_json::from_value(value)
-                    .map_err(|e| Error::Other(format!("Failed to deserialize document: {}", e)))
-            })
+            .map(|value| self.value_to_typed(value, "find_typed"))
             .collect()
     }
 
     /// Find one typed document matching a query
     pub fn find_one_typed<T: DeserializeOwned>(&self, query: &str) -> Result<Option<T>> {
         match self.find_one(query)? {
-            Some(value) => {
-                let typed = serde_json::from_value(value)
-                    .map_err(|e| Error::Other(format!("Failed to deserialize document: {}", e)))?;
-                Ok(Some(typed))
-            }
+            Some(value) => Ok(Some(self.value_to_typed(value, "find_one_typed")?)),
             None => Ok(None),
         }
     }
 
     /// Update a typed document by ID
     pub fn update_by_id_typed<T: Serialize>(&self, id: &str, updates: &T) -> Result<()> {
-        let value = serde_json::to_value(updates)
-            .map_err(|e| Error::Other(format!("Failed to serialize updates: {}", e)))?;
+        let value = self.typed_to_value(updates, "update_by_id_typed")?;
         self.update_by_id(id, value)
     }
 
     /// Update typed documents matching a query
     pub fn update_typed<T: Serialize>(&self, query: &str, updates: &T) -> Result<usize> {
-        let value = serde_json::to_value(updates)
-            .map_err(|e| Error::Other(format!("Failed to serialize updates: {}", e)))?;
+        let value = self.typed_to_value(updates, "update_typed")?;
         self.update(query, value)
     }
 
     /// Update one typed document matching a query
     pub fn update_one_typed<T: Serialize>(&self, query: &str, updates: &T) -> Result<bool> {
-        let value = serde_json::to_value(updates)
-            .map_err(|e| Error::Other(format!("Failed to serialize updates: {}", e)))?;
+        let value = self.typed_to_value(updates, "update_one_typed")?;
         self.update_one(query, value)
     }
 
     /// Upsert a typed document by ID
     pub fn upsert_by_id_typed<T: Serialize>(&self, id: &str, doc: &T) -> Result<UpsertResult> {
-        let value = serde_json::to_value(doc)
-            .map_err(|e| Error::Other(format!("Failed to serialize document: {}", e)))?;
+        let value = self.typed_to_value(doc, "upsert_by_id_typed")?;
         self.upsert_by_id(id, value)
     }
 
     /// Upsert a typed document matching a query
     pub fn upsert_typed<T: Serialize>(&self, query: &str, doc: &T) -> Result<UpsertResult> {
-        let value = serde_json::to_value(doc)
-            .map_err(|e| Error::Other(format!("Failed to serialize document: {}", e)))?;
+        let value = self.typed_to_value(doc, "upsert_typed")?;
         self.upsert(query, value)
     }
+
+    fn typed_to_value<T: Serialize>(&self, doc: &T, operation: &str) -> Result<Value> {
+        serde_json::to_value(doc).map_err(|e| Error::SerializationError {
+            context: format!("{} of {} in collection '{}'", operation, std::any::type_name::<T>(), self.name),
+            error: e.to_string(),
+        })
+    }
+
+    fn value_to_typed<T: DeserializeOwned>(&self, value: Value, operation: &str) -> Result<T> {
+        serde_json::from_value(value).map_err(|e| Error::DeserializationError {
+            context: format!("{} into {} in collection '{}'", operation, std::any::type_name::<T>(), self.name),
+            error: e.to_string(),
+        })
+    }
+}
+
+/// Stamp `_rev`, `_created_at` and `_updated_at` on a document being inserted
+pub(crate) fn stamp_inserted_document(doc: &mut serde_json::Map<String, Value>) {
+    let now = Value::from(now_millis());
+    doc.insert("_rev".to_string(), Value::from(1u64));
+    doc.insert("_created_at".to_string(), now.clone());
+    doc.insert("_updated_at".to_string(), now);
 }
 
-/// Helper function to get nested field value from a document map
-fn get_nested_field_value(doc: &serde_json::Map<String, Value>, field: &str) -> Value {
-    let parts: Vec<&str> = field.split('.').collect();
-    let mut current = Value::Object(doc.clone());
+/// Metadata carried over from the stored version of a document being updated,
+/// so updates can't rewind `_rev` or rewrite `_created_at`
+pub(crate) struct UpdateStamp {
+    rev: u64,
+    created_at: Option<Value>,
+}
 
-    for part in parts {
-        if let Some(obj) = current.as_object() {
-            current = obj.get(part).cloned().unwrap_or(Value::Null);
-        } else {
-            return Value::Null;
+impl UpdateStamp {
+    pub(crate) fn of(stored: &serde_json::Map<String, Value>) -> Self {
+        Self {
+            rev: stored_rev(stored),
+            created_at: stored.get("_created_at").cloned(),
         }
     }
 
-    current
+    pub(crate) fn apply(self, doc: &mut serde_json::Map<String, Value>) {
+        let now = now_millis();
+        doc.insert("_rev".to_string(), Value::from(self.rev + 1));
+        if let Some(created_at) = self.created_at {
+            doc.insert("_created_at".to_string(), created_at);
+        }
+        doc.insert("_updated_at".to_string(), Value::from(now));
+    }
+}
+
+fn stored_rev(doc: &serde_json::Map<String, Value>) -> u64 {
+    doc.get("_rev").and_then(|v| v.as_u64()).unwrap_or(0)
+}
+
+pub(crate) fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
 }
 
 fn generate_id() -> String {
@@ -1399,6 +3369,97 @@ mod tests {
         let _ = fs::remove_file(format!("{}-wal", path));
     }
 
+    #[test]
+    fn test_collection_find_by_ids() {
+        let path = "/tmp/test_collection_find_by_ids.db";
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}.lock", path));
+        let _ = fs::remove_file(format!("{}-wal", path));
+
+        let db = Arc::new(Database::open(path).unwrap());
+        let coll = Collection::new(db.clone(), "users".to_string());
+
+        // A collection that was never written to has none of them
+        assert_eq!(coll.find_by_ids(&["a", "b"]).unwrap(), vec![None, None]);
+
+        let alice = coll.insert(json!({"name": "Alice"})).unwrap();
+        let bob = coll.insert(json!({"name": "Bob"})).unwrap();
+        let carol = coll.insert(json!({"name": "Carol"})).unwrap();
+        coll.delete_by_id(&carol).unwrap();
+
+        let found = coll.find_by_ids(&[&bob, "missing", &alice, &carol, &bob]).unwrap();
+        let names: Vec<Option<&str>> = found.iter()
+            .map(|doc| doc.as_ref().map(|doc| doc["name"].as_str().unwrap()))
+            .collect();
+        assert_eq!(names, vec![Some("Bob"), None, Some("Alice"), None, Some("Bob")]);
+
+        assert!(coll.find_by_ids(&[]).unwrap().is_empty());
+
+        db.close().unwrap();
+
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}.lock", path));
+        let _ = fs::remove_file(format!("{}-wal", path));
+    }
+
+    #[test]
+    fn test_collection_scan_range_and_scan_from() {
+        let path = "/tmp/test_collection_scan_range.db";
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}.lock", path));
+        let _ = fs::remove_file(format!("{}-wal", path));
+
+        let db = Arc::new(Database::open(path).unwrap());
+        let coll = Collection::new(db.clone(), "events".to_string());
+        assert!(coll.scan_range("a", "z", true).unwrap().is_empty());
+        assert!(coll.scan_from("", 10).unwrap().is_empty());
+
+        // Enough documents to span several leaves
+        let docs = (0..500).map(|i| json!({"_id": format!("ev{:04}", i), "n": i})).collect();
+        coll.insert_many(docs).unwrap();
+        coll.delete_by_id("ev0101").unwrap();
+
+        let ids = |docs: Vec<Value>| -> Vec<String> {
+            docs.iter().map(|doc| doc["_id"].as_str().unwrap().to_string()).collect()
+        };
+        assert_eq!(ids(coll.scan_range("ev0100", "ev0103", false).unwrap()), ["ev0100", "ev0102"]);
+        assert_eq!(ids(coll.scan_range("ev0100", "ev0103", true).unwrap()), ["ev0100", "ev0102", "ev0103"]);
+        assert_eq!(coll.scan_range("ev03", "ev04", false).unwrap().len(), 100);
+        assert!(coll.scan_range("ev0200", "ev0100", true).unwrap().is_empty());
+        assert!(coll.scan_range("zz", "zzz", true).unwrap().is_empty());
+
+        assert_eq!(ids(coll.scan_from("ev0099", 3).unwrap()), ["ev0099", "ev0100", "ev0102"]);
+        assert_eq!(ids(coll.scan_from("ev04985", 10).unwrap()), ["ev0499"]);
+        assert!(coll.scan_from("ev0000", 0).unwrap().is_empty());
+
+        // Paging through the whole collection
+        let mut seen = Vec::new();
+        let mut page = coll.scan_from("", 64).unwrap();
+        while let Some(last) = page.last() {
+            let next = format!("{}\0", last["_id"].as_str().unwrap());
+            seen.extend(ids(page));
+            page = coll.scan_from(&next, 64).unwrap();
+        }
+        assert_eq!(seen.len(), 499);
+        assert!(seen.windows(2).all(|pair| pair[0] < pair[1]));
+
+        // Scans through a snapshot don't see later writes
+        let snapshot = db.snapshot().unwrap();
+        coll.insert(json!({"_id": "ev0100a"})).unwrap();
+        coll.delete_by_id("ev0102").unwrap();
+        let old = snapshot.collection("events");
+        assert_eq!(ids(old.scan_range("ev0100", "ev0103", false).unwrap()), ["ev0100", "ev0102"]);
+        assert_eq!(ids(coll.scan_range("ev0100", "ev0103", false).unwrap()), ["ev0100", "ev0100a"]);
+        drop(old);
+        drop(snapshot);
+
+        db.close().unwrap();
+
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}.lock", path));
+        let _ = fs::remove_file(format!("{}-wal", path));
+    }
+
     #[test]
     fn test_collection_find_with_query() {
         let path = "/tmp/test_collection_find_query.db";
@@ -1505,6 +3566,35 @@ mod tests {
         let _ = fs::remove_file(format!("{}-wal", path));
     }
 
+    #[test]
+    fn test_collection_update_returning() {
+        let path = "/tmp/test_collection_update_returning.db";
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}.lock", path));
+        let _ = fs::remove_file(format!("{}-wal", path));
+
+        let db = Arc::new(Database::open(path).unwrap());
+        let coll = Collection::new(db.clone(), "users".to_string());
+
+        coll.insert(json!({"_id": "a", "age": 30})).unwrap();
+        coll.insert(json!({"_id": "b", "age": 25})).unwrap();
+        coll.insert(json!({"_id": "c", "age": 35})).unwrap();
+
+        let mut ids = coll.update_returning("age > 28", json!({"status": "senior"})).unwrap();
+        ids.sort();
+        assert_eq!(ids, vec!["a", "c"]);
+        assert!(coll.update_returning("age > 90", json!({"status": "old"})).unwrap().is_empty());
+
+        assert_eq!(coll.update_one_returning("age < 28", json!({"status": "junior"})).unwrap().as_deref(), Some("b"));
+        assert_eq!(coll.update_one_returning("age > 90", json!({"status": "old"})).unwrap(), None);
+        assert_eq!(coll.find_by_id("b").unwrap()["status"], "junior");
+
+        db.close().unwrap();
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}.lock", path));
+        let _ = fs::remove_file(format!("{}-wal", path));
+    }
+
     #[test]
     fn test_collection_delete_with_query() {
         let path = "/tmp/test_collection_delete_query.db";
@@ -1820,6 +3910,105 @@ mod tests {
         let _ = fs::remove_file(format!("{}-wal", path));
     }
 
+    #[test]
+    fn test_upsert_merge_by_id() {
+        let path = "/tmp/test_upsert_merge_by_id.db";
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}.lock", path));
+        let _ = fs::remove_file(format!("{}-wal", path));
+
+        let db = Arc::new(Database::open(path).unwrap());
+        let coll = Collection::new(db.clone(), "users".to_string());
+
+        let result = coll.upsert_merge_by_id("user1", json!({
+            "name": "Alice",
+            "address": {"city": "NYC", "zip": "10001"}
+        })).unwrap();
+        assert_eq!(result, UpsertResult::Inserted("user1".to_string()));
+
+        let result = coll.upsert_merge_by_id("user1", json!({
+            "age": 31,
+            "address": {"city": "Boston"}
+        })).unwrap();
+        assert_eq!(result, UpsertResult::Updated("user1".to_string()));
+
+        let doc = coll.find_by_id("user1").unwrap();
+        assert_eq!(doc["name"], "Alice");
+        assert_eq!(doc["age"], 31);
+        assert_eq!(doc["address"], json!({"city": "Boston", "zip": "10001"}));
+        assert_eq!(coll.count().unwrap(), 1);
+
+        db.close().unwrap();
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}.lock", path));
+        let _ = fs::remove_file(format!("{}-wal", path));
+    }
+
+    #[test]
+    fn test_upsert_merge_by_query() {
+        let path = "/tmp/test_upsert_merge_by_query.db";
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}.lock", path));
+        let _ = fs::remove_file(format!("{}-wal", path));
+
+        let db = Arc::new(Database::open(path).unwrap());
+        let coll = Collection::new(db.clone(), "metrics".to_string());
+
+        let inserted = match coll.upsert_merge("name is \"page_views\"", json!({"name": "page_views", "count": 1})).unwrap() {
+            UpsertResult::Inserted(id) => id,
+            UpsertResult::Updated(_) => panic!("Expected insert, got update"),
+        };
+
+        coll.update_by_id(&inserted, json!({"owner": "web"})).unwrap();
+
+        let result = coll.upsert_merge("name is \"page_views\"", json!({"count": 2})).unwrap();
+        assert_eq!(result, UpsertResult::Updated(inserted.clone()));
+
+        let doc = coll.find_by_id(&inserted).unwrap();
+        assert_eq!(doc["count"], 2);
+        assert_eq!(doc["owner"], "web");
+        assert_eq!(doc["name"], "page_views");
+        assert_eq!(coll.count().unwrap(), 1);
+
+        db.close().unwrap();
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}.lock", path));
+        let _ = fs::remove_file(format!("{}-wal", path));
+    }
+
+    #[test]
+    fn test_upsert_merge_validates_merged_document() {
+        use crate::core::validation::{Schema, ValueType};
+
+        let path = "/tmp/test_upsert_merge_schema.db";
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}.lock", path));
+        let _ = fs::remove_file(format!("{}-wal", path));
+
+        let db = Arc::new(Database::open(path).unwrap());
+        let mut schema = Schema::new();
+        schema.value_type = Some(ValueType::Object);
+        schema.required = Some(vec!["name".to_string(), "age".to_string()]);
+        db.set_schema("users", schema).unwrap();
+
+        let coll = Collection::new(db.clone(), "users".to_string());
+
+        // The patch alone would fail `required`, but the merged document passes
+        coll.insert(json!({"_id": "user1", "name": "Alice", "age": 30})).unwrap();
+        let result = coll.upsert_merge_by_id("user1", json!({"age": 31})).unwrap();
+        assert_eq!(result, UpsertResult::Updated("user1".to_string()));
+        assert_eq!(coll.find_by_id("user1").unwrap()["age"], 31);
+
+        // Inserting the patch as a new document is checked on its own
+        assert!(coll.upsert_merge_by_id("user2", json!({"age": 20})).is_err());
+        assert!(coll.find_by_id("user2").is_err());
+
+        db.close().unwrap();
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}.lock", path));
+        let _ = fs::remove_file(format!("{}-wal", path));
+    }
+
     #[test]
     fn test_distinct_simple() {
         let path = "/tmp/test_distinct_simple.db";
@@ -2035,6 +4224,80 @@ mod tests {
         let _ = fs::remove_file(format!("{}-wal", path));
     }
 
+    #[test]
+    fn test_distinct_with_query() {
+        let path = "/tmp/test_distinct_with_query.db";
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}.lock", path));
+        let _ = fs::remove_file(format!("{}-wal", path));
+
+        let db = Arc::new(Database::open(path).unwrap());
+        let coll = Collection::new(db.clone(), "users".to_string());
+
+        coll.insert(json!({"city": "NYC", "country": "US", "tags": ["a", "b"]})).unwrap();
+        coll.insert(json!({"city": "LA", "country": "US", "tags": ["b", "c"]})).unwrap();
+        coll.insert(json!({"city": "NYC", "country": "US"})).unwrap();
+        coll.insert(json!({"city": "London", "country": "UK", "tags": ["d"]})).unwrap();
+
+        let mut cities: Vec<String> = coll.distinct_with_query("city", "country is 'US'").unwrap()
+            .iter()
+            .map(|v| v.as_str().unwrap().to_string())
+            .collect();
+        cities.sort();
+        assert_eq!(cities, vec!["LA", "NYC"]);
+
+        let tags = coll.distinct_with_query("tags", "city is 'LA' or city is 'London'").unwrap();
+        assert_eq!(tags, vec![json!("b"), json!("c"), json!("d")]);
+
+        assert!(coll.distinct_with_query("city", "country is 'FR'").unwrap().is_empty());
+        assert!(coll.distinct_with_query("city", "country is").is_err());
+
+        let missing = Collection::new(db.clone(), "missing".to_string());
+        assert!(missing.distinct_with_query("city", "country is 'US'").unwrap().is_empty());
+
+        db.close().unwrap();
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}.lock", path));
+        let _ = fs::remove_file(format!("{}-wal", path));
+    }
+
+    #[test]
+    fn test_distinct_from_index() {
+        let path = "/tmp/test_distinct_from_index.db";
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}.lock", path));
+        let _ = fs::remove_file(format!("{}-wal", path));
+
+        let db = Arc::new(Database::open(path).unwrap());
+        let coll = Collection::new(db.clone(), "users".to_string());
+
+        coll.insert(json!({"city": "SF", "tags": ["x", "y"]})).unwrap();
+        coll.insert(json!({"city": "LA", "tags": ["y"]})).unwrap();
+        coll.insert(json!({"name": "no city"})).unwrap();
+        let id = coll.insert(json!({"city": "NYC"})).unwrap();
+        coll.insert(json!({"city": "LA"})).unwrap();
+        coll.update_by_id(&id, json!({"city": "Boston"})).unwrap();
+
+        db.create_index("users", "city_idx", "city", false).unwrap();
+        db.create_index("users", "tags_idx", "tags", false).unwrap();
+
+        // Values come back in index order, with stale entries ignored
+        let cities = coll.distinct("city").unwrap();
+        assert_eq!(cities, vec![Value::Null, json!("Boston"), json!("LA"), json!("SF")]);
+
+        let mut tags: Vec<String> = coll.distinct("tags").unwrap()
+            .iter()
+            .filter_map(|v| v.as_str().map(str::to_string))
+            .collect();
+        tags.sort();
+        assert_eq!(tags, vec!["x", "y"]);
+
+        db.close().unwrap();
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}.lock", path));
+        let _ = fs::remove_file(format!("{}-wal", path));
+    }
+
     #[test]
     fn test_bulk_write_inserts() {
         let path = "/tmp/test_bulk_write_inserts.db";
@@ -2738,7 +5001,9 @@ mod tests {
         assert!(result.is_err());
 
         let err = result.unwrap_err();
-        assert!(matches!(err, Error::Other(_)));
+        assert!(matches!(err, Error::DeserializationError { .. }));
+        assert!(err.to_string().contains("User"), "error should name the target type: {}", err);
+        assert!(err.to_string().contains("'users'"));
 
         db.close().unwrap();
         let _ = fs::remove_file(path);
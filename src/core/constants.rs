@@ -1,8 +1,35 @@
 
+/// Page size of databases created without `DatabaseOptions::page_size`.
+/// Existing databases use the size recorded in their header, so code that
+/// lays out pages reads `Pager::page_size()` instead of this constant.
 pub const PAGE_SIZE: usize = 4096;
 
+/// Smallest supported page size. B-tree nodes split by key count rather than
+/// by bytes, so a full node must still fit in one page.
+pub const MIN_PAGE_SIZE: usize = 4096;
+
+/// Largest supported page size
+pub const MAX_PAGE_SIZE: usize = 65536;
+
+/// File format version of databases without page checksums
 pub const VERSION: u32 = 1;
 
+/// File format version of databases with page checksums. Builds from before
+/// checksums only open `VERSION` files, so they refuse these instead of
+/// reading the checksums as page data.
+pub const VERSION_PAGE_CHECKSUMS: u32 = 2;
+
+/// Header flag set on databases whose pages start with a checksum
+pub const HEADER_FLAG_PAGE_CHECKSUMS: u32 = 1;
+
+/// Every `HEADER_FLAG_*` bit this build understands. Opening a file with any
+/// other bit set fails, since it may change how pages are laid out.
+pub const KNOWN_HEADER_FLAGS: u32 = HEADER_FLAG_PAGE_CHECKSUMS;
+
+/// Bytes at the start of every page holding its CRC32C, in databases with
+/// `HEADER_FLAG_PAGE_CHECKSUMS`. `Pager::page_size()` excludes them.
+pub const PAGE_CHECKSUM_SIZE: usize = 4;
+
 pub const MAGIC: [u8; 4] = *b"DEVI";
 
 pub const WAL_MAGIC: [u8; 4] = *b"WLOG";
@@ -15,6 +42,7 @@ pub const MIN_KEYS: usize = BTREE_ORDER / 2;
 
 pub const WAL_HEADER_SIZE: usize = 32;
 
+// Frame size for the default PAGE_SIZE
 pub const WAL_FRAME_SIZE: usize = PAGE_SIZE + 28;
 
 pub const DOC_ID_LEN_SIZE: usize = 2;
@@ -25,12 +53,15 @@ pub const XMAX_SIZE: usize = 8;
 
 pub const FIRST_PAGE_META: usize = DOC_ID_LEN_SIZE + DATA_LEN_SIZE + OVERFLOW_SIZE;
 pub const VERSIONED_FIRST_PAGE_META: usize = XMIN_SIZE + XMAX_SIZE + DOC_ID_LEN_SIZE + DATA_LEN_SIZE + OVERFLOW_SIZE;
+// Document capacities for the default PAGE_SIZE
 pub const MAX_FIRST_PAGE_DATA: usize = PAGE_SIZE - FIRST_PAGE_META - 256;
 pub const MAX_OVERFLOW_DATA: usize = PAGE_SIZE - OVERFLOW_SIZE;
 pub const MAX_VERSIONED_FIRST_PAGE_DATA: usize = PAGE_SIZE - VERSIONED_FIRST_PAGE_META - 256;
 
 pub const MAX_OVERFLOW_CHAIN_LENGTH: usize = 250000;
 
+pub const INDEX_REBUILD_PROGRESS_INTERVAL: u64 = 1000;
+
 pub type TransactionID = u64;
 
 pub type PageNum = u64;
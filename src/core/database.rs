@@ -1,15 +1,34 @@
 
 use crate::core::constants::*;
 use crate::core::errors::*;
-use crate::core::metadata::Metadata;
-use crate::core::metrics::{Metrics, MetricsSnapshot};
-use crate::core::mvcc::TransactionManager;
+use crate::core::metadata::{self, Metadata};
+use crate::core::metrics::{CollectionMetricsSnapshot, Metrics, MetricsSnapshot};
+use crate::core::mvcc::{TransactionManager, VersionChain};
 use crate::core::pager::Pager;
+use crate::core::recycle_bin;
+use crate::core::replication;
 use crate::core::transaction::Transaction;
 use crate::core::wal::WAL;
 use crate::core::btree::BTree;
-use crate::core::watch::{WatcherStorage, new_watcher_storage};
+use crate::core::metadata::{CollectionMeta, IndexMeta};
+use crate::core::text_search::TextIndexOptions;
+use crate::core::collation::Collation;
+use crate::core::watch::{ChangeOperation, WatcherStorage, new_watcher_storage};
 use crate::core::buffer_pool::BufferPool;
+use crate::core::id_filter::IdFilter;
+use crate::core::id_strategy::random_u64;
+use crate::core::integrity::{self, IntegrityReport};
+use crate::core::repair::{self, RecoveredTree, RepairReport};
+use crate::core::space::{self, SpaceStats};
+use crate::core::warm::{self, CacheWarmup};
+use crate::core::snapshot::Snapshot;
+use crate::core::slow_query::{SlowQuery, SlowQueryLog};
+use crate::core::events::{DbEvent, EventHook, EventHooks};
+use crate::core::query_cache::QueryCache;
+use crate::core::durability::{self, Durability, SyncPolicy};
+use crate::core::query_builder::SortOrder;
+use crate::core::query::parser::Node;
+use crate::core::binary::decode_document;
 use fs2::FileExt;
 use std::collections::{HashMap, HashSet};
 use std::fs::{File, OpenOptions};
@@ -24,8 +43,31 @@ use std::os::unix::fs::OpenOptionsExt;
 pub struct DatabaseOptions {
     pub cache_size: usize,
     pub auto_checkpoint_threshold: u64,
+    /// Cap on the WAL size in bytes. A commit that finds the WAL at or over
+    /// the cap first runs a checkpoint on its own thread and waits for it, so
+    /// the WAL never grows much past the cap (by at most one commit's pages).
+    ///
+    /// This bounds disk use at the cost of latency: the commit that hits the
+    /// cap, and any commit that arrives while the checkpoint copies pages into
+    /// the database file, stalls for the whole checkpoint. The frame-count
+    /// `auto_checkpoint_threshold` instead checkpoints in the background and
+    /// never holds up a commit, but lets the WAL keep growing while a burst of
+    /// writes outpaces it. Setting both keeps checkpoints in the background
+    /// normally, with the cap as a backstop. 0 means no cap.
+    /// Default: 0
+    pub max_wal_bytes: u64,
     pub file_permissions: u32,
+    /// Open without writing. A database file has either one writer or any
+    /// number of read-only openers at a time, across all processes; this is
+    /// enforced with an advisory lock on `<path>.lock`, taken exclusively by
+    /// a writer and shared by readers.
+    /// Default: false
     pub read_only: bool,
+    /// How long `open` waits for another process to release an incompatible
+    /// lock on the database before failing with `Error::DatabaseLocked`.
+    /// 0 fails straight away.
+    /// Default: 0
+    pub lock_timeout_ms: u64,
     /// Maximum number of documents in bulk operations (insert_many, bulk_write)
     /// Default: 100,000
     pub max_bulk_operations: usize,
@@ -35,6 +77,88 @@ pub struct DatabaseOptions {
     /// Maximum HTTP request body size for web server in bytes
     /// Default: 50MB (52,428,800 bytes)
     pub max_request_body_size: usize,
+    /// Stamp documents with `_rev`, `_created_at` and `_updated_at` on insert
+    /// and update. Timestamps are milliseconds since the Unix epoch.
+    /// Default: false
+    pub document_metadata: bool,
+    /// Number of threads that read and filter documents during a full
+    /// collection scan (`find`, `count`, query builders and aggregations).
+    /// Results come back in the same order as a serial scan. 0 or 1 scans on
+    /// the calling thread.
+    /// Default: 1
+    pub scan_threads: usize,
+    /// Most documents a collection scan behind `find`, `find_all`, query
+    /// builders and aggregations may examine. A query that would read more
+    /// fails with `Error::QueryTooExpensive` rather than loading the whole
+    /// collection into memory. 0 means no limit.
+    /// Default: 0
+    pub max_scan_documents: usize,
+    /// Most documents those queries may collect before they are returned,
+    /// counted before `skip` and `limit` apply; more fails with
+    /// `Error::QueryTooExpensive`. 0 means no limit.
+    /// Default: 0
+    pub max_result_documents: usize,
+    /// Keep a bloom filter over each collection's `_id`s so `find_by_id` can
+    /// answer for missing IDs without walking the B-tree. Filters live in
+    /// memory and are built from the B-tree keys on open and after `vacuum`.
+    /// Default: false
+    pub enable_id_bloom_filter: bool,
+    /// Page size in bytes for a new database file: a power of two from 4096
+    /// to 65536. Larger pages hold bigger documents without overflow pages
+    /// and leave more room for collection metadata. The size is stored in the
+    /// file header and can't change later; opening an existing database with
+    /// a different non-zero value fails with `Error::PageSizeMismatch`.
+    /// 0 uses the existing file's page size, or 4096 for a new file.
+    /// Default: 0
+    pub page_size: usize,
+    /// Report every schema violation when an insert or update fails
+    /// validation, rather than stopping at the first. The `SchemaValidation`
+    /// error then lists all of them; use `Schema::validate_all` to get them
+    /// individually.
+    /// Default: false
+    pub collect_all_validation_errors: bool,
+    /// Give every page of a new database file a CRC32C checksum, verified
+    /// whenever the page is read from disk; a mismatch fails the read with
+    /// `Error::PageChecksumMismatch`. Costs 4 bytes per page. Like
+    /// `page_size` this is recorded in the file header, so existing files
+    /// keep whatever they were created with. Files with checksums use format
+    /// version 2, which builds from before checksums refuse to open.
+    /// Default: true
+    pub page_checksums: bool,
+    /// Record `find`, `aggregate`, `update` and `delete` calls, and query
+    /// builders, that take at least this long, see [`Database::slow_queries`].
+    /// 0 turns the log off.
+    /// Default: 0
+    pub slow_query_threshold_ms: u64,
+    /// How many slow queries to keep; the oldest are dropped first
+    /// Default: 100
+    pub slow_query_log_size: usize,
+    /// When commits fsync, trading what survives a power loss or OS crash
+    /// for write throughput. See [`Durability`] for what each level keeps.
+    /// Default: `Durability::Full`
+    pub durability: Durability,
+    /// How query comparisons (`is`, `is not`, `<`, `<=`, `>`, `>=`) treat a
+    /// number on one side and a string on the other. Strict, they never
+    /// match, so `age > 18` skips a document whose `age` is `"21"`. Lax, a
+    /// string that parses as a number, ignoring surrounding whitespace, is
+    /// compared as that number.
+    ///
+    /// In both modes strings compare with strings as text, booleans, null,
+    /// objects and non-numeric strings only match values of their own type,
+    /// and a field holding an array matches when the array or any of its
+    /// elements does. `is not` matches exactly the documents `is` doesn't.
+    /// Applies to finds, query builders, counts, updates, deletes and
+    /// aggregation match stages; change stream filters are always strict.
+    /// Default: true
+    pub strict_query_types: bool,
+    /// How many `find` and aggregation results to keep in memory, keyed by
+    /// collection and query text, so repeating a query skips the scan. The
+    /// least recently used results are dropped first, and every commit that
+    /// writes to a collection drops all of that collection's results, so a
+    /// cached result is never older than the last write. Hits and misses
+    /// show up in [`Database::metrics`]. 0 turns the cache off.
+    /// Default: 0
+    pub query_cache_size: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -42,6 +166,19 @@ pub struct TransactionConfig {
     pub max_retries: usize,
     pub retry_backoff_base_ms: u64,
     pub max_retry_backoff_ms: u64,
+    /// Shortest wait between retries; raises the doubled backoff when it is
+    /// smaller, and is the low end of the range `jitter` picks from.
+    /// Default: 0
+    pub min_retry_backoff_ms: u64,
+    /// Wait a random time between `min_retry_backoff_ms` and the doubled
+    /// backoff instead of the backoff itself, so writers that conflicted with
+    /// each other don't all retry at the same moment and conflict again.
+    /// Default: false
+    pub jitter: bool,
+    /// Transactions open longer than this fail with `Error::TxTimeout` and stop
+    /// holding back garbage collection. 0 means no limit.
+    /// Default: 0
+    pub max_transaction_duration_ms: u64,
 }
 
 impl Default for TransactionConfig {
@@ -50,7 +187,35 @@ impl Default for TransactionConfig {
             max_retries: 3,
             retry_backoff_base_ms: 1,
             max_retry_backoff_ms: 100,
+            min_retry_backoff_ms: 0,
+            jitter: false,
+            max_transaction_duration_ms: 0,
+        }
+    }
+}
+
+impl TransactionConfig {
+    /// How long to wait after the `attempt`th conflict (counting from 0)
+    /// before trying again
+    pub fn retry_backoff(&self, attempt: usize) -> std::time::Duration {
+        let factor = u32::try_from(attempt).ok()
+            .and_then(|attempt| 1u64.checked_shl(attempt))
+            .unwrap_or(u64::MAX);
+        let max_ms = self.retry_backoff_base_ms
+            .saturating_mul(factor)
+            .min(self.max_retry_backoff_ms)
+            .max(self.min_retry_backoff_ms);
+
+        if !self.jitter {
+            return std::time::Duration::from_millis(max_ms);
         }
+
+        // Microsecond resolution, since a 1 ms base leaves little room to spread
+        let min_us = self.min_retry_backoff_ms.saturating_mul(1000);
+        let max_us = max_ms.saturating_mul(1000);
+        let span = max_us - min_us;
+        let offset = if span == u64::MAX { random_u64() } else { random_u64() % (span + 1) };
+        std::time::Duration::from_micros(min_us + offset)
     }
 }
 
@@ -76,6 +241,12 @@ pub struct CollectionInfo {
     pub document_count: usize,
     pub btree_root: u64,
     pub indexes: Vec<IndexInfo>,
+    /// Operation counters since the database was opened
+    pub metrics: CollectionMetricsSnapshot,
+    /// Encoded size in bytes of the current version of every document
+    pub data_size_bytes: u64,
+    /// `data_size_bytes` over `document_count`, 0 for an empty collection
+    pub avg_document_size: f64,
 }
 
 /// Information about an index
@@ -84,7 +255,67 @@ pub struct IndexInfo {
     pub name: String,
     pub fields: Vec<String>,
     pub unique: bool,
+    /// Whether the index returns entries in descending order
+    pub descending: bool,
+    /// Whether documents missing an indexed field are left out of the index
+    pub sparse: bool,
+    /// Whether this is a geo index over a latitude and a longitude field
+    pub geo: bool,
+    /// Whether this is an expression index, whose only field is the expression
+    pub expression: bool,
+    /// Collation the indexed strings are compared under
+    pub collation: Collation,
     pub btree_root: u64,
+    /// Tokenization options, set only for text indexes
+    pub text_options: Option<TextIndexOptions>,
+    /// Entries in the index's B-tree: one per indexed document, or one per
+    /// term for a text index. Counted by `collection_stats` and `info` by
+    /// walking the index; 0 from `list_indexes`.
+    pub entry_count: u64,
+    /// Bytes of the pages holding the index, text posting lists included.
+    /// Measured like `entry_count`.
+    pub size_bytes: u64,
+}
+
+/// How much queries have used an index, from [`Database::index_usage`]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct IndexUsage {
+    pub name: String,
+    pub fields: Vec<String>,
+    pub unique: bool,
+    /// Whether this is a text index, used by `search`
+    pub text: bool,
+    /// Queries that read the index since the database was opened
+    pub uses: u64,
+    /// When a query last read the index, in milliseconds since the Unix
+    /// epoch, or `None` if none has since the database was opened
+    pub last_used_millis: Option<u64>,
+}
+
+/// Options for [`Database::create_index_with_options`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IndexOptions {
+    /// Reject documents whose combination of field values already exists
+    pub unique: bool,
+    /// Return entries in descending order, so queries sorting the indexed
+    /// fields in descending order can read them straight from the index
+    pub descending: bool,
+    /// Leave out documents where any indexed field is absent or `null`.
+    ///
+    /// Combined with `unique`, such documents never conflict with each other,
+    /// while every document that has all the fields must still be unique. A
+    /// regular index stores absent and `null` fields alike as `null`, so
+    /// unique indexes without `sparse` allow only one such document. Queries
+    /// never pick a sparse index on their own, since it doesn't hold every
+    /// document.
+    pub sparse: bool,
+    /// Collation the indexed strings are compared under. With
+    /// [`Collation::CaseInsensitive`], a unique index rejects values that
+    /// differ only in case. Queries only read their sort order from an index
+    /// with the collation set by [`QueryBuilder::collation`](crate::QueryBuilder::collation),
+    /// and an index with any collation but [`Collation::Binary`] never covers
+    /// a query, since it stores folded strings.
+    pub collation: Collation,
 }
 
 /// Overall database information
@@ -97,6 +328,8 @@ pub struct DatabaseInfo {
     pub collections: Vec<CollectionInfo>,
     pub total_documents: usize,
     pub read_only: bool,
+    /// Page usage, see [`Database::space_stats`]
+    pub space: SpaceStats,
 }
 
 impl Default for DatabaseOptions {
@@ -104,11 +337,26 @@ impl Default for DatabaseOptions {
         Self {
             cache_size: 25_000,  // 25K pages = ~100MB cache (sized for large bulk operations)
             auto_checkpoint_threshold: 1000,
+            max_wal_bytes: 0,
             file_permissions: 0o644,
             read_only: false,
+            lock_timeout_ms: 0,
             max_bulk_operations: 100_000,           // 100K documents
             max_document_size: 67_108_864,          // 64MB
             max_request_body_size: 52_428_800,      // 50MB
+            document_metadata: false,
+            scan_threads: 1,
+            max_scan_documents: 0,
+            max_result_documents: 0,
+            enable_id_bloom_filter: false,
+            page_size: 0,
+            collect_all_validation_errors: false,
+            page_checksums: true,
+            slow_query_threshold_ms: 0,
+            slow_query_log_size: 100,
+            durability: Durability::Full,
+            strict_query_types: true,
+            query_cache_size: 0,
         }
     }
 }
@@ -142,6 +390,9 @@ pub(crate) struct PendingWrite {
     pub modified_collections: HashSet<String>,
     pub doc_existed_in_snapshot: HashMap<String, HashMap<String, bool>>,
     pub doc_original_xmin: HashMap<String, HashMap<String, TransactionID>>,
+    pub doc_before_images: crate::core::transaction::DocBeforeImages,
+    pub count_deltas: HashMap<String, i64>,
+    pub index_writes: crate::core::index_writes::IndexWrites,
 
     pub _tx_id: u64,
     pub snapshot_id: TransactionID,
@@ -160,7 +411,7 @@ pub struct Database {
     path: String,
     read_only: bool,
     commit_mu: Arc<Mutex<()>>,
-    pub(crate) version_chains: Arc<RwLock<HashMap<String, HashMap<String, crate::core::mvcc::VersionChain>>>>,
+    pub(crate) version_chains: Arc<RwLock<HashMap<String, HashMap<String, VersionChain>>>>,
     tx_config: Arc<RwLock<TransactionConfig>>,
     auto_checkpoint_threshold: Arc<RwLock<u64>>,
     checkpoint_in_progress: Arc<(Mutex<bool>, Condvar)>,
@@ -177,6 +428,19 @@ pub struct Database {
     max_bulk_operations: usize,
     max_document_size: usize,
     max_request_body_size: usize,
+    document_metadata: bool,
+    scan_threads: usize,
+    max_scan_documents: usize,
+    max_result_documents: usize,
+    max_wal_bytes: u64,
+    collect_all_validation_errors: bool,
+    strict_query_types: bool,
+    /// Per-collection `_id` bloom filters, `None` unless enabled in the options
+    id_filters: Option<Arc<RwLock<HashMap<String, IdFilter>>>>,
+    slow_queries: Arc<SlowQueryLog>,
+    sync_policy: Arc<SyncPolicy>,
+    events: Arc<EventHooks>,
+    query_cache: Arc<QueryCache>,
 }
 
 impl Clone for Database {
@@ -204,6 +468,18 @@ impl Clone for Database {
             max_bulk_operations: self.max_bulk_operations,
             max_document_size: self.max_document_size,
             max_request_body_size: self.max_request_body_size,
+            document_metadata: self.document_metadata,
+            scan_threads: self.scan_threads,
+            max_scan_documents: self.max_scan_documents,
+            max_result_documents: self.max_result_documents,
+            collect_all_validation_errors: self.collect_all_validation_errors,
+            strict_query_types: self.strict_query_types,
+            max_wal_bytes: self.max_wal_bytes,
+            id_filters: self.id_filters.clone(),
+            slow_queries: self.slow_queries.clone(),
+            sync_policy: self.sync_policy.clone(),
+            events: self.events.clone(),
+            query_cache: self.query_cache.clone(),
         }
     }
 }
@@ -266,66 +542,21 @@ impl Database {
             .create(true)
             .open(&lock_path)?;
 
-        if opts.read_only {
-            fs2::FileExt::try_lock_shared(&lock_file)?;
-        } else {
-            fs2::FileExt::try_lock_exclusive(&lock_file)?;
-        }
+        lock_database_file(&lock_file, opts.read_only, opts.lock_timeout_ms)?;
 
-        let pager = Arc::new(Pager::open(
+        let pager = Arc::new(Pager::open_with_page_size(
             path,
             opts.cache_size,
             opts.file_permissions,
             opts.read_only,
+            (opts.page_size != 0).then_some(opts.page_size),
+            opts.page_checksums,
         )?);
 
-        let wal = Arc::new(WAL::open(path, opts.file_permissions)?);
-
-        let frame_count = wal.frame_count();
-        if !opts.read_only && frame_count > 0 {
-            let frames = wal.read_all_frames()?;
-
-            let mut latest_meta_page: Option<u64> = None;
-            let mut latest_num_pages: Option<u64> = None;
-            for frame in &frames {
-                if frame.page_num == 0 && frame.page_data.len() >= 32 {
-                    let meta_page_bytes: [u8; 8] = frame.page_data[24..32].try_into()
-                        .map_err(|_| Error::DataCorruption {
-                            details: "invalid metadata page in WAL frame".to_string()
-                        })?;
-                    let meta_page = u64::from_le_bytes(meta_page_bytes);
-                    if meta_page > 0 {
-                        latest_meta_page = Some(meta_page);
-                    }
-
-                    let num_pages_bytes: [u8; 8] = frame.page_data[12..20].try_into()
-                        .map_err(|_| Error::DataCorruption {
-                            details: "invalid num_pages in WAL frame".to_string()
-                        })?;
-                    let num_pages = u64::from_le_bytes(num_pages_bytes);
-                    latest_num_pages = Some(num_pages);
-                }
-            }
-
-            let mut max_page = 0u64;
-            for frame in &frames {
-                if frame.page_num > max_page {
-                    max_page = frame.page_num;
-                }
-            }
-
-            if let Some(num_pages) = latest_num_pages {
-                pager.set_num_pages(num_pages)?;
-            } else if max_page >= pager.num_pages()? {
-                pager.set_num_pages(max_page + 1)?;
-            }
-
-            wal.checkpoint(&pager)?;
+        let wal = Arc::new(WAL::open_with_page_size(path, opts.file_permissions, pager.page_size())?);
 
-            if let Some(meta_page) = latest_meta_page {
-                pager.set_metadata_page(meta_page)?;
-                pager.write_header()?;
-            }
+        if !opts.read_only {
+            replay_wal(&pager, &wal)?;
         }
 
         let tx_manager = Arc::new(TransactionManager::new());
@@ -335,8 +566,8 @@ impl Database {
 
         let metadata_page_num = pager.metadata_page()?;
         let metadata = if metadata_page_num > 0 {
-            let meta_data = pager.read_page(metadata_page_num)?;
-            let meta = Metadata::deserialize(&meta_data)?;
+            let mut meta = Metadata::read(&pager, metadata_page_num)?;
+            rebuild_document_counts(&pager, &mut meta)?;
             meta
         } else {
             let meta = Metadata::new();
@@ -344,8 +575,8 @@ impl Database {
             let meta_page = pager.alloc_page()?;
             let mut meta_data = meta.serialize()?;
 
-            if meta_data.len() < PAGE_SIZE {
-                meta_data.resize(PAGE_SIZE, 0);
+            if meta_data.len() < pager.page_size() {
+                meta_data.resize(pager.page_size(), 0);
             }
 
             pager.write_page_transfer(meta_page, meta_data)?;
@@ -356,12 +587,27 @@ impl Database {
             meta
         };
 
+        let id_filters = if opts.enable_id_bloom_filter {
+            Some(Arc::new(RwLock::new(build_id_filters(&pager, &metadata)?)))
+        } else {
+            None
+        };
+
         let metrics = Arc::new(Metrics::new());
 
         // Set metrics on pager and WAL for instrumentation
         pager.set_metrics(metrics.clone());
         wal.set_metrics(metrics.clone());
 
+        let events = Arc::new(EventHooks::default());
+        pager.set_event_hooks(events.clone());
+        wal.set_event_hooks(events.clone());
+
+        let sync_policy = Arc::new(SyncPolicy::new(opts.durability));
+        if !opts.read_only {
+            durability::spawn_batched_syncer(&sync_policy, &wal, &pager);
+        }
+
         Ok(Database {
             pager,
             wal,
@@ -388,10 +634,88 @@ impl Database {
             max_bulk_operations: opts.max_bulk_operations,
             max_document_size: opts.max_document_size,
             max_request_body_size: opts.max_request_body_size,
+            document_metadata: opts.document_metadata,
+            scan_threads: opts.scan_threads,
+            max_scan_documents: opts.max_scan_documents,
+            max_result_documents: opts.max_result_documents,
+            collect_all_validation_errors: opts.collect_all_validation_errors,
+            strict_query_types: opts.strict_query_types,
+            max_wal_bytes: opts.max_wal_bytes,
+            id_filters,
+            slow_queries: Arc::new(SlowQueryLog::new(opts.slow_query_threshold_ms, opts.slow_query_log_size)),
+            sync_policy,
+            events,
+            query_cache: Arc::new(QueryCache::new(opts.query_cache_size)),
         })
     }
 
+    /// Begin a read-write transaction
+    ///
+    /// Writes made through any number of collections in the transaction
+    /// commit together: every collection's B-tree root, index changes and
+    /// document count reach the WAL under a single commit, and a rollback, a
+    /// failed commit or a crash before commit leaves none of them applied.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # use serde_json::json;
+    /// # let db = Database::open("my.db").unwrap();
+    /// let mut tx = db.begin().unwrap();
+    /// tx.collection("accounts").unwrap()
+    ///     .update_by_id("alice", json!({"$inc": {"balance": -25}})).unwrap();
+    /// tx.collection("ledger").unwrap()
+    ///     .insert(json!({"from": "alice", "amount": 25})).unwrap();
+    /// tx.commit().unwrap();
+    /// ```
     pub fn begin(&self) -> Result<Transaction> {
+        self.start_transaction(false)
+    }
+
+    /// Begin a transaction that can only read
+    ///
+    /// Inserts, updates, deletes and collection changes fail with
+    /// `Error::ReadOnlyTransaction`. The transaction takes no part in conflict
+    /// detection, and read-only transactions begun between the same two commits
+    /// share one snapshot.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # let db = Database::open("my.db").unwrap();
+    /// let mut tx = db.begin_read_only().unwrap();
+    /// let users = tx.collection("users").unwrap().find_all().unwrap();
+    /// tx.commit().unwrap();
+    /// ```
+    pub fn begin_read_only(&self) -> Result<Transaction> {
+        self.start_transaction(true)
+    }
+
+    /// Freeze a read-only view of the database as of the latest commit
+    ///
+    /// Collections taken from the snapshot all read from that one point in
+    /// time, unlike `Database::collection`, where each call reads the latest
+    /// commit. Dropping the snapshot and its collections releases it.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # let db = Database::open("my.db").unwrap();
+    /// let snapshot = db.snapshot().unwrap();
+    /// let users = snapshot.collection("users").count().unwrap();
+    /// let orders = snapshot.collection("orders").count().unwrap();
+    /// ```
+    pub fn snapshot(&self) -> Result<Snapshot> {
+        // Holding commit_mu stops commits changing the metadata between
+        // pinning the snapshot and copying it
+        let _commit_guard = self.commit_mu.lock()
+            .map_err(|_| Error::LockPoisoned { lock_name: "database.commit_mu".to_string() })?;
+        let tx = self.begin_read_only()?;
+        let metadata = self.get_metadata();
+        Ok(Snapshot::new(self.clone(), tx, metadata))
+    }
+
+    fn start_transaction(&self, read_only: bool) -> Result<Transaction> {
         let metadata = self.metadata.read()
             .map_err(|_| Error::LockPoisoned { lock_name: "database.metadata".to_string() })?;
         let mut collection_roots = HashMap::new();
@@ -401,7 +725,8 @@ impl Database {
         }
         drop(metadata);
 
-        let mut tx = Transaction::new(
+        let start = if read_only { Transaction::new_read_only } else { Transaction::new };
+        let mut tx = start(
             self.pager.clone(),
             self.wal.clone(),
             self.tx_manager.clone(),
@@ -410,8 +735,17 @@ impl Database {
             Some(self.tx_id_counter.clone()),
         )?;
 
+        let max_duration_ms = self.tx_config.read()
+            .map_err(|_| Error::LockPoisoned { lock_name: "database.tx_config".to_string() })?
+            .max_transaction_duration_ms;
+        if max_duration_ms > 0 {
+            tx.set_max_duration(std::time::Duration::from_millis(max_duration_ms))?;
+        }
+
         // Track transaction metrics
         self.metrics.transaction_begun();
+        let tx_id = tx.tx_id;
+        self.events.emit(|| DbEvent::TransactionBegin { tx_id, read_only });
 
         let db_ref = Arc::new(Self {
             pager: self.pager.clone(),
@@ -436,6 +770,18 @@ impl Database {
             max_bulk_operations: self.max_bulk_operations,
             max_document_size: self.max_document_size,
             max_request_body_size: self.max_request_body_size,
+            document_metadata: self.document_metadata,
+            scan_threads: self.scan_threads,
+            max_scan_documents: self.max_scan_documents,
+            max_result_documents: self.max_result_documents,
+            collect_all_validation_errors: self.collect_all_validation_errors,
+            strict_query_types: self.strict_query_types,
+            max_wal_bytes: self.max_wal_bytes,
+            id_filters: self.id_filters.clone(),
+            slow_queries: self.slow_queries.clone(),
+            sync_policy: self.sync_policy.clone(),
+            events: self.events.clone(),
+            query_cache: self.query_cache.clone(),
         });
         tx.set_database(db_ref);
 
@@ -464,29 +810,61 @@ impl Database {
             return Err(Error::Other("database is read-only".to_string()));
         }
 
-        let mut metadata = self.metadata.write()
-            .map_err(|_| Error::LockPoisoned { lock_name: "database.metadata".to_string() })?;
-        f(&mut metadata);
+        let _commit_guard = self.commit_mu.lock()
+            .map_err(|_| Error::LockPoisoned { lock_name: "database.commit_mu".to_string() })?;
+        self.update_metadata_locked(f)
+    }
 
-        let mut meta_data = metadata.serialize()?;
+    /// `update_metadata` for callers already holding `commit_mu`
+    ///
+    /// The metadata page is logged to the WAL like a commit's, so checkpoints
+    /// and recovery replay this version instead of the one the last commit
+    /// logged.
+    pub(crate) fn update_metadata_locked<F>(&self, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut Metadata),
+    {
+        self.update_metadata_no_flush(f);
 
-        if meta_data.len() < PAGE_SIZE {
-            meta_data.resize(PAGE_SIZE, 0);
-        }
+        self.log_metadata(self.pager.get_current_transaction_id()?)?;
+        self.wal.sync()?;
+        self.pager.write_header_no_sync()?;
+        self.pager.flush()?;
+        Ok(())
+    }
 
-        let meta_page = self.pager.metadata_page()?;
+    /// Write the metadata pages, then a header frame pointing at the first, to
+    /// the WAL and the page cache. The pages of the previous version are
+    /// reused, and those it no longer needs freed. Called under `commit_mu`;
+    /// the caller syncs the WAL.
+    pub(crate) fn log_metadata(&self, tx_id: TransactionID) -> Result<()> {
+        let pages = self.get_metadata().serialize_pages(self.pager.page_size())?;
 
-        if meta_page > 0 {
-            self.pager.write_page_transfer(meta_page, meta_data)?;
-        } else {
-            let new_meta_page = self.pager.alloc_page()?;
-            self.pager.write_page_transfer(new_meta_page, meta_data)?;
-            self.pager.set_metadata_page(new_meta_page)?;
-            self.pager.write_header()?;
+        let mut chain = metadata::page_chain(&self.pager, self.pager.metadata_page()?)?;
+        while chain.len() < pages.len() {
+            chain.push(self.pager.alloc_page()?);
+        }
+        for page in chain.split_off(pages.len()) {
+            self.pager.free_page(page)?;
         }
 
-        self.pager.flush()?;
-        Ok(())
+        for (i, mut meta_data) in pages.into_iter().enumerate() {
+            if let Some(&next) = chain.get(i + 1) {
+                Metadata::link_page(&mut meta_data, next);
+            }
+            self.wal.write_frame(tx_id, chain[i], meta_data.clone())?;
+            self.pager.write_page_transfer(chain[i], meta_data)?;
+        }
+        let meta_page = chain[0];
+
+        let mut header_data = self.pager.get_header_data()?;
+        header_data[24..32].copy_from_slice(&meta_page.to_le_bytes());
+        // Recovery only reads the leading header fields, so the frame is
+        // cut to the size of a data page when pages carry checksums
+        header_data.truncate(self.pager.page_size());
+        self.wal.write_frame(tx_id, 0, header_data)?;
+
+        self.pager.set_metadata_page(meta_page)
     }
 
     pub(crate) fn update_metadata_no_flush<F>(&self, f: F)
@@ -510,6 +888,101 @@ impl Database {
         self.wal.checkpoint(&self.pager)
     }
 
+    /// Make every committed transaction durable in the database file without
+    /// checkpointing
+    ///
+    /// Fsyncs the WAL, then writes the page cache's dirty pages to the
+    /// database file and fsyncs it. Unlike `checkpoint`, the WAL frames stay
+    /// in the WAL, so this is a cheaper durability barrier for `Normal` and
+    /// `Batched` durability. Waits for any commit in progress to finish.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use jasonisnthappy::{Database, DatabaseOptions, Durability};
+    /// # use serde_json::json;
+    /// let opts = DatabaseOptions { durability: Durability::Normal, ..Default::default() };
+    /// let db = Database::open_with_options("my.db", opts).unwrap();
+    /// db.collection("users").insert(json!({"name": "ann"})).unwrap();
+    /// assert!(!db.is_fully_synced());
+    /// db.sync().unwrap();
+    /// assert!(db.is_fully_synced());
+    /// ```
+    pub fn sync(&self) -> Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+
+        let _commit_guard = self.commit_mu.lock()
+            .map_err(|_| Error::LockPoisoned { lock_name: "database.commit_mu".to_string() })?;
+        self.sync_policy.sync(&self.wal, &self.pager)
+    }
+
+    /// Whether the database file has been fsynced since the last commit and
+    /// the page cache holds no pages waiting to be written
+    ///
+    /// Always true under `Durability::Full` once commits return. Under
+    /// `Normal` and `Batched` it turns false with the first commit that skips
+    /// the database file's fsync and true again after `sync`, `close` or a batched fsync;
+    /// checkpoints leave it unchanged.
+    pub fn is_fully_synced(&self) -> bool {
+        !self.sync_policy.has_unsynced() && !self.pager.has_dirty_pages()
+    }
+
+    /// Checkpoint the whole WAL into the database file and truncate the WAL
+    /// file to zero bytes. Waits for a running background checkpoint first.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # let db = Database::open("my.db").unwrap();
+    /// db.checkpoint_truncate().unwrap();
+    /// assert_eq!(std::fs::metadata("my.db-wal").unwrap().len(), 0);
+    /// ```
+    pub fn checkpoint_truncate(&self) -> Result<()> {
+        if self.read_only {
+            return Err(Error::Other("cannot checkpoint in read-only mode".to_string()));
+        }
+
+        self.with_checkpoint_flag(|| self.wal.checkpoint_truncate(&self.pager))
+    }
+
+    /// Called before a commit writes to the WAL: if the WAL has reached
+    /// `max_wal_bytes`, checkpoint it before letting the commit continue
+    pub(crate) fn checkpoint_if_wal_full(&self) -> Result<()> {
+        if self.max_wal_bytes == 0 || self.wal.size_bytes() < self.max_wal_bytes {
+            return Ok(());
+        }
+
+        self.with_checkpoint_flag(|| {
+            // Another commit may have checkpointed while this one waited
+            if self.wal.size_bytes() < self.max_wal_bytes {
+                return Ok(());
+            }
+            self.wal.checkpoint(&self.pager)
+        })
+    }
+
+    /// Run `f` on the calling thread once no other checkpoint is in progress,
+    /// keeping background checkpoints out until it finishes
+    fn with_checkpoint_flag<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        let (lock, cvar) = &*self.checkpoint_in_progress;
+        {
+            let guard = lock.lock()
+                .map_err(|_| Error::LockPoisoned { lock_name: "database.checkpoint_in_progress".to_string() })?;
+            let mut in_progress = cvar.wait_while(guard, |in_progress| *in_progress)
+                .map_err(|_| Error::LockPoisoned { lock_name: "database.checkpoint_in_progress".to_string() })?;
+            *in_progress = true;
+        }
+
+        let result = f();
+
+        let mut in_progress = lock.lock().recover_poison();
+        *in_progress = false;
+        cvar.notify_all();
+
+        result
+    }
+
     pub fn garbage_collect(&self) -> Result<GarbageCollectionStats> {
         let mut stats = GarbageCollectionStats {
             versions_removed: 0,
@@ -555,7 +1028,7 @@ impl Database {
             version_chains.remove(&coll_name);
         }
 
-        stats.bytes_freed = (stats.pages_freed as i64) * (PAGE_SIZE as i64);
+        stats.bytes_freed = (stats.pages_freed as i64) * (self.pager.file_page_size() as i64);
 
         Ok(stats)
     }
@@ -570,7 +1043,7 @@ impl Database {
         let _ = cvar.wait_timeout_while(guard, timeout, |in_progress| *in_progress)
             .map_err(|_| Error::LockPoisoned { lock_name: "database.checkpoint_in_progress".to_string() })?;
 
-        self.pager.flush()?;
+        self.sync_policy.sync(&self.wal, &self.pager)?;
 
         self.wal.close()?;
 
@@ -601,6 +1074,197 @@ impl Database {
         self.max_request_body_size
     }
 
+    pub fn document_metadata(&self) -> bool {
+        self.document_metadata
+    }
+
+    pub fn scan_threads(&self) -> usize {
+        self.scan_threads
+    }
+
+    /// Scan limit from `DatabaseOptions::max_scan_documents`; 0 means none
+    pub fn max_scan_documents(&self) -> usize {
+        self.max_scan_documents
+    }
+
+    /// Result limit from `DatabaseOptions::max_result_documents`; 0 means none
+    pub fn max_result_documents(&self) -> usize {
+        self.max_result_documents
+    }
+
+    /// The slowest recent queries, oldest first: those that took at least
+    /// `DatabaseOptions::slow_query_threshold_ms`, up to
+    /// `DatabaseOptions::slow_query_log_size` of them
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::core::database::{Database, DatabaseOptions};
+    /// let opts = DatabaseOptions { slow_query_threshold_ms: 50, ..Default::default() };
+    /// let db = Database::open_with_options("my.db", opts).unwrap();
+    /// db.collection("users").find("age > 30").unwrap();
+    /// for query in db.slow_queries() {
+    ///     println!("{:.1}ms {} on {}: {}", query.elapsed_ms, query.operation.as_str(), query.collection, query.query);
+    /// }
+    /// ```
+    pub fn slow_queries(&self) -> Vec<SlowQuery> {
+        self.slow_queries.entries()
+    }
+
+    /// Empty the slow query log
+    pub fn clear_slow_queries(&self) {
+        self.slow_queries.clear()
+    }
+
+    /// Threshold the slow query log records at; 0 means it is off
+    pub fn slow_query_threshold_ms(&self) -> u64 {
+        self.slow_queries.threshold_ms()
+    }
+
+    /// Change `DatabaseOptions::slow_query_threshold_ms` while the database is open
+    pub fn set_slow_query_threshold_ms(&self, threshold_ms: u64) {
+        self.slow_queries.set_threshold_ms(threshold_ms)
+    }
+
+    /// Call `callback` with every slow query as it is recorded, on the thread
+    /// that ran the query. Replaces any earlier callback.
+    pub fn on_slow_query<F>(&self, callback: F)
+    where
+        F: Fn(&SlowQuery) + Send + Sync + 'static,
+    {
+        self.slow_queries.set_callback(Some(Arc::new(callback)))
+    }
+
+    /// Stop calling the callback given to [`on_slow_query`](Self::on_slow_query)
+    pub fn clear_slow_query_callback(&self) {
+        self.slow_queries.set_callback(None)
+    }
+
+    /// Call `hook` with transaction, checkpoint, page cache and WAL events as
+    /// they happen, for debugging. Replaces any earlier hook.
+    ///
+    /// The hook runs on the thread that caused the event, sometimes with
+    /// internal locks held, so it should return quickly and must not use the
+    /// database itself. While no hook is set events aren't built at all.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::{Database, DbEvent};
+    /// let db = Database::open("my.db").unwrap();
+    /// db.set_event_hook(Box::new(|event| {
+    ///     if let DbEvent::TransactionConflict { tx_id, collection, .. } = event {
+    ///         eprintln!("tx {} conflicted on {}", tx_id, collection);
+    ///     }
+    /// }));
+    /// ```
+    pub fn set_event_hook(&self, hook: EventHook) {
+        self.events.set(Some(hook))
+    }
+
+    /// Stop calling the hook given to [`set_event_hook`](Self::set_event_hook)
+    pub fn clear_event_hook(&self) {
+        self.events.set(None)
+    }
+
+    pub(crate) fn emit_event(&self, event: impl FnOnce() -> DbEvent) {
+        self.events.emit(event)
+    }
+
+    pub(crate) fn slow_query_log(&self) -> &SlowQueryLog {
+        &self.slow_queries
+    }
+
+    pub(crate) fn query_cache(&self) -> &QueryCache {
+        &self.query_cache
+    }
+
+    /// Drop cached query results for `collection_name` after a write to it
+    pub(crate) fn invalidate_query_cache(&self, collection_name: &str) {
+        self.query_cache.invalidate(collection_name);
+    }
+
+    /// When commits fsync, as set by `DatabaseOptions::durability`
+    pub fn durability(&self) -> Durability {
+        self.sync_policy.durability()
+    }
+
+    /// Whether the commit about to write its pages should fsync them
+    pub(crate) fn commit_needs_sync(&self) -> bool {
+        self.sync_policy.commit_needs_sync()
+    }
+
+    pub fn collect_all_validation_errors(&self) -> bool {
+        self.collect_all_validation_errors
+    }
+
+    /// `DatabaseOptions::strict_query_types`
+    pub fn strict_query_types(&self) -> bool {
+        self.strict_query_types
+    }
+
+    /// `ast` with its comparisons following `DatabaseOptions::strict_query_types`
+    pub(crate) fn apply_query_types(&self, mut ast: Box<dyn Node>) -> Box<dyn Node> {
+        if !self.strict_query_types {
+            ast.coerce_numeric_strings();
+        }
+        ast
+    }
+
+    /// Size in bytes of the database's pages, fixed when the file was created
+    pub fn page_size(&self) -> usize {
+        self.pager.file_page_size()
+    }
+
+    /// Whether the database's pages carry checksums, fixed when the file was created
+    pub fn page_checksums(&self) -> bool {
+        self.pager.has_page_checksums()
+    }
+
+    /// Whether `id` may be in `collection`. Only false when the collection's
+    /// `_id` bloom filter rules it out.
+    pub(crate) fn may_contain_id(&self, collection: &str, id: &str) -> bool {
+        let filters = match &self.id_filters {
+            Some(filters) => filters.read().recover_poison(),
+            None => return true,
+        };
+        filters.get(collection).is_none_or(|filter| filter.may_contain(id))
+    }
+
+    /// Add a written document's ID to its collection's bloom filter. A filter
+    /// is only started for a collection with no committed documents, since
+    /// one started later would miss the IDs already stored.
+    pub(crate) fn record_id(&self, collection: &str, id: &str) {
+        let filters = match &self.id_filters {
+            Some(filters) => filters,
+            None => return,
+        };
+        if let Some(filter) = filters.write().recover_poison().get_mut(collection) {
+            filter.insert(id);
+            return;
+        }
+
+        let is_empty = self.metadata.read().recover_poison()
+            .collections
+            .get(collection)
+            .is_none_or(|coll_meta| coll_meta.btree_root == 0);
+        if is_empty {
+            filters.write().recover_poison()
+                .entry(collection.to_string())
+                .or_insert_with(|| IdFilter::with_capacity(0))
+                .insert(id);
+        }
+    }
+
+    /// Drop or move a collection's bloom filter along with the collection
+    pub(crate) fn rename_id_filter(&self, old_name: &str, new_name: Option<&str>) {
+        if let Some(filters) = &self.id_filters {
+            let mut filters = filters.write().recover_poison();
+            let filter = filters.remove(old_name);
+            if let (Some(new_name), Some(filter)) = (new_name, filter) {
+                filters.insert(new_name.to_string(), filter);
+            }
+        }
+    }
+
     pub fn collection(&self, name: &str) -> crate::core::collection::Collection {
         crate::core::collection::Collection::new(
             std::sync::Arc::new(Self {
@@ -626,43 +1290,177 @@ impl Database {
                 max_bulk_operations: self.max_bulk_operations,
                 max_document_size: self.max_document_size,
                 max_request_body_size: self.max_request_body_size,
+                document_metadata: self.document_metadata,
+                scan_threads: self.scan_threads,
+                max_scan_documents: self.max_scan_documents,
+                max_result_documents: self.max_result_documents,
+                collect_all_validation_errors: self.collect_all_validation_errors,
+                strict_query_types: self.strict_query_types,
+                max_wal_bytes: self.max_wal_bytes,
+                id_filters: self.id_filters.clone(),
+                slow_queries: self.slow_queries.clone(),
+                sync_policy: self.sync_policy.clone(),
+                events: self.events.clone(),
+                query_cache: self.query_cache.clone(),
             }),
             name.to_string(),
         )
     }
 
+    /// `collection` used as a key-value store, with each document's `_id` as
+    /// its key. See [`KvStore`](crate::KvStore).
+    pub fn kv(&self, collection: &str) -> crate::core::kv::KvStore {
+        crate::core::kv::KvStore::new(self.collection(collection))
+    }
+
     /// List all collections in the database
     pub fn list_collections(&self) -> Result<Vec<String>> {
         let metadata = self.metadata.read()
             .map_err(|_| Error::LockPoisoned { lock_name: "database.metadata".to_string() })?;
-        let mut names: Vec<String> = metadata.collections.keys().cloned().collect();
+        let mut names: Vec<String> = metadata.collections.keys()
+            .filter(|name| !recycle_bin::is_bin(name) && !replication::is_log(name))
+            .cloned()
+            .collect();
         names.sort();
         Ok(names)
     }
 
+    /// Whether a collection exists as of the last commit
+    ///
+    /// Reads metadata directly, so no transaction is started.
+    pub fn collection_exists(&self, name: &str) -> bool {
+        self.metadata.read()
+            .recover_poison()
+            .collections
+            .contains_key(name)
+    }
+
+    /// Whether a collection has an index (regular or text) with this name
+    pub fn index_exists(&self, collection_name: &str, index_name: &str) -> bool {
+        self.metadata.read()
+            .recover_poison()
+            .collections
+            .get(collection_name)
+            .is_some_and(|coll_meta| {
+                coll_meta.indexes.contains_key(index_name)
+                    || coll_meta.text_indexes.contains_key(index_name)
+            })
+    }
+
+    /// Rename a collection in a transaction of its own, saving the change
+    /// right away
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # let db = Database::open("my.db").unwrap();
+    /// db.rename_collection("users", "accounts").unwrap();
+    /// ```
+    pub fn rename_collection(&self, old_name: &str, new_name: &str) -> Result<()> {
+        use crate::core::validation::validate_collection_name;
+
+        validate_collection_name(new_name)?;
+        let mut tx = self.begin()?;
+        tx.rename_collection(old_name, new_name)?;
+        tx.commit()?;
+        // A rename only touches metadata, which the commit doesn't save. The
+        // WAL is checkpointed first so replaying it can't undo the rename.
+        self.wal.checkpoint(&self.pager)?;
+        self.save_metadata()
+    }
+
+    /// Create `dst` as a copy of `src`: its documents, `_id`s included, and its
+    /// indexes, schema, id strategy and default sort
+    ///
+    /// The documents and indexes are written in one transaction. If anything
+    /// fails `dst` is dropped again, so it is either a full copy or absent.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # let db = Database::open("my.db").unwrap();
+    /// db.copy_collection("users", "users_v2").unwrap();
+    /// // ...migrate users_v2, then swap it in
+    /// ```
+    pub fn copy_collection(&self, src: &str, dst: &str) -> Result<()> {
+        use crate::core::validation::validate_collection_name;
+
+        validate_collection_name(dst)?;
+        if self.read_only {
+            return Err(Error::Other("cannot copy collection: database is in read-only mode".to_string()));
+        }
+
+        let src_meta = self.get_metadata().collections.get(src).cloned()
+            .ok_or_else(|| Error::CollectionDoesNotExist { name: src.to_string() })?;
+
+        let mut tx = self.begin()?;
+        tx.create_collection(dst)?;
+        let result = self.copy_collection_into(tx, src, dst, &src_meta);
+        if result.is_err() {
+            // Dropping only touches metadata, which the commit doesn't save
+            let mut tx = self.begin()?;
+            if tx.drop_collection(dst).is_ok() && tx.commit().is_ok() && self.wal.checkpoint(&self.pager).is_ok() {
+                let _ = self.save_metadata();
+            }
+        }
+        result
+    }
+
+    /// Fill the freshly created `dst` from `src`, committing `tx`
+    fn copy_collection_into(&self, mut tx: Transaction, src: &str, dst: &str, src_meta: &CollectionMeta) -> Result<()> {
+        // Index definitions go in first, so the inserts below fill them in
+        self.update_metadata_no_flush(|m| {
+            let dst_meta = m.get_collection(dst);
+            dst_meta.indexes = src_meta.indexes.iter()
+                .map(|(name, index)| (name.clone(), IndexMeta { btree_root: 0, ..index.clone() }))
+                .collect();
+            dst_meta.schema = src_meta.schema.clone();
+            dst_meta.id_strategy = src_meta.id_strategy;
+            dst_meta.last_auto_id = src_meta.last_auto_id;
+            dst_meta.default_sort = src_meta.default_sort.clone();
+            dst_meta.soft_delete = src_meta.soft_delete;
+        });
+
+        let docs = tx.collection(src)?.find_all()?;
+        {
+            let mut dst_coll = tx.collection(dst)?;
+            for doc in docs {
+                dst_coll.insert(doc)?;
+            }
+        }
+        tx.commit()?;
+
+        // Text indexes aren't kept up by writes, so they are built from the copy
+        for text_index in src_meta.text_indexes.values() {
+            let fields: Vec<&str> = text_index.fields.iter().map(|f| f.as_str()).collect();
+            self.create_text_index(dst, &text_index.name, &fields, text_index.options.clone())?;
+        }
+
+        Ok(())
+    }
+
     /// Get detailed statistics for a specific collection
     pub fn collection_stats(&self, name: &str) -> Result<CollectionInfo> {
         let metadata = self.metadata.read()
             .map_err(|_| Error::LockPoisoned { lock_name: "database.metadata".to_string() })?;
         let coll_meta = metadata.collections.get(name)
-            .ok_or_else(|| Error::Other(format!("collection {} not found", name)))?;
+            .ok_or_else(|| Error::CollectionDoesNotExist { name: name.to_string() })?;
 
         // Count documents by iterating the btree
-        let document_count = if coll_meta.btree_root == 0 {
-            0
+        let (document_count, data_size_bytes) = if coll_meta.btree_root == 0 {
+            (0, 0)
         } else {
-            self.count_documents_in_btree(coll_meta.btree_root)?
+            self.measure_documents_in_btree(coll_meta.btree_root)?
         };
 
-        // Collect index information
-        let mut indexes = Vec::new();
-        for (idx_name, idx_meta) in &coll_meta.indexes {
-            indexes.push(IndexInfo {
-                name: idx_name.clone(),
-                fields: idx_meta.fields.clone(),
-                unique: idx_meta.unique,
-                btree_root: idx_meta.btree_root,
-            });
+        // Collect index information, with sizes from walking each index
+        let mut indexes = index_infos(coll_meta);
+        let page_size = self.pager.file_page_size() as u64;
+        for index in &mut indexes {
+            let text = index.text_options.is_some();
+            let size = crate::core::space::index_size(&self.pager, index.btree_root, text)?;
+            index.entry_count = size.entries;
+            index.size_bytes = size.pages * page_size;
         }
 
         Ok(CollectionInfo {
@@ -670,6 +1468,13 @@ impl Database {
             document_count,
             btree_root: coll_meta.btree_root,
             indexes,
+            metrics: self.metrics.collection_snapshot(name),
+            data_size_bytes,
+            avg_document_size: if document_count > 0 {
+                data_size_bytes as f64 / document_count as f64
+            } else {
+                0.0
+            },
         })
     }
 
@@ -678,23 +1483,16 @@ impl Database {
         let metadata = self.metadata.read()
             .map_err(|_| Error::LockPoisoned { lock_name: "database.metadata".to_string() })?;
         let coll_meta = metadata.collections.get(collection_name)
-            .ok_or_else(|| Error::Other(format!("collection {} not found", collection_name)))?;
-
-        let mut indexes = Vec::new();
-        for (idx_name, idx_meta) in &coll_meta.indexes {
-            indexes.push(IndexInfo {
-                name: idx_name.clone(),
-                fields: idx_meta.fields.clone(),
-                unique: idx_meta.unique,
-                btree_root: idx_meta.btree_root,
-            });
-        }
+            .ok_or_else(|| Error::CollectionDoesNotExist { name: collection_name.to_string() })?;
 
-        Ok(indexes)
+        Ok(index_infos(coll_meta))
     }
 
     /// Get comprehensive database information
     pub fn info(&self) -> Result<DatabaseInfo> {
+        // Counted before reading metadata, which space_stats locks after commit_mu
+        let space = self.space_stats()?;
+
         let metadata = self.metadata.read()
             .map_err(|_| Error::LockPoisoned { lock_name: "database.metadata".to_string() })?;
 
@@ -707,7 +1505,7 @@ impl Database {
         let mut collections = Vec::new();
         let mut total_documents = 0;
 
-        for coll_name in metadata.collections.keys() {
+        for coll_name in metadata.collections.keys().filter(|name| !recycle_bin::is_bin(name) && !replication::is_log(name)) {
             match self.collection_stats(coll_name) {
                 Ok(coll_info) => {
                     total_documents += coll_info.document_count;
@@ -721,17 +1519,186 @@ impl Database {
 
         Ok(DatabaseInfo {
             path: self.path.clone(),
-            version: VERSION,
+            version: self.pager.format_version(),
             num_pages: self.pager.num_pages()?,
             file_size,
             collections,
             total_documents,
             read_only: self.read_only,
+            space,
         })
     }
 
-    /// Helper: Count documents in a btree
-    fn count_documents_in_btree(&self, root_page: u64) -> Result<usize> {
+    /// Check the database file for corruption without modifying it.
+    ///
+    /// Walks every collection, index and text index B-tree checking node
+    /// invariants, decodes every document, compares each secondary index with
+    /// the documents it should hold, and accounts for every page in the file.
+    /// Commits wait until the check finishes; readers are not blocked.
+    pub fn check_integrity(&self) -> Result<IntegrityReport> {
+        let _commit_guard = self.commit_mu.lock()
+            .map_err(|_| Error::LockPoisoned { lock_name: "database.commit_mu".to_string() })?;
+        let metadata = self.metadata.read()
+            .map_err(|_| Error::LockPoisoned { lock_name: "database.metadata".to_string() })?
+            .clone();
+        // Held so garbage collection can't free old versions mid-check
+        let version_chains = self.version_chains.read()
+            .map_err(|_| Error::LockPoisoned { lock_name: "database.version_chains".to_string() })?;
+
+        let version_pages = held_version_pages(&version_chains)?;
+
+        integrity::check(&self.pager, &metadata, &version_pages)
+    }
+
+    /// Rebuild a database whose metadata is lost or damaged from the pages
+    /// still in the file. A last resort, for files `check_integrity` can't
+    /// help with because the metadata no longer lists their collections.
+    ///
+    /// Every page is scanned for B-tree nodes and documents, and each
+    /// collection found is copied into a fresh file that then replaces the
+    /// damaged one. The original names are lost, so collections come back as
+    /// `recovered_1`, `recovered_2` and so on, and indexes, schemas and other
+    /// settings have to be recreated. The report lists what was recovered and
+    /// what couldn't be read.
+    ///
+    /// Which of the versions copy-on-write leaves behind is current is only
+    /// inferred: an older version of a collection whose documents have all
+    /// been rewritten since can come back as an extra collection, so look
+    /// through the result before dropping the copy of the damaged file you
+    /// took beforehand. The database must not be open anywhere while this runs.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// let report = Database::repair("my.db").unwrap();
+    /// for collection in &report.collections {
+    ///     println!("{}: {} documents", collection.name, collection.documents);
+    /// }
+    /// let db = Database::open("my.db").unwrap();
+    /// db.rename_collection("recovered_1", "users").unwrap();
+    /// ```
+    pub fn repair(path: &str) -> Result<RepairReport> {
+        if path.is_empty() {
+            return Err(Error::Other("database path cannot be empty".to_string()));
+        }
+        if !std::path::Path::new(path).exists() {
+            return Err(Error::Other(format!("database file '{}' does not exist", path)));
+        }
+
+        let opts = DatabaseOptions::default();
+        let lock_path = format!("{}.lock", path);
+
+        #[cfg(unix)]
+        let lock_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .mode(opts.file_permissions)
+            .open(&lock_path)?;
+
+        #[cfg(not(unix))]
+        let lock_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(&lock_path)?;
+
+        lock_database_file(&lock_file, false, opts.lock_timeout_ms)?;
+
+        let pager = Pager::open(path, opts.cache_size, opts.file_permissions, false)?;
+        let wal = WAL::open_with_page_size(path, opts.file_permissions, pager.page_size())?;
+        replay_wal(&pager, &wal)?;
+
+        let (report, trees) = repair::scan(&pager)?;
+
+        let repaired_path = format!("{}.repair", path);
+        remove_database_files(&repaired_path);
+        if let Err(e) = write_repaired_copy(&pager, &trees, &repaired_path) {
+            remove_database_files(&repaired_path);
+            return Err(e);
+        }
+        let _ = std::fs::remove_file(format!("{}.lock", repaired_path));
+        let _ = std::fs::remove_file(format!("{}-wal", repaired_path));
+        std::fs::set_permissions(&repaired_path, std::fs::metadata(path)?.permissions())?;
+
+        // Everything in the WAL was replayed into the file being replaced
+        wal.close()?;
+        drop(wal);
+        let _ = std::fs::remove_file(format!("{}-wal", path));
+        std::fs::rename(&repaired_path, path)?;
+
+        FileExt::unlock(&lock_file)?;
+        Ok(report)
+    }
+
+    /// Report how the pages of the database file are used: what each
+    /// collection holds, what is on the free list, and what `garbage_collect`
+    /// and `vacuum` could reclaim. Commits wait while the pages are counted.
+    pub fn space_stats(&self) -> Result<SpaceStats> {
+        let _commit_guard = self.commit_mu.lock()
+            .map_err(|_| Error::LockPoisoned { lock_name: "database.commit_mu".to_string() })?;
+        let metadata = self.metadata.read()
+            .map_err(|_| Error::LockPoisoned { lock_name: "database.metadata".to_string() })?
+            .clone();
+        let version_chains = self.version_chains.read()
+            .map_err(|_| Error::LockPoisoned { lock_name: "database.version_chains".to_string() })?;
+
+        let version_pages = held_version_pages(&version_chains)?;
+
+        space::measure(&self.pager, &metadata, &version_pages)
+    }
+
+    /// Read a collection's document B-tree and index B-trees into the page
+    /// cache, and with `documents` its document pages too, so the first
+    /// queries after opening don't wait on disk. Reading stops once the cache
+    /// is full, reported in the result. Commits wait while pages are read.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// let db = Database::open("my.db").unwrap();
+    /// let warmup = db.warm_cache("users", true).unwrap();
+    /// println!("{} pages cached", warmup.pages());
+    /// ```
+    pub fn warm_cache(&self, collection_name: &str, documents: bool) -> Result<CacheWarmup> {
+        let _commit_guard = self.commit_mu.lock()
+            .map_err(|_| Error::LockPoisoned { lock_name: "database.commit_mu".to_string() })?;
+        let metadata = self.metadata.read()
+            .map_err(|_| Error::LockPoisoned { lock_name: "database.metadata".to_string() })?;
+        let coll_meta = metadata.collections.get(collection_name)
+            .ok_or_else(|| Error::CollectionDoesNotExist { name: collection_name.to_string() })?;
+
+        warm::warm(&self.pager, &[coll_meta], documents)
+    }
+
+    /// [`warm_cache`](Self::warm_cache) for every collection, B-trees of all
+    /// collections first
+    pub fn warm_cache_all(&self, documents: bool) -> Result<CacheWarmup> {
+        let _commit_guard = self.commit_mu.lock()
+            .map_err(|_| Error::LockPoisoned { lock_name: "database.commit_mu".to_string() })?;
+        let metadata = self.metadata.read()
+            .map_err(|_| Error::LockPoisoned { lock_name: "database.metadata".to_string() })?;
+        let mut names: Vec<&String> = metadata.collections.keys().collect();
+        names.sort();
+        let collections: Vec<_> = names.into_iter().map(|name| &metadata.collections[name]).collect();
+
+        warm::warm(&self.pager, &collections, documents)
+    }
+
+    /// Live document count of a collection as of the last commit, read from
+    /// metadata without touching the B-tree.
+    pub(crate) fn estimated_document_count(&self, collection_name: &str) -> Result<usize> {
+        let metadata = self.metadata.read()
+            .map_err(|_| Error::LockPoisoned { lock_name: "database.metadata".to_string() })?;
+        Ok(metadata.collections
+            .get(collection_name)
+            .and_then(|coll_meta| coll_meta.document_count)
+            .unwrap_or(0) as usize)
+    }
+
+    /// Helper: Count documents in a btree
+    /// Visible documents in the B-tree at `root_page` and their encoded size in bytes
+    fn measure_documents_in_btree(&self, root_page: u64) -> Result<(usize, u64)> {
         use crate::core::btree::BTree;
         use crate::core::document::read_versioned_document;
 
@@ -740,6 +1707,7 @@ impl Database {
         let btree = BTree::open(pager.clone(), root_page);
 
         let mut count = 0;
+        let mut bytes = 0;
         let tx_writes_arc = tx.get_writes_arc();
         let tx_writes = tx_writes_arc.read()
             .map_err(|_| Error::LockPoisoned { lock_name: "transaction.writes".to_string() })?;
@@ -751,52 +1719,66 @@ impl Database {
                 Ok(vdoc) => {
                     if vdoc.is_visible(tx.snapshot_id) {
                         count += 1;
+                        bytes += vdoc.data.len() as u64;
                     }
                 }
                 Err(_) => continue,
             }
         }
 
-        Ok(count)
+        Ok((count, bytes))
     }
 
-    pub fn run_transaction<F, R>(&self, f: F) -> Result<R>
+    /// Runs `f` in a new transaction and commits it, retrying with backoff on
+    /// `TxConflict` as configured by `TransactionConfig`. If `f` fails the
+    /// transaction is rolled back, and only conflicts are retried.
+    pub fn run_transaction<F, R>(&self, mut f: F) -> Result<R>
     where
-        F: Fn(&mut Transaction) -> Result<R>,
+        F: FnMut(&mut Transaction) -> Result<R>,
     {
-        let config = self.tx_config.read()
-            .map_err(|_| Error::LockPoisoned { lock_name: "database.tx_config".to_string() })?
-            .clone();
-        let mut last_err = None;
-
-        for attempt in 0..=(config.max_retries) {
+        self.retry_on_conflict(|| {
             let mut tx = self.begin()?;
 
             match f(&mut tx) {
                 Ok(result) => {
-                    match tx.commit() {
-                        Ok(_) => return Ok(result),
-                        Err(e) => {
-                            if matches!(e, Error::TxConflict) {
-                                self.metrics.transaction_conflict();
-                                last_err = Some(e);
-                            } else {
-                                return Err(e);
-                            }
-                        }
-                    }
+                    tx.commit()?;
+                    Ok(result)
                 }
                 Err(e) => {
                     let _ = tx.rollback();
-                    return Err(e);
+                    Err(e)
+                }
+            }
+        })
+    }
+
+    /// Calls `attempt` until it succeeds or fails with something other than
+    /// `TxConflict`, backing off between tries as configured by
+    /// `TransactionConfig`. This is the retry loop behind `run_transaction`, for
+    /// callers that need to own the transaction themselves.
+    pub fn retry_on_conflict<F, R>(&self, mut attempt: F) -> Result<R>
+    where
+        F: FnMut() -> Result<R>,
+    {
+        let config = self.tx_config.read()
+            .map_err(|_| Error::LockPoisoned { lock_name: "database.tx_config".to_string() })?
+            .clone();
+        let mut last_err = None;
+
+        for attempt_num in 0..=(config.max_retries) {
+            match attempt() {
+                Ok(result) => return Ok(result),
+                Err(Error::TxConflict) => {
+                    self.metrics.transaction_conflict();
+                    last_err = Some(Error::TxConflict);
                 }
+                Err(e) => return Err(e),
             }
 
-            if attempt < config.max_retries {
-                let backoff_ms = config.retry_backoff_base_ms * (1 << attempt);
-                let backoff_ms = backoff_ms.min(config.max_retry_backoff_ms);
-                if backoff_ms > 0 {
-                    std::thread::sleep(std::time::Duration::from_millis(backoff_ms));
+            if attempt_num < config.max_retries {
+                let backoff = config.retry_backoff(attempt_num);
+                if !backoff.is_zero() {
+                    std::thread::sleep(backoff);
                 }
             }
         }
@@ -824,6 +1806,15 @@ impl Database {
         self.wal.frame_count()
     }
 
+    /// Current size of the WAL in bytes, as compared against `max_wal_bytes`
+    pub fn wal_size(&self) -> u64 {
+        self.wal.size_bytes()
+    }
+
+    pub fn max_wal_bytes(&self) -> u64 {
+        self.max_wal_bytes
+    }
+
     pub(crate) fn maybe_auto_checkpoint(&self) {
         let threshold = *self.auto_checkpoint_threshold.read()
             .recover_poison();
@@ -894,15 +1885,127 @@ impl Database {
     /// // - Queries on "age" alone cannot use this index
     /// ```
     pub fn create_compound_index(&self, collection_name: &str, index_name: &str, fields: &[&str], unique: bool) -> Result<()> {
-        use crate::core::validation::validate_collection_name;
-        use crate::core::btree::BTree;
-
-        validate_collection_name(collection_name)?;
+        self.create_index_with_options(collection_name, index_name, fields, IndexOptions { unique, ..Default::default() })
+    }
 
+    /// Create a single-field or compound index with explicit options.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::{Database, IndexOptions, SortOrder};
+    /// # let db = Database::open("my.db").unwrap();
+    /// let options = IndexOptions { descending: true, ..Default::default() };
+    /// db.create_index_with_options("events", "ts_desc", &["timestamp", "kind"], options).unwrap();
+    ///
+    /// // Answered from the index alone, newest first
+    /// let latest = db.collection("events").query()
+    ///     .filter("kind is 'login'")
+    ///     .sort_by("timestamp", SortOrder::Desc)
+    ///     .project(&["timestamp", "kind"])
+    ///     .execute()
+    ///     .unwrap();
+    /// ```
+    pub fn create_index_with_options(&self, collection_name: &str, index_name: &str, fields: &[&str], options: IndexOptions) -> Result<()> {
         if fields.is_empty() {
             return Err(Error::Other("index must have at least one field".to_string()));
         }
 
+        self.add_index(collection_name, crate::core::metadata::IndexMeta {
+            name: index_name.to_string(),
+            field: None,  // Deprecated field
+            fields: fields.iter().map(|s| s.to_string()).collect(),
+            btree_root: 0,
+            unique: options.unique,
+            descending: options.descending,
+            sparse: options.sparse,
+            geo: false,
+            expression: false,
+            collation: options.collation,
+        })
+    }
+
+    /// Create a geo index over the latitude and longitude fields of a
+    /// collection's documents, in degrees. Queries using
+    /// `near(<field>, lat, lng, radius_km)` read candidates from a geo index on
+    /// `<field>.lat` and `<field>.lng` instead of scanning every document.
+    ///
+    /// Documents whose fields aren't numbers within range are left out of the
+    /// index and never match `near()`.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # let db = Database::open("my.db").unwrap();
+    /// db.create_geo_index("places", "location_geo", "location.lat", "location.lng").unwrap();
+    ///
+    /// // Places within 5 km of the Eiffel Tower, closest first
+    /// let nearby = db.collection("places").query()
+    ///     .filter("near(location, 48.8584, 2.2945, 5)")
+    ///     .sort_by_distance("location", 48.8584, 2.2945)
+    ///     .execute()
+    ///     .unwrap();
+    /// ```
+    pub fn create_geo_index(&self, collection_name: &str, index_name: &str, lat_field: &str, lng_field: &str) -> Result<()> {
+        self.add_index(collection_name, crate::core::metadata::IndexMeta {
+            name: index_name.to_string(),
+            field: None,
+            fields: vec![lat_field.to_string(), lng_field.to_string()],
+            btree_root: 0,
+            unique: false,
+            descending: false,
+            sparse: true,
+            geo: true,
+            expression: false,
+            collation: Collation::Binary,
+        })
+    }
+
+    /// Create an index keyed by a value computed from each document, such as
+    /// `lower(email)` or `length(tags)`. See [`crate::core::expression`] for
+    /// the functions an expression may use.
+    ///
+    /// Queries comparing the same expression with `is`, written the same way
+    /// up to spacing, read matching documents from the index instead of
+    /// scanning the collection. A unique index on `lower(email)` rejects two
+    /// addresses differing only in case.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::{Database, IndexOptions};
+    /// # use serde_json::json;
+    /// # let db = Database::open("my.db").unwrap();
+    /// let options = IndexOptions { unique: true, sparse: true, ..Default::default() };
+    /// db.create_expression_index("users", "email_ci", "lower(email)", options).unwrap();
+    ///
+    /// let users = db.collection("users");
+    /// users.insert(json!({"email": "Ann@Example.com"})).unwrap();
+    /// assert!(users.insert(json!({"email": "ann@example.com"})).is_err());
+    /// let ann = users.find("lower(email) is 'ann@example.com'").unwrap();
+    /// ```
+    pub fn create_expression_index(&self, collection_name: &str, index_name: &str, expression: &str, options: IndexOptions) -> Result<()> {
+        let expression = crate::core::expression::Expression::parse(expression)?;
+
+        self.add_index(collection_name, crate::core::metadata::IndexMeta {
+            name: index_name.to_string(),
+            field: None,
+            fields: vec![expression.to_string()],
+            btree_root: 0,
+            unique: options.unique,
+            descending: options.descending,
+            sparse: options.sparse,
+            geo: false,
+            expression: true,
+            collation: options.collation,
+        })
+    }
+
+    /// Build `index_meta` from the collection's documents and record it in metadata
+    fn add_index(&self, collection_name: &str, mut index_meta: crate::core::metadata::IndexMeta) -> Result<()> {
+        use crate::core::validation::validate_collection_name;
+
+        validate_collection_name(collection_name)?;
+        let index_name = index_meta.name.as_str();
+
         if self.read_only {
             return Err(Error::Other("cannot create index: database is in read-only mode".to_string()));
         }
@@ -922,8 +2025,6 @@ impl Database {
             }
         }
 
-        let index_btree = BTree::new(self.pager.clone())?;
-
         let coll_btree_root = {
             let metadata = self.metadata.read()
                 .map_err(|_| Error::LockPoisoned { lock_name: "database.metadata".to_string() })?;
@@ -933,29 +2034,14 @@ impl Database {
                 .unwrap_or(0)
         };
 
-        let fields_vec: Vec<String> = fields.iter().map(|s| s.to_string()).collect();
-
-        if coll_btree_root != 0 {
-            self.build_compound_index_from_btree(&index_btree, coll_btree_root, &fields_vec, unique)?;
-        }
-
-        // Get the root page AFTER building the index (it may have changed due to splits)
-        let index_root = index_btree.root_page();
+        let tx = self.begin()?;
+        index_meta.btree_root = self.build_index(collection_name, &index_meta, coll_btree_root, tx.snapshot_id, &mut |_| {})?;
 
         {
             let mut metadata = self.metadata.write()
                 .map_err(|_| Error::LockPoisoned { lock_name: "database.metadata".to_string() })?;
             let coll_meta = metadata.get_collection(collection_name);
-            coll_meta.indexes.insert(
-                index_name.to_string(),
-                crate::core::metadata::IndexMeta {
-                    name: index_name.to_string(),
-                    field: None,  // Deprecated field
-                    fields: fields_vec,
-                    btree_root: index_root,
-                    unique,
-                },
-            );
+            coll_meta.indexes.insert(index_meta.name.clone(), index_meta);
         }
 
         self.save_metadata()?;
@@ -973,22 +2059,37 @@ impl Database {
     /// * `collection_name` - Name of the collection to index
     /// * `index_name` - Name for the text index
     /// * `fields` - List of text fields to index for search
+    /// * `options` - Stopwords and stemming applied to documents and queries
+    ///
+    /// The options are fixed once the index is built. To change them, drop the
+    /// index and create it again so existing documents are re-tokenized.
     ///
     /// # Examples
     /// ```no_run
     /// # use jasonisnthappy::Database;
+    /// use jasonisnthappy::{Language, TextIndexOptions};
     /// # let db = Database::open("my.db").unwrap();
     /// // Create text index on title and body fields
-    /// db.create_text_index("posts", "search_idx", &["title", "body"]).unwrap();
+    /// let options = TextIndexOptions {
+    ///     stopwords: vec!["the".to_string(), "a".to_string(), "is".to_string()],
+    ///     stemming: Some(Language::English),
+    ///     ..Default::default()
+    /// };
+    /// db.create_text_index("posts", "search_idx", &["title", "body"], options).unwrap();
     ///
     /// // Now you can search across these fields
     /// let posts = db.collection("posts");
     /// let results = posts.search("rust database").unwrap();
     /// ```
-    pub fn create_text_index(&self, collection_name: &str, index_name: &str, fields: &[&str]) -> Result<()> {
+    pub fn create_text_index(
+        &self,
+        collection_name: &str,
+        index_name: &str,
+        fields: &[&str],
+        options: TextIndexOptions,
+    ) -> Result<()> {
         use crate::core::validation::validate_collection_name;
-        use crate::core::btree::BTree;
-        use crate::core::text_search::{TextIndex, TextIndexMeta};
+        use crate::core::text_search::TextIndexMeta;
 
         validate_collection_name(collection_name)?;
 
@@ -1006,7 +2107,13 @@ impl Database {
             let metadata = self.metadata.read()
                 .map_err(|_| Error::LockPoisoned { lock_name: "database.metadata".to_string() })?;
             if let Some(coll_meta) = metadata.collections.get(collection_name) {
-                if coll_meta.text_indexes.contains_key(index_name) {
+                if let Some(existing) = coll_meta.text_indexes.get(index_name) {
+                    if existing.options != options {
+                        return Err(Error::Other(format!(
+                            "text index {} on collection {} was built with different options; drop it and recreate it to rebuild",
+                            index_name, collection_name
+                        )));
+                    }
                     return Err(Error::Other(format!(
                         "text index {} already exists on collection {}",
                         index_name, collection_name
@@ -1015,11 +2122,6 @@ impl Database {
             }
         }
 
-        let index_btree = BTree::new(self.pager.clone())?;
-        let fields_vec: Vec<String> = fields.iter().map(|s| s.to_string()).collect();
-        let mut text_index = TextIndex::new(index_btree, fields_vec.clone());
-
-        // Build index from existing documents
         let coll_btree_root = {
             let metadata = self.metadata.read()
                 .map_err(|_| Error::LockPoisoned { lock_name: "database.metadata".to_string() })?;
@@ -1029,25 +2131,22 @@ impl Database {
                 .unwrap_or(0)
         };
 
-        if coll_btree_root != 0 {
-            self.build_text_index_from_btree(&mut text_index, coll_btree_root, &fields_vec)?;
-        }
+        let mut text_meta = TextIndexMeta {
+            name: index_name.to_string(),
+            fields: fields.iter().map(|s| s.to_string()).collect(),
+            btree_root: 0,
+            options,
+        };
 
-        // Get the root page AFTER building the index
-        let index_root = text_index.btree().root_page();
+        // Build index from existing documents
+        let tx = self.begin()?;
+        text_meta.btree_root = self.build_text_index(&text_meta, coll_btree_root, tx.snapshot_id, &mut |_| {})?;
 
         {
             let mut metadata = self.metadata.write()
                 .map_err(|_| Error::LockPoisoned { lock_name: "database.metadata".to_string() })?;
             let coll_meta = metadata.get_collection(collection_name);
-            coll_meta.text_indexes.insert(
-                index_name.to_string(),
-                TextIndexMeta {
-                    name: index_name.to_string(),
-                    fields: fields_vec,
-                    btree_root: index_root,
-                },
-            );
+            coll_meta.text_indexes.insert(index_name.to_string(), text_meta);
         }
 
         self.save_metadata()?;
@@ -1088,7 +2187,7 @@ impl Database {
             let coll_meta = metadata.collections.get(collection_name)
                 .ok_or_else(|| Error::Other(format!("collection {} does not exist", collection_name)))?;
 
-            if !coll_meta.indexes.contains_key(index_name) {
+            if !coll_meta.indexes.contains_key(index_name) && !coll_meta.text_indexes.contains_key(index_name) {
                 return Err(Error::Other(format!(
                     "index {} does not exist on collection {}",
                     index_name, collection_name
@@ -1102,8 +2201,10 @@ impl Database {
                 .map_err(|_| Error::LockPoisoned { lock_name: "database.metadata".to_string() })?;
             if let Some(coll_meta) = metadata.collections.get_mut(collection_name) {
                 coll_meta.indexes.remove(index_name);
+                coll_meta.text_indexes.remove(index_name);
             }
         }
+        self.metrics.collection(collection_name).forget_index(index_name);
 
         // Save metadata and flush
         self.save_metadata()?;
@@ -1113,6 +2214,114 @@ impl Database {
         Ok(())
     }
 
+    /// Rebuild an index from the collection's current documents, keeping its
+    /// definition: fields, uniqueness, direction, sparseness and text options.
+    ///
+    /// Commits are held off while the collection is scanned into a fresh
+    /// B-tree, which only replaces the old one once it is complete, so a failed
+    /// rebuild leaves the existing index in place. Rebuilding a unique index
+    /// fails if two documents share a value, naming both.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # let db = Database::open("my.db").unwrap();
+    /// db.rebuild_index("users", "email_idx").unwrap();
+    /// ```
+    pub fn rebuild_index(&self, collection_name: &str, index_name: &str) -> Result<()> {
+        self.rebuild_index_with_progress(collection_name, index_name, |_, _| {})
+    }
+
+    /// Rebuild an index like [`Database::rebuild_index`], calling `progress`
+    /// with the number of documents indexed so far and the collection's
+    /// document count every 1000 documents and once the scan finishes.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # let db = Database::open("my.db").unwrap();
+    /// db.rebuild_index_with_progress("events", "ts_idx", |done, total| {
+    ///     println!("indexed {}/{}", done, total);
+    /// }).unwrap();
+    /// ```
+    pub fn rebuild_index_with_progress<F>(&self, collection_name: &str, index_name: &str, mut progress: F) -> Result<()>
+    where
+        F: FnMut(u64, u64),
+    {
+        use crate::core::validation::validate_collection_name;
+
+        validate_collection_name(collection_name)?;
+
+        if self.read_only {
+            return Err(Error::Other("cannot rebuild index: database is in read-only mode".to_string()));
+        }
+
+        let _commit_guard = self.commit_mu.lock()
+            .map_err(|_| Error::LockPoisoned { lock_name: "database.commit_mu".to_string() })?;
+        self.wal.checkpoint(&self.pager)?;
+
+        let (coll_root, total, index_meta, text_meta) = {
+            let metadata = self.metadata.read()
+                .map_err(|_| Error::LockPoisoned { lock_name: "database.metadata".to_string() })?;
+            let coll_meta = metadata.collections.get(collection_name)
+                .ok_or_else(|| Error::Other(format!("collection {} does not exist", collection_name)))?;
+            (
+                coll_meta.btree_root,
+                coll_meta.document_count.unwrap_or(0),
+                coll_meta.indexes.get(index_name).cloned(),
+                coll_meta.text_indexes.get(index_name).cloned(),
+            )
+        };
+
+        let mut tx = self.begin()?;
+        let mut indexed = 0;
+        let mut report = |count: u64| {
+            indexed = count;
+            if count.is_multiple_of(INDEX_REBUILD_PROGRESS_INTERVAL) {
+                progress(count, total);
+            }
+        };
+
+        let built = if let Some(index_meta) = &index_meta {
+            self.build_index(collection_name, index_meta, coll_root, tx.snapshot_id, &mut report)
+                .map(|root| (root, index_meta.btree_root, false))
+        } else if let Some(text_meta) = &text_meta {
+            self.build_text_index(text_meta, coll_root, tx.snapshot_id, &mut report)
+                .map(|root| (root, text_meta.btree_root, true))
+        } else {
+            Err(Error::Other(format!(
+                "index {} does not exist on collection {}",
+                index_name, collection_name
+            )))
+        };
+        // The transaction only held the snapshot the scan read
+        tx.rollback()?;
+        let (index_root, old_root, text) = built?;
+        // The last batch was already reported if it filled an interval
+        if indexed == 0 || !indexed.is_multiple_of(INDEX_REBUILD_PROGRESS_INTERVAL) {
+            progress(indexed, total);
+        }
+
+        // Nothing points into the old tree once the new one replaces it, and
+        // the free list is logged with the metadata that swaps them
+        for page in space::index_pages(&self.pager, old_root, text)? {
+            self.pager.free_page(page)?;
+        }
+        self.update_metadata_locked(|metadata| {
+            if let Some(coll_meta) = metadata.collections.get_mut(collection_name) {
+                if let Some(index_meta) = coll_meta.indexes.get_mut(index_name) {
+                    index_meta.btree_root = index_root;
+                } else if let Some(text_meta) = coll_meta.text_indexes.get_mut(index_name) {
+                    text_meta.btree_root = index_root;
+                }
+            }
+        })?;
+        self.pager.flush()?;
+        self.pager.write_header()?;
+
+        Ok(())
+    }
+
     /// Set a validation schema for a collection
     ///
     /// Documents inserted or updated in this collection will be validated against this schema.
@@ -1147,6 +2356,8 @@ impl Database {
             return Err(Error::Other("cannot set schema: database is in read-only mode".to_string()));
         }
 
+        schema.check_patterns()?;
+
         {
             let mut metadata = self.metadata.write()
                 .map_err(|_| Error::LockPoisoned { lock_name: "database.metadata".to_string() })?;
@@ -1160,6 +2371,41 @@ impl Database {
         Ok(())
     }
 
+    /// Set a collection's validation schema from a standard JSON Schema document
+    ///
+    /// The supported subset is translated with [`Schema::from_json_schema`]
+    /// and stored like [`Database::set_schema`]. Returns one warning per
+    /// keyword that was ignored; an empty list means every constraint in
+    /// `json_schema` will be enforced.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use jasonisnthappy::Database;
+    /// use serde_json::json;
+    ///
+    /// let db = Database::open("my.db").unwrap();
+    ///
+    /// let warnings = db.set_json_schema("users", &json!({
+    ///     "$schema": "https://json-schema.org/draft/2020-12/schema",
+    ///     "type": "object",
+    ///     "required": ["email"],
+    ///     "properties": {
+    ///         "email": {"type": "string", "pattern": "^[^@]+@[^@]+$"},
+    ///         "age": {"type": "integer", "minimum": 0, "exclusiveMaximum": 150}
+    ///     }
+    /// })).unwrap();
+    ///
+    /// // ["#/properties/age: keyword 'exclusiveMaximum' is not supported and was ignored"]
+    /// println!("{:?}", warnings);
+    /// ```
+    ///
+    /// [`Schema::from_json_schema`]: crate::core::validation::Schema::from_json_schema
+    pub fn set_json_schema(&self, collection_name: &str, json_schema: &serde_json::Value) -> Result<Vec<String>> {
+        let (schema, warnings) = crate::core::validation::Schema::from_json_schema(json_schema)?;
+        self.set_schema(collection_name, schema)?;
+        Ok(warnings)
+    }
+
     /// Get the validation schema for a collection
     ///
     /// Returns None if no schema is set for the collection.
@@ -1197,168 +2443,492 @@ impl Database {
         Ok(())
     }
 
-    fn build_compound_index_from_btree(
-        &self,
-        index_btree: &BTree,
-        root_page: PageNum,
-        fields: &[String],
-        unique: bool,
-    ) -> Result<()> {
-        self.scan_btree_node_for_compound_index(index_btree, root_page, fields, unique)
+    /// Set how a collection generates `_id` for documents inserted without one
+    pub fn set_id_strategy(&self, collection_name: &str, strategy: crate::core::id_strategy::IdStrategy) -> Result<()> {
+        use crate::core::validation::validate_collection_name;
+
+        validate_collection_name(collection_name)?;
+
+        if self.read_only {
+            return Err(Error::Other("cannot set id strategy: database is in read-only mode".to_string()));
+        }
+
+        self.update_metadata(|m| {
+            m.get_collection(collection_name).id_strategy = Some(strategy);
+        })
     }
 
-    fn scan_btree_node_for_compound_index(
-        &self,
-        index_btree: &BTree,
-        page_num: PageNum,
-        fields: &[String],
-        unique: bool,
-    ) -> Result<()> {
-        use crate::core::btree::{deserialize_node, NodeType};
-        use crate::core::document::read_versioned_document;
-        use crate::core::index_key::{
-            IndexKey, serialize_index_key, extract_field_values,
-            CompoundIndexKey, serialize_compound_index_key
-        };
-        use serde_json::Value;
+    /// Set the order a collection's queries return documents in when they
+    /// don't sort themselves, as `(field, order)` pairs. An empty slice goes
+    /// back to primary key order.
+    ///
+    /// Queries are answered from an index in that order when one exists, so
+    /// results come out sorted without sorting them in memory; otherwise they
+    /// are sorted after being read.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::{Database, IndexOptions, SortOrder};
+    /// # let db = Database::open("my.db").unwrap();
+    /// db.create_index_with_options("events", "by_timestamp", &["timestamp"], IndexOptions::default()).unwrap();
+    /// db.set_default_sort("events", &[("timestamp", SortOrder::Asc)]).unwrap();
+    /// let oldest_first = db.collection("events").find("kind is 'click'").unwrap();
+    /// ```
+    pub fn set_default_sort(&self, collection_name: &str, sort: &[(&str, SortOrder)]) -> Result<()> {
+        use crate::core::validation::validate_collection_name;
+
+        validate_collection_name(collection_name)?;
+
+        if self.read_only {
+            return Err(Error::Other("cannot set default sort: database is in read-only mode".to_string()));
+        }
 
-        if page_num == 0 {
+        let sort: Vec<(String, SortOrder)> = sort.iter()
+            .map(|(field, order)| (field.to_string(), *order))
+            .collect();
+        self.update_metadata(|m| {
+            m.get_collection(collection_name).default_sort = sort;
+        })?;
+        self.invalidate_query_cache(collection_name);
+        Ok(())
+    }
+
+    /// The default sort of a collection, empty if it has none
+    pub fn get_default_sort(&self, collection_name: &str) -> Vec<(String, SortOrder)> {
+        let metadata = self.metadata.read()
+            .recover_poison();
+        metadata.collections
+            .get(collection_name)
+            .map(|c| c.default_sort.clone())
+            .unwrap_or_default()
+    }
+
+    /// Turn soft delete on or off for a collection. With it on, deleting a
+    /// document moves it into the collection's recycle bin, stamped with
+    /// `_deleted_at`, where queries and counts don't see it until
+    /// [`Collection::restore`](crate::Collection::restore) brings it back.
+    /// Documents stay in the bin until
+    /// [`Collection::purge_deleted`](crate::Collection::purge_deleted)
+    /// removes them; turning soft delete off leaves the bin as it is.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # let db = Database::open("my.db").unwrap();
+    /// db.set_soft_delete("orders", true).unwrap();
+    /// let orders = db.collection("orders");
+    /// orders.delete_by_id("order_1").unwrap();
+    /// assert!(orders.find_by_id("order_1").is_err());
+    /// orders.restore("order_1").unwrap();
+    /// ```
+    pub fn set_soft_delete(&self, collection_name: &str, enabled: bool) -> Result<()> {
+        use crate::core::validation::validate_collection_name;
+
+        validate_collection_name(collection_name)?;
+
+        if self.read_only {
+            return Err(Error::Other("cannot set soft delete: database is in read-only mode".to_string()));
+        }
+
+        self.update_metadata(|m| {
+            m.get_collection(collection_name).soft_delete = enabled;
+        })
+    }
+
+    /// Whether deletes from a collection go to its recycle bin
+    pub fn soft_delete(&self, collection_name: &str) -> bool {
+        self.metadata.read()
+            .recover_poison()
+            .collections
+            .get(collection_name)
+            .is_some_and(|c| c.soft_delete)
+    }
+
+    /// Start keeping a change log of every document committed from now on,
+    /// for [`replication_stream`](Self::replication_stream) to read. The log
+    /// is kept in the database file and stays on once enabled; calling this
+    /// again does nothing. Trim records followers have applied with
+    /// [`trim_replication_log`](Self::trim_replication_log), since the log
+    /// grows with every write.
+    pub fn enable_replication_log(&self) -> Result<()> {
+        if self.read_only {
+            return Err(Error::Other("cannot enable replication log: database is in read-only mode".to_string()));
+        }
+        if self.replication_log_enabled() {
             return Ok(());
         }
 
-        let page_data = self.pager.read_page(page_num)?;
-        let node = deserialize_node(page_num, &page_data)?;
+        self.update_metadata(|m| {
+            m.get_collection(replication::LOG_COLLECTION);
+        })
+    }
 
-        if node.node_type == NodeType::LeafNode {
-            for entry in &node.entries {
-                let doc_page_num = entry.value;
-                let doc_id = &entry.key;
+    /// Whether commits are recorded in the change log
+    pub fn replication_log_enabled(&self) -> bool {
+        self.replication_log_state().is_some()
+    }
 
-                let vdoc = match read_versioned_document(&self.pager, doc_page_num, &std::collections::HashMap::new()) {
-                    Ok(vdoc) => vdoc,
-                    Err(_) => continue,
-                };
+    /// Root page of the change log and the last sequence handed out, or
+    /// `None` without a log
+    pub(crate) fn replication_log_state(&self) -> Option<(PageNum, u64)> {
+        self.metadata.read()
+            .recover_poison()
+            .collections
+            .get(replication::LOG_COLLECTION)
+            .map(|log| (log.btree_root, log.last_auto_id))
+    }
 
-                let doc_map: serde_json::Map<String, Value> = match serde_json::from_slice(&vdoc.data) {
-                    Ok(map) => map,
-                    Err(_) => continue,
-                };
+    /// Committed document writes from the change log, starting at
+    /// `from_sequence` (0 for the oldest record kept). A follower saves the
+    /// sequence of the last record it applied and resumes from the one after
+    /// it. Fails with `Error::ReplicationLogTrimmed` if the records from
+    /// `from_sequence` on were already trimmed, since the follower then
+    /// needs a fresh copy of the database.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # let leader = Database::open("leader.db").unwrap();
+    /// # let follower = Database::open("follower.db").unwrap();
+    /// leader.enable_replication_log().unwrap();
+    ///
+    /// let mut applied = 0;
+    /// for record in leader.replication_stream(applied + 1).unwrap() {
+    ///     let record = record.unwrap();
+    ///     follower.apply_replication(&record).unwrap();
+    ///     applied = record.sequence;
+    /// }
+    /// ```
+    pub fn replication_stream(&self, from_sequence: u64) -> Result<replication::ReplicationStream> {
+        let (_, last) = self.replication_log_state()
+            .ok_or_else(|| Error::Other("replication log is not enabled".to_string()))?;
+        let log = self.collection(replication::LOG_COLLECTION);
+        if from_sequence != 0 {
+            // Trimming removes the oldest records, so the log holds every
+            // sequence from its first record on
+            let oldest = match log.scan_from("", 1)?.first() {
+                Some(record) => record["sequence"].as_u64().unwrap_or(0),
+                None => last + 1,
+            };
+            if from_sequence < oldest {
+                return Err(Error::ReplicationLogTrimmed { requested: from_sequence, oldest });
+            }
+        }
 
-                let key_str = if fields.len() == 1 {
-                    // Single-field index (backward compatible)
-                    let field_value = extract_field_values(&doc_map, fields)[0].clone();
-                    let index_key = IndexKey {
-                        field_value,
-                        doc_id: doc_id.clone(),
-                    };
-                    serialize_index_key(&index_key)?
-                } else {
-                    // Compound index
-                    let field_values = extract_field_values(&doc_map, fields);
-                    let compound_key = CompoundIndexKey {
-                        field_values,
-                        doc_id: doc_id.clone(),
-                    };
-                    serialize_compound_index_key(&compound_key)?
-                };
+        Ok(replication::ReplicationStream::new(log, from_sequence))
+    }
 
-                if unique {
-                    if index_btree.search(&key_str).is_ok() {
-                        return Err(Error::Other(format!(
-                            "unique constraint violation on fields {:?}: duplicate value found",
-                            fields
-                        )));
-                    }
-                }
+    /// Replay a record from another database's
+    /// [`replication_stream`](Self::replication_stream), writing the whole
+    /// document for inserts and updates and removing it for deletes.
+    /// Replaying a record again leaves the same document behind, so records
+    /// may be applied more than once. The collection is created if needed,
+    /// but its indexes and settings are not replicated.
+    pub fn apply_replication(&self, record: &replication::ReplicationRecord) -> Result<()> {
+        if self.read_only {
+            return Err(Error::Other("cannot apply replication: database is in read-only mode".to_string()));
+        }
+        if replication::is_log(&record.collection) {
+            return Err(Error::CollectionNameReserved);
+        }
 
-                index_btree.insert(&key_str, doc_page_num)?;
+        self.run_transaction(|tx| {
+            let mut collection = tx.collection(&record.collection)?;
+            if record.operation == ChangeOperation::Delete {
+                return match collection.delete_by_id(&record.doc_id) {
+                    Ok(()) | Err(Error::DocumentNotFound { .. }) => Ok(()),
+                    Err(e) => Err(e),
+                };
             }
 
-            if node.next_leaf != 0 {
-                return self.scan_btree_node_for_compound_index(index_btree, node.next_leaf, fields, unique);
+            let mut doc = replication::replicated_document(record)?;
+            doc.insert("_id".to_string(), serde_json::Value::String(record.doc_id.clone()));
+            match collection.find_by_id(&record.doc_id) {
+                Ok(_) => collection.replace_by_id(&record.doc_id, doc),
+                Err(Error::DocumentNotFound { .. }) => collection.insert_stored(record.doc_id.clone(), doc).map(|_| ()),
+                Err(e) => Err(e),
             }
-        } else {
-            for child_page in &node.children {
-                self.scan_btree_node_for_compound_index(index_btree, *child_page, fields, unique)?;
+        })
+    }
+
+    /// Remove the records up to and including `through_sequence` from the
+    /// change log, once every follower has applied them, and return how many
+    /// were removed. Streams can't start at a removed record afterwards.
+    pub fn trim_replication_log(&self, through_sequence: u64) -> Result<usize> {
+        if self.read_only {
+            return Err(Error::Other("cannot trim replication log: database is in read-only mode".to_string()));
+        }
+        if !self.replication_log_enabled() || through_sequence == 0 {
+            return Ok(0);
+        }
+
+        let ids: Vec<String> = self.collection(replication::LOG_COLLECTION)
+            .scan_range("", &replication::sequence_key(through_sequence), true)?
+            .iter()
+            .filter_map(|doc| doc.get("_id").and_then(|id| id.as_str()).map(String::from))
+            .collect();
+        if ids.is_empty() {
+            return Ok(0);
+        }
+
+        self.run_transaction(|tx| {
+            let mut log = tx.collection(replication::LOG_COLLECTION)?;
+            for id in &ids {
+                match log.delete_by_id(id) {
+                    Ok(()) | Err(Error::DocumentNotFound { .. }) => {}
+                    Err(e) => return Err(e),
+                }
             }
+            Ok(())
+        })?;
+        Ok(ids.len())
+    }
+
+    /// Raise the `AutoIncrement` counter of a collection to at least `last_id`,
+    /// so generated ids continue after documents restored with numeric ids
+    pub fn reserve_auto_ids(&self, collection_name: &str, last_id: u64) -> Result<()> {
+        use crate::core::validation::validate_collection_name;
+
+        validate_collection_name(collection_name)?;
+
+        if self.read_only {
+            return Err(Error::Other("cannot reserve auto ids: database is in read-only mode".to_string()));
         }
 
-        Ok(())
+        self.update_metadata(|m| {
+            let coll_meta = m.get_collection(collection_name);
+            coll_meta.last_auto_id = coll_meta.last_auto_id.max(last_id);
+        })
+    }
+
+    /// Get the id strategy of a collection, or None if it uses the built-in ids
+    pub fn get_id_strategy(&self, collection_name: &str) -> Option<crate::core::id_strategy::IdStrategy> {
+        let metadata = self.metadata.read()
+            .recover_poison();
+        metadata.collections
+            .get(collection_name)
+            .and_then(|c| c.id_strategy)
+    }
+
+    /// Generate an `_id` using the collection's id strategy, or None if it has
+    /// no strategy. AutoIncrement numbers are taken under the metadata lock so
+    /// concurrent inserts never share one; the bumped counter is persisted with
+    /// the next commit's metadata.
+    pub(crate) fn next_document_id(&self, collection_name: &str) -> Result<Option<String>> {
+        use crate::core::id_strategy::IdStrategy;
+
+        let strategy = match self.get_id_strategy(collection_name) {
+            Some(strategy) => strategy,
+            None => return Ok(None),
+        };
+
+        if strategy != IdStrategy::AutoIncrement {
+            return Ok(strategy.generate());
+        }
+
+        let mut metadata = self.metadata.write()
+            .map_err(|_| Error::LockPoisoned { lock_name: "database.metadata".to_string() })?;
+        let coll_meta = metadata.get_collection(collection_name);
+        coll_meta.last_auto_id += 1;
+        Ok(Some(coll_meta.last_auto_id.to_string()))
     }
 
-    fn build_text_index_from_btree(
+    /// Build a fresh B-tree for a secondary index from every document visible
+    /// to `snapshot_id`, calling `progress` with the running count, and return
+    /// its root page. Fails without touching metadata if a unique index would
+    /// hold the same value twice.
+    fn build_index(
         &self,
-        text_index: &mut crate::core::text_search::TextIndex,
-        root_page: PageNum,
-        fields: &[String],
-    ) -> Result<()> {
-        self.scan_btree_node_for_text_index(text_index, root_page, fields)
+        collection_name: &str,
+        index_meta: &crate::core::metadata::IndexMeta,
+        coll_root: PageNum,
+        snapshot_id: TransactionID,
+        progress: &mut dyn FnMut(u64),
+    ) -> Result<PageNum> {
+        use crate::core::index_key::{serialize_compound_index_key, CompoundIndexKey};
+        use crate::core::index_writes::indexed_values;
+
+        let fields = index_meta.get_fields();
+        let index_btree = BTree::new(self.pager.clone())?;
+        let mut owners: HashMap<String, String> = HashMap::new();
+        let mut indexed = 0u64;
+
+        self.for_each_visible_document(coll_root, snapshot_id, |doc_id, page_num, doc_map| {
+            if index_meta.geo {
+                if let Some(key) = crate::core::index_writes::index_key(index_meta, doc_id, doc_map)? {
+                    index_btree.insert(&key, page_num)?;
+                }
+                indexed += 1;
+                progress(indexed);
+                return Ok(());
+            }
+
+            let field_values = indexed_values(index_meta, doc_map);
+            if !index_meta.indexes_values(&field_values) {
+                // Progress is measured against the collection's document count
+                indexed += 1;
+                progress(indexed);
+                return Ok(());
+            }
+
+            if index_meta.unique {
+                let value_json = serde_json::to_string(&field_values)?;
+                if let Some(owner) = owners.insert(value_json.clone(), doc_id.to_string()) {
+                    return Err(Error::Other(format!(
+                        "unique constraint violation on index {} for collection {}: documents {} and {} both have {:?} = {}",
+                        index_meta.name, collection_name, owner, doc_id, fields, value_json
+                    )));
+                }
+            }
+
+            // A single value serializes the same way as a single-field key
+            let key_str = serialize_compound_index_key(&CompoundIndexKey {
+                field_values,
+                doc_id: doc_id.to_string(),
+            })?;
+            index_btree.insert(&key_str, page_num)?;
+
+            indexed += 1;
+            progress(indexed);
+            Ok(())
+        })?;
+
+        Ok(index_btree.root_page())
     }
 
-    fn scan_btree_node_for_text_index(
+    /// Build a fresh B-tree for a text index from every document visible to
+    /// `snapshot_id`, calling `progress` with the running count, and return its
+    /// root page
+    fn build_text_index(
         &self,
-        text_index: &mut crate::core::text_search::TextIndex,
-        page_num: PageNum,
-        fields: &[String],
-    ) -> Result<()> {
-        use crate::core::btree::{deserialize_node, NodeType};
+        text_meta: &crate::core::text_search::TextIndexMeta,
+        coll_root: PageNum,
+        snapshot_id: TransactionID,
+        progress: &mut dyn FnMut(u64),
+    ) -> Result<PageNum> {
+        use crate::core::text_search::TextIndex;
+
+        let index_btree = BTree::new(self.pager.clone())?;
+        let mut text_index = TextIndex::new(index_btree, text_meta.fields.clone(), text_meta.options.clone());
+        let mut indexed = 0u64;
+
+        self.for_each_visible_document(coll_root, snapshot_id, |doc_id, _, doc_map| {
+            // Extract text field values
+            let mut field_values = HashMap::new();
+            for field in &text_meta.fields {
+                if let Some(text) = doc_map.get(field).and_then(|value| value.as_str()) {
+                    field_values.insert(field.clone(), text.to_string());
+                }
+            }
+
+            // Index the document if it has any text fields
+            if !field_values.is_empty() {
+                text_index.index_document(doc_id, &field_values)?;
+            }
+
+            indexed += 1;
+            progress(indexed);
+            Ok(())
+        })?;
+
+        Ok(text_index.btree().root_page())
+    }
+
+    /// Call `f` with the id, page and body of every document in a collection
+    /// B-tree visible to `snapshot_id`, in id order
+    fn for_each_visible_document<F>(&self, coll_root: PageNum, snapshot_id: TransactionID, mut f: F) -> Result<()>
+    where
+        F: FnMut(&str, PageNum, &serde_json::Map<String, serde_json::Value>) -> Result<()>,
+    {
         use crate::core::document::read_versioned_document;
-        use serde_json::Value;
 
-        if page_num == 0 {
+        if coll_root == 0 {
             return Ok(());
         }
 
-        let page_data = self.pager.read_page(page_num)?;
-        let node = deserialize_node(page_num, &page_data)?;
-
-        if node.node_type == NodeType::LeafNode {
-            for entry in &node.entries {
-                let doc_page_num = entry.value;
-                let doc_id = &entry.key;
+        let primary = BTree::open(self.pager.clone(), coll_root);
+        let no_writes = HashMap::new();
+        let mut iter = primary.iterator()?;
+        while iter.next() {
+            let (doc_id, page_num) = iter.entry();
+            let vdoc = match read_versioned_document(&self.pager, page_num, &no_writes) {
+                Ok(vdoc) => vdoc,
+                Err(_) => continue,
+            };
+            if !vdoc.is_visible(snapshot_id) {
+                continue;
+            }
+            let doc_map = match decode_document(&vdoc.data) {
+                Ok(map) => map,
+                Err(_) => continue,
+            };
+            f(doc_id, page_num, &doc_map)?;
+        }
 
-                let vdoc = match read_versioned_document(&self.pager, doc_page_num, &std::collections::HashMap::new()) {
-                    Ok(vdoc) => vdoc,
-                    Err(_) => continue,
-                };
+        Ok(())
+    }
 
-                let doc_map: serde_json::Map<String, Value> = match serde_json::from_slice(&vdoc.data) {
-                    Ok(map) => map,
-                    Err(_) => continue,
-                };
+    /// Get a snapshot of current database metrics.
+    /// This is a zero-cost operation that reads atomic counters.
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
+    }
 
-                // Extract text field values
-                let mut field_values = std::collections::HashMap::new();
-                for field in fields {
-                    if let Some(value) = doc_map.get(field) {
-                        if let Some(text) = value.as_str() {
-                            field_values.insert(field.clone(), text.to_string());
-                        }
-                    }
-                }
+    /// Operation counters for a single collection since the database was opened:
+    /// inserts, updates, deletes, finds, and how many reads were served by an
+    /// index versus a full scan. Collections with no recorded activity report zeros.
+    pub fn collection_metrics(&self, name: &str) -> CollectionMetricsSnapshot {
+        self.metrics.collection_snapshot(name)
+    }
 
-                // Index the document if it has any text fields
-                if !field_values.is_empty() {
-                    text_index.index_document(doc_id, &field_values)?;
+    /// How often queries have used each index of a collection since the
+    /// database was opened, to find indexes that cost writes without ever
+    /// answering a query. Counts are kept in memory only and start over on
+    /// every open.
+    ///
+    /// An index counts as used whenever a query, sort, `distinct` or text
+    /// search reads it. Unique indexes are also checked on every write, which
+    /// isn't counted, so an unused unique index may still be worth keeping.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # let db = Database::open("my.db").unwrap();
+    /// for index in db.index_usage("users").unwrap() {
+    ///     if index.uses == 0 && !index.unique {
+    ///         println!("{} hasn't answered a query", index.name);
+    ///     }
+    /// }
+    /// ```
+    pub fn index_usage(&self, collection_name: &str) -> Result<Vec<IndexUsage>> {
+        let counters = self.metrics.collection(collection_name);
+        let mut usage: Vec<IndexUsage> = self.list_indexes(collection_name)?
+            .into_iter()
+            .map(|index| {
+                let (uses, last_used_millis) = counters.index_usage(&index.name);
+                IndexUsage {
+                    text: index.text_options.is_some(),
+                    name: index.name,
+                    fields: index.fields,
+                    unique: index.unique,
+                    uses,
+                    last_used_millis,
                 }
-            }
-
-            if node.next_leaf != 0 {
-                return self.scan_btree_node_for_text_index(text_index, node.next_leaf, fields);
-            }
-        } else {
-            for child_page in &node.children {
-                self.scan_btree_node_for_text_index(text_index, *child_page, fields)?;
-            }
-        }
+            })
+            .collect();
+        usage.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(usage)
+    }
 
-        Ok(())
+    /// Render current metrics, including per-operation latency histograms,
+    /// in the Prometheus text exposition format.
+    pub fn metrics_prometheus(&self) -> String {
+        self.metrics.snapshot().to_prometheus()
     }
 
-    /// Get a snapshot of current database metrics.
-    /// This is a zero-cost operation that reads atomic counters.
-    pub fn metrics(&self) -> MetricsSnapshot {
-        self.metrics.snapshot()
+    /// Clear the latency histograms without touching the counters.
+    pub fn reset_latency_metrics(&self) {
+        self.metrics.reset_latencies();
     }
 
     /// Get a reference to the internal metrics object for instrumentation.
@@ -1410,6 +2980,187 @@ impl Database {
         Ok(())
     }
 
+    /// Rewrite the database into a fresh file holding only live documents,
+    /// indexes and collection settings, then swap it in for the current file.
+    /// Dead MVCC versions and freed pages are dropped, so the file shrinks.
+    ///
+    /// Fails if any transaction is open, since its snapshot would point into
+    /// the old file. Transactions that try to begin during the vacuum wait for
+    /// it to finish.
+    ///
+    /// # Returns
+    /// The number of bytes the database file shrank by.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # let db = Database::open("my.db").unwrap();
+    /// let reclaimed = db.vacuum().unwrap();
+    /// println!("Reclaimed {} bytes", reclaimed);
+    /// ```
+    pub fn vacuum(&self) -> Result<u64> {
+        if self.read_only {
+            return Err(Error::Other("cannot vacuum: database is read-only".to_string()));
+        }
+
+        // Claim the checkpoint flag so no background checkpoint touches the file meanwhile
+        self.with_checkpoint_flag(|| self.vacuum_exclusive())
+    }
+
+    fn vacuum_exclusive(&self) -> Result<u64> {
+        let _commit_guard = self.commit_mu.lock()
+            .map_err(|_| Error::LockPoisoned { lock_name: "database.commit_mu".to_string() })?;
+        // begin() reads the metadata, so holding the write lock keeps new transactions out
+        let mut metadata = self.metadata.write()
+            .map_err(|_| Error::LockPoisoned { lock_name: "database.metadata".to_string() })?;
+
+        let open_txs = self.tx_manager.active_transaction_count()?
+            + self.tx_manager.read_only_transaction_count()?;
+        if open_txs > 0 {
+            return Err(Error::Other(format!(
+                "cannot vacuum: {} transaction(s) still open and holding snapshots", open_txs
+            )));
+        }
+
+        self.wal.checkpoint(&self.pager)?;
+        let size_before = std::fs::metadata(&self.path)?.len();
+
+        let vacuum_path = format!("{}.vacuum", self.path);
+        remove_database_files(&vacuum_path);
+        if let Err(e) = self.write_vacuumed_copy(&metadata, &vacuum_path) {
+            remove_database_files(&vacuum_path);
+            return Err(e);
+        }
+        // The copy was opened and closed on its own, only the main file is needed
+        let _ = std::fs::remove_file(format!("{}.lock", vacuum_path));
+        let _ = std::fs::remove_file(format!("{}-wal", vacuum_path));
+
+        std::fs::set_permissions(&vacuum_path, std::fs::metadata(&self.path)?.permissions())?;
+
+        let old_next_tx_id = self.pager.get_current_transaction_id()?
+            .max(self.tx_manager.get_latest_committed_tx_id() + 1);
+        std::fs::rename(&vacuum_path, &self.path)?;
+        self.pager.reopen(&self.path)?;
+
+        // The copy stamped documents with its own transaction IDs, so continue
+        // from whichever counter is further ahead to keep every copied version visible
+        let next_tx_id = self.pager.get_current_transaction_id()?.max(old_next_tx_id);
+        self.pager.set_next_transaction_id(next_tx_id)?;
+        self.pager.write_header()?;
+        self.tx_manager.initialize_from_pager(next_tx_id);
+
+        *metadata = Metadata::read(&self.pager, self.pager.metadata_page()?)?;
+
+        // Old versions pointed at pages of the previous file
+        self.version_chains.write()
+            .map_err(|_| Error::LockPoisoned { lock_name: "database.version_chains".to_string() })?
+            .clear();
+
+        // Rebuilding drops the IDs of deleted documents
+        if let Some(id_filters) = &self.id_filters {
+            *id_filters.write().recover_poison() = build_id_filters(&self.pager, &metadata)?;
+        }
+
+        let size_after = std::fs::metadata(&self.path)?.len();
+        Ok(size_before.saturating_sub(size_after))
+    }
+
+    /// Copy every collection's live documents, indexes and settings into a new
+    /// database at `dest_path`
+    fn write_vacuumed_copy(&self, metadata: &Metadata, dest_path: &str) -> Result<()> {
+        use crate::core::document::read_versioned_document;
+
+        const BATCH_SIZE: usize = 1000;
+
+        let opts = DatabaseOptions {
+            max_bulk_operations: self.max_bulk_operations,
+            max_document_size: self.max_document_size,
+            page_size: self.pager.file_page_size(),
+            page_checksums: self.pager.has_page_checksums(),
+            ..DatabaseOptions::default()
+        };
+        let dest = Database::open_with_options(dest_path, opts)?;
+
+        let snapshot_id = self.tx_manager.get_latest_committed_tx_id();
+        let no_writes = HashMap::new();
+
+        // The change log is copied last, so copying the other collections
+        // doesn't add records to it
+        let mut names: Vec<&String> = metadata.collections.keys().collect();
+        names.sort_by_key(|name| replication::is_log(name));
+
+        for name in names {
+            let coll_meta = &metadata.collections[name];
+            let mut tx = dest.begin()?;
+            tx.create_collection(name)?;
+            tx.commit()?;
+
+            if coll_meta.btree_root != 0 {
+                let btree = BTree::open(self.pager.clone(), coll_meta.btree_root);
+                let mut iter = btree.iterator()?;
+                let mut batch = Vec::with_capacity(BATCH_SIZE);
+
+                while iter.next() {
+                    let (_doc_id, page_num) = iter.entry();
+                    let vdoc = read_versioned_document(&self.pager, page_num, &no_writes)?;
+                    if !vdoc.is_visible(snapshot_id) {
+                        continue;
+                    }
+
+                    batch.push(decode_document(&vdoc.data)?);
+                    if batch.len() >= BATCH_SIZE.min(self.max_bulk_operations) {
+                        dest.collection(name).insert_many(std::mem::take(&mut batch))?;
+                    }
+                }
+
+                if !batch.is_empty() {
+                    dest.collection(name).insert_many(batch)?;
+                }
+            }
+
+            for index in coll_meta.indexes.values() {
+                let fields = index.get_fields();
+                if index.geo {
+                    dest.create_geo_index(name, &index.name, &fields[0], &fields[1])?;
+                    continue;
+                }
+                let fields: Vec<&str> = fields.iter().map(|f| f.as_str()).collect();
+                let options = IndexOptions {
+                    unique: index.unique,
+                    descending: index.descending,
+                    sparse: index.sparse,
+                    collation: index.collation,
+                };
+                if index.expression {
+                    dest.create_expression_index(name, &index.name, fields[0], options)?;
+                    continue;
+                }
+                dest.create_index_with_options(name, &index.name, &fields, options)?;
+            }
+
+            for text_index in coll_meta.text_indexes.values() {
+                let fields: Vec<&str> = text_index.fields.iter().map(|f| f.as_str()).collect();
+                dest.create_text_index(name, &text_index.name, &fields, text_index.options.clone())?;
+            }
+
+            if let Some(schema) = &coll_meta.schema {
+                dest.set_schema(name, schema.clone())?;
+            }
+
+            dest.update_metadata(|m| {
+                let dest_meta = m.get_collection(name);
+                dest_meta.id_strategy = coll_meta.id_strategy;
+                dest_meta.last_auto_id = coll_meta.last_auto_id;
+                dest_meta.default_sort = coll_meta.default_sort.clone();
+                dest_meta.soft_delete = coll_meta.soft_delete;
+            })?;
+        }
+
+        dest.checkpoint()?;
+        dest.close()?;
+        Ok(())
+    }
+
     /// Verify a backup file by checking its magic number and metadata.
     ///
     /// # Arguments
@@ -1430,8 +3181,8 @@ impl Database {
         // Open the backup file read-only
         let mut file = std::fs::File::open(backup_path)?;
 
-        // Read and verify the header (first page)
-        let mut header_buf = vec![0u8; PAGE_SIZE];
+        // Read and verify the header (the fields are within the smallest possible page)
+        let mut header_buf = vec![0u8; MIN_PAGE_SIZE];
         file.read_exact(&mut header_buf)?;
 
         // Check magic number
@@ -1464,8 +3215,7 @@ impl Database {
         let num_collections = if metadata_page > 0 {
             // Open in temporary read-only mode to read metadata
             let temp_pager = Pager::open(backup_path, 100, 0o644, true)?;
-            let meta_data = temp_pager.read_page(metadata_page)?;
-            let metadata = Metadata::deserialize(&meta_data)?;
+            let metadata = Metadata::read(&temp_pager, metadata_page)?;
             metadata.collections.len()
         } else {
             0
@@ -1500,6 +3250,28 @@ impl Database {
     /// ```
     #[cfg(feature = "web-ui")]
     pub fn start_web_ui(&self, addr: &str) -> Result<crate::core::web_server::WebServer> {
+        self.start_web_ui_with_options(addr, crate::core::web_server::WebServerOptions::default())
+    }
+
+    /// Start the web UI server with authentication, CORS and read-only settings.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use jasonisnthappy::{Database, WebServerOptions};
+    /// # let db = Database::open("my.db").unwrap();
+    /// let options = WebServerOptions {
+    ///     auth_token: Some("s3cret".to_string()),
+    ///     allowed_origins: vec!["http://localhost:3000".to_string()],
+    ///     read_only: true,
+    /// };
+    /// let web_server = db.start_web_ui_with_options("127.0.0.1:8080", options).unwrap();
+    /// ```
+    #[cfg(feature = "web-ui")]
+    pub fn start_web_ui_with_options(
+        &self,
+        addr: &str,
+        options: crate::core::web_server::WebServerOptions,
+    ) -> Result<crate::core::web_server::WebServer> {
         let db = Arc::new(Self {
             pager: self.pager.clone(),
             wal: self.wal.clone(),
@@ -1523,13 +3295,246 @@ impl Database {
             max_bulk_operations: self.max_bulk_operations,
             max_document_size: self.max_document_size,
             max_request_body_size: self.max_request_body_size,
+            document_metadata: self.document_metadata,
+            scan_threads: self.scan_threads,
+            max_scan_documents: self.max_scan_documents,
+            max_result_documents: self.max_result_documents,
+            collect_all_validation_errors: self.collect_all_validation_errors,
+            strict_query_types: self.strict_query_types,
+            max_wal_bytes: self.max_wal_bytes,
+            id_filters: self.id_filters.clone(),
+            slow_queries: self.slow_queries.clone(),
+            sync_policy: self.sync_policy.clone(),
+            events: self.events.clone(),
+            query_cache: self.query_cache.clone(),
         });
 
-        crate::core::web_server::WebServer::start(db, addr)
+        crate::core::web_server::WebServer::start(db, addr, options)
             .map_err(|e| Error::Other(format!("Failed to start web UI: {}", e)))
     }
 }
 
+/// How often `lock_database_file` retries while another process holds the lock
+const LOCK_RETRY_INTERVAL_MS: u64 = 10;
+
+/// Take the advisory lock on a database's lock file: shared for a read-only
+/// open, exclusive for a writer. While another process holds an incompatible
+/// lock, retry for up to `timeout_ms` before failing with `Error::DatabaseLocked`.
+/// Copy the documents `Database::repair` found into a new database at
+/// `dest_path`, laid out like the damaged file
+fn write_repaired_copy(pager: &Pager, trees: &[RecoveredTree], dest_path: &str) -> Result<()> {
+    use crate::core::document::read_versioned_document;
+
+    const BATCH_SIZE: usize = 1000;
+
+    let opts = DatabaseOptions {
+        page_size: pager.file_page_size(),
+        page_checksums: pager.has_page_checksums(),
+        ..DatabaseOptions::default()
+    };
+    let dest = Database::open_with_options(dest_path, opts)?;
+    let no_writes = HashMap::new();
+
+    for tree in trees {
+        let mut tx = dest.begin()?;
+        tx.create_collection(&tree.name)?;
+        tx.commit()?;
+
+        for pages in tree.doc_pages.chunks(BATCH_SIZE.min(dest.max_bulk_operations)) {
+            let mut batch = Vec::with_capacity(pages.len());
+            for &page in pages {
+                let vdoc = read_versioned_document(pager, page, &no_writes)?;
+                batch.push(decode_document(&vdoc.data)?);
+            }
+            dest.collection(&tree.name).insert_many(batch)?;
+        }
+    }
+
+    dest.checkpoint()?;
+    dest.close()?;
+    Ok(())
+}
+
+/// Copy committed WAL frames into the database file, along with the page
+/// count and metadata page the last header frame recorded
+fn replay_wal(pager: &Pager, wal: &WAL) -> Result<()> {
+    if wal.frame_count() == 0 {
+        return Ok(());
+    }
+
+    let frames = wal.read_all_frames()?;
+
+    let mut latest_meta_page: Option<u64> = None;
+    let mut latest_num_pages: Option<u64> = None;
+    let mut latest_header: Option<&[u8]> = None;
+    for frame in &frames {
+        if frame.page_num == 0 && frame.page_data.len() >= 32 {
+            latest_header = Some(&frame.page_data);
+            let meta_page_bytes: [u8; 8] = frame.page_data[24..32].try_into()
+                .map_err(|_| Error::DataCorruption {
+                    details: "invalid metadata page in WAL frame".to_string()
+                })?;
+            let meta_page = u64::from_le_bytes(meta_page_bytes);
+            if meta_page > 0 {
+                latest_meta_page = Some(meta_page);
+            }
+
+            let num_pages_bytes: [u8; 8] = frame.page_data[12..20].try_into()
+                .map_err(|_| Error::DataCorruption {
+                    details: "invalid num_pages in WAL frame".to_string()
+                })?;
+            let num_pages = u64::from_le_bytes(num_pages_bytes);
+            latest_num_pages = Some(num_pages);
+        }
+    }
+
+    let mut max_page = 0u64;
+    for frame in &frames {
+        if frame.page_num > max_page {
+            max_page = frame.page_num;
+        }
+    }
+
+    if let Some(num_pages) = latest_num_pages {
+        pager.set_num_pages(num_pages)?;
+    } else if max_page >= pager.num_pages()? {
+        pager.set_num_pages(max_page + 1)?;
+    }
+
+    if let Some(header) = latest_header.filter(|header| header.len() >= 40) {
+        pager.restore_logged_header(header)?;
+    }
+    // The header frame is logged before its commit's ID is handed on
+    let max_tx_id = frames.iter().map(|frame| frame.tx_id).max().unwrap_or(0);
+    if pager.get_current_transaction_id()? <= max_tx_id {
+        pager.set_next_transaction_id(max_tx_id + 1)?;
+    }
+
+    wal.checkpoint(pager)?;
+
+    if let Some(meta_page) = latest_meta_page {
+        pager.set_metadata_page(meta_page)?;
+        pager.write_header()?;
+    }
+
+    Ok(())
+}
+
+fn lock_database_file(lock_file: &File, shared: bool, timeout_ms: u64) -> Result<()> {
+    let deadline = std::time::Instant::now() + std::time::Duration::from_millis(timeout_ms);
+    loop {
+        let result = if shared {
+            FileExt::try_lock_shared(lock_file)
+        } else {
+            FileExt::try_lock_exclusive(lock_file)
+        };
+        match result {
+            Ok(()) => return Ok(()),
+            Err(e) if e.kind() == fs2::lock_contended_error().kind() => {
+                let now = std::time::Instant::now();
+                if now >= deadline {
+                    return Err(Error::DatabaseLocked);
+                }
+                let wait = std::time::Duration::from_millis(LOCK_RETRY_INTERVAL_MS).min(deadline - now);
+                std::thread::sleep(wait);
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Remove a database file along with its lock and WAL files, ignoring missing ones
+fn remove_database_files(path: &str) {
+    let _ = std::fs::remove_file(path);
+    let _ = std::fs::remove_file(format!("{}.lock", path));
+    let _ = std::fs::remove_file(format!("{}-wal", path));
+}
+
+/// Fill in document counts for collections written before counts were tracked.
+/// Every key in the primary B-tree is a live document once the WAL is replayed.
+fn rebuild_document_counts(pager: &Arc<Pager>, metadata: &mut Metadata) -> Result<()> {
+    for coll_meta in metadata.collections.values_mut() {
+        if coll_meta.document_count.is_some() {
+            continue;
+        }
+        let mut count = 0;
+        if coll_meta.btree_root != 0 {
+            let btree = BTree::open(pager.clone(), coll_meta.btree_root);
+            let mut iter = btree.iterator()?;
+            while iter.next() {
+                count += 1;
+            }
+        }
+        coll_meta.document_count = Some(count);
+    }
+    Ok(())
+}
+
+/// Bloom filter of every `_id` in each collection's primary B-tree
+fn build_id_filters(pager: &Arc<Pager>, metadata: &Metadata) -> Result<HashMap<String, IdFilter>> {
+    let mut filters = HashMap::new();
+    for (name, coll_meta) in &metadata.collections {
+        let expected = coll_meta.document_count.unwrap_or(0) as usize;
+        let mut filter = IdFilter::with_capacity(expected);
+        if coll_meta.btree_root != 0 {
+            let btree = BTree::open(pager.clone(), coll_meta.btree_root);
+            let mut iter = btree.iterator()?;
+            while iter.next() {
+                filter.insert(iter.entry().0);
+            }
+        }
+        filters.insert(name.clone(), filter);
+    }
+    Ok(filters)
+}
+
+/// Describe the regular and text indexes of a collection
+/// First pages of superseded document versions still held for open snapshots
+fn held_version_pages(version_chains: &HashMap<String, HashMap<String, VersionChain>>) -> Result<Vec<PageNum>> {
+    let mut version_pages = Vec::new();
+    for chain in version_chains.values().flat_map(|doc_chains| doc_chains.values()) {
+        version_pages.extend(chain.get_versions()?.into_iter().map(|version| version.page_num));
+    }
+    Ok(version_pages)
+}
+
+fn index_infos(coll_meta: &CollectionMeta) -> Vec<IndexInfo> {
+    let mut indexes = Vec::new();
+    for (idx_name, idx_meta) in &coll_meta.indexes {
+        indexes.push(IndexInfo {
+            name: idx_name.clone(),
+            fields: idx_meta.fields.clone(),
+            unique: idx_meta.unique,
+            descending: idx_meta.descending,
+            sparse: idx_meta.sparse,
+            geo: idx_meta.geo,
+            expression: idx_meta.expression,
+            collation: idx_meta.collation,
+            btree_root: idx_meta.btree_root,
+            text_options: None,
+            entry_count: 0,
+            size_bytes: 0,
+        });
+    }
+    for (idx_name, text_meta) in &coll_meta.text_indexes {
+        indexes.push(IndexInfo {
+            name: idx_name.clone(),
+            fields: text_meta.fields.clone(),
+            unique: false,
+            descending: false,
+            sparse: false,
+            geo: false,
+            expression: false,
+            collation: Collation::Binary,
+            btree_root: text_meta.btree_root,
+            text_options: Some(text_meta.options.clone()),
+            entry_count: 0,
+            size_bytes: 0,
+        });
+    }
+    indexes
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1808,6 +3813,175 @@ mod tests {
         let _ = fs::remove_file(format!("{}-wal", path));
     }
 
+    #[test]
+    fn test_collection_and_index_exists() {
+        use serde_json::json;
+
+        let path = "/tmp/test_collection_exists.db";
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}.lock", path));
+        let _ = fs::remove_file(format!("{}-wal", path));
+
+        let db = Database::open(path).unwrap();
+
+        // Getting a handle doesn't create the collection
+        let users = db.collection("users");
+        assert!(!db.collection_exists("users"));
+        assert!(!users.exists());
+
+        users.insert(json!({"name": "Alice"})).unwrap();
+        assert!(db.collection_exists("users"));
+        assert!(users.exists());
+
+        assert!(!db.index_exists("users", "name_idx"));
+        db.create_index("users", "name_idx", "name", false).unwrap();
+        assert!(db.index_exists("users", "name_idx"));
+        assert!(!db.index_exists("posts", "name_idx"));
+
+        db.drop_index("users", "name_idx").unwrap();
+        assert!(!db.index_exists("users", "name_idx"));
+
+        db.close().unwrap();
+
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}.lock", path));
+        let _ = fs::remove_file(format!("{}-wal", path));
+    }
+
+    #[test]
+    fn test_copy_and_rename_collection() {
+        use crate::core::text_search::TextIndexOptions;
+        use serde_json::json;
+
+        let path = "/tmp/test_copy_collection.db";
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}.lock", path));
+        let _ = fs::remove_file(format!("{}-wal", path));
+
+        {
+            let db = Database::open(path).unwrap();
+            let users = db.collection("users");
+            let ids: Vec<String> = (0..50)
+                .map(|n| users.insert(json!({"email": format!("u{}@x.io", n), "bio": format!("likes rust {}", n)})).unwrap())
+                .collect();
+            db.create_index("users", "by_email", "email", true).unwrap();
+            db.create_text_index("users", "bio_text", &["bio"], TextIndexOptions::default()).unwrap();
+            db.set_default_sort("users", &[("email", SortOrder::Asc)]).unwrap();
+
+            db.copy_collection("users", "users_v2").unwrap();
+            let copy = db.collection("users_v2");
+            assert_eq!(copy.count().unwrap(), 50);
+            assert_eq!(copy.find_by_id(&ids[7]).unwrap()["email"], "u7@x.io");
+            assert!(db.index_exists("users_v2", "by_email"));
+            assert!(copy.insert(json!({"email": "u3@x.io"})).is_err());
+            assert_eq!(copy.search("rust").unwrap().len(), 50);
+            assert_eq!(db.get_default_sort("users_v2"), db.get_default_sort("users"));
+
+            // The copy is independent of its source
+            copy.delete_by_id(&ids[0]).unwrap();
+            assert_eq!(users.count().unwrap(), 50);
+
+            assert!(matches!(db.copy_collection("users", "users_v2"), Err(Error::CollectionAlreadyExists { .. })));
+            assert!(matches!(db.copy_collection("missing", "other"), Err(Error::CollectionDoesNotExist { .. })));
+            assert!(!db.collection_exists("other"));
+
+            db.rename_collection("users", "users_old").unwrap();
+            assert!(!db.collection_exists("users"));
+        }
+
+        let db = Database::open(path).unwrap();
+        assert_eq!(db.list_collections().unwrap(), vec!["users_old", "users_v2"]);
+        assert_eq!(db.collection("users_old").count().unwrap(), 50);
+        assert_eq!(db.collection("users_v2").count().unwrap(), 49);
+        assert!(db.index_exists("users_v2", "by_email"));
+        db.close().unwrap();
+
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}.lock", path));
+        let _ = fs::remove_file(format!("{}-wal", path));
+    }
+
+    #[test]
+    fn test_id_bloom_filter() {
+        use serde_json::json;
+
+        let path = "/tmp/test_id_bloom_filter.db";
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}.lock", path));
+        let _ = fs::remove_file(format!("{}-wal", path));
+
+        let opts = DatabaseOptions {
+            enable_id_bloom_filter: true,
+            ..Default::default()
+        };
+        let open = || Database::open_with_options(path, opts.clone()).unwrap();
+
+        let db = open();
+        let users = db.collection("users");
+        let ids: Vec<String> = (0..2_000)
+            .map(|i| users.insert(json!({"n": i})).unwrap())
+            .collect();
+        let ruled_out = |db: &Database, collection: &str| {
+            (0..1_000).filter(|i| !db.may_contain_id(collection, &format!("missing-{}", i))).count()
+        };
+
+        assert!(ids.iter().all(|id| db.may_contain_id("users", id)));
+        assert!(ruled_out(&db, "users") > 950);
+        assert!(matches!(users.find_by_id("missing-1"), Err(Error::NotFound)));
+        assert_eq!(users.find_by_ids(&[ids[0].as_str(), "missing-1"]).unwrap()[1], None);
+
+        // Uncommitted writes are visible to their own transaction
+        let mut tx = db.begin().unwrap();
+        let id = tx.collection("users").unwrap().insert(json!({"_id": "in-tx"})).unwrap();
+        assert_eq!(tx.collection("users").unwrap().find_by_id(&id).unwrap()["_id"], "in-tx");
+        tx.commit().unwrap();
+        assert!(users.find_by_id("in-tx").is_ok());
+
+        // The filter follows renames and is dropped with its collection
+        let mut tx = db.begin().unwrap();
+        tx.rename_collection("users", "people").unwrap();
+        tx.commit().unwrap();
+        assert!(db.collection("people").find_by_id(&ids[5]).is_ok());
+        assert!(db.may_contain_id("users", "missing-1"));
+
+        let mut tx = db.begin().unwrap();
+        tx.drop_collection("people").unwrap();
+        tx.commit().unwrap();
+        let people = db.collection("people");
+        people.insert(json!({"_id": "fresh"})).unwrap();
+        assert!(people.find_by_id("fresh").is_ok());
+        assert!(ruled_out(&db, "people") > 950);
+        db.close().unwrap();
+
+        // Reopening rebuilds the filter from the stored IDs
+        let db = open();
+        let people = db.collection("people");
+        assert!(people.find_by_id("fresh").is_ok());
+        assert!(ruled_out(&db, "people") > 950);
+
+        // Deleted IDs stay in the filter until a vacuum rebuilds it
+        let gone: Vec<String> = (0..200)
+            .map(|i| people.insert(json!({"_id": format!("gone-{}", i)})).unwrap())
+            .collect();
+        for id in &gone {
+            people.delete_by_id(id).unwrap();
+        }
+        assert!(gone.iter().all(|id| db.may_contain_id("people", id)));
+        db.vacuum().unwrap();
+        assert!(gone.iter().filter(|id| db.may_contain_id("people", id)).count() < 20);
+        assert!(people.find_by_id("fresh").is_ok());
+        db.close().unwrap();
+
+        // Without the option nothing is ruled out
+        let db = Database::open(path).unwrap();
+        assert!(db.may_contain_id("people", "missing-1"));
+        db.close().unwrap();
+
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}.lock", path));
+        let _ = fs::remove_file(format!("{}-wal", path));
+    }
+
     #[test]
     fn test_collection_stats() {
         use serde_json::json;
@@ -1831,6 +4005,16 @@ mod tests {
         assert_eq!(stats.document_count, 3);
         assert!(stats.btree_root > 0);
         assert_eq!(stats.indexes.len(), 0); // No indexes yet
+        assert!(stats.data_size_bytes > 0);
+        assert_eq!(stats.avg_document_size, stats.data_size_bytes as f64 / 3.0);
+
+        db.create_index("users", "age_idx", "age", false).unwrap();
+        let stats = db.collection_stats("users").unwrap();
+        assert_eq!(stats.indexes[0].entry_count, 3);
+        assert_eq!(stats.indexes[0].size_bytes, db.page_size() as u64);
+        assert_eq!(db.list_indexes("users").unwrap()[0].entry_count, 0);
+        let info = db.info().unwrap();
+        assert_eq!(info.collections[0].indexes[0].entry_count, 3);
 
         // Test non-existent collection
         let result = db.collection_stats("nonexistent");
@@ -1843,6 +4027,110 @@ mod tests {
         let _ = fs::remove_file(format!("{}-wal", path));
     }
 
+    #[test]
+    fn test_collection_metrics() {
+        use serde_json::json;
+
+        let path = "/tmp/test_collection_metrics.db";
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}.lock", path));
+        let _ = fs::remove_file(format!("{}-wal", path));
+
+        let db = Database::open(path).unwrap();
+        let users = db.collection("users");
+        let events = db.collection("events");
+
+        let id = users.insert(json!({"name": "Alice"})).unwrap();
+        users.find_by_id(&id).unwrap();
+        users.update_by_id(&id, json!({"name": "Alicia"})).unwrap();
+
+        events.insert_many(vec![json!({"n": 1}), json!({"n": 2}), json!({"n": 3})]).unwrap();
+        events.find("n > 1").unwrap();
+        events.delete("n is 3").unwrap();
+
+        let users_metrics = db.collection_metrics("users");
+        assert_eq!(users_metrics.inserts, 1);
+        assert_eq!(users_metrics.updates, 1);
+        assert_eq!(users_metrics.finds, 1);
+        assert_eq!(users_metrics.index_hits, 1);
+        assert_eq!(users_metrics.full_scans, 0);
+
+        // find() and delete() each walk all three documents
+        let events_metrics = db.collection_metrics("events");
+        assert_eq!(events_metrics.inserts, 3);
+        assert_eq!(events_metrics.deletes, 1);
+        assert_eq!(events_metrics.finds, 2);
+        assert_eq!(events_metrics.full_scans, 2);
+        assert_eq!(events_metrics.documents_scanned, 6);
+        assert_eq!(events_metrics.index_hits, 0);
+
+        assert_eq!(db.collection_stats("events").unwrap().metrics, events_metrics);
+        assert_eq!(db.collection_metrics("unknown"), CollectionMetricsSnapshot::default());
+
+        let mut tx = db.begin().unwrap();
+        tx.rename_collection("events", "archived").unwrap();
+        tx.commit().unwrap();
+        assert_eq!(db.collection_metrics("archived").inserts, 3);
+        assert_eq!(db.collection_metrics("events"), CollectionMetricsSnapshot::default());
+
+        db.close().unwrap();
+
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}.lock", path));
+        let _ = fs::remove_file(format!("{}-wal", path));
+    }
+
+    #[test]
+    fn test_estimated_count() {
+        use serde_json::json;
+
+        let path = "/tmp/test_estimated_count.db";
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}.lock", path));
+        let _ = fs::remove_file(format!("{}-wal", path));
+
+        let db = Database::open(path).unwrap();
+        let items = db.collection("items");
+        assert_eq!(items.estimated_count().unwrap(), 0);
+
+        let ids = items.insert_many((0..5).map(|n| json!({"n": n})).collect()).unwrap();
+        let id = items.insert(json!({"n": 5})).unwrap();
+        items.update_by_id(&id, json!({"n": 6})).unwrap();
+        items.delete_by_id(&ids[0]).unwrap();
+        items.delete("n is 1").unwrap();
+        assert_eq!(items.estimated_count().unwrap(), 4);
+
+        // Uncommitted writes don't show up, rolled back ones never do
+        let mut tx = db.begin().unwrap();
+        tx.collection("items").unwrap().insert(json!({"n": 7})).unwrap();
+        tx.savepoint("sp").unwrap();
+        tx.collection("items").unwrap().delete_by_id(&ids[2]).unwrap();
+        tx.collection("items").unwrap().delete_by_id(&ids[3]).unwrap();
+        assert_eq!(items.estimated_count().unwrap(), 4);
+        tx.rollback_to_savepoint("sp").unwrap();
+        tx.commit().unwrap();
+        assert_eq!(items.estimated_count().unwrap(), 5);
+
+        let mut tx = db.begin().unwrap();
+        tx.collection("items").unwrap().insert(json!({"n": 8})).unwrap();
+        tx.rollback().unwrap();
+        assert_eq!(items.estimated_count().unwrap(), 5);
+        assert_eq!(items.estimated_count().unwrap(), items.count().unwrap());
+
+        // Databases written before counts were tracked get them rebuilt on open
+        db.update_metadata(|m| m.get_collection("items").document_count = None).unwrap();
+        db.close().unwrap();
+
+        let db = Database::open(path).unwrap();
+        assert_eq!(db.collection("items").estimated_count().unwrap(), 5);
+        assert_eq!(db.collection("missing").estimated_count().unwrap(), 0);
+        db.close().unwrap();
+
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}.lock", path));
+        let _ = fs::remove_file(format!("{}-wal", path));
+    }
+
     #[test]
     fn test_list_indexes() {
         use serde_json::json;
@@ -1981,4 +4269,33 @@ mod tests {
         let _ = fs::remove_file(format!("{}.lock", path));
         let _ = fs::remove_file(format!("{}-wal", path));
     }
+
+    #[test]
+    fn test_retry_backoff_bounds() {
+        use std::time::Duration;
+
+        let config = TransactionConfig {
+            retry_backoff_base_ms: 2,
+            max_retry_backoff_ms: 50,
+            min_retry_backoff_ms: 3,
+            ..TransactionConfig::default()
+        };
+        let ms = |attempt| config.retry_backoff(attempt).as_millis();
+        assert_eq!(ms(0), 3);
+        assert_eq!(ms(1), 4);
+        assert_eq!(ms(3), 16);
+        assert_eq!(ms(5), 50);
+        assert_eq!(ms(usize::MAX), 50);
+
+        let config = TransactionConfig { jitter: true, ..config };
+        for attempt in 0..10 {
+            for _ in 0..50 {
+                let backoff = config.retry_backoff(attempt);
+                assert!(backoff >= Duration::from_millis(3), "{:?}", backoff);
+                assert!(backoff <= Duration::from_millis(50), "{:?}", backoff);
+            }
+        }
+        let config = TransactionConfig { min_retry_backoff_ms: 0, ..config };
+        assert!(config.retry_backoff(1) <= Duration::from_millis(4));
+    }
 }
@@ -0,0 +1,216 @@
+//! Timestamp parsing shared by date queries and date grouping
+//!
+//! Everything is normalized to milliseconds since the Unix epoch in UTC.
+//! Accepted inputs:
+//! - RFC3339 strings such as `2024-01-15T10:30:00Z` or `2024-01-15T10:30:00.250+02:00`
+//!   (a space may stand in for the `T`)
+//! - Plain dates such as `2024-01-15`, taken as midnight UTC
+//! - JSON numbers, taken as Unix epoch **seconds** (fractions allowed)
+//!
+//! Offsets are applied when parsing and discarded afterwards, so two strings
+//! naming the same instant compare equal and always land in the same UTC bucket.
+
+use serde_json::Value;
+
+const MILLIS_PER_DAY: i64 = 86_400_000;
+
+/// Bucket size for grouping timestamps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateGranularity {
+    /// `YYYY-MM-DD`
+    Day,
+    /// `YYYY-MM`
+    Month,
+    /// `YYYY`
+    Year,
+}
+
+impl DateGranularity {
+    /// Parse `"day"`, `"month"` or `"year"` (case-insensitive)
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "day" => Some(DateGranularity::Day),
+            "month" => Some(DateGranularity::Month),
+            "year" => Some(DateGranularity::Year),
+            _ => None,
+        }
+    }
+
+    /// UTC bucket label for a timestamp in epoch milliseconds
+    pub fn bucket(&self, millis: i64) -> String {
+        let (year, month, day) = civil_from_days(millis.div_euclid(MILLIS_PER_DAY));
+        match self {
+            DateGranularity::Day => format!("{:04}-{:02}-{:02}", year, month, day),
+            DateGranularity::Month => format!("{:04}-{:02}", year, month),
+            DateGranularity::Year => format!("{:04}", year),
+        }
+    }
+}
+
+/// Milliseconds since the epoch for a JSON timestamp, or None if it isn't one
+pub fn timestamp_millis(value: &Value) -> Option<i64> {
+    match value {
+        Value::String(s) => parse_rfc3339(s),
+        Value::Number(n) => {
+            let secs = n.as_f64()?;
+            let millis = (secs * 1000.0).round();
+            if millis.is_finite() && millis.abs() < i64::MAX as f64 {
+                Some(millis as i64)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Parse an RFC3339 timestamp or a plain `YYYY-MM-DD` date
+pub fn parse_rfc3339(s: &str) -> Option<i64> {
+    let bytes = s.as_bytes();
+    if bytes.len() < 10 || bytes[4] != b'-' || bytes[7] != b'-' {
+        return None;
+    }
+
+    let year = parse_digits(&bytes[0..4])?;
+    let month = parse_digits(&bytes[5..7])?;
+    let day = parse_digits(&bytes[8..10])?;
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+    let days = days_from_civil(year, month, day);
+
+    if bytes.len() == 10 {
+        return Some(days * MILLIS_PER_DAY);
+    }
+
+    // Time part: THH:MM:SS[.fraction](Z|+HH:MM|-HH:MM)
+    if !matches!(bytes[10], b'T' | b't' | b' ') || bytes.len() < 20 {
+        return None;
+    }
+    if bytes[13] != b':' || bytes[16] != b':' {
+        return None;
+    }
+    let hour = parse_digits(&bytes[11..13])?;
+    let minute = parse_digits(&bytes[14..16])?;
+    // Allow a leap second to parse; it simply rolls into the next minute
+    let second = parse_digits(&bytes[17..19])?;
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let mut pos = 19;
+    let mut millis = 0;
+    if bytes[pos] == b'.' {
+        pos += 1;
+        let start = pos;
+        while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+            pos += 1;
+        }
+        if pos == start {
+            return None;
+        }
+        // Only millisecond precision is kept
+        let frac = &bytes[start..pos.min(start + 3)];
+        millis = parse_digits(frac)? * 10i64.pow(3 - frac.len() as u32);
+    }
+
+    let offset_minutes = match bytes.get(pos)? {
+        b'Z' | b'z' if pos + 1 == bytes.len() => 0,
+        sign @ (b'+' | b'-') if pos + 6 == bytes.len() && bytes[pos + 3] == b':' => {
+            let oh = parse_digits(&bytes[pos + 1..pos + 3])?;
+            let om = parse_digits(&bytes[pos + 4..pos + 6])?;
+            if oh > 23 || om > 59 {
+                return None;
+            }
+            let offset = oh * 60 + om;
+            if *sign == b'-' { -offset } else { offset }
+        }
+        _ => return None,
+    };
+
+    let secs = days * 86_400 + hour * 3_600 + minute * 60 + second - offset_minutes * 60;
+    Some(secs * 1000 + millis)
+}
+
+fn parse_digits(bytes: &[u8]) -> Option<i64> {
+    if bytes.is_empty() || !bytes.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    Some(bytes.iter().fold(0, |acc, b| acc * 10 + (b - b'0') as i64))
+}
+
+fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date (Howard Hinnant's algorithm)
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Inverse of `days_from_civil`
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_rfc3339() {
+        assert_eq!(parse_rfc3339("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(parse_rfc3339("1970-01-01"), Some(0));
+        assert_eq!(parse_rfc3339("2024-01-01T00:00:00Z"), Some(1_704_067_200_000));
+        assert_eq!(parse_rfc3339("2024-01-01T02:00:00+02:00"), Some(1_704_067_200_000));
+        assert_eq!(parse_rfc3339("2023-12-31T19:00:00.5-05:00"), Some(1_704_067_200_500));
+        assert_eq!(parse_rfc3339("2024-01-01 00:00:00.123456z"), Some(1_704_067_200_123));
+        assert_eq!(parse_rfc3339("1969-12-31T23:59:59Z"), Some(-1000));
+
+        assert_eq!(parse_rfc3339("2024-02-30"), None);
+        assert_eq!(parse_rfc3339("2024-01-01T00:00:00"), None);
+        assert_eq!(parse_rfc3339("2024-01-01T24:00:00Z"), None);
+        assert_eq!(parse_rfc3339("not a date"), None);
+    }
+
+    #[test]
+    fn test_timestamp_millis_from_epoch_seconds() {
+        assert_eq!(timestamp_millis(&json!(1_704_067_200)), Some(1_704_067_200_000));
+        assert_eq!(timestamp_millis(&json!(1.5)), Some(1500));
+        assert_eq!(timestamp_millis(&json!(true)), None);
+    }
+
+    #[test]
+    fn test_buckets_are_utc() {
+        let millis = parse_rfc3339("2024-03-01T01:30:00+03:00").unwrap();
+        assert_eq!(DateGranularity::Day.bucket(millis), "2024-02-29");
+        assert_eq!(DateGranularity::Month.bucket(millis), "2024-02");
+        assert_eq!(DateGranularity::Year.bucket(millis), "2024");
+        assert_eq!(DateGranularity::Day.bucket(-1), "1969-12-31");
+    }
+}
@@ -40,7 +40,7 @@ pub fn write_document(
     let id_bytes = doc_id.as_bytes();
     let total_size = DOC_ID_LEN_SIZE + id_bytes.len() + DATA_LEN_SIZE + data.len() + OVERFLOW_SIZE;
 
-    if total_size <= PAGE_SIZE {
+    if total_size <= pager.page_size() {
         write_single_page(pager, doc_id, data)
     } else {
         write_multi_page(pager, doc_id, data)
@@ -54,7 +54,7 @@ fn write_single_page(
 ) -> Result<PageNum> {
     let page_num = pager.alloc_page()?;
 
-    let mut buf = crate::core::buffer_pool::get_page_buffer();
+    let mut buf = crate::core::buffer_pool::get_page_buffer(pager.page_size());
     let mut offset = 0;
 
     let id_bytes = doc_id.as_bytes();
@@ -83,14 +83,16 @@ fn write_multi_page(
 ) -> Result<PageNum> {
     let first_page = pager.alloc_page()?;
     let id_bytes = doc_id.as_bytes();
+    let page_size = pager.page_size();
+    let max_overflow_data = page_size - OVERFLOW_SIZE;
 
-    let first_page_available = PAGE_SIZE - DOC_ID_LEN_SIZE - id_bytes.len() - DATA_LEN_SIZE - OVERFLOW_SIZE;
+    let first_page_available = page_size - DOC_ID_LEN_SIZE - id_bytes.len() - DATA_LEN_SIZE - OVERFLOW_SIZE;
     let first_chunk_size = first_page_available.min(data.len());
 
     let mut chunks = Vec::new();
     let mut remaining = &data[first_chunk_size..];
     while !remaining.is_empty() {
-        let chunk_size = remaining.len().min(MAX_OVERFLOW_DATA);
+        let chunk_size = remaining.len().min(max_overflow_data);
         chunks.push(&remaining[..chunk_size]);
         remaining = &remaining[chunk_size..];
     }
@@ -99,17 +101,17 @@ fn write_multi_page(
     for chunk in chunks.iter().rev() {
         let overflow_page = pager.alloc_page()?;
 
-        let mut overflow_buf = crate::core::buffer_pool::get_page_buffer();
+        let mut overflow_buf = crate::core::buffer_pool::get_page_buffer(page_size);
 
         overflow_buf[..chunk.len()].copy_from_slice(chunk);
-        overflow_buf[PAGE_SIZE - 8..].copy_from_slice(&next_overflow.to_le_bytes());
+        overflow_buf[page_size - 8..].copy_from_slice(&next_overflow.to_le_bytes());
 
         pager.write_page_transfer(overflow_page, overflow_buf)?;
 
         next_overflow = overflow_page;
     }
 
-    let mut first_buf = crate::core::buffer_pool::get_page_buffer();
+    let mut first_buf = crate::core::buffer_pool::get_page_buffer(page_size);
     let mut offset = 0;
 
     first_buf[offset..offset + 2].copy_from_slice(&(id_bytes.len() as u16).to_le_bytes());
@@ -123,7 +125,7 @@ fn write_multi_page(
 
     first_buf[offset..offset + first_chunk_size].copy_from_slice(&data[..first_chunk_size]);
 
-    let overflow_offset = PAGE_SIZE - 8;
+    let overflow_offset = page_size - 8;
     first_buf[overflow_offset..overflow_offset + 8].copy_from_slice(&next_overflow.to_le_bytes());
 
     pager.write_page_transfer(first_page, first_buf)?;
@@ -131,6 +133,9 @@ fn write_multi_page(
 }
 
 pub fn read_document(pager: &Pager, page_num: PageNum) -> Result<Document> {
+    pager.record_document_read();
+    let page_size = pager.page_size();
+    let max_overflow_data = page_size - OVERFLOW_SIZE;
     let page_data = pager.read_page(page_num)?;
     let mut offset = 0;
 
@@ -161,7 +166,7 @@ pub fn read_document(pager: &Pager, page_num: PageNum) -> Result<Document> {
         return Err(Error::InvalidDocument);
     }
 
-    let overflow_offset = PAGE_SIZE - 8;
+    let overflow_offset = page_size - 8;
     let overflow_page = u64::from_le_bytes(page_data[overflow_offset..overflow_offset + 8].try_into().unwrap());
 
     if overflow_page == 0 {
@@ -173,7 +178,7 @@ pub fn read_document(pager: &Pager, page_num: PageNum) -> Result<Document> {
         Ok(Document { id: doc_id, data })
     } else {
         // Multi-page document with overflow chain
-        let first_chunk_size = PAGE_SIZE - DOC_ID_LEN_SIZE - id_len - DATA_LEN_SIZE - OVERFLOW_SIZE;
+        let first_chunk_size = page_size - DOC_ID_LEN_SIZE - id_len - DATA_LEN_SIZE - OVERFLOW_SIZE;
         let mut data = Vec::with_capacity(data_len);
         let first_data_len = first_chunk_size.min(data_len);
 
@@ -205,12 +210,12 @@ pub fn read_document(pager: &Pager, page_num: PageNum) -> Result<Document> {
 
             let overflow_data = pager.read_page(current_overflow)?;
             let remaining = data_len - data.len();
-            let chunk_size = remaining.min(MAX_OVERFLOW_DATA);
+            let chunk_size = remaining.min(max_overflow_data);
 
             data.extend_from_slice(&overflow_data[..chunk_size]);
 
             current_overflow = u64::from_le_bytes(
-                overflow_data[PAGE_SIZE - 8..].try_into().unwrap()
+                overflow_data[page_size - 8..].try_into().unwrap()
             );
         }
 
@@ -227,9 +232,10 @@ pub fn read_document(pager: &Pager, page_num: PageNum) -> Result<Document> {
 }
 
 pub fn delete_document(pager: &Pager, page_num: PageNum) -> Result<()> {
+    let page_size = pager.page_size();
     let page_data = pager.read_page(page_num)?;
 
-    let overflow_offset = PAGE_SIZE - 8;
+    let overflow_offset = page_size - 8;
     let mut overflow_page = u64::from_le_bytes(
         page_data[overflow_offset..overflow_offset + 8].try_into().unwrap()
     );
@@ -259,7 +265,7 @@ pub fn delete_document(pager: &Pager, page_num: PageNum) -> Result<()> {
 
         let overflow_data = pager.read_page(overflow_page)?;
         let next_overflow = u64::from_le_bytes(
-            overflow_data[PAGE_SIZE - 8..].try_into().unwrap()
+            overflow_data[page_size - 8..].try_into().unwrap()
         );
 
         pager.free_page(overflow_page)?;
@@ -283,11 +289,13 @@ pub fn write_versioned_document(
     }
 
     let id_bytes = doc_id.as_bytes();
+    let page_size = pager.page_size();
+    let max_overflow_data = page_size - OVERFLOW_SIZE;
     let first_page_header = XMIN_SIZE + XMAX_SIZE + DOC_ID_LEN_SIZE + id_bytes.len() + DATA_LEN_SIZE + OVERFLOW_SIZE;
-    let first_page_capacity = PAGE_SIZE - first_page_header;
+    let first_page_capacity = page_size - first_page_header;
 
     let first_page_num = pager.alloc_page()?;
-    let mut first_page_data = vec![0u8; PAGE_SIZE];
+    let mut first_page_data = vec![0u8; page_size];
     let mut offset = 0;
 
     // Write xmin
@@ -337,12 +345,12 @@ pub fn write_versioned_document(
         allocated_pages.push(overflow_page_num);
 
         // Allocate new overflow page
-        let mut overflow_page_data = vec![0u8; PAGE_SIZE];
-        let chunk_size = MAX_OVERFLOW_DATA.min(data.len() - data_offset);
+        let mut overflow_page_data = vec![0u8; page_size];
+        let chunk_size = max_overflow_data.min(data.len() - data_offset);
         overflow_page_data[..chunk_size].copy_from_slice(&data[data_offset..data_offset + chunk_size]);
         data_offset += chunk_size;
 
-        let overflow_page_data_offset = PAGE_SIZE - OVERFLOW_SIZE;
+        let overflow_page_data_offset = page_size - OVERFLOW_SIZE;
         overflow_page_data[overflow_page_data_offset..overflow_page_data_offset + 8].copy_from_slice(&0u64.to_le_bytes());
 
         // Update the PREVIOUS page's overflow pointer to point to this new page
@@ -408,6 +416,10 @@ pub fn read_versioned_document(
     page_num: PageNum,
     tx_writes: &HashMap<PageNum, Vec<u8>>,
 ) -> Result<VersionedDocument> {
+    pager.record_document_read();
+    let page_size = pager.page_size();
+    let max_overflow_data = page_size - OVERFLOW_SIZE;
+
     // Check transaction's write buffer first (page may not be flushed yet)
     let page_data = if let Some(data) = tx_writes.get(&page_num) {
         data.clone()
@@ -447,7 +459,7 @@ pub fn read_versioned_document(
         return Err(Error::InvalidDocument);
     }
 
-    let first_page_capacity = PAGE_SIZE - (XMIN_SIZE + XMAX_SIZE + DOC_ID_LEN_SIZE + id_len + DATA_LEN_SIZE + OVERFLOW_SIZE);
+    let first_page_capacity = page_size - (XMIN_SIZE + XMAX_SIZE + DOC_ID_LEN_SIZE + id_len + DATA_LEN_SIZE + OVERFLOW_SIZE);
 
     let mut data = vec![0u8; data_len];
     let mut data_offset = 0;
@@ -492,15 +504,15 @@ pub fn read_versioned_document(
             pager.read_page(overflow_page_num)?
         };
 
-        let chunk_size = MAX_OVERFLOW_DATA.min(data_len - data_offset);
+        let chunk_size = max_overflow_data.min(data_len - data_offset);
 
         data[data_offset..data_offset + chunk_size].copy_from_slice(&overflow_data[..chunk_size]);
         data_offset += chunk_size;
 
-        if PAGE_SIZE < OVERFLOW_SIZE {
+        if page_size < OVERFLOW_SIZE {
             return Err(Error::InvalidDocument);
         }
-        overflow_page_num = u64::from_le_bytes(overflow_data[PAGE_SIZE - OVERFLOW_SIZE..PAGE_SIZE].try_into().unwrap());
+        overflow_page_num = u64::from_le_bytes(overflow_data[page_size - OVERFLOW_SIZE..page_size].try_into().unwrap());
     }
 
     // Verify we read exactly the expected amount of data
@@ -519,6 +531,46 @@ pub fn read_versioned_document(
     })
 }
 
+/// Pages holding a versioned document: its first page followed by its
+/// overflow chain
+pub(crate) fn versioned_document_pages(pager: &Pager, page_num: PageNum) -> Result<Vec<PageNum>> {
+    let page_size = pager.page_size();
+    let page_data = pager.read_page(page_num)?;
+
+    let id_offset = XMIN_SIZE + XMAX_SIZE;
+    let id_len = u16::from_le_bytes(page_data[id_offset..id_offset + DOC_ID_LEN_SIZE].try_into().unwrap()) as usize;
+    let header_size = id_offset + DOC_ID_LEN_SIZE + id_len + DATA_LEN_SIZE + OVERFLOW_SIZE;
+    if id_len == 0 || header_size > page_size {
+        return Err(Error::InvalidDocument);
+    }
+
+    let len_offset = id_offset + DOC_ID_LEN_SIZE + id_len;
+    let data_len = u32::from_le_bytes(page_data[len_offset..len_offset + DATA_LEN_SIZE].try_into().unwrap()) as usize;
+    let first_chunk_size = (page_size - header_size).min(data_len);
+    let overflow_offset = len_offset + DATA_LEN_SIZE + first_chunk_size;
+
+    let mut pages = vec![page_num];
+    let mut visited = HashSet::from([page_num]);
+    let mut remaining = data_len - first_chunk_size;
+    let mut overflow_page_num = u64::from_le_bytes(page_data[overflow_offset..overflow_offset + OVERFLOW_SIZE].try_into().unwrap());
+
+    while overflow_page_num != 0 && remaining > 0 {
+        if !visited.insert(overflow_page_num) || pages.len() > MAX_OVERFLOW_CHAIN_LENGTH {
+            return Err(Error::Other(format!(
+                "Overflow chain cycle detected at page {}. Versioned document is corrupted.",
+                overflow_page_num
+            )));
+        }
+        pages.push(overflow_page_num);
+
+        let overflow_data = pager.read_page(overflow_page_num)?;
+        remaining -= (page_size - OVERFLOW_SIZE).min(remaining);
+        overflow_page_num = u64::from_le_bytes(overflow_data[page_size - OVERFLOW_SIZE..page_size].try_into().unwrap());
+    }
+
+    Ok(pages)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
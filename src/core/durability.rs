@@ -0,0 +1,167 @@
+use crate::core::errors::*;
+use crate::core::pager::Pager;
+use crate::core::wal::WAL;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+/// How much a commit waits for its writes to reach the disk
+///
+/// Every level fsyncs a commit's WAL frames before the commit writes any of
+/// its pages to the database file, so neither a crash nor a power loss can
+/// leave the file holding pages the WAL does not describe. The levels differ
+/// in when the database file itself is fsynced; until it is, the commits
+/// since are recovered by replaying the WAL when the database next opens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Durability {
+    /// Fsync the WAL and the database file before every commit returns.
+    ///
+    /// A commit that returned survives a power loss or OS crash without
+    /// relying on the WAL.
+    #[default]
+    Full,
+    /// Fsync the database file only when the WAL is checkpointed and when the
+    /// database closes.
+    ///
+    /// Each commit saves one fsync. After a power loss or OS crash the commits
+    /// since the last checkpoint are replayed from the WAL on the next open.
+    Normal,
+    /// Fsync the database file at most once per interval, from the first
+    /// commit after the interval has passed or from a background thread when
+    /// commits stop.
+    ///
+    /// After a power loss or OS crash, the commits of roughly the last interval
+    /// are replayed from the WAL on the next open.
+    Batched(Duration),
+}
+
+impl Durability {
+    /// Parse `"full"`, `"normal"` or `"batched"`; `"batched"` fsyncs every
+    /// `interval`
+    pub fn parse(s: &str, interval: Duration) -> Option<Self> {
+        match s {
+            "full" => Some(Durability::Full),
+            "normal" => Some(Durability::Normal),
+            "batched" => Some(Durability::Batched(interval)),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Durability::Full => "full",
+            Durability::Normal => "normal",
+            Durability::Batched(_) => "batched",
+        }
+    }
+}
+
+/// Decides which commits fsync under a [`Durability`] level
+pub(crate) struct SyncPolicy {
+    durability: Durability,
+    last_sync: Mutex<Instant>,
+    /// Set by commits that skipped their fsync, cleared by the next one
+    unsynced: AtomicBool,
+}
+
+impl SyncPolicy {
+    pub(crate) fn new(durability: Durability) -> Self {
+        Self {
+            durability,
+            last_sync: Mutex::new(Instant::now()),
+            unsynced: AtomicBool::new(false),
+        }
+    }
+
+    pub(crate) fn durability(&self) -> Durability {
+        self.durability
+    }
+
+    /// Whether the commit about to write its pages should fsync the database
+    /// file. A commit told not to is remembered, so the background thread of
+    /// `Batched` knows there is something to sync.
+    pub(crate) fn commit_needs_sync(&self) -> bool {
+        let needs_sync = match self.durability {
+            Durability::Full => true,
+            Durability::Normal => false,
+            Durability::Batched(interval) => {
+                let mut last_sync = self.last_sync.lock().recover_poison();
+                if last_sync.elapsed() >= interval {
+                    *last_sync = Instant::now();
+                    true
+                } else {
+                    false
+                }
+            }
+        };
+        self.unsynced.store(!needs_sync, Ordering::Release);
+        needs_sync
+    }
+
+    /// Fsync the WAL and the database file if a commit skipped it
+    fn sync_pending(&self, wal: &WAL, pager: &Pager) -> Result<()> {
+        if !self.unsynced.swap(false, Ordering::AcqRel) {
+            return Ok(());
+        }
+        *self.last_sync.lock().recover_poison() = Instant::now();
+        wal.sync()?;
+        pager.sync_data_only()
+    }
+
+    /// Fsync the WAL, then write out any dirty cached pages and fsync the
+    /// database file, whether or not a commit skipped its fsync
+    pub(crate) fn sync(&self, wal: &WAL, pager: &Pager) -> Result<()> {
+        self.unsynced.store(false, Ordering::Release);
+        *self.last_sync.lock().recover_poison() = Instant::now();
+        let result = wal.sync().and_then(|_| pager.flush());
+        if result.is_err() {
+            self.unsynced.store(true, Ordering::Release);
+        }
+        result
+    }
+
+    /// Whether a commit skipped its fsync and nothing has synced since
+    pub(crate) fn has_unsynced(&self) -> bool {
+        self.unsynced.load(Ordering::Acquire)
+    }
+}
+
+/// Start the thread that fsyncs for `Batched` once commits stop arriving. It
+/// holds only weak references and exits once the database is dropped.
+pub(crate) fn spawn_batched_syncer(policy: &Arc<SyncPolicy>, wal: &Arc<WAL>, pager: &Arc<Pager>) {
+    let interval = match policy.durability {
+        Durability::Batched(interval) => interval.max(Duration::from_millis(1)),
+        _ => return,
+    };
+
+    let policy: Weak<SyncPolicy> = Arc::downgrade(policy);
+    let wal: Weak<WAL> = Arc::downgrade(wal);
+    let pager: Weak<Pager> = Arc::downgrade(pager);
+
+    std::thread::spawn(move || loop {
+        std::thread::sleep(interval);
+        let (Some(policy), Some(wal), Some(pager)) = (policy.upgrade(), wal.upgrade(), pager.upgrade()) else {
+            return;
+        };
+        let _ = policy.sync_pending(&wal, &pager);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commit_needs_sync() {
+        assert!(SyncPolicy::new(Durability::Full).commit_needs_sync());
+        assert!(!SyncPolicy::new(Durability::Normal).commit_needs_sync());
+
+        let batched = SyncPolicy::new(Durability::Batched(Duration::from_secs(3600)));
+        assert!(!batched.commit_needs_sync());
+        assert!(batched.unsynced.load(Ordering::Acquire));
+
+        let batched = SyncPolicy::new(Durability::Batched(Duration::ZERO));
+        assert!(batched.commit_needs_sync());
+        assert!(!batched.unsynced.load(Ordering::Acquire));
+    }
+}
@@ -13,9 +13,21 @@ pub enum Error {
     #[error("transaction conflict: data was modified by another transaction")]
     TxConflict,
 
+    #[error("transaction exceeded its maximum duration")]
+    TxTimeout,
+
+    #[error("transaction is read-only")]
+    ReadOnlyTransaction,
+
+    #[error("savepoint '{name}' does not exist")]
+    SavepointNotFound { name: String },
+
     #[error("database already open in this process")]
     DatabaseAlreadyOpen,
 
+    #[error("database is locked by another process")]
+    DatabaseLocked,
+
     #[error("database is closed")]
     DatabaseClosed,
 
@@ -52,12 +64,24 @@ pub enum Error {
     #[error("document already exists: collection={collection:?}, id={id:?}")]
     DocumentAlreadyExists { collection: String, id: String },
 
+    #[error("revision conflict: collection={collection:?}, id={id:?}, expected _rev {expected}, found {actual}")]
+    RevConflict { collection: String, id: String, expected: u64, actual: u64 },
+
+    #[error("unique constraint violation: index {index:?} already holds {value}")]
+    DuplicateKey { index: String, value: String },
+
     #[error("document exceeds maximum size")]
     DocumentTooLarge,
 
     #[error("bulk operation exceeds maximum size: operation has {count} items but limit is {limit}")]
     BulkOperationTooLarge { count: usize, limit: usize },
 
+    #[error("query too expensive: {examined} documents exceeds the limit of {limit}")]
+    QueryTooExpensive { examined: usize, limit: usize },
+
+    #[error("replication log no longer holds sequence {requested}, its oldest record is {oldest}")]
+    ReplicationLogTrimmed { requested: u64, oldest: u64 },
+
     #[error("invalid document format")]
     InvalidDocument,
 
@@ -67,6 +91,9 @@ pub enum Error {
         collection: Option<String>,
     },
 
+    #[error("invalid update: {reason}")]
+    InvalidUpdate { reason: String },
+
     #[error("transaction conflict: collection={collection:?}, document={document_id:?}, operation={operation:?}")]
     Conflict {
         collection: String,
@@ -118,9 +145,15 @@ pub enum Error {
     #[error("invalid page number")]
     InvalidPageNum,
 
-    #[error("page size must be 4096 bytes")]
+    #[error("invalid page size")]
     InvalidPageSize,
 
+    #[error("page size mismatch: database uses {actual}-byte pages but {requested} was requested")]
+    PageSizeMismatch { requested: usize, actual: usize },
+
+    #[error("page {page} failed checksum verification")]
+    PageChecksumMismatch { page: u64 },
+
     #[error("WAL file corrupted")]
     WALCorrupted,
 
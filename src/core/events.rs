@@ -0,0 +1,78 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+use crate::core::constants::PageNum;
+use crate::core::errors::PoisonedLockExt;
+
+/// Something that happened on the write path, passed to the hook set with
+/// `Database::set_event_hook`
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum DbEvent {
+    TransactionBegin { tx_id: u64, read_only: bool },
+    TransactionCommit { tx_id: u64 },
+    TransactionRollback { tx_id: u64 },
+    /// A write lost to one committed after the transaction's snapshot. The
+    /// document is known when the conflict is found while writing it, and
+    /// not when it is found at commit.
+    TransactionConflict { tx_id: u64, collection: String, doc_id: Option<String> },
+    /// Copying the WAL into the database file began, with this many frames
+    CheckpointStart { frames: u64 },
+    CheckpointEnd { pages_written: usize },
+    /// A clean page was dropped from the page cache to make room
+    PageEvicted { page_num: PageNum },
+    /// The WAL was cut back after a checkpoint and starts over with new
+    /// frames; `truncated_to` is its new length in bytes
+    WalRotated { truncated_to: u64 },
+}
+
+pub type EventHook = Box<dyn Fn(DbEvent) + Send + Sync>;
+
+/// Shared slot for the event hook. Emitting is a single atomic load while no
+/// hook is set, and events are only built once one is.
+#[derive(Default)]
+pub(crate) struct EventHooks {
+    enabled: AtomicBool,
+    hook: RwLock<Option<Arc<EventHook>>>,
+}
+
+impl EventHooks {
+    pub(crate) fn set(&self, hook: Option<EventHook>) {
+        let mut slot = self.hook.write().recover_poison();
+        self.enabled.store(hook.is_some(), Ordering::Release);
+        *slot = hook.map(Arc::new);
+    }
+
+    pub(crate) fn emit(&self, event: impl FnOnce() -> DbEvent) {
+        if !self.enabled.load(Ordering::Acquire) {
+            return;
+        }
+        // Called outside the lock, so the hook may replace itself
+        let hook = self.hook.read().recover_poison().clone();
+        if let Some(hook) = hook {
+            hook(event());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_events_are_only_built_with_a_hook() {
+        let hooks = EventHooks::default();
+        hooks.emit(|| unreachable!("no hook is set"));
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        hooks.set(Some(Box::new(move |event| sink.lock().unwrap().push(event))));
+        hooks.emit(|| DbEvent::PageEvicted { page_num: 7 });
+
+        hooks.set(None);
+        hooks.emit(|| unreachable!("the hook was removed"));
+
+        assert_eq!(*seen.lock().unwrap(), vec![DbEvent::PageEvicted { page_num: 7 }]);
+    }
+}
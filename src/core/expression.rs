@@ -0,0 +1,173 @@
+//! Values computed from a document's fields, for expression indexes and the
+//! query conditions they answer.
+//!
+//! An expression is a call to one of a few functions, whose arguments are
+//! field paths, string or number literals, or other calls:
+//!
+//! - `lower(x)`, `upper(x)`: the string in lower or upper case
+//! - `length(x)`: characters in a string, or elements in an array
+//! - `substr(x, start)`, `substr(x, start, count)`: characters from `start` on
+//! - `concat(a, b, ...)`: strings and numbers joined into one string
+//!
+//! Anything else computes to null, like a missing field or `lower` of a number.
+
+use crate::core::errors::*;
+use crate::core::index_key::extract_field_value;
+use crate::core::query::lexer::tokenize;
+use crate::core::query::parser::Parser;
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// Functions an expression may call
+pub(crate) const FUNCTIONS: &[&str] = &["lower", "upper", "length", "substr", "concat"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    /// A dotted field path
+    Field(String),
+    Literal(Value),
+    Lower(Box<Expression>),
+    Upper(Box<Expression>),
+    Length(Box<Expression>),
+    /// Characters from a start position, optionally only so many of them
+    Substr(Box<Expression>, usize, Option<usize>),
+    Concat(Vec<Expression>),
+}
+
+impl Expression {
+    /// Parse a function call such as `lower(email)`. A bare field or literal
+    /// is rejected, since it needs no expression.
+    pub fn parse(text: &str) -> Result<Self> {
+        let invalid = |e: String| Error::Other(format!("invalid expression {:?}: {}", text, e));
+        let tokens = tokenize(text).map_err(invalid)?;
+        Parser::new(tokens).parse_expression_text().map_err(invalid)
+    }
+
+    /// Compute the value for `doc`
+    pub fn eval(&self, doc: &Map<String, Value>) -> Value {
+        match self {
+            Expression::Field(field) => extract_field_value(doc, field),
+            Expression::Literal(value) => value.clone(),
+            Expression::Lower(arg) => map_string(arg.eval(doc), |s| s.to_lowercase()),
+            Expression::Upper(arg) => map_string(arg.eval(doc), |s| s.to_uppercase()),
+            Expression::Length(arg) => match arg.eval(doc) {
+                Value::String(s) => Value::from(s.chars().count()),
+                Value::Array(items) => Value::from(items.len()),
+                _ => Value::Null,
+            },
+            Expression::Substr(arg, start, count) => map_string(arg.eval(doc), |s| {
+                let chars = s.chars().skip(*start);
+                match count {
+                    Some(count) => chars.take(*count).collect(),
+                    None => chars.collect(),
+                }
+            }),
+            Expression::Concat(args) => {
+                let mut joined = String::new();
+                for arg in args {
+                    match arg.eval(doc) {
+                        Value::String(s) => joined.push_str(&s),
+                        Value::Number(n) => joined.push_str(&n.to_string()),
+                        _ => return Value::Null,
+                    }
+                }
+                Value::String(joined)
+            }
+        }
+    }
+}
+
+/// A number with no fractional part as an integer, since the query parser
+/// reads every number as a float but documents usually hold integers.
+/// Anything else is returned unchanged.
+pub(crate) fn integral(value: Value) -> Value {
+    match value.as_f64() {
+        Some(n) if value.is_f64() && n.fract() == 0.0 && n.abs() < 9e15 => Value::from(n as i64),
+        _ => value,
+    }
+}
+
+fn map_string(value: Value, f: impl FnOnce(&str) -> String) -> Value {
+    match value {
+        Value::String(s) => Value::String(f(&s)),
+        _ => Value::Null,
+    }
+}
+
+/// The expression in the syntax it is parsed from, with arguments separated
+/// by `", "`. Indexes are matched to query conditions by this text.
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let call = |f: &mut fmt::Formatter<'_>, name: &str, args: &[&dyn fmt::Display]| {
+            write!(f, "{}(", name)?;
+            for (i, arg) in args.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{}", arg)?;
+            }
+            f.write_str(")")
+        };
+
+        match self {
+            Expression::Field(field) => f.write_str(field),
+            Expression::Literal(Value::String(s)) => {
+                let quote = if s.contains('\'') { '"' } else { '\'' };
+                write!(f, "{}{}{}", quote, s, quote)
+            }
+            Expression::Literal(value) => write!(f, "{}", value),
+            Expression::Lower(arg) => call(f, "lower", &[arg]),
+            Expression::Upper(arg) => call(f, "upper", &[arg]),
+            Expression::Length(arg) => call(f, "length", &[arg]),
+            Expression::Substr(arg, start, None) => call(f, "substr", &[arg, start]),
+            Expression::Substr(arg, start, Some(count)) => call(f, "substr", &[arg, start, count]),
+            Expression::Concat(args) => {
+                let args: Vec<&dyn fmt::Display> = args.iter().map(|arg| arg as &dyn fmt::Display).collect();
+                call(f, "concat", &args)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn eval(text: &str, doc: Value) -> Value {
+        Expression::parse(text).unwrap().eval(doc.as_object().unwrap())
+    }
+
+    #[test]
+    fn test_eval_functions() {
+        assert_eq!(eval("lower(email)", json!({"email": "Ann@Example.COM"})), json!("ann@example.com"));
+        assert_eq!(eval("upper(user.name)", json!({"user": {"name": "ann"}})), json!("ANN"));
+        assert_eq!(eval("length(tags)", json!({"tags": ["a", "b"]})), json!(2));
+        assert_eq!(eval("length(name)", json!({"name": "Zoë"})), json!(3));
+        assert_eq!(eval("substr(code, 1, 2)", json!({"code": "ABCD"})), json!("BC"));
+        assert_eq!(eval("substr(code, 2)", json!({"code": "ABCD"})), json!("CD"));
+        assert_eq!(
+            eval("lower(concat(first, ' ', last, 2))", json!({"first": "Ann", "last": "Lee"})),
+            json!("ann lee2")
+        );
+
+        assert_eq!(eval("lower(email)", json!({})), Value::Null);
+        assert_eq!(eval("lower(age)", json!({"age": 3})), Value::Null);
+        assert_eq!(eval("concat(first, last)", json!({"first": "Ann"})), Value::Null);
+    }
+
+    #[test]
+    fn test_parse_canonical_text() {
+        let err = Expression::parse("LOWER(email)").unwrap_err();
+        assert!(err.to_string().contains("invalid expression"));
+
+        let expr = Expression::parse("concat( first,' ',last )").unwrap();
+        assert_eq!(expr.to_string(), "concat(first, ' ', last)");
+        assert_eq!(Expression::parse(&expr.to_string()).unwrap(), expr);
+        assert_eq!(Expression::parse("substr(a.b,0,3)").unwrap().to_string(), "substr(a.b, 0, 3)");
+
+        for bad in ["email", "'text'", "lower(email", "lower(a, b)", "substr(a, -1)", "sha1(a)", "lower(a) b"] {
+            assert!(Expression::parse(bad).is_err(), "{} should not parse", bad);
+        }
+    }
+}
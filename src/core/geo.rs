@@ -0,0 +1,225 @@
+//! Geohash encoding and distance helpers behind geo indexes and `near()` queries
+//!
+//! Points are stored in geo indexes as geohashes, which interleave longitude
+//! and latitude bits so that points sharing a prefix lie in the same cell. A
+//! radius query is answered by scanning the prefixes of the cells covering the
+//! circle's bounding box, then checking the exact haversine distance of each
+//! candidate.
+
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+
+use crate::core::index_key::extract_field_value;
+
+/// Mean radius of the earth used for distances, in kilometers
+pub const EARTH_RADIUS_KM: f64 = 6371.0088;
+
+/// Length of the geohashes stored in geo indexes, cells of a few centimeters
+pub const GEOHASH_PRECISION: usize = 12;
+
+/// Upper bound on the number of cells scanned for one radius query. Larger
+/// radii are covered with coarser cells.
+const MAX_COVER_CELLS: usize = 32;
+
+const BASE32: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// Geohash of length `precision` for the cell holding the point
+pub fn geohash(lat: f64, lng: f64, precision: usize) -> String {
+    let (mut lat_range, mut lng_range) = ((-90.0, 90.0), (-180.0, 180.0));
+    let mut hash = String::with_capacity(precision);
+    let mut even = true;
+    let mut bits = 0;
+    let mut index = 0;
+
+    while hash.len() < precision {
+        let (range, value) = if even { (&mut lng_range, lng) } else { (&mut lat_range, lat) };
+        let mid = (range.0 + range.1) / 2.0;
+        index <<= 1;
+        if value >= mid {
+            index |= 1;
+            range.0 = mid;
+        } else {
+            range.1 = mid;
+        }
+        even = !even;
+
+        bits += 1;
+        if bits == 5 {
+            hash.push(BASE32[index] as char);
+            bits = 0;
+            index = 0;
+        }
+    }
+
+    hash
+}
+
+/// Great-circle distance between two points in kilometers
+pub fn haversine_km(lat1: f64, lng1: f64, lat2: f64, lng2: f64) -> f64 {
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let d_phi = (lat2 - lat1).to_radians();
+    let d_lambda = (lng2 - lng1).to_radians();
+
+    let a = (d_phi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (d_lambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().min(1.0).asin()
+}
+
+/// Whether a latitude and longitude in degrees name a point on the globe
+pub fn is_valid_point(lat: f64, lng: f64) -> bool {
+    (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lng)
+}
+
+/// The point stored in `doc` under `lat_field` and `lng_field`, `None` unless
+/// both are numbers in range
+pub fn point_at(doc: &Map<String, Value>, lat_field: &str, lng_field: &str) -> Option<(f64, f64)> {
+    let lat = extract_field_value(doc, lat_field).as_f64()?;
+    let lng = extract_field_value(doc, lng_field).as_f64()?;
+    is_valid_point(lat, lng).then_some((lat, lng))
+}
+
+/// Geohash prefixes of the cells covering every point within `radius_km` of
+/// the center. Every point in range has a geohash starting with one of them.
+pub fn covering_cells(lat: f64, lng: f64, radius_km: f64) -> Vec<String> {
+    let boxes = bounding_boxes(lat, lng, radius_km);
+
+    // Finest precision that still covers the boxes with a bounded number of cells
+    let precision = (1..=GEOHASH_PRECISION)
+        .rev()
+        .find(|&precision| {
+            let (cell_lat, cell_lng) = cell_size(precision);
+            let cells: f64 = boxes.iter()
+                .map(|b| {
+                    (((b.max_lat - b.min_lat) / cell_lat).floor() + 2.0)
+                        * (((b.max_lng - b.min_lng) / cell_lng).floor() + 2.0)
+                })
+                .sum();
+            cells <= MAX_COVER_CELLS as f64
+        })
+        .unwrap_or(1);
+
+    let (cell_lat, cell_lng) = cell_size(precision);
+    let mut cells = BTreeSet::new();
+    for b in &boxes {
+        let (lat_start, lat_end) = cell_span(b.min_lat, b.max_lat, -90.0, cell_lat);
+        let (lng_start, lng_end) = cell_span(b.min_lng, b.max_lng, -180.0, cell_lng);
+        for lat_cell in lat_start..=lat_end {
+            for lng_cell in lng_start..=lng_end {
+                let center_lat = -90.0 + (lat_cell as f64 + 0.5) * cell_lat;
+                let center_lng = -180.0 + (lng_cell as f64 + 0.5) * cell_lng;
+                cells.insert(geohash(center_lat, center_lng, precision));
+            }
+        }
+    }
+
+    cells.into_iter().collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct BoundingBox {
+    min_lat: f64,
+    max_lat: f64,
+    min_lng: f64,
+    max_lng: f64,
+}
+
+/// Boxes holding every point within `radius_km` of the center, split in two
+/// when the circle crosses the antimeridian
+fn bounding_boxes(lat: f64, lng: f64, radius_km: f64) -> Vec<BoundingBox> {
+    let angular = radius_km / EARTH_RADIUS_KM;
+    let d_lat = angular.to_degrees();
+    let (min_lat, max_lat) = (lat - d_lat, lat + d_lat);
+
+    // A circle reaching a pole spans every longitude
+    if min_lat <= -90.0 || max_lat >= 90.0 || angular.sin() >= lat.to_radians().cos() {
+        return vec![BoundingBox {
+            min_lat: min_lat.max(-90.0),
+            max_lat: max_lat.min(90.0),
+            min_lng: -180.0,
+            max_lng: 180.0,
+        }];
+    }
+
+    let d_lng = (angular.sin() / lat.to_radians().cos()).asin().to_degrees();
+    let (min_lng, max_lng) = (lng - d_lng, lng + d_lng);
+    let bounded = |min_lng, max_lng| BoundingBox { min_lat, max_lat, min_lng, max_lng };
+
+    if min_lng < -180.0 {
+        vec![bounded(min_lng + 360.0, 180.0), bounded(-180.0, max_lng)]
+    } else if max_lng > 180.0 {
+        vec![bounded(min_lng, 180.0), bounded(-180.0, max_lng - 360.0)]
+    } else {
+        vec![bounded(min_lng, max_lng)]
+    }
+}
+
+/// Height and width in degrees of a geohash cell of length `precision`
+fn cell_size(precision: usize) -> (f64, f64) {
+    let bits = 5 * precision as i32;
+    let lat_bits = bits / 2;
+    let lng_bits = bits - lat_bits;
+    (180.0 / 2f64.powi(lat_bits), 360.0 / 2f64.powi(lng_bits))
+}
+
+/// First and last cell index along one axis overlapping `[min, max]`
+fn cell_span(min: f64, max: f64, origin: f64, size: f64) -> (i64, i64) {
+    let last = ((origin.abs() * 2.0) / size) as i64 - 1;
+    let index = |value: f64| (((value - origin) / size).floor() as i64).clamp(0, last);
+    (index(min), index(max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_geohash_and_distance() {
+        assert_eq!(geohash(57.64911, 10.40744, 11), "u4pruydqqvj");
+        assert_eq!(geohash(-25.382708, -49.265506, 5), "6gkzw");
+
+        // Paris to London is about 344 km
+        let d = haversine_km(48.8566, 2.3522, 51.5074, -0.1278);
+        assert!((d - 343.5).abs() < 1.0, "got {}", d);
+        assert_eq!(haversine_km(10.0, 20.0, 10.0, 20.0), 0.0);
+
+        let doc = json!({"location": {"lat": 48.8566, "lng": 2.3522}, "bad": {"lat": 91, "lng": 0}});
+        let doc = doc.as_object().unwrap();
+        assert_eq!(point_at(doc, "location.lat", "location.lng"), Some((48.8566, 2.3522)));
+        assert_eq!(point_at(doc, "bad.lat", "bad.lng"), None);
+        assert_eq!(point_at(doc, "missing.lat", "missing.lng"), None);
+    }
+
+    #[test]
+    fn test_covering_cells_hold_every_point_in_range() {
+        let centers = [(48.8566, 2.3522), (0.0, 179.99), (-33.86, -180.0), (89.9, 45.0)];
+        for &(lat, lng) in &centers {
+            for &radius in &[0.5, 25.0, 800.0] {
+                let cells = covering_cells(lat, lng, radius);
+                assert!(!cells.is_empty() && cells.len() <= MAX_COVER_CELLS);
+
+                // Points on a ring just inside the radius
+                for step in 0..36 {
+                    let bearing = (step as f64 * 10.0).to_radians();
+                    let angular = radius * 0.99 / EARTH_RADIUS_KM;
+                    let (phi, lambda) = (lat.to_radians(), lng.to_radians());
+                    let phi2 = (phi.sin() * angular.cos() + phi.cos() * angular.sin() * bearing.cos()).asin();
+                    let lambda2 = lambda + (bearing.sin() * angular.sin() * phi.cos())
+                        .atan2(angular.cos() - phi.sin() * phi2.sin());
+                    let (lat2, mut lng2) = (phi2.to_degrees(), lambda2.to_degrees());
+                    if lng2 > 180.0 {
+                        lng2 -= 360.0;
+                    } else if lng2 < -180.0 {
+                        lng2 += 360.0;
+                    }
+
+                    let hash = geohash(lat2, lng2, GEOHASH_PRECISION);
+                    assert!(
+                        cells.iter().any(|cell| hash.starts_with(cell.as_str())),
+                        "({}, {}) within {} km of ({}, {}) not covered by {:?}",
+                        lat2, lng2, radius, lat, lng, cells
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! Bloom filters over the `_id`s of each collection
+//!
+//! Enabled with `DatabaseOptions::enable_id_bloom_filter`. A filter answers
+//! "definitely absent" or "maybe present", which lets `find_by_id` skip the
+//! B-tree walk for IDs that were never written.
+//!
+//! IDs are added when a transaction writes a document, before it commits, so
+//! a committed document is always in its collection's filter. Aborted writes
+//! and deletes leave their IDs behind, which only costs extra false positives.
+//! Filters are kept in memory and rebuilt from the primary B-tree keys when the
+//! database is opened or vacuumed.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// IDs the first layer is sized for
+const INITIAL_CAPACITY: usize = 1024;
+/// False positive rate of the first layer. Each later layer halves it, so the
+/// combined rate stays under twice this however many layers are added.
+const FIRST_LAYER_FALSE_POSITIVE_RATE: f64 = 0.01;
+
+/// Growable bloom filter over document IDs
+///
+/// Once a layer holds as many IDs as it was sized for, a layer twice as large
+/// is started, so the filter keeps up with a growing collection without
+/// rehashing the IDs already added.
+#[derive(Debug, Clone)]
+pub(crate) struct IdFilter {
+    layers: Vec<Layer>,
+}
+
+#[derive(Debug, Clone)]
+struct Layer {
+    bits: Vec<u64>,
+    num_hashes: u64,
+    capacity: usize,
+    len: usize,
+}
+
+impl Layer {
+    fn new(capacity: usize, false_positive_rate: f64) -> Self {
+        let ln2 = std::f64::consts::LN_2;
+        let bits_per_id = -false_positive_rate.ln() / (ln2 * ln2);
+        let num_bits = ((capacity as f64 * bits_per_id).ceil() as usize).next_multiple_of(64);
+        Layer {
+            bits: vec![0; num_bits / 64],
+            num_hashes: (-false_positive_rate.log2()).ceil() as u64,
+            capacity,
+            len: 0,
+        }
+    }
+
+    /// Bit positions for an ID, by double hashing one 64-bit hash
+    fn positions(&self, hash: u64) -> impl Iterator<Item = usize> {
+        let num_bits = self.bits.len() as u64 * 64;
+        let (h1, h2) = (hash & 0xffff_ffff, (hash >> 32) | 1);
+        (0..self.num_hashes).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % num_bits) as usize)
+    }
+
+    fn insert(&mut self, hash: u64) {
+        let positions: Vec<usize> = self.positions(hash).collect();
+        for pos in positions {
+            self.bits[pos / 64] |= 1 << (pos % 64);
+        }
+        self.len += 1;
+    }
+
+    fn contains(&self, hash: u64) -> bool {
+        self.positions(hash).all(|pos| self.bits[pos / 64] & (1 << (pos % 64)) != 0)
+    }
+}
+
+impl IdFilter {
+    /// Filter sized for about `expected` IDs
+    pub(crate) fn with_capacity(expected: usize) -> Self {
+        IdFilter {
+            layers: vec![Layer::new(expected.max(INITIAL_CAPACITY), FIRST_LAYER_FALSE_POSITIVE_RATE)],
+        }
+    }
+
+    pub(crate) fn insert(&mut self, id: &str) {
+        let hash = hash_id(id);
+        if self.contains_hash(hash) {
+            return;
+        }
+
+        let last = self.layers.last().expect("filter always has a layer");
+        if last.len >= last.capacity {
+            let rate = FIRST_LAYER_FALSE_POSITIVE_RATE / 2f64.powi(self.layers.len() as i32);
+            let capacity = last.capacity * 2;
+            self.layers.push(Layer::new(capacity, rate));
+        }
+        self.layers.last_mut().expect("filter always has a layer").insert(hash);
+    }
+
+    /// False only if `id` was never inserted
+    pub(crate) fn may_contain(&self, id: &str) -> bool {
+        self.contains_hash(hash_id(id))
+    }
+
+    fn contains_hash(&self, hash: u64) -> bool {
+        self.layers.iter().any(|layer| layer.contains(hash))
+    }
+}
+
+fn hash_id(id: &str) -> u64 {
+    // DefaultHasher::new() uses fixed keys, so hashes are stable for the filter's lifetime
+    let mut hasher = DefaultHasher::new();
+    id.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_false_negatives() {
+        let mut filter = IdFilter::with_capacity(0);
+        let ids: Vec<String> = (0..10_000).map(|i| format!("doc-{}", i)).collect();
+        for id in &ids {
+            filter.insert(id);
+        }
+
+        assert!(filter.layers.len() > 1, "filter should have grown past its first layer");
+        assert!(ids.iter().all(|id| filter.may_contain(id)));
+    }
+
+    #[test]
+    fn test_false_positive_rate_stays_low() {
+        let mut filter = IdFilter::with_capacity(0);
+        for i in 0..50_000 {
+            filter.insert(&format!("present-{}", i));
+        }
+
+        let false_positives = (0..50_000)
+            .filter(|i| filter.may_contain(&format!("absent-{}", i)))
+            .count();
+        assert!(false_positives < 2_500, "{} false positives", false_positives);
+    }
+}
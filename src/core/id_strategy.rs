@@ -0,0 +1,164 @@
+//! `_id` generation strategies for documents inserted without an `_id`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How a collection generates `_id` for documents inserted without one.
+///
+/// Collections without a strategy keep the built-in `{timestamp}_{hash}` ids.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdStrategy {
+    /// Random (version 4) UUID, e.g. `7f1b1e0c-4d5a-4c3b-9a51-2f0e8d6c9b7a`
+    Uuid,
+    /// 12-byte Mongo-style ObjectId as 24 hex characters: a 4-byte timestamp in
+    /// seconds, 5 random bytes per process and a 3-byte counter
+    ObjectId,
+    /// 26-character ULID: millisecond timestamp plus randomness, sortable by
+    /// creation time
+    Ulid,
+    /// Increasing integers starting at 1, stored as decimal strings. The counter
+    /// lives in the collection metadata, so numbers are never handed out twice,
+    /// though aborted inserts leave gaps.
+    AutoIncrement,
+}
+
+impl IdStrategy {
+    /// Generate an id for strategies that don't need collection state.
+    /// Returns `None` for `AutoIncrement`, which the database allocates itself.
+    pub(crate) fn generate(self) -> Option<String> {
+        match self {
+            IdStrategy::Uuid => Some(generate_uuid()),
+            IdStrategy::ObjectId => Some(generate_object_id()),
+            IdStrategy::Ulid => Some(generate_ulid()),
+            IdStrategy::AutoIncrement => None,
+        }
+    }
+}
+
+pub(crate) fn random_u64() -> u64 {
+    static CALLS: AtomicU64 = AtomicU64::new(0);
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(CALLS.fetch_add(1, Ordering::Relaxed));
+    hasher.write_u128(now_nanos());
+    hasher.finish()
+}
+
+fn now_nanos() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+}
+
+fn generate_uuid() -> String {
+    let mut bytes = [0u8; 16];
+    bytes[..8].copy_from_slice(&random_u64().to_be_bytes());
+    bytes[8..].copy_from_slice(&random_u64().to_be_bytes());
+
+    // Version 4, RFC 4122 variant
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex = to_hex(&bytes);
+    format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
+
+fn generate_object_id() -> String {
+    static PROCESS_RANDOM: once_cell::sync::Lazy<[u8; 5]> = once_cell::sync::Lazy::new(|| {
+        let mut bytes = [0u8; 5];
+        bytes.copy_from_slice(&random_u64().to_be_bytes()[..5]);
+        bytes
+    });
+    static COUNTER: once_cell::sync::Lazy<AtomicU32> =
+        once_cell::sync::Lazy::new(|| AtomicU32::new(random_u64() as u32));
+
+    let seconds = (now_nanos() / 1_000_000_000) as u32;
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed) & 0x00ff_ffff;
+
+    let mut bytes = [0u8; 12];
+    bytes[..4].copy_from_slice(&seconds.to_be_bytes());
+    bytes[4..9].copy_from_slice(&*PROCESS_RANDOM);
+    bytes[9..].copy_from_slice(&count.to_be_bytes()[1..]);
+
+    to_hex(&bytes)
+}
+
+fn generate_ulid() -> String {
+    // Last (timestamp, randomness) handed out, so ids made within the same
+    // millisecond still sort in creation order
+    static LAST: Mutex<(u64, u128)> = Mutex::new((0, 0));
+
+    const RANDOM_MASK: u128 = (1 << 80) - 1;
+
+    let now_ms = (now_nanos() / 1_000_000) as u64 & ((1 << 48) - 1);
+    let fresh = (((random_u64() as u128) << 64) | random_u64() as u128) & RANDOM_MASK;
+
+    let (timestamp, random) = {
+        let mut last = LAST.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if now_ms <= last.0 && last.1 < RANDOM_MASK {
+            last.1 += 1;
+        } else {
+            *last = (now_ms.max(last.0), fresh);
+        }
+        *last
+    };
+
+    encode_crockford(((timestamp as u128) << 80) | random)
+}
+
+/// Encode a 128-bit value as 26 Crockford base32 characters
+fn encode_crockford(mut value: u128) -> String {
+    const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+    let mut out = [0u8; 26];
+    for slot in out.iter_mut().rev() {
+        *slot = ALPHABET[(value & 0x1f) as usize];
+        value >>= 5;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_uuid_format() {
+        let id = generate_uuid();
+        assert_eq!(id.len(), 36);
+        assert_eq!(&id[14..15], "4");
+        assert!(matches!(&id[19..20], "8" | "9" | "a" | "b"));
+        assert_ne!(id, generate_uuid());
+    }
+
+    #[test]
+    fn test_object_id_format() {
+        let id = generate_object_id();
+        assert_eq!(id.len(), 24);
+        assert!(id.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
+    #[test]
+    fn test_ulids_are_sorted_and_unique() {
+        let ids: Vec<String> = (0..1000).map(|_| generate_ulid()).collect();
+        assert!(ids.iter().all(|id| id.len() == 26));
+
+        let mut sorted = ids.clone();
+        sorted.sort();
+        assert_eq!(ids, sorted);
+
+        let unique: HashSet<_> = ids.iter().collect();
+        assert_eq!(unique.len(), ids.len());
+    }
+}
@@ -16,6 +16,14 @@ pub fn compare_index_keys(a: &IndexKey, b: &IndexKey) -> Ordering {
     }
 }
 
+/// Canonical order of JSON values, shared by `sort_by`, aggregation sorts and
+/// index scans so an index-served sort matches an in-memory one.
+///
+/// Values of different types order as null < bool < number < string < array
+/// < object. Numbers compare by value whether stored as integers or floats,
+/// so `2` equals `2.0` and large integers keep their precision. Strings
+/// compare by Unicode code point, arrays element by element, and objects by
+/// their sorted keys and then the values under them.
 pub fn compare_values(a: &Value, b: &Value) -> Ordering {
     use Value::*;
 
@@ -28,18 +36,7 @@ pub fn compare_values(a: &Value, b: &Value) -> Ordering {
         (Bool(_), _) => Ordering::Less,
         (_, Bool(_)) => Ordering::Greater,
 
-        (Number(a_num), Number(b_num)) => {
-            let a_f64 = a_num.as_f64().unwrap_or(0.0);
-            let b_f64 = b_num.as_f64().unwrap_or(0.0);
-
-            if a_f64 < b_f64 {
-                Ordering::Less
-            } else if a_f64 > b_f64 {
-                Ordering::Greater
-            } else {
-                Ordering::Equal
-            }
-        }
+        (Number(a_num), Number(b_num)) => compare_numbers(a_num, b_num),
         (Number(_), _) => Ordering::Less,
         (_, Number(_)) => Ordering::Greater,
 
@@ -81,6 +78,33 @@ pub fn compare_values(a: &Value, b: &Value) -> Ordering {
     }
 }
 
+fn compare_numbers(a: &serde_json::Number, b: &serde_json::Number) -> Ordering {
+    match (integer_value(a), integer_value(b)) {
+        (Some(a_int), Some(b_int)) => a_int.cmp(&b_int),
+        (Some(a_int), None) => compare_integer_to_float(a_int, b.as_f64().unwrap_or(0.0)),
+        (None, Some(b_int)) => compare_integer_to_float(b_int, a.as_f64().unwrap_or(0.0)).reverse(),
+        (None, None) => {
+            let a_f64 = a.as_f64().unwrap_or(0.0);
+            let b_f64 = b.as_f64().unwrap_or(0.0);
+            a_f64.partial_cmp(&b_f64).unwrap_or(Ordering::Equal)
+        }
+    }
+}
+
+fn integer_value(n: &serde_json::Number) -> Option<i128> {
+    n.as_i64().map(i128::from).or_else(|| n.as_u64().map(i128::from))
+}
+
+/// Rounding an integer to f64 never moves it past a float it differs from, so
+/// only a tie needs the exact comparison, and a tie means the float is whole
+fn compare_integer_to_float(int: i128, float: f64) -> Ordering {
+    match (int as f64).partial_cmp(&float) {
+        Some(Ordering::Equal) => int.cmp(&(float as i128)),
+        Some(order) => order,
+        None => Ordering::Equal,
+    }
+}
+
 pub fn serialize_index_key(key: &IndexKey) -> Result<String> {
     let value_json = serde_json::to_string(&key.field_value)?;
     Ok(format!("{}|{}", value_json, key.doc_id))
@@ -103,23 +127,20 @@ pub fn deserialize_index_key(s: &str) -> Result<IndexKey> {
 }
 
 pub fn extract_field_value(doc: &Map<String, Value>, field: &str) -> Value {
-    let parts: Vec<&str> = field.split('.').collect();
-    let mut current = Value::Object(doc.clone());
+    let mut parts = field.split('.');
+    let mut current = match parts.next().and_then(|part| doc.get(part)) {
+        Some(value) => value,
+        None => return Value::Null,
+    };
 
     for part in parts {
-        match current {
-            Value::Object(obj) => {
-                if let Some(value) = obj.get(part) {
-                    current = value.clone();
-                } else {
-                    return Value::Null;
-                }
-            }
-            _ => return Value::Null,
+        match current.as_object().and_then(|obj| obj.get(part)) {
+            Some(value) => current = value,
+            None => return Value::Null,
         }
     }
 
-    current
+    current.clone()
 }
 
 /// Compound index key structure for multi-field indexes
@@ -144,29 +165,41 @@ pub fn serialize_compound_index_key(key: &CompoundIndexKey) -> Result<String> {
     Ok(parts.join("|"))
 }
 
-/// Deserialize a compound index key from a string
-pub fn deserialize_compound_index_key(s: &str, num_fields: usize) -> Result<CompoundIndexKey> {
-    let parts: Vec<&str> = s.split('|').collect();
-
-    if parts.len() < num_fields + 1 {
-        return Err(Error::Other(format!(
-            "invalid compound index key format: expected {} fields + doc_id, got {} parts",
-            num_fields, parts.len()
-        )));
+/// Prefix shared by every compound index key holding `field_values`, whatever
+/// document it belongs to
+pub fn compound_index_key_prefix(field_values: &[Value]) -> Result<String> {
+    let mut prefix = String::new();
+    for value in field_values {
+        prefix.push_str(&serde_json::to_string(value)?);
+        prefix.push('|');
     }
+    Ok(prefix)
+}
 
+/// Deserialize a compound index key from a string
+///
+/// A '|' can only appear inside a JSON string, where cutting at it leaves the
+/// string unterminated, so the first separator giving valid JSON ends the value.
+/// This lets values and doc ids containing '|' survive the round trip.
+pub fn deserialize_compound_index_key(s: &str, num_fields: usize) -> Result<CompoundIndexKey> {
     let mut field_values = Vec::with_capacity(num_fields);
-    for i in 0..num_fields {
-        let value: Value = serde_json::from_str(parts[i])?;
+    let mut rest = s;
+
+    for _ in 0..num_fields {
+        let (value, end) = rest
+            .match_indices('|')
+            .find_map(|(pos, _)| serde_json::from_str::<Value>(&rest[..pos]).ok().map(|value| (value, pos)))
+            .ok_or_else(|| Error::Other(format!(
+                "invalid compound index key format: expected {} fields + doc_id",
+                num_fields
+            )))?;
         field_values.push(value);
+        rest = &rest[end + 1..];
     }
 
-    // The rest is the doc_id (join remaining parts in case doc_id contains '|')
-    let doc_id = parts[num_fields..].join("|");
-
     Ok(CompoundIndexKey {
         field_values,
-        doc_id,
+        doc_id: rest.to_string(),
     })
 }
 
@@ -214,6 +247,26 @@ mod tests {
         assert_eq!(compare_values(&json!("hello"), &json!("hello")), Ordering::Equal);
     }
 
+    #[test]
+    fn test_compare_mixed_numbers() {
+        assert_eq!(compare_values(&json!(2), &json!(2.0)), Ordering::Equal);
+        assert_eq!(compare_values(&json!(2), &json!(10.5)), Ordering::Less);
+        assert_eq!(compare_values(&json!(-1), &json!(u64::MAX)), Ordering::Less);
+        assert_eq!(compare_values(&json!(-0.5), &json!(-1)), Ordering::Greater);
+
+        // Integers beyond 2^53 don't collapse onto their nearest float
+        let big = 9_007_199_254_740_993_i64;
+        assert_eq!(compare_values(&json!(big), &json!(big - 1)), Ordering::Greater);
+        assert_eq!(compare_values(&json!(big), &json!(9_007_199_254_740_992.0)), Ordering::Greater);
+        assert_eq!(compare_values(&json!(9_007_199_254_740_992.0), &json!(big - 1)), Ordering::Equal);
+
+        // Numeric strings are strings, compared by code point
+        assert_eq!(compare_values(&json!("10"), &json!("9")), Ordering::Less);
+        assert_eq!(compare_values(&json!(100), &json!("9")), Ordering::Less);
+        assert_eq!(compare_values(&json!("Z"), &json!("a")), Ordering::Less);
+        assert_eq!(compare_values(&json!("é"), &json!("z")), Ordering::Greater);
+    }
+
     #[test]
     fn test_serialize_deserialize_index_key() {
         let key = IndexKey {
@@ -244,6 +297,20 @@ mod tests {
         assert_eq!(deserialized.doc_id, "doc456");
     }
 
+    #[test]
+    fn test_deserialize_compound_key_with_separator_in_values() {
+        let key = CompoundIndexKey {
+            field_values: vec![json!("a|b"), json!(["x|y", 2]), json!(3.5)],
+            doc_id: "id|1".to_string(),
+        };
+
+        let serialized = serialize_compound_index_key(&key).unwrap();
+        assert_eq!(deserialize_compound_index_key(&serialized, 3).unwrap(), key);
+
+        assert!(deserialize_compound_index_key("\"unterminated|id", 1).is_err());
+        assert!(deserialize_compound_index_key("1 2|id", 1).is_err());
+    }
+
     #[test]
     fn test_extract_field_value() {
         let doc = serde_json::from_value::<Map<String, Value>>(json!({
@@ -0,0 +1,332 @@
+//! Secondary index changes buffered by a transaction.
+//!
+//! Writes record the body each document had before and after the transaction.
+//! Index entries are only written to the shared index B-trees when the
+//! transaction commits, under the commit lock, so an aborted transaction leaves
+//! no entries behind and concurrent writers never see each other's keys.
+
+use crate::core::btree::BTree;
+use crate::core::constants::PageNum;
+use crate::core::errors::*;
+use crate::core::geo;
+use crate::core::expression::Expression;
+use crate::core::index_key::{
+    compound_index_key_prefix, extract_field_values, serialize_compound_index_key, CompoundIndexKey,
+};
+use crate::core::metadata::{CollectionMeta, IndexMeta, Metadata};
+use crate::core::pager::Pager;
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+type Document = Map<String, Value>;
+
+#[derive(Clone, Debug)]
+struct DocChange {
+    /// Body the committed index entries were built from, `None` for new documents
+    before: Option<Document>,
+    /// Body and page the document is left with, `None` once it is deleted
+    after: Option<(Document, PageNum)>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct CollectionWrites {
+    docs: HashMap<String, DocChange>,
+    /// Document holding each unique value written so far, by index name and key prefix
+    claims: HashMap<String, HashMap<String, String>>,
+}
+
+/// Documents written by a transaction, per collection, for index maintenance
+#[derive(Clone, Debug, Default)]
+pub(crate) struct IndexWrites {
+    collections: HashMap<String, CollectionWrites>,
+}
+
+/// New root of an index B-tree after [`IndexWrites::apply`]
+pub(crate) struct IndexRoot {
+    pub collection: String,
+    pub index: String,
+    pub root: PageNum,
+}
+
+impl IndexWrites {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.collections.is_empty()
+    }
+
+    /// Add the writes of another transaction, for checking a batch of
+    /// transactions against each other. Documents written by both keep the
+    /// other transaction's change.
+    pub(crate) fn extend(&mut self, other: &IndexWrites) {
+        for (collection, other_writes) in &other.collections {
+            let writes = self.collections.entry(collection.clone()).or_default();
+            writes.docs.extend(other_writes.docs.iter().map(|(id, change)| (id.clone(), change.clone())));
+        }
+    }
+
+    /// Fail if giving `doc_id` the body `doc` would put a value already held by
+    /// another document into one of the collection's unique indexes. Both
+    /// committed entries and earlier writes of this transaction are checked.
+    pub(crate) fn check_unique(
+        &self,
+        coll_meta: &CollectionMeta,
+        pager: &Arc<Pager>,
+        collection: &str,
+        doc_id: &str,
+        doc: &Document,
+    ) -> Result<()> {
+        let writes = self.collections.get(collection);
+
+        for index_meta in coll_meta.indexes.values().filter(|index_meta| index_meta.unique) {
+            let (values, prefix) = match unique_key(index_meta, doc)? {
+                Some(key) => key,
+                None => continue,
+            };
+
+            let claimed = writes
+                .and_then(|writes| writes.claims.get(&index_meta.name))
+                .and_then(|claims| claims.get(&prefix));
+            if claimed.is_some_and(|owner| owner != doc_id) {
+                return Err(duplicate_key(index_meta, &values));
+            }
+
+            let rewritten = |id: &str| id == doc_id || writes.is_some_and(|writes| writes.docs.contains_key(id));
+            if held_by_committed_document(pager, coll_meta, index_meta, &prefix, rewritten)? {
+                return Err(duplicate_key(index_meta, &values));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Record that `doc_id` went from `before` to `after` (`None` for a missing
+    /// document). Only the first before-image is kept, since that is what the
+    /// committed index entries were built from.
+    pub(crate) fn record(
+        &mut self,
+        coll_meta: &CollectionMeta,
+        collection: &str,
+        doc_id: &str,
+        before: Option<Document>,
+        after: Option<(Document, PageNum)>,
+    ) {
+        let writes = self.collections.entry(collection.to_string()).or_default();
+        let previous = writes.docs.get(doc_id).and_then(|change| change.after.as_ref());
+
+        for index_meta in coll_meta.indexes.values().filter(|index_meta| index_meta.unique) {
+            let claims = writes.claims.entry(index_meta.name.clone()).or_default();
+            if let Some((doc, _)) = previous {
+                if let Ok(Some((_, prefix))) = unique_key(index_meta, doc) {
+                    if claims.get(&prefix).is_some_and(|owner| owner == doc_id) {
+                        claims.remove(&prefix);
+                    }
+                }
+            }
+            if let Some((doc, _)) = &after {
+                if let Ok(Some((_, prefix))) = unique_key(index_meta, doc) {
+                    claims.insert(prefix, doc_id.to_string());
+                }
+            }
+        }
+
+        match writes.docs.get_mut(doc_id) {
+            Some(change) => change.after = after,
+            None => {
+                writes.docs.insert(doc_id.to_string(), DocChange { before, after });
+            }
+        }
+    }
+
+    /// Check every recorded write against the unique indexes in `metadata`.
+    /// Run at commit, since other transactions may have committed the same
+    /// values (or created new indexes) after the writes were first checked.
+    pub(crate) fn validate(&self, metadata: &Metadata, pager: &Arc<Pager>) -> Result<()> {
+        for (collection, writes) in &self.collections {
+            let coll_meta = match metadata.collections.get(collection) {
+                Some(coll_meta) => coll_meta,
+                None => continue,
+            };
+
+            for index_meta in coll_meta.indexes.values().filter(|index_meta| index_meta.unique) {
+                let mut taken = HashSet::new();
+
+                for change in writes.docs.values() {
+                    let doc = match &change.after {
+                        Some((doc, _)) => doc,
+                        None => continue,
+                    };
+                    let (values, prefix) = match unique_key(index_meta, doc)? {
+                        Some(key) => key,
+                        None => continue,
+                    };
+
+                    if !taken.insert(prefix.clone()) {
+                        return Err(duplicate_key(index_meta, &values));
+                    }
+
+                    let rewritten = |id: &str| writes.docs.contains_key(id);
+                    if held_by_committed_document(pager, coll_meta, index_meta, &prefix, rewritten)? {
+                        return Err(duplicate_key(index_meta, &values));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replace the index entries of every recorded document, returning the new
+    /// index roots to store in metadata. Must run under the commit lock, after
+    /// [`IndexWrites::validate`].
+    pub(crate) fn apply(&self, metadata: &Metadata, pager: &Arc<Pager>) -> Result<Vec<IndexRoot>> {
+        let mut roots = Vec::new();
+
+        for (collection, writes) in &self.collections {
+            let coll_meta = match metadata.collections.get(collection) {
+                Some(coll_meta) => coll_meta,
+                None => continue,
+            };
+
+            for index_meta in coll_meta.indexes.values() {
+                let index_btree = if index_meta.btree_root == 0 {
+                    BTree::new(pager.clone())?
+                } else {
+                    BTree::open(pager.clone(), index_meta.btree_root)
+                };
+
+                for (doc_id, change) in &writes.docs {
+                    if let Some(before) = &change.before {
+                        if let Some(key) = index_key(index_meta, doc_id, before)? {
+                            match index_btree.delete(&key) {
+                                Ok(()) | Err(Error::NotFound) => {}
+                                Err(e) => return Err(e),
+                            }
+                        }
+                    }
+                    if let Some((after, page_num)) = &change.after {
+                        if let Some(key) = index_key(index_meta, doc_id, after)? {
+                            index_btree.insert(&key, *page_num)?;
+                        }
+                    }
+                }
+
+                roots.push(IndexRoot {
+                    collection: collection.clone(),
+                    index: index_meta.name.clone(),
+                    root: index_btree.root_page(),
+                });
+            }
+        }
+
+        Ok(roots)
+    }
+}
+
+/// Point each index in `metadata` at its root from [`IndexWrites::apply`]
+pub(crate) fn store_index_roots(metadata: &mut Metadata, roots: &[IndexRoot]) {
+    for root in roots {
+        let index_meta = metadata.collections
+            .get_mut(&root.collection)
+            .and_then(|coll_meta| coll_meta.indexes.get_mut(&root.index));
+        if let Some(index_meta) = index_meta {
+            index_meta.btree_root = root.root;
+        }
+    }
+}
+
+/// Entry key for `doc_id` in `index_meta`, `None` if a sparse index leaves the document out
+///
+/// Geo indexes key documents by the geohash of their point, so that nearby
+/// points share a key prefix.
+pub(crate) fn index_key(index_meta: &IndexMeta, doc_id: &str, doc: &Document) -> Result<Option<String>> {
+    if index_meta.geo {
+        let fields = index_meta.get_fields();
+        let Some((lat, lng)) = geo::point_at(doc, &fields[0], &fields[1]) else {
+            return Ok(None);
+        };
+        return serialize_compound_index_key(&CompoundIndexKey {
+            field_values: vec![Value::String(geo::geohash(lat, lng, geo::GEOHASH_PRECISION))],
+            doc_id: doc_id.to_string(),
+        }).map(Some);
+    }
+
+    let field_values = indexed_values(index_meta, doc);
+    if !index_meta.indexes_values(&field_values) {
+        return Ok(None);
+    }
+    // A single value serializes the same way as a single-field key
+    serialize_compound_index_key(&CompoundIndexKey {
+        field_values,
+        doc_id: doc_id.to_string(),
+    }).map(Some)
+}
+
+/// Values `index_meta` holds for `doc`: its indexed fields, or the value an
+/// expression index computes, with strings folded under the index's
+/// collation. Geo indexes are keyed by [`index_key`] instead.
+pub(crate) fn indexed_values(index_meta: &IndexMeta, doc: &Document) -> Vec<Value> {
+    let fields = index_meta.get_fields();
+    let values = if index_meta.expression {
+        // Checked when the index was created, so only damaged metadata fails here
+        vec![Expression::parse(&fields[0]).map_or(Value::Null, |expr| expr.eval(doc))]
+    } else {
+        extract_field_values(doc, &fields)
+    };
+    if index_meta.collation.is_binary() {
+        return values;
+    }
+    values.iter().map(|value| index_meta.collation.fold_value(value).into_owned()).collect()
+}
+
+/// Indexed values of `doc` and the key prefix they share with every other
+/// document holding them, `None` if a sparse index leaves the document out
+pub(crate) fn unique_key(index_meta: &IndexMeta, doc: &Document) -> Result<Option<(Vec<Value>, String)>> {
+    let values = indexed_values(index_meta, doc);
+    if !index_meta.indexes_values(&values) {
+        return Ok(None);
+    }
+    let prefix = compound_index_key_prefix(&values)?;
+    Ok(Some((values, prefix)))
+}
+
+/// Whether a committed document has a live entry in `index_meta` starting with
+/// `prefix`. Documents for which `rewritten` returns true are skipped, since
+/// their entries are about to be replaced.
+fn held_by_committed_document(
+    pager: &Arc<Pager>,
+    coll_meta: &CollectionMeta,
+    index_meta: &IndexMeta,
+    prefix: &str,
+    rewritten: impl Fn(&str) -> bool,
+) -> Result<bool> {
+    if index_meta.btree_root == 0 || coll_meta.btree_root == 0 {
+        return Ok(false);
+    }
+
+    let index_btree = BTree::open(pager.clone(), index_meta.btree_root);
+    let primary = BTree::open(pager.clone(), coll_meta.btree_root);
+
+    for (key, page_num) in index_btree.scan_prefix(prefix)? {
+        let owner = &key[prefix.len()..];
+        if rewritten(owner) {
+            continue;
+        }
+        // Entries left behind by older versions point at pages the document has moved off
+        if primary.search(owner).ok() == Some(page_num) {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+fn duplicate_key(index_meta: &IndexMeta, values: &[Value]) -> Error {
+    let value = match values {
+        [value] => serde_json::to_string(value),
+        values => serde_json::to_string(values),
+    };
+    Error::DuplicateKey {
+        index: index_meta.name.clone(),
+        value: value.unwrap_or_default(),
+    }
+}
@@ -0,0 +1,475 @@
+use crate::core::btree::{deserialize_node, NodeType};
+use crate::core::constants::*;
+use crate::core::binary::decode_document;
+use crate::core::document::{read_versioned_document, versioned_document_pages};
+use crate::core::errors::*;
+use crate::core::index_writes::{index_key, unique_key};
+use crate::core::metadata::{page_chain, CollectionMeta, IndexMeta, Metadata};
+use crate::core::pager::Pager;
+use crate::core::text_search::{TextIndexMeta, Tokenizer};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+
+/// Individual mismatches reported per index before the rest are summarized
+const MAX_REPORTED_ENTRIES: usize = 10;
+
+/// Result of [`Database::check_integrity`](crate::Database::check_integrity)
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IntegrityReport {
+    /// Collections whose B-trees were walked
+    pub collections_checked: usize,
+    /// Documents decoded from the collection B-trees
+    pub documents_checked: u64,
+    /// Secondary and text indexes compared against the documents
+    pub indexes_checked: usize,
+    /// Pages in the database file, including the header page
+    pub total_pages: u64,
+    /// Pages on the free list
+    pub free_pages: u64,
+    /// Pages neither reachable from the metadata nor on the free list.
+    /// Copy-on-write leaves superseded pages behind until `vacuum` reclaims
+    /// them, so these are wasted space rather than corruption.
+    pub orphaned_pages: u64,
+    /// In-use pages whose on-disk checksum was verified; 0 for databases
+    /// created without page checksums. Pages only in the WAL so far are skipped.
+    pub pages_verified: u64,
+    /// Corruption found; empty when the database is consistent
+    pub problems: Vec<IntegrityProblem>,
+    /// Inconsistencies that don't lose data, such as text indexes that need
+    /// a rebuild
+    pub warnings: Vec<IntegrityProblem>,
+}
+
+impl IntegrityReport {
+    /// Whether no problems were found. Warnings and orphaned pages don't count.
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// A single finding of [`Database::check_integrity`](crate::Database::check_integrity)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IntegrityProblem {
+    /// Collection the finding belongs to, or None for file-level findings
+    pub collection: Option<String>,
+    /// Page the finding is about, if it concerns a single page
+    pub page: Option<PageNum>,
+    pub message: String,
+}
+
+impl std::fmt::Display for IntegrityProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(collection) = &self.collection {
+            write!(f, "{}: ", collection)?;
+        }
+        if let Some(page) = self.page {
+            write!(f, "page {}: ", page)?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+/// Check every collection in `metadata` against the pages it references.
+/// `version_pages` are first pages of superseded document versions still held
+/// for open snapshots; they count as in use.
+pub(crate) fn check(pager: &Pager, metadata: &Metadata, version_pages: &[PageNum]) -> Result<IntegrityReport> {
+    let mut checker = Checker {
+        pager,
+        num_pages: pager.num_pages()?,
+        owners: HashMap::new(),
+        report: IntegrityReport::default(),
+    };
+
+    checker.owners.insert(0, "file header".to_string());
+    for page in page_chain(pager, pager.metadata_page()?)? {
+        checker.claim(None, page, "metadata");
+    }
+
+    let mut names: Vec<&String> = metadata.collections.keys().collect();
+    names.sort();
+    for name in names {
+        checker.check_collection(name, &metadata.collections[name]);
+    }
+
+    for &page in version_pages {
+        match versioned_document_pages(pager, page) {
+            Ok(pages) => {
+                for page in pages {
+                    checker.owners.entry(page).or_insert_with(|| "old document version".to_string());
+                }
+            }
+            Err(_) => {
+                checker.owners.entry(page).or_insert_with(|| "old document version".to_string());
+            }
+        }
+    }
+
+    if pager.has_page_checksums() {
+        checker.verify_checksums();
+    }
+    checker.check_free_list()?;
+    Ok(checker.report)
+}
+
+struct Checker<'a> {
+    pager: &'a Pager,
+    num_pages: u64,
+    /// What each page reached so far belongs to, to catch pages used twice
+    owners: HashMap<PageNum, String>,
+    report: IntegrityReport,
+}
+
+impl Checker<'_> {
+    fn problem(&mut self, collection: Option<&str>, page: Option<PageNum>, message: String) {
+        self.report.problems.push(IntegrityProblem {
+            collection: collection.map(str::to_string),
+            page,
+            message,
+        });
+    }
+
+    fn warning(&mut self, collection: Option<&str>, message: String) {
+        self.report.warnings.push(IntegrityProblem {
+            collection: collection.map(str::to_string),
+            page: None,
+            message,
+        });
+    }
+
+    /// Record `page` as belonging to `owner`. Returns false, after reporting
+    /// it, if the page is outside the file or already belongs to something else.
+    fn claim(&mut self, collection: Option<&str>, page: PageNum, owner: &str) -> bool {
+        if page == 0 || page >= self.num_pages {
+            self.problem(collection, Some(page), format!(
+                "{} points outside the file ({} pages)", owner, self.num_pages
+            ));
+            return false;
+        }
+        if let Some(existing) = self.owners.get(&page) {
+            let message = format!("{} uses a page that already belongs to {}", owner, existing);
+            self.problem(collection, Some(page), message);
+            return false;
+        }
+        self.owners.insert(page, owner.to_string());
+        true
+    }
+
+    fn check_collection(&mut self, name: &str, meta: &CollectionMeta) {
+        self.report.collections_checked += 1;
+
+        let entries = if meta.btree_root == 0 {
+            Vec::new()
+        } else {
+            self.walk_tree(name, "document B-tree", meta.btree_root)
+        };
+
+        // Unreadable documents are reported on their own, so the count check uses the tree
+        let stored = entries.len() as u64;
+        let no_writes = HashMap::new();
+        let mut docs: Vec<(String, Map<String, Value>)> = Vec::with_capacity(entries.len());
+        for (id, page) in entries {
+            let owner = format!("document '{}'", id);
+            match versioned_document_pages(self.pager, page) {
+                Ok(pages) => {
+                    // The first page was already checked to be in range by the tree walk
+                    if !pages.iter().all(|&p| self.claim(Some(name), p, &owner)) {
+                        continue;
+                    }
+                }
+                Err(e) => {
+                    self.problem(Some(name), Some(page), format!("{} can't be read: {}", owner, e));
+                    continue;
+                }
+            }
+
+            let vdoc = match read_versioned_document(self.pager, page, &no_writes) {
+                Ok(vdoc) => vdoc,
+                Err(e) => {
+                    self.problem(Some(name), Some(page), format!("{} can't be read: {}", owner, e));
+                    continue;
+                }
+            };
+            self.report.documents_checked += 1;
+
+            if vdoc.id != id {
+                self.problem(Some(name), Some(page), format!("{} is stored under id '{}'", owner, vdoc.id));
+            }
+            match decode_document::<Map<String, Value>>(&vdoc.data) {
+                Ok(doc) => docs.push((id, doc)),
+                Err(e) => self.problem(Some(name), Some(page), format!("{} is not a JSON object: {}", owner, e)),
+            }
+        }
+
+        if let Some(count) = meta.document_count {
+            if count != stored {
+                self.problem(Some(name), None, format!(
+                    "metadata records {} documents but the collection holds {}", count, stored
+                ));
+            }
+        }
+
+        let mut indexes: Vec<&IndexMeta> = meta.indexes.values().collect();
+        indexes.sort_by(|a, b| a.name.cmp(&b.name));
+        for index in indexes {
+            self.check_index(name, index, &docs);
+        }
+
+        let mut text_indexes: Vec<&TextIndexMeta> = meta.text_indexes.values().collect();
+        text_indexes.sort_by(|a, b| a.name.cmp(&b.name));
+        for text_index in text_indexes {
+            self.check_text_index(name, text_index, &docs);
+        }
+    }
+
+    /// Compare a secondary index with the entries its documents should have
+    fn check_index(&mut self, collection: &str, index: &IndexMeta, docs: &[(String, Map<String, Value>)]) {
+        self.report.indexes_checked += 1;
+        let label = format!("index '{}'", index.name);
+
+        let actual: HashSet<String> = if index.btree_root == 0 {
+            HashSet::new()
+        } else {
+            self.walk_tree(collection, &label, index.btree_root).into_iter().map(|(key, _)| key).collect()
+        };
+
+        let mut expected = HashSet::with_capacity(docs.len());
+        let mut unique_values: HashMap<String, usize> = HashMap::new();
+        for (id, doc) in docs {
+            match index_key(index, id, doc) {
+                Ok(Some(key)) => {
+                    expected.insert(key);
+                }
+                Ok(None) => continue,
+                Err(e) => {
+                    self.problem(Some(collection), None, format!("{} can't encode document '{}': {}", label, id, e));
+                    continue;
+                }
+            }
+            if index.unique {
+                if let Ok(Some((_, prefix))) = unique_key(index, doc) {
+                    *unique_values.entry(prefix).or_default() += 1;
+                }
+            }
+        }
+
+        let mut stale: Vec<&String> = actual.difference(&expected).collect();
+        stale.sort();
+        self.report_entries(collection, &label, "has an entry for a missing or changed document", &stale);
+
+        let mut missing: Vec<&String> = expected.difference(&actual).collect();
+        missing.sort();
+        self.report_entries(collection, &label, "is missing the entry", &missing);
+
+        let mut duplicates: Vec<(String, usize)> = unique_values.into_iter().filter(|(_, n)| *n > 1).collect();
+        duplicates.sort();
+        for (value, count) in duplicates {
+            self.problem(Some(collection), None, format!(
+                "unique {} holds {} documents with the value {}", label, count, value
+            ));
+        }
+    }
+
+    /// Report the first few mismatched index entries one by one and summarize the rest
+    fn report_entries(&mut self, collection: &str, label: &str, what: &str, keys: &[&String]) {
+        for key in keys.iter().take(MAX_REPORTED_ENTRIES) {
+            self.problem(Some(collection), None, format!("{} {} '{}'", label, what, key));
+        }
+        if keys.len() > MAX_REPORTED_ENTRIES {
+            self.problem(Some(collection), None, format!(
+                "{} {} for {} more document(s)", label, what, keys.len() - MAX_REPORTED_ENTRIES
+            ));
+        }
+    }
+
+    /// Check that a text index's posting lists decode and list the documents
+    /// holding each term. Text indexes are built when created and refreshed by
+    /// `rebuild_index`, so differences are warnings.
+    fn check_text_index(&mut self, collection: &str, meta: &TextIndexMeta, docs: &[(String, Map<String, Value>)]) {
+        self.report.indexes_checked += 1;
+        let label = format!("text index '{}'", meta.name);
+
+        let entries = if meta.btree_root == 0 {
+            Vec::new()
+        } else {
+            self.walk_tree(collection, &label, meta.btree_root)
+        };
+
+        let tokenizer = Tokenizer::new(&meta.options);
+        let mut expected: HashMap<String, HashSet<&str>> = HashMap::new();
+        for (id, doc) in docs {
+            for field in &meta.fields {
+                if let Some(text) = doc.get(field).and_then(Value::as_str) {
+                    for term in tokenizer.tokenize(text) {
+                        expected.entry(format!("{}:{}", term, field)).or_default().insert(id);
+                    }
+                }
+            }
+        }
+
+        let mut stale = 0;
+        let mut missing = 0;
+        for (key, page) in entries {
+            if !self.claim(Some(collection), page, &format!("{} entry '{}'", label, key)) {
+                continue;
+            }
+
+            let data = match self.pager.read_page(page) {
+                Ok(data) => data,
+                Err(e) => {
+                    self.problem(Some(collection), Some(page), format!("{} entry '{}' can't be read: {}", label, key, e));
+                    continue;
+                }
+            };
+            let end = data.iter().rposition(|&b| b != 0).map_or(0, |pos| pos + 1);
+            let postings = match serde_json::from_slice::<Value>(&data[..end]) {
+                Ok(Value::Object(postings)) => postings,
+                _ => {
+                    self.problem(Some(collection), Some(page), format!("{} entry '{}' is not valid JSON", label, key));
+                    continue;
+                }
+            };
+
+            // Field statistics are stored under keys starting with NUL
+            if key.starts_with('\u{0}') {
+                continue;
+            }
+
+            let holders = expected.remove(&key).unwrap_or_default();
+            stale += postings.keys().filter(|id| !holders.contains(id.as_str())).count();
+            missing += holders.iter().filter(|id| !postings.contains_key(**id)).count();
+        }
+        missing += expected.values().map(HashSet::len).sum::<usize>();
+
+        if stale > 0 || missing > 0 {
+            self.warning(Some(collection), format!(
+                "{} is out of date ({} stale and {} missing postings); rebuild it with rebuild_index",
+                label, stale, missing
+            ));
+        }
+    }
+
+    /// Walk a B-tree checking that its nodes decode, keys are in order and
+    /// within their parent's bounds, and all leaves are at the same depth.
+    /// Returns the leaf entries in key order.
+    fn walk_tree(&mut self, collection: &str, label: &str, root: PageNum) -> Vec<(String, u64)> {
+        let mut walk = TreeWalk { label, leaf_depth: None, entries: Vec::new() };
+        self.walk_node(collection, &mut walk, root, None, None, 0);
+        walk.entries
+    }
+
+    fn walk_node(
+        &mut self,
+        collection: &str,
+        walk: &mut TreeWalk<'_>,
+        page: PageNum,
+        lower: Option<&str>,
+        upper: Option<&str>,
+        depth: usize,
+    ) {
+        if !self.claim(Some(collection), page, &format!("{} node", walk.label)) {
+            return;
+        }
+
+        let node = match self.pager.read_page(page).and_then(|data| deserialize_node(page, &data)) {
+            Ok(node) => node,
+            Err(e) => {
+                self.problem(Some(collection), Some(page), format!("{} node can't be read: {}", walk.label, e));
+                return;
+            }
+        };
+
+        let in_bounds = |key: &str| lower.is_none_or(|lower| key >= lower) && upper.is_none_or(|upper| key < upper);
+
+        if node.node_type == NodeType::LeafNode {
+            match walk.leaf_depth {
+                Some(expected) if expected != depth => {
+                    self.problem(Some(collection), Some(page), format!(
+                        "{} leaf is at depth {} but other leaves are at depth {}", walk.label, depth, expected
+                    ));
+                }
+                Some(_) => {}
+                None => walk.leaf_depth = Some(depth),
+            }
+
+            for (i, entry) in node.entries.iter().enumerate() {
+                if i > 0 && node.entries[i - 1].key >= entry.key {
+                    self.problem(Some(collection), Some(page), format!(
+                        "{} leaf keys are out of order at '{}'", walk.label, entry.key
+                    ));
+                } else if !in_bounds(&entry.key) {
+                    self.problem(Some(collection), Some(page), format!(
+                        "{} key '{}' is outside the range its parent routes to this leaf", walk.label, entry.key
+                    ));
+                }
+                walk.entries.push((entry.key.clone(), entry.value));
+            }
+            return;
+        }
+
+        if node.children.len() != node.keys.len() + 1 {
+            self.problem(Some(collection), Some(page), format!(
+                "{} internal node has {} keys but {} children", walk.label, node.keys.len(), node.children.len()
+            ));
+            return;
+        }
+        if node.keys.windows(2).any(|pair| pair[0] >= pair[1]) {
+            self.problem(Some(collection), Some(page), format!("{} internal node keys are out of order", walk.label));
+            return;
+        }
+
+        for (i, &child) in node.children.iter().enumerate() {
+            let child_lower = if i == 0 { lower } else { Some(node.keys[i - 1].as_str()) };
+            let child_upper = node.keys.get(i).map(String::as_str).or(upper);
+            self.walk_node(collection, walk, child, child_lower, child_upper, depth + 1);
+        }
+    }
+
+    /// Verify the stored checksum of every page in use. Pages that already
+    /// failed to read are not reported again.
+    fn verify_checksums(&mut self) {
+        let reported: HashSet<PageNum> = self.report.problems.iter().filter_map(|p| p.page).collect();
+        let mut pages: Vec<(PageNum, String)> = self.owners.iter()
+            .filter(|(page, _)| **page != 0 && !reported.contains(page))
+            .map(|(page, owner)| (*page, owner.clone()))
+            .collect();
+        pages.sort();
+
+        for (page, owner) in pages {
+            match self.pager.verify_page(page) {
+                Ok(true) => self.report.pages_verified += 1,
+                Ok(false) => {}
+                Err(e) => self.problem(None, Some(page), format!("{} can't be read: {}", owner, e)),
+            }
+        }
+    }
+
+    /// Check the free list against the pages found in use and count the
+    /// pages that belong to nothing
+    fn check_free_list(&mut self) -> Result<()> {
+        let mut free = HashSet::new();
+        for page in self.pager.free_pages()? {
+            if page == 0 || page >= self.num_pages {
+                self.problem(None, Some(page), format!("free list entry is outside the file ({} pages)", self.num_pages));
+            } else if let Some(owner) = self.owners.get(&page) {
+                let message = format!("page is on the free list but belongs to {}", owner);
+                self.problem(None, Some(page), message);
+            } else if !free.insert(page) {
+                self.problem(None, Some(page), "page is on the free list twice".to_string());
+            }
+        }
+
+        let in_use = self.owners.keys().filter(|&&page| page < self.num_pages).count() as u64;
+        self.report.total_pages = self.num_pages;
+        self.report.free_pages = free.len() as u64;
+        self.report.orphaned_pages = self.num_pages.saturating_sub(in_use + free.len() as u64);
+        Ok(())
+    }
+}
+
+/// State carried through one B-tree walk
+struct TreeWalk<'a> {
+    label: &'a str,
+    leaf_depth: Option<usize>,
+    entries: Vec<(String, u64)>,
+}
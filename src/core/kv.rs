@@ -0,0 +1,128 @@
+use crate::core::collection::Collection;
+use crate::core::errors::*;
+use serde_json::{Map, Value};
+
+/// A collection used as a key-value store, from [`Database::kv`]
+///
+/// Each key is a document's `_id` and its value is the rest of the document,
+/// so values are JSON objects. Reads and writes go straight to the primary
+/// key B-tree without parsing or planning a query. `set` replaces the whole
+/// value, unlike `update_by_id`, which keeps the fields it isn't given.
+///
+/// [`Database::kv`]: crate::Database::kv
+///
+/// # Examples
+/// ```no_run
+/// # use jasonisnthappy::Database;
+/// use serde_json::json;
+///
+/// # let db = Database::open("my.db").unwrap();
+/// let settings = db.kv("settings");
+/// settings.set("theme", json!({"mode": "dark"})).unwrap();
+/// settings.mset(&[("lang", json!({"code": "en"})), ("tz", json!({"name": "UTC"}))]).unwrap();
+///
+/// assert_eq!(settings.get("theme").unwrap(), Some(json!({"mode": "dark"})));
+/// assert_eq!(settings.mget(&["lang", "missing"]).unwrap()[1], None);
+/// ```
+#[derive(Clone)]
+pub struct KvStore {
+    collection: Collection,
+}
+
+impl KvStore {
+    pub(crate) fn new(collection: Collection) -> Self {
+        Self { collection }
+    }
+
+    /// The collection the values are stored in
+    pub fn collection(&self) -> &Collection {
+        &self.collection
+    }
+
+    /// The value stored under `key`, if any
+    pub fn get(&self, key: &str) -> Result<Option<Value>> {
+        match self.collection.find_by_id(key) {
+            Ok(doc) => Ok(Some(value_of(doc))),
+            Err(Error::NotFound | Error::DocumentNotFound { .. } | Error::CollectionDoesNotExist { .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The values stored under `keys`, read from one snapshot, in the order
+    /// of `keys` with `None` for keys that aren't set
+    pub fn mget(&self, keys: &[&str]) -> Result<Vec<Option<Value>>> {
+        Ok(self.collection.find_by_ids(keys)?
+            .into_iter()
+            .map(|doc| doc.map(value_of))
+            .collect())
+    }
+
+    /// Store `value` under `key`, replacing what was there
+    pub fn set(&self, key: &str, value: Value) -> Result<()> {
+        self.mset(&[(key, value)])
+    }
+
+    /// Store every pair in one transaction: either all of them are written or,
+    /// if one fails, none are. A key given twice ends up with its last value.
+    pub fn mset(&self, pairs: &[(&str, Value)]) -> Result<()> {
+        let name = self.collection.name();
+        let docs = pairs.iter()
+            .map(|(key, value)| document_of(name, key, value))
+            .collect::<Result<Vec<_>>>()?;
+
+        self.collection.database().run_transaction(|tx| {
+            let mut coll = tx.collection(name)?;
+            for (key, doc) in pairs.iter().map(|(key, _)| key).zip(&docs) {
+                match coll.find_by_id(key) {
+                    Ok(_) => coll.replace_by_id(key, doc.clone())?,
+                    Err(Error::NotFound | Error::DocumentNotFound { .. }) => {
+                        coll.insert(Value::Object(doc.clone()))?;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Remove `key`. Returns whether it was set.
+    pub fn delete(&self, key: &str) -> Result<bool> {
+        match self.collection.delete_by_id(key) {
+            Ok(()) => Ok(true),
+            Err(Error::NotFound | Error::DocumentNotFound { .. } | Error::CollectionDoesNotExist { .. }) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// The document storing `value` under `key`
+fn document_of(collection: &str, key: &str, value: &Value) -> Result<Map<String, Value>> {
+    let mut doc = value.as_object()
+        .ok_or_else(|| Error::InvalidDocumentFormat {
+            reason: format!("value of '{}' must be an object", key),
+            collection: Some(collection.to_string()),
+        })?
+        .clone();
+
+    match doc.get("_id") {
+        Some(Value::String(id)) if id == key => {}
+        Some(_) => {
+            return Err(Error::InvalidDocumentFormat {
+                reason: format!("value of '{}' has a different _id", key),
+                collection: Some(collection.to_string()),
+            });
+        }
+        None => {
+            doc.insert("_id".to_string(), Value::String(key.to_string()));
+        }
+    }
+    Ok(doc)
+}
+
+/// The value a stored document holds: everything but its `_id`
+fn value_of(mut doc: Value) -> Value {
+    if let Some(map) = doc.as_object_mut() {
+        map.remove("_id");
+    }
+    doc
+}
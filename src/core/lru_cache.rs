@@ -157,12 +157,13 @@ impl LRUCacheInner {
         }
     }
 
-    fn put(&mut self, page_num: PageNum, data: Vec<u8>) {
+    fn put(&mut self, page_num: PageNum, data: Vec<u8>) -> Option<PageNum> {
         if let Some(&idx) = self.cache.get(&page_num) {
             self.move_to_front(idx);
             if let Some(node) = &mut self.nodes[idx] {
                 node.data = data;
             }
+            None
         } else {
             let idx = self.allocate_node(page_num, data);
             self.cache.insert(page_num, idx);
@@ -186,14 +187,16 @@ impl LRUCacheInner {
             }
 
             if self.cache.len() > self.capacity {
-                self.evict();
+                self.evict()
+            } else {
+                None
             }
         }
     }
 
     // Atomic put + mark dirty: adds page to cache and marks dirty, then evicts if needed
     // This prevents race condition where flush() might see dirty flag but page not in cache
-    fn put_dirty_atomic(&mut self, page_num: PageNum, data: Vec<u8>) {
+    fn put_dirty_atomic(&mut self, page_num: PageNum, data: Vec<u8>) -> Option<PageNum> {
         // If page already exists, just update it
         if let Some(&idx) = self.cache.get(&page_num) {
             self.move_to_front(idx);
@@ -201,7 +204,7 @@ impl LRUCacheInner {
                 node.data = data;
             }
             self.dirty.insert(page_num, true);
-            return;
+            return None;
         }
 
         // New page: add to cache first, then mark dirty, then evict if needed
@@ -231,15 +234,18 @@ impl LRUCacheInner {
 
         // Now check if we need to evict
         if self.cache.len() > self.capacity {
-            self.evict();
+            self.evict()
+        } else {
+            None
         }
     }
 
-    fn evict(&mut self) {
+    /// Drops the least recently used clean page, returning its number
+    fn evict(&mut self) -> Option<PageNum> {
         let mut current = self.tail;
         let mut checked = 0;
         let max_checks = self.cache.len();
-        let mut found_clean = false;
+        let mut evicted = None;
 
         // First pass: try to evict a clean (non-dirty) page
         while let Some(idx) = current {
@@ -257,7 +263,7 @@ impl LRUCacheInner {
                     self.cache.remove(&page_num);
                     self.remove_node(idx);
                     self.failed_evictions = 0; // Reset counter on successful eviction
-                    found_clean = true;
+                    evicted = Some(page_num);
                     break;
                 }
 
@@ -267,9 +273,10 @@ impl LRUCacheInner {
             }
         }
 
-        if !found_clean {
+        if evicted.is_none() {
             self.failed_evictions += 1;
         }
+        evicted
     }
 
     fn remove(&mut self, page_num: PageNum) {
@@ -315,10 +322,11 @@ impl LRUCache {
         inner.get_read_only(page_num)
     }
 
-    pub fn put(&self, page_num: PageNum, data: Vec<u8>) {
+    /// Caches a clean page, returning the page evicted to make room, if any
+    pub fn put(&self, page_num: PageNum, data: Vec<u8>) -> Option<PageNum> {
         let mut inner = self.inner.write()
             .recover_poison();
-        inner.put(page_num, data);
+        inner.put(page_num, data)
     }
 
     pub fn mark_dirty(&self, page_num: PageNum) {
@@ -327,11 +335,11 @@ impl LRUCache {
         inner.dirty.insert(page_num, true);
     }
 
-    pub fn put_dirty(&self, page_num: PageNum, data: Vec<u8>) {
+    pub fn put_dirty(&self, page_num: PageNum, data: Vec<u8>) -> Option<PageNum> {
         let mut inner = self.inner.write()
             .recover_poison();
         // Use atomic version that adds to cache and marks dirty without eviction window
-        inner.put_dirty_atomic(page_num, data);
+        inner.put_dirty_atomic(page_num, data)
     }
 
     pub fn clear_dirty(&self, page_num: PageNum) {
@@ -352,6 +360,12 @@ impl LRUCache {
         inner.dirty.keys().copied().collect()
     }
 
+    pub fn has_dirty(&self) -> bool {
+        let inner = self.inner.read()
+            .recover_poison();
+        !inner.dirty.is_empty()
+    }
+
     pub fn clear_all_dirty(&self) {
         let mut inner = self.inner.write()
             .recover_poison();
@@ -364,6 +378,14 @@ impl LRUCache {
         inner.remove(page_num);
     }
 
+    /// Drop every cached page, dirty or not
+    pub fn clear(&self) {
+        let mut inner = self.inner.write()
+            .recover_poison();
+        let capacity = inner.capacity;
+        *inner = LRUCacheInner::new(capacity);
+    }
+
     pub fn len(&self) -> usize {
         let inner = self.inner.read()
             .recover_poison();
@@ -373,6 +395,11 @@ impl LRUCache {
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
+
+    /// Most pages held before the least recently used are evicted
+    pub fn capacity(&self) -> usize {
+        self.inner.read().recover_poison().capacity
+    }
 }
 
 #[cfg(test)]
@@ -1,9 +1,20 @@
 
 use serde::{Serialize, Deserialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use crate::core::constants::PageNum;
 use crate::core::errors::*;
+use crate::core::pager::Pager;
 use crate::core::validation::Schema;
+use crate::core::collation::Collation;
 use crate::core::text_search::TextIndexMeta;
+use crate::core::id_strategy::IdStrategy;
+use crate::core::query_builder::SortOrder;
+
+/// Metadata that doesn't fit in one page is spread over a chain of pages,
+/// each starting with a zero byte and the number of the next page. A page of
+/// plain JSON always starts with `{`, so single-page metadata keeps the
+/// format older versions wrote.
+const CHAIN_HEADER_SIZE: usize = 9;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Metadata {
@@ -19,6 +30,30 @@ pub struct CollectionMeta {
     pub text_indexes: HashMap<String, TextIndexMeta>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub schema: Option<Schema>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_strategy: Option<IdStrategy>,
+    /// Last number handed out by the `AutoIncrement` id strategy
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub last_auto_id: u64,
+    /// Number of live documents as of the last commit. `None` for collections
+    /// written by older versions until the count is rebuilt on open.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document_count: Option<u64>,
+    /// Order queries return documents in when they don't ask for one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub default_sort: Vec<(String, SortOrder)>,
+    /// Deletes move documents into the collection's recycle bin instead of
+    /// removing them
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub soft_delete: bool,
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -33,6 +68,24 @@ pub struct IndexMeta {
     pub fields: Vec<String>,
     pub btree_root: u64,
     pub unique: bool,
+    /// Entries are returned in descending key order
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub descending: bool,
+    /// Documents missing any indexed field are left out of the index. An
+    /// explicit JSON `null` counts as missing.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub sparse: bool,
+    /// Geo index over a latitude and a longitude field, holding one geohash
+    /// per document instead of the field values
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub geo: bool,
+    /// Expression index keyed by the value its only field, an expression such
+    /// as `lower(email)`, computes for each document
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub expression: bool,
+    /// Collation the indexed strings are folded under before being stored
+    #[serde(default, skip_serializing_if = "Collation::is_binary")]
+    pub collation: Collation,
 }
 
 impl IndexMeta {
@@ -52,6 +105,12 @@ impl IndexMeta {
     pub fn is_compound(&self) -> bool {
         self.get_fields().len() > 1
     }
+
+    /// Whether a document with these indexed field values gets an entry.
+    /// Always true unless the index is sparse.
+    pub fn indexes_values(&self, values: &[serde_json::Value]) -> bool {
+        !self.sparse || values.iter().all(|value| !value.is_null())
+    }
 }
 
 impl Metadata {
@@ -74,12 +133,46 @@ impl Metadata {
         Ok(serde_json::from_slice(trimmed)?)
     }
 
+    /// Serialize into page-sized buffers, one page when the metadata fits.
+    /// Pages of a longer chain point nowhere until linked with
+    /// [`link_page`](Self::link_page).
+    pub(crate) fn serialize_pages(&self, page_size: usize) -> Result<Vec<Vec<u8>>> {
+        let mut data = self.serialize()?;
+        if data.len() <= page_size {
+            data.resize(page_size, 0);
+            return Ok(vec![data]);
+        }
+
+        Ok(data.chunks(page_size - CHAIN_HEADER_SIZE).map(|chunk| {
+            let mut page = vec![0; page_size];
+            page[CHAIN_HEADER_SIZE..CHAIN_HEADER_SIZE + chunk.len()].copy_from_slice(chunk);
+            page
+        }).collect())
+    }
+
+    /// Point a page from [`serialize_pages`](Self::serialize_pages) at the
+    /// page holding the next part of the chain
+    pub(crate) fn link_page(page: &mut [u8], next: PageNum) {
+        page[1..CHAIN_HEADER_SIZE].copy_from_slice(&next.to_le_bytes());
+    }
+
+    /// Read the metadata starting at `first_page`
+    pub(crate) fn read(pager: &Pager, first_page: PageNum) -> Result<Self> {
+        let (_, data) = read_chain(pager, first_page)?;
+        Self::deserialize(&data)
+    }
+
     pub fn get_collection(&mut self, name: &str) -> &mut CollectionMeta {
         self.collections.entry(name.to_string()).or_insert(CollectionMeta {
             btree_root: 0,
             indexes: HashMap::new(),
             text_indexes: HashMap::new(),
             schema: None,
+            id_strategy: None,
+            last_auto_id: 0,
+            document_count: Some(0),
+            default_sort: Vec::new(),
+            soft_delete: false,
         })
     }
 
@@ -95,16 +188,71 @@ impl Metadata {
                             fields: iv.fields.clone(),
                             btree_root: iv.btree_root,
                             unique: iv.unique,
+                            descending: iv.descending,
+                            sparse: iv.sparse,
+                            geo: iv.geo,
+                            expression: iv.expression,
+                            collation: iv.collation,
                         })
                     }).collect(),
                     text_indexes: v.text_indexes.clone(),
                     schema: v.schema.clone(),
+                    id_strategy: v.id_strategy,
+                    last_auto_id: v.last_auto_id,
+                    document_count: v.document_count,
+                    default_sort: v.default_sort.clone(),
+                    soft_delete: v.soft_delete,
                 })
             }).collect(),
         }
     }
 }
 
+/// Pages holding the metadata that starts at `first_page`, none for 0
+pub(crate) fn page_chain(pager: &Pager, first_page: PageNum) -> Result<Vec<PageNum>> {
+    if first_page == 0 {
+        return Ok(Vec::new());
+    }
+    Ok(read_chain(pager, first_page)?.0)
+}
+
+fn read_chain(pager: &Pager, first_page: PageNum) -> Result<(Vec<PageNum>, Vec<u8>)> {
+    let first = pager.read_page(first_page)?;
+    if first.first() != Some(&0) {
+        return Ok((vec![first_page], first));
+    }
+
+    let num_pages = pager.num_pages()?;
+    let mut pages = Vec::new();
+    let mut data = Vec::new();
+    let mut seen = HashSet::new();
+    let mut page_num = first_page;
+    let mut page = first;
+    loop {
+        if page.len() < CHAIN_HEADER_SIZE || page[0] != 0 {
+            return Err(Error::DataCorruption {
+                details: format!("metadata page {} is not part of a metadata chain", page_num),
+            });
+        }
+        pages.push(page_num);
+        seen.insert(page_num);
+        data.extend_from_slice(&page[CHAIN_HEADER_SIZE..]);
+
+        let next = u64::from_le_bytes(page[1..CHAIN_HEADER_SIZE].try_into()
+            .map_err(|_| Error::DataCorruption { details: "failed to parse next metadata page".to_string() })?);
+        if next == 0 {
+            return Ok((pages, data));
+        }
+        if next >= num_pages || seen.contains(&next) {
+            return Err(Error::DataCorruption {
+                details: format!("metadata page {} points at invalid page {}", page_num, next),
+            });
+        }
+        page_num = next;
+        page = pager.read_page(page_num)?;
+    }
+}
+
 impl Default for Metadata {
     fn default() -> Self {
         Self::new()
@@ -146,6 +294,11 @@ mod tests {
             fields: vec!["email".to_string()],
             btree_root: 200,
             unique: true,
+            descending: false,
+            sparse: false,
+            geo: false,
+            expression: false,
+            collation: Collation::Binary,
         });
 
         let data = meta.serialize().unwrap();
@@ -163,6 +316,26 @@ mod tests {
         assert!(idx.unique);
     }
 
+    #[test]
+    fn test_serialize_pages() {
+        let mut meta = Metadata::new();
+        meta.get_collection("users");
+        let pages = meta.serialize_pages(4096).unwrap();
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0][0], b'{');
+
+        for i in 0..200 {
+            meta.get_collection(&format!("collection_{}", i));
+        }
+        let data = meta.serialize().unwrap();
+        let pages = meta.serialize_pages(4096).unwrap();
+        assert_eq!(pages.len(), data.len().div_ceil(4096 - CHAIN_HEADER_SIZE));
+        assert!(pages.iter().all(|page| page.len() == 4096 && page[0] == 0));
+
+        let joined: Vec<u8> = pages.iter().flat_map(|page| page[CHAIN_HEADER_SIZE..].to_vec()).collect();
+        assert_eq!(Metadata::deserialize(&joined).unwrap().collections.len(), 201);
+    }
+
     #[test]
     fn test_clone() {
         let mut meta = Metadata::new();
@@ -175,6 +348,11 @@ mod tests {
             fields: vec!["email".to_string()],
             btree_root: 200,
             unique: true,
+            descending: false,
+            sparse: false,
+            geo: false,
+            expression: false,
+            collation: Collation::Binary,
         });
 
         let meta2 = meta.clone();
@@ -196,6 +374,11 @@ mod tests {
             fields: Vec::new(),
             btree_root: 100,
             unique: false,
+            descending: false,
+            sparse: false,
+            geo: false,
+            expression: false,
+            collation: Collation::Binary,
         });
 
         let idx = &coll.indexes["old_idx"];
@@ -213,6 +396,11 @@ mod tests {
             fields: vec!["city".to_string(), "age".to_string()],
             btree_root: 200,
             unique: false,
+            descending: false,
+            sparse: false,
+            geo: false,
+            expression: false,
+            collation: Collation::Binary,
         });
 
         let idx = &coll.indexes["compound_idx"];
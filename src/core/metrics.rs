@@ -1,4 +1,335 @@
+use std::collections::HashMap;
+use std::fmt::Write;
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Upper bounds of the latency histogram buckets in microseconds. Anything
+/// slower lands in a final overflow bucket.
+pub const LATENCY_BUCKETS_MICROS: [u64; 14] = [
+    50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000, 250_000, 500_000, 1_000_000,
+];
+
+/// Operations whose latency is tracked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Insert,
+    Find,
+    Update,
+    Delete,
+    Commit,
+}
+
+impl Operation {
+    pub const ALL: [Operation; 5] = [
+        Operation::Insert,
+        Operation::Find,
+        Operation::Update,
+        Operation::Delete,
+        Operation::Commit,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operation::Insert => "insert",
+            Operation::Find => "find",
+            Operation::Update => "update",
+            Operation::Delete => "delete",
+            Operation::Commit => "commit",
+        }
+    }
+}
+
+/// Sub-buckets per power of two in the log-linear buckets behind the
+/// percentiles, which keeps their relative error under 1/8
+const SUB_BUCKET_BITS: u32 = 3;
+const SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+
+/// Durations of 2^36 microseconds (about 19 hours) and up share the last
+/// log-linear bucket
+const MAX_EXPONENT: u32 = 36;
+
+const LOG_BUCKETS: usize = SUB_BUCKETS * (MAX_EXPONENT - SUB_BUCKET_BITS + 1) as usize;
+
+/// Percentiles computed for every histogram snapshot
+const SNAPSHOT_PERCENTILES: [f64; 4] = [0.5, 0.9, 0.95, 0.99];
+
+/// Log-linear bucket holding `micros`, HDR-histogram style: values below
+/// `SUB_BUCKETS` get a bucket each, and every power of two above that is
+/// split into `SUB_BUCKETS` equal parts
+#[inline]
+fn log_bucket(micros: u64) -> usize {
+    if micros < SUB_BUCKETS as u64 {
+        return micros as usize;
+    }
+    let exponent = 63 - micros.leading_zeros();
+    if exponent >= MAX_EXPONENT {
+        return LOG_BUCKETS - 1;
+    }
+    let shift = exponent - SUB_BUCKET_BITS;
+    SUB_BUCKETS * (shift as usize + 1) + (micros >> shift) as usize - SUB_BUCKETS
+}
+
+/// Largest value that lands in log-linear bucket `index`
+fn log_bucket_upper_bound(index: usize) -> u64 {
+    if index < SUB_BUCKETS {
+        return index as u64;
+    }
+    let shift = (index / SUB_BUCKETS - 1) as u32;
+    let sub_bucket = (SUB_BUCKETS + index % SUB_BUCKETS) as u64;
+    ((sub_bucket + 1) << shift) - 1
+}
+
+/// Latency histogram. Observations go into the fixed buckets exported to
+/// Prometheus and into finer log-linear buckets used for percentiles, so
+/// recording is a handful of relaxed atomic operations and never locks.
+#[derive(Debug)]
+pub struct LatencyHistogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS_MICROS.len() + 1],
+    log_buckets: [AtomicU64; LOG_BUCKETS],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+    max_micros: AtomicU64,
+}
+
+/// Snapshot of a latency histogram. `buckets[i]` counts observations at or
+/// below `LATENCY_BUCKETS_MICROS[i]` and above the previous bound; the last
+/// entry counts everything slower than the largest bound. The percentile
+/// fields are `None` while nothing was recorded.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct HistogramSnapshot {
+    pub buckets: Vec<u64>,
+    pub count: u64,
+    pub sum_micros: u64,
+    pub max_micros: u64,
+    pub p50_micros: Option<u64>,
+    pub p90_micros: Option<u64>,
+    pub p95_micros: Option<u64>,
+    pub p99_micros: Option<u64>,
+    #[serde(skip)]
+    log_buckets: Vec<u64>,
+}
+
+impl LatencyHistogram {
+    pub fn new() -> Self {
+        Self {
+            buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+            log_buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+            count: AtomicU64::new(0),
+            sum_micros: AtomicU64::new(0),
+            max_micros: AtomicU64::new(0),
+        }
+    }
+
+    #[inline]
+    pub fn record(&self, elapsed: Duration) {
+        let micros = elapsed.as_micros() as u64;
+        let bucket = LATENCY_BUCKETS_MICROS
+            .iter()
+            .position(|&bound| micros <= bound)
+            .unwrap_or(LATENCY_BUCKETS_MICROS.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.log_buckets[log_bucket(micros)].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros.fetch_add(micros, Ordering::Relaxed);
+        self.max_micros.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    pub fn reset(&self) {
+        for bucket in self.buckets.iter().chain(&self.log_buckets) {
+            bucket.store(0, Ordering::Relaxed);
+        }
+        self.count.store(0, Ordering::Relaxed);
+        self.sum_micros.store(0, Ordering::Relaxed);
+        self.max_micros.store(0, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        let mut snapshot = HistogramSnapshot {
+            buckets: self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).collect(),
+            log_buckets: self.log_buckets.iter().map(|b| b.load(Ordering::Relaxed)).collect(),
+            count: self.count.load(Ordering::Relaxed),
+            sum_micros: self.sum_micros.load(Ordering::Relaxed),
+            max_micros: self.max_micros.load(Ordering::Relaxed),
+            ..Default::default()
+        };
+        let [p50, p90, p95, p99] = SNAPSHOT_PERCENTILES.map(|p| snapshot.percentile_micros(p));
+        snapshot.p50_micros = p50;
+        snapshot.p90_micros = p90;
+        snapshot.p95_micros = p95;
+        snapshot.p99_micros = p99;
+        snapshot
+    }
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HistogramSnapshot {
+    pub fn mean_micros(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        self.sum_micros as f64 / self.count as f64
+    }
+
+    /// Approximate percentile (0.0 - 1.0), reported as the largest value of
+    /// the log-linear bucket it falls in, capped at the slowest observation.
+    /// The estimate is at most 1/8 above the true value. Returns `None` when
+    /// nothing was recorded.
+    pub fn percentile_micros(&self, percentile: f64) -> Option<u64> {
+        if self.count == 0 {
+            return None;
+        }
+        // Counters are read one by one while writers keep going, so the
+        // buckets may add up to a little less than `count`
+        let total: u64 = self.log_buckets.iter().sum();
+        let target = ((total as f64) * percentile.clamp(0.0, 1.0)).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (i, count) in self.log_buckets.iter().enumerate() {
+            seen += count;
+            if seen >= target {
+                return Some(log_bucket_upper_bound(i).min(self.max_micros));
+            }
+        }
+        Some(self.max_micros)
+    }
+}
+
+/// Counters for a single collection. Handles look this up once and then only
+/// touch the atomics, so the per-operation cost is a relaxed add.
+#[derive(Debug, Default)]
+pub struct CollectionMetrics {
+    inserts: AtomicU64,
+    updates: AtomicU64,
+    deletes: AtomicU64,
+    finds: AtomicU64,
+    documents_scanned: AtomicU64,
+    index_hits: AtomicU64,
+    full_scans: AtomicU64,
+    /// Secondary index uses by index name
+    index_uses: RwLock<HashMap<String, Arc<IndexUseCounter>>>,
+}
+
+/// Times an index answered a query, and when it last did
+#[derive(Debug, Default)]
+struct IndexUseCounter {
+    uses: AtomicU64,
+    last_used_millis: AtomicU64,
+}
+
+/// Snapshot of a collection's counters
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct CollectionMetricsSnapshot {
+    pub inserts: u64,
+    pub updates: u64,
+    pub deletes: u64,
+    pub finds: u64,
+    /// Documents read while scanning the collection
+    pub documents_scanned: u64,
+    /// Lookups answered from an index (including the `_id` index)
+    pub index_hits: u64,
+    /// Reads that had to walk every document
+    pub full_scans: u64,
+}
+
+impl CollectionMetrics {
+    #[inline]
+    pub fn inserted(&self, count: u64) {
+        self.inserts.fetch_add(count, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn updated(&self, count: u64) {
+        self.updates.fetch_add(count, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn deleted(&self, count: u64) {
+        self.deletes.fetch_add(count, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn found(&self) {
+        self.finds.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn index_hit(&self) {
+        self.index_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn full_scan(&self, documents: u64) {
+        self.full_scans.fetch_add(1, Ordering::Relaxed);
+        self.documents_scanned.fetch_add(documents, Ordering::Relaxed);
+    }
+
+    /// Record that a query read `index`
+    pub fn index_used(&self, index: &str) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or(0);
+        let counter = match self.index_uses.read().ok().and_then(|uses| uses.get(index).cloned()) {
+            Some(counter) => counter,
+            None => match self.index_uses.write() {
+                Ok(mut uses) => uses.entry(index.to_string()).or_default().clone(),
+                Err(_) => return,
+            },
+        };
+        counter.uses.fetch_add(1, Ordering::Relaxed);
+        counter.last_used_millis.fetch_max(now, Ordering::Relaxed);
+    }
+
+    /// How many queries read `index`, and when the last one did in
+    /// milliseconds since the Unix epoch
+    pub fn index_usage(&self, index: &str) -> (u64, Option<u64>) {
+        match self.index_uses.read().ok().and_then(|uses| uses.get(index).cloned()) {
+            Some(counter) => (
+                counter.uses.load(Ordering::Relaxed),
+                Some(counter.last_used_millis.load(Ordering::Relaxed)),
+            ),
+            None => (0, None),
+        }
+    }
+
+    /// Start `index`'s usage over, once it's dropped, so an index created
+    /// later under the same name doesn't inherit it
+    pub fn forget_index(&self, index: &str) {
+        if let Ok(mut uses) = self.index_uses.write() {
+            uses.remove(index);
+        }
+    }
+
+    pub fn snapshot(&self) -> CollectionMetricsSnapshot {
+        CollectionMetricsSnapshot {
+            inserts: self.inserts.load(Ordering::Relaxed),
+            updates: self.updates.load(Ordering::Relaxed),
+            deletes: self.deletes.load(Ordering::Relaxed),
+            finds: self.finds.load(Ordering::Relaxed),
+            documents_scanned: self.documents_scanned.load(Ordering::Relaxed),
+            index_hits: self.index_hits.load(Ordering::Relaxed),
+            full_scans: self.full_scans.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Records the time from creation to drop into an operation's histogram
+pub(crate) struct LatencyTimer {
+    metrics: Arc<Metrics>,
+    operation: Operation,
+    start: Instant,
+}
+
+impl Drop for LatencyTimer {
+    fn drop(&mut self) {
+        self.metrics.record_latency(self.operation, self.start.elapsed());
+    }
+}
 
 /// Metrics collected during database operations.
 /// All fields use atomic types for lock-free updates with minimal overhead.
@@ -21,6 +352,8 @@ pub struct Metrics {
     pages_freed: AtomicU64,
     cache_hits: AtomicU64,
     cache_misses: AtomicU64,
+    query_cache_hits: AtomicU64,
+    query_cache_misses: AtomicU64,
     dirty_pages: AtomicUsize,
 
     // WAL metrics
@@ -37,6 +370,13 @@ pub struct Metrics {
     // Error metrics
     io_errors: AtomicU64,
     transaction_conflicts: AtomicU64,
+    queries_over_limit: AtomicU64,
+
+    // Latency metrics, indexed like `Operation::ALL`
+    latencies: [LatencyHistogram; 5],
+
+    // Per-collection counters
+    collections: RwLock<HashMap<String, Arc<CollectionMetrics>>>,
 }
 
 /// Snapshot of metrics at a point in time.
@@ -65,6 +405,8 @@ pub struct MetricsSnapshot {
     pub cache_misses: u64,
     pub cache_total_requests: u64,
     pub cache_hit_rate: f64,
+    pub query_cache_hits: u64,
+    pub query_cache_misses: u64,
     pub dirty_pages: usize,
 
     // WAL metrics
@@ -82,6 +424,15 @@ pub struct MetricsSnapshot {
     // Error metrics
     pub io_errors: u64,
     pub transaction_conflicts: u64,
+    /// Queries refused by `max_scan_documents` or `max_result_documents`
+    pub queries_over_limit: u64,
+
+    // Latency metrics
+    pub insert_latency: HistogramSnapshot,
+    pub find_latency: HistogramSnapshot,
+    pub update_latency: HistogramSnapshot,
+    pub delete_latency: HistogramSnapshot,
+    pub commit_latency: HistogramSnapshot,
 }
 
 impl Metrics {
@@ -101,6 +452,8 @@ impl Metrics {
             pages_freed: AtomicU64::new(0),
             cache_hits: AtomicU64::new(0),
             cache_misses: AtomicU64::new(0),
+            query_cache_hits: AtomicU64::new(0),
+            query_cache_misses: AtomicU64::new(0),
             dirty_pages: AtomicUsize::new(0),
 
             wal_writes: AtomicU64::new(0),
@@ -114,6 +467,11 @@ impl Metrics {
 
             io_errors: AtomicU64::new(0),
             transaction_conflicts: AtomicU64::new(0),
+            queries_over_limit: AtomicU64::new(0),
+
+            latencies: std::array::from_fn(|_| LatencyHistogram::new()),
+
+            collections: RwLock::new(HashMap::new()),
         }
     }
 
@@ -179,6 +537,16 @@ impl Metrics {
         self.cache_misses.fetch_add(1, Ordering::Relaxed);
     }
 
+    #[inline]
+    pub fn query_cache_hit(&self) {
+        self.query_cache_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn query_cache_miss(&self) {
+        self.query_cache_misses.fetch_add(1, Ordering::Relaxed);
+    }
+
     #[inline]
     pub fn set_dirty_pages(&self, count: usize) {
         self.dirty_pages.store(count, Ordering::Relaxed);
@@ -228,6 +596,75 @@ impl Metrics {
         self.transaction_conflicts.fetch_add(1, Ordering::Relaxed);
     }
 
+    #[inline]
+    pub fn query_over_limit(&self) {
+        self.queries_over_limit.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Latency metrics
+    #[inline]
+    pub fn record_latency(&self, operation: Operation, elapsed: Duration) {
+        self.latencies[operation as usize].record(elapsed);
+    }
+
+    /// Start timing an operation; the latency is recorded when the timer drops
+    pub(crate) fn start_timer(self: &Arc<Self>, operation: Operation) -> LatencyTimer {
+        LatencyTimer {
+            metrics: self.clone(),
+            operation,
+            start: Instant::now(),
+        }
+    }
+
+    pub fn latency(&self, operation: Operation) -> HistogramSnapshot {
+        self.latencies[operation as usize].snapshot()
+    }
+
+    /// Clear all latency histograms. Counters are left alone.
+    pub fn reset_latencies(&self) {
+        for histogram in &self.latencies {
+            histogram.reset();
+        }
+    }
+
+    // Per-collection metrics
+
+    /// Counters for `name`, created on first use
+    pub fn collection(&self, name: &str) -> Arc<CollectionMetrics> {
+        if let Ok(collections) = self.collections.read() {
+            if let Some(metrics) = collections.get(name) {
+                return metrics.clone();
+            }
+        }
+        match self.collections.write() {
+            Ok(mut collections) => collections.entry(name.to_string()).or_default().clone(),
+            // Still hand out counters so callers never have to care about a poisoned lock
+            Err(_) => Arc::new(CollectionMetrics::default()),
+        }
+    }
+
+    pub fn collection_snapshot(&self, name: &str) -> CollectionMetricsSnapshot {
+        self.collections
+            .read()
+            .ok()
+            .and_then(|collections| collections.get(name).map(|m| m.snapshot()))
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn remove_collection(&self, name: &str) {
+        if let Ok(mut collections) = self.collections.write() {
+            collections.remove(name);
+        }
+    }
+
+    pub(crate) fn rename_collection(&self, old_name: &str, new_name: &str) {
+        if let Ok(mut collections) = self.collections.write() {
+            if let Some(metrics) = collections.remove(old_name) {
+                collections.insert(new_name.to_string(), metrics);
+            }
+        }
+    }
+
     /// Take a snapshot of current metrics.
     /// Uses Relaxed ordering since we don't need strict consistency for monitoring.
     pub fn snapshot(&self) -> MetricsSnapshot {
@@ -285,6 +722,8 @@ impl Metrics {
             } else {
                 0.0
             },
+            query_cache_hits: self.query_cache_hits.load(Ordering::Relaxed),
+            query_cache_misses: self.query_cache_misses.load(Ordering::Relaxed),
             dirty_pages: self.dirty_pages.load(Ordering::Relaxed),
 
             wal_writes: self.wal_writes.load(Ordering::Relaxed),
@@ -299,10 +738,119 @@ impl Metrics {
 
             io_errors: self.io_errors.load(Ordering::Relaxed),
             transaction_conflicts: self.transaction_conflicts.load(Ordering::Relaxed),
+            queries_over_limit: self.queries_over_limit.load(Ordering::Relaxed),
+
+            insert_latency: self.latency(Operation::Insert),
+            find_latency: self.latency(Operation::Find),
+            update_latency: self.latency(Operation::Update),
+            delete_latency: self.latency(Operation::Delete),
+            commit_latency: self.latency(Operation::Commit),
         }
     }
 }
 
+impl MetricsSnapshot {
+    pub fn latency(&self, operation: Operation) -> &HistogramSnapshot {
+        match operation {
+            Operation::Insert => &self.insert_latency,
+            Operation::Find => &self.find_latency,
+            Operation::Update => &self.update_latency,
+            Operation::Delete => &self.delete_latency,
+            Operation::Commit => &self.commit_latency,
+        }
+    }
+
+    /// Render the snapshot in the Prometheus text exposition format
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+
+        let scalars: [(&str, &str, &str, f64); 23] = [
+            ("transactions_begun_total", "Transactions started", "counter", self.transactions_begun as f64),
+            ("transactions_committed_total", "Transactions committed", "counter", self.transactions_committed as f64),
+            ("transactions_aborted_total", "Transactions aborted", "counter", self.transactions_aborted as f64),
+            ("transaction_conflicts_total", "Transactions that hit a write conflict", "counter", self.transaction_conflicts as f64),
+            ("active_transactions", "Transactions currently open", "gauge", self.active_transactions as f64),
+            ("batches_committed_total", "Group commit batches written", "counter", self.batches_committed as f64),
+            ("max_batch_size", "Largest group commit batch", "gauge", self.max_batch_size as f64),
+            ("pages_allocated_total", "Pages allocated", "counter", self.pages_allocated as f64),
+            ("pages_freed_total", "Pages freed", "counter", self.pages_freed as f64),
+            ("cache_hits_total", "Page cache hits", "counter", self.cache_hits as f64),
+            ("cache_misses_total", "Page cache misses", "counter", self.cache_misses as f64),
+            ("query_cache_hits_total", "Queries answered from the result cache", "counter", self.query_cache_hits as f64),
+            ("query_cache_misses_total", "Queries the result cache could not answer", "counter", self.query_cache_misses as f64),
+            ("dirty_pages", "Pages modified but not yet checkpointed", "gauge", self.dirty_pages as f64),
+            ("wal_writes_total", "WAL frames written", "counter", self.wal_writes as f64),
+            ("wal_bytes_written_total", "Bytes written to the WAL", "counter", self.wal_bytes_written as f64),
+            ("checkpoints_total", "WAL checkpoints completed", "counter", self.checkpoints as f64),
+            ("documents_inserted_total", "Documents inserted", "counter", self.documents_inserted as f64),
+            ("documents_updated_total", "Documents updated", "counter", self.documents_updated as f64),
+            ("documents_deleted_total", "Documents deleted", "counter", self.documents_deleted as f64),
+            ("documents_read_total", "Documents read", "counter", self.documents_read as f64),
+            ("io_errors_total", "I/O errors", "counter", self.io_errors as f64),
+            ("queries_over_limit_total", "Queries refused for scanning or returning too many documents", "counter", self.queries_over_limit as f64),
+        ];
+        for (name, help, kind, value) in scalars {
+            let _ = writeln!(out, "# HELP jasonisnthappy_{} {}", name, help);
+            let _ = writeln!(out, "# TYPE jasonisnthappy_{} {}", name, kind);
+            let _ = writeln!(out, "jasonisnthappy_{} {}", name, value);
+        }
+
+        let _ = writeln!(out, "# HELP jasonisnthappy_operation_duration_seconds Latency of database operations");
+        let _ = writeln!(out, "# TYPE jasonisnthappy_operation_duration_seconds histogram");
+        for operation in Operation::ALL {
+            let histogram = self.latency(operation);
+            let label = operation.as_str();
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS_MICROS.iter().zip(&histogram.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "jasonisnthappy_operation_duration_seconds_bucket{{operation=\"{}\",le=\"{}\"}} {}",
+                    label, *bound as f64 / 1_000_000.0, cumulative
+                );
+            }
+            let _ = writeln!(
+                out,
+                "jasonisnthappy_operation_duration_seconds_bucket{{operation=\"{}\",le=\"+Inf\"}} {}",
+                label, histogram.count
+            );
+            let _ = writeln!(
+                out,
+                "jasonisnthappy_operation_duration_seconds_sum{{operation=\"{}\"}} {}",
+                label, histogram.sum_micros as f64 / 1_000_000.0
+            );
+            let _ = writeln!(
+                out,
+                "jasonisnthappy_operation_duration_seconds_count{{operation=\"{}\"}} {}",
+                label, histogram.count
+            );
+        }
+
+        let _ = writeln!(out, "# HELP jasonisnthappy_operation_duration_quantile_seconds Estimated latency percentiles of database operations");
+        let _ = writeln!(out, "# TYPE jasonisnthappy_operation_duration_quantile_seconds gauge");
+        for operation in Operation::ALL {
+            let histogram = self.latency(operation);
+            let quantiles = [
+                ("0.5", histogram.p50_micros),
+                ("0.9", histogram.p90_micros),
+                ("0.95", histogram.p95_micros),
+                ("0.99", histogram.p99_micros),
+            ];
+            for (quantile, micros) in quantiles {
+                if let Some(micros) = micros {
+                    let _ = writeln!(
+                        out,
+                        "jasonisnthappy_operation_duration_quantile_seconds{{operation=\"{}\",quantile=\"{}\"}} {}",
+                        operation.as_str(), quantile, micros as f64 / 1_000_000.0
+                    );
+                }
+            }
+        }
+
+        out
+    }
+}
+
 impl Default for Metrics {
     fn default() -> Self {
         Self::new()
@@ -392,4 +940,91 @@ mod tests {
         assert_eq!(snapshot.documents_read, 3);
         assert_eq!(snapshot.total_document_operations, 7);
     }
+
+    #[test]
+    fn test_latency_histogram_buckets_and_reset() {
+        let metrics = Metrics::new();
+
+        metrics.record_latency(Operation::Insert, Duration::from_micros(40));
+        metrics.record_latency(Operation::Insert, Duration::from_micros(50));
+        metrics.record_latency(Operation::Insert, Duration::from_micros(700));
+        metrics.record_latency(Operation::Insert, Duration::from_secs(5));
+
+        let insert = metrics.latency(Operation::Insert);
+        assert_eq!(insert.count, 4);
+        assert_eq!(insert.buckets[0], 2);
+        assert_eq!(insert.buckets[4], 1);
+        assert_eq!(insert.buckets[LATENCY_BUCKETS_MICROS.len()], 1);
+        assert_eq!(insert.percentile_micros(0.5), Some(51));
+        assert_eq!(insert.percentile_micros(0.75), Some(703));
+        assert_eq!(insert.percentile_micros(1.0), Some(5_000_000));
+        assert_eq!(insert.max_micros, 5_000_000);
+        assert_eq!(insert.p50_micros, Some(51));
+        assert_eq!(insert.p99_micros, Some(5_000_000));
+        assert_eq!(metrics.latency(Operation::Find).count, 0);
+        assert_eq!(metrics.latency(Operation::Find).p50_micros, None);
+
+        metrics.reset_latencies();
+        assert_eq!(metrics.latency(Operation::Insert).count, 0);
+        assert!(metrics.latency(Operation::Insert).buckets.iter().all(|&b| b == 0));
+        assert_eq!(metrics.latency(Operation::Insert).percentile_micros(0.5), None);
+    }
+
+    #[test]
+    fn test_log_buckets() {
+        for micros in (0..100_000).chain([u32::MAX as u64, 1 << 35, (1 << 36) - 1]) {
+            let index = log_bucket(micros);
+            let upper = log_bucket_upper_bound(index);
+            assert!(micros <= upper, "{} above bucket {} bound {}", micros, index, upper);
+            assert!(upper - micros <= micros / SUB_BUCKETS as u64, "{} too far below {}", micros, upper);
+            if index > 0 {
+                assert!(micros > log_bucket_upper_bound(index - 1));
+            }
+        }
+        assert_eq!(log_bucket(u64::MAX), LOG_BUCKETS - 1);
+    }
+
+    #[test]
+    fn test_percentiles_of_uniform_latencies() {
+        let histogram = LatencyHistogram::new();
+        for micros in 1..=10_000 {
+            histogram.record(Duration::from_micros(micros));
+        }
+
+        let snapshot = histogram.snapshot();
+        for (percentile, estimate) in [
+            (0.5, snapshot.p50_micros),
+            (0.9, snapshot.p90_micros),
+            (0.95, snapshot.p95_micros),
+            (0.99, snapshot.p99_micros),
+        ] {
+            let exact = (10_000.0 * percentile) as u64;
+            let estimate = estimate.unwrap();
+            assert!(estimate >= exact && estimate <= exact + exact / 8, "p{} = {}", percentile, estimate);
+        }
+        assert_eq!(snapshot.max_micros, 10_000);
+
+        let json = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(json["p50_micros"], snapshot.p50_micros.unwrap());
+        assert!(json.get("log_buckets").is_none());
+    }
+
+    #[test]
+    fn test_prometheus_rendering() {
+        let metrics = Metrics::new();
+        metrics.document_inserted();
+        metrics.record_latency(Operation::Commit, Duration::from_micros(300));
+        metrics.record_latency(Operation::Commit, Duration::from_micros(2_000));
+
+        let text = metrics.snapshot().to_prometheus();
+        assert!(text.contains("# TYPE jasonisnthappy_documents_inserted_total counter\njasonisnthappy_documents_inserted_total 1\n"));
+        assert!(text.contains("# TYPE jasonisnthappy_operation_duration_seconds histogram"));
+        assert!(text.contains("jasonisnthappy_operation_duration_seconds_bucket{operation=\"commit\",le=\"0.0005\"} 1\n"));
+        assert!(text.contains("jasonisnthappy_operation_duration_seconds_bucket{operation=\"commit\",le=\"+Inf\"} 2\n"));
+        assert!(text.contains("jasonisnthappy_operation_duration_seconds_sum{operation=\"commit\"} 0.0023\n"));
+        assert!(text.contains("jasonisnthappy_operation_duration_seconds_count{operation=\"insert\"} 0\n"));
+        assert!(text.contains("jasonisnthappy_operation_duration_quantile_seconds{operation=\"commit\",quantile=\"0.5\"} 0.000319\n"));
+        assert!(text.contains("jasonisnthappy_operation_duration_quantile_seconds{operation=\"commit\",quantile=\"0.99\"} 0.002\n"));
+        assert!(!text.contains("quantile_seconds{operation=\"insert\""));
+    }
 }
@@ -3,6 +3,7 @@ pub mod constants;
 pub mod errors;
 pub mod pager;
 pub mod document;
+pub mod binary;
 pub mod btree;
 pub mod tx_btree;
 pub mod wal;
@@ -14,29 +15,72 @@ pub mod tx_collection;
 pub mod metadata;
 pub mod lru_cache;
 pub mod index_key;
+pub mod collation;
+pub(crate) mod index_writes;
+pub(crate) mod update_ops;
+pub(crate) mod id_filter;
 pub mod validation;
+pub(crate) mod pattern;
 pub mod query;
 pub mod query_builder;
 pub mod buffer_pool;
 pub mod metrics;
 pub mod aggregation;
+pub mod datetime;
 pub mod watch;
 pub mod text_search;
+pub mod id_strategy;
+pub mod integrity;
+pub mod repair;
+pub mod space;
+pub mod warm;
+pub mod snapshot;
+pub mod slow_query;
+pub mod events;
+pub(crate) mod query_cache;
+pub(crate) mod recycle_bin;
+pub mod replication;
+pub mod durability;
+pub mod expression;
+pub mod geo;
+pub mod kv;
 
 #[cfg(feature = "web-ui")]
 pub mod web_server;
 
+#[cfg(feature = "tokio")]
+pub mod async_db;
+
 pub use constants::*;
-pub use database::{Database, CollectionInfo, IndexInfo, DatabaseInfo};
+pub use database::{Database, CollectionInfo, IndexInfo, IndexOptions, IndexUsage, DatabaseInfo};
 pub use transaction::Transaction;
-pub use collection::{Collection, UpsertResult, BulkWrite, BulkWriteResult, BulkWriteError};
+pub use collection::{Collection, FindBatches, UpsertResult, BulkWrite, BulkWriteResult, BulkWriteError, ChunkedInsertResult, ChunkFailure, WriteBatch};
 pub use tx_collection::TxCollection;
-pub use metrics::{Metrics, MetricsSnapshot};
-pub use query_builder::{QueryBuilder, SortOrder};
-pub use aggregation::AggregationPipeline;
-pub use validation::{Schema, ValueType};
+pub use metrics::{CollectionMetricsSnapshot, HistogramSnapshot, Metrics, MetricsSnapshot, Operation};
+pub use query::ast::{FieldQuery, Query, QueryAst};
+pub use collation::Collation;
+pub use query_builder::{QueryBuilder, SortOrder, QueryExplain, AccessPath, IndexHint};
+pub use aggregation::{AggregationPipeline, FieldExpr, OutMode};
+pub use datetime::DateGranularity;
+pub use validation::{Schema, ValidationError, ValueType};
 pub use watch::{ChangeEvent, ChangeOperation, WatchBuilder, WatchHandle};
-pub use text_search::SearchResult;
+pub use text_search::{SearchResult, TermScore, TextIndexOptions, Language};
+pub use slow_query::{SlowQuery, SlowQueryOperation};
+pub use events::{DbEvent, EventHook};
+pub use durability::Durability;
+pub use id_strategy::IdStrategy;
+pub use integrity::{IntegrityProblem, IntegrityReport};
+pub use repair::{RecoveredCollection, RepairReport};
+pub use space::{CollectionSpace, SpaceStats};
+pub use warm::CacheWarmup;
+pub use snapshot::Snapshot;
+pub use binary::{binary_bytes, binary_value};
+pub use geo::haversine_km;
+pub use kv::KvStore;
+pub use replication::{ReplicationRecord, ReplicationStream};
 
 #[cfg(feature = "web-ui")]
-pub use web_server::WebServer;
+pub use web_server::{WebServer, WebServerOptions};
+
+#[cfg(feature = "tokio")]
+pub use async_db::{AsyncCollection, AsyncDatabase};
@@ -3,6 +3,7 @@ use crate::core::constants::*;
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, RwLock};
+use std::time::Instant;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TxStatus {
@@ -16,12 +17,16 @@ pub struct TransactionInfo {
     pub id: TransactionID,
     pub start_time: TransactionID,
     pub status: TxStatus,
+    /// Past this point the transaction is abandoned and no longer pins old versions
+    pub deadline: Option<Instant>,
 }
 
 pub struct TransactionManager {
     next_tx_id: Arc<AtomicU64>,
     last_committed_tx_id: Arc<AtomicU64>,
     active_txs: Arc<RwLock<HashMap<TransactionID, TransactionInfo>>>,
+    /// Read-only transactions open on each snapshot
+    pinned_snapshots: Arc<RwLock<HashMap<TransactionID, usize>>>,
 }
 
 impl TransactionManager {
@@ -30,6 +35,7 @@ impl TransactionManager {
             next_tx_id: Arc::new(AtomicU64::new(1)),
             last_committed_tx_id: Arc::new(AtomicU64::new(0)),
             active_txs: Arc::new(RwLock::new(HashMap::new())),
+            pinned_snapshots: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
@@ -52,6 +58,7 @@ impl TransactionManager {
             id: tx_id,
             start_time: snapshot_time,
             status: TxStatus::Active,
+            deadline: None,
         });
 
         Ok(tx_id)
@@ -66,10 +73,21 @@ impl TransactionManager {
             id: tx_id,
             start_time: snapshot_time,
             status: TxStatus::Active,
+            deadline: None,
         });
         Ok(())
     }
 
+    pub fn set_transaction_deadline(&self, tx_id: TransactionID, deadline: Instant) -> crate::core::errors::Result<()> {
+        let mut active_txs = self.active_txs.write()
+            .map_err(|_| crate::core::errors::Error::LockPoisoned { lock_name: "mvcc.active_txs".to_string() })?;
+
+        if let Some(info) = active_txs.get_mut(&tx_id) {
+            info.deadline = Some(deadline);
+        }
+        Ok(())
+    }
+
     pub fn commit_transaction(&self, tx_id: TransactionID) -> crate::core::errors::Result<()> {
         let mut active_txs = self.active_txs.write()
             .map_err(|_| crate::core::errors::Error::LockPoisoned { lock_name: "mvcc.active_txs".to_string() })?;
@@ -97,6 +115,43 @@ impl TransactionManager {
         Ok(())
     }
 
+    /// Keep the latest committed snapshot readable without starting a
+    /// transaction, returning the snapshot. Readers of the same snapshot share
+    /// one entry.
+    pub fn pin_snapshot(&self) -> crate::core::errors::Result<TransactionID> {
+        let mut pinned = self.pinned_snapshots.write()
+            .map_err(|_| crate::core::errors::Error::LockPoisoned { lock_name: "mvcc.pinned_snapshots".to_string() })?;
+
+        let snapshot_time = self.get_latest_committed_tx_id();
+        *pinned.entry(snapshot_time).or_insert(0) += 1;
+        Ok(snapshot_time)
+    }
+
+    pub fn unpin_snapshot(&self, snapshot_time: TransactionID) -> crate::core::errors::Result<()> {
+        let mut pinned = self.pinned_snapshots.write()
+            .map_err(|_| crate::core::errors::Error::LockPoisoned { lock_name: "mvcc.pinned_snapshots".to_string() })?;
+
+        if let Some(readers) = pinned.get_mut(&snapshot_time) {
+            *readers -= 1;
+            if *readers == 0 {
+                pinned.remove(&snapshot_time);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn active_transaction_count(&self) -> crate::core::errors::Result<usize> {
+        let active_txs = self.active_txs.read()
+            .map_err(|_| crate::core::errors::Error::LockPoisoned { lock_name: "mvcc.active_txs".to_string() })?;
+        Ok(active_txs.len())
+    }
+
+    pub fn read_only_transaction_count(&self) -> crate::core::errors::Result<usize> {
+        let pinned = self.pinned_snapshots.read()
+            .map_err(|_| crate::core::errors::Error::LockPoisoned { lock_name: "mvcc.pinned_snapshots".to_string() })?;
+        Ok(pinned.values().sum())
+    }
+
     pub fn get_latest_committed_tx_id(&self) -> TransactionID {
         self.last_committed_tx_id.load(Ordering::SeqCst)
     }
@@ -105,17 +160,28 @@ impl TransactionManager {
         let active_txs = self.active_txs.read()
             .map_err(|_| crate::core::errors::Error::LockPoisoned { lock_name: "mvcc.active_txs".to_string() })?;
 
-        if active_txs.is_empty() {
-            return Ok(self.get_latest_committed_tx_id() + 1);
-        }
-
+        let now = Instant::now();
         let mut oldest = TransactionID::MAX;
         for info in active_txs.values() {
+            // Timed-out transactions can't read anymore, so their snapshot doesn't count
+            if info.deadline.is_some_and(|deadline| now >= deadline) {
+                continue;
+            }
             if info.start_time < oldest {
                 oldest = info.start_time;
             }
         }
 
+        let pinned = self.pinned_snapshots.read()
+            .map_err(|_| crate::core::errors::Error::LockPoisoned { lock_name: "mvcc.pinned_snapshots".to_string() })?;
+        if let Some(&snapshot_time) = pinned.keys().min() {
+            oldest = oldest.min(snapshot_time);
+        }
+
+        if oldest == TransactionID::MAX {
+            return Ok(self.get_latest_committed_tx_id() + 1);
+        }
+
         Ok(oldest)
     }
 }
@@ -251,6 +317,41 @@ mod tests {
         assert_eq!(tm.get_oldest_active_transaction().unwrap(), tm.get_latest_committed_tx_id() + 1);
     }
 
+    #[test]
+    fn test_pinned_snapshot_holds_oldest_active() {
+        let tm = TransactionManager::new();
+
+        let snapshot = tm.pin_snapshot().unwrap();
+        assert_eq!(tm.pin_snapshot().unwrap(), snapshot);
+        assert_eq!(tm.active_transaction_count().unwrap(), 0);
+        assert_eq!(tm.read_only_transaction_count().unwrap(), 2);
+
+        let tx1 = tm.begin_transaction().unwrap();
+        tm.commit_transaction(tx1).unwrap();
+        assert_eq!(tm.get_oldest_active_transaction().unwrap(), snapshot);
+
+        tm.unpin_snapshot(snapshot).unwrap();
+        assert_eq!(tm.get_oldest_active_transaction().unwrap(), snapshot);
+
+        tm.unpin_snapshot(snapshot).unwrap();
+        assert_eq!(tm.get_oldest_active_transaction().unwrap(), tm.get_latest_committed_tx_id() + 1);
+    }
+
+    #[test]
+    fn test_oldest_active_transaction_skips_expired() {
+        let tm = TransactionManager::new();
+
+        let tx1 = tm.begin_transaction().unwrap();
+        let tx2 = tm.begin_transaction().unwrap();
+        let tx2_start = tm.active_txs.read().unwrap()[&tx2].start_time;
+
+        tm.set_transaction_deadline(tx1, Instant::now()).unwrap();
+        assert_eq!(tm.get_oldest_active_transaction().unwrap(), tx2_start);
+
+        tm.set_transaction_deadline(tx2, Instant::now()).unwrap();
+        assert_eq!(tm.get_oldest_active_transaction().unwrap(), tm.get_latest_committed_tx_id() + 1);
+    }
+
     #[test]
     fn test_document_version_visibility() {
         let version = DocumentVersion {
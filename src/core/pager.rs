@@ -1,6 +1,7 @@
 
 use crate::core::constants::*;
 use crate::core::errors::*;
+use crate::core::events::{DbEvent, EventHooks};
 use crate::core::lru_cache::LRUCache;
 use crate::core::metrics::Metrics;
 use std::collections::HashSet;
@@ -9,7 +10,7 @@ use std::io::{Read, Seek, SeekFrom, Write};
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
-use std::sync::{Arc, RwLock, Mutex};
+use std::sync::{Arc, OnceLock, RwLock, Mutex};
 
 #[derive(Debug, Clone)]
 pub struct Header {
@@ -21,8 +22,15 @@ pub struct Header {
     pub metadata_page: u64,
     pub next_tx_id: u64,
     pub free_list: Vec<PageNum>,
+    /// `HEADER_FLAG_*` bits, stored in the last bytes of the header page
+    pub flags: u32,
 }
 
+/// Bytes at the end of the header page kept clear of the free list. The flags
+/// sit in the last four; files from before flags existed may have the upper
+/// half of a free list entry there, which is zero for any real page number.
+const HEADER_TAIL_SIZE: usize = 8;
+
 impl Header {
     #[cfg(test)]
     fn new() -> Self {
@@ -35,11 +43,13 @@ impl Header {
             metadata_page: 0,
             next_tx_id: 1,
             free_list: Vec::new(),
+            flags: 0,
         }
     }
 
     fn serialize(&self) -> Vec<u8> {
-        let mut buf = vec![0u8; PAGE_SIZE];
+        let page_size = self.page_size as usize;
+        let mut buf = vec![0u8; page_size];
         let mut offset = 0;
 
         buf[offset..offset + 4].copy_from_slice(&self.magic);
@@ -64,18 +74,20 @@ impl Header {
         offset += 8;
 
         for &page_num in &self.free_list {
-            if offset + 8 > PAGE_SIZE {
+            if offset + 8 > page_size - HEADER_TAIL_SIZE {
                 break;
             }
             buf[offset..offset + 8].copy_from_slice(&page_num.to_le_bytes());
             offset += 8;
         }
 
+        buf[page_size - 4..].copy_from_slice(&self.flags.to_le_bytes());
+
         buf
     }
 
     fn deserialize(data: &[u8]) -> Result<Self> {
-        if data.len() < PAGE_SIZE {
+        if data.len() < MIN_PAGE_SIZE {
             return Err(Error::InvalidPageSize);
         }
 
@@ -90,14 +102,14 @@ impl Header {
 
         let version = u32::from_le_bytes(data[offset..offset + 4].try_into()
             .map_err(|_| Error::DataCorruption { details: "failed to parse version from header".to_string() })?);
-        if version != VERSION {
+        if version != VERSION && version != VERSION_PAGE_CHECKSUMS {
             return Err(Error::InvalidVersion);
         }
         offset += 4;
 
         let page_size = u32::from_le_bytes(data[offset..offset + 4].try_into()
             .map_err(|_| Error::DataCorruption { details: "failed to parse page_size from header".to_string() })?);
-        if page_size != PAGE_SIZE as u32 {
+        if !is_supported_page_size(page_size as usize) || page_size as usize > data.len() {
             return Err(Error::InvalidPageSize);
         }
         offset += 4;
@@ -120,7 +132,7 @@ impl Header {
 
         let mut free_list = Vec::new();
         for _ in 0..free_count {
-            if offset + 8 > PAGE_SIZE {
+            if offset + 8 > page_size as usize - HEADER_TAIL_SIZE {
                 break;
             }
             let page_num = u64::from_le_bytes(data[offset..offset + 8].try_into()
@@ -129,6 +141,16 @@ impl Header {
             offset += 8;
         }
 
+        let flags = read_header_flags(data, page_size as usize);
+        if flags & !KNOWN_HEADER_FLAGS != 0 {
+            return Err(Error::InvalidVersion);
+        }
+        if (flags & HEADER_FLAG_PAGE_CHECKSUMS != 0) != (version == VERSION_PAGE_CHECKSUMS) {
+            return Err(Error::DataCorruption {
+                details: format!("header version {} does not match its page checksum flag", version),
+            });
+        }
+
         Ok(Self {
             magic,
             version,
@@ -138,6 +160,7 @@ impl Header {
             metadata_page,
             next_tx_id,
             free_list,
+            flags,
         })
     }
 }
@@ -150,16 +173,47 @@ pub struct Pager {
     next_tx_id: Arc<RwLock<u64>>,
     free_list: Arc<RwLock<Vec<PageNum>>>,
     read_only: bool,
+    /// Size of a page in the file, including its checksum if it has one
+    page_size: usize,
+    /// Whether pages carry a checksum, fixed when the file was created
+    checksums: bool,
     metrics: Arc<RwLock<Option<Arc<Metrics>>>>,
+    events: OnceLock<Arc<EventHooks>>,
 }
 
 impl Pager {
-    #[cfg_attr(not(unix), allow(unused_variables))]
     pub fn open(path: &str, cache_size: usize, permissions: u32, read_only: bool) -> Result<Self> {
+        Self::open_with_page_size(path, cache_size, permissions, read_only, None, false)
+    }
+
+    /// Open `path`, creating it with `page_size`-byte pages if it doesn't exist.
+    /// An existing file keeps the page size in its header; asking for a
+    /// different one fails with `Error::PageSizeMismatch`. `None` means the
+    /// file's own page size, or `self.page_size` for a new file.
+    /// `page_checksums` only applies to a new file; an existing one keeps the
+    /// setting recorded in its header.
+    #[cfg_attr(not(unix), allow(unused_variables))]
+    pub fn open_with_page_size(
+        path: &str,
+        cache_size: usize,
+        permissions: u32,
+        read_only: bool,
+        page_size: Option<usize>,
+        page_checksums: bool,
+    ) -> Result<Self> {
+        if let Some(size) = page_size {
+            if !is_supported_page_size(size) {
+                return Err(Error::Other(format!(
+                    "page_size must be a power of two between {} and {} bytes, got {}",
+                    MIN_PAGE_SIZE, MAX_PAGE_SIZE, size
+                )));
+            }
+        }
+
         let path_obj = Path::new(path);
         let exists = path_obj.exists();
 
-        let file = if read_only {
+        let mut file = if read_only {
             OpenOptions::new()
                 .read(true)
                 .open(path)?
@@ -184,9 +238,22 @@ impl Pager {
             f
         };
 
+        let (page_size, checksums) = if exists {
+            let actual = read_page_size(&mut file)?;
+            if let Some(requested) = page_size.filter(|&requested| requested != actual) {
+                return Err(Error::PageSizeMismatch { requested, actual });
+            }
+            let mut header = vec![0u8; actual];
+            file.seek(SeekFrom::Start(0))?;
+            file.read_exact(&mut header)?;
+            (actual, read_header_flags(&header, actual) & HEADER_FLAG_PAGE_CHECKSUMS != 0)
+        } else {
+            (page_size.unwrap_or(PAGE_SIZE), page_checksums)
+        };
+
         let cache = LRUCache::new(cache_size);
 
-        let mut pager = Self {
+        let pager = Self {
             file: Arc::new(Mutex::new(file)),
             cache,
             num_pages: Arc::new(RwLock::new(1)),
@@ -194,7 +261,10 @@ impl Pager {
             next_tx_id: Arc::new(RwLock::new(1)),
             free_list: Arc::new(RwLock::new(Vec::new())),
             read_only,
+            page_size,
+            checksums,
             metrics: Arc::new(RwLock::new(None)),
+            events: OnceLock::new(),
         };
 
         if exists {
@@ -206,7 +276,7 @@ impl Pager {
         Ok(pager)
     }
 
-    pub fn read_header(&mut self) -> Result<()> {
+    pub fn read_header(&self) -> Result<()> {
         let mut file = self.file.lock()
             .map_err(|_| Error::LockPoisoned { lock_name: "pager.file".to_string() })?;
 
@@ -214,10 +284,23 @@ impl Pager {
         let file_size = file.seek(SeekFrom::End(0))?;
         file.seek(SeekFrom::Start(0))?;
 
-        let mut buf = vec![0u8; PAGE_SIZE];
+        let mut buf = vec![0u8; self.page_size];
         file.read_exact(&mut buf)?;
 
         let header = Header::deserialize(&buf)?;
+        if header.page_size as usize != self.page_size {
+            return Err(Error::DataCorruption {
+                details: format!(
+                    "header page size {} does not match the open database's page size {}",
+                    header.page_size, self.page_size
+                ),
+            });
+        }
+        if (header.flags & HEADER_FLAG_PAGE_CHECKSUMS != 0) != self.checksums {
+            return Err(Error::DataCorruption {
+                details: "header page checksum flag does not match the open database".to_string(),
+            });
+        }
 
         // === CORRUPTION DETECTION VALIDATION ===
 
@@ -232,7 +315,7 @@ impl Pager {
         }
 
         // 2. File size must be large enough to contain all claimed pages
-        let expected_min_size = header.num_pages * PAGE_SIZE as u64;
+        let expected_min_size = header.num_pages * self.page_size as u64;
         if file_size < expected_min_size {
             return Err(Error::DataCorruption {
                 details: format!(
@@ -310,6 +393,13 @@ impl Pager {
         Ok(())
     }
 
+    /// Pages currently on the free list
+    pub(crate) fn free_pages(&self) -> Result<Vec<PageNum>> {
+        Ok(self.free_list.read()
+            .map_err(|_| Error::LockPoisoned { lock_name: "pager.free_list".to_string() })?
+            .clone())
+    }
+
     pub fn get_header_data(&self) -> Result<Vec<u8>> {
         let num_pages = *self.num_pages.read()
             .map_err(|_| Error::LockPoisoned { lock_name: "pager.num_pages".to_string() })?;
@@ -322,13 +412,14 @@ impl Pager {
 
         let header = Header {
             magic: MAGIC,
-            version: VERSION,
-            page_size: PAGE_SIZE as u32,
+            version: self.format_version(),
+            page_size: self.page_size as u32,
             num_pages,
             free_count: free_list.len() as u32,
             metadata_page,
             next_tx_id,
             free_list: free_list.clone(),
+            flags: if self.checksums { HEADER_FLAG_PAGE_CHECKSUMS } else { 0 },
         };
 
         Ok(header.serialize())
@@ -398,15 +489,10 @@ impl Pager {
             }
         }
 
-        let mut file = self.file.lock()
-            .map_err(|_| Error::LockPoisoned { lock_name: "pager.file".to_string() })?;
-        let offset = page_num * PAGE_SIZE as u64;
-        file.seek(SeekFrom::Start(offset))?;
-
-        let mut buf = vec![0u8; PAGE_SIZE];
-        file.read_exact(&mut buf)?;
+        let buf = self.read_page_from_file(page_num)?;
 
-        self.cache.put(page_num, buf.clone());
+        let evicted = self.cache.put(page_num, buf.clone());
+        self.evicted(evicted);
 
         Ok(buf)
     }
@@ -429,30 +515,86 @@ impl Pager {
             }
         }
 
-        let mut file = self.file.lock()
-            .map_err(|_| Error::LockPoisoned { lock_name: "pager.file".to_string() })?;
-        let offset = page_num * PAGE_SIZE as u64;
-        file.seek(SeekFrom::Start(offset))?;
-
-        let mut buf = vec![0u8; PAGE_SIZE];
-        file.read_exact(&mut buf)?;
+        let buf = self.read_page_from_file(page_num)?;
 
-        self.cache.put(page_num, buf.clone());
+        let evicted = self.cache.put(page_num, buf.clone());
+        self.evicted(evicted);
 
         Ok(buf)
     }
 
+    /// Read a page from the file, bypassing the cache, and verify its checksum
+    fn read_page_from_file(&self, page_num: PageNum) -> Result<Vec<u8>> {
+        let mut buf = vec![0u8; self.page_size];
+        {
+            let mut file = self.file.lock()
+                .map_err(|_| Error::LockPoisoned { lock_name: "pager.file".to_string() })?;
+            file.seek(SeekFrom::Start(page_num * self.page_size as u64))?;
+            file.read_exact(&mut buf)?;
+        }
+        self.strip_checksum(page_num, buf)
+    }
+
+    /// Split the checksum off a page read from the file, failing if it doesn't match
+    fn strip_checksum(&self, page_num: PageNum, mut buf: Vec<u8>) -> Result<Vec<u8>> {
+        if !self.checksums {
+            return Ok(buf);
+        }
+
+        let stored = u32::from_le_bytes(buf[..PAGE_CHECKSUM_SIZE].try_into()?);
+        let data = buf.split_off(PAGE_CHECKSUM_SIZE);
+        // Pages allocated but never written are all zeros, checksum included
+        if stored != page_checksum(page_num, &data) && (stored != 0 || data.iter().any(|&b| b != 0)) {
+            return Err(Error::PageChecksumMismatch { page: page_num });
+        }
+        Ok(data)
+    }
+
+    /// Check the checksum of a page as stored in the file, ignoring any newer
+    /// copy in the cache. Returns false if the page hasn't reached the file
+    /// yet, or the database has no page checksums.
+    pub(crate) fn verify_page(&self, page_num: PageNum) -> Result<bool> {
+        if !self.checksums {
+            return Ok(false);
+        }
+
+        let mut buf = vec![0u8; self.page_size];
+        {
+            let mut file = self.file.lock()
+                .map_err(|_| Error::LockPoisoned { lock_name: "pager.file".to_string() })?;
+            let offset = page_num * self.page_size as u64;
+            if offset + self.page_size as u64 > file.seek(SeekFrom::End(0))? {
+                return Ok(false);
+            }
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(&mut buf)?;
+        }
+        self.strip_checksum(page_num, buf).map(|_| true)
+    }
+
+    /// `data` as laid out in the file, with its checksum in front if pages carry one
+    fn page_bytes<'a>(&self, page_num: PageNum, data: &'a [u8]) -> std::borrow::Cow<'a, [u8]> {
+        if !self.checksums {
+            return std::borrow::Cow::Borrowed(data);
+        }
+        let mut buf = Vec::with_capacity(self.page_size);
+        buf.extend_from_slice(&page_checksum(page_num, data).to_le_bytes());
+        buf.extend_from_slice(data);
+        std::borrow::Cow::Owned(buf)
+    }
+
     pub fn write_page(&self, page_num: PageNum, data: &[u8]) -> Result<()> {
         if self.read_only {
             return Err(Error::Other("cannot write page: database is read-only".to_string()));
         }
 
-        if data.len() != PAGE_SIZE {
+        if data.len() != self.page_size() {
             return Err(Error::InvalidPageSize);
         }
 
-        self.cache.put(page_num, data.to_vec());
+        let evicted = self.cache.put(page_num, data.to_vec());
         self.cache.mark_dirty(page_num);
+        self.evicted(evicted);
 
         Ok(())
     }
@@ -462,11 +604,12 @@ impl Pager {
             return Err(Error::Other("cannot write page: database is read-only".to_string()));
         }
 
-        if data.len() != PAGE_SIZE {
+        if data.len() != self.page_size() {
             return Err(Error::InvalidPageSize);
         }
 
-        self.cache.put_dirty(page_num, data);
+        let evicted = self.cache.put_dirty(page_num, data);
+        self.evicted(evicted);
 
         Ok(())
     }
@@ -512,6 +655,16 @@ impl Pager {
         Ok(page_num)
     }
 
+    /// Count a document read for metrics. Called by the document readers,
+    /// since the pager itself can't tell document pages from B-tree pages.
+    pub(crate) fn record_document_read(&self) {
+        if let Ok(guard) = self.metrics.read() {
+            if let Some(metrics) = guard.as_ref() {
+                metrics.document_read();
+            }
+        }
+    }
+
     pub fn free_page(&self, page_num: PageNum) -> Result<()> {
         if self.read_only {
             return Err(Error::Other("cannot free page: database is read-only".to_string()));
@@ -553,7 +706,7 @@ impl Pager {
 
         // Validate all pages first
         for (_, data) in &sorted_pages {
-            if data.len() != PAGE_SIZE {
+            if data.len() != self.page_size() {
                 return Err(Error::InvalidPageSize);
             }
         }
@@ -585,17 +738,17 @@ impl Pager {
             if batch_size == 1 {
                 // Single page - write directly
                 let (page_num, data) = &sorted_pages[batch_start_idx];
-                let offset = page_num * PAGE_SIZE as u64;
+                let offset = page_num * self.page_size as u64;
                 file.seek(SeekFrom::Start(offset))?;
-                file.write_all(data)?;
+                file.write_all(&self.page_bytes(*page_num, data))?;
             } else {
                 // Multiple consecutive pages - batch them into one write
-                let mut batch_buffer = Vec::with_capacity(batch_size * PAGE_SIZE);
-                for i in batch_start_idx..=batch_end_idx {
-                    batch_buffer.extend_from_slice(&sorted_pages[i].1);
+                let mut batch_buffer = Vec::with_capacity(batch_size * self.page_size);
+                for (page_num, data) in &sorted_pages[batch_start_idx..=batch_end_idx] {
+                    batch_buffer.extend_from_slice(&self.page_bytes(*page_num, data));
                 }
 
-                let offset = start_page_num * PAGE_SIZE as u64;
+                let offset = start_page_num * self.page_size as u64;
                 file.seek(SeekFrom::Start(offset))?;
                 file.write_all(&batch_buffer)?;
             }
@@ -621,9 +774,9 @@ impl Pager {
             .map_err(|_| Error::LockPoisoned { lock_name: "pager.file".to_string() })?;
         for page_num in &dirty_pages {
             if let Some(data) = self.cache.get_read_only(*page_num) {
-                let offset = page_num * PAGE_SIZE as u64;
+                let offset = page_num * self.page_size as u64;
                 file.seek(SeekFrom::Start(offset))?;
-                file.write_all(&data)?;
+                file.write_all(&self.page_bytes(*page_num, &data))?;
             }
         }
 
@@ -633,6 +786,19 @@ impl Pager {
         Ok(())
     }
 
+    /// Whether the cache holds pages not yet written to the database file
+    pub fn has_dirty_pages(&self) -> bool {
+        self.cache.has_dirty()
+    }
+
+    /// The cached pages not yet written to the database file, with their contents
+    pub(crate) fn dirty_pages(&self) -> Vec<(PageNum, Vec<u8>)> {
+        self.cache.get_all_dirty()
+            .into_iter()
+            .filter_map(|page_num| self.cache.get_read_only(page_num).map(|data| (page_num, data)))
+            .collect()
+    }
+
     pub fn flush_no_sync(&self) -> Result<()> {
         if self.read_only {
             return Ok(());
@@ -644,9 +810,9 @@ impl Pager {
             .map_err(|_| Error::LockPoisoned { lock_name: "pager.file".to_string() })?;
         for page_num in &dirty_pages {
             if let Some(data) = self.cache.get_read_only(*page_num) {
-                let offset = page_num * PAGE_SIZE as u64;
+                let offset = page_num * self.page_size as u64;
                 file.seek(SeekFrom::Start(offset))?;
-                file.write_all(&data)?;
+                file.write_all(&self.page_bytes(*page_num, &data))?;
             }
         }
         // No sync - caller handles it
@@ -657,6 +823,24 @@ impl Pager {
         Ok(())
     }
 
+    /// Switch to the file now at `path`, dropping cached pages and loading its header.
+    /// Used after a rewritten database file has been renamed over the old one.
+    pub(crate) fn reopen(&self, path: &str) -> Result<()> {
+        let new_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)?;
+
+        {
+            let mut file = self.file.lock()
+                .map_err(|_| Error::LockPoisoned { lock_name: "pager.file".to_string() })?;
+            *file = new_file;
+        }
+
+        self.cache.clear();
+        self.read_header()
+    }
+
     pub fn close(self) -> Result<()> {
         if !self.read_only {
             self.flush()?;
@@ -695,6 +879,33 @@ impl Pager {
         Ok(())
     }
 
+    /// Take the free list and next transaction id from a header frame read
+    /// back from the WAL. The header in the database file is only fsynced at
+    /// checkpoints under relaxed durability, so after a power loss it can be
+    /// older than the pages the WAL replays.
+    pub(crate) fn restore_logged_header(&self, data: &[u8]) -> Result<()> {
+        if data.len() < 40 {
+            return Err(Error::DataCorruption {
+                details: "header frame in WAL is too short".to_string(),
+            });
+        }
+        let free_count = u32::from_le_bytes(data[20..24].try_into()?) as usize;
+        let next_tx_id = u64::from_le_bytes(data[32..40].try_into()?);
+        let end = data.len().min(self.page_size - HEADER_TAIL_SIZE);
+        let free_list: Vec<PageNum> = data[40..end]
+            .chunks_exact(8)
+            .take(free_count)
+            .map(|entry| u64::from_le_bytes(entry.try_into().unwrap()))
+            .collect();
+
+        *self.free_list.write()
+            .map_err(|_| Error::LockPoisoned { lock_name: "pager.free_list".to_string() })? = free_list;
+        let mut current = self.next_tx_id.write()
+            .map_err(|_| Error::LockPoisoned { lock_name: "pager.next_tx_id".to_string() })?;
+        *current = (*current).max(next_tx_id);
+        Ok(())
+    }
+
     pub fn metadata_page(&self) -> Result<u64> {
         Ok(*self.metadata_page.read()
             .map_err(|_| Error::LockPoisoned { lock_name: "pager.metadata_page".to_string() })?)
@@ -706,6 +917,41 @@ impl Pager {
         Ok(())
     }
 
+    /// Bytes of each page available to callers: the file's page size less the
+    /// checksum, if pages carry one. Buffers passed to `write_page` must be
+    /// exactly this long.
+    /// Most pages the page cache holds
+    pub(crate) fn cache_capacity(&self) -> usize {
+        self.cache.capacity()
+    }
+
+    pub fn page_size(&self) -> usize {
+        if self.checksums {
+            self.page_size - PAGE_CHECKSUM_SIZE
+        } else {
+            self.page_size
+        }
+    }
+
+    /// Size in bytes of every page in the file, fixed when it was created
+    pub fn file_page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// Whether pages carry a checksum that is verified when they are read from disk
+    pub fn has_page_checksums(&self) -> bool {
+        self.checksums
+    }
+
+    /// File format version recorded in the header
+    pub fn format_version(&self) -> u32 {
+        if self.checksums {
+            VERSION_PAGE_CHECKSUMS
+        } else {
+            VERSION
+        }
+    }
+
     pub fn is_read_only(&self) -> bool {
         self.read_only
     }
@@ -715,6 +961,81 @@ impl Pager {
             *guard = Some(metrics);
         }
     }
+
+    pub(crate) fn set_event_hooks(&self, events: Arc<EventHooks>) {
+        let _ = self.events.set(events);
+    }
+
+    fn evicted(&self, page_num: Option<PageNum>) {
+        if let (Some(page_num), Some(events)) = (page_num, self.events.get()) {
+            events.emit(|| DbEvent::PageEvicted { page_num });
+        }
+    }
+}
+
+/// Whether a database can be created with `page_size`-byte pages
+pub fn is_supported_page_size(page_size: usize) -> bool {
+    page_size.is_power_of_two() && (MIN_PAGE_SIZE..=MAX_PAGE_SIZE).contains(&page_size)
+}
+
+/// `HEADER_FLAG_*` bits of a `page_size`-byte header page
+fn read_header_flags(header: &[u8], page_size: usize) -> u32 {
+    u32::from_le_bytes(header[page_size - 4..page_size].try_into().unwrap())
+}
+
+/// CRC32C of a page's contents. The page number is included so a page written
+/// to the wrong place in the file fails verification too.
+fn page_checksum(page_num: PageNum, data: &[u8]) -> u32 {
+    !crc32c_update(crc32c_update(0xFFFFFFFF, &page_num.to_le_bytes()), data)
+}
+
+/// Feed `data` into a running CRC32C (Castagnoli) started from `0xFFFFFFFF`;
+/// invert the result once all data is in
+fn crc32c_update(mut crc: u32, data: &[u8]) -> u32 {
+    const CRC32C_TABLE: [u32; 256] = generate_crc32c_table();
+
+    for &byte in data {
+        let index = ((crc ^ byte as u32) & 0xFF) as usize;
+        crc = (crc >> 8) ^ CRC32C_TABLE[index];
+    }
+    crc
+}
+
+const fn generate_crc32c_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            if crc & 1 != 0 {
+                crc = (crc >> 1) ^ 0x82F63B78;
+            } else {
+                crc >>= 1;
+            }
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// Read the page size from the header of an existing database file, before
+/// the rest of the header (which fills a whole page) can be read
+fn read_page_size(file: &mut File) -> Result<usize> {
+    let mut buf = [0u8; 12];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut buf)?;
+
+    if buf[0..4] != MAGIC {
+        return Err(Error::InvalidMagic);
+    }
+    let page_size = u32::from_le_bytes(buf[8..12].try_into()?) as usize;
+    if !is_supported_page_size(page_size) {
+        return Err(Error::InvalidPageSize);
+    }
+    Ok(page_size)
 }
 
 #[cfg(test)]
@@ -733,6 +1054,30 @@ mod tests {
         assert_eq!(parsed.page_size, PAGE_SIZE as u32);
     }
 
+    #[test]
+    fn test_header_rejects_unknown_flags_and_versions() {
+        let mut header = Header::new();
+        header.flags = 1 << 7;
+        assert!(matches!(Header::deserialize(&header.serialize()), Err(Error::InvalidVersion)));
+
+        let mut header = Header::new();
+        header.version = VERSION_PAGE_CHECKSUMS + 1;
+        assert!(matches!(Header::deserialize(&header.serialize()), Err(Error::InvalidVersion)));
+
+        // Checksummed files carry their own version so older builds refuse them
+        let mut header = Header::new();
+        header.flags = HEADER_FLAG_PAGE_CHECKSUMS;
+        assert!(Header::deserialize(&header.serialize()).is_err());
+        header.version = VERSION_PAGE_CHECKSUMS;
+        assert_eq!(Header::deserialize(&header.serialize()).unwrap().flags, HEADER_FLAG_PAGE_CHECKSUMS);
+    }
+
+    #[test]
+    fn test_crc32c() {
+        assert_eq!(!crc32c_update(0xFFFFFFFF, b""), 0);
+        assert_eq!(!crc32c_update(0xFFFFFFFF, b"123456789"), 0xE3069283);
+    }
+
     #[test]
     fn test_pager_create() {
         let path = "/tmp/test_pager_create.db";
@@ -786,4 +1131,36 @@ mod tests {
         pager.close().unwrap();
         let _ = fs::remove_file(path);
     }
+
+    #[test]
+    fn test_page_checksum_mismatch() {
+        let path = "/tmp/test_page_checksum.db";
+        let _ = fs::remove_file(path);
+
+        let page_num = {
+            let pager = Pager::open_with_page_size(path, 100, 0o644, false, None, true).unwrap();
+            assert_eq!(pager.page_size(), PAGE_SIZE - PAGE_CHECKSUM_SIZE);
+
+            let page_num = pager.alloc_page().unwrap();
+            pager.write_page_transfer(page_num, vec![42u8; pager.page_size()]).unwrap();
+            pager.flush().unwrap();
+            pager.close().unwrap();
+            page_num
+        };
+
+        let mut contents = fs::read(path).unwrap();
+        contents[page_num as usize * PAGE_SIZE + 100] ^= 0x01;
+        fs::write(path, &contents).unwrap();
+
+        // The checksum flag comes from the header, whatever is asked for
+        let pager = Pager::open_with_page_size(path, 100, 0o644, false, None, false).unwrap();
+        assert!(pager.has_page_checksums());
+        match pager.read_page(page_num) {
+            Err(Error::PageChecksumMismatch { page }) => assert_eq!(page, page_num),
+            other => panic!("expected a checksum mismatch, got {:?}", other.map(|d| d.len())),
+        }
+
+        pager.close().unwrap();
+        let _ = fs::remove_file(path);
+    }
 }
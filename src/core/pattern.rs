@@ -0,0 +1,383 @@
+//! Small backtracking matcher for the regular expressions JSON Schema uses in
+//! `pattern`. Covers the common ECMA-262 subset: literals, `.`, character
+//! classes (`[a-z]`, `[^...]`, `\d \w \s` and their negations), groups with
+//! `|`, the quantifiers `* + ? {n} {n,} {n,m}` (greedy or lazy) and the `^`/`$`
+//! anchors. Lookaround, backreferences and named groups are rejected when the
+//! pattern is compiled rather than silently misread.
+//!
+//! As in JSON Schema, a pattern matches if it is found anywhere in the string;
+//! anchor it with `^...$` to match the whole value.
+
+/// A compiled pattern
+#[derive(Debug, Clone)]
+pub(crate) struct Pattern {
+    alternatives: Vec<Vec<Node>>,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Char(char),
+    Any,
+    Class { ranges: Vec<(char, char)>, negated: bool },
+    Start,
+    End,
+    Group(Vec<Vec<Node>>),
+    Repeat { node: Box<Node>, min: usize, max: Option<usize>, greedy: bool },
+}
+
+const DIGIT: &[(char, char)] = &[('0', '9')];
+const WORD: &[(char, char)] = &[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+const SPACE: &[(char, char)] = &[('\t', '\r'), (' ', ' '), ('\u{a0}', '\u{a0}'), ('\u{2028}', '\u{2029}'), ('\u{feff}', '\u{feff}')];
+
+impl Pattern {
+    /// Compile `source`, or describe why it can't be used
+    pub(crate) fn compile(source: &str) -> std::result::Result<Self, String> {
+        let mut parser = Parser { chars: source.chars().collect(), pos: 0 };
+        let alternatives = parser.alternatives()?;
+        if parser.pos < parser.chars.len() {
+            return Err(format!("unmatched ')' at position {}", parser.pos));
+        }
+        Ok(Self { alternatives })
+    }
+
+    /// Whether the pattern occurs anywhere in `text`
+    pub(crate) fn is_match(&self, text: &str) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        (0..=chars.len()).any(|start| {
+            self.alternatives.iter().any(|seq| match_seq(seq, &chars, start, &|_| true))
+        })
+    }
+}
+
+fn match_seq(nodes: &[Node], text: &[char], pos: usize, k: &dyn Fn(usize) -> bool) -> bool {
+    match nodes.split_first() {
+        None => k(pos),
+        Some((node, rest)) => match_node(node, text, pos, &|next| match_seq(rest, text, next, k)),
+    }
+}
+
+fn match_node(node: &Node, text: &[char], pos: usize, k: &dyn Fn(usize) -> bool) -> bool {
+    match node {
+        Node::Char(c) => text.get(pos) == Some(c) && k(pos + 1),
+        Node::Any => text.get(pos).is_some_and(|&c| !is_line_terminator(c)) && k(pos + 1),
+        Node::Class { ranges, negated } => {
+            text.get(pos).is_some_and(|&c| in_ranges(ranges, c) != *negated) && k(pos + 1)
+        }
+        Node::Start => pos == 0 && k(pos),
+        Node::End => pos == text.len() && k(pos),
+        Node::Group(alternatives) => alternatives.iter().any(|seq| match_seq(seq, text, pos, k)),
+        Node::Repeat { node, min, max, greedy } => {
+            match_repeat(node, *min, *max, *greedy, 0, text, pos, k)
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn match_repeat(
+    node: &Node,
+    min: usize,
+    max: Option<usize>,
+    greedy: bool,
+    count: usize,
+    text: &[char],
+    pos: usize,
+    k: &dyn Fn(usize) -> bool,
+) -> bool {
+    if count < min {
+        return match_node(node, text, pos, &|next| {
+            match_repeat(node, min, max, greedy, count + 1, text, next, k)
+        });
+    }
+
+    // An iteration that consumes nothing can't lead anywhere new, so stop there
+    let more = || {
+        max.is_none_or(|max| count < max)
+            && match_node(node, text, pos, &|next| {
+                next != pos && match_repeat(node, min, max, greedy, count + 1, text, next, k)
+            })
+    };
+
+    // Greedy repeats try another iteration before the rest of the pattern,
+    // lazy ones the other way round
+    if greedy {
+        return more() || k(pos);
+    }
+    k(pos) || more()
+}
+
+fn in_ranges(ranges: &[(char, char)], c: char) -> bool {
+    ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi)
+}
+
+fn is_line_terminator(c: char) -> bool {
+    matches!(c, '\n' | '\r' | '\u{2028}' | '\u{2029}')
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += 1;
+        c
+    }
+
+    fn alternatives(&mut self) -> std::result::Result<Vec<Vec<Node>>, String> {
+        let mut alternatives = vec![self.sequence()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            alternatives.push(self.sequence()?);
+        }
+        Ok(alternatives)
+    }
+
+    fn sequence(&mut self) -> std::result::Result<Vec<Node>, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.quantified(atom)?);
+        }
+        Ok(nodes)
+    }
+
+    fn atom(&mut self) -> std::result::Result<Node, String> {
+        let start = self.pos;
+        match self.next() {
+            Some('^') => Ok(Node::Start),
+            Some('$') => Ok(Node::End),
+            Some('.') => Ok(Node::Any),
+            Some('(') => {
+                if self.peek() == Some('?') {
+                    self.pos += 1;
+                    if self.next() != Some(':') {
+                        return Err(format!("lookaround and named groups are not supported (position {})", start));
+                    }
+                }
+                let alternatives = self.alternatives()?;
+                if self.next() != Some(')') {
+                    return Err(format!("unclosed group at position {}", start));
+                }
+                Ok(Node::Group(alternatives))
+            }
+            Some('[') => self.class(start),
+            Some('\\') => self.escape(start),
+            Some(c @ ('*' | '+' | '?')) => Err(format!("nothing to repeat before '{}' at position {}", c, start)),
+            Some(c) => Ok(Node::Char(c)),
+            None => Err("unexpected end of pattern".to_string()),
+        }
+    }
+
+    fn escape(&mut self, start: usize) -> std::result::Result<Node, String> {
+        let c = self.next().ok_or("pattern ends with '\\'")?;
+        Ok(match c {
+            'd' => Node::Class { ranges: DIGIT.to_vec(), negated: false },
+            'D' => Node::Class { ranges: DIGIT.to_vec(), negated: true },
+            'w' => Node::Class { ranges: WORD.to_vec(), negated: false },
+            'W' => Node::Class { ranges: WORD.to_vec(), negated: true },
+            's' => Node::Class { ranges: SPACE.to_vec(), negated: false },
+            'S' => Node::Class { ranges: SPACE.to_vec(), negated: true },
+            _ => Node::Char(self.escaped_char(c, start)?),
+        })
+    }
+
+    /// The character an escape other than a class shorthand stands for
+    fn escaped_char(&mut self, c: char, start: usize) -> std::result::Result<char, String> {
+        match c {
+            't' => Ok('\t'),
+            'n' => Ok('\n'),
+            'r' => Ok('\r'),
+            'f' => Ok('\u{c}'),
+            'v' => Ok('\u{b}'),
+            '0' => Ok('\0'),
+            'u' => {
+                let hex: String = self.chars.iter().skip(self.pos).take(4).collect();
+                let code = u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .filter(|_| hex.len() == 4)
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| format!("invalid \\u escape at position {}", start))?;
+                self.pos += 4;
+                Ok(code)
+            }
+            c if c.is_ascii_alphanumeric() => {
+                Err(format!("escape '\\{}' is not supported (position {})", c, start))
+            }
+            c => Ok(c),
+        }
+    }
+
+    fn class(&mut self, start: usize) -> std::result::Result<Node, String> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.next().ok_or_else(|| format!("unclosed character class at position {}", start))?;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+
+            let lo = match c {
+                '\\' => {
+                    let escaped = self.next().ok_or("pattern ends with '\\'")?;
+                    let shorthand = match escaped {
+                        'd' => Some(DIGIT),
+                        'w' => Some(WORD),
+                        's' => Some(SPACE),
+                        'D' | 'W' | 'S' => {
+                            return Err(format!("negated shorthand inside a class is not supported (position {})", start));
+                        }
+                        _ => None,
+                    };
+                    if let Some(shorthand) = shorthand {
+                        ranges.extend_from_slice(shorthand);
+                        continue;
+                    }
+                    if escaped == 'b' { '\u{8}' } else { self.escaped_char(escaped, start)? }
+                }
+                c => c,
+            };
+
+            // `-` forms a range unless it is last in the class
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&c| c != ']') {
+                self.pos += 1;
+                let hi = match self.next() {
+                    Some('\\') => {
+                        let escaped = self.next().ok_or("pattern ends with '\\'")?;
+                        self.escaped_char(escaped, start)?
+                    }
+                    Some(c) => c,
+                    None => return Err(format!("unclosed character class at position {}", start)),
+                };
+                if hi < lo {
+                    return Err(format!("range out of order in character class at position {}", start));
+                }
+                ranges.push((lo, hi));
+            } else {
+                ranges.push((lo, lo));
+            }
+        }
+
+        Ok(Node::Class { ranges, negated })
+    }
+
+    fn quantified(&mut self, atom: Node) -> std::result::Result<Node, String> {
+        let start = self.pos;
+        let (min, max) = match self.peek() {
+            Some('{') => match self.braces() {
+                Some(bounds) => bounds,
+                None => return Ok(atom),
+            },
+            Some(c @ ('*' | '+' | '?')) => {
+                self.pos += 1;
+                match c {
+                    '*' => (0, None),
+                    '+' => (1, None),
+                    _ => (0, Some(1)),
+                }
+            }
+            _ => return Ok(atom),
+        };
+
+        if matches!(atom, Node::Start | Node::End) {
+            return Err(format!("nothing to repeat at position {}", start));
+        }
+
+        let greedy = self.peek() != Some('?');
+        if !greedy {
+            self.pos += 1;
+        }
+
+        Ok(Node::Repeat { node: Box::new(atom), min, max, greedy })
+    }
+
+    /// Parse `{n}`, `{n,}` or `{n,m}` at the cursor and move past it. Anything
+    /// else leaves the cursor alone so the `{` reads as a literal.
+    fn braces(&mut self) -> Option<(usize, Option<usize>)> {
+        let rest: String = self.chars[self.pos + 1..].iter().collect();
+        let end = rest.find('}')?;
+        let body = &rest[..end];
+
+        let (min, max) = match body.split_once(',') {
+            None => {
+                let n = body.parse().ok()?;
+                (n, Some(n))
+            }
+            Some((min, "")) => (min.parse().ok()?, None),
+            Some((min, max)) => (min.parse().ok()?, Some(max.parse().ok()?)),
+        };
+        if max.is_some_and(|max| max < min) {
+            return None;
+        }
+
+        self.pos += 1 + body.chars().count() + 1;
+        Some((min, max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        Pattern::compile(pattern).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn test_unanchored_search() {
+        assert!(matches("abc", "xxabcxx"));
+        assert!(!matches("abd", "xxabcxx"));
+        assert!(matches("", "anything"));
+    }
+
+    #[test]
+    fn test_anchors_and_quantifiers() {
+        assert!(matches("^a+b*c?$", "aaab"));
+        assert!(matches("^a+b*c?$", "ac"));
+        assert!(!matches("^a+b*c?$", "b"));
+        assert!(matches("^\\d{3}-\\d{4}$", "555-1234"));
+        assert!(!matches("^\\d{3}-\\d{4}$", "55-1234"));
+        assert!(matches("^x{2,}$", "xxxx"));
+        assert!(matches("^x{1,2}y$", "xxy"));
+        assert!(!matches("^x{1,2}y$", "xxxy"));
+        assert!(matches("^a{,2}$", "a{,2}"));
+    }
+
+    #[test]
+    fn test_classes_groups_and_alternation() {
+        let email = "^[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\\.[A-Za-z]{2,}$";
+        assert!(matches(email, "alice@example.com"));
+        assert!(!matches(email, "alice@example"));
+        assert!(matches("^(cat|dog)s?$", "dogs"));
+        assert!(!matches("^(cat|dog)s?$", "cow"));
+        assert!(matches("^[^0-9]+$", "abc"));
+        assert!(!matches("^[^0-9]+$", "ab1"));
+        assert!(matches("^(?:ab)+$", "abab"));
+        assert!(matches("^a.*?b$", "axxb"));
+        assert!(matches("^\\u00e9$", "é"));
+    }
+
+    #[test]
+    fn test_unsupported_syntax_is_rejected() {
+        assert!(Pattern::compile("(?=a)").is_err());
+        assert!(Pattern::compile("(a)\\1").is_err());
+        assert!(Pattern::compile("(abc").is_err());
+        assert!(Pattern::compile("abc)").is_err());
+        assert!(Pattern::compile("[abc").is_err());
+        assert!(Pattern::compile("*a").is_err());
+    }
+}
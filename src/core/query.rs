@@ -1,4 +1,5 @@
 
 pub mod lexer;
 pub mod parser;
+pub mod ast;
 pub mod eval;
@@ -0,0 +1,280 @@
+use super::parser::{parse_query, parse_query_params, BinaryOp, CompareOp, ElemMatchOp, ExistsOp, HasOp, Node, UnaryOp};
+use crate::core::collation::Collation;
+use crate::core::errors::*;
+use serde_json::Value;
+use std::fmt;
+
+/// A query built in Rust instead of parsed from a string
+///
+/// Values are stored as given and never go through the query parser, so user
+/// input can't change the shape of the query and never needs quoting.
+///
+/// # Examples
+/// ```no_run
+/// # use jasonisnthappy::{Database, Query};
+/// # use serde_json::json;
+/// # let db = Database::open("my.db").unwrap();
+/// # let user_input = "O'Brien";
+/// let users = db.collection("users");
+/// let adults = users.find(
+///     Query::field("age").gt(json!(18)).and(Query::field("name").eq(json!(user_input)))
+/// ).unwrap();
+/// ```
+pub struct Query {
+    node: Box<dyn Node>,
+    text: String,
+}
+
+impl Query {
+    /// Start a condition on `field`, which may be a dotted path
+    pub fn field(field: &str) -> FieldQuery {
+        FieldQuery { field: field.to_string() }
+    }
+
+    /// Wrap an AST node from [`crate::core::query::parser`] as a query
+    pub fn raw(node: Box<dyn Node>) -> Self {
+        let text = format!("{:?}", node);
+        Self { node, text }
+    }
+
+    pub fn and(self, other: Query) -> Self {
+        self.binary("and", other)
+    }
+
+    pub fn or(self, other: Query) -> Self {
+        self.binary("or", other)
+    }
+
+    /// Documents that don't match this query
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Self {
+        Self {
+            text: format!("not ({})", self.text),
+            node: Box::new(UnaryOp { op: "not".to_string(), child: self.node }),
+        }
+    }
+
+    pub fn into_node(self) -> Box<dyn Node> {
+        self.node
+    }
+
+    fn binary(self, op: &str, other: Query) -> Self {
+        Self {
+            text: format!("({}) {} ({})", self.text, op, other.text),
+            node: Box::new(BinaryOp { op: op.to_string(), left: self.node, right: other.node }),
+        }
+    }
+}
+
+/// Renders the query in the query language, for logs and debugging
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl fmt::Debug for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Query").field(&self.text).finish()
+    }
+}
+
+/// Conditions on one field, created by [`Query::field`]
+pub struct FieldQuery {
+    field: String,
+}
+
+impl FieldQuery {
+    pub fn eq(self, value: Value) -> Query {
+        self.compare("is", "is", value)
+    }
+
+    pub fn ne(self, value: Value) -> Query {
+        self.compare("is_not", "is not", value)
+    }
+
+    pub fn gt(self, value: Value) -> Query {
+        self.compare(">", ">", value)
+    }
+
+    pub fn gte(self, value: Value) -> Query {
+        self.compare(">=", ">=", value)
+    }
+
+    pub fn lt(self, value: Value) -> Query {
+        self.compare("<", "<", value)
+    }
+
+    pub fn lte(self, value: Value) -> Query {
+        self.compare("<=", "<=", value)
+    }
+
+    /// The field is present and not null
+    pub fn exists(self) -> Query {
+        self.exists_op(false)
+    }
+
+    pub fn not_exists(self) -> Query {
+        self.exists_op(true)
+    }
+
+    /// The array field contains `value`
+    pub fn has(self, value: Value) -> Query {
+        self.has_op("has", "has", vec![value])
+    }
+
+    pub fn has_any(self, values: Vec<Value>) -> Query {
+        self.has_op("has_any", "has any", values)
+    }
+
+    pub fn has_all(self, values: Vec<Value>) -> Query {
+        self.has_op("has_all", "has all", values)
+    }
+
+    /// Some element of the array field matches `condition`, whose fields are
+    /// relative to the element
+    pub fn elem_match(self, condition: Query) -> Query {
+        Query {
+            text: format!("{} elemMatch ({})", self.field, condition.text),
+            node: Box::new(ElemMatchOp { field: self.field, condition: condition.node }),
+        }
+    }
+
+    fn compare(self, op: &str, keyword: &str, value: Value) -> Query {
+        Query {
+            text: format!("{} {} {}", self.field, keyword, value),
+            node: Box::new(CompareOp { field: self.field, op: op.to_string(), value, lax: false, collation: Collation::Binary }),
+        }
+    }
+
+    fn exists_op(self, not: bool) -> Query {
+        Query {
+            text: format!("{} {}", self.field, if not { "not exists" } else { "exists" }),
+            node: Box::new(ExistsOp { field: self.field, not }),
+        }
+    }
+
+    fn has_op(self, op: &str, keyword: &str, values: Vec<Value>) -> Query {
+        let text = match op {
+            "has" => format!("{} {} {}", self.field, keyword, values[0]),
+            _ => format!("{} {} {}", self.field, keyword, Value::Array(values.clone())),
+        };
+        Query {
+            text,
+            node: Box::new(HasOp { field: self.field, op: op.to_string(), values, collation: Collation::Binary }),
+        }
+    }
+}
+
+/// Anything a query can be given as: a query string, a query string with
+/// bound parameters or a [`Query`]
+///
+/// Strings are parsed when the query runs, so a malformed one still fails
+/// there with the parser's error.
+pub struct QueryAst {
+    source: Source,
+}
+
+enum Source {
+    Text(String),
+    Params(String, Vec<Value>),
+    Built(Query),
+}
+
+impl QueryAst {
+    /// A query string whose `?1`, `?2`, ... placeholders (or bare `?`, in
+    /// order) are bound to `params`. Each value is used as a whole JSON
+    /// value, so it can't change the shape of the query.
+    pub fn with_params(query: &str, params: &[Value]) -> Self {
+        Self { source: Source::Params(query.to_string(), params.to_vec()) }
+    }
+
+    /// The query as text, for logs
+    pub fn text(&self) -> &str {
+        match &self.source {
+            Source::Text(text) | Source::Params(text, _) => text,
+            Source::Built(query) => &query.text,
+        }
+    }
+
+    /// Text identifying the query's results in the query cache. Queries
+    /// built in Rust have none, since their text is only for logs.
+    pub(crate) fn cache_key(&self) -> Option<String> {
+        match &self.source {
+            Source::Text(text) => Some(text.clone()),
+            Source::Params(text, params) => Some(format!("{}\0{}", text, Value::Array(params.clone()))),
+            Source::Built(_) => None,
+        }
+    }
+
+    /// The query's AST, parsing it first if it was given as a string
+    pub fn into_node(self) -> Result<Box<dyn Node>> {
+        self.into_parts().map(|(node, _)| node)
+    }
+
+    pub(crate) fn into_parts(self) -> Result<(Box<dyn Node>, String)> {
+        match self.source {
+            Source::Text(text) => parse_query(&text)
+                .map(|node| (node, text))
+                .map_err(|e| Error::Other(format!("failed to parse query: {}", e))),
+            Source::Params(text, params) => parse_query_params(&text, &params)
+                .map(|node| (node, text))
+                .map_err(|e| Error::Other(format!("failed to parse query: {}", e))),
+            Source::Built(query) => Ok((query.node, query.text)),
+        }
+    }
+}
+
+impl From<Query> for QueryAst {
+    fn from(query: Query) -> Self {
+        Self { source: Source::Built(query) }
+    }
+}
+
+impl<T: AsRef<str> + ?Sized> From<&T> for QueryAst {
+    fn from(text: &T) -> Self {
+        Self { source: Source::Text(text.as_ref().to_string()) }
+    }
+}
+
+impl From<String> for QueryAst {
+    fn from(text: String) -> Self {
+        Self { source: Source::Text(text) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn eval(query: Query, doc: Value) -> bool {
+        query.into_node().eval(doc.as_object().unwrap())
+    }
+
+    #[test]
+    fn test_built_queries_match_parsed_ones() {
+        let doc = json!({"age": 30, "name": "O'Brien \" and 1", "tags": ["a", "b"], "items": [{"qty": 3}]});
+
+        assert!(eval(Query::field("age").gt(json!(18)).and(Query::field("name").eq(json!("O'Brien \" and 1"))), doc.clone()));
+        assert!(!eval(Query::field("age").lt(json!(18)).or(Query::field("name").eq(json!("x"))), doc.clone()));
+        assert!(eval(Query::field("age").gte(json!(30)).and(Query::field("age").lte(json!(30))), doc.clone()));
+        assert!(eval(Query::field("age").ne(json!(31)), doc.clone()));
+        assert!(eval(Query::field("missing").exists().not(), doc.clone()));
+        assert!(eval(Query::field("missing").not_exists(), doc.clone()));
+        assert!(eval(Query::field("tags").has(json!("a")), doc.clone()));
+        assert!(eval(Query::field("tags").has_all(vec![json!("a"), json!("b")]), doc.clone()));
+        assert!(!eval(Query::field("tags").has_any(vec![json!("z")]), doc.clone()));
+        assert!(eval(Query::field("items").elem_match(Query::field("qty").gt(json!(2))), doc));
+    }
+
+    #[test]
+    fn test_query_text() {
+        let query = Query::field("age").gt(json!(18)).and(Query::field("name").ne(json!("bob")));
+        assert_eq!(query.to_string(), "(age > 18) and (name is not \"bob\")");
+
+        let ast = QueryAst::from("age > 18");
+        assert_eq!(ast.text(), "age > 18");
+        assert!(QueryAst::from("age >").into_node().is_err());
+    }
+}
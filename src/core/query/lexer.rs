@@ -12,6 +12,7 @@ pub enum TokenType {
     True,
     False,
     Null,
+    Param,
 
     Gt,
     Gte,
@@ -26,6 +27,8 @@ pub enum TokenType {
 
     Exists,
     Has,
+    Contains,
+    ElemMatch,
     Any,
     All,
 
@@ -48,6 +51,7 @@ impl fmt::Display for TokenType {
             TokenType::True => write!(f, "TRUE"),
             TokenType::False => write!(f, "FALSE"),
             TokenType::Null => write!(f, "NULL"),
+            TokenType::Param => write!(f, "PARAM"),
             TokenType::Gt => write!(f, ">"),
             TokenType::Gte => write!(f, ">="),
             TokenType::Lt => write!(f, "<"),
@@ -59,6 +63,8 @@ impl fmt::Display for TokenType {
             TokenType::Not => write!(f, "NOT"),
             TokenType::Exists => write!(f, "EXISTS"),
             TokenType::Has => write!(f, "HAS"),
+            TokenType::Contains => write!(f, "CONTAINS"),
+            TokenType::ElemMatch => write!(f, "ELEMMATCH"),
             TokenType::Any => write!(f, "ANY"),
             TokenType::All => write!(f, "ALL"),
             TokenType::LParen => write!(f, "("),
@@ -184,6 +190,11 @@ impl Lexer {
                     tok
                 }
             }
+            '?' => {
+                self.read_char();
+                let value = self.read_digits();
+                Token::new(TokenType::Param, value, token_pos)
+            }
             '"' | '\'' => {
                 let value = self.read_string();
                 Token::new(TokenType::String, value, token_pos)
@@ -193,7 +204,7 @@ impl Lexer {
                     let value = self.read_identifier();
                     let token_type = lookup_keyword(&value);
                     return Token::new(token_type, value, token_pos);
-                } else if is_digit(self.ch) {
+                } else if is_digit(self.ch) || (self.ch == '-' && is_digit(self.peek_char())) {
                     let value = self.read_number();
                     return Token::new(TokenType::Number, value, token_pos);
                 } else {
@@ -223,6 +234,9 @@ impl Lexer {
 
     fn read_number(&mut self) -> String {
         let start = self.pos - 1;
+        if self.ch == '-' {
+            self.read_char();
+        }
         while is_digit(self.ch) {
             self.read_char();
         }
@@ -237,6 +251,15 @@ impl Lexer {
         self.input[start..self.pos - 1].iter().collect()
     }
 
+    /// The digits of a `?N` placeholder, empty for a bare `?`
+    fn read_digits(&mut self) -> String {
+        let start = self.pos - 1;
+        while self.ch.is_ascii_digit() {
+            self.read_char();
+        }
+        self.input[start..self.pos - 1].iter().collect()
+    }
+
     fn read_string(&mut self) -> String {
         let quote = self.ch;
         self.read_char();
@@ -260,6 +283,8 @@ fn lookup_keyword(ident: &str) -> TokenType {
         "is" => TokenType::Is,
         "exists" => TokenType::Exists,
         "has" => TokenType::Has,
+        "contains" => TokenType::Contains,
+        "elemmatch" => TokenType::ElemMatch,
         "any" => TokenType::Any,
         "all" => TokenType::All,
         "true" => TokenType::True,
@@ -329,6 +354,16 @@ mod tests {
         assert_eq!(tokens[4].token_type, TokenType::Is);
     }
 
+    #[test]
+    fn test_array_operators() {
+        let input = "contains elemMatch ELEMMATCH";
+        let tokens = tokenize(input).unwrap();
+
+        assert_eq!(tokens[0].token_type, TokenType::Contains);
+        assert_eq!(tokens[1].token_type, TokenType::ElemMatch);
+        assert_eq!(tokens[2].token_type, TokenType::ElemMatch);
+    }
+
     #[test]
     fn test_logical_operators() {
         let input = "and or not";
@@ -403,6 +438,15 @@ mod tests {
         assert_eq!(tokens[2].value, "19.99");
     }
 
+    #[test]
+    fn test_negative_numbers() {
+        let tokens = tokenize("lng > -0.5").unwrap();
+
+        assert_eq!(tokens[2].token_type, TokenType::Number);
+        assert_eq!(tokens[2].value, "-0.5");
+        assert_eq!(tokens[3].token_type, TokenType::Eof);
+    }
+
     #[test]
     fn test_dot_notation() {
         let input = "user.address.city";
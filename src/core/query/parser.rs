@@ -1,9 +1,35 @@
 
 use super::lexer::{Token, TokenType};
+use crate::core::binary::{binary_value, decode_base64};
+use crate::core::collation::Collation;
+use crate::core::datetime::timestamp_millis;
+use crate::core::expression::{self, Expression};
+use crate::core::geo;
 use serde_json::Value;
 
-pub trait Node: std::fmt::Debug {
+pub trait Node: std::fmt::Debug + Send + Sync {
     fn eval(&self, doc: &serde_json::Map<String, Value>) -> bool;
+
+    /// Append the document fields this node reads to `fields`
+    fn collect_fields(&self, fields: &mut Vec<String>);
+
+    /// A `near()` condition every matching document satisfies, which a geo
+    /// index can answer
+    fn near(&self) -> Option<&NearOp> {
+        None
+    }
+
+    /// Append the `is` comparisons on computed values that every matching
+    /// document satisfies, which an expression index can answer
+    fn expression_equalities<'a>(&'a self, _found: &mut Vec<(&'a Expression, &'a Value)>) {}
+
+    /// Let the comparisons in this node match a string holding a number
+    /// against a number, as set by `DatabaseOptions::strict_query_types`
+    fn coerce_numeric_strings(&mut self) {}
+
+    /// Compare the strings in this node's comparisons under `collation`, as
+    /// set by `QueryBuilder::collation`
+    fn set_collation(&mut self, _collation: Collation) {}
 }
 
 #[derive(Debug)]
@@ -21,6 +47,35 @@ impl Node for BinaryOp {
             _ => false,
         }
     }
+
+    fn collect_fields(&self, fields: &mut Vec<String>) {
+        self.left.collect_fields(fields);
+        self.right.collect_fields(fields);
+    }
+
+    fn near(&self) -> Option<&NearOp> {
+        match self.op.as_str() {
+            "and" => self.left.near().or_else(|| self.right.near()),
+            _ => None,
+        }
+    }
+
+    fn expression_equalities<'a>(&'a self, found: &mut Vec<(&'a Expression, &'a Value)>) {
+        if self.op == "and" {
+            self.left.expression_equalities(found);
+            self.right.expression_equalities(found);
+        }
+    }
+
+    fn coerce_numeric_strings(&mut self) {
+        self.left.coerce_numeric_strings();
+        self.right.coerce_numeric_strings();
+    }
+
+    fn set_collation(&mut self, collation: Collation) {
+        self.left.set_collation(collation);
+        self.right.set_collation(collation);
+    }
 }
 
 #[derive(Debug)]
@@ -37,6 +92,18 @@ impl Node for UnaryOp {
             false
         }
     }
+
+    fn collect_fields(&self, fields: &mut Vec<String>) {
+        self.child.collect_fields(fields);
+    }
+
+    fn coerce_numeric_strings(&mut self) {
+        self.child.coerce_numeric_strings();
+    }
+
+    fn set_collation(&mut self, collation: Collation) {
+        self.child.set_collation(collation);
+    }
 }
 
 #[derive(Debug)]
@@ -44,22 +111,94 @@ pub struct CompareOp {
     pub field: String,
     pub op: String,
     pub value: Value,
+    /// Compare strings holding numbers as those numbers
+    pub lax: bool,
+    /// Collation strings are compared under, with `value` already folded
+    pub collation: Collation,
 }
 
 impl Node for CompareOp {
     fn eval(&self, doc: &serde_json::Map<String, Value>) -> bool {
-        let field_value = get_field(doc, &self.field);
+        let value = &self.value;
+        let coerced = |v: &Value| if self.lax { coerce_numeric_pair(v, value) } else { None };
+        let greater = |v: &Value| {
+            let v = &*self.collation.fold_value(v);
+            coerced(v).map_or_else(|| compare_greater(v, value), |(a, b)| a > b)
+        };
+        let less = |v: &Value| {
+            let v = &*self.collation.fold_value(v);
+            coerced(v).map_or_else(|| compare_less(v, value), |(a, b)| a < b)
+        };
+        let equal = |v: &Value| {
+            let v = &*self.collation.fold_value(v);
+            coerced(v).map_or_else(|| compare_equal(v, value), |(a, b)| a == b)
+        };
+        match self.op.as_str() {
+            ">" => any_field_value(doc, &self.field, greater),
+            ">=" => any_field_value(doc, &self.field, |v| greater(v) || equal(v)),
+            "<" => any_field_value(doc, &self.field, less),
+            "<=" => any_field_value(doc, &self.field, |v| less(v) || equal(v)),
+            "is" => any_field_value(doc, &self.field, equal),
+            "is_not" => !any_field_value(doc, &self.field, equal),
+            _ => false,
+        }
+    }
+
+    fn collect_fields(&self, fields: &mut Vec<String>) {
+        fields.push(self.field.clone());
+    }
 
+    fn coerce_numeric_strings(&mut self) {
+        self.lax = true;
+    }
+
+    fn set_collation(&mut self, collation: Collation) {
+        self.value = collation.fold_value(&self.value).into_owned();
+        self.collation = collation;
+    }
+}
+
+/// A comparison of a value computed from the document, such as
+/// `lower(email) is 'ann@example.com'`
+#[derive(Debug)]
+pub struct ExpressionCompareOp {
+    pub expr: Expression,
+    pub op: String,
+    pub value: Value,
+    /// Collation strings are compared under, with `value` already folded
+    pub collation: Collation,
+}
+
+impl Node for ExpressionCompareOp {
+    fn eval(&self, doc: &serde_json::Map<String, Value>) -> bool {
+        let computed = self.expr.eval(doc);
+        let computed = &*self.collation.fold_value(&computed);
         match self.op.as_str() {
-            ">" => compare_greater(&field_value, &self.value),
-            ">=" => compare_greater(&field_value, &self.value) || compare_equal(&field_value, &self.value),
-            "<" => compare_less(&field_value, &self.value),
-            "<=" => compare_less(&field_value, &self.value) || compare_equal(&field_value, &self.value),
-            "is" => compare_equal(&field_value, &self.value),
-            "is_not" => !compare_equal(&field_value, &self.value),
+            ">" => compare_greater(computed, &self.value),
+            ">=" => compare_greater(computed, &self.value) || compare_equal(computed, &self.value),
+            "<" => compare_less(computed, &self.value),
+            "<=" => compare_less(computed, &self.value) || compare_equal(computed, &self.value),
+            "is" => compare_equal(computed, &self.value),
+            "is_not" => !compare_equal(computed, &self.value),
             _ => false,
         }
     }
+
+    /// Reported as the expression text, the field an expression index holds
+    fn collect_fields(&self, fields: &mut Vec<String>) {
+        fields.push(self.expr.to_string());
+    }
+
+    fn expression_equalities<'a>(&'a self, found: &mut Vec<(&'a Expression, &'a Value)>) {
+        if self.op == "is" {
+            found.push((&self.expr, &self.value));
+        }
+    }
+
+    fn set_collation(&mut self, collation: Collation) {
+        self.value = collation.fold_value(&self.value).into_owned();
+        self.collation = collation;
+    }
 }
 
 #[derive(Debug)]
@@ -70,13 +209,17 @@ pub struct ExistsOp {
 
 impl Node for ExistsOp {
     fn eval(&self, doc: &serde_json::Map<String, Value>) -> bool {
-        let exists = !get_field(doc, &self.field).is_null();
+        let exists = field_values(doc, &self.field).iter().any(|value| !value.is_null());
         if self.not {
             !exists
         } else {
             exists
         }
     }
+
+    fn collect_fields(&self, fields: &mut Vec<String>) {
+        fields.push(self.field.clone());
+    }
 }
 
 #[derive(Debug)]
@@ -84,13 +227,18 @@ pub struct HasOp {
     pub field: String,
     pub op: String,
     pub values: Vec<Value>,
+    /// Collation strings are compared under, with `values` already folded
+    pub collation: Collation,
 }
 
 impl Node for HasOp {
     fn eval(&self, doc: &serde_json::Map<String, Value>) -> bool {
-        let field_value = get_field(doc, &self.field);
-
-        if let Some(arr) = field_value.as_array() {
+        field_values(doc, &self.field).into_iter().any(|field_value| {
+            let Some(arr) = field_value.as_array() else {
+                return false;
+            };
+            let folded = self.collation.fold_value(field_value);
+            let arr = folded.as_array().unwrap_or(arr);
             match self.op.as_str() {
                 "has" => {
                     if let Some(val) = self.values.first() {
@@ -111,20 +259,144 @@ impl Node for HasOp {
                 }
                 _ => false,
             }
-        } else {
-            false
+        })
+    }
+
+    fn collect_fields(&self, fields: &mut Vec<String>) {
+        fields.push(self.field.clone());
+    }
+
+    fn set_collation(&mut self, collation: Collation) {
+        for value in &mut self.values {
+            *value = collation.fold_value(value).into_owned();
         }
+        self.collation = collation;
+    }
+}
+
+/// Matches when at least one element of an array field satisfies the whole
+/// sub-query. Field names in the sub-query are relative to the element.
+#[derive(Debug)]
+pub struct ElemMatchOp {
+    pub field: String,
+    pub condition: Box<dyn Node>,
+}
+
+impl Node for ElemMatchOp {
+    fn eval(&self, doc: &serde_json::Map<String, Value>) -> bool {
+        field_values(doc, &self.field).into_iter().any(|field_value| {
+            field_value.as_array().is_some_and(|arr| arr.iter().any(|elem| {
+                elem.as_object().is_some_and(|elem_map| self.condition.eval(elem_map))
+            }))
+        })
+    }
+
+    /// The sub-query is evaluated against whole elements, so the array field
+    /// itself is what gets read
+    fn collect_fields(&self, fields: &mut Vec<String>) {
+        fields.push(self.field.clone());
+    }
+
+    fn coerce_numeric_strings(&mut self) {
+        self.condition.coerce_numeric_strings();
+    }
+
+    fn set_collation(&mut self, collation: Collation) {
+        self.condition.set_collation(collation);
+    }
+}
+
+/// Comparison against a `date(...)` literal. Both sides are normalized to
+/// UTC epoch milliseconds; documents whose field isn't a timestamp never match.
+#[derive(Debug)]
+pub struct DateCompareOp {
+    pub field: String,
+    pub op: String,
+    pub millis: i64,
+}
+
+impl Node for DateCompareOp {
+    fn eval(&self, doc: &serde_json::Map<String, Value>) -> bool {
+        let field_millis: Vec<i64> = comparable_field_values(doc, &self.field)
+            .filter_map(timestamp_millis)
+            .collect();
+        if field_millis.is_empty() {
+            return false;
+        }
+
+        match self.op.as_str() {
+            ">" => field_millis.iter().any(|&millis| millis > self.millis),
+            ">=" => field_millis.iter().any(|&millis| millis >= self.millis),
+            "<" => field_millis.iter().any(|&millis| millis < self.millis),
+            "<=" => field_millis.iter().any(|&millis| millis <= self.millis),
+            "is" => field_millis.contains(&self.millis),
+            "is_not" => !field_millis.contains(&self.millis),
+            _ => false,
+        }
+    }
+
+    fn collect_fields(&self, fields: &mut Vec<String>) {
+        fields.push(self.field.clone());
+    }
+}
+
+/// `near(field, lat, lng, radius_km)`: the point at `field.lat` and
+/// `field.lng` is within `radius_km` of the given point
+#[derive(Debug)]
+pub struct NearOp {
+    pub field: String,
+    pub lat: f64,
+    pub lng: f64,
+    pub radius_km: f64,
+    lat_field: String,
+    lng_field: String,
+}
+
+impl NearOp {
+    /// Fields holding the latitude and longitude a geo index must cover
+    pub fn point_fields(&self) -> (&str, &str) {
+        (&self.lat_field, &self.lng_field)
+    }
+
+    /// Distance in kilometers from the query point to the document's point,
+    /// `None` if the document has no valid point
+    pub fn distance_km(&self, doc: &serde_json::Map<String, Value>) -> Option<f64> {
+        geo::point_at(doc, &self.lat_field, &self.lng_field)
+            .map(|(lat, lng)| geo::haversine_km(self.lat, self.lng, lat, lng))
+    }
+}
+
+impl Node for NearOp {
+    fn eval(&self, doc: &serde_json::Map<String, Value>) -> bool {
+        self.distance_km(doc).is_some_and(|distance| distance <= self.radius_km)
+    }
+
+    fn collect_fields(&self, fields: &mut Vec<String>) {
+        fields.push(self.lat_field.clone());
+        fields.push(self.lng_field.clone());
+    }
+
+    fn near(&self) -> Option<&NearOp> {
+        Some(self)
     }
 }
 
 pub struct Parser {
     tokens: Vec<Token>,
     pos: usize,
+    params: Vec<Value>,
+    /// Index of the value a bare `?` binds to next
+    next_param: usize,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, pos: 0 }
+        Self::with_params(tokens, Vec::new())
+    }
+
+    /// Parser that binds `?1`, `?2`, ... (or bare `?`, in order) to `params`
+    pub fn with_params(tokens: Vec<Token>, params: Vec<Value>) -> Self {
+        Self { tokens, pos: 0, params, next_param: 0 }
     }
 
     pub fn parse(&mut self) -> Result<Box<dyn Node>, String> {
@@ -181,23 +453,20 @@ impl Parser {
             }));
         }
 
-        if !self.check(TokenType::Ident) {
-            return Err(format!(
-                "expected field name at position {}, got {:?}",
-                self.current().pos,
-                self.current().token_type
-            ));
+        if self.check_call("near") {
+            return self.parse_near();
         }
-        let mut field = self.advance().value.clone();
 
-        while self.match_token(&[TokenType::Dot]) {
-            if !self.check(TokenType::Ident) {
-                return Err(format!("expected field name after '.' at position {}", self.current().pos));
-            }
-            field.push('.');
-            field.push_str(&self.advance().value);
+        if self.check_expression_call() {
+            let expr = self.parse_expression()?;
+            let op = self.compare_op()
+                .ok_or_else(|| format!("expected comparison operator at position {}", self.current().pos))?;
+            let value = self.parse_value()?;
+            return Ok(Box::new(ExpressionCompareOp { expr, op, value, collation: Collation::Binary }));
         }
 
+        let field = self.parse_field()?;
+
         if self.match_token(&[TokenType::Exists]) {
             return Ok(Box::new(ExistsOp {
                 field,
@@ -215,38 +484,244 @@ impl Parser {
             }));
         }
 
-        if self.match_token(&[TokenType::Has]) {
+        if self.match_token(&[TokenType::Has, TokenType::Contains]) {
             return self.parse_has(field);
         }
 
+        if self.match_token(&[TokenType::ElemMatch]) {
+            if !self.match_token(&[TokenType::LParen]) {
+                return Err(format!("expected '(' after 'elemMatch' at position {}", self.current().pos));
+            }
+            let condition = self.or_expr()?;
+            if !self.match_token(&[TokenType::RParen]) {
+                return Err(format!("expected ')' at position {}", self.current().pos));
+            }
+            return Ok(Box::new(ElemMatchOp { field, condition }));
+        }
+
+        let op = match self.compare_op() {
+            Some(op) => op,
+            None => {
+                if self.is_at_end() || self.check(TokenType::And) || self.check(TokenType::Or) || self.check(TokenType::RParen) {
+                    return Ok(Box::new(CompareOp {
+                        field,
+                        op: "is".to_string(),
+                        value: Value::Bool(true),
+                        lax: false,
+                        collation: Collation::Binary,
+                    }));
+                }
+                return Err(format!("expected comparison operator at position {}", self.current().pos));
+            }
+        };
+
+        if self.check_date_call() {
+            let millis = self.parse_date()?;
+            return Ok(Box::new(DateCompareOp { field, op, millis }));
+        }
+
+        if self.check_call("binary") {
+            let pos = self.current().pos;
+            if op != "is" && op != "is_not" {
+                return Err(format!("binary values at position {} can only be compared with 'is' and 'is not'", pos));
+            }
+            let value = self.parse_binary()?;
+            return Ok(Box::new(CompareOp { field, op, value, lax: false, collation: Collation::Binary }));
+        }
+
+        let value = self.parse_value()?;
+
+        Ok(Box::new(CompareOp { field, op, value, lax: false, collation: Collation::Binary }))
+    }
+
+    /// Consume a comparison operator, if one comes next
+    fn compare_op(&mut self) -> Option<String> {
         let op = if self.match_token(&[TokenType::Gt]) {
-            ">".to_string()
+            ">"
         } else if self.match_token(&[TokenType::Gte]) {
-            ">=".to_string()
+            ">="
         } else if self.match_token(&[TokenType::Lt]) {
-            "<".to_string()
+            "<"
         } else if self.match_token(&[TokenType::Lte]) {
-            "<=".to_string()
+            "<="
         } else if self.match_token(&[TokenType::Is]) {
             if self.match_token(&[TokenType::Not]) {
-                "is_not".to_string()
+                "is_not"
             } else {
-                "is".to_string()
+                "is"
             }
         } else {
-            if self.is_at_end() || self.check(TokenType::And) || self.check(TokenType::Or) || self.check(TokenType::RParen) {
-                return Ok(Box::new(CompareOp {
-                    field,
-                    op: "is".to_string(),
-                    value: Value::Bool(true),
-                }));
+            return None;
+        };
+        Some(op.to_string())
+    }
+
+    fn check_expression_call(&self) -> bool {
+        expression::FUNCTIONS.iter().any(|name| self.check_call(name))
+    }
+
+    /// Parse a whole query string holding only an expression, as given to
+    /// `Database::create_expression_index`
+    pub(crate) fn parse_expression_text(&mut self) -> Result<Expression, String> {
+        if !self.check_expression_call() {
+            return Err(format!("expected one of {} at position {}", expression::FUNCTIONS.join(", "), self.current().pos));
+        }
+        let expr = self.parse_expression()?;
+        if !self.is_at_end() {
+            return Err(format!("unexpected {} at position {}", self.current().token_type, self.current().pos));
+        }
+        Ok(expr)
+    }
+
+    /// Parse a call such as `lower(email)` or `concat(first, ' ', last)`
+    fn parse_expression(&mut self) -> Result<Expression, String> {
+        let name = self.advance().value.clone();
+        self.advance();
+
+        let mut args = Vec::new();
+        while !self.check(TokenType::RParen) {
+            let arg = if self.check_expression_call() {
+                self.parse_expression()?
+            } else if self.check(TokenType::Ident) {
+                Expression::Field(self.parse_field()?)
+            } else {
+                Expression::Literal(expression::integral(self.parse_value()?))
+            };
+            args.push(arg);
+            if !self.match_token(&[TokenType::Comma]) {
+                break;
             }
-            return Err(format!("expected comparison operator at position {}", self.current().pos));
+        }
+        if !self.match_token(&[TokenType::RParen]) {
+            return Err(format!("expected ')' after {} at position {}", name, self.current().pos));
+        }
+
+        let count = |n: &Expression| match n {
+            Expression::Literal(Value::Number(n)) => n.as_f64()
+                .filter(|n| *n >= 0.0 && n.fract() == 0.0)
+                .map(|n| n as usize),
+            _ => None,
         };
+        let wrong_args = || format!("wrong arguments to {}()", name);
+        match (name.as_str(), args.len()) {
+            ("lower", 1) => Ok(Expression::Lower(Box::new(args.remove(0)))),
+            ("upper", 1) => Ok(Expression::Upper(Box::new(args.remove(0)))),
+            ("length", 1) => Ok(Expression::Length(Box::new(args.remove(0)))),
+            ("substr", 2 | 3) => {
+                let start = count(&args[1]).ok_or_else(|| "substr() start must be a non-negative integer".to_string())?;
+                let len = match args.get(2) {
+                    Some(arg) => Some(count(arg).ok_or_else(|| "substr() count must be a non-negative integer".to_string())?),
+                    None => None,
+                };
+                Ok(Expression::Substr(Box::new(args.remove(0)), start, len))
+            }
+            ("concat", n) if n > 0 => Ok(Expression::Concat(args)),
+            _ => Err(wrong_args()),
+        }
+    }
 
-        let value = self.parse_value()?;
+    /// Parse a field name, possibly a dotted path into nested objects
+    fn parse_field(&mut self) -> Result<String, String> {
+        if !self.check(TokenType::Ident) {
+            return Err(format!(
+                "expected field name at position {}, got {:?}",
+                self.current().pos,
+                self.current().token_type
+            ));
+        }
+        let mut field = self.advance().value.clone();
+
+        while self.match_token(&[TokenType::Dot]) {
+            if !self.check(TokenType::Ident) {
+                return Err(format!("expected field name after '.' at position {}", self.current().pos));
+            }
+            field.push('.');
+            field.push_str(&self.advance().value);
+        }
+        Ok(field)
+    }
+
+    /// Parse `near(location, 48.85, 2.35, 10)`
+    fn parse_near(&mut self) -> Result<Box<dyn Node>, String> {
+        self.advance();
+        self.advance();
+
+        let field = self.parse_field()?;
+        let mut args = Vec::with_capacity(3);
+        for name in ["latitude", "longitude", "radius"] {
+            if !self.match_token(&[TokenType::Comma]) {
+                return Err(format!("expected ',' before {} at position {}", name, self.current().pos));
+            }
+            let pos = self.current().pos;
+            match self.parse_value()? {
+                Value::Number(n) => args.push(n.as_f64().unwrap_or(f64::NAN)),
+                _ => return Err(format!("expected {} at position {} to be a number", name, pos)),
+            }
+        }
+        if !self.match_token(&[TokenType::RParen]) {
+            return Err(format!("expected ')' after near at position {}", self.current().pos));
+        }
+
+        let (lat, lng, radius_km) = (args[0], args[1], args[2]);
+        if !geo::is_valid_point(lat, lng) {
+            return Err(format!("invalid point in near(): ({}, {}) is out of range", lat, lng));
+        }
+        if radius_km < 0.0 {
+            return Err(format!("invalid radius in near(): {} is negative", radius_km));
+        }
+
+        Ok(Box::new(NearOp {
+            lat_field: format!("{}.lat", field),
+            lng_field: format!("{}.lng", field),
+            field,
+            lat,
+            lng,
+            radius_km,
+        }))
+    }
+
+    fn check_date_call(&self) -> bool {
+        self.check_call("date")
+    }
+
+    fn check_call(&self, name: &str) -> bool {
+        self.check(TokenType::Ident)
+            && self.current().value == name
+            && self.tokens.get(self.pos + 1).is_some_and(|t| t.token_type == TokenType::LParen)
+    }
+
+    /// Parse `date('2024-01-01')`, `date('2024-01-01T10:00:00+02:00')` or `date(1704067200)`
+    fn parse_date(&mut self) -> Result<i64, String> {
+        self.advance();
+        self.advance();
 
-        Ok(Box::new(CompareOp { field, op, value }))
+        let pos = self.current().pos;
+        let arg = self.parse_value()?;
+        let millis = timestamp_millis(&arg)
+            .ok_or_else(|| format!("invalid date at position {}: expected RFC3339 string or epoch seconds", pos))?;
+
+        if !self.match_token(&[TokenType::RParen]) {
+            return Err(format!("expected ')' after date at position {}", self.current().pos));
+        }
+        Ok(millis)
+    }
+
+    /// Parse `binary('<base64>')` into the binary value it stands for
+    fn parse_binary(&mut self) -> Result<Value, String> {
+        self.advance();
+        self.advance();
+
+        let pos = self.current().pos;
+        let bytes = match self.parse_value()? {
+            Value::String(text) => decode_base64(&text)
+                .map_err(|e| format!("invalid binary at position {}: {}", pos, e))?,
+            _ => return Err(format!("invalid binary at position {}: expected a base64 string", pos)),
+        };
+
+        if !self.match_token(&[TokenType::RParen]) {
+            return Err(format!("expected ')' after binary at position {}", self.current().pos));
+        }
+        Ok(binary_value(&bytes))
     }
 
     fn parse_has(&mut self, field: String) -> Result<Box<dyn Node>, String> {
@@ -264,6 +739,7 @@ impl Parser {
                 field,
                 op: has_op,
                 values: vec![value],
+                collation: Collation::Binary,
             }));
         } else {
             if !self.match_token(&[TokenType::LBracket]) {
@@ -288,6 +764,7 @@ impl Parser {
                 field,
                 op: has_op,
                 values,
+                collation: Collation::Binary,
             }));
         }
     }
@@ -323,9 +800,36 @@ impl Parser {
             return Ok(Value::Null);
         }
 
+        if self.match_token(&[TokenType::Param]) {
+            return self.bind_param();
+        }
+
         Err(format!("expected value at position {}", self.current().pos))
     }
 
+    /// The bound value for the placeholder just consumed. It is used as a
+    /// whole JSON value and never tokenized.
+    fn bind_param(&mut self) -> Result<Value, String> {
+        let token = self.previous();
+        let pos = token.pos;
+        let index = if token.value.is_empty() {
+            self.next_param += 1;
+            self.next_param
+        } else {
+            token.value.parse::<usize>()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or_else(|| format!("invalid placeholder at position {}: numbering starts at ?1", pos))?
+        };
+
+        self.params.get(index - 1).cloned().ok_or_else(|| format!(
+            "no value bound for placeholder ?{} at position {} ({} given)",
+            index,
+            pos,
+            self.params.len()
+        ))
+    }
+
 
     fn current(&self) -> &Token {
         if self.pos >= self.tokens.len() {
@@ -369,19 +873,65 @@ impl Parser {
     }
 }
 
-fn get_field(doc: &serde_json::Map<String, Value>, field: &str) -> Value {
+/// Values a dotted `field` reaches in `doc`. A path running into an array
+/// continues into each object element, so `items.price` yields the price of
+/// every item and one document can give several values. A field that is
+/// missing everywhere gives a single null, like an explicit null.
+pub(crate) fn field_values<'a>(doc: &'a serde_json::Map<String, Value>, field: &str) -> Vec<&'a Value> {
     let parts: Vec<&str> = field.split('.').collect();
-    let mut current = Value::Object(doc.clone());
+    let mut values = Vec::new();
+    if let Some(value) = doc.get(parts[0]) {
+        collect_field_values(value, &parts[1..], &mut values);
+    }
 
-    for part in parts {
-        if let Some(obj) = current.as_object() {
-            current = obj.get(part).cloned().unwrap_or(Value::Null);
-        } else {
-            return Value::Null;
+    if values.is_empty() {
+        values.push(&Value::Null);
+    }
+    values
+}
+
+fn collect_field_values<'a>(value: &'a Value, parts: &[&str], values: &mut Vec<&'a Value>) {
+    let Some((part, rest)) = parts.split_first() else {
+        values.push(value);
+        return;
+    };
+
+    match value {
+        Value::Object(obj) => {
+            if let Some(child) = obj.get(*part) {
+                collect_field_values(child, rest, values);
+            }
+        }
+        Value::Array(items) => {
+            for item in items.iter().filter(|item| item.is_object()) {
+                collect_field_values(item, parts, values);
+            }
         }
+        _ => {}
     }
+}
 
-    current
+/// Values `field` reaches, with each array followed by its elements, so
+/// `tags is "x"` matches a document whose tags contain "x"
+fn comparable_field_values<'a>(doc: &'a serde_json::Map<String, Value>, field: &str) -> impl Iterator<Item = &'a Value> {
+    field_values(doc, field).into_iter().flat_map(|value| {
+        std::iter::once(value).chain(value.as_array().into_iter().flatten())
+    })
+}
+
+fn any_field_value(doc: &serde_json::Map<String, Value>, field: &str, matches: impl Fn(&Value) -> bool) -> bool {
+    comparable_field_values(doc, field).any(matches)
+}
+
+/// Both values as numbers when one is a number and the other a string that
+/// parses as one, ignoring surrounding whitespace
+fn coerce_numeric_pair(a: &Value, b: &Value) -> Option<(f64, f64)> {
+    let numeric_string = |s: &str| s.trim().parse::<f64>().ok().filter(|n| n.is_finite());
+    match (a, b) {
+        (Value::Number(na), Value::String(sb)) => Some((na.as_f64()?, numeric_string(sb)?)),
+        (Value::String(sa), Value::Number(nb)) => Some((numeric_string(sa)?, nb.as_f64()?)),
+        _ => None,
+    }
 }
 
 fn compare_equal(a: &Value, b: &Value) -> bool {
@@ -414,8 +964,14 @@ fn compare_less(a: &Value, b: &Value) -> bool {
 }
 
 pub fn parse_query(query: &str) -> Result<Box<dyn Node>, String> {
+    parse_query_params(query, &[])
+}
+
+/// Parse `query`, binding its `?1`, `?2`, ... placeholders to `params`. A bare
+/// `?` takes the next value in order.
+pub fn parse_query_params(query: &str, params: &[Value]) -> Result<Box<dyn Node>, String> {
     let tokens = super::lexer::tokenize(query)?;
-    let mut parser = Parser::new(tokens);
+    let mut parser = Parser::with_params(tokens, params.to_vec());
     parser.parse()
 }
 
@@ -482,6 +1038,61 @@ mod tests {
         test_eval("email not exists", json!({"name": "Alice"}), true);
     }
 
+    #[test]
+    fn test_date_comparison() {
+        test_eval("createdAt >= date('2024-01-01')", json!({"createdAt": "2024-01-01T00:00:00Z"}), true);
+        test_eval("createdAt >= date('2024-01-01')", json!({"createdAt": "2023-12-31T23:59:59Z"}), false);
+        // Offsets are honoured: this is 2023-12-31T22:00:00Z
+        test_eval("createdAt < date('2024-01-01')", json!({"createdAt": "2024-01-01T00:00:00+02:00"}), true);
+        test_eval("createdAt is date('2024-01-01T01:00:00+01:00')", json!({"createdAt": "2024-01-01T00:00:00Z"}), true);
+        // Epoch seconds on either side
+        test_eval("createdAt > date('2024-01-01')", json!({"createdAt": 1_704_067_201}), true);
+        test_eval("createdAt <= date(1704067200)", json!({"createdAt": "2024-01-01"}), true);
+        // Non-timestamps never match
+        test_eval("createdAt > date('2024-01-01')", json!({"createdAt": "soon"}), false);
+        test_eval("createdAt is not date('2024-01-01')", json!({}), false);
+
+        assert!(parse_query("createdAt > date('yesterday')").is_err());
+        assert!(parse_query("createdAt > date('2024-01-01'").is_err());
+        // A bare `date` is still an identifier value
+        test_eval("kind is date", json!({"kind": "date"}), true);
+    }
+
+    #[test]
+    fn test_binary_equality() {
+        test_eval("thumb is binary('aGk=')", json!({"thumb": {"$binary": "aGk="}}), true);
+        // Padding is optional in the query
+        test_eval("thumb is binary('aGk')", json!({"thumb": {"$binary": "aGk="}}), true);
+        test_eval("thumb is binary('aGk=')", json!({"thumb": {"$binary": "aGU="}}), false);
+        test_eval("thumb is not binary('aGk=')", json!({"thumb": {"$binary": "aGU="}}), true);
+        test_eval("thumb is binary('aGk=')", json!({"thumb": "aGk="}), false);
+
+        assert!(parse_query("thumb > binary('aGk=')").is_err());
+        assert!(parse_query("thumb is binary('a*')").is_err());
+        assert!(parse_query("thumb is binary(5)").is_err());
+    }
+
+    #[test]
+    fn test_near() {
+        let paris = json!({"location": {"lat": 48.8566, "lng": 2.3522}});
+        test_eval("near(location, 51.5074, -0.1278, 350)", paris.clone(), true);
+        test_eval("near(location, 51.5074, -0.1278, 300)", paris.clone(), false);
+        test_eval("near(location, 48.85, 2.35, 1) and not near(location, 48.85, 2.35, 0.1)", paris, true);
+        test_eval("near(location, 0, 0, 20000)", json!({"location": {"lat": "48", "lng": 2}}), false);
+        test_eval("near(location, 0, 0, 20000)", json!({}), false);
+
+        let ast = parse_query("age > 3 and near(home.location, -33.86, 151.2, 5)").unwrap();
+        let near = ast.near().unwrap();
+        assert_eq!(near.point_fields(), ("home.location.lat", "home.location.lng"));
+        assert_eq!((near.lat, near.lng, near.radius_km), (-33.86, 151.2, 5.0));
+        assert!(parse_query("age > 3 or near(location, 1, 1, 5)").unwrap().near().is_none());
+
+        assert!(parse_query("near(location, 91, 0, 5)").is_err());
+        assert!(parse_query("near(location, 0, 0, -5)").is_err());
+        assert!(parse_query("near(location, 0, 0)").is_err());
+        assert!(parse_query("near(location, '1', 0, 5)").is_err());
+    }
+
     #[test]
     fn test_dot_notation() {
         test_eval(
@@ -496,6 +1107,111 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_dot_notation_through_arrays_of_objects() {
+        let doc = json!({"items": [
+            {"name": "pen", "price": 2, "tags": ["office"]},
+            {"name": "lamp", "price": 40, "tags": ["home", "light"]},
+            {"name": "gift card"}
+        ]});
+
+        // A predicate matches when any element's field satisfies it
+        test_eval("items.price > 30", doc.clone(), true);
+        test_eval("items.price > 50", doc.clone(), false);
+        test_eval("items.price is 2", doc.clone(), true);
+        test_eval("items.name is 'lamp' and items.price < 5", doc.clone(), true);
+        test_eval("items.price is not 40", doc.clone(), false);
+        test_eval("items.price is not 41", doc.clone(), true);
+        test_eval("items.tags contains 'light'", doc.clone(), true);
+        test_eval("items.tags contains all ['office', 'home']", doc.clone(), false);
+        test_eval("items.price exists", doc.clone(), true);
+        test_eval("items.weight exists", doc.clone(), false);
+        test_eval("items.weight is null", doc.clone(), true);
+
+        let nested = json!({"orders": [{"lines": [{"sku": "a"}, {"sku": "b"}]}, {"lines": [{"sku": "c"}]}]});
+        test_eval("orders.lines.sku is 'c'", nested.clone(), true);
+        test_eval("orders.lines.sku is 'd'", nested, false);
+    }
+
+    #[test]
+    fn test_comparison_on_arrays_of_scalars() {
+        let doc = json!({"scores": [3, 8, 12], "tags": ["rust", "db"]});
+
+        test_eval("scores > 10", doc.clone(), true);
+        test_eval("scores < 3", doc.clone(), false);
+        test_eval("scores >= 12", doc.clone(), true);
+        test_eval("tags is 'db'", doc.clone(), true);
+        test_eval("tags is not 'db'", doc.clone(), false);
+        test_eval("tags is not 'go'", doc.clone(), true);
+        test_eval("tags.name is 'db'", doc, false);
+    }
+
+    #[test]
+    fn test_expression_comparisons() {
+        let doc = json!({"email": "Ann@Example.com", "tags": ["a", "b"], "length": 4});
+
+        test_eval("lower(email) is 'ann@example.com'", doc.clone(), true);
+        test_eval("lower(email) is not 'ann@example.com'", doc.clone(), false);
+        test_eval("length(tags) >= 2 and length(tags) < 3", doc.clone(), true);
+        test_eval("substr(lower(email), 4) is 'example.com'", doc.clone(), true);
+        test_eval("length > 3", doc.clone(), true);
+        test_eval("upper(missing) is null", doc, true);
+
+        let ast = parse_query("length(tags) is 2 and lower( email ) is 'x' or lower(email) is 'y'").unwrap();
+        let mut found = Vec::new();
+        ast.expression_equalities(&mut found);
+        assert!(found.is_empty(), "an 'or' has no condition every match satisfies");
+
+        let ast = parse_query("length(tags) is 2 and lower( email ) is 'x' and length(tags) > 1").unwrap();
+        let mut found = Vec::new();
+        ast.expression_equalities(&mut found);
+        let found: Vec<String> = found.iter().map(|(expr, value)| format!("{} {}", expr, value)).collect();
+        assert_eq!(found, ["length(tags) 2.0", "lower(email) \"x\""]);
+
+        assert!(parse_query("lower(email)").is_err());
+        assert!(parse_query("lower(email, 2) is 'x'").is_err());
+    }
+
+    #[test]
+    fn test_numeric_string_coercion() {
+        let eval = |query: &str, doc: Value, lax: bool| {
+            let mut ast = parse_query(query).unwrap();
+            if lax {
+                ast.coerce_numeric_strings();
+            }
+            ast.eval(doc.as_object().unwrap())
+        };
+        let doc = json!({"age": "21", "score": 7, "codes": ["3", " 10 "], "name": "abc", "flag": "1"});
+
+        // Strict: a number never matches a string
+        assert!(!eval("age > 18", doc.clone(), false));
+        assert!(!eval("age is 21", doc.clone(), false));
+        assert!(eval("age is not 21", doc.clone(), false));
+
+        assert!(eval("age > 18", doc.clone(), true));
+        assert!(eval("age is 21", doc.clone(), true));
+        assert!(!eval("age is not 21", doc.clone(), true));
+        assert!(eval("score < '10'", doc.clone(), true));
+        assert!(eval("codes >= 10", doc.clone(), true));
+        assert!(eval("not (age < 18) and name is 'abc'", doc.clone(), true));
+
+        // Strings still compare as text, and other types never coerce
+        assert!(!eval("age > '3'", doc.clone(), true));
+        assert!(!eval("name > 1", doc.clone(), true));
+        assert!(!eval("flag is true", doc, true));
+    }
+
+    #[test]
+    fn test_field_values() {
+        let doc = json!({"items": [{"price": 2}, {"price": [3, 4]}, 7, {"other": 1}], "a": {"b": 1}});
+        let doc = doc.as_object().unwrap();
+
+        assert_eq!(field_values(doc, "items.price"), vec![&json!(2), &json!([3, 4])]);
+        assert_eq!(field_values(doc, "a.b"), vec![&json!(1)]);
+        assert_eq!(field_values(doc, "a.c"), vec![&Value::Null]);
+        assert_eq!(field_values(doc, "missing"), vec![&Value::Null]);
+    }
+
     #[test]
     fn test_parentheses() {
         test_eval(
@@ -510,9 +1226,73 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_contains_operator() {
+        test_eval("tags contains 'rust'", json!({"tags": ["rust", "db"]}), true);
+        test_eval("tags contains 'go'", json!({"tags": ["rust", "db"]}), false);
+        test_eval("tags contains 'rust'", json!({"tags": "rust"}), false);
+        test_eval("tags contains any ['go', 'db']", json!({"tags": ["rust", "db"]}), true);
+        test_eval("tags contains all ['go', 'db']", json!({"tags": ["rust", "db"]}), false);
+    }
+
+    #[test]
+    fn test_elem_match_operator() {
+        let doc = json!({"scores": [
+            {"subject": "math", "value": 70},
+            {"subject": "art", "value": 95}
+        ]});
+
+        test_eval("scores elemMatch (value > 80)", doc.clone(), true);
+        test_eval("scores elemMatch (subject is 'art' and value > 80)", doc.clone(), true);
+        // Each condition holds for some element, but no single element satisfies both
+        test_eval("scores elemMatch (subject is 'math' and value > 80)", doc.clone(), false);
+        test_eval("scores elemMatch (subject is 'math' or value > 90)", doc.clone(), true);
+        test_eval("not scores elemMatch (value > 100)", doc, true);
+
+        test_eval("scores elemMatch (value > 1)", json!({"scores": [1, 2]}), false);
+        test_eval("scores elemMatch (value > 1)", json!({"scores": {"value": 2}}), false);
+        test_eval(
+            "user.orders elemMatch (item.sku is 'A1' and qty >= 2)",
+            json!({"user": {"orders": [{"item": {"sku": "A1"}, "qty": 2}]}}),
+            true,
+        );
+
+        assert!(parse_query("scores elemMatch value > 80").is_err());
+        assert!(parse_query("scores elemMatch (value > 80").is_err());
+    }
+
+    #[test]
+    fn test_elem_match_fields() {
+        let ast = parse_query("scores elemMatch (value > 80 and subject is 'math') and age > 3").unwrap();
+        let mut fields = Vec::new();
+        ast.collect_fields(&mut fields);
+        assert_eq!(fields, vec!["scores", "age"]);
+    }
+
     #[test]
     fn test_shorthand_boolean() {
         test_eval("active", json!({"active": true}), true);
         test_eval("active", json!({"active": false}), false);
     }
+
+    #[test]
+    fn test_placeholders() {
+        let doc = json!({"email": "a\" or 1 is 1 or x is \"", "age": 30, "tags": ["x", "y"], "meta": {"k": 1}});
+        let map = doc.as_object().unwrap();
+
+        let ast = parse_query_params("email is ?1 and age > ?2", &[json!("a\" or 1 is 1 or x is \""), json!(18)]).unwrap();
+        assert!(ast.eval(map));
+        let ast = parse_query_params("email is ?1", &[json!("a")]).unwrap();
+        assert!(!ast.eval(map));
+
+        // Bare placeholders bind in order, numbered ones can repeat
+        assert!(parse_query_params("age > ? and age < ?", &[json!(20), json!(40)]).unwrap().eval(map));
+        assert!(parse_query_params("age >= ?1 and age <= ?1", &[json!(30)]).unwrap().eval(map));
+        assert!(parse_query_params("tags has any [?1, ?2]", &[json!("z"), json!("y")]).unwrap().eval(map));
+        assert!(parse_query_params("meta is ?1", &[json!({"k": 1})]).unwrap().eval(map));
+
+        assert!(parse_query_params("age > ?2", &[json!(1)]).is_err());
+        assert!(parse_query_params("age > ?0", &[json!(1)]).is_err());
+        assert!(parse_query("age > ?1").is_err());
+    }
 }
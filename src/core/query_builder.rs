@@ -1,9 +1,16 @@
-use crate::core::collection::Collection;
+use crate::core::collation::Collation;
+use crate::core::collection::{Collection, FindAndModify};
+use crate::core::database::IndexInfo;
 use crate::core::errors::*;
-use crate::core::query::parser::parse_query;
+use crate::core::geo;
+use crate::core::query::parser::Node;
+use crate::core::slow_query::SlowQueryOperation;
 use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SortOrder {
     Asc,
     Desc,
@@ -16,23 +23,106 @@ pub struct QueryBuilder<'a> {
     limit_count: Option<usize>,
     skip_count: usize,
     projection: Option<Projection>,
+    hint: Option<IndexHint>,
+    /// Field and point results are ordered by distance from
+    distance_sort: Option<(String, f64, f64)>,
+    /// `sort_fields` holds the collection's default sort, which the first
+    /// explicit sort replaces
+    default_sort: bool,
+    /// Also match documents in the collection's recycle bin
+    with_deleted: bool,
+    /// Collation strings are filtered and sorted under
+    collation: Collation,
 }
 
-#[derive(Debug, Clone)]
-enum Projection {
-    Include(Vec<String>),
-    Exclude(Vec<String>),
+/// Index choice forced with [`QueryBuilder::hint`] or [`QueryBuilder::hint_none`]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexHint {
+    /// Answer the query from the named index
+    Index(String),
+    /// Scan the collection without using any index
+    NoIndex,
+}
+
+/// How a query located its documents
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessPath {
+    /// Every document in the collection was read and checked against the filter
+    CollectionScan,
+    /// Documents were read in the order of an index and checked against the filter
+    IndexScan,
+    /// Results were built from the keys of an index holding every field the
+    /// query filters, sorts and projects, without reading documents
+    CoveringIndexScan,
+    /// Candidates for a `near()` condition were read from a geo index and
+    /// checked against the filter
+    GeoIndexScan,
+    /// Candidates for an `is` condition on an expression such as
+    /// `lower(email)` were read from an index on that expression and checked
+    /// against the filter
+    ExpressionIndexScan,
+}
+
+/// Execution report returned by [`QueryBuilder::explain`]
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct QueryExplain {
+    pub collection: String,
+    pub filter: Option<String>,
+    pub access_path: AccessPath,
+    /// Index the access path read, if any
+    pub index: Option<String>,
+    /// Index choice forced by the caller, which the access path always honors
+    pub hint: Option<IndexHint>,
+    /// Fields referenced by the filter
+    pub filter_fields: Vec<String>,
+    /// Existing indexes whose leading field appears in the filter
+    pub candidate_indexes: Vec<String>,
+    /// Documents the access path was expected to read
+    pub estimated_docs_examined: usize,
+    /// Index entries read
+    pub keys_examined: usize,
+    /// Documents read from their pages
+    pub docs_examined: usize,
+    pub docs_returned: usize,
+    /// Whether results were sorted after being read rather than coming out of an index in order
+    pub in_memory_sort: bool,
+    pub elapsed_micros: u64,
+}
+
+/// How [`QueryBuilder::run`] answered a query
+struct RunStats {
+    access_path: AccessPath,
+    index: Option<String>,
+    keys_examined: usize,
+    docs_examined: usize,
+    in_memory_sort: bool,
+}
+
+/// Fields kept in and dropped from results, set by [`QueryBuilder::project`]
+/// and [`QueryBuilder::exclude`]
+#[derive(Debug, Clone, Default)]
+struct Projection {
+    include: Vec<String>,
+    exclude: Vec<String>,
 }
 
 impl<'a> QueryBuilder<'a> {
     pub(crate) fn new(collection: &'a Collection) -> Self {
+        let sort_fields = collection.default_sort();
         Self {
             collection,
             query: None,
-            sort_fields: Vec::new(),
+            default_sort: !sort_fields.is_empty(),
+            sort_fields,
             limit_count: None,
             skip_count: 0,
             projection: None,
+            hint: None,
+            distance_sort: None,
+            with_deleted: false,
+            collation: Collation::Binary,
         }
     }
 
@@ -42,12 +132,60 @@ impl<'a> QueryBuilder<'a> {
         self
     }
 
-    /// Sort results by a field in the specified order
+    /// Sort results by a field in the specified order. Values of mixed types
+    /// follow [`compare_values`](crate::core::index_key::compare_values):
+    /// null < bool < number < string < array < object.
+    ///
+    /// Replaces the collection's default sort, if it has one.
     pub fn sort_by(mut self, field: &str, order: SortOrder) -> Self {
+        self.clear_default_sort();
         self.sort_fields.push((field.to_string(), order));
         self
     }
 
+    /// Order results by distance from a point, closest first, using the
+    /// point at `field.lat` and `field.lng` like `near()`. Documents without a
+    /// valid point come last. Fields given to [`sort_by`](Self::sort_by)
+    /// break ties.
+    pub fn sort_by_distance(mut self, field: &str, lat: f64, lng: f64) -> Self {
+        self.clear_default_sort();
+        self.distance_sort = Some((field.to_string(), lat, lng));
+        self
+    }
+
+    /// Compare strings under `collation` when filtering and sorting, so with
+    /// [`Collation::CaseInsensitive`] `name is 'alice'` matches `"Alice"` and
+    /// `"ALICE"`, and a sort on `name` puts them next to each other instead
+    /// of every uppercase name first.
+    ///
+    /// Only indexes created with the same collation (see
+    /// [`IndexOptions::collation`](crate::IndexOptions::collation)) can
+    /// answer the sort; otherwise results are sorted in memory.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use jasonisnthappy::{Collation, Database, SortOrder};
+    /// # let db = Database::open("my.db").unwrap();
+    /// let users = db.collection("users");
+    /// let alices = users.query()
+    ///     .filter("name is 'alice'")
+    ///     .sort_by("name", SortOrder::Asc)
+    ///     .collation(Collation::CaseInsensitive)
+    ///     .execute()
+    ///     .unwrap();
+    /// ```
+    pub fn collation(mut self, collation: Collation) -> Self {
+        self.collation = collation;
+        self
+    }
+
+    fn clear_default_sort(&mut self) {
+        if self.default_sort {
+            self.sort_fields.clear();
+            self.default_sort = false;
+        }
+    }
+
     /// Limit the number of results returned
     pub fn limit(mut self, n: usize) -> Self {
         self.limit_count = Some(n);
@@ -60,72 +198,347 @@ impl<'a> QueryBuilder<'a> {
         self
     }
 
-    /// Project only the specified fields (inclusion projection)
-    /// _id is always included unless explicitly excluded
+    /// Keep only the specified fields, and `_id` unless it is excluded.
+    ///
+    /// Dotted paths keep a nested field, and reach into every object in an
+    /// array, so `items.sku` keeps the `sku` of each element of `items`.
+    /// Including a field and a path inside it keeps the whole field. Nested
+    /// objects and arrays left with none of the fields are dropped, and
+    /// `project(&["_id"])` keeps nothing but `_id`.
+    ///
+    /// Combined with [`exclude`](Self::exclude), only `_id` and paths inside
+    /// an included field may be excluded: including `address` and excluding
+    /// `address.zip` keeps the address without its zip code. Excluding
+    /// anything else, such as an included field or one of its parents, makes
+    /// the query fail.
     pub fn project(mut self, fields: &[&str]) -> Self {
-        self.projection = Some(Projection::Include(
-            fields.iter().map(|s| s.to_string()).collect()
-        ));
+        self.projection.get_or_insert_with(Projection::default).include =
+            fields.iter().map(|s| s.to_string()).collect();
         self
     }
 
-    /// Exclude the specified fields from results (exclusion projection)
+    /// Drop the specified fields from results, keeping everything else.
+    /// Dotted paths drop nested fields, inside every object of an array
+    /// along the way. See [`project`](Self::project) for combining the two.
     pub fn exclude(mut self, fields: &[&str]) -> Self {
-        self.projection = Some(Projection::Exclude(
-            fields.iter().map(|s| s.to_string()).collect()
-        ));
+        self.projection.get_or_insert_with(Projection::default).exclude =
+            fields.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    /// Answer the query from the named index instead of letting the planner
+    /// choose. The query fails if the index doesn't exist, is a text index, or
+    /// doesn't index the first field it is filtered or sorted on.
+    pub fn hint(mut self, index_name: &str) -> Self {
+        self.hint = Some(IndexHint::Index(index_name.to_string()));
+        self
+    }
+
+    /// Always scan the whole collection, ignoring any index
+    pub fn hint_none(mut self) -> Self {
+        self.hint = Some(IndexHint::NoIndex);
+        self
+    }
+
+    /// Include documents soft deleted from the collection (see
+    /// [`Database::set_soft_delete`](crate::Database::set_soft_delete)),
+    /// which carry the `_deleted_at` time they were deleted at. Without a
+    /// sort, results are in `_id` order.
+    pub fn with_deleted(mut self) -> Self {
+        self.with_deleted = true;
         self
     }
 
     /// Execute the query and return results
     pub fn execute(self) -> Result<Vec<Value>> {
-        // Step 1: Get all documents (filtered if query specified)
-        let mut results = if let Some(q) = &self.query {
-            let ast = parse_query(q)
-                .map_err(|e| Error::Other(format!("failed to parse query: {}", e)))?;
-
-            let all_docs = match self.collection.find_all() {
-                Ok(docs) => docs,
-                Err(Error::Other(msg)) if msg.contains("not found") => Vec::new(),
-                Err(e) => return Err(e),
-            };
-            all_docs
-                .into_iter()
-                .filter(|doc| {
-                    if let Some(doc_map) = doc.as_object() {
-                        ast.eval(doc_map)
-                    } else {
-                        false
-                    }
-                })
-                .collect()
-        } else {
-            match self.collection.find_all() {
-                Ok(docs) => docs,
-                Err(Error::Other(msg)) if msg.contains("not found") => Vec::new(),
-                Err(e) => return Err(e),
+        self.run().map(|(results, _)| results)
+    }
+
+    /// Execute the query and report how it was answered: the access path,
+    /// how many documents were examined and returned, whether results were
+    /// sorted in memory, and how long it took.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # let db = Database::open("my.db").unwrap();
+    /// let users = db.collection("users");
+    /// let explain = users.query().filter("age > 30").explain().unwrap();
+    /// println!("{} examined, {} returned", explain.docs_examined, explain.docs_returned);
+    /// ```
+    pub fn explain(self) -> Result<QueryExplain> {
+        let started = Instant::now();
+
+        let mut filter_fields = Vec::new();
+        if let Some(q) = &self.query {
+            let ast = self.collection.parse_filter(q)?;
+            ast.collect_fields(&mut filter_fields);
+
+            let mut seen = HashSet::new();
+            filter_fields.retain(|field| seen.insert(field.clone()));
+        }
+
+        let collection = self.collection.name();
+        let candidate_indexes = self.indexes()?
+            .into_iter()
+            .filter(|idx| idx.fields.first().is_some_and(|field| filter_fields.contains(field)))
+            .map(|idx| idx.name)
+            .collect();
+
+        let (results, stats) = self.run()?;
+        let estimated_docs_examined = match stats.access_path {
+            AccessPath::CollectionScan | AccessPath::IndexScan => self.collection.estimated_count()?,
+            AccessPath::CoveringIndexScan => 0,
+            AccessPath::GeoIndexScan | AccessPath::ExpressionIndexScan => stats.docs_examined,
+        };
+
+        Ok(QueryExplain {
+            collection: collection.to_string(),
+            filter: self.query.clone(),
+            access_path: stats.access_path,
+            index: stats.index,
+            hint: self.hint.clone(),
+            filter_fields,
+            candidate_indexes,
+            estimated_docs_examined,
+            keys_examined: stats.keys_examined,
+            docs_examined: stats.docs_examined,
+            docs_returned: results.len(),
+            in_memory_sort: stats.in_memory_sort,
+            elapsed_micros: started.elapsed().as_micros() as u64,
+        })
+    }
+
+    /// Indexes on the collection, none if it doesn't exist yet
+    fn indexes(&self) -> Result<Vec<IndexInfo>> {
+        match self.collection.database().list_indexes(self.collection.name()) {
+            Ok(indexes) => Ok(indexes),
+            Err(Error::CollectionDoesNotExist { .. }) => Ok(Vec::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Every field the query filters, sorts and projects, when results can be
+    /// built from index keys alone. Only inclusion projections qualify, since
+    /// anything else returns whole documents.
+    fn covered_fields(&self, ast: Option<&dyn Node>) -> Option<Vec<String>> {
+        let mut needed = match &self.projection {
+            Some(projection) if !projection.include.is_empty() && projection.exclude.is_empty() => {
+                projection.include.clone()
             }
+            _ => return None,
+        };
+        if let Some(ast) = ast {
+            ast.collect_fields(&mut needed);
+        }
+        needed.extend(self.sort_fields.iter().map(|(field, _)| field.clone()));
+        needed.retain(|field| field != "_id");
+        Some(needed)
+    }
+
+    /// Pick an index holding every field the query filters, sorts and projects,
+    /// so results can be built from its keys. Indexes already in the requested
+    /// sort order are preferred, then the smallest. Sparse indexes are skipped
+    /// since they leave documents out.
+    fn covering_index(&self, ast: Option<&dyn Node>) -> Result<Option<IndexInfo>> {
+        let needed = match self.covered_fields(ast) {
+            Some(needed) => needed,
+            None => return Ok(None),
         };
 
-        // Step 2: Apply sorting
-        if !self.sort_fields.is_empty() {
-            results.sort_by(|a, b| {
-                for (field, order) in &self.sort_fields {
-                    let val_a = get_nested_field(a, field);
-                    let val_b = get_nested_field(b, field);
-
-                    let cmp = compare_values(&val_a, &val_b);
-                    let cmp = match order {
-                        SortOrder::Asc => cmp,
-                        SortOrder::Desc => cmp.reverse(),
-                    };
-
-                    if cmp != std::cmp::Ordering::Equal {
-                        return cmp;
+        Ok(self.indexes()?
+            .into_iter()
+            .filter(|idx| idx.text_options.is_none() && !idx.sparse && !idx.expression && idx.collation.is_binary())
+            .filter(|idx| needed.iter().all(|field| idx.fields.contains(field)))
+            .min_by_key(|idx| (!self.sorted_by_index(idx), idx.fields.len(), idx.name.clone())))
+    }
+
+    /// Look up the index named by [`QueryBuilder::hint`], returning it and
+    /// whether it covers the query
+    fn hinted_index(&self, index_name: &str, ast: Option<&dyn Node>) -> Result<(IndexInfo, bool)> {
+        let collection = self.collection.name();
+        let index = self.indexes()?
+            .into_iter()
+            .find(|idx| idx.name == index_name)
+            .ok_or_else(|| Error::Other(format!(
+                "hinted index {} not found on collection {}", index_name, collection
+            )))?;
+
+        if index.text_options.is_some() {
+            return Err(Error::Other(format!(
+                "hinted index {} is a text index and can't answer queries", index_name
+            )));
+        }
+
+        if index.geo {
+            return Err(Error::Other(format!(
+                "hinted index {} is a geo index and only answers near() conditions", index_name
+            )));
+        }
+
+        if index.sparse {
+            return Err(Error::Other(format!(
+                "hinted index {} is sparse and doesn't hold every document", index_name
+            )));
+        }
+
+        if index.expression {
+            return Err(Error::Other(format!(
+                "hinted index {} is an expression index and only answers 'is' conditions on {}",
+                index_name, index.fields[0]
+            )));
+        }
+
+        let mut used = Vec::new();
+        if let Some(ast) = ast {
+            ast.collect_fields(&mut used);
+        }
+        used.extend(self.sort_fields.iter().map(|(field, _)| field.clone()));
+        if !index.fields.first().is_some_and(|field| used.contains(field)) {
+            return Err(Error::Other(format!(
+                "hinted index {} can't satisfy the query: it isn't filtered or sorted on {}",
+                index_name, index.fields[0]
+            )));
+        }
+
+        let covered = index.collation.is_binary() && self.covered_fields(ast)
+            .is_some_and(|needed| needed.iter().all(|field| index.fields.contains(field)));
+        Ok((index, covered))
+    }
+
+    /// `query` parsed, with its comparisons under the query's collation
+    fn filter_ast(&self, query: &str) -> Result<Box<dyn Node>> {
+        let mut ast = self.collection.parse_filter(query)?;
+        if !self.collation.is_binary() {
+            ast.set_collation(self.collation);
+        }
+        Ok(ast)
+    }
+
+    /// Whether entries coming out of `index` are already in the requested sort order
+    fn sorted_by_index(&self, index: &IndexInfo) -> bool {
+        index_sorts_by(index, &self.sort_fields, self.collation)
+    }
+
+    /// Run the query, returning the results and how they were found
+    fn run(&self) -> Result<(Vec<Value>, RunStats)> {
+        let start = Instant::now();
+        if let Some(projection) = &self.projection {
+            projection.check()?;
+        }
+        let ast = match &self.query {
+            Some(q) => Some(self.filter_ast(q)?),
+            None => None,
+        };
+
+        // Step 1: Get all documents, from the hinted index or a covering index
+        // when one can answer the query
+        let planned = match &self.hint {
+            Some(IndexHint::NoIndex) => None,
+            Some(IndexHint::Index(name)) => Some(self.hinted_index(name, ast.as_deref())?),
+            None => match self.covering_index(ast.as_deref())? {
+                Some(index) => Some((index, true)),
+                None => index_for_sort(self.indexes()?, &self.sort_fields, self.collation)
+                    .map(|index| (index, false)),
+            },
+        };
+
+        let scanned = match planned {
+            Some((index, covered)) => match self.collection.index_scan(&index.name, covered)? {
+                Some(scan) => Some((index, covered, scan)),
+                None if self.hint.is_some() => {
+                    return Err(Error::Other(format!(
+                        "hinted index {} is out of date with collection {}",
+                        index.name, self.collection.name()
+                    )));
+                }
+                None => None,
+            },
+            None => None,
+        };
+
+        let (mut results, mut stats) = match scanned {
+            Some((index, covered, scan)) => {
+                let stats = RunStats {
+                    access_path: if covered { AccessPath::CoveringIndexScan } else { AccessPath::IndexScan },
+                    in_memory_sort: !self.sorted_by_index(&index),
+                    index: Some(index.name),
+                    keys_examined: scan.keys_examined,
+                    docs_examined: scan.docs_examined,
+                };
+                let docs = match &ast {
+                    Some(ast) => scan.docs
+                        .into_iter()
+                        .filter(|doc| doc.as_object().is_some_and(|doc_map| ast.eval(doc_map)))
+                        .collect(),
+                    None => scan.docs,
+                };
+                (docs, stats)
+            }
+            None => {
+                let indexed = match (&ast, &self.hint) {
+                    (Some(ast), None) => match self.collection.geo_scan(ast.as_ref())? {
+                        Some((index, scan)) => Some((AccessPath::GeoIndexScan, index, scan)),
+                        None => self.collection.expression_scan(ast.as_ref(), self.collation)?
+                            .map(|(index, scan)| (AccessPath::ExpressionIndexScan, index, scan)),
+                    },
+                    _ => None,
+                };
+                match indexed {
+                    Some((access_path, index, scan)) => {
+                        let stats = RunStats {
+                            access_path,
+                            index: Some(index),
+                            keys_examined: scan.keys_examined,
+                            docs_examined: scan.docs_examined,
+                            in_memory_sort: !self.sort_fields.is_empty(),
+                        };
+                        (scan.docs, stats)
+                    }
+                    None => {
+                        // The collection scan filters documents as it reads them
+                        let (docs, examined) = match self.collection.find_matching(ast.as_deref()) {
+                            Ok(scan) => scan,
+                            Err(Error::CollectionDoesNotExist { .. }) => (Vec::new(), 0),
+                            Err(e) => return Err(e),
+                        };
+                        let stats = RunStats {
+                            access_path: AccessPath::CollectionScan,
+                            index: None,
+                            keys_examined: 0,
+                            docs_examined: examined,
+                            in_memory_sort: !self.sort_fields.is_empty(),
+                        };
+                        (docs, stats)
                     }
                 }
-                std::cmp::Ordering::Equal
-            });
+            }
+        };
+
+        if self.with_deleted {
+            let (deleted, examined) = match self.collection.recycle_bin().find_matching(ast.as_deref()) {
+                Ok(scan) => scan,
+                Err(Error::CollectionDoesNotExist { .. }) => (Vec::new(), 0),
+                Err(e) => return Err(e),
+            };
+            stats.docs_examined += examined;
+            if !deleted.is_empty() {
+                results.extend(deleted);
+                if self.sort_fields.is_empty() {
+                    sort_documents(&mut results, &[("_id".to_string(), SortOrder::Asc)], Collation::Binary);
+                } else {
+                    stats.in_memory_sort = true;
+                }
+            }
+        }
+
+        // Step 2: Apply sorting
+        if stats.in_memory_sort {
+            sort_documents(&mut results, &self.sort_fields, self.collation);
+        }
+        if let Some((field, lat, lng)) = &self.distance_sort {
+            stats.in_memory_sort = true;
+            sort_by_distance(&mut results, field, *lat, *lng);
         }
 
         // Step 3: Apply skip
@@ -142,13 +555,19 @@ impl<'a> QueryBuilder<'a> {
         let results = if let Some(projection) = &self.projection {
             results
                 .into_iter()
-                .map(|doc| apply_projection(doc, projection))
+                .map(|doc| projection.apply(doc))
                 .collect()
         } else {
             results
         };
 
-        Ok(results)
+        self.collection.record_slow_query(
+            SlowQueryOperation::Find,
+            self.query.as_deref().unwrap_or(""),
+            start,
+            stats.docs_examined,
+        );
+        Ok((results, stats))
     }
 
     /// Execute and return the first result
@@ -159,36 +578,79 @@ impl<'a> QueryBuilder<'a> {
         Ok(results.into_iter().next())
     }
 
+    /// Atomically update the first matching document and return it, as it was
+    /// before the update or, with `return_new`, as it is after. Matches are
+    /// ordered by the sort fields and skip is applied, so the sort decides which
+    /// document is first; limit is ignored. Returns `None` if nothing matches.
+    ///
+    /// The document is picked and updated in one transaction that is retried
+    /// if another writer changes it first, so concurrent callers never claim
+    /// the same document.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use jasonisnthappy::{Database, SortOrder};
+    /// # use serde_json::json;
+    /// # let db = Database::open("my.db").unwrap();
+    /// let jobs = db.collection("jobs");
+    /// let job = jobs.query()
+    ///     .filter("status is \"queued\"")
+    ///     .sort_by("priority", SortOrder::Desc)
+    ///     .find_one_and_update(json!({"status": "running"}), true)
+    ///     .unwrap();
+    /// ```
+    pub fn find_one_and_update(self, updates: Value, return_new: bool) -> Result<Option<Value>> {
+        let change = FindAndModify::Update { updates, return_new };
+        self.find_one_and_modify(&change)
+    }
+
+    /// Atomically delete the first matching document and return it, picking
+    /// the document the same way as [`find_one_and_update`](Self::find_one_and_update).
+    pub fn find_one_and_delete(self) -> Result<Option<Value>> {
+        self.find_one_and_modify(&FindAndModify::Delete)
+    }
+
+    fn find_one_and_modify(&self, change: &FindAndModify) -> Result<Option<Value>> {
+        if let Some(projection) = &self.projection {
+            projection.check()?;
+        }
+        let doc = self.collection.find_one_and_modify(
+            self.query.as_deref(),
+            &self.sort_fields,
+            self.skip_count,
+            self.collation,
+            change,
+        )?;
+
+        Ok(match &self.projection {
+            Some(projection) => doc.map(|doc| projection.apply(doc)),
+            None => doc,
+        })
+    }
+
     /// Count results without fetching them all
     pub fn count(self) -> Result<usize> {
         // For count, we don't need to sort or apply limit
-        let results = if let Some(q) = &self.query {
-            let ast = parse_query(q)
-                .map_err(|e| Error::Other(format!("failed to parse query: {}", e)))?;
-
-            let all_docs = match self.collection.find_all() {
-                Ok(docs) => docs,
-                Err(Error::Other(msg)) if msg.contains("not found") => Vec::new(),
-                Err(e) => return Err(e),
+        let ast = match &self.query {
+            Some(q) => Some(self.filter_ast(q)?),
+            None => None,
+        };
+        let count_in = |collection: &Collection| {
+            let counted = match &ast {
+                Some(ast) => collection.find_matching(Some(ast.as_ref())).map(|(docs, _)| docs.len()),
+                None => collection.count(),
             };
-            all_docs
-                .into_iter()
-                .filter(|doc| {
-                    if let Some(doc_map) = doc.as_object() {
-                        ast.eval(doc_map)
-                    } else {
-                        false
-                    }
-                })
-                .count()
-        } else {
-            match self.collection.count() {
-                Ok(count) => count,
-                Err(Error::Other(msg)) if msg.contains("not found") => 0,
-                Err(e) => return Err(e),
+            match counted {
+                Err(Error::CollectionDoesNotExist { .. }) => Ok(0),
+                counted => counted,
             }
         };
 
+        let mut results = count_in(self.collection)?;
+        if self.with_deleted {
+            results += count_in(&self.collection.recycle_bin())?;
+        }
+
         // Apply skip to count
         if self.skip_count >= results {
             Ok(0)
@@ -200,6 +662,70 @@ impl<'a> QueryBuilder<'a> {
     }
 }
 
+/// Whether entries coming out of `index` are in `sort_fields` order under `collation`
+fn index_sorts_by(index: &IndexInfo, sort_fields: &[(String, SortOrder)], collation: Collation) -> bool {
+    let index_order = if index.descending { SortOrder::Desc } else { SortOrder::Asc };
+    index.collation == collation
+        && sort_fields.len() <= index.fields.len()
+        && sort_fields
+            .iter()
+            .zip(&index.fields)
+            .all(|((field, order), index_field)| field == index_field && *order == index_order)
+}
+
+/// An index whose entries come out in `sort_fields` order under `collation`
+/// and hold every document, so reading it replaces sorting in memory. The
+/// smallest one wins.
+pub(crate) fn index_for_sort(indexes: Vec<IndexInfo>, sort_fields: &[(String, SortOrder)], collation: Collation) -> Option<IndexInfo> {
+    if sort_fields.is_empty() {
+        return None;
+    }
+    indexes.into_iter()
+        .filter(|idx| idx.text_options.is_none() && !idx.geo && !idx.sparse && !idx.expression)
+        .filter(|idx| index_sorts_by(idx, sort_fields, collation))
+        .min_by_key(|idx| (idx.fields.len(), idx.name.clone()))
+}
+
+/// Sort documents in place by each of `sort_fields` in turn, comparing
+/// strings under `collation`
+pub(crate) fn sort_documents(docs: &mut [Value], sort_fields: &[(String, SortOrder)], collation: Collation) {
+    docs.sort_by(|a, b| {
+        for (field, order) in sort_fields {
+            let val_a = get_nested_field(a, field);
+            let val_b = get_nested_field(b, field);
+
+            let cmp = collation.compare(&val_a, &val_b);
+            let cmp = match order {
+                SortOrder::Asc => cmp,
+                SortOrder::Desc => cmp.reverse(),
+            };
+
+            if cmp != std::cmp::Ordering::Equal {
+                return cmp;
+            }
+        }
+        std::cmp::Ordering::Equal
+    });
+}
+
+/// Stable sort of `docs` by the distance of the point at `field.lat` and
+/// `field.lng` from the given point, documents without one last
+fn sort_by_distance(docs: &mut Vec<Value>, field: &str, lat: f64, lng: f64) {
+    let (lat_field, lng_field) = (format!("{}.lat", field), format!("{}.lng", field));
+    let distance = |doc: &Value| {
+        doc.as_object()
+            .and_then(|doc_map| geo::point_at(doc_map, &lat_field, &lng_field))
+            .map(|(doc_lat, doc_lng)| geo::haversine_km(lat, lng, doc_lat, doc_lng))
+            .unwrap_or(f64::INFINITY)
+    };
+    let mut keyed: Vec<(f64, Value)> = std::mem::take(docs)
+        .into_iter()
+        .map(|doc| (distance(&doc), doc))
+        .collect();
+    keyed.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+    *docs = keyed.into_iter().map(|(_, doc)| doc).collect();
+}
+
 /// Extract a potentially nested field from a Value
 fn get_nested_field(value: &Value, field: &str) -> Value {
     let parts: Vec<&str> = field.split('.').collect();
@@ -216,136 +742,135 @@ fn get_nested_field(value: &Value, field: &str) -> Value {
     current
 }
 
-/// Compare two JSON values for sorting
-fn compare_values(a: &Value, b: &Value) -> std::cmp::Ordering {
-    use std::cmp::Ordering;
-
-    match (a, b) {
-        // Null values sort first
-        (Value::Null, Value::Null) => Ordering::Equal,
-        (Value::Null, _) => Ordering::Less,
-        (_, Value::Null) => Ordering::Greater,
-
-        // Numbers
-        (Value::Number(na), Value::Number(nb)) => {
-            let fa = na.as_f64().unwrap_or(0.0);
-            let fb = nb.as_f64().unwrap_or(0.0);
-            fa.partial_cmp(&fb).unwrap_or(Ordering::Equal)
+impl Projection {
+    /// Fail when an exclusion conflicts with the included fields
+    fn check(&self) -> Result<()> {
+        if self.include.is_empty() {
+            return Ok(());
         }
-
-        // Strings
-        (Value::String(sa), Value::String(sb)) => sa.cmp(sb),
-
-        // Booleans
-        (Value::Bool(ba), Value::Bool(bb)) => ba.cmp(bb),
-
-        // Arrays - compare lexicographically
-        (Value::Array(aa), Value::Array(ab)) => {
-            for (item_a, item_b) in aa.iter().zip(ab.iter()) {
-                let cmp = compare_values(item_a, item_b);
-                if cmp != Ordering::Equal {
-                    return cmp;
-                }
+        for excluded in &self.exclude {
+            let inside_included = self.include.iter().any(|included| {
+                excluded.strip_prefix(included.as_str()).is_some_and(|rest| rest.starts_with('.'))
+            });
+            if excluded != "_id" && !inside_included {
+                return Err(Error::Other(format!(
+                    "can't exclude '{}' from a projection that includes fields: only _id and \
+                     fields inside an included one can be excluded",
+                    excluded
+                )));
             }
-            aa.len().cmp(&ab.len())
         }
+        Ok(())
+    }
 
-        // Objects - not really comparable, compare as JSON strings
-        (Value::Object(_), Value::Object(_)) => {
-            let sa = serde_json::to_string(a).unwrap_or_default();
-            let sb = serde_json::to_string(b).unwrap_or_default();
-            sa.cmp(&sb)
-        }
+    fn apply(&self, doc: Value) -> Value {
+        let Value::Object(obj) = doc else {
+            return doc;
+        };
 
-        // Different types - order by type precedence
-        _ => {
-            let type_order = |v: &Value| match v {
-                Value::Null => 0,
-                Value::Bool(_) => 1,
-                Value::Number(_) => 2,
-                Value::String(_) => 3,
-                Value::Array(_) => 4,
-                Value::Object(_) => 5,
-            };
-            type_order(a).cmp(&type_order(b))
+        let mut result = if self.include.is_empty() {
+            obj
+        } else {
+            let mut tree = PathTree::default();
+            for field in &self.include {
+                tree.insert(field);
+            }
+            let mut result = serde_json::Map::new();
+            if let Some(id) = obj.get("_id") {
+                result.insert("_id".to_string(), id.clone());
+            }
+            result.extend(include_fields(&obj, &tree.children));
+            result
+        };
+
+        for field in &self.exclude {
+            let parts: Vec<&str> = field.split('.').collect();
+            remove_path(&mut result, &parts);
         }
+        Value::Object(result)
     }
 }
 
-/// Apply projection to a document
-fn apply_projection(doc: Value, projection: &Projection) -> Value {
-    if let Value::Object(obj) = doc {
-        match projection {
-            Projection::Include(fields) => {
-                let mut result = serde_json::Map::new();
-
-                // Always include _id unless explicitly excluded
-                if let Some(id) = obj.get("_id") {
-                    result.insert("_id".to_string(), id.clone());
-                }
-
-                // Include specified fields
-                for field in fields {
-                    if field == "_id" {
-                        // Already handled above
-                        continue;
-                    }
-
-                    if field.contains('.') {
-                        // Handle nested fields
-                        if let Some(value) = get_nested_field_from_map(&obj, field) {
-                            set_nested_field(&mut result, field, value);
-                        }
-                    } else {
-                        // Simple field
-                        if let Some(value) = obj.get(field) {
-                            result.insert(field.clone(), value.clone());
-                        }
-                    }
-                }
-
-                Value::Object(result)
-            }
-            Projection::Exclude(fields) => {
-                let mut result = obj.clone();
-
-                // Remove excluded fields
-                for field in fields {
-                    if field.contains('.') {
-                        // Handle nested fields
-                        remove_nested_field(&mut result, field);
-                    } else {
-                        // Simple field
-                        result.remove(field);
-                    }
-                }
+/// Included paths, merged so a field included whole swallows paths inside it
+#[derive(Default)]
+struct PathTree {
+    children: HashMap<String, PathTree>,
+    /// Keep the whole value here, whatever paths continue below
+    whole: bool,
+}
 
-                Value::Object(result)
+impl PathTree {
+    fn insert(&mut self, path: &str) {
+        let mut node = self;
+        for part in path.split('.') {
+            if node.whole {
+                return;
             }
+            node = node.children.entry(part.to_string()).or_default();
         }
-    } else {
-        doc
+        node.whole = true;
+        node.children.clear();
     }
 }
 
-/// Get a nested field value from a map
-fn get_nested_field_from_map(obj: &serde_json::Map<String, Value>, path: &str) -> Option<Value> {
-    let parts: Vec<&str> = path.split('.').collect();
-    let mut current = Value::Object(obj.clone());
-
-    for part in parts {
-        if let Some(obj) = current.as_object() {
-            current = obj.get(part)?.clone();
+/// The fields of `obj` named in `tree`, in document order
+fn include_fields(obj: &serde_json::Map<String, Value>, tree: &HashMap<String, PathTree>) -> serde_json::Map<String, Value> {
+    let mut result = serde_json::Map::new();
+    for (key, value) in obj {
+        let Some(node) = tree.get(key) else { continue };
+        let kept = if node.whole {
+            Some(value.clone())
         } else {
-            return None;
+            include_nested(value, &node.children)
+        };
+        if let Some(kept) = kept {
+            result.insert(key.clone(), kept);
         }
     }
+    result
+}
 
-    Some(current)
+/// `value` cut down to the paths in `tree`, looking into every object of an
+/// array; `None` when nothing is left
+fn include_nested(value: &Value, tree: &HashMap<String, PathTree>) -> Option<Value> {
+    match value {
+        Value::Object(obj) => {
+            let kept = include_fields(obj, tree);
+            (!kept.is_empty()).then_some(Value::Object(kept))
+        }
+        Value::Array(items) => {
+            let kept: Vec<Value> = items.iter()
+                .filter_map(|item| include_nested(item, tree))
+                .collect();
+            (!kept.is_empty()).then_some(Value::Array(kept))
+        }
+        _ => None,
+    }
+}
+
+/// Remove the field at `parts`, from every object of an array along the way
+fn remove_path(obj: &mut serde_json::Map<String, Value>, parts: &[&str]) {
+    match parts {
+        [] => {}
+        [last] => {
+            obj.remove(*last);
+        }
+        [first, rest @ ..] => match obj.get_mut(*first) {
+            Some(Value::Object(nested)) => remove_path(nested, rest),
+            Some(Value::Array(items)) => {
+                for item in items {
+                    if let Value::Object(nested) = item {
+                        remove_path(nested, rest);
+                    }
+                }
+            }
+            _ => {}
+        },
+    }
 }
 
 /// Set a nested field in a map
-fn set_nested_field(obj: &mut serde_json::Map<String, Value>, path: &str, value: Value) {
+pub(crate) fn set_nested_field(obj: &mut serde_json::Map<String, Value>, path: &str, value: Value) {
     let parts: Vec<&str> = path.split('.').collect();
     set_nested_field_recursive(obj, &parts, value);
 }
@@ -371,28 +896,6 @@ fn set_nested_field_recursive(obj: &mut serde_json::Map<String, Value>, parts: &
     }
 }
 
-/// Remove a nested field from a map
-fn remove_nested_field(obj: &mut serde_json::Map<String, Value>, path: &str) {
-    let parts: Vec<&str> = path.split('.').collect();
-    remove_nested_field_recursive(obj, &parts);
-}
-
-fn remove_nested_field_recursive(obj: &mut serde_json::Map<String, Value>, parts: &[&str]) {
-    if parts.is_empty() {
-        return;
-    }
-
-    if parts.len() == 1 {
-        obj.remove(parts[0]);
-        return;
-    }
-
-    // Recurse into the nested object
-    if let Some(Value::Object(nested)) = obj.get_mut(parts[0]) {
-        remove_nested_field_recursive(nested, &parts[1..]);
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
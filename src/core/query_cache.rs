@@ -0,0 +1,161 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use serde_json::Value;
+
+use crate::core::errors::PoisonedLockExt;
+
+/// Results of recent queries, keyed by collection and query, dropped least
+/// recently used first once `capacity` entries are held
+///
+/// Every commit that writes to a collection moves that collection to a new
+/// generation. Entries remember the generation they were computed in and
+/// are only returned while it is still current, so no result survives a
+/// write to its collection.
+pub(crate) struct QueryCache {
+    capacity: usize,
+    inner: Mutex<QueryCacheInner>,
+}
+
+#[derive(Default)]
+struct QueryCacheInner {
+    entries: HashMap<(String, String), Entry>,
+    /// Keys by when they were last used, oldest first
+    recency: BTreeMap<u64, (String, String)>,
+    generations: HashMap<String, u64>,
+    clock: u64,
+}
+
+struct Entry {
+    generation: u64,
+    last_used: u64,
+    results: Vec<Value>,
+}
+
+impl QueryCache {
+    /// A cache of `capacity` results; 0 disables it
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self { capacity, inner: Mutex::new(QueryCacheInner::default()) }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// The collection's current generation, taken before running a query so
+    /// results computed across a commit are stored as already stale
+    pub(crate) fn generation(&self, collection: &str) -> u64 {
+        let inner = self.inner.lock().recover_poison();
+        inner.generations.get(collection).copied().unwrap_or(0)
+    }
+
+    pub(crate) fn get(&self, collection: &str, query: &str) -> Option<Vec<Value>> {
+        let mut inner = self.inner.lock().recover_poison();
+        let generation = inner.generations.get(collection).copied().unwrap_or(0);
+        let key = (collection.to_string(), query.to_string());
+        let last_used = match inner.entries.get(&key) {
+            Some(entry) if entry.generation == generation => entry.last_used,
+            Some(_) => {
+                inner.remove(&key);
+                return None;
+            }
+            None => return None,
+        };
+
+        inner.clock += 1;
+        let now = inner.clock;
+        inner.recency.remove(&last_used);
+        inner.recency.insert(now, key.clone());
+        let entry = inner.entries.get_mut(&key)?;
+        entry.last_used = now;
+        Some(entry.results.clone())
+    }
+
+    /// Store `results`, computed as of `generation`
+    pub(crate) fn put(&self, collection: &str, query: &str, generation: u64, results: &[Value]) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut inner = self.inner.lock().recover_poison();
+        if inner.generations.get(collection).copied().unwrap_or(0) != generation {
+            return;
+        }
+
+        let key = (collection.to_string(), query.to_string());
+        inner.remove(&key);
+        while inner.entries.len() >= self.capacity {
+            let Some((_, oldest)) = inner.recency.pop_first() else { break };
+            inner.entries.remove(&oldest);
+        }
+
+        inner.clock += 1;
+        let now = inner.clock;
+        inner.recency.insert(now, key.clone());
+        inner.entries.insert(key, Entry { generation, last_used: now, results: results.to_vec() });
+    }
+
+    /// Forget every result for `collection`, after a write to it
+    pub(crate) fn invalidate(&self, collection: &str) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut inner = self.inner.lock().recover_poison();
+        *inner.generations.entry(collection.to_string()).or_insert(0) += 1;
+        let stale: Vec<(String, String)> = inner.entries.keys()
+            .filter(|(name, _)| name == collection)
+            .cloned()
+            .collect();
+        for key in stale {
+            inner.remove(&key);
+        }
+    }
+}
+
+impl QueryCacheInner {
+    fn remove(&mut self, key: &(String, String)) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.recency.remove(&entry.last_used);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_least_recently_used_results_are_dropped() {
+        let cache = QueryCache::new(2);
+        cache.put("a", "q1", 0, &[json!(1)]);
+        cache.put("a", "q2", 0, &[json!(2)]);
+        assert_eq!(cache.get("a", "q1"), Some(vec![json!(1)]));
+
+        cache.put("b", "q3", 0, &[json!(3)]);
+        assert_eq!(cache.get("a", "q2"), None);
+        assert_eq!(cache.get("a", "q1"), Some(vec![json!(1)]));
+        assert_eq!(cache.get("b", "q3"), Some(vec![json!(3)]));
+    }
+
+    #[test]
+    fn test_writes_invalidate_their_collection() {
+        let cache = QueryCache::new(10);
+        cache.put("a", "q", 0, &[json!(1)]);
+        cache.put("b", "q", 0, &[json!(2)]);
+
+        let generation = cache.generation("a");
+        cache.invalidate("a");
+        assert_eq!(cache.get("a", "q"), None);
+        assert_eq!(cache.get("b", "q"), Some(vec![json!(2)]));
+
+        // Results computed before the write aren't stored
+        cache.put("a", "q", generation, &[json!(1)]);
+        assert_eq!(cache.get("a", "q"), None);
+        cache.put("a", "q", cache.generation("a"), &[json!(3)]);
+        assert_eq!(cache.get("a", "q"), Some(vec![json!(3)]));
+
+        let disabled = QueryCache::new(0);
+        disabled.put("a", "q", 0, &[json!(1)]);
+        assert_eq!(disabled.get("a", "q"), None);
+    }
+}
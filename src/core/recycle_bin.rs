@@ -0,0 +1,54 @@
+//! Recycle bins for collections with soft delete turned on.
+//!
+//! Deleting from such a collection moves the document into the collection's
+//! bin, a hidden collection of its own, stamped with `_deleted_at` in
+//! milliseconds since the Unix epoch. Queries, counts and indexes of the
+//! collection only ever see live documents, since deleted ones aren't in it
+//! anymore. [`Collection::restore`](crate::Collection::restore) moves a
+//! document back and
+//! [`Collection::purge_deleted`](crate::Collection::purge_deleted) empties
+//! the bin for good.
+
+use crate::core::collection::now_millis;
+use crate::core::database::Database;
+use crate::core::errors::*;
+use crate::core::transaction::Transaction;
+use crate::core::tx_collection::TxCollection;
+use serde_json::{Map, Value};
+use std::sync::Arc;
+
+/// Field holding when a document in a recycle bin was deleted
+pub(crate) const DELETED_AT: &str = "_deleted_at";
+
+/// Collection names can't contain `#`, so bins never clash with a collection
+const BIN_SUFFIX: &str = "#deleted";
+
+/// Name of the hidden collection holding `collection`'s deleted documents
+pub(crate) fn bin_name(collection: &str) -> String {
+    format!("{}{}", collection, BIN_SUFFIX)
+}
+
+/// Whether `name` is a recycle bin rather than a collection of its own
+pub(crate) fn is_bin(name: &str) -> bool {
+    name.ends_with(BIN_SUFFIX)
+}
+
+/// Put `doc`, just deleted from `collection` in `tx`, into the collection's
+/// bin, replacing an earlier deleted document with the same `_id`
+pub(crate) fn recycle(
+    tx: &mut Transaction,
+    db: &Arc<Database>,
+    collection: &str,
+    id: &str,
+    mut doc: Map<String, Value>,
+) -> Result<()> {
+    doc.insert(DELETED_AT.to_string(), Value::from(now_millis()));
+
+    let mut bin = TxCollection::new(tx, db.clone(), bin_name(collection))?;
+    match bin.delete_by_id(id) {
+        Ok(()) | Err(Error::DocumentNotFound { .. }) => {}
+        Err(e) => return Err(e),
+    }
+    bin.insert_stored(id.to_string(), doc)?;
+    Ok(())
+}
@@ -0,0 +1,242 @@
+//! Last-resort recovery of databases whose metadata is lost.
+//!
+//! Nothing but the metadata ties a B-tree to its collection, so
+//! [`Database::repair`](crate::Database::repair) finds the collections by
+//! scanning every page: pages that decode as B-tree nodes are linked into
+//! trees, and the trees whose leaves point at documents stored under their
+//! keys are collections. Copy-on-write leaves the older versions of every
+//! tree in the file; since a collection's new root is never allocated below
+//! its current one, trees are visited from the highest root down and a tree
+//! sharing a node, or most of its documents, with the trees visited before
+//! it is an older version.
+
+use crate::core::binary::decode_document;
+use crate::core::btree::{deserialize_node, BTreeNode, NodeType};
+use crate::core::constants::*;
+use crate::core::document::read_versioned_document;
+use crate::core::errors::*;
+use crate::core::integrity::IntegrityProblem;
+use crate::core::metadata::page_chain;
+use crate::core::pager::Pager;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+
+/// Result of [`Database::repair`](crate::Database::repair)
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RepairReport {
+    /// Pages in the damaged file, including the header page
+    pub pages_scanned: u64,
+    /// Pages that decoded as B-tree nodes
+    pub btree_nodes: u64,
+    /// Collections rebuilt from the documents found, by name
+    pub collections: Vec<RecoveredCollection>,
+    /// Documents copied into the repaired database
+    pub documents_recovered: u64,
+    /// Older versions of the recovered collections that were left out
+    pub superseded_trees: u64,
+    /// B-trees holding no documents: secondary and text indexes, and empty
+    /// collections. Neither can be told apart from the pages alone, so
+    /// recreate indexes after a repair.
+    pub skipped_trees: u64,
+    /// Pages that couldn't be read and documents of the recovered
+    /// collections that couldn't be decoded
+    pub unrecoverable: Vec<IntegrityProblem>,
+}
+
+/// A collection found by [`Database::repair`](crate::Database::repair)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RecoveredCollection {
+    /// Name the collection was given, since the original one is lost
+    pub name: String,
+    /// Root page of the B-tree the documents were found through
+    pub btree_root: PageNum,
+    /// Documents recovered
+    pub documents: u64,
+}
+
+/// Documents of one recovered collection, to be copied into the repaired file
+pub(crate) struct RecoveredTree {
+    pub name: String,
+    pub doc_pages: Vec<PageNum>,
+}
+
+/// A B-tree reachable from a root no other node points to
+struct Tree {
+    root: PageNum,
+    nodes: Vec<PageNum>,
+    /// Leaf entries pointing at a document stored under their key
+    documents: Vec<PageNum>,
+    /// Leaf entries that don't, as (key, page)
+    bad_entries: Vec<(String, PageNum)>,
+    /// Children that aren't readable B-tree nodes
+    missing_nodes: Vec<PageNum>,
+}
+
+/// Scan `pager`'s pages for collections. The metadata pages and pages on the
+/// free list are ignored.
+pub(crate) fn scan(pager: &Pager) -> Result<(RepairReport, Vec<RecoveredTree>)> {
+    let num_pages = pager.num_pages()?;
+    let free: HashSet<PageNum> = pager.free_pages()?.into_iter().collect();
+    let metadata_page = pager.metadata_page()?;
+    // The metadata chain may be what's damaged, so pages past a break in it
+    // are scanned like any other
+    let metadata_pages: HashSet<PageNum> = page_chain(pager, metadata_page)
+        .unwrap_or_else(|_| vec![metadata_page])
+        .into_iter()
+        .collect();
+
+    let mut report = RepairReport { pages_scanned: num_pages, ..Default::default() };
+    let mut nodes = HashMap::new();
+    for page in 1..num_pages {
+        if metadata_pages.contains(&page) || free.contains(&page) {
+            continue;
+        }
+        match pager.read_page(page) {
+            Ok(data) => {
+                if let Some(node) = plausible_node(page, &data, num_pages) {
+                    nodes.insert(page, node);
+                }
+            }
+            Err(e) => report.unrecoverable.push(IntegrityProblem {
+                collection: None,
+                page: Some(page),
+                message: format!("page can't be read: {}", e),
+            }),
+        }
+    }
+    report.btree_nodes = nodes.len() as u64;
+
+    let children: HashSet<PageNum> = nodes.values().flat_map(|node| node.children.iter().copied()).collect();
+    let mut roots: Vec<PageNum> = nodes.keys().copied().filter(|page| !children.contains(page)).collect();
+    roots.sort_unstable_by(|a, b| b.cmp(a));
+
+    let mut documents = DocumentPages { pager, free: &free, ids: HashMap::new() };
+    let mut claimed: HashSet<PageNum> = HashSet::new();
+    let mut taken = Vec::new();
+    for root in roots {
+        let tree = walk_tree(&nodes, root, &mut documents);
+        if tree.documents.is_empty() {
+            report.skipped_trees += 1;
+            continue;
+        }
+
+        let shares_node = tree.nodes.iter().any(|page| claimed.contains(page));
+        let shared_documents = tree.documents.iter().filter(|page| claimed.contains(page)).count();
+        // An older version's pages are claimed too, so the version before it
+        // is matched against everything the collection has held since
+        claimed.extend(tree.nodes.iter().chain(&tree.documents).copied());
+        if shares_node || shared_documents * 2 > tree.documents.len() {
+            report.superseded_trees += 1;
+            continue;
+        }
+        taken.push(tree);
+    }
+
+    // Lowest roots first, roughly the order the collections were created in
+    taken.sort_by_key(|tree| tree.root);
+    let mut recovered = Vec::with_capacity(taken.len());
+    for (i, tree) in taken.into_iter().enumerate() {
+        let name = format!("recovered_{}", i + 1);
+        for page in tree.missing_nodes {
+            report.unrecoverable.push(IntegrityProblem {
+                collection: Some(name.clone()),
+                page: Some(page),
+                message: "B-tree node can't be read; the documents below it are lost".to_string(),
+            });
+        }
+        for (key, page) in tree.bad_entries {
+            report.unrecoverable.push(IntegrityProblem {
+                collection: Some(name.clone()),
+                page: Some(page),
+                message: format!("document '{}' can't be read", key),
+            });
+        }
+
+        report.documents_recovered += tree.documents.len() as u64;
+        report.collections.push(RecoveredCollection {
+            name: name.clone(),
+            btree_root: tree.root,
+            documents: tree.documents.len() as u64,
+        });
+        recovered.push(RecoveredTree { name, doc_pages: tree.documents });
+    }
+
+    Ok((report, recovered))
+}
+
+/// Decode `data` as a B-tree node, rejecting pages that only happen to parse
+/// as one
+fn plausible_node(page: PageNum, data: &[u8], num_pages: u64) -> Option<BTreeNode> {
+    let node = deserialize_node(page, data).ok()?;
+    if node.node_type == NodeType::LeafNode {
+        let ordered = node.entries.windows(2).all(|pair| pair[0].key < pair[1].key);
+        return (ordered && node.num_keys as usize == node.entries.len()).then_some(node);
+    }
+
+    let shaped = !node.keys.is_empty()
+        && node.num_keys as usize == node.keys.len()
+        && node.children.len() == node.keys.len() + 1;
+    let ordered = node.keys.windows(2).all(|pair| pair[0] < pair[1]);
+    let in_file = node.children.iter().all(|&child| child != 0 && child != page && child < num_pages);
+    (shaped && ordered && in_file).then_some(node)
+}
+
+/// Walk the tree under `root`, sorting its leaf entries into documents and
+/// entries that point at something else
+fn walk_tree(nodes: &HashMap<PageNum, BTreeNode>, root: PageNum, documents: &mut DocumentPages<'_>) -> Tree {
+    let mut tree = Tree {
+        root,
+        nodes: Vec::new(),
+        documents: Vec::new(),
+        bad_entries: Vec::new(),
+        missing_nodes: Vec::new(),
+    };
+    let mut visited = HashSet::new();
+    let mut stack = vec![root];
+    while let Some(page) = stack.pop() {
+        if !visited.insert(page) {
+            continue;
+        }
+        let Some(node) = nodes.get(&page) else {
+            tree.missing_nodes.push(page);
+            continue;
+        };
+        tree.nodes.push(page);
+
+        if node.node_type == NodeType::InternalNode {
+            stack.extend(node.children.iter().rev());
+            continue;
+        }
+        for entry in &node.entries {
+            if documents.id(entry.value) == Some(entry.key.as_str()) {
+                tree.documents.push(entry.value);
+            } else {
+                tree.bad_entries.push((entry.key.clone(), entry.value));
+            }
+        }
+    }
+    tree
+}
+
+/// Ids of the pages holding a readable document, looked up once each since
+/// the versions of a tree share most of their documents
+struct DocumentPages<'a> {
+    pager: &'a Pager,
+    free: &'a HashSet<PageNum>,
+    ids: HashMap<PageNum, Option<String>>,
+}
+
+impl DocumentPages<'_> {
+    fn id(&mut self, page: PageNum) -> Option<&str> {
+        let (pager, free) = (self.pager, self.free);
+        self.ids.entry(page).or_insert_with(|| {
+            if page == 0 || free.contains(&page) || page >= pager.num_pages().ok()? {
+                return None;
+            }
+            let vdoc = read_versioned_document(pager, page, &HashMap::new()).ok()?;
+            decode_document::<Map<String, Value>>(&vdoc.data).ok()?;
+            Some(vdoc.id)
+        }).as_deref()
+    }
+}
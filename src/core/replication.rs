@@ -0,0 +1,206 @@
+//! Logical replication through a change log.
+//!
+//! Once [`Database::enable_replication_log`](crate::Database::enable_replication_log)
+//! is called, every commit appends one [`ReplicationRecord`] per document
+//! it wrote to a hidden collection, numbered by a sequence that counts up in
+//! commit order. The records are appended under the commit lock, like index
+//! entries, so a commit's records are in the log as soon as its writes are
+//! visible and the log never skips a sequence.
+//! [`Database::replication_stream`](crate::Database::replication_stream)
+//! reads the log from a sequence on and
+//! [`Database::apply_replication`](crate::Database::apply_replication)
+//! replays a record on another database.
+//!
+//! Records carry whole documents rather than the updates that produced them,
+//! so replaying one is idempotent: a follower that restarts may replay the
+//! records after the last sequence it saved without harm. Only documents are
+//! replicated; indexes, schemas and other collection settings are not.
+
+use crate::core::binary::encode_document;
+use crate::core::btree::BTree;
+use crate::core::collection::Collection;
+use crate::core::database::Database;
+use crate::core::document::write_versioned_document;
+use crate::core::errors::*;
+use crate::core::pager::Pager;
+use crate::core::watch::ChangeOperation;
+use crate::core::constants::TransactionID;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+/// Collection names can't contain `#`, so the log never clashes with one
+pub(crate) const LOG_COLLECTION: &str = "#replication";
+
+/// Records a stream reads from the log at a time
+const STREAM_BATCH: usize = 256;
+
+/// Whether `name` is the change log rather than a collection of its own
+pub(crate) fn is_log(name: &str) -> bool {
+    name == LOG_COLLECTION
+}
+
+/// `_id` of the record with `sequence` in the log, padded so ids sort in
+/// sequence order
+pub(crate) fn sequence_key(sequence: u64) -> String {
+    format!("{:020}", sequence)
+}
+
+/// One committed document write, as read from the change log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplicationRecord {
+    /// Position in the log. Records of one transaction have consecutive
+    /// sequences.
+    pub sequence: u64,
+    /// Collection the document was written to
+    pub collection: String,
+    pub operation: ChangeOperation,
+    pub doc_id: String,
+    /// The document as written, `None` for deletes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document: Option<Value>,
+}
+
+/// A document write of a committing transaction, to be appended to the log
+pub(crate) struct LoggedWrite {
+    pub collection: String,
+    pub operation: ChangeOperation,
+    pub doc_id: String,
+    pub document: Option<Value>,
+}
+
+/// Append `writes`, made by transaction `tx_id`, to the change log if the
+/// database keeps one. Called under the commit lock, before the metadata is
+/// written, so the new root and sequence are persisted with the commit.
+pub(crate) fn append(db: &Database, pager: &Arc<Pager>, tx_id: TransactionID, writes: Vec<LoggedWrite>) -> Result<()> {
+    if writes.is_empty() {
+        return Ok(());
+    }
+    let (root, mut sequence) = match db.replication_log_state() {
+        Some(state) => state,
+        None => return Ok(()),
+    };
+
+    let btree = if root == 0 {
+        BTree::new(pager.clone())?
+    } else {
+        BTree::open(pager.clone(), root)
+    };
+
+    let appended = writes.len() as u64;
+    for write in writes {
+        sequence += 1;
+        let key = sequence_key(sequence);
+        let record = ReplicationRecord {
+            sequence,
+            collection: write.collection,
+            operation: write.operation,
+            doc_id: write.doc_id,
+            document: write.document,
+        };
+        let mut doc = match serde_json::to_value(record)? {
+            Value::Object(doc) => doc,
+            _ => unreachable!("records serialize to objects"),
+        };
+        doc.insert("_id".to_string(), Value::String(key.clone()));
+        let data = encode_document(&doc, LOG_COLLECTION)?;
+
+        let mut pages = HashMap::new();
+        let (page_num, _) = write_versioned_document(pager, &key, &data, tx_id, 0, &mut pages)?;
+        for (page, page_data) in pages {
+            pager.write_page_transfer(page, page_data)?;
+        }
+        btree.insert(&key, page_num)?;
+    }
+
+    let root = btree.root_page();
+    db.update_metadata_no_flush(|m| {
+        let log = m.get_collection(LOG_COLLECTION);
+        log.btree_root = root;
+        log.last_auto_id = sequence;
+        log.document_count = log.document_count.map(|count| count + appended);
+    });
+    Ok(())
+}
+
+/// Records of the change log from a sequence on, see
+/// [`Database::replication_stream`](crate::Database::replication_stream)
+///
+/// The stream ends once it has read every record committed so far. Records
+/// are read a batch at a time, each batch from the latest commit, so records
+/// committed while the stream is read show up in it as well. If records the
+/// stream hasn't reached yet are trimmed from the log, it fails with
+/// `Error::ReplicationLogTrimmed`.
+pub struct ReplicationStream {
+    log: Collection,
+    /// Sequence of the next record, 0 before reading the oldest one kept
+    next: u64,
+    buffer: VecDeque<ReplicationRecord>,
+    done: bool,
+}
+
+impl ReplicationStream {
+    pub(crate) fn new(log: Collection, from_sequence: u64) -> Self {
+        Self {
+            log,
+            next: from_sequence,
+            buffer: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    /// Sequence of the next record the stream returns, still 0 if it was
+    /// started at 0 and hasn't returned a record. Pass it to
+    /// `replication_stream` to carry on where this stream stopped.
+    pub fn next_sequence(&self) -> u64 {
+        self.next
+    }
+
+    fn fetch(&mut self) -> Result<()> {
+        let docs = self.log.scan_from(&sequence_key(self.next), STREAM_BATCH)?;
+        self.done = docs.len() < STREAM_BATCH;
+
+        let mut expected = self.next;
+        for doc in docs {
+            let record: ReplicationRecord = serde_json::from_value(doc)?;
+            if expected == 0 {
+                expected = record.sequence;
+            }
+            if record.sequence != expected {
+                return Err(Error::ReplicationLogTrimmed { requested: expected, oldest: record.sequence });
+            }
+            expected += 1;
+            self.buffer.push_back(record);
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for ReplicationStream {
+    type Item = Result<ReplicationRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buffer.is_empty() && !self.done {
+            if let Err(e) = self.fetch() {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+
+        let record = self.buffer.pop_front()?;
+        self.next = record.sequence + 1;
+        Some(Ok(record))
+    }
+}
+
+/// `record`'s document as the map to store on a follower
+pub(crate) fn replicated_document(record: &ReplicationRecord) -> Result<Map<String, Value>> {
+    match &record.document {
+        Some(Value::Object(doc)) => Ok(doc.clone()),
+        _ => Err(Error::InvalidDocumentFormat {
+            reason: format!("replication record {} has no document to {}", record.sequence, record.operation.as_str()),
+            collection: Some(record.collection.clone()),
+        }),
+    }
+}
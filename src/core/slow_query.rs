@@ -0,0 +1,149 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::core::errors::PoisonedLockExt;
+
+/// Kind of operation recorded in the slow query log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SlowQueryOperation {
+    Find,
+    Aggregate,
+    Update,
+    Delete,
+}
+
+impl SlowQueryOperation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SlowQueryOperation::Find => "find",
+            SlowQueryOperation::Aggregate => "aggregate",
+            SlowQueryOperation::Update => "update",
+            SlowQueryOperation::Delete => "delete",
+        }
+    }
+}
+
+/// A query that took longer than `DatabaseOptions::slow_query_threshold_ms`
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct SlowQuery {
+    pub operation: SlowQueryOperation,
+    pub collection: String,
+    /// The query string, empty for queries over the whole collection. For
+    /// aggregations, the pipeline's stages.
+    pub query: String,
+    pub elapsed_ms: f64,
+    /// Documents read to answer the query
+    pub docs_examined: usize,
+    /// When the query finished, in milliseconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+type SlowQueryCallback = Arc<dyn Fn(&SlowQuery) + Send + Sync>;
+
+/// The most recent slow queries, oldest first, dropping the oldest once full
+pub(crate) struct SlowQueryLog {
+    threshold_ms: AtomicU64,
+    capacity: usize,
+    entries: Mutex<VecDeque<SlowQuery>>,
+    callback: RwLock<Option<SlowQueryCallback>>,
+}
+
+impl SlowQueryLog {
+    pub(crate) fn new(threshold_ms: u64, capacity: usize) -> Self {
+        Self {
+            threshold_ms: AtomicU64::new(threshold_ms),
+            capacity,
+            entries: Mutex::new(VecDeque::with_capacity(capacity.min(1024))),
+            callback: RwLock::new(None),
+        }
+    }
+
+    pub(crate) fn threshold_ms(&self) -> u64 {
+        self.threshold_ms.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_threshold_ms(&self, threshold_ms: u64) {
+        self.threshold_ms.store(threshold_ms, Ordering::Relaxed);
+    }
+
+    pub(crate) fn set_callback(&self, callback: Option<SlowQueryCallback>) {
+        *self.callback.write().recover_poison() = callback;
+    }
+
+    /// Log the query started at `start` if it ran past the threshold
+    pub(crate) fn record(
+        &self,
+        operation: SlowQueryOperation,
+        collection: &str,
+        query: impl FnOnce() -> String,
+        start: Instant,
+        docs_examined: usize,
+    ) {
+        let threshold_ms = self.threshold_ms();
+        let elapsed = start.elapsed();
+        if threshold_ms == 0 || elapsed.as_millis() < threshold_ms as u128 {
+            return;
+        }
+
+        let entry = SlowQuery {
+            operation,
+            collection: collection.to_string(),
+            query: query(),
+            elapsed_ms: elapsed.as_secs_f64() * 1000.0,
+            docs_examined,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+        };
+
+        // Called outside the lock, so a callback may read the log itself
+        let callback = self.callback.read().recover_poison().clone();
+        if let Some(callback) = callback {
+            callback(&entry);
+        }
+
+        if self.capacity > 0 {
+            let mut entries = self.entries.lock().recover_poison();
+            if entries.len() == self.capacity {
+                entries.pop_front();
+            }
+            entries.push_back(entry);
+        }
+    }
+
+    pub(crate) fn entries(&self) -> Vec<SlowQuery> {
+        self.entries.lock().recover_poison().iter().cloned().collect()
+    }
+
+    pub(crate) fn clear(&self) {
+        self.entries.lock().recover_poison().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_slow_query_log_is_bounded() {
+        let log = SlowQueryLog::new(1, 2);
+        let start = Instant::now() - Duration::from_millis(5);
+        for query in ["a", "b", "c"] {
+            log.record(SlowQueryOperation::Find, "users", || query.to_string(), start, 1);
+        }
+        let queries: Vec<String> = log.entries().into_iter().map(|q| q.query).collect();
+        assert_eq!(queries, vec!["b", "c"]);
+
+        // Fast queries and a zero threshold record nothing
+        log.clear();
+        log.record(SlowQueryOperation::Find, "users", || "fast".to_string(), Instant::now(), 1);
+        log.set_threshold_ms(0);
+        log.record(SlowQueryOperation::Find, "users", || "off".to_string(), start, 1);
+        assert!(log.entries().is_empty());
+    }
+}
@@ -0,0 +1,70 @@
+use crate::core::collection::Collection;
+use crate::core::constants::TransactionID;
+use crate::core::database::Database;
+use crate::core::metadata::Metadata;
+use crate::core::recycle_bin;
+use crate::core::replication;
+use crate::core::transaction::Transaction;
+use std::sync::Arc;
+
+/// What a snapshot's collections read through: a read-only transaction that
+/// pins the snapshot, and the metadata as of the same commit
+pub(crate) struct SnapshotView {
+    pub(crate) tx: Transaction,
+    pub(crate) metadata: Metadata,
+}
+
+/// A read-only view of the database frozen at one commit, from
+/// [`Database::snapshot`]
+///
+/// Every read through [`collection`](Self::collection) sees the same data, so
+/// a report built from several queries and aggregations is consistent even
+/// while other threads commit. Writers are never blocked. The snapshot stays
+/// pinned, holding back garbage collection of the versions it can see, until
+/// it and every collection taken from it are dropped.
+///
+/// # Examples
+/// ```no_run
+/// # use jasonisnthappy::Database;
+/// # let db = Database::open("my.db").unwrap();
+/// let snapshot = db.snapshot().unwrap();
+/// let orders = snapshot.collection("orders");
+/// let total = orders.count().unwrap();
+/// let open = orders.find("status is 'open'").unwrap();
+/// assert!(open.len() <= total);
+/// ```
+#[derive(Clone)]
+pub struct Snapshot {
+    db: Database,
+    view: Arc<SnapshotView>,
+}
+
+impl Snapshot {
+    pub(crate) fn new(db: Database, tx: Transaction, metadata: Metadata) -> Self {
+        Self {
+            db,
+            view: Arc::new(SnapshotView { tx, metadata }),
+        }
+    }
+
+    /// A handle on `name` whose reads all see this snapshot. Inserts, updates
+    /// and deletes through it fail with `Error::ReadOnlyTransaction`.
+    pub fn collection(&self, name: &str) -> Collection {
+        self.db.collection(name).with_snapshot(self.view.clone())
+    }
+
+    /// Names of the collections that existed when the snapshot was taken
+    pub fn list_collections(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.view.metadata.collections.keys()
+            .filter(|name| !recycle_bin::is_bin(name) && !replication::is_log(name))
+            .cloned()
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// ID of the last transaction whose writes the snapshot sees
+    pub fn snapshot_id(&self) -> TransactionID {
+        self.view.tx.snapshot_id
+    }
+}
@@ -0,0 +1,268 @@
+use crate::core::btree::{deserialize_node, NodeType};
+use crate::core::constants::*;
+use crate::core::document::versioned_document_pages;
+use crate::core::errors::*;
+use crate::core::metadata::{page_chain, CollectionMeta, Metadata};
+use crate::core::pager::Pager;
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Result of [`Database::space_stats`](crate::Database::space_stats)
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SpaceStats {
+    /// Size in bytes of every page in the file
+    pub page_size: usize,
+    /// Pages in the database file, including the header page
+    pub total_pages: u64,
+    /// Pages reachable from the metadata, plus old versions held for snapshots
+    pub pages_in_use: u64,
+    /// Pages on the free list, reused by later writes before the file grows
+    pub free_pages: u64,
+    /// Continuation pages of documents too large for a single page
+    pub overflow_pages: u64,
+    /// Pages of superseded document versions that `garbage_collect` frees
+    /// once no open transaction or snapshot can still see them
+    pub old_version_pages: u64,
+    /// Pages neither in use nor on the free list, left behind by
+    /// copy-on-write until `vacuum` reclaims them
+    pub orphaned_pages: u64,
+    /// Per-collection usage, sorted by name
+    pub collections: Vec<CollectionSpace>,
+}
+
+impl SpaceStats {
+    /// Pages `garbage_collect` followed by `vacuum` could give back
+    pub fn reclaimable_pages(&self) -> u64 {
+        self.free_pages + self.orphaned_pages + self.old_version_pages
+    }
+
+    /// Share of the file that is reclaimable, between 0.0 and 1.0
+    pub fn reclaimable_ratio(&self) -> f64 {
+        if self.total_pages == 0 {
+            return 0.0;
+        }
+        self.reclaimable_pages() as f64 / self.total_pages as f64
+    }
+}
+
+/// Pages used by one collection in [`SpaceStats`]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CollectionSpace {
+    pub name: String,
+    /// All pages below, together
+    pub pages: u64,
+    /// Document B-tree nodes
+    pub tree_pages: u64,
+    /// First pages of the current document versions
+    pub document_pages: u64,
+    /// Continuation pages of large documents
+    pub overflow_pages: u64,
+    /// Secondary and text index nodes, and text index posting lists
+    pub index_pages: u64,
+}
+
+/// Count the pages `metadata` references. `version_pages` are first pages of
+/// superseded document versions still held for open snapshots. Pages that
+/// can't be read are counted but not followed; `check_integrity` reports them.
+pub(crate) fn measure(pager: &Pager, metadata: &Metadata, version_pages: &[PageNum]) -> Result<SpaceStats> {
+    let num_pages = pager.num_pages()?;
+    let mut counter = Counter {
+        pager,
+        num_pages,
+        seen: HashSet::from([0]),
+    };
+
+    for page in page_chain(pager, pager.metadata_page()?)? {
+        counter.claim(page);
+    }
+
+    let mut stats = SpaceStats {
+        page_size: pager.file_page_size(),
+        total_pages: num_pages,
+        ..Default::default()
+    };
+
+    let mut names: Vec<&String> = metadata.collections.keys().collect();
+    names.sort();
+    for name in names {
+        let collection = counter.measure_collection(name, &metadata.collections[name]);
+        stats.overflow_pages += collection.overflow_pages;
+        stats.collections.push(collection);
+    }
+
+    for &page in version_pages {
+        let (first, overflow) = counter.document(page);
+        stats.old_version_pages += first + overflow;
+        stats.overflow_pages += overflow;
+    }
+
+    let free: HashSet<PageNum> = pager.free_pages()?
+        .into_iter()
+        .filter(|page| *page < num_pages && !counter.seen.contains(page))
+        .collect();
+
+    stats.pages_in_use = counter.seen.len() as u64;
+    stats.free_pages = free.len() as u64;
+    stats.orphaned_pages = num_pages.saturating_sub(stats.pages_in_use + stats.free_pages);
+    Ok(stats)
+}
+
+/// Size of one index in [`IndexInfo`](crate::IndexInfo)
+pub(crate) struct IndexSize {
+    /// B-tree nodes, plus posting list pages for a text index
+    pub pages: u64,
+    /// Leaf entries
+    pub entries: u64,
+}
+
+/// Measure the index whose B-tree is rooted at `root`. A text index's
+/// entries point at posting list pages, which are counted too.
+pub(crate) fn index_size(pager: &Pager, root: PageNum, text: bool) -> Result<IndexSize> {
+    let mut counter = Counter {
+        pager,
+        num_pages: pager.num_pages()?,
+        seen: HashSet::from([0]),
+    };
+    let (mut pages, entries) = counter.tree(root);
+    let entry_count = entries.len() as u64;
+    if text {
+        pages += entries.into_iter().filter(|&page| counter.claim(page)).count() as u64;
+    }
+    Ok(IndexSize { pages, entries: entry_count })
+}
+
+/// Every page of the index whose B-tree is rooted at `root`: its nodes and,
+/// for a text index, the posting list pages its entries point at
+pub(crate) fn index_pages(pager: &Pager, root: PageNum, text: bool) -> Result<Vec<PageNum>> {
+    let mut counter = Counter {
+        pager,
+        num_pages: pager.num_pages()?,
+        seen: HashSet::from([0]),
+    };
+    let (_, entries) = counter.tree(root);
+    if text {
+        for page in entries {
+            counter.claim(page);
+        }
+    }
+    counter.seen.remove(&0);
+    Ok(counter.seen.into_iter().collect())
+}
+
+struct Counter<'a> {
+    pager: &'a Pager,
+    num_pages: u64,
+    /// Pages counted so far, so shared or cyclic references count once
+    seen: HashSet<PageNum>,
+}
+
+impl Counter<'_> {
+    /// Whether `page` is inside the file and wasn't counted before
+    fn claim(&mut self, page: PageNum) -> bool {
+        page < self.num_pages && self.seen.insert(page)
+    }
+
+    fn measure_collection(&mut self, name: &str, meta: &CollectionMeta) -> CollectionSpace {
+        let mut space = CollectionSpace { name: name.to_string(), ..Default::default() };
+
+        let (tree_pages, entries) = self.tree(meta.btree_root);
+        space.tree_pages = tree_pages;
+        for page in entries {
+            let (first, overflow) = self.document(page);
+            space.document_pages += first;
+            space.overflow_pages += overflow;
+        }
+
+        for index in meta.indexes.values() {
+            space.index_pages += self.tree(index.btree_root).0;
+        }
+        for text_index in meta.text_indexes.values() {
+            let (tree_pages, postings) = self.tree(text_index.btree_root);
+            space.index_pages += tree_pages;
+            space.index_pages += postings.into_iter().filter(|&page| self.claim(page)).count() as u64;
+        }
+
+        space.pages = space.tree_pages + space.document_pages + space.overflow_pages + space.index_pages;
+        space
+    }
+
+    /// Count the nodes of the B-tree at `root`, returning the count and the
+    /// values stored in its leaves
+    fn tree(&mut self, root: PageNum) -> (u64, Vec<u64>) {
+        let mut nodes = 0;
+        let mut values = Vec::new();
+        let mut stack = if root == 0 { Vec::new() } else { vec![root] };
+
+        while let Some(page) = stack.pop() {
+            if !self.claim(page) {
+                continue;
+            }
+            nodes += 1;
+
+            let Ok(node) = self.pager.read_page(page).and_then(|data| deserialize_node(page, &data)) else {
+                continue;
+            };
+            if node.node_type == NodeType::LeafNode {
+                values.extend(node.entries.iter().map(|entry| entry.value));
+            } else {
+                stack.extend(node.children.iter().copied());
+            }
+        }
+
+        (nodes, values)
+    }
+
+    /// Count a stored document version as its first page and its overflow pages
+    fn document(&mut self, page: PageNum) -> (u64, u64) {
+        let pages = versioned_document_pages(self.pager, page).unwrap_or_else(|_| vec![page]);
+        let first = self.claim(page) as u64;
+        let overflow = pages[1..].iter().filter(|&&page| self.claim(page)).count() as u64;
+        (first, overflow)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::database::Database;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_space_stats_accounts_for_every_page() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("space.db");
+        let db = Database::open(path.to_str().unwrap()).unwrap();
+
+        let users = db.collection("users");
+        let ids: Vec<String> = (0..50)
+            .map(|i| users.insert(json!({"n": i, "bio": "x".repeat(if i == 0 { 20_000 } else { 10 })})).unwrap())
+            .collect();
+        db.create_index("users", "n_idx", "n", false).unwrap();
+
+        let stats = db.space_stats().unwrap();
+        let collection = &stats.collections[0];
+        assert_eq!(collection.name, "users");
+        assert_eq!(collection.document_pages, 50);
+        assert!(collection.overflow_pages > 0);
+        assert_eq!(stats.overflow_pages, collection.overflow_pages);
+        assert!(collection.tree_pages > 0);
+        assert!(collection.index_pages > 0);
+        assert_eq!(stats.total_pages, stats.pages_in_use + stats.free_pages + stats.orphaned_pages);
+
+        // A snapshot keeps the deleted versions alive until it is released
+        let snapshot = db.snapshot().unwrap();
+        for id in &ids[..25] {
+            users.delete_by_id(id).unwrap();
+        }
+        let stats = db.space_stats().unwrap();
+        assert_eq!(stats.collections[0].document_pages, 25);
+        assert!(stats.old_version_pages >= 25);
+
+        drop(snapshot);
+        db.garbage_collect().unwrap();
+        let stats = db.space_stats().unwrap();
+        assert_eq!(stats.old_version_pages, 0);
+        assert!(stats.reclaimable_pages() >= 25);
+        assert!(stats.reclaimable_ratio() > 0.0 && stats.reclaimable_ratio() <= 1.0);
+    }
+}
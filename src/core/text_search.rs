@@ -1,10 +1,16 @@
 use crate::core::btree::BTree;
-use crate::core::constants::PAGE_SIZE;
 use crate::core::errors::*;
+use rust_stemmers::{Algorithm, Stemmer};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use unicode_segmentation::UnicodeSegmentation;
 
+/// Default BM25 term frequency saturation
+pub const DEFAULT_BM25_K1: f32 = 1.2;
+/// Default BM25 document length normalization
+pub const DEFAULT_BM25_B: f32 = 0.75;
+
 /// Metadata about a text index stored in collection metadata
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct TextIndexMeta {
@@ -14,6 +20,113 @@ pub struct TextIndexMeta {
     pub fields: Vec<String>,
     /// Root page of the inverted index B-tree
     pub btree_root: u64,
+    /// Tokenization options the index was built with
+    #[serde(default)]
+    pub options: TextIndexOptions,
+}
+
+/// Language used for stemming terms
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    /// English (Porter stemmer)
+    English,
+}
+
+impl Language {
+    fn algorithm(self) -> Algorithm {
+        match self {
+            Language::English => Algorithm::English,
+        }
+    }
+}
+
+/// Options controlling how a text index tokenizes and scores text
+///
+/// The same options are applied when indexing documents and when tokenizing
+/// search queries, so they are fixed once the index has been built.
+///
+/// # Examples
+/// ```
+/// use jasonisnthappy::{Language, TextIndexOptions};
+///
+/// let options = TextIndexOptions {
+///     stopwords: vec!["the".to_string(), "is".to_string()],
+///     stemming: Some(Language::English),
+///     ..Default::default()
+/// };
+/// assert_eq!(options.tokenize("The cat is running"), vec!["cat", "run"]);
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TextIndexOptions {
+    /// Terms to drop before indexing (matched case-insensitively)
+    #[serde(default)]
+    pub stopwords: Vec<String>,
+    /// Language to stem terms with, or None to index words as-is
+    #[serde(default)]
+    pub stemming: Option<Language>,
+    /// BM25 term frequency saturation. Higher values let repeated terms keep adding score.
+    #[serde(default = "default_k1")]
+    pub k1: f32,
+    /// BM25 length normalization, from 0.0 (ignore field length) to 1.0 (fully normalize)
+    #[serde(default = "default_b")]
+    pub b: f32,
+}
+
+fn default_k1() -> f32 {
+    DEFAULT_BM25_K1
+}
+
+fn default_b() -> f32 {
+    DEFAULT_BM25_B
+}
+
+impl Default for TextIndexOptions {
+    fn default() -> Self {
+        Self {
+            stopwords: Vec::new(),
+            stemming: None,
+            k1: DEFAULT_BM25_K1,
+            b: DEFAULT_BM25_B,
+        }
+    }
+}
+
+impl TextIndexOptions {
+    /// Tokenize text, then remove stopwords and stem the remaining terms
+    ///
+    /// Builds the stopword set and stemmer on every call; code tokenizing
+    /// many texts with the same options uses a `Tokenizer` instead.
+    pub fn tokenize(&self, text: &str) -> Vec<String> {
+        Tokenizer::new(self).tokenize(text)
+    }
+}
+
+/// Stopword set and stemmer built once from an index's options and reused
+/// for every document and query it tokenizes
+pub(crate) struct Tokenizer {
+    stopwords: HashSet<String>,
+    stemmer: Option<Stemmer>,
+}
+
+impl Tokenizer {
+    pub(crate) fn new(options: &TextIndexOptions) -> Self {
+        Self {
+            stopwords: options.stopwords.iter().map(|w| w.to_lowercase()).collect(),
+            stemmer: options.stemming.map(|lang| Stemmer::create(lang.algorithm())),
+        }
+    }
+
+    pub(crate) fn tokenize(&self, text: &str) -> Vec<String> {
+        tokenize(text)
+            .into_iter()
+            .filter(|token| !self.stopwords.contains(token))
+            .map(|token| match &self.stemmer {
+                Some(stemmer) => stemmer.stem(&token).into_owned(),
+                None => token,
+            })
+            .collect()
+    }
 }
 
 /// A search result with relevance score
@@ -23,25 +136,68 @@ pub struct SearchResult {
     pub doc_id: String,
     /// Relevance score (higher = more relevant)
     pub score: f32,
+    /// Per-term contributions to the score, only filled in by explained searches
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub components: Option<Vec<TermScore>>,
 }
 
 impl SearchResult {
     pub fn new(doc_id: String, score: f32) -> Self {
-        Self { doc_id, score }
+        Self { doc_id, score, components: None }
+    }
+}
+
+/// Contribution of a single query term in a single field to a document's score
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct TermScore {
+    /// Query term after tokenization
+    pub term: String,
+    /// Field the term matched in
+    pub field: String,
+    /// BM25 score for this term and field
+    pub score: f32,
+}
+
+/// A document's entry in a term's posting list
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(untagged)]
+enum Posting {
+    /// Number of occurrences of the term and length of the field in terms
+    Counts(u32, u32),
+    /// Normalized term frequency written by older versions
+    Legacy(f32),
+}
+
+/// Per-field totals used to compute the average field length
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct FieldStats {
+    docs: u64,
+    total_len: u64,
+}
+
+impl FieldStats {
+    fn avg_len(&self) -> f32 {
+        if self.docs == 0 {
+            return 0.0;
+        }
+        self.total_len as f32 / self.docs as f32
     }
 }
 
 /// Inverted index for text search
-/// Maps terms to documents and their scores
+/// Maps terms to documents and their term counts
 pub struct TextIndex {
     btree: BTree,
     fields: Vec<String>,
+    options: TextIndexOptions,
+    tokenizer: Tokenizer,
 }
 
 impl TextIndex {
     /// Create a new text index with the given B-tree
-    pub fn new(btree: BTree, fields: Vec<String>) -> Self {
-        Self { btree, fields }
+    pub fn new(btree: BTree, fields: Vec<String>, options: TextIndexOptions) -> Self {
+        let tokenizer = Tokenizer::new(&options);
+        Self { btree, fields, options, tokenizer }
     }
 
     /// Get a reference to the underlying B-tree
@@ -54,41 +210,32 @@ impl TextIndex {
         &self.fields
     }
 
+    /// Get the tokenization options
+    pub fn options(&self) -> &TextIndexOptions {
+        &self.options
+    }
+
     /// Index a document's text fields
     pub fn index_document(&mut self, doc_id: &str, field_values: &HashMap<String, String>) -> Result<()> {
         for field in &self.fields {
             if let Some(text) = field_values.get(field) {
-                let tokens = tokenize(text);
-                let term_freq = calculate_term_frequency(&tokens);
+                let tokens = self.tokenizer.tokenize(text);
+                if tokens.is_empty() {
+                    continue;
+                }
+                let field_len = tokens.len() as u32;
 
-                for (term, freq) in term_freq {
+                for (term, count) in count_terms(&tokens) {
                     let key = format!("{}:{}", term, field);
 
-                    // Get existing postings or create new
-                    let mut postings: HashMap<String, f32> = match self.btree.search(&key) {
-                        Ok(page_num) => {
-                            let pager = self.btree.pager();
-                            let page = pager.read_page(page_num)?;
-
-                            // Trim null bytes
-                            let trimmed = page.iter()
-                                .rposition(|&b| b != 0)
-                                .map(|pos| &page[..=pos])
-                                .unwrap_or(&page[..0]);
-
-                            serde_json::from_slice(trimmed).unwrap_or_default()
-                        }
-                        Err(_) => HashMap::new(),
-                    };
-
-                    // Add or update this document's term frequency
-                    postings.insert(doc_id.to_string(), freq);
-
-                    // Serialize and store
-                    let data = serde_json::to_vec(&postings)?;
-                    let page_num = self.write_data(&data)?;
-                    self.btree.insert(&key, page_num)?;
+                    // Add or update this document's term count
+                    let mut postings: HashMap<String, Posting> =
+                        self.read_entry(&key)?.unwrap_or_default();
+                    postings.insert(doc_id.to_string(), Posting::Counts(count, field_len));
+                    self.write_entry(&key, &postings)?;
                 }
+
+                self.update_field_stats(field, 1, field_len as i64)?;
             }
         }
 
@@ -99,25 +246,17 @@ impl TextIndex {
     pub fn remove_document(&mut self, doc_id: &str, field_values: &HashMap<String, String>) -> Result<()> {
         for field in &self.fields {
             if let Some(text) = field_values.get(field) {
-                let tokens = tokenize(text);
-                let unique_terms: std::collections::HashSet<_> = tokens.into_iter().collect();
+                let tokens = self.tokenizer.tokenize(text);
+                if tokens.is_empty() {
+                    continue;
+                }
+                let field_len = tokens.len() as i64;
+                let unique_terms: HashSet<_> = tokens.into_iter().collect();
 
                 for term in unique_terms {
                     let key = format!("{}:{}", term, field);
 
-                    if let Ok(page_num) = self.btree.search(&key) {
-                        let pager = self.btree.pager();
-                        let page = pager.read_page(page_num)?;
-
-                        // Trim null bytes
-                        let trimmed = page.iter()
-                            .rposition(|&b| b != 0)
-                            .map(|pos| &page[..=pos])
-                            .unwrap_or(&page[..0]);
-
-                        let mut postings: HashMap<String, f32> =
-                            serde_json::from_slice(trimmed).unwrap_or_default();
-
+                    if let Some(mut postings) = self.read_entry::<HashMap<String, Posting>>(&key)? {
                         postings.remove(doc_id);
 
                         if postings.is_empty() {
@@ -125,12 +264,12 @@ impl TextIndex {
                             self.btree.delete(&key)?;
                         } else {
                             // Update with remaining documents
-                            let data = serde_json::to_vec(&postings)?;
-                            let new_page_num = self.write_data(&data)?;
-                            self.btree.insert(&key, new_page_num)?;
+                            self.write_entry(&key, &postings)?;
                         }
                     }
                 }
+
+                self.update_field_stats(field, -1, -field_len)?;
             }
         }
 
@@ -138,68 +277,134 @@ impl TextIndex {
     }
 
     /// Search for documents matching the query
-    /// Returns documents sorted by relevance (highest score first)
+    /// Returns documents sorted by BM25 relevance (highest score first)
     pub fn search(&self, query: &str, total_docs: usize) -> Result<Vec<SearchResult>> {
-        let query_terms = tokenize(query);
+        self.score_query(query, total_docs, false)
+    }
+
+    /// Like `search`, but also reports each term's contribution to the score
+    pub fn search_explain(&self, query: &str, total_docs: usize) -> Result<Vec<SearchResult>> {
+        self.score_query(query, total_docs, true)
+    }
+
+    fn score_query(&self, query: &str, total_docs: usize, explain: bool) -> Result<Vec<SearchResult>> {
+        let query_terms = self.tokenizer.tokenize(query);
         if query_terms.is_empty() {
             return Ok(Vec::new());
         }
 
+        // Average field lengths for length normalization
+        let mut avg_lens: HashMap<&str, f32> = HashMap::new();
+        for field in &self.fields {
+            let stats: FieldStats = self.read_entry(&stats_key(field))?.unwrap_or_default();
+            avg_lens.insert(field, stats.avg_len());
+        }
+
         // Collect document scores for each term
-        let mut doc_scores: HashMap<String, f32> = HashMap::new();
+        let mut doc_scores: HashMap<String, SearchResult> = HashMap::new();
 
         for term in query_terms {
             // Search across all indexed fields
             for field in &self.fields {
                 let key = format!("{}:{}", term, field);
 
-                if let Ok(page_num) = self.btree.search(&key) {
-                    let pager = self.btree.pager();
-                    let page = pager.read_page(page_num)?;
-
-                    // Trim null bytes from the end
-                    let trimmed = page.iter()
-                        .rposition(|&b| b != 0)
-                        .map(|pos| &page[..=pos])
-                        .unwrap_or(&page[..0]);
-
-                    let postings: HashMap<String, f32> =
-                        serde_json::from_slice(trimmed).unwrap_or_default();
-
-                    // Calculate IDF for this term
-                    let idf = calculate_idf(total_docs, postings.len());
-
-                    // Add TF-IDF score for each document
-                    for (doc_id, tf) in postings {
-                        let score = tf * idf;
-                        *doc_scores.entry(doc_id).or_insert(0.0) += score;
+                if let Some(postings) = self.read_entry::<HashMap<String, Posting>>(&key)? {
+                    // Rare terms contribute more than common ones
+                    let idf = calculate_idf(total_docs.max(postings.len()), postings.len());
+                    let avg_len = avg_lens[field.as_str()];
+
+                    for (doc_id, posting) in postings {
+                        let score = self.bm25(idf, posting, avg_len);
+                        let result = doc_scores.entry(doc_id.clone())
+                            .or_insert_with(|| SearchResult::new(doc_id, 0.0));
+                        result.score += score;
+
+                        if explain {
+                            result.components.get_or_insert_with(Vec::new).push(TermScore {
+                                term: term.clone(),
+                                field: field.clone(),
+                                score,
+                            });
+                        }
                     }
                 }
             }
         }
 
-        // Convert to SearchResult and sort by score
-        let mut results: Vec<SearchResult> = doc_scores
-            .into_iter()
-            .map(|(doc_id, score)| SearchResult::new(doc_id, score))
-            .collect();
+        // Sort by score
+        let mut results: Vec<SearchResult> = doc_scores.into_values().collect();
 
         results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
 
         Ok(results)
     }
 
+    /// BM25 score of one posting:
+    /// idf * tf * (k1 + 1) / (tf + k1 * (1 - b + b * len / avg_len))
+    fn bm25(&self, idf: f32, posting: Posting, avg_len: f32) -> f32 {
+        let (tf, len) = match posting {
+            Posting::Counts(count, len) => (count as f32, len as f32),
+            // Older entries don't know the field length, so skip length normalization
+            Posting::Legacy(tf) => (tf, avg_len),
+        };
+
+        let k1 = self.options.k1;
+        let b = self.options.b;
+        let length_norm = if avg_len > 0.0 {
+            1.0 - b + b * len / avg_len
+        } else {
+            1.0
+        };
+
+        idf * tf * (k1 + 1.0) / (tf + k1 * length_norm)
+    }
+
+    /// Adjust the document count and total length recorded for a field
+    fn update_field_stats(&self, field: &str, docs_delta: i64, len_delta: i64) -> Result<()> {
+        let key = stats_key(field);
+        let mut stats: FieldStats = self.read_entry(&key)?.unwrap_or_default();
+        stats.docs = stats.docs.saturating_add_signed(docs_delta);
+        stats.total_len = stats.total_len.saturating_add_signed(len_delta);
+        self.write_entry(&key, &stats)
+    }
+
+    /// Read and deserialize the entry stored under a key, if any
+    fn read_entry<T: DeserializeOwned + Default>(&self, key: &str) -> Result<Option<T>> {
+        let page_num = match self.btree.search(key) {
+            Ok(page_num) => page_num,
+            Err(_) => return Ok(None),
+        };
+
+        let pager = self.btree.pager();
+        let page = pager.read_page(page_num)?;
+
+        // Trim null bytes from the end
+        let trimmed = page.iter()
+            .rposition(|&b| b != 0)
+            .map(|pos| &page[..=pos])
+            .unwrap_or(&page[..0]);
+
+        Ok(Some(serde_json::from_slice(trimmed).unwrap_or_default()))
+    }
+
+    /// Serialize a value to a new page and point the key at it
+    fn write_entry<T: Serialize>(&self, key: &str, value: &T) -> Result<()> {
+        let data = serde_json::to_vec(value)?;
+        let page_num = self.write_data(&data)?;
+        self.btree.insert(key, page_num)
+    }
+
     /// Write data to a new page and return the page number
     fn write_data(&self, data: &[u8]) -> Result<u64> {
         let pager = self.btree.pager();
         let page_num = pager.alloc_page()?;
 
         // Ensure the data fits in a page
-        if data.len() > PAGE_SIZE {
+        if data.len() > pager.page_size() {
             return Err(Error::Other("text index data too large for page".to_string()));
         }
 
-        let mut page_data = vec![0u8; PAGE_SIZE];
+        let mut page_data = vec![0u8; pager.page_size()];
         page_data[..data.len()].copy_from_slice(data);
         pager.write_page(page_num, &page_data)?;
 
@@ -207,6 +412,11 @@ impl TextIndex {
     }
 }
 
+/// Key of the per-field length statistics. The leading NUL can't appear in a term.
+fn stats_key(field: &str) -> String {
+    format!("\u{0}stats:{}", field)
+}
+
 /// Tokenize text into terms (lowercase words)
 pub fn tokenize(text: &str) -> Vec<String> {
     text.unicode_words()
@@ -215,31 +425,26 @@ pub fn tokenize(text: &str) -> Vec<String> {
         .collect()
 }
 
-/// Calculate term frequency for a list of tokens
-/// TF = count of term / total number of terms
-fn calculate_term_frequency(tokens: &[String]) -> HashMap<String, f32> {
-    let total = tokens.len() as f32;
-    let mut counts: HashMap<String, f32> = HashMap::new();
+/// Count occurrences of each term in a list of tokens
+fn count_terms(tokens: &[String]) -> HashMap<String, u32> {
+    let mut counts: HashMap<String, u32> = HashMap::new();
 
     for token in tokens {
-        *counts.entry(token.clone()).or_insert(0.0) += 1.0;
-    }
-
-    // Normalize by total count
-    for count in counts.values_mut() {
-        *count /= total;
+        *counts.entry(token.clone()).or_insert(0) += 1;
     }
 
     counts
 }
 
-/// Calculate inverse document frequency
-/// IDF = log(total_documents / documents_containing_term)
+/// Calculate BM25 inverse document frequency
+/// IDF = ln(1 + (total_documents - docs_with_term + 0.5) / (docs_with_term + 0.5))
 fn calculate_idf(total_docs: usize, docs_with_term: usize) -> f32 {
     if docs_with_term == 0 {
         return 0.0;
     }
-    ((total_docs as f32) / (docs_with_term as f32)).ln()
+    let n = total_docs as f32;
+    let df = docs_with_term as f32;
+    (1.0 + (n - df + 0.5) / (df + 0.5)).ln()
 }
 
 #[cfg(test)]
@@ -268,18 +473,42 @@ mod tests {
     }
 
     #[test]
-    fn test_term_frequency() {
+    fn test_options_stopwords_and_stemming() {
+        let options = TextIndexOptions {
+            stopwords: vec!["The".to_string(), "is".to_string()],
+            stemming: Some(Language::English),
+            ..Default::default()
+        };
+        let tokens = options.tokenize("The runner is running runs");
+        assert_eq!(tokens, vec!["runner", "run", "run"]);
+
+        // Default options behave like plain tokenization
+        let tokens = TextIndexOptions::default().tokenize("The runner is running");
+        assert_eq!(tokens, vec!["the", "runner", "is", "running"]);
+    }
+
+    #[test]
+    fn test_count_terms() {
         let tokens = vec![
             "rust".to_string(),
             "database".to_string(),
             "rust".to_string(),
             "test".to_string(),
         ];
-        let tf = calculate_term_frequency(&tokens);
+        let counts = count_terms(&tokens);
+
+        assert_eq!(counts.get("rust"), Some(&2));
+        assert_eq!(counts.get("database"), Some(&1));
+        assert_eq!(counts.get("test"), Some(&1));
+    }
+
+    #[test]
+    fn test_posting_reads_legacy_format() {
+        let postings: HashMap<String, Posting> =
+            serde_json::from_str(r#"{"a": [3, 10], "b": 0.25}"#).unwrap();
 
-        assert_eq!(tf.get("rust"), Some(&0.5)); // 2/4
-        assert_eq!(tf.get("database"), Some(&0.25)); // 1/4
-        assert_eq!(tf.get("test"), Some(&0.25)); // 1/4
+        assert!(matches!(postings["a"], Posting::Counts(3, 10)));
+        assert!(matches!(postings["b"], Posting::Legacy(tf) if tf == 0.25));
     }
 
     #[test]
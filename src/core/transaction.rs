@@ -1,18 +1,30 @@
 
 use crate::core::constants::*;
 use crate::core::errors::*;
+use crate::core::metadata::Metadata;
 use crate::core::mvcc::TransactionManager;
+use crate::core::metrics::Operation;
 use crate::core::pager::Pager;
 use crate::core::wal::WAL;
 use crate::core::tx_collection::TxCollection;
+use crate::core::recycle_bin;
+use crate::core::replication::{self, LoggedWrite};
 use crate::core::database::Database;
-use crate::core::watch::{emit_change, ChangeOperation};
+use crate::core::events::DbEvent;
+use crate::core::watch::{before_change_interest, emit_change, ChangeOperation};
+use crate::core::binary::decode_document;
 use crate::core::document::read_versioned_document;
+use crate::core::index_writes::{store_index_roots, IndexWrites};
 use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex, RwLock};
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 use crate::core::errors::PoisonedLockExt;
 
+/// Pre-change document data per collection and document ID. `None` means the
+/// document was changed but nobody asked for its previous contents.
+pub(crate) type DocBeforeImages = HashMap<String, HashMap<String, Option<Vec<u8>>>>;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TxState {
     Active,
@@ -20,11 +32,39 @@ pub enum TxState {
     RolledBack,
 }
 
+/// Copy of a transaction's buffered writes and tracking state, taken by
+/// `Transaction::savepoint` and restored by `Transaction::rollback_to_savepoint`
+struct Savepoint {
+    name: String,
+    writes: HashMap<PageNum, Vec<u8>>,
+    doc_writes: HashMap<String, HashMap<String, PageNum>>,
+    updated_roots: HashMap<String, PageNum>,
+    doc_existed_in_snapshot: HashMap<String, HashMap<String, bool>>,
+    doc_original_xmin: HashMap<String, HashMap<String, TransactionID>>,
+    modified_collections: HashSet<String>,
+    old_versions: HashMap<String, HashMap<String, crate::core::mvcc::DocumentVersion>>,
+    doc_before_images: DocBeforeImages,
+    count_deltas: HashMap<String, i64>,
+    index_writes: IndexWrites,
+}
+
+/// A read-write or read-only unit of work over one snapshot of the database
+///
+/// Write conflicts are detected per document, not per collection. A commit
+/// fails with `Error::TxConflict` only when a document this transaction
+/// updated or deleted has had a newer version committed since the
+/// transaction began, or was deleted in the meantime. Transactions that write
+/// different `_id`s in the same collection both commit, and the later one is
+/// rebased onto the earlier one's B-tree. Two transactions inserting the same
+/// new `_id` also conflict, while a clash on a unique index value fails with
+/// `Error::DuplicateKey`. Documents that were only read are not tracked, so
+/// the isolation level is snapshot isolation rather than serializable.
 pub struct Transaction {
     pub tx_id: u64,
     pub mvcc_tx_id: TransactionID,
     pub snapshot_id: TransactionID,
     pub state: TxState,
+    read_only: bool,
 
     writes: Arc<RwLock<HashMap<PageNum, Vec<u8>>>>,
     doc_writes: Arc<RwLock<HashMap<String, HashMap<String, PageNum>>>>,
@@ -47,11 +87,40 @@ pub struct Transaction {
 
     old_versions: Arc<RwLock<HashMap<String, HashMap<String, crate::core::mvcc::DocumentVersion>>>>,
 
+    // Pre-change document data for updated/deleted documents, used for change events
+    doc_before_images: Arc<RwLock<DocBeforeImages>>,
+
+    // Net documents added (or removed) per collection, applied to metadata on commit
+    count_deltas: Arc<RwLock<HashMap<String, i64>>>,
+
+    // Documents whose secondary index entries are replaced on commit
+    index_writes: Arc<RwLock<IndexWrites>>,
+
+    // Savepoints in creation order, latest last
+    savepoints: Vec<Savepoint>,
+
+    started_at: Instant,
+    // Operations after this point fail with TxTimeout
+    deadline: Option<Instant>,
+
     commit_mu: Arc<Mutex<()>>,
 }
 
 static GLOBAL_TX_ID: AtomicU64 = AtomicU64::new(1);
 
+/// Longest a writer waiting on a batch commit sleeps before checking
+/// whether it should lead the next batch itself
+const FOLLOWER_RECHECK_INTERVAL: Duration = Duration::from_millis(1);
+
+type Completion = Arc<(Mutex<Option<Result<()>>>, std::sync::Condvar)>;
+
+/// Whether a leader has stored the result of a queued write
+fn completion_ready(completion: &Completion) -> Result<bool> {
+    let result = completion.0.lock()
+        .map_err(|_| Error::LockPoisoned { lock_name: "completion.lock".to_string() })?;
+    Ok(result.is_some())
+}
+
 impl Transaction {
     pub fn new(
         pager: Arc<Pager>,
@@ -60,6 +129,32 @@ impl Transaction {
         collection_roots: HashMap<String, PageNum>,
         commit_mu: Arc<Mutex<()>>,
         tx_id_counter: Option<Arc<AtomicU64>>,
+    ) -> Result<Self> {
+        Self::start(pager, wal, tx_manager, collection_roots, commit_mu, tx_id_counter, false)
+    }
+
+    /// Start a transaction that can only read. It shares the latest committed
+    /// snapshot with other read-only transactions instead of taking an MVCC
+    /// transaction ID, so it never conflicts with writers.
+    pub fn new_read_only(
+        pager: Arc<Pager>,
+        wal: Arc<WAL>,
+        tx_manager: Arc<TransactionManager>,
+        collection_roots: HashMap<String, PageNum>,
+        commit_mu: Arc<Mutex<()>>,
+        tx_id_counter: Option<Arc<AtomicU64>>,
+    ) -> Result<Self> {
+        Self::start(pager, wal, tx_manager, collection_roots, commit_mu, tx_id_counter, true)
+    }
+
+    fn start(
+        pager: Arc<Pager>,
+        wal: Arc<WAL>,
+        tx_manager: Arc<TransactionManager>,
+        collection_roots: HashMap<String, PageNum>,
+        commit_mu: Arc<Mutex<()>>,
+        tx_id_counter: Option<Arc<AtomicU64>>,
+        read_only: bool,
     ) -> Result<Self> {
         // Use per-database TX ID counter if provided, otherwise fall back to global
         let tx_id = if let Some(counter) = &tx_id_counter {
@@ -67,8 +162,13 @@ impl Transaction {
         } else {
             GLOBAL_TX_ID.fetch_add(1, Ordering::SeqCst)
         };
-        let mvcc_tx_id = tx_manager.begin_transaction()?;
-        let snapshot_id = tx_manager.get_latest_committed_tx_id();
+        let (mvcc_tx_id, snapshot_id) = if read_only {
+            // MVCC IDs start at 1, so no document is ever taken for this transaction's own write
+            (0, tx_manager.pin_snapshot()?)
+        } else {
+            let mvcc_tx_id = tx_manager.begin_transaction()?;
+            (mvcc_tx_id, tx_manager.get_latest_committed_tx_id())
+        };
 
 
         Ok(Self {
@@ -76,6 +176,7 @@ impl Transaction {
             mvcc_tx_id,
             snapshot_id,
             state: TxState::Active,
+            read_only,
             writes: Arc::new(RwLock::new(HashMap::new())),
             doc_writes: Arc::new(RwLock::new(HashMap::new())),
             snapshot_roots: collection_roots.clone(),
@@ -88,6 +189,12 @@ impl Transaction {
             db: None,
             modified_collections: Arc::new(RwLock::new(HashSet::new())),
             old_versions: Arc::new(RwLock::new(HashMap::new())),
+            doc_before_images: Arc::new(RwLock::new(HashMap::new())),
+            count_deltas: Arc::new(RwLock::new(HashMap::new())),
+            index_writes: Arc::new(RwLock::new(IndexWrites::default())),
+            savepoints: Vec::new(),
+            started_at: Instant::now(),
+            deadline: None,
             commit_mu,
         })
     }
@@ -97,6 +204,8 @@ impl Transaction {
     }
 
     pub fn collection(&mut self, name: &str) -> Result<TxCollection<'_>> {
+        self.expire_if_past_deadline()?;
+
         let db = self.db.as_ref()
             .expect("Transaction must have database reference set")
             .clone();
@@ -105,9 +214,7 @@ impl Transaction {
 
     /// Create a new collection
     pub fn create_collection(&mut self, name: &str) -> Result<()> {
-        if !self.is_active() {
-            return Err(Error::TxNotActive);
-        }
+        self.ensure_writable()?;
 
         let db = self.db.as_ref()
             .expect("Transaction must have database reference set");
@@ -132,9 +239,7 @@ impl Transaction {
 
     /// Drop (delete) a collection and all its documents
     pub fn drop_collection(&mut self, name: &str) -> Result<()> {
-        if !self.is_active() {
-            return Err(Error::TxNotActive);
-        }
+        self.ensure_writable()?;
 
         let db = self.db.as_ref()
             .expect("Transaction must have database reference set");
@@ -155,20 +260,29 @@ impl Transaction {
         db.update_metadata_no_flush(|m| {
             m.collections.remove(name);
         });
+        db.metrics_ref().remove_collection(name);
+        db.invalidate_query_cache(name);
+        db.rename_id_filter(name, None);
+        self.count_deltas.write().recover_poison().remove(name);
 
         // Remove from version chains
         let mut chains = db.version_chains.write()
             .map_err(|_| Error::LockPoisoned { lock_name: "database.version_chains".to_string() })?;
         chains.remove(name);
+        drop(chains);
+
+        // Deleted documents go with the collection
+        let bin = recycle_bin::bin_name(name);
+        if db.collection_exists(&bin) {
+            self.drop_collection(&bin)?;
+        }
 
         Ok(())
     }
 
     /// Rename a collection
     pub fn rename_collection(&mut self, old_name: &str, new_name: &str) -> Result<()> {
-        if !self.is_active() {
-            return Err(Error::TxNotActive);
-        }
+        self.ensure_writable()?;
 
         let db = self.db.as_ref()
             .expect("Transaction must have database reference set");
@@ -194,6 +308,10 @@ impl Transaction {
                 m.collections.insert(new_name.to_string(), collection_meta);
             }
         });
+        db.metrics_ref().rename_collection(old_name, new_name);
+        db.invalidate_query_cache(old_name);
+        db.invalidate_query_cache(new_name);
+        db.rename_id_filter(old_name, Some(new_name));
 
         // Move version chains
         let mut chains = db.version_chains.write()
@@ -201,18 +319,98 @@ impl Transaction {
         if let Some(chain) = chains.remove(old_name) {
             chains.insert(new_name.to_string(), chain);
         }
+        drop(chains);
+
+        let old_bin = recycle_bin::bin_name(old_name);
+        if db.collection_exists(&old_bin) {
+            self.rename_collection(&old_bin, &recycle_bin::bin_name(new_name))?;
+        }
 
         Ok(())
     }
 
     pub fn is_active(&self) -> bool {
-        self.state == TxState::Active
+        self.state == TxState::Active && !self.is_past_deadline()
     }
 
-    pub fn write_page(&self, page_num: PageNum, data: Vec<u8>) -> Result<()> {
-        if !self.is_active() {
+    /// When the transaction began
+    pub fn started_at(&self) -> Instant {
+        self.started_at
+    }
+
+    /// Limit how long the transaction may stay open, counted from when it began
+    pub(crate) fn set_max_duration(&mut self, max_duration: Duration) -> Result<()> {
+        let deadline = self.started_at + max_duration;
+        self.deadline = Some(deadline);
+        self.tx_manager.set_transaction_deadline(self.mvcc_tx_id, deadline)
+    }
+
+    fn is_past_deadline(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Fail with `TxTimeout` once the transaction has run past its deadline
+    pub(crate) fn check_deadline(&self) -> Result<()> {
+        if self.is_past_deadline() {
+            return Err(Error::TxTimeout);
+        }
+        Ok(())
+    }
+
+    /// Fail unless the transaction can still be used
+    pub(crate) fn ensure_active(&self) -> Result<()> {
+        if self.state != TxState::Active {
             return Err(Error::TxNotActive);
         }
+        self.check_deadline()
+    }
+
+    /// Whether the transaction was started with `Database::begin_read_only`
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Fail unless the transaction can still be used to write
+    pub(crate) fn ensure_writable(&self) -> Result<()> {
+        self.ensure_active()?;
+        if self.read_only {
+            return Err(Error::ReadOnlyTransaction);
+        }
+        Ok(())
+    }
+
+    /// Stop holding the transaction's snapshot open, aborting it in the
+    /// transaction manager unless it is read-only
+    fn release_snapshot(&self) -> Result<()> {
+        if self.read_only {
+            self.tx_manager.unpin_snapshot(self.snapshot_id)
+        } else {
+            self.tx_manager.abort_transaction(self.mvcc_tx_id)
+        }
+    }
+
+    /// Abandon the transaction if it has run past its deadline
+    fn expire_if_past_deadline(&mut self) -> Result<()> {
+        if !self.is_past_deadline() {
+            return Ok(());
+        }
+
+        if self.state == TxState::Active {
+            self.writes.write().recover_poison().clear();
+            self.doc_writes.write().recover_poison().clear();
+            self.state = TxState::RolledBack;
+            self.release_snapshot()?;
+
+            if let Some(db) = &self.db {
+                db.metrics_ref().transaction_aborted();
+            }
+        }
+
+        Err(Error::TxTimeout)
+    }
+
+    pub fn write_page(&self, page_num: PageNum, data: Vec<u8>) -> Result<()> {
+        self.ensure_writable()?;
 
         let mut writes = self.writes.write()
             .map_err(|_| Error::LockPoisoned { lock_name: "transaction.writes".to_string() })?;
@@ -221,9 +419,7 @@ impl Transaction {
     }
 
     pub fn write_document(&self, collection: &str, doc_id: &str, page_num: PageNum) -> Result<()> {
-        if !self.is_active() {
-            return Err(Error::TxNotActive);
-        }
+        self.ensure_writable()?;
 
         let mut doc_writes = self.doc_writes.write()
             .map_err(|_| Error::LockPoisoned { lock_name: "transaction.doc_writes".to_string() })?;
@@ -231,6 +427,13 @@ impl Transaction {
             .entry(collection.to_string())
             .or_insert_with(HashMap::new)
             .insert(doc_id.to_string(), page_num);
+
+        // Recorded before commit so a committed document is never missing from the filter
+        if page_num != PageNum::MAX {
+            if let Some(db) = &self.db {
+                db.record_id(collection, doc_id);
+            }
+        }
         Ok(())
     }
 
@@ -261,6 +464,135 @@ impl Transaction {
             .insert(doc_id.to_string(), version);
     }
 
+    /// Remember the version of a document as it was before this transaction
+    /// changed it, so that change events can report it.
+    pub(crate) fn track_doc_before_image(&self, collection: &str, doc_id: &str, data: &[u8]) {
+        let db = match &self.db {
+            Some(db) => db,
+            None => return,
+        };
+
+        // Nobody is watching this collection, nothing to remember
+        let wants_data = match before_change_interest(&db.get_watchers(), collection) {
+            Some(wants_data) => wants_data,
+            None => return,
+        };
+
+        let mut before_images = self.doc_before_images.write()
+            .recover_poison();
+        before_images
+            .entry(collection.to_string())
+            .or_insert_with(HashMap::new)
+            .entry(doc_id.to_string())
+            .or_insert_with(|| if wants_data { Some(data.to_vec()) } else { None });
+    }
+
+    /// Record that `delta` documents were added to (or removed from) a collection.
+    /// The collection's document count in metadata is adjusted when the
+    /// transaction commits.
+    pub(crate) fn adjust_document_count(&self, collection: &str, delta: i64) {
+        let mut count_deltas = self.count_deltas.write()
+            .recover_poison();
+        *count_deltas.entry(collection.to_string()).or_insert(0) += delta;
+    }
+
+    /// Fail if giving `doc_id` the body `doc` would duplicate a value in one of
+    /// the collection's unique indexes. Call before writing the document, so a
+    /// rejected write leaves nothing behind.
+    pub(crate) fn check_unique_indexes(&self, collection: &str, doc_id: &str, doc: &serde_json::Map<String, serde_json::Value>) -> Result<()> {
+        let db = match &self.db {
+            Some(db) => db,
+            None => return Ok(()),
+        };
+        let metadata = db.get_metadata();
+        let coll_meta = match metadata.collections.get(collection) {
+            Some(coll_meta) => coll_meta,
+            None => return Ok(()),
+        };
+
+        let index_writes = self.index_writes.read()
+            .map_err(|_| Error::LockPoisoned { lock_name: "transaction.index_writes".to_string() })?;
+        index_writes.check_unique(coll_meta, &self.pager, collection, doc_id, doc)
+    }
+
+    /// Record that `doc_id` went from `before` to `after` (its body and new
+    /// page), so the collection's secondary indexes are updated on commit.
+    /// `None` stands for a document that didn't exist or was deleted. Writes
+    /// to collections without indexes aren't kept.
+    pub(crate) fn write_index_entries(
+        &self,
+        collection: &str,
+        doc_id: &str,
+        before: Option<serde_json::Map<String, serde_json::Value>>,
+        after: Option<(serde_json::Map<String, serde_json::Value>, PageNum)>,
+    ) {
+        let metadata = match &self.db {
+            Some(db) => db.get_metadata(),
+            None => return,
+        };
+        let coll_meta = match metadata.collections.get(collection) {
+            Some(coll_meta) if !coll_meta.indexes.is_empty() => coll_meta,
+            _ => return,
+        };
+
+        let mut index_writes = self.index_writes.write()
+            .recover_poison();
+        index_writes.record(coll_meta, collection, doc_id, before, after);
+    }
+
+    /// Check the buffered index writes against the unique indexes as they
+    /// stand now. Must be called with the commit lock held.
+    fn validate_index_writes(&self, db: &Database) -> Result<()> {
+        let index_writes = self.index_writes.read()
+            .map_err(|_| Error::LockPoisoned { lock_name: "transaction.index_writes".to_string() })?;
+        if index_writes.is_empty() {
+            return Ok(());
+        }
+        index_writes.validate(&db.get_metadata(), &self.pager)
+    }
+
+    /// Write the buffered index writes to the index B-trees and point metadata
+    /// at the new roots. Must be called with the commit lock held, after
+    /// `validate_index_writes`.
+    fn apply_index_writes(&self, db: &Database) -> Result<()> {
+        let index_writes = self.index_writes.read()
+            .map_err(|_| Error::LockPoisoned { lock_name: "transaction.index_writes".to_string() })?;
+        if index_writes.is_empty() {
+            return Ok(());
+        }
+        let roots = index_writes.apply(&db.get_metadata(), &self.pager)?;
+        db.update_metadata_no_flush(|m| store_index_roots(m, &roots));
+        Ok(())
+    }
+
+    /// Append the documents this transaction wrote to the change log, if the
+    /// database keeps one
+    fn log_replication(&self, db: &Database) -> Result<()> {
+        if !db.replication_log_enabled() {
+            return Ok(());
+        }
+        let writes = logged_writes(
+            &self.pager,
+            &self.doc_writes.read().recover_poison(),
+            &self.doc_existed_in_snapshot.read().recover_poison(),
+            &self.doc_before_images.read().recover_poison(),
+            &self.writes.read().recover_poison(),
+        )?;
+        replication::append(db, &self.pager, self.mvcc_tx_id, writes)
+    }
+
+    /// A `TxConflict` for `tx_id`, reported to the event hook first
+    pub(crate) fn conflict(&self, tx_id: u64, collection: &str, doc_id: Option<&str>) -> Error {
+        if let Some(db) = &self.db {
+            db.emit_event(|| DbEvent::TransactionConflict {
+                tx_id,
+                collection: collection.to_string(),
+                doc_id: doc_id.map(str::to_string),
+            });
+        }
+        Error::TxConflict
+    }
+
     pub(crate) fn set_collection_root(&self, collection: &str, new_root: PageNum) {
         let mut updated_roots = self.updated_roots.write()
             .recover_poison();
@@ -273,26 +605,23 @@ impl Transaction {
 
     fn detect_write_conflicts_with_context(
         &self,
+        pending: &crate::core::database::PendingWrite,
         collection_name: &str,
         current_root: PageNum,
-        snapshot_id: TransactionID,
-        doc_writes_map: &HashMap<String, HashMap<String, PageNum>>,
-        doc_existed_map: &HashMap<String, HashMap<String, bool>>,
-        doc_xmin_map: &HashMap<String, HashMap<String, TransactionID>>,
     ) -> Result<()> {
         use crate::core::tx_btree::TxBTree;
         use crate::core::document::read_versioned_document;
 
-        let collection_writes = match doc_writes_map.get(collection_name) {
+        let collection_writes = match pending.doc_writes.get(collection_name) {
             Some(writes) if !writes.is_empty() => writes,
             _ => return Ok(()), // No writes, no conflicts
         };
 
-        let collection_xmins = doc_xmin_map.get(collection_name);
+        let collection_xmins = pending.doc_original_xmin.get(collection_name);
 
         // Only create B-tree if we have documents that need conflict checking
         let needs_check = collection_writes.iter().any(|(doc_id, _)| {
-            let existed = doc_existed_map
+            let existed = pending.doc_existed_in_snapshot
                 .get(collection_name)
                 .and_then(|docs| docs.get(doc_id).copied())
                 .unwrap_or(false);
@@ -310,7 +639,7 @@ impl Transaction {
         // Check each document we wrote
         for (doc_id, _) in collection_writes.iter() {
             // Check if this document existed in our snapshot
-            let existed_in_snapshot = doc_existed_map
+            let existed_in_snapshot = pending.doc_existed_in_snapshot
                 .get(collection_name)
                 .and_then(|docs| docs.get(doc_id).copied())
                 .unwrap_or(false);
@@ -331,21 +660,21 @@ impl Transaction {
                         let empty_map = HashMap::new();
                         match read_versioned_document(&self.pager, committed_page_num, &empty_map) {
                             Ok(committed_vdoc) => {
-                                // Conflict if committed version is different and newer than our snapshot
+                                // Conflict if another transaction committed a version after the one we read.
+                                // Its ID may be below our snapshot's, since IDs are handed out at begin
+                                // and transactions commit in any order
                                 if committed_vdoc.xmin != orig_xmin {
-                                    if committed_vdoc.xmin > snapshot_id {
-                                        return Err(Error::TxConflict);
-                                    }
+                                    return Err(self.conflict(pending._tx_id, collection_name, Some(doc_id)));
                                 }
                             }
                             Err(_) => {
-                                return Err(Error::TxConflict);
+                                return Err(self.conflict(pending._tx_id, collection_name, Some(doc_id)));
                             }
                         }
                     }
                     Err(_) => {
                         // Document was deleted - CONFLICT
-                        return Err(Error::TxConflict);
+                        return Err(self.conflict(pending._tx_id, collection_name, Some(doc_id)));
                     }
                 }
             }
@@ -415,25 +744,22 @@ impl Transaction {
                         let empty_map = HashMap::new();
                         match read_versioned_document(&self.pager, committed_page_num, &empty_map) {
                             Ok(committed_vdoc) => {
-                                // Conflict if the committed version was created by a different transaction
-                                // after our snapshot AND is different from what we read
+                                // Conflict if another transaction committed a version after the one we read.
+                                // Its ID may be below our snapshot's, since IDs are handed out at begin
+                                // and transactions commit in any order
                                 if committed_vdoc.xmin != orig_xmin {
-                                    // Someone modified it - check if they committed after our snapshot
-                                    if committed_vdoc.xmin > self.snapshot_id {
-                                        // CONFLICT: Document modified after our snapshot
-                                        return Err(Error::TxConflict);
-                                    }
+                                    return Err(self.conflict(self.tx_id, collection_name, Some(doc_id)));
                                 }
                             }
                             Err(_) => {
                                 // Could not read document - might be corrupted, treat as conflict
-                                return Err(Error::TxConflict);
+                                return Err(self.conflict(self.tx_id, collection_name, Some(doc_id)));
                             }
                         }
                     }
                     Err(_) => {
                         // Document was deleted - CONFLICT
-                        return Err(Error::TxConflict);
+                        return Err(self.conflict(self.tx_id, collection_name, Some(doc_id)));
                     }
                 }
             }
@@ -443,10 +769,45 @@ impl Transaction {
     }
 
     pub fn commit(&mut self) -> Result<()> {
+        let written = self.written_collections();
+        self.try_commit()?;
+        if let Some(db) = &self.db {
+            // Only once the writes are visible, so a query can't cache what it
+            // read just before them under the new generation
+            for collection in &written {
+                db.invalidate_query_cache(collection);
+            }
+            let tx_id = self.tx_id;
+            db.emit_event(|| DbEvent::TransactionCommit { tx_id });
+        }
+        Ok(())
+    }
+
+    /// Collections this transaction has written to
+    fn written_collections(&self) -> HashSet<String> {
+        let mut written = self.modified_collections.read().recover_poison().clone();
+        written.extend(self.doc_writes.read().recover_poison().keys().cloned());
+        written.extend(self.count_deltas.read().recover_poison().keys().cloned());
+        written
+    }
+
+    fn try_commit(&mut self) -> Result<()> {
+        self.expire_if_past_deadline()?;
+
         if self.state != TxState::Active {
             return Err(Error::TxAlreadyDone);
         }
 
+        if self.read_only {
+            self.state = TxState::Committed;
+            self.release_snapshot()?;
+
+            if let Some(db) = &self.db {
+                db.metrics_ref().transaction_committed();
+            }
+            return Ok(());
+        }
+
         // Check if we have any writes (acquire and drop lock immediately)
         let has_writes = {
             let writes = self.writes.read()
@@ -455,6 +816,21 @@ impl Transaction {
         };
 
         if !has_writes {
+            // Deletes buffer no pages, so there is no commit below to persist
+            // their document counts and index entries
+            if let Some(db) = &self.db {
+                let count_deltas = self.count_deltas.read()
+                    .map_err(|_| Error::LockPoisoned { lock_name: "transaction.count_deltas".to_string() })?;
+                if !count_deltas.is_empty() {
+                    let _commit_guard = self.commit_mu.lock()
+                        .map_err(|_| Error::LockPoisoned { lock_name: "transaction.commit_mu".to_string() })?;
+                    self.validate_index_writes(db)?;
+                    self.apply_index_writes(db)?;
+                    self.log_replication(db)?;
+                    db.update_metadata_locked(|m| apply_count_deltas(m, &count_deltas))?;
+                }
+            }
+
             self.state = TxState::Committed;
             self.tx_manager.commit_transaction(self.mvcc_tx_id)?;
 
@@ -463,9 +839,20 @@ impl Transaction {
                 db.metrics_ref().transaction_committed();
             }
 
+            // Deletes don't buffer any pages but still produce change events
+            self.emit_change_events()?;
+
             return Ok(());
         }
 
+        // Only commits that write anything are timed
+        let _timer = self.db.as_ref().map(|db| db.metrics_ref().start_timer(Operation::Commit));
+
+        // A WAL at its size cap is checkpointed before this commit adds to it
+        if let Some(db) = &self.db {
+            db.checkpoint_if_wal_full()?;
+        }
+
         // Check if batching is enabled
         let batch_enabled = self.db.as_ref().map(|db| db.batch_config.enabled).unwrap_or(false);
 
@@ -477,112 +864,126 @@ impl Transaction {
     }
 
     fn commit_batched(&mut self) -> Result<()> {
-        use std::time::Instant;
-
         // Phase 1: Prepare write request
         let pending = self.prepare_write_request()?;
         let completion = pending.completion.clone();
 
         // Phase 2: Submit to queue
-        if let Some(db) = &self.db {
+        let db = self.db.clone().ok_or_else(|| Error::Other("database reference not set".into()))?;
+        {
             let mut queue = db.pending_writes.lock()
                 .map_err(|_| Error::LockPoisoned { lock_name: "database.pending_writes".to_string() })?;
             queue.push_back(pending);
-        } else {
-            return Err(Error::Other("database reference not set".into()));
         }
 
-        // Phase 3: Try to become leader by acquiring commit_mu
-        let _commit_guard = self.commit_mu.lock()
-            .map_err(|_| Error::LockPoisoned { lock_name: "transaction.commit_mu".to_string() })?;
+        // Phase 3: Become the leader, or wait for a leader to commit our write.
+        // Followers wait on their own completion rather than on commit_mu, so
+        // everyone in a batch returns as soon as it is durable instead of
+        // taking commit_mu one at a time behind the next batch.
+        let commit_guard = loop {
+            if completion_ready(&completion)? {
+                break None;
+            }
 
-        // Phase 3.5: Check if we've already been processed by another leader
-        {
-            let (lock, _) = &*completion;
-            let result = lock.lock()
-                .map_err(|_| Error::LockPoisoned { lock_name: "completion.lock".to_string() })?;
-            if result.is_some() {
-                // We've been processed by another leader, just wait and return
-                drop(result);
-                drop(_commit_guard);
-
-                // Wait for result
-                let (lock, cvar) = &*completion;
-                let mut result = lock.lock()
-                    .map_err(|_| Error::LockPoisoned { lock_name: "completion.lock".to_string() })?;
-                while result.is_none() {
-                    result = cvar.wait(result)
-                        .map_err(|_| Error::LockPoisoned { lock_name: "completion.condvar".to_string() })?;
+            match self.commit_mu.try_lock() {
+                Ok(guard) => {
+                    // A leader that finished just before we got the lock may
+                    // already have committed our write
+                    if completion_ready(&completion)? {
+                        break None;
+                    }
+                    break Some(guard);
+                }
+                Err(std::sync::TryLockError::WouldBlock) => {
+                    // The leader may drain the queue just before our write
+                    // joins it; it wakes the next writer when it steps down,
+                    // and the timeout covers a wake-up that comes too early
+                    let (lock, cvar) = &*completion;
+                    let result = lock.lock()
+                        .map_err(|_| Error::LockPoisoned { lock_name: "completion.lock".to_string() })?;
+                    if result.is_none() {
+                        let _ = cvar.wait_timeout(result, FOLLOWER_RECHECK_INTERVAL)
+                            .map_err(|_| Error::LockPoisoned { lock_name: "completion.condvar".to_string() })?;
+                    }
+                }
+                Err(std::sync::TryLockError::Poisoned(_)) => {
+                    return Err(Error::LockPoisoned { lock_name: "transaction.commit_mu".to_string() });
                 }
-
-                let commit_result = result.take().unwrap()?;
-                self.state = TxState::Committed;
-                return Ok(commit_result);
             }
-        }
+        };
 
-        loop {
-            let mut batch = self.collect_batch()?;
-            let batch_size = batch.len();
-
-            // Phase 5: Execute batch commit
-            let batch_start = Instant::now();
-            let batch_result = self.execute_batch_commit(&mut batch);
-
-            // Phase 6: Notify all waiters with results
-            for pending_write in batch {
-                let (lock, cvar) = &*pending_write.completion;
-                let mut result = lock.lock()
-                    .map_err(|_| Error::LockPoisoned { lock_name: "pending_write.completion".to_string() })?;
-                *result = Some(batch_result.clone());
-                cvar.notify_one();
-            }
+        if let Some(commit_guard) = commit_guard {
+            // Our write is still queued, so the first batch is never empty
+            loop {
+                let mut batch = self.collect_batch()?;
+                let batch_size = batch.len();
+
+                // Phase 4: Execute batch commit
+                let batch_start = Instant::now();
+                let batch_result = self.execute_batch_commit(&mut batch);
+
+                // Phase 5: Notify all waiters with results
+                for pending_write in batch {
+                    let (lock, cvar) = &*pending_write.completion;
+                    let mut result = lock.lock()
+                        .map_err(|_| Error::LockPoisoned { lock_name: "pending_write.completion".to_string() })?;
+                    *result = Some(batch_result.clone());
+                    cvar.notify_one();
+                }
 
-            // Phase 7: Track metrics
-            if let Some(db) = &self.db {
+                // Phase 6: Track metrics
                 let elapsed = batch_start.elapsed();
                 db.metrics_ref().batch_committed(batch_size, elapsed);
 
                 // Also track individual transaction commit
                 db.metrics_ref().transaction_committed();
                 db.maybe_auto_checkpoint();
-            }
 
-            // Check if there are more items in the queue
-            let queue_empty = {
-                let db = self.db.as_ref().ok_or_else(|| Error::Other("database reference not set".into()))?;
-                let queue = db.pending_writes.lock()
-                    .map_err(|_| Error::LockPoisoned { lock_name: "database.pending_writes".to_string() })?;
-                queue.is_empty()
-            };
+                // Check if there are more items in the queue
+                let queue_empty = {
+                    let queue = db.pending_writes.lock()
+                        .map_err(|_| Error::LockPoisoned { lock_name: "database.pending_writes".to_string() })?;
+                    queue.is_empty()
+                };
 
-            if queue_empty {
-                break;  // No more work, exit loop
+                if queue_empty {
+                    break;  // No more work, exit loop
+                }
+                // Otherwise, loop and process next batch
             }
-            // Otherwise, loop and process next batch
-        }
 
-        // Release commit_mu (implicit drop)
-        drop(_commit_guard);
+            drop(commit_guard);
 
-        // Phase 8: Wait for our own result
-        let (lock, cvar) = &*completion;
-        let mut result = lock.lock()
-            .map_err(|_| Error::LockPoisoned { lock_name: "completion.lock".to_string() })?;
-        while result.is_none() {
-            result = cvar.wait(result)
-                .map_err(|_| Error::LockPoisoned { lock_name: "completion.condvar".to_string() })?;
+            // A write queued after the last check would otherwise wait out
+            // its recheck interval before taking over as leader
+            let next = db.pending_writes.lock()
+                .map_err(|_| Error::LockPoisoned { lock_name: "database.pending_writes".to_string() })?
+                .front()
+                .map(|pending| pending.completion.clone());
+            if let Some(next) = next {
+                next.1.notify_all();
+            }
         }
 
-        let commit_result = result.take().unwrap()?;
+        // Phase 7: Collect our own result
+        let result = completion.0.lock()
+            .map_err(|_| Error::LockPoisoned { lock_name: "completion.lock".to_string() })?
+            .take()
+            .ok_or_else(|| Error::Other("batch commit finished without a result".into()))?;
+        result?;
 
         // Mark ourselves as committed
         self.state = TxState::Committed;
 
-        Ok(commit_result)
+        Ok(())
     }
 
     fn commit_single(&mut self) -> Result<()> {
+        // Conflicts are checked under the commit lock, so no other commit can
+        // change a document between the check and this commit writing it
+        let _commit_guard = self.commit_mu.lock()
+            .map_err(|_| Error::LockPoisoned { lock_name: "transaction.commit_mu".to_string() })?;
+
         if let Some(db) = &self.db {
             let modified = self.modified_collections.read()
                 .map_err(|_| Error::LockPoisoned { lock_name: "transaction.modified_collections".to_string() })?;
@@ -598,11 +999,10 @@ impl Transaction {
                 // (documents can be modified without changing the tree structure)
                 self.detect_write_conflicts(collection_name, current_root)?;
             }
-        }
+            drop(modified);
 
-        // Now acquire commit lock AFTER conflict detection
-        let _commit_guard = self.commit_mu.lock()
-            .map_err(|_| Error::LockPoisoned { lock_name: "transaction.commit_mu".to_string() })?;
+            self.validate_index_writes(db)?;
+        }
 
         // Conflict detection passed! Now write to WAL and pager.
         // Snapshot the writes to release the lock quickly
@@ -658,8 +1058,6 @@ impl Transaction {
 
                     self.wal.refresh_frame_count()?;
 
-                    self.validate_unique_constraints_for_collection(collection_name)?;
-
                     let mut page_remapping: HashMap<PageNum, PageNum> = HashMap::new();
                     let doc_writes_guard = self.doc_writes.read()
                         .map_err(|_| Error::LockPoisoned { lock_name: "transaction.doc_writes".to_string() })?;
@@ -720,7 +1118,14 @@ impl Transaction {
 
                     let mut rebased_btree = TxBTree::new(self.pager.clone(), current_root, self.writes.clone());
                     for (doc_id, page_num) in &documents_to_rebase {
-                        rebased_btree.insert(doc_id, *page_num)?;
+                        if *page_num != PageNum::MAX {
+                            rebased_btree.insert(doc_id, *page_num)?;
+                        }
+                    }
+                    for (doc_id, page_num) in &documents_to_rebase {
+                        if *page_num == PageNum::MAX {
+                            rebased_btree.delete(doc_id)?;
+                        }
                     }
 
                     {
@@ -764,36 +1169,22 @@ impl Transaction {
                 }
             }
 
-            let metadata = db.get_metadata();
-            let mut meta_data = metadata.serialize()?;
-            if meta_data.len() < PAGE_SIZE {
-                meta_data.resize(PAGE_SIZE, 0);
-            }
-
-            let meta_page = self.pager.metadata_page()?;
-            let meta_page = if meta_page == 0 {
-                self.pager.alloc_page()?
-            } else {
-                meta_page
-            };
+            self.apply_index_writes(db)?;
+            self.log_replication(db)?;
 
-            self.wal.write_frame(self.mvcc_tx_id, meta_page, meta_data.clone())?;
-            self.pager.write_page_transfer(meta_page, meta_data)?;
-
-            let mut header_data = self.pager.get_header_data()?;
-            header_data[24..32].copy_from_slice(&meta_page.to_le_bytes());
-            self.wal.write_frame(self.mvcc_tx_id, 0, header_data)?;
+            {
+                let count_deltas = self.count_deltas.read()
+                    .map_err(|_| Error::LockPoisoned { lock_name: "transaction.count_deltas".to_string() })?;
+                db.update_metadata_no_flush(|m| apply_count_deltas(m, &count_deltas));
+            }
 
-            self.pager.set_metadata_page(meta_page)?;
+            let logged: HashSet<PageNum> = self.writes.read().recover_poison().keys().copied().collect();
+            self.log_cached_pages(self.mvcc_tx_id, &logged)?;
+            db.log_metadata(self.mvcc_tx_id)?;
             self.pager.set_next_transaction_id(self.mvcc_tx_id + 1)?;
 
-            self.pager.write_header_no_sync()?;
-
-            self.wal.sync()?;
-
-            self.pager.flush_no_sync()?;
-
-            self.pager.sync_data_only()?;
+            let sync = db.commit_needs_sync();
+            self.write_back(sync)?;
         }
 
         self.state = TxState::Committed;
@@ -829,6 +1220,13 @@ impl Transaction {
         }
 
         // Emit change events for watchers
+        self.emit_change_events()?;
+
+        Ok(())
+    }
+
+    /// Notify watchers about every document this transaction wrote
+    fn emit_change_events(&self) -> Result<()> {
         if let Some(db) = &self.db {
             let watchers = db.get_watchers();
             let doc_writes = self.doc_writes.read()
@@ -837,28 +1235,30 @@ impl Transaction {
                 .map_err(|_| Error::LockPoisoned { lock_name: "transaction.doc_existed_in_snapshot".to_string() })?;
             let writes = self.writes.read()
                 .map_err(|_| Error::LockPoisoned { lock_name: "transaction.writes".to_string() })?;
+            let before_images = self.doc_before_images.read()
+                .map_err(|_| Error::LockPoisoned { lock_name: "transaction.doc_before_images".to_string() })?;
 
             for (collection_name, docs) in doc_writes.iter() {
                 for (doc_id, page_num) in docs.iter() {
-                    // Determine if this was an insert or update
-                    let is_insert = doc_existed
+                    let before_image = before_images
                         .get(collection_name)
-                        .and_then(|coll_docs| coll_docs.get(doc_id))
-                        .map(|existed| !existed)
-                        .unwrap_or(true); // Default to insert if not tracked
+                        .and_then(|coll_docs| coll_docs.get(doc_id));
+                    let operation = change_operation(&doc_existed, before_image.is_some(), collection_name, doc_id, *page_num);
 
-                    let operation = if is_insert {
-                        ChangeOperation::Insert
+                    // Read the document data (deleted documents have none)
+                    let document = if *page_num == PageNum::MAX {
+                        None
                     } else {
-                        ChangeOperation::Update
+                        read_versioned_document(&self.pager, *page_num, &writes)
+                            .ok()
+                            .and_then(|vdoc| decode_document(&vdoc.data).ok())
                     };
 
-                    // Read the document data
-                    let document = read_versioned_document(&self.pager, *page_num, &writes)
-                        .ok()
-                        .and_then(|vdoc| serde_json::from_slice(&vdoc.data).ok());
+                    let old_document = before_image
+                        .and_then(|data| data.as_ref())
+                        .and_then(|data| decode_document(data).ok());
 
-                    emit_change(&watchers, collection_name, operation, doc_id, document);
+                    emit_change(&watchers, collection_name, operation, doc_id, document, old_document);
                 }
             }
         }
@@ -884,16 +1284,103 @@ impl Transaction {
         }
 
         self.state = TxState::RolledBack;
-        self.tx_manager.abort_transaction(self.mvcc_tx_id)?;
+        self.release_snapshot()?;
 
         // Track metrics
         if let Some(db) = &self.db {
             db.metrics_ref().transaction_aborted();
+            let tx_id = self.tx_id;
+            db.emit_event(|| DbEvent::TransactionRollback { tx_id });
         }
 
         Ok(())
     }
 
+    /// Mark the current point in the transaction so it can be rolled back to later
+    ///
+    /// Savepoints cover document and page writes made through the transaction.
+    /// Collection creates, drops and renames update metadata immediately and are
+    /// not undone by rolling back to a savepoint. Reusing a name hides the earlier
+    /// savepoint with that name until the newer one is released.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use jasonisnthappy::Database;
+    /// # use serde_json::json;
+    /// # let db = Database::open("my.db").unwrap();
+    /// let mut tx = db.begin().unwrap();
+    /// tx.collection("users").unwrap().insert(json!({"name": "Alice"})).unwrap();
+    ///
+    /// tx.savepoint("batch").unwrap();
+    /// tx.collection("users").unwrap().insert(json!({"name": "Bob"})).unwrap();
+    /// tx.rollback_to_savepoint("batch").unwrap();
+    ///
+    /// // Only Alice is committed
+    /// tx.commit().unwrap();
+    /// ```
+    pub fn savepoint(&mut self, name: &str) -> Result<()> {
+        self.ensure_active()?;
+
+        let savepoint = Savepoint {
+            name: name.to_string(),
+            writes: self.writes.read().recover_poison().clone(),
+            doc_writes: self.doc_writes.read().recover_poison().clone(),
+            updated_roots: self.updated_roots.read().recover_poison().clone(),
+            doc_existed_in_snapshot: self.doc_existed_in_snapshot.read().recover_poison().clone(),
+            doc_original_xmin: self.doc_original_xmin.read().recover_poison().clone(),
+            modified_collections: self.modified_collections.read().recover_poison().clone(),
+            old_versions: self.old_versions.read().recover_poison().clone(),
+            doc_before_images: self.doc_before_images.read().recover_poison().clone(),
+            count_deltas: self.count_deltas.read().recover_poison().clone(),
+            index_writes: self.index_writes.read().recover_poison().clone(),
+        };
+        self.savepoints.push(savepoint);
+        Ok(())
+    }
+
+    /// Discard all writes made since the named savepoint was created
+    ///
+    /// The savepoint itself is kept, so it can be rolled back to again.
+    /// Savepoints created after it are removed.
+    pub fn rollback_to_savepoint(&mut self, name: &str) -> Result<()> {
+        self.ensure_active()?;
+
+        let pos = self.find_savepoint(name)?;
+        self.savepoints.truncate(pos + 1);
+        let savepoint = &self.savepoints[pos];
+
+        *self.writes.write().recover_poison() = savepoint.writes.clone();
+        *self.doc_writes.write().recover_poison() = savepoint.doc_writes.clone();
+        *self.updated_roots.write().recover_poison() = savepoint.updated_roots.clone();
+        *self.doc_existed_in_snapshot.write().recover_poison() = savepoint.doc_existed_in_snapshot.clone();
+        *self.doc_original_xmin.write().recover_poison() = savepoint.doc_original_xmin.clone();
+        *self.modified_collections.write().recover_poison() = savepoint.modified_collections.clone();
+        *self.old_versions.write().recover_poison() = savepoint.old_versions.clone();
+        *self.doc_before_images.write().recover_poison() = savepoint.doc_before_images.clone();
+        *self.count_deltas.write().recover_poison() = savepoint.count_deltas.clone();
+        *self.index_writes.write().recover_poison() = savepoint.index_writes.clone();
+
+        Ok(())
+    }
+
+    /// Forget the named savepoint, keeping all writes made since it was created
+    ///
+    /// Savepoints created after it are removed as well.
+    pub fn release_savepoint(&mut self, name: &str) -> Result<()> {
+        self.ensure_active()?;
+
+        let pos = self.find_savepoint(name)?;
+        self.savepoints.truncate(pos);
+        Ok(())
+    }
+
+    fn find_savepoint(&self, name: &str) -> Result<usize> {
+        self.savepoints
+            .iter()
+            .rposition(|sp| sp.name == name)
+            .ok_or_else(|| Error::SavepointNotFound { name: name.to_string() })
+    }
+
     pub fn get_snapshot_root(&self, collection: &str) -> Option<PageNum> {
         // Check updated_roots first (for writes in this transaction)
         // Then fall back to snapshot_roots (original snapshot)
@@ -930,100 +1417,6 @@ impl Transaction {
         collection_map.entry(doc_id.to_string()).or_insert(xmin);
     }
 
-    fn validate_unique_constraints_for_collection(&self, collection_name: &str) -> Result<()> {
-        let db = match &self.db {
-            Some(db) => db,
-            None => return Ok(()),
-        };
-
-        let doc_writes = self.doc_writes.read()
-            .map_err(|_| Error::LockPoisoned { lock_name: "transaction.doc_writes".to_string() })?;
-        let doc_existed = self.doc_existed_in_snapshot.read()
-            .map_err(|_| Error::LockPoisoned { lock_name: "transaction.doc_existed_in_snapshot".to_string() })?;
-
-        let docs = match doc_writes.get(collection_name) {
-            Some(docs) => docs,
-            None => return Ok(()),
-        };
-
-        let coll_name = collection_name;
-
-        let metadata = db.get_metadata();
-        let coll_meta = match metadata.collections.get(coll_name) {
-            Some(meta) => meta,
-            None => return Ok(()),
-        };
-
-        for (index_name, index_meta) in &coll_meta.indexes {
-            if !index_meta.unique {
-                continue;
-            }
-
-            use crate::core::btree::BTree;
-            let index_btree = if index_meta.btree_root == 0 {
-                continue;
-            } else {
-                BTree::open(self.pager.clone(), index_meta.btree_root)
-            };
-
-            for (doc_id, page_num) in docs {
-                let existed = doc_existed
-                    .get(coll_name)
-                    .and_then(|m| m.get(doc_id))
-                    .copied()
-                    .unwrap_or(false);
-
-                if !existed {
-                    use crate::core::document::read_versioned_document;
-                    let tx_writes_arc = self.get_writes_arc();
-                    let tx_writes = tx_writes_arc.read()
-                        .map_err(|_| Error::LockPoisoned { lock_name: "transaction.writes".to_string() })?;
-                    let vdoc = read_versioned_document(&self.pager, *page_num, &*tx_writes)?;
-
-                    let doc_map: serde_json::Map<String, serde_json::Value> =
-                        serde_json::from_slice(&vdoc.data)?;
-
-                    use crate::core::index_key::extract_field_values;
-
-                    let index_fields = index_meta.get_fields();
-                    let prefix = if index_fields.len() == 1 {
-                        // Single-field index
-                        let field_value = extract_field_values(&doc_map, &index_fields)[0].clone();
-                        let value_json = serde_json::to_string(&field_value)?;
-                        format!("{}|", value_json)
-                    } else {
-                        // Compound index
-                        let field_values = extract_field_values(&doc_map, &index_fields);
-                        let values_json: Vec<String> = field_values.iter()
-                            .map(|v| serde_json::to_string(v).unwrap_or_default())
-                            .collect();
-                        format!("{}|", values_json.join("|"))
-                    };
-
-                    let mut has_conflict = false;
-                    let mut iter = index_btree.iterator()?;
-                    while iter.next() {
-                        let (key, _value) = iter.entry();
-                        if key.starts_with(&prefix) {
-                            has_conflict = true;
-                            break;
-                        }
-                    }
-
-                    if has_conflict {
-                        return Err(Error::Other(format!(
-                            "unique constraint violation on index '{}' for collection '{}': \
-                            value was inserted by another transaction",
-                            index_name, coll_name
-                        )));
-                    }
-                }
-            }
-        }
-
-        Ok(())
-    }
-
     // ===== BATCH COMMIT METHODS =====
 
     fn prepare_write_request(&mut self) -> Result<crate::core::database::PendingWrite> {
@@ -1049,6 +1442,12 @@ impl Transaction {
 
         let doc_original_xmin = self.doc_original_xmin.read()
             .map_err(|_| Error::LockPoisoned { lock_name: "transaction.doc_original_xmin".to_string() })?.clone();
+        let doc_before_images = self.doc_before_images.read()
+            .map_err(|_| Error::LockPoisoned { lock_name: "transaction.doc_before_images".to_string() })?.clone();
+        let count_deltas = self.count_deltas.read()
+            .map_err(|_| Error::LockPoisoned { lock_name: "transaction.count_deltas".to_string() })?.clone();
+        let index_writes = self.index_writes.read()
+            .map_err(|_| Error::LockPoisoned { lock_name: "transaction.index_writes".to_string() })?.clone();
 
         Ok(crate::core::database::PendingWrite {
             writes,
@@ -1059,6 +1458,9 @@ impl Transaction {
             modified_collections,
             doc_existed_in_snapshot,
             doc_original_xmin,
+            doc_before_images,
+            count_deltas,
+            index_writes,
             _tx_id: self.tx_id,
             snapshot_id: self.snapshot_id,
             mvcc_tx_id: self.mvcc_tx_id,
@@ -1204,20 +1606,14 @@ impl Transaction {
                 )?;
 
                 if has_doc_conflict {
-                    return Err(Error::TxConflict); // Real document conflict - reject
+                    // Real document conflict - reject
+                    return Err(self.conflict(pending._tx_id, collection_name, None));
                 }
 
                 // No document conflicts - rebase will be needed
             } else {
                 // Root hasn't changed - still check for MVCC conflicts on documents
-                self.detect_write_conflicts_with_context(
-                    collection_name,
-                    current_root,
-                    pending.snapshot_id,
-                    &pending.doc_writes,
-                    &pending.doc_existed_in_snapshot,
-                    &pending.doc_original_xmin,
-                )?;
+                self.detect_write_conflicts_with_context(pending, collection_name, current_root)?;
             }
         }
 
@@ -1288,9 +1684,9 @@ impl Transaction {
                             let empty_map = HashMap::new();
                             match read_versioned_document(&self.pager, committed_page, &empty_map) {
                                 Ok(committed_vdoc) => {
-                                    // Check if document was modified after our snapshot
-                                    if committed_vdoc.xmin != orig_xmin
-                                       && committed_vdoc.xmin > snapshot_id {
+                                    // Check if another transaction committed a version after the one we read,
+                                    // whichever side of our snapshot its ID falls
+                                    if committed_vdoc.xmin != orig_xmin {
                                         return Ok(true); // Conflict detected
                                     }
                                 }
@@ -1431,6 +1827,32 @@ impl Transaction {
         Ok(())
     }
 
+    /// Log the cached pages a commit wrote straight through the pager, such as
+    /// index and change log pages, so the WAL describes every page it hands to
+    /// the database file. Pages in `logged` already have their frames.
+    fn log_cached_pages(&self, tx_id: TransactionID, logged: &HashSet<PageNum>) -> Result<()> {
+        for (page_num, page_data) in self.pager.dirty_pages() {
+            if !logged.contains(&page_num) {
+                self.wal.write_frame(tx_id, page_num, page_data)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Fsync the WAL frames of a commit, then write the header and cached
+    /// pages to the database file, fsyncing it too when `sync` is set. The WAL
+    /// is always fsynced first so the file never holds a page the WAL could
+    /// lose.
+    fn write_back(&self, sync: bool) -> Result<()> {
+        self.wal.sync()?;
+        self.pager.write_header_no_sync()?;
+        self.pager.flush_no_sync()?;
+        if sync {
+            self.pager.sync_data_only()?;
+        }
+        Ok(())
+    }
+
     fn execute_batch_commit(&self, batch: &mut [crate::core::database::PendingWrite]) -> Result<()> {
         let db = self.db.as_ref().ok_or_else(|| Error::Other("database reference not set".into()))?;
 
@@ -1443,6 +1865,14 @@ impl Transaction {
         // PHASE 2: Check for intra-batch conflicts (after rebase)
         self.check_batch_conflicts(batch)?;
 
+        // Unique values must not collide with committed documents or with
+        // another transaction in the batch
+        let mut batch_index_writes = IndexWrites::default();
+        for pending in batch.iter() {
+            batch_index_writes.extend(&pending.index_writes);
+        }
+        batch_index_writes.validate(&db.get_metadata(), &self.pager)?;
+
         // PHASE 3: Write all frames to WAL (buffered)
         for pending in batch.iter() {
             for (page_num, page_data) in &pending.writes {
@@ -1494,47 +1924,41 @@ impl Transaction {
                         });
                     }
                 }
-            }
 
-            let metadata = db.get_metadata();
-            let mut meta_data = metadata.serialize()?;
-            if meta_data.len() < PAGE_SIZE {
-                meta_data.resize(PAGE_SIZE, 0);
-            }
+                if !pending.index_writes.is_empty() {
+                    let roots = pending.index_writes.apply(&db.get_metadata(), &self.pager)?;
+                    db.update_metadata_no_flush(|m| store_index_roots(m, &roots));
+                }
 
-            let meta_page = self.pager.metadata_page()?;
-            let meta_page = if meta_page == 0 {
-                self.pager.alloc_page()?
-            } else {
-                meta_page
-            };
+                db.update_metadata_no_flush(|m| apply_count_deltas(m, &pending.count_deltas));
+
+                if db.replication_log_enabled() {
+                    let writes = logged_writes(
+                        &self.pager,
+                        &pending.doc_writes,
+                        &pending.doc_existed_in_snapshot,
+                        &pending.doc_before_images,
+                        &pending.writes,
+                    )?;
+                    replication::append(db, &self.pager, pending.mvcc_tx_id, writes)?;
+                }
+            }
 
             let max_tx_id = batch.iter().map(|p| p.mvcc_tx_id).max().unwrap_or(self.mvcc_tx_id);
-
-            self.wal.write_frame(max_tx_id, meta_page, meta_data.clone())?;
-            self.pager.write_page_transfer(meta_page, meta_data)?;
-
-            let mut header_data = self.pager.get_header_data()?;
-            header_data[24..32].copy_from_slice(&meta_page.to_le_bytes());
-            self.wal.write_frame(max_tx_id, 0, header_data)?;
-
-            self.pager.set_metadata_page(meta_page)?;
+            let logged: HashSet<PageNum> = batch.iter().flat_map(|p| p.writes.keys().copied()).collect();
+            self.log_cached_pages(max_tx_id, &logged)?;
+            db.log_metadata(max_tx_id)?;
             self.pager.set_next_transaction_id(max_tx_id + 1)?;
-
-            self.pager.write_header_no_sync()?;
         }
 
-        self.wal.sync()?;
-
         for pending in batch.iter() {
             for (page_num, page_data) in &pending.writes {
                 self.pager.write_page_transfer(*page_num, page_data.clone())?;
             }
         }
 
-        self.pager.flush_no_sync()?;
-
-        self.pager.sync_data_only()?;
+        let sync = db.commit_needs_sync();
+        self.write_back(sync)?;
 
         for pending in batch.iter() {
             self.tx_manager.commit_transaction(pending.mvcc_tx_id)?;
@@ -1565,27 +1989,24 @@ impl Transaction {
         for pending in batch.iter() {
             for (collection_name, docs) in pending.doc_writes.iter() {
                 for (doc_id, page_num) in docs.iter() {
-                    let is_insert = pending.doc_existed_in_snapshot
+                    let before_image = pending.doc_before_images
                         .get(collection_name)
-                        .and_then(|coll_docs| coll_docs.get(doc_id))
-                        .map(|existed| !existed)
-                        .unwrap_or(true);
-
-                    let operation = if is_insert {
-                        ChangeOperation::Insert
-                    } else {
-                        ChangeOperation::Update
-                    };
+                        .and_then(|coll_docs| coll_docs.get(doc_id));
+                    let operation = change_operation(&pending.doc_existed_in_snapshot, before_image.is_some(), collection_name, doc_id, *page_num);
 
                     // Read the document data from pending writes
                     let document = pending.writes.get(page_num)
                         .and_then(|_page_data| {
                             read_versioned_document(&self.pager, *page_num, &pending.writes)
                                 .ok()
-                                .and_then(|vdoc| serde_json::from_slice(&vdoc.data).ok())
+                                .and_then(|vdoc| decode_document(&vdoc.data).ok())
                         });
 
-                    emit_change(&watchers, collection_name, operation, doc_id, document);
+                    let old_document = before_image
+                        .and_then(|data| data.as_ref())
+                        .and_then(|data| decode_document(data).ok());
+
+                    emit_change(&watchers, collection_name, operation, doc_id, document, old_document);
                 }
             }
         }
@@ -1595,6 +2016,87 @@ impl Transaction {
 
 }
 
+/// Classify a document write for change events.
+///
+/// Deletes are recorded with a `PageNum::MAX` page. Everything else is an update
+/// if the document existed in the snapshot (or we captured its previous version),
+/// and an insert otherwise.
+fn change_operation(
+    doc_existed: &HashMap<String, HashMap<String, bool>>,
+    has_before_image: bool,
+    collection: &str,
+    doc_id: &str,
+    page_num: PageNum,
+) -> ChangeOperation {
+    if page_num == PageNum::MAX {
+        return ChangeOperation::Delete;
+    }
+
+    let existed = doc_existed
+        .get(collection)
+        .and_then(|coll_docs| coll_docs.get(doc_id))
+        .copied()
+        .unwrap_or(false); // Default to insert if not tracked
+
+    if existed || has_before_image {
+        ChangeOperation::Update
+    } else {
+        ChangeOperation::Insert
+    }
+}
+
+/// A transaction's document writes as change log entries, in collection and
+/// `_id` order. Writes to the log itself are left out.
+fn logged_writes(
+    pager: &Pager,
+    doc_writes: &HashMap<String, HashMap<String, PageNum>>,
+    doc_existed: &HashMap<String, HashMap<String, bool>>,
+    before_images: &DocBeforeImages,
+    writes: &HashMap<PageNum, Vec<u8>>,
+) -> Result<Vec<LoggedWrite>> {
+    let mut collections: Vec<&String> = doc_writes.keys()
+        .filter(|name| !replication::is_log(name))
+        .collect();
+    collections.sort();
+
+    let mut logged = Vec::new();
+    for collection in collections {
+        let docs = &doc_writes[collection];
+        let mut doc_ids: Vec<&String> = docs.keys().collect();
+        doc_ids.sort();
+
+        for doc_id in doc_ids {
+            let page_num = docs[doc_id];
+            let has_before_image = before_images
+                .get(collection)
+                .is_some_and(|coll_docs| coll_docs.contains_key(doc_id));
+            let operation = change_operation(doc_existed, has_before_image, collection, doc_id, page_num);
+            let document = if page_num == PageNum::MAX {
+                None
+            } else {
+                Some(decode_document(&read_versioned_document(pager, page_num, writes)?.data)?)
+            };
+            logged.push(LoggedWrite {
+                collection: collection.clone(),
+                operation,
+                doc_id: doc_id.clone(),
+                document,
+            });
+        }
+    }
+    Ok(logged)
+}
+
+/// Apply a transaction's net inserts/deletes to the document counts in metadata.
+/// Collections dropped by the transaction are skipped.
+fn apply_count_deltas(metadata: &mut Metadata, count_deltas: &HashMap<String, i64>) {
+    for (collection, delta) in count_deltas {
+        if let Some(count) = metadata.collections.get_mut(collection).and_then(|c| c.document_count.as_mut()) {
+            *count = count.saturating_add_signed(*delta);
+        }
+    }
+}
+
 impl Drop for Transaction {
     fn drop(&mut self) {
         if self.state == TxState::Active {
@@ -1605,9 +2107,13 @@ impl Drop for Transaction {
                 doc_writes.clear();
             }
 
-            let _ = self.tx_manager.abort_transaction(self.mvcc_tx_id);
+            let _ = self.release_snapshot();
 
             self.state = TxState::RolledBack;
+            if let Some(db) = &self.db {
+                let tx_id = self.tx_id;
+                db.emit_event(|| DbEvent::TransactionRollback { tx_id });
+            }
         }
     }
 }
@@ -218,18 +218,21 @@ impl TxBTree {
 
     pub fn iterator(&self) -> Result<TxBTreeIterator<'_>> {
         let root_page = self.get_current_root();
+        let mut path = Vec::new();
         let mut node = self.read_node(root_page)?;
 
         while node.node_type == NodeType::InternalNode {
             if node.children.is_empty() {
                 return Err(Error::Other("internal node has no children".to_string()));
             }
-            let child_page = node.children[0];
-            node = self.read_node(child_page)?;
+            let child = self.read_node(node.children[0])?;
+            path.push((node, 0));
+            node = child;
         }
 
         Ok(TxBTreeIterator {
             btree: self,
+            path,
             current_leaf: Some(node),
             index: 0,
             started: false,
@@ -262,6 +265,11 @@ impl TxBTree {
     }
 
     fn read_node(&self, page_num: PageNum) -> Result<BTreeNode> {
+        // Page 0 is the file header, so a zero root stands for an empty tree
+        if page_num == 0 {
+            return Ok(BTreeNode::new_leaf(0));
+        }
+
         let actual_page = self.cow_pages.get(&page_num).copied().unwrap_or(page_num);
 
         let data = {
@@ -306,7 +314,7 @@ impl TxBTree {
             }
         }
 
-        let mut data = get_node_serialize_buffer();
+        let mut data = get_node_serialize_buffer(self.pager.page_size());
         serialize_node_into(&node_to_write, &mut data, page_num);
 
         {
@@ -461,13 +469,14 @@ impl TxBTree {
         node.keys.pop(); // Remove the key at mid (now at end after split_off)
         node.num_keys = node.keys.len() as u16;
 
-        // Write both nodes
-        let _ = self.write_node(&node)?;
-        let _ = self.write_node(&new_node)?;
+        // Write both nodes. The left one may be copied on write, so link the
+        // page it actually landed on rather than the one it was read from
+        let actual_left_page = self.write_node(&node)?;
+        let actual_right_page = self.write_node(&new_node)?;
 
         // If splitting root, create new root
         if path.len() == 1 {
-            return self.create_new_root(node.page_num, new_node.page_num, promote_key);
+            return self.create_new_root(actual_left_page, actual_right_page, promote_key);
         }
 
         // Otherwise, insert promoted key into parent
@@ -476,17 +485,17 @@ impl TxBTree {
 
         // Update parent's child pointer if node was COW'd
         let old_node_page = path[path.len() - 1];
-        if old_node_page != node.page_num {
+        if old_node_page != actual_left_page {
             for child in parent.children.iter_mut() {
                 if *child == old_node_page {
-                    *child = node.page_num;
+                    *child = actual_left_page;
                     break;
                 }
             }
         }
 
         // Recursively insert into parent (which may cause further splits)
-        self.insert_into_parent(parent, promote_key, new_node.page_num, path[..path.len() - 1].to_vec())
+        self.insert_into_parent(parent, promote_key, actual_right_page, path[..path.len() - 1].to_vec())
     }
 
     fn update_path_after_modification(&mut self, _modified_page: PageNum, path: Vec<PageNum>) -> Result<()> {
@@ -649,6 +658,9 @@ impl TxBTree {
 
 pub struct TxBTreeIterator<'a> {
     btree: &'a TxBTree,
+    /// Internal nodes above the current leaf, each with the index of the
+    /// child being walked
+    path: Vec<(BTreeNode, usize)>,
     current_leaf: Option<BTreeNode>,
     index: usize,
     started: bool,
@@ -660,60 +672,49 @@ impl<'a> TxBTreeIterator<'a> {
             return false;
         }
 
-        if !self.started {
+        if self.started {
+            self.index += 1;
+        } else {
             self.started = true;
-            return self.current_leaf.as_ref().unwrap().entries.len() > 0;
         }
 
-        self.index += 1;
-
-        let current = self.current_leaf.as_ref().unwrap();
-        if self.index >= current.entries.len() {
-            let next_leaf_result = if current.next_leaf != 0 {
-                self.btree.read_node(current.next_leaf).ok()
-            } else {
-                None
-            };
-
-            let next_leaf = if let Some(leaf) = next_leaf_result {
-                let root = match self.btree.read_node(self.btree.get_current_root()) {
-                    Ok(r) => r,
-                    Err(_) => return false,
-                };
+        // Deletes can leave leaves empty, so keep going until one has entries
+        loop {
+            let current = self.current_leaf.as_ref().unwrap();
+            if self.index < current.entries.len() {
+                return true;
+            }
 
-                if self.btree.is_page_in_tree(&root, leaf.page_num) {
-                    Some(leaf)
-                } else {
-                    if let Some(last_entry) = current.entries.last() {
-                        self.btree.find_next_leaf_by_key(&root, &last_entry.key)
-                            .and_then(|page| self.btree.read_node(page).ok())
-                    } else {
-                        None
-                    }
+            match self.next_leaf() {
+                Ok(Some(next_leaf)) => {
+                    self.current_leaf = Some(next_leaf);
+                    self.index = 0;
                 }
-            } else {
-                let root = match self.btree.read_node(self.btree.get_current_root()) {
-                    Ok(r) => r,
-                    Err(_) => return false,
-                };
+                _ => return false,
+            }
+        }
+    }
 
-                if let Some(last_entry) = current.entries.last() {
-                    self.btree.find_next_leaf_by_key(&root, &last_entry.key)
-                        .and_then(|page| self.btree.read_node(page).ok())
-                } else {
-                    None
+    /// The leaf after the current one, found through the internal nodes above
+    /// it. Updates and deletes copy a leaf without relinking the leaf before
+    /// it, so its next_leaf pointer can still name the old page.
+    fn next_leaf(&mut self) -> Result<Option<BTreeNode>> {
+        while let Some((node, idx)) = self.path.last_mut() {
+            if *idx + 1 < node.children.len() {
+                *idx += 1;
+                let mut child = self.btree.read_node(node.children[*idx])?;
+                while child.node_type == NodeType::InternalNode {
+                    let first = *child.children.first()
+                        .ok_or_else(|| Error::Other("internal node has no children".to_string()))?;
+                    let grandchild = self.btree.read_node(first)?;
+                    self.path.push((child, 0));
+                    child = grandchild;
                 }
-            };
-
-            if let Some(next) = next_leaf {
-                self.current_leaf = Some(next);
-                self.index = 0;
-            } else {
-                return false;
+                return Ok(Some(child));
             }
+            self.path.pop();
         }
-
-        self.index < self.current_leaf.as_ref().unwrap().entries.len()
+        Ok(None)
     }
 
     pub fn entry(&self) -> (&str, u64) {
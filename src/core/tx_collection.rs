@@ -1,31 +1,31 @@
 
 use crate::core::tx_btree::TxBTree;
+use crate::core::binary::{decode_document, encode_document};
 use crate::core::document::{read_versioned_document, write_versioned_document};
 use crate::core::errors::*;
 use crate::core::transaction::Transaction;
 use crate::core::database::Database;
-use crate::core::metadata::IndexMeta;
+use crate::core::collection::{stamp_inserted_document, UpdateStamp};
 use crate::core::constants::PageNum;
+use crate::core::metrics::{CollectionMetrics, Operation};
+use crate::core::update_ops::apply_update;
+use crate::core::recycle_bin;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use serde_json::Value;
 use std::sync::Arc;
-use std::collections::HashMap;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 pub struct TxCollection<'tx> {
     tx: &'tx mut Transaction,
     name: String,
     btree: TxBTree,
-    indexes: HashMap<String, TxBTree>,
-    index_meta: HashMap<String, IndexMeta>,
+    db: Arc<Database>,
+    metrics: Arc<CollectionMetrics>,
 }
 
 impl<'tx> TxCollection<'tx> {
     pub(crate) fn new(tx: &'tx mut Transaction, db: Arc<Database>, name: String) -> Result<Self> {
-        let metadata = db.get_metadata();
-        let coll_meta = metadata.collections.get(&name);
-
         // Use the transaction's snapshot root, not the current committed root
         // This ensures we see a consistent snapshot view
         let btree_root = tx.get_snapshot_root(&name).unwrap_or(0);
@@ -33,30 +33,16 @@ impl<'tx> TxCollection<'tx> {
         let pager = tx.get_pager().clone();
         let tx_writes = tx.get_writes_arc();
 
-        let btree = if btree_root == 0 {
+        // Read-only transactions see a missing collection as empty rather than creating it
+        let btree = if btree_root == 0 && !tx.is_read_only() {
             TxBTree::create_empty(pager.clone(), tx_writes.clone())?
         } else {
             TxBTree::new(pager.clone(), btree_root, tx_writes.clone())
         };
 
-        let mut indexes = HashMap::new();
-        let mut index_meta = HashMap::new();
-
-        if let Some(coll) = coll_meta {
-            for (index_name, idx_meta) in &coll.indexes {
-                index_meta.insert(index_name.clone(), idx_meta.clone());
-
-                let index_btree = if idx_meta.btree_root == 0 {
-                    TxBTree::create_empty(pager.clone(), tx_writes.clone())?
-                } else {
-                    TxBTree::new(pager.clone(), idx_meta.btree_root, tx_writes.clone())
-                };
-
-                indexes.insert(index_name.clone(), index_btree);
-            }
-        }
+        let metrics = db.metrics_ref().collection(&name);
 
-        Ok(Self { tx, name, btree, indexes, index_meta })
+        Ok(Self { tx, name, btree, db, metrics })
     }
 
     pub fn name(&self) -> &str {
@@ -64,6 +50,8 @@ impl<'tx> TxCollection<'tx> {
     }
 
     pub fn insert(&mut self, doc: Value) -> Result<String> {
+        let _timer = self.db.metrics_ref().start_timer(Operation::Insert);
+
         if !doc.is_object() {
             return Err(Error::InvalidDocumentFormat {
                 reason: "document must be an object".to_string(),
@@ -71,9 +59,7 @@ impl<'tx> TxCollection<'tx> {
             });
         }
 
-        if !self.tx.is_active() {
-            return Err(Error::TxNotActive);
-        }
+        self.tx.ensure_writable()?;
 
         let mut doc_map = doc.as_object()
             .ok_or_else(|| Error::InvalidDocumentFormat {
@@ -90,12 +76,23 @@ impl<'tx> TxCollection<'tx> {
                 })?
                 .to_string()
         } else {
-            let id = generate_id();
+            let id = self.db.next_document_id(&self.name)?
+                .unwrap_or_else(generate_id);
             doc_map.insert("_id".to_string(), Value::String(id.clone()));
             id
         };
 
-        let data = serde_json::to_vec(&doc_map)?;
+        if self.db.document_metadata() {
+            stamp_inserted_document(&mut doc_map);
+        }
+
+        self.insert_stored(doc_id, doc_map)
+    }
+
+    /// Insert a document as given, with its `_id` already in place and no
+    /// metadata stamped on it
+    pub(crate) fn insert_stored(&mut self, doc_id: String, doc_map: serde_json::Map<String, Value>) -> Result<String> {
+        let data = encode_document(&doc_map, &self.name)?;
 
         let existed = self.btree.search(&doc_id).is_ok();
         self.tx.track_doc_existed_in_snapshot(&self.name, &doc_id, existed);
@@ -107,6 +104,8 @@ impl<'tx> TxCollection<'tx> {
             });
         }
 
+        self.tx.check_unique_indexes(&self.name, &doc_id, &doc_map)?;
+
         let pager = self.tx.get_pager();
         let mut tx_writes = std::collections::HashMap::new();
         let (page_num, _page_data) = write_versioned_document(
@@ -120,82 +119,31 @@ impl<'tx> TxCollection<'tx> {
 
         self.btree.insert(&doc_id, page_num)?;
 
-        for (index_name, index_btree) in &mut self.indexes {
-            let index_meta = &self.index_meta[index_name];
-            let index_fields = index_meta.get_fields();
-
-            use crate::core::index_key::{
-                IndexKey, serialize_index_key, extract_field_values,
-                CompoundIndexKey, serialize_compound_index_key
-            };
-
-            let key_str = if index_fields.len() == 1 {
-                // Single-field index
-                let field_value = extract_field_values(&doc_map, &index_fields)[0].clone();
-
-                if index_meta.unique {
-                    let value_json = serde_json::to_string(&field_value)?;
-                    let prefix = format!("{}|", value_json);
-
-                    if index_btree.has_prefix(&prefix)? {
-                        return Err(Error::Other(format!(
-                            "unique constraint violation on index {}: value {:?} already exists",
-                            index_name, field_value
-                        )));
-                    }
-                }
-
-                let index_key = IndexKey {
-                    field_value,
-                    doc_id: doc_id.clone(),
-                };
-                serialize_index_key(&index_key)?
-            } else {
-                // Compound index
-                let field_values = extract_field_values(&doc_map, &index_fields);
-
-                if index_meta.unique {
-                    // For compound unique constraints, serialize just the field values part
-                    let values_json: Vec<String> = field_values.iter()
-                        .map(|v| serde_json::to_string(v).unwrap_or_default())
-                        .collect();
-                    let prefix = format!("{}|", values_json.join("|"));
-
-                    if index_btree.has_prefix(&prefix)? {
-                        return Err(Error::Other(format!(
-                            "unique constraint violation on index {}: combination {:?} already exists",
-                            index_name, field_values
-                        )));
-                    }
-                }
-
-                let compound_key = CompoundIndexKey {
-                    field_values,
-                    doc_id: doc_id.clone(),
-                };
-                serialize_compound_index_key(&compound_key)?
-            };
-
-            index_btree.insert(&key_str, page_num)?;
-        }
-
         // Add all pages (including overflow pages) to transaction write buffer
         for (pg_num, pg_data) in tx_writes {
             self.tx.write_page(pg_num, pg_data)?;
         }
 
         self.tx.write_document(&self.name, &doc_id, page_num)?;
+        self.tx.adjust_document_count(&self.name, 1);
+        self.tx.write_index_entries(&self.name, &doc_id, None, Some((doc_map, page_num)));
         self.tx.set_collection_root(&self.name, self.btree.get_current_root());
 
         // Track metrics
         if let Some(db) = self.tx.get_database() {
             db.metrics_ref().document_inserted();
         }
+        self.metrics.inserted(1);
 
         Ok(doc_id)
     }
 
     pub fn find_by_id(&self, id: &str) -> Result<Value> {
+        let _timer = self.db.metrics_ref().start_timer(Operation::Find);
+        self.tx.check_deadline()?;
+        self.metrics.found();
+        self.metrics.index_hit();
+
         let page_num = self.btree.search(id)?;
         let pager = self.tx.get_pager();
 
@@ -221,7 +169,7 @@ impl<'tx> TxCollection<'tx> {
             self.tx.track_doc_original_xmin(&self.name, id, vdoc.xmin);
         }
 
-        let result: Value = serde_json::from_slice(&vdoc.data)?;
+        let result: Value = decode_document(&vdoc.data)?;
 
         // Track metrics
         if let Some(db) = self.tx.get_database() {
@@ -232,17 +180,32 @@ impl<'tx> TxCollection<'tx> {
     }
 
     pub fn update_by_id(&mut self, id: &str, updates: Value) -> Result<()> {
-
-        if !updates.is_object() {
-            return Err(Error::InvalidDocumentFormat {
+        let updates_map = updates.as_object()
+            .ok_or_else(|| Error::InvalidDocumentFormat {
                 reason: "updates must be an object".to_string(),
                 collection: Some(self.name.clone()),
-            });
-        }
+            })?;
+        self.rewrite_by_id(id, |doc| apply_update(doc, updates_map))
+    }
 
-        if !self.tx.is_active() {
-            return Err(Error::TxNotActive);
-        }
+    /// Replace the document with this ID by `doc` as a whole, where
+    /// `update_by_id` with plain fields would keep the fields `doc` lacks
+    pub(crate) fn replace_by_id(&mut self, id: &str, doc: serde_json::Map<String, Value>) -> Result<()> {
+        self.rewrite_by_id(id, |stored| {
+            *stored = doc;
+            Ok(())
+        })
+    }
+
+    /// Write a new version of the document with this ID, produced by `rewrite`
+    /// from the current one
+    fn rewrite_by_id<F>(&mut self, id: &str, rewrite: F) -> Result<()>
+    where
+        F: FnOnce(&mut serde_json::Map<String, Value>) -> Result<()>,
+    {
+        let _timer = self.db.metrics_ref().start_timer(Operation::Update);
+
+        self.tx.ensure_writable()?;
 
         let old_page_num = self.btree.search(id)?;
 
@@ -257,6 +220,11 @@ impl<'tx> TxCollection<'tx> {
         // Check if this is our own write first (always visible to us)
         let is_own_write = vdoc.xmin == self.tx.mvcc_tx_id;
 
+        // A version committed after our snapshot means another writer got here
+        // first; report a conflict so the caller can retry
+        if !is_own_write && vdoc.xmin > self.tx.snapshot_id {
+            return Err(self.tx.conflict(self.tx.tx_id, &self.name, Some(id)));
+        }
         if !is_own_write && !vdoc.is_visible(self.tx.snapshot_id) {
             return Err(Error::DocumentNotFound {
                 collection: self.name.clone(),
@@ -280,22 +248,24 @@ impl<'tx> TxCollection<'tx> {
                 page_num: old_page_num,
             };
             self.tx.add_old_version(&self.name, id, old_doc_version);
+            self.tx.track_doc_before_image(&self.name, id, &vdoc.data);
         }
 
-        let mut doc: serde_json::Map<String, Value> = serde_json::from_slice(&vdoc.data)?;
+        let mut doc: serde_json::Map<String, Value> = decode_document(&vdoc.data)?;
+        let before = doc.clone();
+        let stamp = self.db.document_metadata().then(|| UpdateStamp::of(&doc));
 
-        let updates_map = updates.as_object()
-            .ok_or_else(|| Error::InvalidDocumentFormat {
-                reason: "updates must be an object".to_string(),
-                collection: Some(self.name.clone()),
-            })?;
-        for (key, value) in updates_map {
-            doc.insert(key.clone(), value.clone());
-        }
+        rewrite(&mut doc)?;
 
         doc.insert("_id".to_string(), Value::String(id.to_string()));
 
-        let new_data = serde_json::to_vec(&doc)?;
+        if let Some(stamp) = stamp {
+            stamp.apply(&mut doc);
+        }
+
+        self.tx.check_unique_indexes(&self.name, id, &doc)?;
+
+        let new_data = encode_document(&doc, &self.name)?;
 
         let mut tx_writes = std::collections::HashMap::new();
         let (new_page_num, _page_data) = write_versioned_document(
@@ -317,6 +287,7 @@ impl<'tx> TxCollection<'tx> {
         }
 
         self.tx.write_document(&self.name, id, new_page_num)?;
+        self.tx.write_index_entries(&self.name, id, Some(before), Some((doc, new_page_num)));
 
         let new_root = self.btree.get_current_root();
         self.tx.set_collection_root(&self.name, new_root);
@@ -325,14 +296,14 @@ impl<'tx> TxCollection<'tx> {
         if let Some(db) = self.tx.get_database() {
             db.metrics_ref().document_updated();
         }
+        self.metrics.updated(1);
 
         Ok(())
     }
 
     pub fn delete_by_id(&mut self, id: &str) -> Result<()> {
-        if !self.tx.is_active() {
-            return Err(Error::TxNotActive);
-        }
+        let _timer = self.db.metrics_ref().start_timer(Operation::Delete);
+        self.tx.ensure_writable()?;
 
         let page_num = self.btree.search(id)?;
 
@@ -347,6 +318,11 @@ impl<'tx> TxCollection<'tx> {
         // Check if this is our own write first (always visible to us)
         let is_own_write = vdoc.xmin == self.tx.mvcc_tx_id;
 
+        // A version committed after our snapshot means another writer got here
+        // first; report a conflict so the caller can retry
+        if !is_own_write && vdoc.xmin > self.tx.snapshot_id {
+            return Err(self.tx.conflict(self.tx.tx_id, &self.name, Some(id)));
+        }
         if !is_own_write && !vdoc.is_visible(self.tx.snapshot_id) {
             return Err(Error::DocumentNotFound {
                 collection: self.name.clone(),
@@ -362,23 +338,38 @@ impl<'tx> TxCollection<'tx> {
                 page_num,
             };
             self.tx.add_old_version(&self.name, id, old_doc_version);
+            self.tx.track_doc_before_image(&self.name, id, &vdoc.data);
         }
 
         self.btree.delete(id)?;
 
         self.tx.write_document(&self.name, id, PageNum::MAX)?;
+        self.tx.adjust_document_count(&self.name, -1);
+        let before = decode_document::<serde_json::Map<String, Value>>(&vdoc.data);
+        if let Ok(before) = &before {
+            self.tx.write_index_entries(&self.name, id, Some(before.clone()), None);
+        }
 
         self.tx.set_collection_root(&self.name, self.btree.get_current_root());
 
+        if self.db.soft_delete(&self.name) {
+            recycle_bin::recycle(self.tx, &self.db, &self.name, id, before?)?;
+        }
+
         // Track metrics
         if let Some(db) = self.tx.get_database() {
             db.metrics_ref().document_deleted();
         }
+        self.metrics.deleted(1);
 
         Ok(())
     }
 
     pub fn find_all(&self) -> Result<Vec<Value>> {
+        let _timer = self.db.metrics_ref().start_timer(Operation::Find);
+        self.tx.check_deadline()?;
+        self.metrics.found();
+
         let mut results = Vec::new();
         let pager = self.tx.get_pager();
         let tx_writes_arc = self.tx.get_writes_arc();
@@ -394,7 +385,7 @@ impl<'tx> TxCollection<'tx> {
                     let is_own_write = vdoc.xmin == self.tx.mvcc_tx_id;
 
                     if is_own_write || vdoc.is_visible(self.tx.snapshot_id) {
-                        if let Ok(doc) = serde_json::from_slice(&vdoc.data) {
+                        if let Ok(doc) = decode_document(&vdoc.data) {
                             results.push(doc);
 
                             // Track metrics for each document read
@@ -408,10 +399,14 @@ impl<'tx> TxCollection<'tx> {
             }
         }
 
+        self.metrics.full_scan(results.len() as u64);
+
         Ok(results)
     }
 
     pub fn count(&self) -> Result<usize> {
+        self.tx.check_deadline()?;
+
         let mut count = 0;
         let pager = self.tx.get_pager();
         let tx_writes_arc = self.tx.get_writes_arc();
@@ -436,6 +431,8 @@ impl<'tx> TxCollection<'tx> {
             }
         }
 
+        self.metrics.full_scan(count as u64);
+
         Ok(count)
     }
 
@@ -468,14 +465,13 @@ impl<'tx> TxCollection<'tx> {
             Ok(value) => {
                 let typed = serde_json::from_value(value)
                     .map_err(|e| Error::DeserializationError {
-                        context: format!("find_by_id_typed in collection '{}', document '{}'", self.name, id),
+                        context: format!("find_by_id_typed into {} in collection '{}', document '{}'", std::any::type_name::<T>(), self.name, id),
                         error: e.to_string(),
                     })?;
                 Ok(Some(typed))
             }
             Err(Error::NotFound) => Ok(None),
             Err(Error::DocumentNotFound { .. }) => Ok(None),
-            Err(Error::Other(msg)) if msg.contains("not found") => Ok(None),
             Err(e) => Err(e),
         }
     }
@@ -488,7 +484,7 @@ impl<'tx> TxCollection<'tx> {
             .map(|value| {
                 serde_json::from_value(value)
                     .map_err(|e| Error::DeserializationError {
-                        context: format!("find_all_typed in collection '{}'", self.name),
+                        context: format!("find_all_typed into {} in collection '{}'", std::any::type_name::<T>(), self.name),
                         error: e.to_string(),
                     })
             })
@@ -0,0 +1,371 @@
+//! Applying an update document to a stored document.
+//!
+//! An update is either a set of plain fields, which replace the top-level
+//! fields of the same name, or a set of operators:
+//!
+//! - `$set` / `$unset`: set or remove fields
+//! - `$inc`: add to a number, starting from 0 if the field is missing
+//! - `$min` / `$max`: set a field only if the new value sorts below / above
+//!   the current one, in the order [`compare_values`] defines
+//! - `$rename`: move a field to a new path
+//! - `$push`: append to an array, or with `{"$each": [...], "$slice": n}`
+//!   append several values and keep only the first `n` (or last `-n`)
+//!
+//! Operator paths use dot notation, and numeric segments address array
+//! elements, so `{"$set": {"scores.0.value": 10}}` changes the first element.
+
+use crate::core::errors::*;
+use crate::core::index_key::compare_values;
+use serde_json::{Map, Number, Value};
+use std::cmp::Ordering;
+
+/// Whether `updates` uses operators rather than plain fields
+pub(crate) fn has_operators(updates: &Map<String, Value>) -> bool {
+    updates.keys().any(|key| key.starts_with('$'))
+}
+
+/// Apply `updates` to `doc`. On error `doc` may be partly updated, so callers
+/// apply it to a copy they can discard.
+pub(crate) fn apply_update(doc: &mut Map<String, Value>, updates: &Map<String, Value>) -> Result<()> {
+    if !has_operators(updates) {
+        for (key, value) in updates {
+            doc.insert(key.clone(), value.clone());
+        }
+        return Ok(());
+    }
+
+    if let Some(field) = updates.keys().find(|key| !key.starts_with('$')) {
+        return Err(invalid(format!(
+            "field '{}' can't be mixed with update operators; use $set", field
+        )));
+    }
+
+    let mut root = Value::Object(std::mem::take(doc));
+    let result = updates.iter().try_for_each(|(op, args)| apply_operator(&mut root, op, args));
+    if let Value::Object(map) = root {
+        *doc = map;
+    }
+    result
+}
+
+/// The document an upsert inserts when nothing matches: operators are
+/// applied to an empty document, plain fields are used as they are
+pub(crate) fn document_for_insert(updates: Value) -> Result<Value> {
+    match &updates {
+        Value::Object(map) if has_operators(map) => {
+            let mut doc = Map::new();
+            apply_update(&mut doc, map)?;
+            Ok(Value::Object(doc))
+        }
+        _ => Ok(updates),
+    }
+}
+
+fn invalid(reason: String) -> Error {
+    Error::InvalidUpdate { reason }
+}
+
+fn apply_operator(root: &mut Value, op: &str, args: &Value) -> Result<()> {
+    let args = args.as_object()
+        .ok_or_else(|| invalid(format!("{} takes an object of field paths", op)))?;
+
+    for (path, arg) in args {
+        let segments = parse_path(op, path)?;
+        match op {
+            "$set" => set(root, op, &segments, arg.clone())?,
+            "$unset" => {
+                remove(root, &segments);
+            }
+            "$inc" => inc(root, op, path, &segments, arg)?,
+            "$min" => set_if(root, op, &segments, arg, Ordering::Less)?,
+            "$max" => set_if(root, op, &segments, arg, Ordering::Greater)?,
+            "$rename" => rename(root, op, path, &segments, arg)?,
+            "$push" => push(root, op, path, &segments, arg)?,
+            _ => return Err(invalid(format!("unknown update operator '{}'", op))),
+        }
+    }
+    Ok(())
+}
+
+fn parse_path<'a>(op: &str, path: &'a str) -> Result<Vec<&'a str>> {
+    let segments: Vec<&str> = path.split('.').collect();
+    if segments.iter().any(|segment| segment.is_empty()) {
+        return Err(invalid(format!("{} has an empty segment in field path '{}'", op, path)));
+    }
+    if segments[0] == "_id" {
+        return Err(invalid(format!("{} can't change _id", op)));
+    }
+    Ok(segments)
+}
+
+/// The object or array holding the last segment of a path. With `create`,
+/// missing objects along the way are created, and anything that can't be
+/// descended into is an error; without it the result is None instead.
+fn parent_mut<'a>(root: &'a mut Value, op: &str, segments: &[&str], create: bool) -> Result<Option<&'a mut Value>> {
+    let mut current = root;
+    for (depth, segment) in segments[..segments.len() - 1].iter().enumerate() {
+        let next = match current {
+            Value::Object(map) => {
+                if create {
+                    Some(map.entry(segment.to_string()).or_insert_with(|| Value::Object(Map::new())))
+                } else {
+                    map.get_mut(*segment)
+                }
+            }
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get_mut(i)),
+            _ => None,
+        };
+        match next {
+            Some(value @ (Value::Object(_) | Value::Array(_))) => current = value,
+            _ if !create => return Ok(None),
+            _ => return Err(invalid(format!(
+                "{} can't reach '{}': '{}' is not an object or an existing array element",
+                op,
+                segments.join("."),
+                segments[..=depth].join(".")
+            ))),
+        }
+    }
+    Ok(Some(current))
+}
+
+fn child_mut<'a>(parent: &'a mut Value, key: &str) -> Option<&'a mut Value> {
+    match parent {
+        Value::Object(map) => map.get_mut(key),
+        Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get_mut(i)),
+        _ => None,
+    }
+}
+
+fn set(root: &mut Value, op: &str, segments: &[&str], value: Value) -> Result<()> {
+    let key = segments[segments.len() - 1];
+    match parent_mut(root, op, segments, true)? {
+        Some(Value::Object(map)) => {
+            map.insert(key.to_string(), value);
+            Ok(())
+        }
+        Some(Value::Array(items)) => match key.parse::<usize>().ok().and_then(|i| items.get_mut(i)) {
+            Some(slot) => {
+                *slot = value;
+                Ok(())
+            }
+            None => Err(invalid(format!(
+                "{} can't set '{}': the array has no element {}", op, segments.join("."), key
+            ))),
+        },
+        _ => unreachable!("parent_mut only returns objects and arrays"),
+    }
+}
+
+/// Remove a field, or null out an array element so later indexes don't shift
+fn remove(root: &mut Value, segments: &[&str]) -> Option<Value> {
+    let key = segments[segments.len() - 1];
+    match parent_mut(root, "$unset", segments, false).ok()?? {
+        Value::Object(map) => map.remove(key),
+        parent => child_mut(parent, key).map(std::mem::take),
+    }
+}
+
+fn get_mut<'a>(root: &'a mut Value, op: &str, segments: &[&str]) -> Result<Option<&'a mut Value>> {
+    let key = segments[segments.len() - 1];
+    Ok(parent_mut(root, op, segments, true)?.and_then(|parent| child_mut(parent, key)))
+}
+
+fn inc(root: &mut Value, op: &str, path: &str, segments: &[&str], amount: &Value) -> Result<()> {
+    let Value::Number(amount) = amount else {
+        return Err(invalid(format!("{} on '{}' needs a number, got {}", op, path, amount)));
+    };
+
+    let sum = match get_mut(root, op, segments)? {
+        None => Value::Number(amount.clone()),
+        Some(Value::Number(current)) => add_numbers(current, amount)
+            .ok_or_else(|| invalid(format!("{} on '{}' overflowed", op, path)))?,
+        Some(current) => return Err(invalid(format!(
+            "{} on '{}', which holds {} rather than a number", op, path, current
+        ))),
+    };
+    set(root, op, segments, sum)
+}
+
+fn add_numbers(a: &Number, b: &Number) -> Option<Value> {
+    if let (Some(a), Some(b)) = (a.as_i64(), b.as_i64()) {
+        if let Some(sum) = a.checked_add(b) {
+            return Some(Value::from(sum));
+        }
+    }
+    Number::from_f64(a.as_f64()? + b.as_f64()?).map(Value::Number)
+}
+
+fn set_if(root: &mut Value, op: &str, segments: &[&str], value: &Value, wanted: Ordering) -> Result<()> {
+    let replace = match get_mut(root, op, segments)? {
+        None => true,
+        Some(current) => compare_values(value, current) == wanted,
+    };
+    if replace {
+        set(root, op, segments, value.clone())?;
+    }
+    Ok(())
+}
+
+fn rename(root: &mut Value, op: &str, path: &str, segments: &[&str], target: &Value) -> Result<()> {
+    let target = target.as_str()
+        .ok_or_else(|| invalid(format!("{} of '{}' needs the new field path as a string", op, path)))?;
+    let target_segments = parse_path(op, target)?;
+    if target == path {
+        return Err(invalid(format!("{} of '{}' to itself", op, path)));
+    }
+
+    match remove(root, segments) {
+        Some(value) => set(root, op, &target_segments, value),
+        None => Ok(()),
+    }
+}
+
+fn push(root: &mut Value, op: &str, path: &str, segments: &[&str], arg: &Value) -> Result<()> {
+    let (values, slice) = match arg {
+        Value::Object(modifiers) if modifiers.contains_key("$each") => {
+            if let Some(key) = modifiers.keys().find(|key| *key != "$each" && *key != "$slice") {
+                return Err(invalid(format!("{} on '{}' doesn't support '{}'", op, path, key)));
+            }
+            let values = modifiers["$each"].as_array()
+                .ok_or_else(|| invalid(format!("{} on '{}' needs $each to be an array", op, path)))?
+                .clone();
+            let slice = match modifiers.get("$slice") {
+                None => None,
+                Some(n) => Some(n.as_i64()
+                    .ok_or_else(|| invalid(format!("{} on '{}' needs $slice to be an integer", op, path)))?),
+            };
+            (values, slice)
+        }
+        Value::Object(modifiers) if modifiers.contains_key("$slice") => {
+            return Err(invalid(format!("{} on '{}' needs $each to use $slice", op, path)));
+        }
+        value => (vec![value.clone()], None),
+    };
+
+    if get_mut(root, op, segments)?.is_none() {
+        set(root, op, segments, Value::Array(Vec::new()))?;
+    }
+    let items = match get_mut(root, op, segments)? {
+        Some(Value::Array(items)) => items,
+        Some(current) => return Err(invalid(format!(
+            "{} on '{}', which holds {} rather than an array", op, path, current
+        ))),
+        None => unreachable!("the field was just set"),
+    };
+
+    items.extend(values);
+    match slice {
+        Some(n) if n >= 0 => items.truncate(n as usize),
+        Some(n) => {
+            let keep = n.unsigned_abs() as usize;
+            if items.len() > keep {
+                items.drain(..items.len() - keep);
+            }
+        }
+        None => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn updated(doc: Value, updates: Value) -> Result<Value> {
+        let mut doc = doc.as_object().unwrap().clone();
+        apply_update(&mut doc, updates.as_object().unwrap())?;
+        Ok(Value::Object(doc))
+    }
+
+    #[test]
+    fn test_plain_fields_replace_top_level_fields() {
+        let doc = updated(json!({"a": {"b": 1}, "c": 2}), json!({"a": {"d": 3}})).unwrap();
+        assert_eq!(doc, json!({"a": {"d": 3}, "c": 2}));
+    }
+
+    #[test]
+    fn test_set_unset_and_inc() {
+        let doc = updated(
+            json!({"name": "a", "stats": {"visits": 1}, "old": true}),
+            json!({"$set": {"address.city": "Oslo"}, "$unset": {"old": ""}, "$inc": {"stats.visits": 2, "stats.likes": 1}}),
+        ).unwrap();
+        assert_eq!(doc, json!({"name": "a", "address": {"city": "Oslo"}, "stats": {"visits": 3, "likes": 1}}));
+
+        let doc = updated(json!({"n": 1}), json!({"$inc": {"n": 0.5}})).unwrap();
+        assert_eq!(doc["n"], 1.5);
+        assert!(updated(json!({"n": "x"}), json!({"$inc": {"n": 1}})).is_err());
+        assert!(updated(json!({"n": 1}), json!({"$inc": {"n": "1"}})).is_err());
+    }
+
+    #[test]
+    fn test_min_and_max() {
+        let doc = json!({"low": 5, "high": 5});
+        let doc = updated(doc, json!({"$min": {"low": 3, "new": 1}, "$max": {"high": 9}})).unwrap();
+        assert_eq!(doc, json!({"low": 3, "high": 9, "new": 1}));
+
+        let doc = updated(doc, json!({"$min": {"low": 4}, "$max": {"high": 8}})).unwrap();
+        assert_eq!(doc["low"], 3);
+        assert_eq!(doc["high"], 9);
+    }
+
+    #[test]
+    fn test_rename() {
+        let doc = updated(json!({"a": 1, "b": {"c": 2}}), json!({"$rename": {"a": "x.y", "b.c": "d", "missing": "e"}})).unwrap();
+        assert_eq!(doc, json!({"x": {"y": 1}, "b": {}, "d": 2}));
+
+        assert!(updated(json!({"a": 1}), json!({"$rename": {"a": 5}})).is_err());
+        assert!(updated(json!({"a": 1}), json!({"$rename": {"a": "a"}})).is_err());
+        assert!(updated(json!({"a": 1}), json!({"$rename": {"a": "_id"}})).is_err());
+    }
+
+    #[test]
+    fn test_push_each_and_slice() {
+        let doc = updated(json!({"tags": ["a"]}), json!({"$push": {"tags": "b", "new": 1}})).unwrap();
+        assert_eq!(doc, json!({"tags": ["a", "b"], "new": [1]}));
+
+        let doc = updated(doc, json!({"$push": {"tags": {"$each": ["c", "d", "e"], "$slice": -3}}})).unwrap();
+        assert_eq!(doc["tags"], json!(["c", "d", "e"]));
+        let doc = updated(doc, json!({"$push": {"tags": {"$each": [], "$slice": 1}}})).unwrap();
+        assert_eq!(doc["tags"], json!(["c"]));
+
+        let err = updated(json!({"tags": "a"}), json!({"$push": {"tags": "b"}})).unwrap_err();
+        assert!(err.to_string().contains("rather than an array"), "{}", err);
+        assert!(updated(json!({}), json!({"$push": {"tags": {"$slice": 1}}})).is_err());
+        assert!(updated(json!({}), json!({"$push": {"tags": {"$each": 1}}})).is_err());
+    }
+
+    #[test]
+    fn test_array_positions() {
+        let doc = json!({"scores": [{"value": 1}, {"value": 2}]});
+        let doc = updated(doc, json!({"$set": {"scores.0.value": 10}, "$inc": {"scores.1.value": 5}})).unwrap();
+        assert_eq!(doc, json!({"scores": [{"value": 10}, {"value": 7}]}));
+
+        let doc = updated(doc, json!({"$unset": {"scores.0": ""}})).unwrap();
+        assert_eq!(doc, json!({"scores": [null, {"value": 7}]}));
+
+        assert!(updated(doc.clone(), json!({"$set": {"scores.5.value": 1}})).is_err());
+        assert!(updated(doc, json!({"$set": {"scores.5": 1}})).is_err());
+    }
+
+    #[test]
+    fn test_invalid_updates() {
+        assert!(updated(json!({}), json!({"$set": {"a": 1}, "b": 2})).is_err());
+        assert!(updated(json!({}), json!({"$bogus": {"a": 1}})).is_err());
+        assert!(updated(json!({}), json!({"$set": 1})).is_err());
+        assert!(updated(json!({}), json!({"$set": {"a..b": 1}})).is_err());
+        assert!(updated(json!({}), json!({"$unset": {"_id": ""}})).is_err());
+        assert!(matches!(
+            updated(json!({"a": 1}), json!({"$set": {"a.b": 1}})),
+            Err(Error::InvalidUpdate { .. })
+        ));
+    }
+
+    #[test]
+    fn test_document_for_insert() {
+        let doc = document_for_insert(json!({"$set": {"name": "a"}, "$inc": {"n": 1}})).unwrap();
+        assert_eq!(doc, json!({"name": "a", "n": 1}));
+        assert_eq!(document_for_insert(json!({"name": "a"})).unwrap(), json!({"name": "a"}));
+    }
+}
@@ -1,8 +1,11 @@
 
+use crate::core::binary::{binary_bytes, is_binary};
 use crate::core::errors::*;
+use crate::core::pattern::Pattern;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::{HashMap, HashSet};
+use std::ops::ControlFlow;
 
 fn reserved_collection_names() -> HashSet<&'static str> {
     let mut set = HashSet::new();
@@ -69,17 +72,24 @@ pub struct Schema {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub maximum: Option<f64>,
 
-    /// Minimum length (for strings and arrays)
+    /// Minimum length (for strings, arrays and binary values)
     #[serde(rename = "minLength", skip_serializing_if = "Option::is_none")]
     pub min_length: Option<usize>,
 
-    /// Maximum length (for strings and arrays)
+    /// Maximum length (for strings, arrays and binary values)
     #[serde(rename = "maxLength", skip_serializing_if = "Option::is_none")]
     pub max_length: Option<usize>,
 
     /// Allowed values (enum)
     #[serde(rename = "enum", skip_serializing_if = "Option::is_none")]
     pub enum_values: Option<Vec<Value>>,
+
+    /// Regular expression strings must contain a match for (anchor with `^...$`
+    /// to match the whole string). Supports the usual ECMA-262 subset: classes,
+    /// groups, alternation, quantifiers and anchors, but not lookaround or
+    /// backreferences.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -92,6 +102,9 @@ pub enum ValueType {
     Object,
     Array,
     Null,
+    /// A `{"$binary": "<base64>"}` value; `minLength` and `maxLength`
+    /// bound its size in bytes
+    Binary,
 }
 
 impl Schema {
@@ -107,40 +120,117 @@ impl Schema {
             min_length: None,
             max_length: None,
             enum_values: None,
+            pattern: None,
         }
     }
 
-    /// Validate a document against this schema
+    /// Translate a standard JSON Schema (draft 2020-12 and earlier drafts that
+    /// share its keywords) into a native schema.
+    ///
+    /// `type`, `required`, `properties`, `items`, `enum`, `const`, `minimum`,
+    /// `maximum`, `minLength`, `maxLength`, `minItems`, `maxItems` and `pattern`
+    /// are translated. Annotations such as `title` or `description` are dropped.
+    /// Every other keyword is ignored and reported in the returned warnings,
+    /// each prefixed with the JSON pointer of the subschema it appeared in, so
+    /// callers can tell which constraints won't be enforced.
+    ///
+    /// The native format uses the same keyword names, so a serialized
+    /// [`Schema`] translates back to itself without warnings.
+    pub fn from_json_schema(value: &Value) -> Result<(Schema, Vec<String>)> {
+        let mut warnings = Vec::new();
+        let schema = translate_json_schema(value, "#", &mut warnings)?;
+        Ok((schema, warnings))
+    }
+
+    /// Fail if any `pattern` in this schema or its subschemas can't be compiled
+    pub(crate) fn check_patterns(&self) -> Result<()> {
+        if let Some(ref pattern) = self.pattern {
+            Pattern::compile(pattern)
+                .map_err(|e| Error::Other(format!("invalid schema pattern '{}': {}", pattern, e)))?;
+        }
+        for schema in self.properties.iter().flat_map(|p| p.values()) {
+            schema.check_patterns()?;
+        }
+        if let Some(ref items) = self.items {
+            items.check_patterns()?;
+        }
+        Ok(())
+    }
+
+    /// Validate a document against this schema, stopping at the first violation
     pub fn validate(&self, value: &Value) -> Result<()> {
-        self.validate_with_path(value, "")
+        let mut collector = Collector { errors: Vec::new(), all: false };
+        let _ = self.check(value, "", &mut collector);
+        match collector.errors.into_iter().next() {
+            Some(error) => Err(Error::SchemaValidation(error.message)),
+            None => Ok(()),
+        }
     }
 
-    fn validate_with_path(&self, value: &Value, path: &str) -> Result<()> {
+    /// Validate a document against this schema, reporting every violation
+    /// instead of only the first
+    pub fn validate_all(&self, value: &Value) -> std::result::Result<(), Vec<ValidationError>> {
+        let mut collector = Collector { errors: Vec::new(), all: true };
+        let _ = self.check(value, "", &mut collector);
+        if collector.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(collector.errors)
+        }
+    }
+
+    fn check(&self, value: &Value, path: &str, collector: &mut Collector) -> ControlFlow<()> {
         // Type validation
         if let Some(ref expected_type) = self.value_type {
-            self.validate_type(value, expected_type, path)?;
+            if !type_matches(expected_type, value) {
+                collector.fail(path, "type", format!(
+                    "Type mismatch at '{}': expected {:?}, got {}",
+                    path,
+                    expected_type,
+                    type_name(value)
+                ))?;
+            }
         }
 
         // Enum validation
         if let Some(ref allowed_values) = self.enum_values {
             if !allowed_values.contains(value) {
-                return Err(Error::SchemaValidation(format!(
+                collector.fail(path, "enum", format!(
                     "Value at '{}' must be one of {:?}, got: {}",
                     path, allowed_values, value
-                )));
+                ))?;
             }
         }
 
         match value {
+            _ if is_binary(value) => {
+                let len = binary_bytes(value).map(|bytes| bytes.len()).unwrap_or(0);
+                if let Some(min) = self.min_length {
+                    if len < min {
+                        collector.fail(path, "minLength", format!(
+                            "Binary value at '{}' length {} is less than minimum {}",
+                            path, len, min
+                        ))?;
+                    }
+                }
+                if let Some(max) = self.max_length {
+                    if len > max {
+                        collector.fail(path, "maxLength", format!(
+                            "Binary value at '{}' length {} exceeds maximum {}",
+                            path, len, max
+                        ))?;
+                    }
+                }
+            }
             Value::Object(obj) => {
                 // Required fields validation
                 if let Some(ref required) = self.required {
                     for field in required {
                         if !obj.contains_key(field) {
-                            return Err(Error::SchemaValidation(format!(
+                            collector.fail(path, "required", format!(
                                 "Missing required field '{}' at '{}'",
                                 field, path
-                            )));
+                            ))?;
                         }
                     }
                 }
@@ -154,7 +244,7 @@ impl Schema {
                             } else {
                                 format!("{}.{}", path, key)
                             };
-                            prop_schema.validate_with_path(val, &new_path)?;
+                            prop_schema.check(val, &new_path, collector)?;
                         }
                     }
                 }
@@ -163,22 +253,22 @@ impl Schema {
                 // Min/max length validation for arrays
                 if let Some(min) = self.min_length {
                     if arr.len() < min {
-                        return Err(Error::SchemaValidation(format!(
+                        collector.fail(path, "minLength", format!(
                             "Array at '{}' length {} is less than minimum {}",
                             path,
                             arr.len(),
                             min
-                        )));
+                        ))?;
                     }
                 }
                 if let Some(max) = self.max_length {
                     if arr.len() > max {
-                        return Err(Error::SchemaValidation(format!(
+                        collector.fail(path, "maxLength", format!(
                             "Array at '{}' length {} exceeds maximum {}",
                             path,
                             arr.len(),
                             max
-                        )));
+                        ))?;
                     }
                 }
 
@@ -186,7 +276,7 @@ impl Schema {
                 if let Some(ref items_schema) = self.items {
                     for (idx, item) in arr.iter().enumerate() {
                         let new_path = format!("{}[{}]", path, idx);
-                        items_schema.validate_with_path(item, &new_path)?;
+                        items_schema.check(item, &new_path, collector)?;
                     }
                 }
             }
@@ -194,22 +284,36 @@ impl Schema {
                 // Min/max length validation for strings
                 if let Some(min) = self.min_length {
                     if s.len() < min {
-                        return Err(Error::SchemaValidation(format!(
+                        collector.fail(path, "minLength", format!(
                             "String at '{}' length {} is less than minimum {}",
                             path,
                             s.len(),
                             min
-                        )));
+                        ))?;
                     }
                 }
                 if let Some(max) = self.max_length {
                     if s.len() > max {
-                        return Err(Error::SchemaValidation(format!(
+                        collector.fail(path, "maxLength", format!(
                             "String at '{}' length {} exceeds maximum {}",
                             path,
                             s.len(),
                             max
-                        )));
+                        ))?;
+                    }
+                }
+
+                if let Some(ref pattern) = self.pattern {
+                    match Pattern::compile(pattern) {
+                        Ok(compiled) if compiled.is_match(s) => {}
+                        Ok(_) => collector.fail(path, "pattern", format!(
+                            "String at '{}' does not match pattern '{}'",
+                            path, pattern
+                        ))?,
+                        Err(e) => collector.fail(path, "pattern", format!(
+                            "Invalid pattern '{}' at '{}': {}",
+                            pattern, path, e
+                        ))?,
                     }
                 }
             }
@@ -219,66 +323,98 @@ impl Schema {
                 // Minimum validation
                 if let Some(min) = self.minimum {
                     if num_val < min {
-                        return Err(Error::SchemaValidation(format!(
+                        collector.fail(path, "minimum", format!(
                             "Number at '{}' ({}) is less than minimum {}",
                             path, num_val, min
-                        )));
+                        ))?;
                     }
                 }
 
                 // Maximum validation
                 if let Some(max) = self.maximum {
                     if num_val > max {
-                        return Err(Error::SchemaValidation(format!(
+                        collector.fail(path, "maximum", format!(
                             "Number at '{}' ({}) exceeds maximum {}",
                             path, num_val, max
-                        )));
-                    }
-                }
-
-                // Integer validation
-                if let Some(ValueType::Integer) = self.value_type {
-                    if !n.is_i64() && !n.is_u64() {
-                        return Err(Error::SchemaValidation(format!(
-                            "Value at '{}' must be an integer, got: {}",
-                            path, n
-                        )));
+                        ))?;
                     }
                 }
             }
             _ => {}
         }
 
-        Ok(())
+        ControlFlow::Continue(())
+    }
+}
+
+/// One rule a value broke, as reported by [`Schema::validate_all`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValidationError {
+    /// Path to the value, like `address.city` or `tags[2]`; empty for the
+    /// document itself
+    pub path: String,
+    /// Schema keyword that failed: `type`, `required`, `enum`, `minimum`,
+    /// `maximum`, `minLength`, `maxLength` or `pattern`
+    pub rule: String,
+    /// Description of the failure, the same text [`Schema::validate`] reports
+    pub message: String,
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Turn the violations from [`Schema::validate_all`] into one
+/// `Error::SchemaValidation` listing all of them
+pub(crate) fn validation_errors_to_error(errors: Vec<ValidationError>) -> Error {
+    if errors.len() == 1 {
+        return Error::SchemaValidation(errors.into_iter().next().map(|e| e.message).unwrap_or_default());
     }
+    let messages: Vec<String> = errors.into_iter().map(|e| e.message).collect();
+    Error::SchemaValidation(format!("{} problems: {}", messages.len(), messages.join("; ")))
+}
 
-    fn validate_type(&self, value: &Value, expected: &ValueType, path: &str) -> Result<()> {
-        let matches = match (expected, value) {
-            (ValueType::String, Value::String(_)) => true,
-            (ValueType::Number, Value::Number(_)) => true,
-            (ValueType::Integer, Value::Number(n)) => n.is_i64() || n.is_u64(),
-            (ValueType::Boolean, Value::Bool(_)) => true,
-            (ValueType::Object, Value::Object(_)) => true,
-            (ValueType::Array, Value::Array(_)) => true,
-            (ValueType::Null, Value::Null) => true,
-            _ => false,
-        };
-
-        if !matches {
-            return Err(Error::SchemaValidation(format!(
-                "Type mismatch at '{}': expected {:?}, got {}",
-                path,
-                expected,
-                type_name(value)
-            )));
+/// Gathers violations while walking a schema; in fail-fast mode the first one
+/// breaks the walk
+struct Collector {
+    errors: Vec<ValidationError>,
+    all: bool,
+}
+
+impl Collector {
+    fn fail(&mut self, path: &str, rule: &str, message: String) -> ControlFlow<()> {
+        self.errors.push(ValidationError {
+            path: path.to_string(),
+            rule: rule.to_string(),
+            message,
+        });
+        if self.all {
+            ControlFlow::Continue(())
+        } else {
+            ControlFlow::Break(())
         }
+    }
+}
 
-        Ok(())
+fn type_matches(expected: &ValueType, value: &Value) -> bool {
+    match (expected, value) {
+        (ValueType::String, Value::String(_)) => true,
+        (ValueType::Number, Value::Number(_)) => true,
+        (ValueType::Integer, Value::Number(n)) => n.is_i64() || n.is_u64(),
+        (ValueType::Boolean, Value::Bool(_)) => true,
+        (ValueType::Object, Value::Object(_)) => true,
+        (ValueType::Array, Value::Array(_)) => true,
+        (ValueType::Null, Value::Null) => true,
+        (ValueType::Binary, value) => is_binary(value),
+        _ => false,
     }
 }
 
 fn type_name(value: &Value) -> &'static str {
     match value {
+        _ if is_binary(value) => "binary",
         Value::String(_) => "string",
         Value::Number(_) => "number",
         Value::Bool(_) => "boolean",
@@ -288,6 +424,104 @@ fn type_name(value: &Value) -> &'static str {
     }
 }
 
+/// Keywords that only describe a schema and never affect validation
+const ANNOTATION_KEYWORDS: &[&str] = &[
+    "$schema", "$id", "$comment", "$anchor", "title", "description",
+    "default", "examples", "deprecated", "readOnly", "writeOnly",
+];
+
+fn translate_json_schema(value: &Value, pointer: &str, warnings: &mut Vec<String>) -> Result<Schema> {
+    let invalid = |keyword: &str, expected: &str| {
+        Error::Other(format!("invalid JSON Schema at {}: '{}' must be {}", pointer, keyword, expected))
+    };
+
+    let obj = match value {
+        Value::Object(obj) => obj,
+        // `true` accepts everything, which an empty schema already does
+        Value::Bool(true) => return Ok(Schema::new()),
+        Value::Bool(false) => {
+            warnings.push(format!("{}: boolean schema 'false' is not supported and was ignored", pointer));
+            return Ok(Schema::new());
+        }
+        _ => return Err(Error::Other(format!("invalid JSON Schema at {}: expected an object or boolean", pointer))),
+    };
+
+    let length = |keyword: &str| -> Result<usize> {
+        obj[keyword].as_u64().map(|n| n as usize).ok_or_else(|| invalid(keyword, "a non-negative integer"))
+    };
+
+    // Native min/max length covers strings and arrays with one field each
+    let has_string_bounds = obj.contains_key("minLength") || obj.contains_key("maxLength");
+
+    let mut schema = Schema::new();
+    for (keyword, val) in obj {
+        match keyword.as_str() {
+            "type" => {
+                let single = match val {
+                    Value::Array(types) if types.len() == 1 => &types[0],
+                    Value::Array(_) => {
+                        warnings.push(format!("{}: 'type' with several types is not supported and was ignored", pointer));
+                        continue;
+                    }
+                    other => other,
+                };
+                schema.value_type = Some(serde_json::from_value(single.clone())
+                    .map_err(|_| invalid("type", "one of string, number, integer, boolean, object, array, null or binary"))?);
+            }
+            "required" => {
+                let fields = val.as_array()
+                    .and_then(|fields| fields.iter().map(|f| f.as_str().map(String::from)).collect::<Option<Vec<_>>>())
+                    .ok_or_else(|| invalid("required", "an array of strings"))?;
+                schema.required = Some(fields);
+            }
+            "properties" => {
+                let props = val.as_object().ok_or_else(|| invalid("properties", "an object"))?;
+                let mut properties = HashMap::new();
+                for (name, sub) in props {
+                    let sub_pointer = format!("{}/properties/{}", pointer, name.replace('~', "~0").replace('/', "~1"));
+                    properties.insert(name.clone(), translate_json_schema(sub, &sub_pointer, warnings)?);
+                }
+                schema.properties = Some(properties);
+            }
+            "items" if val.is_array() => {
+                warnings.push(format!("{}: tuple form of 'items' is not supported and was ignored", pointer));
+            }
+            "items" => {
+                let sub_pointer = format!("{}/items", pointer);
+                schema.items = Some(Box::new(translate_json_schema(val, &sub_pointer, warnings)?));
+            }
+            "minimum" => schema.minimum = Some(val.as_f64().ok_or_else(|| invalid("minimum", "a number"))?),
+            "maximum" => schema.maximum = Some(val.as_f64().ok_or_else(|| invalid("maximum", "a number"))?),
+            "enum" => schema.enum_values = Some(val.as_array().ok_or_else(|| invalid("enum", "an array"))?.clone()),
+            "const" => schema.enum_values = Some(vec![val.clone()]),
+            "minLength" => schema.min_length = Some(length(keyword)?),
+            "maxLength" => schema.max_length = Some(length(keyword)?),
+            "minItems" if !has_string_bounds => schema.min_length = Some(length(keyword)?),
+            "maxItems" if !has_string_bounds => schema.max_length = Some(length(keyword)?),
+            // Reported once below
+            "minItems" | "maxItems" => {}
+            "pattern" => {
+                let pattern = val.as_str().ok_or_else(|| invalid("pattern", "a string"))?;
+                match Pattern::compile(pattern) {
+                    Ok(_) => schema.pattern = Some(pattern.to_string()),
+                    Err(e) => warnings.push(format!("{}: pattern '{}' is not supported ({}) and was ignored", pointer, pattern, e)),
+                }
+            }
+            k if ANNOTATION_KEYWORDS.contains(&k) => {}
+            k => warnings.push(format!("{}: keyword '{}' is not supported and was ignored", pointer, k)),
+        }
+    }
+
+    if has_string_bounds && (obj.contains_key("minItems") || obj.contains_key("maxItems")) {
+        warnings.push(format!(
+            "{}: minItems/maxItems alongside minLength/maxLength is not supported; the item bounds were ignored",
+            pointer
+        ));
+    }
+
+    Ok(schema)
+}
+
 impl Default for Schema {
     fn default() -> Self {
         Self::new()
@@ -515,4 +749,126 @@ mod tests {
 
         assert_eq!(schema, deserialized);
     }
+
+    #[test]
+    fn test_pattern_validation() {
+        let mut schema = Schema::new();
+        schema.value_type = Some(ValueType::String);
+        schema.pattern = Some("^[a-z]+-\\d+$".to_string());
+
+        assert!(schema.validate(&json!("order-42")).is_ok());
+        assert!(schema.validate(&json!("Order-42")).is_err());
+        assert!(schema.validate(&json!("order-")).is_err());
+
+        schema.pattern = Some("(?=x)".to_string());
+        assert!(schema.check_patterns().is_err());
+    }
+
+    #[test]
+    fn test_binary_validation() {
+        use crate::core::binary::binary_value;
+
+        let mut schema = Schema::new();
+        schema.value_type = Some(ValueType::Binary);
+        schema.max_length = Some(4);
+
+        assert!(schema.validate(&binary_value(b"abcd")).is_ok());
+        assert!(schema.validate(&json!("abcd")).is_err());
+        assert!(schema.validate(&json!({"$binary": 1})).is_err());
+        let err = schema.validate(&binary_value(b"abcde")).unwrap_err();
+        assert!(err.to_string().contains("length 5 exceeds maximum 4"), "{}", err);
+
+        let (schema, _) = Schema::from_json_schema(&json!({"type": "binary"})).unwrap();
+        assert_eq!(schema.value_type, Some(ValueType::Binary));
+    }
+
+    #[test]
+    fn test_validate_all_reports_every_violation() {
+        let (schema, _) = Schema::from_json_schema(&json!({
+            "type": "object",
+            "required": ["name", "email"],
+            "properties": {
+                "age": {"type": "integer", "minimum": 0},
+                "tags": {"type": "array", "items": {"type": "string", "maxLength": 3}}
+            }
+        })).unwrap();
+
+        let errors = schema.validate_all(&json!({"age": -1, "tags": ["ok", 7, "toolong"]})).unwrap_err();
+        let found: Vec<(&str, &str)> = errors.iter().map(|e| (e.path.as_str(), e.rule.as_str())).collect();
+        assert_eq!(found, vec![
+            ("", "required"),
+            ("", "required"),
+            ("age", "minimum"),
+            ("tags[1]", "type"),
+            ("tags[2]", "maxLength"),
+        ]);
+        assert_eq!(errors[1].message, "Missing required field 'email' at ''");
+
+        // The fail-fast path reports the first of them
+        let first = schema.validate(&json!({"age": -1, "tags": ["ok", 7, "toolong"]})).unwrap_err();
+        assert_eq!(first.to_string(), format!("schema validation error: {}", errors[0].message));
+
+        assert!(schema.validate_all(&json!({"name": "a", "email": "b", "age": 3})).is_ok());
+    }
+
+    #[test]
+    fn test_from_json_schema() {
+        let (schema, warnings) = Schema::from_json_schema(&json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": "User",
+            "type": "object",
+            "required": ["name"],
+            "properties": {
+                "name": {"type": "string", "minLength": 1, "pattern": "^[A-Z]"},
+                "age": {"type": ["integer"], "minimum": 0, "maximum": 150},
+                "role": {"enum": ["admin", "user"]},
+                "kind": {"const": "person"},
+                "tags": {"type": "array", "items": {"type": "string"}, "maxItems": 3}
+            }
+        })).unwrap();
+
+        assert!(warnings.is_empty(), "unexpected warnings: {:?}", warnings);
+        assert!(schema.validate(&json!({"name": "Alice", "age": 30, "role": "admin", "kind": "person", "tags": ["a"]})).is_ok());
+        assert!(schema.validate(&json!({"age": 30})).is_err());
+        assert!(schema.validate(&json!({"name": "alice"})).is_err());
+        assert!(schema.validate(&json!({"name": "Alice", "age": 1.5})).is_err());
+        assert!(schema.validate(&json!({"name": "Alice", "role": "root"})).is_err());
+        assert!(schema.validate(&json!({"name": "Alice", "kind": "robot"})).is_err());
+        assert!(schema.validate(&json!({"name": "Alice", "tags": ["a", "b", "c", "d"]})).is_err());
+        assert!(schema.validate(&json!({"name": "Alice", "tags": [1]})).is_err());
+
+        // The native format reads back as itself
+        let native = serde_json::to_value(&schema).unwrap();
+        let (round_trip, warnings) = Schema::from_json_schema(&native).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(round_trip, schema);
+    }
+
+    #[test]
+    fn test_from_json_schema_warns_on_unsupported_keywords() {
+        let (schema, warnings) = Schema::from_json_schema(&json!({
+            "type": "object",
+            "additionalProperties": false,
+            "properties": {
+                "age": {"type": ["integer", "null"], "exclusiveMinimum": 0},
+                "code": {"type": "string", "pattern": "^(?<code>\\d+)$", "format": "uuid"}
+            }
+        })).unwrap();
+
+        assert_eq!(warnings, vec![
+            "#: keyword 'additionalProperties' is not supported and was ignored".to_string(),
+            "#/properties/age: keyword 'exclusiveMinimum' is not supported and was ignored".to_string(),
+            "#/properties/age: 'type' with several types is not supported and was ignored".to_string(),
+            "#/properties/code: keyword 'format' is not supported and was ignored".to_string(),
+            "#/properties/code: pattern '^(?<code>\\d+)$' is not supported (lookaround and named groups are not supported (position 1)) and was ignored".to_string(),
+        ]);
+
+        // What could be translated is still enforced
+        assert!(schema.validate(&json!({"code": 5})).is_err());
+        assert!(schema.validate(&json!({"code": "abc", "age": null})).is_ok());
+
+        assert!(Schema::from_json_schema(&json!({"required": "name"})).is_err());
+        assert!(Schema::from_json_schema(&json!({"type": "text"})).is_err());
+        assert!(Schema::from_json_schema(&json!("object")).is_err());
+    }
 }
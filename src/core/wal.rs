@@ -1,19 +1,20 @@
 
 use crate::core::constants::*;
 use crate::core::errors::*;
+use crate::core::events::{DbEvent, EventHooks};
 use crate::core::metrics::Metrics;
 use crate::core::pager::Pager;
 use std::fs::{File, OpenOptions};
 use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
-use std::path::Path;
-use std::sync::{Arc, Mutex, RwLock};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
 use std::time::{Duration, Instant};
 
 #[cfg(unix)]
 use std::os::unix::fs::OpenOptionsExt;
 
 const WAL_HEADER_SIZE: usize = 32;
-const WAL_FRAME_SIZE: usize = PAGE_SIZE + 28;
+/// Frame header (tx_id, page_num, salts) plus trailing checksum
+const WAL_FRAME_OVERHEAD: usize = 28;
 const STAT_CACHE_TTL: Duration = Duration::from_millis(100);
 const WAL_BUFFER_SIZE: usize = 64 * 1024;
 
@@ -43,6 +44,10 @@ struct WALInner {
     header: WALHeader,
     frame_num: u64,
     checksum_buf: Vec<u8>,
+    page_size: usize,
+    /// Set after the file was truncated to zero bytes; the header is written
+    /// back before the next frame
+    header_pending: bool,
 
     cached_file_size: i64,
     cache_timestamp: Option<Instant>,
@@ -52,14 +57,18 @@ struct WALInner {
 pub struct WAL {
     inner: Arc<Mutex<WALInner>>,
     metrics: Arc<RwLock<Option<Arc<Metrics>>>>,
+    events: OnceLock<Arc<EventHooks>>,
 }
 
 impl WAL {
-    #[cfg_attr(not(unix), allow(unused_variables))]
     pub fn open(db_path: &str, permissions: u32) -> Result<Self> {
-        let wal_path = format!("{}-wal", db_path);
+        Self::open_with_page_size(db_path, permissions, PAGE_SIZE)
+    }
 
-        let is_new = !Path::new(&wal_path).exists();
+    /// Open the WAL of a database whose pages are `page_size` bytes
+    #[cfg_attr(not(unix), allow(unused_variables))]
+    pub fn open_with_page_size(db_path: &str, permissions: u32, page_size: usize) -> Result<Self> {
+        let wal_path = format!("{}-wal", db_path);
 
         #[cfg(unix)]
         let file = OpenOptions::new()
@@ -76,6 +85,9 @@ impl WAL {
             .create(true)
             .open(&wal_path)?;
 
+        // A WAL left empty by `checkpoint_truncate` is started over like a new one
+        let is_new = file.metadata()?.len() == 0;
+
         let writer_file = file.try_clone()?;
         let writer = BufWriter::with_capacity(WAL_BUFFER_SIZE, writer_file);
 
@@ -89,7 +101,9 @@ impl WAL {
                 salt2: generate_salt(),
             },
             frame_num: 0,
-            checksum_buf: vec![0u8; 16 + PAGE_SIZE],
+            checksum_buf: vec![0u8; 16 + page_size],
+            page_size,
+            header_pending: false,
             cached_file_size: 0,
             cache_timestamp: None,
             file_position: -1,
@@ -105,6 +119,7 @@ impl WAL {
         Ok(WAL {
             inner: Arc::new(Mutex::new(inner)),
             metrics: Arc::new(RwLock::new(None)),
+            events: OnceLock::new(),
         })
     }
 
@@ -114,14 +129,29 @@ impl WAL {
         }
     }
 
-    pub fn write_frame(&self, tx_id: u64, page_num: u64, page_data: Vec<u8>) -> Result<()> {
-        if page_data.len() != PAGE_SIZE {
-            return Err(Error::InvalidPageSize);
+    pub(crate) fn set_event_hooks(&self, events: Arc<EventHooks>) {
+        let _ = self.events.set(events);
+    }
+
+    fn emit(&self, event: impl FnOnce() -> DbEvent) {
+        if let Some(events) = self.events.get() {
+            events.emit(event);
         }
+    }
 
+    pub fn write_frame(&self, tx_id: u64, page_num: u64, page_data: Vec<u8>) -> Result<()> {
         let mut inner = self.inner.lock()
             .map_err(|_| Error::LockPoisoned { lock_name: "wal.inner".to_string() })?;
 
+        if page_data.len() != inner.page_size {
+            return Err(Error::InvalidPageSize);
+        }
+        if inner.header_pending {
+            inner.write_header()?;
+            inner.header_pending = false;
+        }
+        let frame_size = inner.frame_size();
+
         let frame = WALFrame {
             tx_id,
             page_num,
@@ -134,10 +164,10 @@ impl WAL {
         let checksum = inner.calculate_checksum(&frame);
         let frame = WALFrame { checksum, ..frame };
 
-        let mut data = vec![0u8; WAL_FRAME_SIZE];
+        let mut data = vec![0u8; frame_size];
         serialize_frame_into(&frame, &mut data);
 
-        let offset = WAL_HEADER_SIZE as i64 + (inner.frame_num as i64 * WAL_FRAME_SIZE as i64);
+        let offset = WAL_HEADER_SIZE as i64 + (inner.frame_num as i64 * frame_size as i64);
 
         // Only seek if we're not at the expected position (e.g., after reads or file reopen)
         // For sequential writes, this optimization avoids unnecessary seeks and flushes
@@ -147,23 +177,31 @@ impl WAL {
         }
 
         inner.writer.write_all(&data)?;
-        inner.file_position = offset + WAL_FRAME_SIZE as i64;
+        inner.file_position = offset + frame_size as i64;
 
         inner.frame_num += 1;
-        inner.cached_file_size += WAL_FRAME_SIZE as i64;
+        inner.cached_file_size += frame_size as i64;
         inner.cache_timestamp = Some(Instant::now());
 
         // Track metrics (must happen after releasing the inner lock to avoid deadlock)
         drop(inner);
         if let Ok(m) = self.metrics.read() {
             if let Some(metrics) = m.as_ref() {
-                metrics.wal_write(WAL_FRAME_SIZE as u64);
+                metrics.wal_write(frame_size as u64);
             }
         }
 
         Ok(())
     }
 
+    /// Hand buffered frames to the OS without waiting for them to reach the disk
+    pub fn flush(&self) -> Result<()> {
+        let mut inner = self.inner.lock()
+            .map_err(|_| Error::LockPoisoned { lock_name: "wal.inner".to_string() })?;
+        inner.writer.flush()?;
+        Ok(())
+    }
+
     pub fn sync(&self) -> Result<()> {
         let mut inner = self.inner.lock()
             .map_err(|_| Error::LockPoisoned { lock_name: "wal.inner".to_string() })?;
@@ -178,6 +216,13 @@ impl WAL {
             .unwrap_or(0)
     }
 
+    /// Bytes taken by the header and the frames written since the last checkpoint
+    pub fn size_bytes(&self) -> u64 {
+        self.inner.lock()
+            .map(|inner| WAL_HEADER_SIZE as u64 + inner.frame_num * inner.frame_size() as u64)
+            .unwrap_or(0)
+    }
+
     pub fn refresh_frame_count(&self) -> Result<()> {
         let mut inner = self.inner.lock()
             .map_err(|_| Error::LockPoisoned { lock_name: "wal.inner".to_string() })?;
@@ -190,7 +235,7 @@ impl WAL {
                     return Ok(());
                 }
 
-                let file_frames = ((size - WAL_HEADER_SIZE as i64) / WAL_FRAME_SIZE as i64) as u64;
+                let file_frames = ((size - WAL_HEADER_SIZE as i64) / inner.frame_size() as i64) as u64;
                 if file_frames > inner.frame_num {
                     let metadata = inner.file.metadata()?;
                     inner.cached_file_size = metadata.len() as i64;
@@ -210,7 +255,7 @@ impl WAL {
             return Ok(());
         }
 
-        let file_frames = ((size - WAL_HEADER_SIZE as i64) / WAL_FRAME_SIZE as i64) as u64;
+        let file_frames = ((size - WAL_HEADER_SIZE as i64) / inner.frame_size() as i64) as u64;
 
         if file_frames > inner.frame_num {
             inner.frame_num = inner.count_frames();
@@ -227,10 +272,11 @@ impl WAL {
             return Err(Error::Other("EOF".to_string()));
         }
 
-        let offset = WAL_HEADER_SIZE as i64 + (frame_num as i64 * WAL_FRAME_SIZE as i64);
+        let frame_size = inner.frame_size();
+        let offset = WAL_HEADER_SIZE as i64 + (frame_num as i64 * frame_size as i64);
         inner.file.seek(SeekFrom::Start(offset as u64))?;
 
-        let mut data = vec![0u8; WAL_FRAME_SIZE];
+        let mut data = vec![0u8; frame_size];
         inner.file.read_exact(&mut data)?;
         inner.file_position = offset + data.len() as i64;
 
@@ -260,6 +306,18 @@ impl WAL {
     }
 
     pub fn checkpoint(&self, pager: &Pager) -> Result<()> {
+        self.checkpoint_to(pager, WAL_HEADER_SIZE as u64)
+    }
+
+    /// Checkpoint like `checkpoint`, then truncate the WAL file to zero bytes
+    /// instead of keeping its header
+    pub fn checkpoint_truncate(&self, pager: &Pager) -> Result<()> {
+        self.checkpoint_to(pager, 0)
+    }
+
+    /// Copy every valid frame into the database file, then cut the WAL file
+    /// down to `truncated_len` bytes: the header, or nothing at all
+    fn checkpoint_to(&self, pager: &Pager, truncated_len: u64) -> Result<()> {
         let mut inner = self.inner.lock()
             .map_err(|_| Error::LockPoisoned { lock_name: "wal.inner".to_string() })?;
 
@@ -271,11 +329,12 @@ impl WAL {
         let salt1 = inner.header.salt1;
         let salt2 = inner.header.salt2;
         let frame_count = inner.frame_num;
+        self.emit(|| DbEvent::CheckpointStart { frames: frame_count });
 
         inner.file.seek(SeekFrom::Start(WAL_HEADER_SIZE as u64))?;
 
         // Create BufReader in a scope to release the file borrow
-        let mut frame_buf = vec![0u8; WAL_FRAME_SIZE];
+        let mut frame_buf = vec![0u8; inner.frame_size()];
         let mut raw_frames = Vec::with_capacity(frame_count as usize);
 
         {
@@ -322,14 +381,21 @@ impl WAL {
         }
 
         let pages: Vec<(PageNum, Vec<u8>)> = page_map.into_iter().collect();
+        let pages_written = pages.len();
 
         pager.write_pages_direct(pages)?;
 
         pager.flush()?;
 
-        inner.file.set_len(WAL_HEADER_SIZE as u64)?;
+        inner.file.set_len(truncated_len)?;
+        if truncated_len == 0 {
+            inner.header_pending = true;
+        } else if inner.header_pending {
+            inner.write_header()?;
+            inner.header_pending = false;
+        }
         inner.frame_num = 0;
-        inner.cached_file_size = WAL_HEADER_SIZE as i64;
+        inner.cached_file_size = truncated_len as i64;
         inner.cache_timestamp = Some(Instant::now());
         inner.file_position = -1;
 
@@ -342,6 +408,8 @@ impl WAL {
                 metrics.checkpoint_completed();
             }
         }
+        self.emit(|| DbEvent::CheckpointEnd { pages_written });
+        self.emit(|| DbEvent::WalRotated { truncated_to: truncated_len });
 
         Ok(())
     }
@@ -355,6 +423,10 @@ impl WAL {
 }
 
 impl WALInner {
+    fn frame_size(&self) -> usize {
+        self.page_size + WAL_FRAME_OVERHEAD
+    }
+
     fn write_header(&mut self) -> Result<()> {
         let mut data = vec![0u8; WAL_HEADER_SIZE];
 
@@ -404,9 +476,9 @@ impl WALInner {
     fn calculate_checksum(&mut self, frame: &WALFrame) -> u32 {
         self.checksum_buf[0..8].copy_from_slice(&frame.tx_id.to_le_bytes());
         self.checksum_buf[8..16].copy_from_slice(&frame.page_num.to_le_bytes());
-        self.checksum_buf[16..16 + PAGE_SIZE].copy_from_slice(&frame.page_data);
+        self.checksum_buf[16..16 + self.page_size].copy_from_slice(&frame.page_data);
 
-        let mut crc = crc32_ieee(&self.checksum_buf[..16 + PAGE_SIZE]);
+        let mut crc = crc32_ieee(&self.checksum_buf[..16 + self.page_size]);
         crc ^= frame.salt1;
         crc ^= frame.salt2;
 
@@ -423,13 +495,14 @@ impl WALInner {
             return 0;
         }
 
-        let max_possible_frames = ((size - WAL_HEADER_SIZE as i64) / WAL_FRAME_SIZE as i64) as u64;
+        let frame_size = self.frame_size();
+        let max_possible_frames = ((size - WAL_HEADER_SIZE as i64) / frame_size as i64) as u64;
         let mut valid_frame_count = 0u64;
 
-        let mut data = vec![0u8; WAL_FRAME_SIZE];
+        let mut data = vec![0u8; frame_size];
 
         for i in 0..max_possible_frames {
-            let offset = WAL_HEADER_SIZE as i64 + (i as i64 * WAL_FRAME_SIZE as i64);
+            let offset = WAL_HEADER_SIZE as i64 + (i as i64 * frame_size as i64);
 
             if self.file.seek(SeekFrom::Start(offset as u64)).is_err() {
                 break;
@@ -464,17 +537,19 @@ impl WALInner {
 }
 
 fn serialize_frame_into(frame: &WALFrame, data: &mut [u8]) {
+    let page_size = data.len() - WAL_FRAME_OVERHEAD;
     data[0..8].copy_from_slice(&frame.tx_id.to_le_bytes());
     data[8..16].copy_from_slice(&frame.page_num.to_le_bytes());
     data[16..20].copy_from_slice(&frame.salt1.to_le_bytes());
     data[20..24].copy_from_slice(&frame.salt2.to_le_bytes());
-    data[24..24 + PAGE_SIZE].copy_from_slice(&frame.page_data);
-    data[24 + PAGE_SIZE..28 + PAGE_SIZE].copy_from_slice(&frame.checksum.to_le_bytes());
+    data[24..24 + page_size].copy_from_slice(&frame.page_data);
+    data[24 + page_size..28 + page_size].copy_from_slice(&frame.checksum.to_le_bytes());
 }
 
 fn parse_frame(data: &[u8]) -> Result<WALFrame> {
-    let mut page_data = vec![0u8; PAGE_SIZE];
-    page_data.copy_from_slice(&data[24..24 + PAGE_SIZE]);
+    let page_size = data.len() - WAL_FRAME_OVERHEAD;
+    let mut page_data = vec![0u8; page_size];
+    page_data.copy_from_slice(&data[24..24 + page_size]);
 
     Ok(WALFrame {
         tx_id: u64::from_le_bytes(data[0..8].try_into()?),
@@ -482,12 +557,13 @@ fn parse_frame(data: &[u8]) -> Result<WALFrame> {
         salt1: u32::from_le_bytes(data[16..20].try_into()?),
         salt2: u32::from_le_bytes(data[20..24].try_into()?),
         page_data,
-        checksum: u32::from_le_bytes(data[24 + PAGE_SIZE..28 + PAGE_SIZE].try_into()?),
+        checksum: u32::from_le_bytes(data[24 + page_size..28 + page_size].try_into()?),
     })
 }
 
 fn parse_frame_header(data: &[u8]) -> Result<WALFrame> {
-    let page_data = data[24..24 + PAGE_SIZE].to_vec();
+    let page_size = data.len() - WAL_FRAME_OVERHEAD;
+    let page_data = data[24..24 + page_size].to_vec();
 
     Ok(WALFrame {
         tx_id: u64::from_le_bytes(data[0..8].try_into()?),
@@ -495,7 +571,7 @@ fn parse_frame_header(data: &[u8]) -> Result<WALFrame> {
         salt1: u32::from_le_bytes(data[16..20].try_into()?),
         salt2: u32::from_le_bytes(data[20..24].try_into()?),
         page_data,
-        checksum: u32::from_le_bytes(data[24 + PAGE_SIZE..28 + PAGE_SIZE].try_into()?),
+        checksum: u32::from_le_bytes(data[24 + page_size..28 + page_size].try_into()?),
     })
 }
 
@@ -635,6 +711,50 @@ mod tests {
         let _ = fs::remove_file(format!("{}-wal", path));
     }
 
+    #[test]
+    fn test_wal_checkpoint_truncate() {
+        let path = "/tmp/test_wal_truncate.db";
+        let wal_path = format!("{}-wal", path);
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(&wal_path);
+
+        let pager = Pager::open(path, 100, 0o644, false).unwrap();
+        pager.set_num_pages(3).unwrap();
+        {
+            let wal = WAL::open(path, 0o644).unwrap();
+            for page_num in 1..3 {
+                wal.write_frame(1, page_num, vec![page_num as u8; PAGE_SIZE]).unwrap();
+            }
+            wal.sync().unwrap();
+            assert_eq!(wal.size_bytes(), (WAL_HEADER_SIZE + 2 * (PAGE_SIZE + WAL_FRAME_OVERHEAD)) as u64);
+
+            wal.checkpoint_truncate(&pager).unwrap();
+            assert_eq!(fs::metadata(&wal_path).unwrap().len(), 0);
+            assert_eq!(wal.frame_count(), 0);
+            assert_eq!(pager.read_page(2).unwrap()[0], 2);
+
+            // The header comes back with the first frame written afterwards
+            wal.write_frame(2, 1, vec![7u8; PAGE_SIZE]).unwrap();
+            wal.sync().unwrap();
+            assert_eq!(wal.size_bytes(), fs::metadata(&wal_path).unwrap().len());
+            assert_eq!(wal.read_frame(0).unwrap().page_data[0], 7);
+
+            wal.checkpoint_truncate(&pager).unwrap();
+        }
+
+        // Reopening an empty WAL starts it over
+        let wal = WAL::open(path, 0o644).unwrap();
+        assert_eq!(wal.frame_count(), 0);
+        assert_eq!(fs::metadata(&wal_path).unwrap().len(), WAL_HEADER_SIZE as u64);
+        wal.write_frame(3, 1, vec![9u8; PAGE_SIZE]).unwrap();
+        wal.sync().unwrap();
+        assert_eq!(wal.read_frame(0).unwrap().tx_id, 3);
+
+        wal.close().unwrap();
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(&wal_path);
+    }
+
     #[test]
     fn test_crc32_ieee() {
         assert_eq!(crc32_ieee(b""), 0);
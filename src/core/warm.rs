@@ -0,0 +1,116 @@
+use crate::core::btree::{deserialize_node, NodeType};
+use crate::core::constants::*;
+use crate::core::document::versioned_document_pages;
+use crate::core::errors::*;
+use crate::core::metadata::CollectionMeta;
+use crate::core::pager::Pager;
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Result of [`Database::warm_cache`](crate::Database::warm_cache)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CacheWarmup {
+    /// B-tree nodes read, from document trees and indexes
+    pub tree_pages: u64,
+    /// Document pages read, including continuation pages of large documents
+    pub document_pages: u64,
+    /// Whether warming stopped early because the page cache was full
+    pub cache_full: bool,
+}
+
+impl CacheWarmup {
+    /// Every page read into the cache
+    pub fn pages(&self) -> u64 {
+        self.tree_pages + self.document_pages
+    }
+}
+
+/// Read the B-trees of `collections` into the page cache, then their documents
+/// if `documents` is set. Stops once as many pages were read as the cache
+/// holds, so warming never evicts pages it loaded itself. Trees go first since
+/// every lookup walks them.
+pub(crate) fn warm(pager: &Pager, collections: &[&CollectionMeta], documents: bool) -> Result<CacheWarmup> {
+    let mut warmer = Warmer {
+        pager,
+        budget: pager.cache_capacity() as u64,
+        seen: HashSet::new(),
+        warmup: CacheWarmup::default(),
+    };
+
+    let mut document_pages = Vec::new();
+    for meta in collections {
+        let entries = warmer.tree(meta.btree_root)?;
+        if documents {
+            document_pages.extend(entries);
+        }
+        for index in meta.indexes.values() {
+            warmer.tree(index.btree_root)?;
+        }
+        for text_index in meta.text_indexes.values() {
+            warmer.tree(text_index.btree_root)?;
+        }
+    }
+
+    for page in document_pages {
+        if !warmer.take() {
+            break;
+        }
+        // Continuation pages are read along with the first one
+        let pages = versioned_document_pages(pager, page)?.len() as u64;
+        warmer.warmup.document_pages += pages;
+        warmer.budget = warmer.budget.saturating_sub(pages - 1);
+    }
+
+    Ok(warmer.warmup)
+}
+
+struct Warmer<'a> {
+    pager: &'a Pager,
+    /// Pages that can still be read before the cache is full
+    budget: u64,
+    /// Tree nodes read so far, so shared pages are read once
+    seen: HashSet<PageNum>,
+    warmup: CacheWarmup,
+}
+
+impl Warmer<'_> {
+    /// Claim one page of the budget, noting when it runs out
+    fn take(&mut self) -> bool {
+        if self.budget == 0 {
+            self.warmup.cache_full = true;
+            return false;
+        }
+        self.budget -= 1;
+        true
+    }
+
+    /// Read the nodes of the B-tree at `root` breadth first, so upper levels
+    /// are cached before the budget runs out, returning the values stored in
+    /// the leaves that were read
+    fn tree(&mut self, root: PageNum) -> Result<Vec<u64>> {
+        let mut values = Vec::new();
+        let mut level = if root == 0 { Vec::new() } else { vec![root] };
+
+        while !level.is_empty() {
+            let mut next = Vec::new();
+            for page in level {
+                if !self.seen.insert(page) {
+                    continue;
+                }
+                if !self.take() {
+                    return Ok(values);
+                }
+                let node = deserialize_node(page, &self.pager.read_page(page)?)?;
+                self.warmup.tree_pages += 1;
+                if node.node_type == NodeType::LeafNode {
+                    values.extend(node.entries.iter().map(|entry| entry.value));
+                } else {
+                    next.extend(node.children.iter().copied());
+                }
+            }
+            level = next;
+        }
+
+        Ok(values)
+    }
+}
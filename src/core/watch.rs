@@ -1,12 +1,14 @@
 use crate::core::errors::*;
 use crate::core::query::parser::parse_query;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::sync::mpsc::{Sender, Receiver, channel};
 use std::sync::{Arc, RwLock};
 use std::collections::HashMap;
 
 /// Type of change operation
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ChangeOperation {
     /// A document was inserted
     Insert,
@@ -16,8 +18,29 @@ pub enum ChangeOperation {
     Delete,
 }
 
+impl ChangeOperation {
+    /// Parse `"insert"`, `"update"` or `"delete"` (case-insensitive)
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "insert" => Some(ChangeOperation::Insert),
+            "update" => Some(ChangeOperation::Update),
+            "delete" => Some(ChangeOperation::Delete),
+            _ => None,
+        }
+    }
+
+    /// `"insert"`, `"update"` or `"delete"`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeOperation::Insert => "insert",
+            ChangeOperation::Update => "update",
+            ChangeOperation::Delete => "delete",
+        }
+    }
+}
+
 /// Event emitted when a document changes
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ChangeEvent {
     /// The collection name where the change occurred
     pub collection: String,
@@ -27,6 +50,10 @@ pub struct ChangeEvent {
     pub doc_id: String,
     /// The document data (None for Delete operations)
     pub document: Option<Value>,
+    /// The document as it was before the change (only populated for Update and
+    /// Delete operations when the watcher was created with
+    /// `with_full_document_before_change()`)
+    pub old_document: Option<Value>,
 }
 
 /// Internal watcher structure
@@ -34,11 +61,18 @@ pub(crate) struct Watcher {
     pub(crate) id: String,
     pub(crate) sender: Sender<ChangeEvent>,
     pub(crate) filter: Option<String>,
+    /// Operations to send events for, all of them if `None`
+    pub(crate) operations: Option<Vec<ChangeOperation>>,
+    pub(crate) full_document_before_change: bool,
 }
 
 impl Watcher {
-    /// Check if this watcher should receive the event based on its filter
+    /// Check if this watcher should receive the event based on its operations
+    /// and filter
     pub(crate) fn matches(&self, event: &ChangeEvent) -> bool {
+        if self.operations.as_ref().is_some_and(|ops| !ops.contains(&event.operation)) {
+            return false;
+        }
         if let Some(filter) = &self.filter {
             if let Some(doc) = &event.document {
                 // Parse and evaluate the filter query
@@ -58,7 +92,10 @@ impl Watcher {
     }
 
     /// Send an event to this watcher
-    pub(crate) fn send(&self, event: ChangeEvent) -> bool {
+    pub(crate) fn send(&self, mut event: ChangeEvent) -> bool {
+        if !self.full_document_before_change {
+            event.old_document = None;
+        }
         self.sender.send(event).is_ok()
     }
 }
@@ -71,11 +108,27 @@ pub(crate) fn new_watcher_storage() -> WatcherStorage {
     Arc::new(RwLock::new(HashMap::new()))
 }
 
+/// Check which kind of before-image the watchers of a collection need.
+///
+/// Returns `None` if nobody is watching the collection, `Some(false)` if there
+/// are watchers but none of them asked for the pre-change document, and
+/// `Some(true)` if at least one watcher wants it.
+pub(crate) fn before_change_interest(storage: &WatcherStorage, collection: &str) -> Option<bool> {
+    let storage = storage.read().ok()?;
+    let watchers = storage.get(collection)?;
+    if watchers.is_empty() {
+        return None;
+    }
+    Some(watchers.iter().any(|w| w.full_document_before_change))
+}
+
 /// Builder for creating a watcher
 pub struct WatchBuilder<'a> {
     collection: &'a str,
     storage: WatcherStorage,
     filter: Option<String>,
+    operations: Option<Vec<ChangeOperation>>,
+    full_document_before_change: bool,
 }
 
 impl<'a> WatchBuilder<'a> {
@@ -84,6 +137,8 @@ impl<'a> WatchBuilder<'a> {
             collection,
             storage,
             filter: None,
+            operations: None,
+            full_document_before_change: false,
         }
     }
 
@@ -107,6 +162,60 @@ impl<'a> WatchBuilder<'a> {
         self
     }
 
+    /// Only send events for these operations. Events are checked against
+    /// them before the filter, and before anything is sent, so other
+    /// operations never reach the receiver.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use jasonisnthappy::{ChangeOperation, Database};
+    ///
+    /// # fn main() -> jasonisnthappy::Result<()> {
+    /// let db = Database::open("my.db")?;
+    /// let collection = db.collection("users");
+    /// let (_handle, rx) = collection.watch()
+    ///     .operations(&[ChangeOperation::Insert])
+    ///     .filter("age > 18")
+    ///     .subscribe()?;
+    ///
+    /// while let Ok(event) = rx.recv() {
+    ///     println!("inserted {}", event.doc_id);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn operations(mut self, operations: &[ChangeOperation]) -> Self {
+        self.operations = Some(operations.to_vec());
+        self
+    }
+
+    /// Include the previous version of the document in Update and Delete events
+    ///
+    /// The pre-change document is only read when at least one watcher asks for
+    /// it, so leave this off if you don't need `ChangeEvent::old_document`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use jasonisnthappy::Database;
+    ///
+    /// # fn main() -> jasonisnthappy::Result<()> {
+    /// let db = Database::open("my.db")?;
+    /// let collection = db.collection("users");
+    /// let (_handle, rx) = collection.watch()
+    ///     .with_full_document_before_change()
+    ///     .subscribe()?;
+    ///
+    /// while let Ok(event) = rx.recv() {
+    ///     println!("{:?} -> {:?}", event.old_document, event.document);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_full_document_before_change(mut self) -> Self {
+        self.full_document_before_change = true;
+        self
+    }
+
     /// Subscribe to changes and return a channel receiver
     ///
     /// # Returns
@@ -121,6 +230,8 @@ impl<'a> WatchBuilder<'a> {
             id: watcher_id.clone(),
             sender,
             filter: self.filter,
+            operations: self.operations,
+            full_document_before_change: self.full_document_before_change,
         };
 
         // Add watcher to storage
@@ -190,12 +301,14 @@ pub(crate) fn emit_change(
     operation: ChangeOperation,
     doc_id: &str,
     document: Option<Value>,
+    old_document: Option<Value>,
 ) {
     let event = ChangeEvent {
         collection: collection.to_string(),
         operation,
         doc_id: doc_id.to_string(),
         document,
+        old_document,
     };
 
     // Send to all matching watchers and collect dead ones
@@ -260,6 +373,8 @@ mod tests {
             id: "test".to_string(),
             sender: tx,
             filter: None,
+            operations: None,
+            full_document_before_change: false,
         };
 
         let event = ChangeEvent {
@@ -267,6 +382,7 @@ mod tests {
             operation: ChangeOperation::Insert,
             doc_id: "1".to_string(),
             document: Some(json!({"name": "Alice", "age": 30})),
+            old_document: None,
         };
 
         assert!(watcher.matches(&event));
@@ -279,6 +395,8 @@ mod tests {
             id: "test".to_string(),
             sender: tx,
             filter: Some("age > 25".to_string()),
+            operations: None,
+            full_document_before_change: false,
         };
 
         let event1 = ChangeEvent {
@@ -286,6 +404,7 @@ mod tests {
             operation: ChangeOperation::Insert,
             doc_id: "1".to_string(),
             document: Some(json!({"name": "Alice", "age": 30})),
+            old_document: None,
         };
 
         let event2 = ChangeEvent {
@@ -293,6 +412,7 @@ mod tests {
             operation: ChangeOperation::Insert,
             doc_id: "2".to_string(),
             document: Some(json!({"name": "Bob", "age": 20})),
+            old_document: None,
         };
 
         assert!(watcher.matches(&event1));
@@ -306,6 +426,8 @@ mod tests {
             id: "test".to_string(),
             sender: tx,
             filter: Some("age > 25".to_string()),
+            operations: None,
+            full_document_before_change: false,
         };
 
         let event = ChangeEvent {
@@ -313,12 +435,46 @@ mod tests {
             operation: ChangeOperation::Delete,
             doc_id: "1".to_string(),
             document: None,
+            old_document: None,
         };
 
         // Delete events with filters don't match (no document to filter)
         assert!(!watcher.matches(&event));
     }
 
+    #[test]
+    fn test_watcher_matches_operations_and_filter() {
+        let (tx, _rx) = channel();
+        let watcher = Watcher {
+            id: "test".to_string(),
+            sender: tx,
+            filter: Some("age > 25".to_string()),
+            operations: Some(vec![ChangeOperation::Insert]),
+            full_document_before_change: false,
+        };
+
+        let event = |operation, age| ChangeEvent {
+            collection: "users".to_string(),
+            operation,
+            doc_id: "1".to_string(),
+            document: Some(json!({"age": age})),
+            old_document: None,
+        };
+
+        assert!(watcher.matches(&event(ChangeOperation::Insert, 30)));
+        assert!(!watcher.matches(&event(ChangeOperation::Insert, 20)));
+        assert!(!watcher.matches(&event(ChangeOperation::Update, 30)));
+    }
+
+    #[test]
+    fn test_change_operation_names() {
+        for op in [ChangeOperation::Insert, ChangeOperation::Update, ChangeOperation::Delete] {
+            assert_eq!(ChangeOperation::parse(op.as_str()), Some(op));
+        }
+        assert_eq!(ChangeOperation::parse("DELETE"), Some(ChangeOperation::Delete));
+        assert_eq!(ChangeOperation::parse("upsert"), None);
+    }
+
     #[test]
     fn test_emit_change() {
         let storage = new_watcher_storage();
@@ -328,6 +484,8 @@ mod tests {
             id: "test".to_string(),
             sender: tx,
             filter: None,
+            operations: None,
+            full_document_before_change: false,
         };
 
         // Add watcher to storage
@@ -343,6 +501,7 @@ mod tests {
             ChangeOperation::Insert,
             "1",
             Some(json!({"name": "Alice"})),
+            None,
         );
 
         // Verify event received
@@ -353,6 +512,51 @@ mod tests {
         assert!(event.document.is_some());
     }
 
+    #[test]
+    fn test_emit_change_old_document_only_when_requested() {
+        let storage = new_watcher_storage();
+        let (plain_tx, plain_rx) = channel();
+        let (before_tx, before_rx) = channel();
+
+        {
+            let mut s = storage.write().recover_poison();
+            s.insert("users".to_string(), vec![
+                Watcher {
+                    id: "plain".to_string(),
+                    sender: plain_tx,
+                    filter: None,
+                    operations: None,
+                    full_document_before_change: false,
+                },
+                Watcher {
+                    id: "before".to_string(),
+                    sender: before_tx,
+                    filter: None,
+                    operations: None,
+                    full_document_before_change: true,
+                },
+            ]);
+        }
+
+        assert_eq!(before_change_interest(&storage, "users"), Some(true));
+        assert_eq!(before_change_interest(&storage, "posts"), None);
+
+        emit_change(
+            &storage,
+            "users",
+            ChangeOperation::Update,
+            "1",
+            Some(json!({"name": "Alice", "age": 31})),
+            Some(json!({"name": "Alice", "age": 30})),
+        );
+
+        let plain_event = plain_rx.recv().unwrap();
+        assert!(plain_event.old_document.is_none());
+
+        let before_event = before_rx.recv().unwrap();
+        assert_eq!(before_event.old_document.unwrap()["age"], 30);
+    }
+
     #[test]
     fn test_watch_handle_auto_cleanup() {
         let storage = new_watcher_storage();
@@ -362,6 +566,8 @@ mod tests {
             id: "test".to_string(),
             sender: tx,
             filter: None,
+            operations: None,
+            full_document_before_change: false,
         };
 
         // Add watcher to storage
@@ -1,113 +1,152 @@
 #[cfg(feature = "web-ui")]
 use crate::core::database::Database;
 #[cfg(feature = "web-ui")]
+use crate::core::errors::Error;
+#[cfg(feature = "web-ui")]
+use crate::core::query_builder::SortOrder;
+#[cfg(feature = "web-ui")]
+use crate::core::query::parser::parse_query;
+#[cfg(feature = "web-ui")]
+use crate::core::recycle_bin;
+use crate::core::replication;
+#[cfg(feature = "web-ui")]
+use crate::core::watch::{ChangeEvent, ChangeOperation, WatchHandle};
+#[cfg(feature = "web-ui")]
+use serde_json::{json, Value};
+#[cfg(feature = "web-ui")]
+use std::io::{Read, Write};
+#[cfg(feature = "web-ui")]
 use std::sync::Arc;
 #[cfg(feature = "web-ui")]
 use std::thread::{self, JoinHandle};
 #[cfg(feature = "web-ui")]
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+#[cfg(feature = "web-ui")]
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+#[cfg(feature = "web-ui")]
+use std::time::{Duration, Instant};
 
+#[cfg(feature = "web-ui")]
+type Response = tiny_http::Response<std::io::Cursor<Vec<u8>>>;
+
+/// Serves the web dashboard and a JSON REST API over the database.
+///
+/// `GET /metrics` serves metrics in the Prometheus text format and `GET /health`
+/// answers `{"status": "ok"}`.
+///
+/// API endpoints (all request and response bodies are JSON):
+/// - `GET /api/metrics` - the `MetricsSnapshot` as JSON
+/// - `GET /api/slow-queries` - the slow query log, oldest first, see `Database::slow_queries`
+/// - `GET /api/collections` - list collection names
+/// - `POST /api/collections` - create a collection from `{"name": ...}`
+/// - `GET /api/collections/:name` - list documents, narrowed with the
+///   `filter`, `sort`, `limit` and `skip` query parameters. `sort` takes
+///   comma-separated fields, each prefixed with `-` for descending order.
+///   Pages hold 100 documents unless `limit` asks for up to 1000. When more
+///   follow, the `X-Next-Cursor` header holds the `cursor` parameter that
+///   fetches the next page. The array is streamed with chunked encoding.
+/// - `POST /api/collections/:name` - insert a document, answers 201 with its `id`
+/// - `PATCH /api/collections/:name` - rename a collection from `{"new_name": ...}`
+/// - `DELETE /api/collections/:name` - drop a collection
+/// - `GET/PUT/DELETE /api/collections/:name/:id` - read, update or delete a document
+/// - `POST /api/collections/:name/aggregate` - run an aggregation pipeline
+/// - `GET /api/collections/:name/watch` - stream changes as server-sent events,
+///   one `insert`, `update` or `delete` event per change with the serialized
+///   `ChangeEvent` as data. Takes an optional `filter` query parameter, and
+///   `access_token` in place of the `Authorization` header for browser clients.
+///
+/// Errors come back as `{"error": "..."}` with a 400, 401, 403, 404, 409, 413 or 500 status.
 #[cfg(feature = "web-ui")]
 pub struct WebServer {
     handle: Option<JoinHandle<()>>,
     shutdown: Arc<AtomicBool>,
+    active_streams: Arc<AtomicUsize>,
+}
+
+/// Access control for the web server
+#[cfg(feature = "web-ui")]
+#[derive(Debug, Clone, Default)]
+pub struct WebServerOptions {
+    /// When set, every request except `/` and `/health` must carry
+    /// `Authorization: Bearer <token>` or it gets a 401
+    pub auth_token: Option<String>,
+    /// Origins allowed to make cross-origin requests, `"*"` allows any.
+    /// Empty means no CORS headers are sent.
+    pub allowed_origins: Vec<String>,
+    /// Reject anything that would modify the database with a 403
+    pub read_only: bool,
 }
 
 #[cfg(feature = "web-ui")]
 impl WebServer {
-    pub fn start(db: Arc<Database>, addr: &str) -> Result<Self, Box<dyn std::error::Error>> {
+    pub fn start(db: Arc<Database>, addr: &str, options: WebServerOptions) -> Result<Self, Box<dyn std::error::Error>> {
         let server = tiny_http::Server::http(addr)
             .map_err(|e| Box::new(std::io::Error::new(std::io::ErrorKind::Other, e.to_string())) as Box<dyn std::error::Error>)?;
         let shutdown = Arc::new(AtomicBool::new(false));
         let shutdown_clone = shutdown.clone();
+        let active_streams = Arc::new(AtomicUsize::new(0));
+        let active_streams_clone = active_streams.clone();
 
         let handle = thread::spawn(move || {
-            for mut request in server.incoming_requests() {
-                if shutdown_clone.load(Ordering::Relaxed) {
-                    break;
+            let mut streams: Vec<JoinHandle<()>> = Vec::new();
+
+            // Poll so shutdown doesn't have to wait for the next request to arrive
+            while !shutdown_clone.load(Ordering::Relaxed) {
+                let mut request = match server.recv_timeout(Duration::from_millis(100)) {
+                    Ok(Some(request)) => request,
+                    Ok(None) => continue,
+                    Err(_) => break,
+                };
+
+                let origin = header_value(&request, "Origin");
+                let cors = cors_headers(&options, origin.as_deref());
+
+                if let Err(response) = authorize(&options, &request) {
+                    let _ = request.respond(with_headers(response, cors));
+                    continue;
                 }
 
-                let path = request.url().to_string();
-                let method = request.method().as_str();
-
-                let response = match (method, path.as_str()) {
-                    ("GET", "/") => serve_dashboard(),
-                    ("GET", "/metrics") => serve_metrics(&db),
-                    ("GET", "/health") => serve_health(),
-                    ("GET", "/api/collections") => serve_collections_list(&db),
-                    ("POST", "/api/collections") => serve_create_collection(&db, &mut request),
-                    ("GET", path) if path.starts_with("/api/collections/") => {
-                        // Path like /api/collections/users or /api/collections/users/doc123
-                        let rest = path.strip_prefix("/api/collections/").unwrap_or("");
-                        let parts: Vec<&str> = rest.split('/').collect();
-
-                        if parts.len() == 1 && !parts[0].is_empty() {
-                            // GET /api/collections/users - list all documents
-                            serve_collection_documents(&db, parts[0])
-                        } else {
-                            serve_404()
-                        }
-                    }
-                    ("POST", path) if path.starts_with("/api/collections/") => {
-                        let rest = path.strip_prefix("/api/collections/").unwrap_or("");
-                        let parts: Vec<&str> = rest.split('/').collect();
-
-                        if parts.len() == 1 && !parts[0].is_empty() {
-                            // POST /api/collections/users - create document
-                            serve_create_document(&db, parts[0], &mut request)
-                        } else {
-                            serve_404()
+                // Change streams hold their connection open, so they get a thread of their own
+                if let Some((collection, query)) = watch_route(&request) {
+                    match subscribe_changes(&db, &collection, &query) {
+                        Ok((watch, events)) => {
+                            streams.retain(|stream| !stream.is_finished());
+                            let shutdown = shutdown_clone.clone();
+                            let active = active_streams_clone.clone();
+                            active.fetch_add(1, Ordering::SeqCst);
+                            streams.push(thread::spawn(move || {
+                                stream_changes(request, cors, watch, events, &shutdown);
+                                active.fetch_sub(1, Ordering::SeqCst);
+                            }));
                         }
-                    }
-                    ("PUT", path) if path.starts_with("/api/collections/") => {
-                        let rest = path.strip_prefix("/api/collections/").unwrap_or("");
-                        let parts: Vec<&str> = rest.split('/').collect();
-
-                        if parts.len() == 2 && !parts[0].is_empty() && !parts[1].is_empty() {
-                            // PUT /api/collections/users/doc123 - update document
-                            serve_update_document(&db, parts[0], parts[1], &mut request)
-                        } else {
-                            serve_404()
-                        }
-                    }
-                    ("PATCH", path) if path.starts_with("/api/collections/") => {
-                        let rest = path.strip_prefix("/api/collections/").unwrap_or("");
-                        let parts: Vec<&str> = rest.split('/').collect();
-
-                        if parts.len() == 1 && !parts[0].is_empty() {
-                            // PATCH /api/collections/users - rename collection
-                            serve_rename_collection(&db, parts[0], &mut request)
-                        } else {
-                            serve_404()
+                        Err(response) => {
+                            let _ = request.respond(with_headers(response, cors));
                         }
                     }
-                    ("DELETE", path) if path.starts_with("/api/collections/") => {
-                        let rest = path.strip_prefix("/api/collections/").unwrap_or("");
-                        let parts: Vec<&str> = rest.split('/').collect();
-
-                        if parts.len() == 1 && !parts[0].is_empty() {
-                            // DELETE /api/collections/users - delete collection
-                            serve_drop_collection(&db, parts[0])
-                        } else if parts.len() == 2 && !parts[0].is_empty() && !parts[1].is_empty() {
-                            // DELETE /api/collections/users/doc123 - delete document
-                            serve_delete_document(&db, parts[0], parts[1])
-                        } else {
-                            serve_404()
-                        }
-                    }
-                    _ => serve_404(),
-                };
+                    continue;
+                }
+
+                let response = handle_request(&db, &mut request);
+                let _ = request.respond(with_headers(response, cors));
+            }
 
-                let _ = request.respond(response);
+            for stream in streams {
+                let _ = stream.join();
             }
         });
 
         Ok(WebServer {
             handle: Some(handle),
             shutdown,
+            active_streams,
         })
     }
 
+    /// Number of change streams currently connected
+    pub fn active_streams(&self) -> usize {
+        self.active_streams.load(Ordering::SeqCst)
+    }
+
     pub fn shutdown(mut self) {
         self.shutdown.store(true, Ordering::Relaxed);
         if let Some(handle) = self.handle.take() {
@@ -126,8 +165,229 @@ impl Drop for WebServer {
     }
 }
 
+/// Check the bearer token and read-only mode before a request reaches the router
+#[cfg(feature = "web-ui")]
+fn authorize(options: &WebServerOptions, request: &tiny_http::Request) -> Result<(), Response> {
+    let method = request.method().as_str();
+    let path = request.url().split('?').next().unwrap_or("");
+
+    // Preflights never carry credentials, and the dashboard page itself holds no data
+    if method == "OPTIONS" {
+        return Err(serve_preflight());
+    }
+    let public = method == "GET" && (path == "/" || path == "/health");
+
+    if let Some(expected) = &options.auth_token {
+        let mut provided = header_value(request, "Authorization")
+            .and_then(|value| value.strip_prefix("Bearer ").map(str::to_string));
+        // EventSource can't set headers, so change streams also take the token as a query parameter
+        if provided.is_none() && watch_route(request).is_some() {
+            let query = request.url().split_once('?').map_or("", |(_, q)| q);
+            provided = parse_query_string(query).into_iter()
+                .find(|(key, _)| key == "access_token")
+                .map(|(_, value)| value);
+        }
+        let token = provided.as_deref();
+        if !public && !token.is_some_and(|token| constant_time_eq(token.as_bytes(), expected.as_bytes())) {
+            return Err(serve_error(401, "Unauthorized").with_header(
+                tiny_http::Header::from_bytes(&b"WWW-Authenticate"[..], &b"Bearer"[..]).unwrap(),
+            ));
+        }
+    }
+
+    // Aggregation is a POST but only reads
+    let is_write = matches!(method, "POST" | "PUT" | "PATCH" | "DELETE") && !path.ends_with("/aggregate");
+    if options.read_only && is_write {
+        return Err(serve_error(403, "Server is read-only"));
+    }
+
+    Ok(())
+}
+
+/// CORS headers for the request's origin, empty unless it is allowed
+#[cfg(feature = "web-ui")]
+fn cors_headers(options: &WebServerOptions, origin: Option<&str>) -> Vec<tiny_http::Header> {
+    let origin = match origin {
+        Some(origin) if options.allowed_origins.iter().any(|o| o == "*" || o == origin) => origin,
+        _ => return Vec::new(),
+    };
+
+    let header = |name: &str, value: &str| tiny_http::Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap();
+    vec![
+        header("Access-Control-Allow-Origin", origin),
+        header("Vary", "Origin"),
+        header("Access-Control-Allow-Methods", "GET, POST, PUT, PATCH, DELETE, OPTIONS"),
+        header("Access-Control-Allow-Headers", "Authorization, Content-Type"),
+        header("Access-Control-Max-Age", "600"),
+    ]
+}
+
+#[cfg(feature = "web-ui")]
+fn with_headers<R: Read>(response: tiny_http::Response<R>, headers: Vec<tiny_http::Header>) -> tiny_http::Response<R> {
+    headers.into_iter().fold(response, |response, header| response.with_header(header))
+}
+
+#[cfg(feature = "web-ui")]
+fn header_value(request: &tiny_http::Request, name: &'static str) -> Option<String> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv(name))
+        .map(|h| h.value.as_str().to_string())
+}
+
+/// Compare tokens without returning early on the first mismatched byte
+#[cfg(feature = "web-ui")]
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(feature = "web-ui")]
+fn serve_preflight() -> Response {
+    tiny_http::Response::from_data(Vec::new()).with_status_code(204)
+}
+
+/// How long an idle change stream waits before sending a keepalive comment.
+/// Writes are also how a closed connection gets noticed.
+#[cfg(feature = "web-ui")]
+const STREAM_KEEPALIVE: Duration = Duration::from_secs(15);
+
+/// Match `GET /api/collections/:name/watch`, returning the collection and query string
+#[cfg(feature = "web-ui")]
+fn watch_route(request: &tiny_http::Request) -> Option<(String, String)> {
+    if request.method().as_str() != "GET" {
+        return None;
+    }
+    let url = request.url();
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let (name, rest) = path.strip_prefix("/api/collections/")?.split_once('/')?;
+    if name.is_empty() || rest != "watch" {
+        return None;
+    }
+    Some((percent_decode(name), query.to_string()))
+}
+
+#[cfg(feature = "web-ui")]
+fn subscribe_changes(
+    db: &Arc<Database>,
+    collection_name: &str,
+    query: &str,
+) -> Result<(WatchHandle, Receiver<ChangeEvent>), Response> {
+    let coll = db.collection(collection_name);
+    let mut builder = coll.watch();
+
+    if let Some((_, filter)) = parse_query_string(query).into_iter().find(|(key, _)| key == "filter") {
+        // A filter that doesn't parse would silently match nothing, so reject it up front
+        if let Err(e) = parse_query(&filter) {
+            return Err(serve_error(400, &format!("Invalid filter: {}", e)));
+        }
+        builder = builder.filter(&filter);
+    }
+
+    builder.subscribe().map_err(|e| serve_db_error("Failed to watch collection", e))
+}
+
+/// Write change events to the client until it disconnects or the server shuts down.
+/// The watch handle is dropped on return, which unsubscribes it.
 #[cfg(feature = "web-ui")]
-fn serve_dashboard() -> tiny_http::Response<std::io::Cursor<Vec<u8>>> {
+fn stream_changes(
+    request: tiny_http::Request,
+    cors: Vec<tiny_http::Header>,
+    _watch: WatchHandle,
+    events: Receiver<ChangeEvent>,
+    shutdown: &AtomicBool,
+) {
+    let mut writer = request.into_writer();
+
+    let mut head = String::from(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n",
+    );
+    for header in &cors {
+        head.push_str(&format!("{}: {}\r\n", header.field, header.value));
+    }
+    head.push_str("\r\n");
+    if writer.write_all(head.as_bytes()).and_then(|_| writer.flush()).is_err() {
+        return;
+    }
+
+    let mut last_write = Instant::now();
+    while !shutdown.load(Ordering::Relaxed) {
+        let message = match events.recv_timeout(Duration::from_millis(100)) {
+            Ok(event) => {
+                let name = match event.operation {
+                    ChangeOperation::Insert => "insert",
+                    ChangeOperation::Update => "update",
+                    ChangeOperation::Delete => "delete",
+                };
+                match serde_json::to_string(&event) {
+                    Ok(data) => format!("event: {}\ndata: {}\n\n", name, data),
+                    Err(_) => continue,
+                }
+            }
+            Err(RecvTimeoutError::Timeout) if last_write.elapsed() >= STREAM_KEEPALIVE => ": keepalive\n\n".to_string(),
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        if writer.write_all(message.as_bytes()).and_then(|_| writer.flush()).is_err() {
+            break;
+        }
+        last_write = Instant::now();
+    }
+}
+
+#[cfg(feature = "web-ui")]
+fn handle_request(db: &Arc<Database>, request: &mut tiny_http::Request) -> tiny_http::ResponseBox {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((url.as_str(), ""));
+    let method = request.method().as_str().to_string();
+
+    // Path like /api/collections/users or /api/collections/users/doc123
+    let parts: Vec<String> = match path.strip_prefix("/api/collections/") {
+        Some(rest) => rest.split('/').map(percent_decode).collect(),
+        None => Vec::new(),
+    };
+    let parts: Vec<&str> = parts.iter().map(|s| s.as_str()).collect();
+
+    let response = match (method.as_str(), path) {
+        ("GET", "/") => serve_dashboard(),
+        ("GET", "/metrics") => serve_prometheus_metrics(db),
+        ("GET", "/api/metrics") => serve_metrics(db),
+        ("GET", "/api/slow-queries") => serve_json(200, &json!(db.slow_queries())),
+        ("GET", "/health") => serve_health(),
+        ("GET", "/api/collections") => serve_collections_list(db),
+        ("POST", "/api/collections") => serve_create_collection(db, request),
+        _ if parts.is_empty() || parts.iter().any(|p| p.is_empty()) => serve_404(),
+        ("GET", _) => match parts[..] {
+            [collection] => return serve_collection_documents(db, collection, query),
+            [collection, id] => serve_get_document(db, collection, id),
+            _ => serve_404(),
+        },
+        ("POST", _) => match parts[..] {
+            [collection] => serve_create_document(db, collection, request),
+            [collection, "aggregate"] => serve_aggregate(db, collection, request),
+            _ => serve_404(),
+        },
+        ("PUT", _) => match parts[..] {
+            [collection, id] => serve_update_document(db, collection, id, request),
+            _ => serve_404(),
+        },
+        ("PATCH", _) => match parts[..] {
+            [collection] => serve_rename_collection(db, collection, request),
+            _ => serve_404(),
+        },
+        ("DELETE", _) => match parts[..] {
+            [collection] => serve_drop_collection(db, collection),
+            [collection, id] => serve_delete_document(db, collection, id),
+            _ => serve_404(),
+        },
+        _ => serve_404(),
+    };
+    response.boxed()
+}
+
+#[cfg(feature = "web-ui")]
+fn serve_dashboard() -> Response {
     let html = include_str!("web_ui.html");
     tiny_http::Response::from_string(html)
         .with_header(
@@ -137,70 +397,175 @@ fn serve_dashboard() -> tiny_http::Response<std::io::Cursor<Vec<u8>>> {
 }
 
 #[cfg(feature = "web-ui")]
-fn serve_metrics(db: &Arc<Database>) -> tiny_http::Response<std::io::Cursor<Vec<u8>>> {
+fn serve_metrics(db: &Arc<Database>) -> Response {
     let metrics = db.metrics();
-    let json = serde_json::to_string_pretty(&metrics).unwrap_or_else(|_| "{}".to_string());
+    match serde_json::to_value(&metrics) {
+        Ok(json) => serve_json(200, &json),
+        Err(e) => serve_error(500, &format!("Failed to serialize metrics: {}", e)),
+    }
+}
 
-    tiny_http::Response::from_string(json)
+#[cfg(feature = "web-ui")]
+fn serve_prometheus_metrics(db: &Arc<Database>) -> Response {
+    tiny_http::Response::from_string(db.metrics_prometheus())
         .with_header(
-            tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+            tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"text/plain; version=0.0.4; charset=utf-8"[..])
                 .unwrap(),
         )
 }
 
 #[cfg(feature = "web-ui")]
-fn serve_health() -> tiny_http::Response<std::io::Cursor<Vec<u8>>> {
-    let json = r#"{"status":"ok"}"#;
-    tiny_http::Response::from_string(json)
-        .with_header(
-            tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
-                .unwrap(),
-        )
+fn serve_health() -> Response {
+    serve_json(200, &json!({ "status": "ok" }))
 }
 
 #[cfg(feature = "web-ui")]
-fn serve_collections_list(db: &Arc<Database>) -> tiny_http::Response<std::io::Cursor<Vec<u8>>> {
+fn serve_collections_list(db: &Arc<Database>) -> Response {
     let metadata = db.get_metadata();
-    let mut collections: Vec<String> = metadata.collections.keys().cloned().collect();
+    let mut collections: Vec<String> = metadata.collections.keys()
+        .filter(|name| !recycle_bin::is_bin(name) && !replication::is_log(name))
+        .cloned()
+        .collect();
     collections.sort(); // Sort alphabetically
-    let json = serde_json::to_string_pretty(&collections).unwrap_or_else(|_| "[]".to_string());
-
-    tiny_http::Response::from_string(json)
-        .with_header(
-            tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
-                .unwrap(),
-        )
+    serve_json(200, &json!(collections))
 }
 
+/// Page size used when a document listing doesn't ask for one
 #[cfg(feature = "web-ui")]
-fn serve_collection_documents(
-    db: &Arc<Database>,
-    collection_name: &str,
-) -> tiny_http::Response<std::io::Cursor<Vec<u8>>> {
-    match db.begin() {
-        Ok(mut tx) => {
-            match tx.collection(collection_name) {
-                Ok(coll) => {
-                    match coll.find_all() {
-                        Ok(docs) => {
-                            let json = serde_json::to_string_pretty(&docs)
-                                .unwrap_or_else(|_| "[]".to_string());
-                            tiny_http::Response::from_string(json)
-                                .with_header(
-                                    tiny_http::Header::from_bytes(
-                                        &b"Content-Type"[..],
-                                        &b"application/json"[..],
-                                    )
-                                    .unwrap(),
-                                )
-                        }
-                        Err(_) => serve_500("Failed to read documents"),
-                    }
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Largest page a document listing may ask for; larger limits are clamped to it
+#[cfg(feature = "web-ui")]
+const MAX_PAGE_SIZE: usize = 1000;
+
+#[cfg(feature = "web-ui")]
+fn serve_collection_documents(db: &Arc<Database>, collection_name: &str, query: &str) -> tiny_http::ResponseBox {
+    if !db.collection_exists(collection_name) {
+        return serve_error(404, &format!("Collection '{}' not found", collection_name)).boxed();
+    }
+
+    let coll = db.collection(collection_name);
+    let mut builder = coll.query();
+    let mut limit = DEFAULT_PAGE_SIZE;
+    let mut skip = 0;
+
+    for (key, value) in parse_query_string(query) {
+        match key.as_str() {
+            "filter" if !value.is_empty() => builder = builder.filter(&value),
+            "sort" => {
+                for field in value.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+                    builder = match field.strip_prefix('-') {
+                        Some(field) => builder.sort_by(field, SortOrder::Desc),
+                        None => builder.sort_by(field.trim_start_matches('+'), SortOrder::Asc),
+                    };
                 }
-                Err(_) => serve_404(),
             }
+            "limit" | "skip" | "cursor" => {
+                let n: usize = match value.parse() {
+                    Ok(n) => n,
+                    Err(_) => return serve_error(400, &format!("Invalid {} '{}': expected a non-negative integer", key, value)).boxed(),
+                };
+                if key == "limit" {
+                    limit = n.min(MAX_PAGE_SIZE);
+                } else {
+                    skip = n;
+                }
+            }
+            _ => {}
         }
-        Err(_) => serve_500("Failed to begin transaction"),
+    }
+
+    // One extra document tells whether there is another page without counting them all
+    let mut docs = match builder.skip(skip).limit(limit + 1).execute() {
+        Ok(docs) => docs,
+        Err(e) => return serve_db_error("Failed to read documents", e).boxed(),
+    };
+
+    let mut headers = vec![
+        tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap(),
+    ];
+    if docs.len() > limit {
+        docs.truncate(limit);
+        let cursor = (skip + limit).to_string();
+        headers.push(tiny_http::Header::from_bytes(&b"X-Next-Cursor"[..], cursor.as_bytes()).unwrap());
+    }
+
+    // No content length, so tiny_http sends the array with chunked encoding as it is serialized
+    let body: Box<dyn Read + Send> = Box::new(DocumentStream::new(docs));
+    tiny_http::Response::new(tiny_http::StatusCode(200), headers, body, None, None)
+}
+
+/// Serializes a JSON array one document at a time as the response is written,
+/// so the whole body never sits in memory at once
+#[cfg(feature = "web-ui")]
+struct DocumentStream {
+    docs: std::vec::IntoIter<Value>,
+    buf: Vec<u8>,
+    pos: usize,
+    first: bool,
+    done: bool,
+}
+
+#[cfg(feature = "web-ui")]
+impl DocumentStream {
+    fn new(docs: Vec<Value>) -> Self {
+        DocumentStream {
+            docs: docs.into_iter(),
+            buf: b"[".to_vec(),
+            pos: 0,
+            first: true,
+            done: false,
+        }
+    }
+
+    /// Refill the buffer with the next document, or the closing bracket
+    fn fill(&mut self) -> std::io::Result<()> {
+        self.buf.clear();
+        self.pos = 0;
+        match self.docs.next() {
+            Some(doc) => {
+                self.buf.extend_from_slice(if self.first { b"\n" } else { b",\n" });
+                self.first = false;
+                serde_json::to_writer(&mut self.buf, &doc)?;
+            }
+            None if !self.done => {
+                self.buf.extend_from_slice(if self.first { b"]" } else { b"\n]" });
+                self.done = true;
+            }
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "web-ui")]
+impl Read for DocumentStream {
+    fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos == self.buf.len() {
+            self.fill()?;
+        }
+        let n = out.len().min(self.buf.len() - self.pos);
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[cfg(feature = "web-ui")]
+fn serve_get_document(db: &Arc<Database>, collection_name: &str, doc_id: &str) -> Response {
+    if !db.collection_exists(collection_name) {
+        return serve_error(404, &format!("Collection '{}' not found", collection_name));
+    }
+
+    match db.begin() {
+        Ok(mut tx) => match tx.collection(collection_name) {
+            Ok(coll) => match coll.find_by_id(doc_id) {
+                Ok(doc) => serve_json(200, &doc),
+                Err(e) => serve_db_error("Failed to read document", e),
+            },
+            Err(e) => serve_db_error("Failed to access collection", e),
+        },
+        Err(e) => serve_db_error("Failed to begin transaction", e),
     }
 }
 
@@ -209,17 +574,10 @@ fn serve_create_document(
     db: &Arc<Database>,
     collection_name: &str,
     request: &mut tiny_http::Request,
-) -> tiny_http::Response<std::io::Cursor<Vec<u8>>> {
-    // Read request body
-    let mut body = String::new();
-    if let Err(_) = request.as_reader().read_to_string(&mut body) {
-        return serve_500("Failed to read request body");
-    }
-
-    // Parse JSON
-    let json_value: serde_json::Value = match serde_json::from_str(&body) {
+) -> Response {
+    let json_value = match read_json_body(db, request) {
         Ok(v) => v,
-        Err(e) => return serve_500(&format!("Invalid JSON: {}", e)),
+        Err(response) => return response,
     };
 
     // Create document
@@ -229,32 +587,20 @@ fn serve_create_document(
                 Ok(mut coll) => {
                     match coll.insert(json_value) {
                         Ok(doc_id) => {
-                            let response_json = serde_json::json!({ "id": doc_id });
-                            let json = serde_json::to_string(&response_json)
-                                .unwrap_or_else(|_| "{}".to_string());
-
                             // Commit transaction
                             if let Err(e) = tx.commit() {
-                                return serve_500(&format!("Failed to commit: {}", e));
+                                return serve_db_error("Failed to commit", e);
                             }
 
-                            tiny_http::Response::from_string(json)
-                                .with_status_code(201)
-                                .with_header(
-                                    tiny_http::Header::from_bytes(
-                                        &b"Content-Type"[..],
-                                        &b"application/json"[..],
-                                    )
-                                    .unwrap(),
-                                )
+                            serve_json(201, &json!({ "id": doc_id }))
                         }
-                        Err(e) => serve_500(&format!("Failed to insert document: {}", e)),
+                        Err(e) => serve_db_error("Failed to insert document", e),
                     }
                 }
-                Err(e) => serve_500(&format!("Failed to access collection: {}", e)),
+                Err(e) => serve_db_error("Failed to access collection", e),
             }
         }
-        Err(e) => serve_500(&format!("Failed to begin transaction: {}", e)),
+        Err(e) => serve_db_error("Failed to begin transaction", e),
     }
 }
 
@@ -264,19 +610,16 @@ fn serve_update_document(
     collection_name: &str,
     doc_id: &str,
     request: &mut tiny_http::Request,
-) -> tiny_http::Response<std::io::Cursor<Vec<u8>>> {
-    // Read request body
-    let mut body = String::new();
-    if let Err(_) = request.as_reader().read_to_string(&mut body) {
-        return serve_500("Failed to read request body");
-    }
-
-    // Parse JSON
-    let json_value: serde_json::Value = match serde_json::from_str(&body) {
+) -> Response {
+    let json_value = match read_json_body(db, request) {
         Ok(v) => v,
-        Err(e) => return serve_500(&format!("Invalid JSON: {}", e)),
+        Err(response) => return response,
     };
 
+    if !db.collection_exists(collection_name) {
+        return serve_error(404, &format!("Collection '{}' not found", collection_name));
+    }
+
     // Update document
     match db.begin() {
         Ok(mut tx) => {
@@ -286,29 +629,18 @@ fn serve_update_document(
                         Ok(_) => {
                             // Commit transaction
                             if let Err(e) = tx.commit() {
-                                return serve_500(&format!("Failed to commit: {}", e));
+                                return serve_db_error("Failed to commit", e);
                             }
 
-                            let response_json = serde_json::json!({ "status": "updated" });
-                            let json = serde_json::to_string(&response_json)
-                                .unwrap_or_else(|_| "{}".to_string());
-
-                            tiny_http::Response::from_string(json)
-                                .with_header(
-                                    tiny_http::Header::from_bytes(
-                                        &b"Content-Type"[..],
-                                        &b"application/json"[..],
-                                    )
-                                    .unwrap(),
-                                )
+                            serve_json(200, &json!({ "status": "updated" }))
                         }
-                        Err(e) => serve_500(&format!("Failed to update document: {}", e)),
+                        Err(e) => serve_db_error("Failed to update document", e),
                     }
                 }
-                Err(e) => serve_500(&format!("Failed to access collection: {}", e)),
+                Err(e) => serve_db_error("Failed to access collection", e),
             }
         }
-        Err(e) => serve_500(&format!("Failed to begin transaction: {}", e)),
+        Err(e) => serve_db_error("Failed to begin transaction", e),
     }
 }
 
@@ -317,7 +649,11 @@ fn serve_delete_document(
     db: &Arc<Database>,
     collection_name: &str,
     doc_id: &str,
-) -> tiny_http::Response<std::io::Cursor<Vec<u8>>> {
+) -> Response {
+    if !db.collection_exists(collection_name) {
+        return serve_error(404, &format!("Collection '{}' not found", collection_name));
+    }
+
     // Delete document
     match db.begin() {
         Ok(mut tx) => {
@@ -327,52 +663,65 @@ fn serve_delete_document(
                         Ok(_) => {
                             // Commit transaction
                             if let Err(e) = tx.commit() {
-                                return serve_500(&format!("Failed to commit: {}", e));
+                                return serve_db_error("Failed to commit", e);
                             }
 
-                            let response_json = serde_json::json!({ "status": "deleted" });
-                            let json = serde_json::to_string(&response_json)
-                                .unwrap_or_else(|_| "{}".to_string());
-
-                            tiny_http::Response::from_string(json)
-                                .with_header(
-                                    tiny_http::Header::from_bytes(
-                                        &b"Content-Type"[..],
-                                        &b"application/json"[..],
-                                    )
-                                    .unwrap(),
-                                )
+                            serve_json(200, &json!({ "status": "deleted" }))
                         }
-                        Err(e) => serve_500(&format!("Failed to delete document: {}", e)),
+                        Err(e) => serve_db_error("Failed to delete document", e),
                     }
                 }
-                Err(e) => serve_500(&format!("Failed to access collection: {}", e)),
+                Err(e) => serve_db_error("Failed to access collection", e),
             }
         }
-        Err(e) => serve_500(&format!("Failed to begin transaction: {}", e)),
+        Err(e) => serve_db_error("Failed to begin transaction", e),
     }
 }
 
 #[cfg(feature = "web-ui")]
-fn serve_create_collection(
+fn serve_aggregate(
     db: &Arc<Database>,
+    collection_name: &str,
     request: &mut tiny_http::Request,
-) -> tiny_http::Response<std::io::Cursor<Vec<u8>>> {
-    // Read request body
-    let mut body = String::new();
-    if let Err(_) = request.as_reader().read_to_string(&mut body) {
-        return serve_500("Failed to read request body");
+) -> Response {
+    let stages = match read_json_body(db, request) {
+        Ok(Value::Array(stages)) => stages,
+        Ok(_) => return serve_error(400, "Aggregation pipeline must be a JSON array of stages"),
+        Err(response) => return response,
+    };
+
+    if !db.collection_exists(collection_name) {
+        return serve_error(404, &format!("Collection '{}' not found", collection_name));
+    }
+
+    let coll = db.collection(collection_name);
+    let pipeline = match coll.aggregate().stages(&stages) {
+        Ok(pipeline) => pipeline,
+        Err(e) => return serve_error(400, &e.to_string()),
+    };
+
+    match pipeline.execute() {
+        Ok(results) => serve_json(200, &Value::Array(results)),
+        Err(e) => serve_db_error("Failed to run aggregation", e),
     }
+}
 
+#[cfg(feature = "web-ui")]
+fn serve_create_collection(
+    db: &Arc<Database>,
+    request: &mut tiny_http::Request,
+) -> Response {
     // Parse JSON to get collection name
     #[derive(serde::Deserialize)]
     struct CreateCollectionRequest {
         name: String,
     }
 
-    let req: CreateCollectionRequest = match serde_json::from_str(&body) {
+    let req: CreateCollectionRequest = match read_json_body(db, request)
+        .and_then(|v| serde_json::from_value(v).map_err(|e| serve_error(400, &format!("Invalid JSON: {}", e))))
+    {
         Ok(r) => r,
-        Err(e) => return serve_500(&format!("Invalid JSON: {}", e)),
+        Err(response) => return response,
     };
 
     // Create collection using transaction API
@@ -382,27 +731,15 @@ fn serve_create_collection(
                 Ok(_) => {
                     // Commit to persist the collection
                     if let Err(e) = tx.commit() {
-                        return serve_500(&format!("Failed to commit: {}", e));
+                        return serve_db_error("Failed to commit", e);
                     }
 
-                    let response_json = serde_json::json!({ "status": "created", "name": req.name });
-                    let json = serde_json::to_string(&response_json)
-                        .unwrap_or_else(|_| "{}".to_string());
-
-                    tiny_http::Response::from_string(json)
-                        .with_status_code(201)
-                        .with_header(
-                            tiny_http::Header::from_bytes(
-                                &b"Content-Type"[..],
-                                &b"application/json"[..],
-                            )
-                            .unwrap(),
-                        )
+                    serve_json(201, &json!({ "status": "created", "name": req.name }))
                 }
-                Err(e) => serve_500(&format!("Failed to create collection: {}", e)),
+                Err(e) => serve_db_error("Failed to create collection", e),
             }
         }
-        Err(e) => serve_500(&format!("Failed to begin transaction: {}", e)),
+        Err(e) => serve_db_error("Failed to begin transaction", e),
     }
 }
 
@@ -411,22 +748,18 @@ fn serve_rename_collection(
     db: &Arc<Database>,
     old_name: &str,
     request: &mut tiny_http::Request,
-) -> tiny_http::Response<std::io::Cursor<Vec<u8>>> {
-    // Read request body
-    let mut body = String::new();
-    if let Err(_) = request.as_reader().read_to_string(&mut body) {
-        return serve_500("Failed to read request body");
-    }
-
+) -> Response {
     // Parse JSON to get new name
     #[derive(serde::Deserialize)]
     struct RenameCollectionRequest {
         new_name: String,
     }
 
-    let req: RenameCollectionRequest = match serde_json::from_str(&body) {
+    let req: RenameCollectionRequest = match read_json_body(db, request)
+        .and_then(|v| serde_json::from_value(v).map_err(|e| serve_error(400, &format!("Invalid JSON: {}", e))))
+    {
         Ok(r) => r,
-        Err(e) => return serve_500(&format!("Invalid JSON: {}", e)),
+        Err(response) => return response,
     };
 
     // Rename collection
@@ -436,30 +769,19 @@ fn serve_rename_collection(
                 Ok(_) => {
                     // Commit to persist the rename
                     if let Err(e) = tx.commit() {
-                        return serve_500(&format!("Failed to commit: {}", e));
+                        return serve_db_error("Failed to commit", e);
                     }
 
-                    let response_json = serde_json::json!({
+                    serve_json(200, &json!({
                         "status": "renamed",
                         "old_name": old_name,
                         "new_name": req.new_name
-                    });
-                    let json = serde_json::to_string(&response_json)
-                        .unwrap_or_else(|_| "{}".to_string());
-
-                    tiny_http::Response::from_string(json)
-                        .with_header(
-                            tiny_http::Header::from_bytes(
-                                &b"Content-Type"[..],
-                                &b"application/json"[..],
-                            )
-                            .unwrap(),
-                        )
+                    }))
                 }
-                Err(e) => serve_500(&format!("Failed to rename collection: {}", e)),
+                Err(e) => serve_db_error("Failed to rename collection", e),
             }
         }
-        Err(e) => serve_500(&format!("Failed to begin transaction: {}", e)),
+        Err(e) => serve_db_error("Failed to begin transaction", e),
     }
 }
 
@@ -467,7 +789,7 @@ fn serve_rename_collection(
 fn serve_drop_collection(
     db: &Arc<Database>,
     name: &str,
-) -> tiny_http::Response<std::io::Cursor<Vec<u8>>> {
+) -> Response {
     // Delete collection
     match db.begin() {
         Ok(mut tx) => {
@@ -475,38 +797,130 @@ fn serve_drop_collection(
                 Ok(_) => {
                     // Commit to persist the deletion
                     if let Err(e) = tx.commit() {
-                        return serve_500(&format!("Failed to commit: {}", e));
+                        return serve_db_error("Failed to commit", e);
                     }
 
-                    let response_json = serde_json::json!({ "status": "deleted", "name": name });
-                    let json = serde_json::to_string(&response_json)
-                        .unwrap_or_else(|_| "{}".to_string());
-
-                    tiny_http::Response::from_string(json)
-                        .with_header(
-                            tiny_http::Header::from_bytes(
-                                &b"Content-Type"[..],
-                                &b"application/json"[..],
-                            )
-                            .unwrap(),
-                        )
+                    serve_json(200, &json!({ "status": "deleted", "name": name }))
                 }
-                Err(e) => serve_500(&format!("Failed to delete collection: {}", e)),
+                Err(e) => serve_db_error("Failed to delete collection", e),
             }
         }
-        Err(e) => serve_500(&format!("Failed to begin transaction: {}", e)),
+        Err(e) => serve_db_error("Failed to begin transaction", e),
     }
 }
 
+/// Read and parse a JSON request body, answering 413 past `max_request_body_size`
 #[cfg(feature = "web-ui")]
-fn serve_404() -> tiny_http::Response<std::io::Cursor<Vec<u8>>> {
-    tiny_http::Response::from_string("Not Found")
-        .with_status_code(404)
+fn read_json_body(db: &Arc<Database>, request: &mut tiny_http::Request) -> Result<Value, Response> {
+    let limit = db.max_request_body_size();
+    let too_large = || serve_error(413, &format!("Request body exceeds the {} byte limit", limit));
+
+    if request.body_length().is_some_and(|len| len > limit) {
+        return Err(too_large());
+    }
+
+    // Chunked bodies carry no length up front, so cap the read as well
+    let mut body = Vec::new();
+    if request.as_reader().take(limit as u64 + 1).read_to_end(&mut body).is_err() {
+        return Err(serve_error(400, "Failed to read request body"));
+    }
+    if body.len() > limit {
+        return Err(too_large());
+    }
+
+    serde_json::from_slice(&body).map_err(|e| serve_error(400, &format!("Invalid JSON: {}", e)))
 }
 
+/// Split a query string into decoded key/value pairs
 #[cfg(feature = "web-ui")]
-fn serve_500(msg: &str) -> tiny_http::Response<std::io::Cursor<Vec<u8>>> {
-    tiny_http::Response::from_string(msg)
-        .with_status_code(500)
+fn parse_query_string(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(&key.replace('+', " ")), percent_decode(&value.replace('+', " ")))
+        })
+        .collect()
 }
 
+#[cfg(feature = "web-ui")]
+fn percent_decode(input: &str) -> String {
+    fn hex_value(byte: u8) -> Option<u8> {
+        (byte as char).to_digit(16).map(|d| d as u8)
+    }
+
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let (Some(hi), Some(lo)) = (hex_value(bytes[i + 1]), hex_value(bytes[i + 2])) {
+                out.push(hi << 4 | lo);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Map a database error to the HTTP status that best describes it
+#[cfg(feature = "web-ui")]
+fn error_status(error: &Error) -> u16 {
+    match error {
+        Error::NotFound
+        | Error::DocumentNotFound { .. }
+        | Error::CollectionDoesNotExist { .. } => 404,
+        Error::Other(msg) if msg.contains("failed to parse query") => 400,
+        Error::DocumentAlreadyExists { .. }
+        | Error::CollectionAlreadyExists { .. }
+        | Error::RevConflict { .. }
+        | Error::TxConflict
+        | Error::Conflict { .. } => 409,
+        Error::CollectionNameEmpty
+        | Error::CollectionNameTooLong
+        | Error::CollectionNameInvalidStart
+        | Error::CollectionNameInvalidChar
+        | Error::CollectionNameReserved
+        | Error::DocumentTooLarge
+        | Error::BulkOperationTooLarge { .. }
+        | Error::QueryTooExpensive { .. }
+        | Error::InvalidDocument
+        | Error::InvalidDocumentFormat { .. }
+        | Error::InvalidUpdate { .. }
+        | Error::Validation { .. }
+        | Error::SchemaValidation(_) => 400,
+        _ => 500,
+    }
+}
+
+#[cfg(feature = "web-ui")]
+fn serve_db_error(context: &str, error: Error) -> Response {
+    serve_error(error_status(&error), &format!("{}: {}", context, error))
+}
+
+#[cfg(feature = "web-ui")]
+fn serve_json(status: u16, value: &Value) -> Response {
+    let json = serde_json::to_string_pretty(value).unwrap_or_else(|_| "null".to_string());
+    tiny_http::Response::from_string(json)
+        .with_status_code(status)
+        .with_header(
+            tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+                .unwrap(),
+        )
+}
+
+#[cfg(feature = "web-ui")]
+fn serve_error(status: u16, msg: &str) -> Response {
+    serve_json(status, &json!({ "error": msg }))
+}
+
+#[cfg(feature = "web-ui")]
+fn serve_404() -> Response {
+    serve_error(404, "Not Found")
+}
@@ -1,10 +1,13 @@
 
 pub mod core;
 
-pub use core::{Database, Transaction, Collection, SortOrder, UpsertResult, BulkWrite, BulkWriteResult, BulkWriteError, CollectionInfo, IndexInfo, DatabaseInfo, AggregationPipeline, Schema, ValueType, ChangeEvent, ChangeOperation, WatchBuilder, WatchHandle, SearchResult};
+pub use core::{Database, Transaction, Collection, FindBatches, SortOrder, Query, QueryAst, QueryExplain, AccessPath, IndexHint, Collation, UpsertResult, BulkWrite, BulkWriteResult, BulkWriteError, ChunkedInsertResult, ChunkFailure, WriteBatch, CollectionInfo, IndexInfo, IndexOptions, IndexUsage, DatabaseInfo, AggregationPipeline, FieldExpr, OutMode, DateGranularity, Schema, ValidationError, ValueType, ChangeEvent, ChangeOperation, WatchBuilder, WatchHandle, SearchResult, TermScore, TextIndexOptions, Language, IdStrategy, IntegrityReport, IntegrityProblem, RepairReport, RecoveredCollection, SpaceStats, CollectionSpace, CacheWarmup, Snapshot, KvStore, ReplicationRecord, ReplicationStream, SlowQuery, SlowQueryOperation, DbEvent, EventHook, Durability, binary_bytes, binary_value, haversine_km};
 pub use core::errors::{Error, Result};
 pub use core::database::{BackupInfo, DatabaseOptions};
-pub use core::metrics::MetricsSnapshot;
+pub use core::metrics::{CollectionMetricsSnapshot, HistogramSnapshot, MetricsSnapshot};
 
 #[cfg(feature = "web-ui")]
-pub use core::WebServer;
+pub use core::{WebServer, WebServerOptions};
+
+#[cfg(feature = "tokio")]
+pub use core::{AsyncCollection, AsyncDatabase};
@@ -235,3 +235,92 @@ fn test_stress_concurrent_mixed_operations() {
     println!("Total successful operations: {}", total_ops);
     assert!(total_ops > 0, "Some operations should have succeeded");
 }
+
+#[test]
+fn test_run_transaction_retries_on_conflict() {
+
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.db");
+    let db = Database::open(db_path.to_str().unwrap()).unwrap();
+    let collection = db.collection("test");
+    collection.insert(json!({"_id": "counter", "value": 0})).unwrap();
+
+    let mut attempts = 0;
+    let value = db.run_transaction(|tx| {
+        attempts += 1;
+        let mut coll = tx.collection("test")?;
+        let current = coll.find_by_id("counter")?["value"].as_i64().unwrap();
+        coll.update_by_id("counter", json!({"value": current + 1}))?;
+
+        if attempts == 1 {
+            // Someone else commits a change to the same document first
+            collection.update_by_id("counter", json!({"value": 10})).unwrap();
+        }
+        Ok(current + 1)
+    }).unwrap();
+
+    assert_eq!(attempts, 2, "Conflicting commit should be retried once");
+    assert_eq!(value, 11);
+    assert_eq!(collection.find_by_id("counter").unwrap()["value"], 11);
+}
+
+#[test]
+fn test_run_transaction_error_rolls_back() {
+
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.db");
+    let db = Database::open(db_path.to_str().unwrap()).unwrap();
+    db.collection("test").insert(json!({"_id": "existing"})).unwrap();
+
+    let mut attempts = 0;
+    let result: jasonisnthappy::Result<()> = db.run_transaction(|tx| {
+        attempts += 1;
+        tx.collection("test")?.insert(json!({"_id": "new"}))?;
+        Err(jasonisnthappy::Error::InvalidDocument)
+    });
+
+    assert!(matches!(result, Err(jasonisnthappy::Error::InvalidDocument)));
+    assert_eq!(attempts, 1, "Non-conflict errors should not be retried");
+    assert!(db.collection("test").find_by_id("new").is_err());
+}
+
+#[test]
+fn test_overlapping_transactions_on_distinct_documents_both_commit() {
+
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.db");
+    let db = Database::open(db_path.to_str().unwrap()).unwrap();
+    db.create_index("test", "by_value", "value", false).unwrap();
+    let collection = db.collection("test");
+    for id in ["a", "b", "c", "d"] {
+        collection.insert(json!({"_id": id, "value": 0})).unwrap();
+    }
+
+    // Both transactions start from the same snapshot and write to the same
+    // collection, but never touch the same document
+    let mut tx1 = db.begin().unwrap();
+    let mut tx2 = db.begin().unwrap();
+    tx1.collection("test").unwrap().update_by_id("a", json!({"value": 1})).unwrap();
+    tx2.collection("test").unwrap().update_by_id("b", json!({"value": 2})).unwrap();
+    tx1.collection("test").unwrap().delete_by_id("c").unwrap();
+    tx2.collection("test").unwrap().insert(json!({"_id": "e", "value": 5})).unwrap();
+
+    tx1.commit().expect("disjoint writes must not conflict");
+    tx2.commit().expect("disjoint writes must not conflict");
+
+    assert_eq!(collection.find_by_id("a").unwrap()["value"], 1);
+    assert_eq!(collection.find_by_id("b").unwrap()["value"], 2);
+    assert!(collection.find_by_id("c").is_err());
+    assert_eq!(collection.find_by_id("e").unwrap()["value"], 5);
+    assert_eq!(collection.count().unwrap(), 4);
+    assert_eq!(collection.find("value is 2").unwrap().len(), 1);
+
+    // Writing the same document from both still conflicts
+    let mut tx1 = db.begin().unwrap();
+    let mut tx2 = db.begin().unwrap();
+    tx1.collection("test").unwrap().update_by_id("d", json!({"value": 1})).unwrap();
+    tx2.collection("test").unwrap().update_by_id("d", json!({"value": 2})).unwrap();
+    tx1.commit().unwrap();
+    assert!(matches!(tx2.commit(), Err(jasonisnthappy::Error::TxConflict)));
+    assert_eq!(collection.find_by_id("d").unwrap()["value"], 1);
+}
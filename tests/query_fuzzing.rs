@@ -125,6 +125,11 @@ fn test_malformed_syntax() -> usize {
         "tags has all",
         "tags has any []",
         "tags has all [",
+        "tags contains",
+        "scores elemMatch",
+        "scores elemMatch value > 80",
+        "scores elemMatch (value > 80",
+        "scores elemMatch ()",
 
         "address.",
         "address.city.",
@@ -1,4 +1,5 @@
 use jasonisnthappy::core::database::{Database, DatabaseOptions};
+use jasonisnthappy::Error;
 use serde_json::json;
 use std::sync::Arc;
 use std::thread;
@@ -254,3 +255,61 @@ fn test_readonly_stress_many_readers() {
         "All readers should successfully read their documents"
     );
 }
+
+#[test]
+fn test_writer_and_readers_exclude_each_other() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.db");
+    let db_path_str = db_path.to_str().unwrap();
+    let read_only = DatabaseOptions {
+        read_only: true,
+        ..Default::default()
+    };
+
+    let writer = Database::open(db_path_str).unwrap();
+    writer.collection("test").insert(json!({"_id": "a"})).unwrap();
+    assert!(matches!(Database::open(db_path_str), Err(Error::DatabaseLocked)));
+    assert!(matches!(
+        Database::open_with_options(db_path_str, read_only.clone()),
+        Err(Error::DatabaseLocked)
+    ));
+    writer.close().unwrap();
+
+    let reader1 = Database::open_with_options(db_path_str, read_only.clone()).unwrap();
+    let reader2 = Database::open_with_options(db_path_str, read_only).unwrap();
+    assert!(matches!(Database::open(db_path_str), Err(Error::DatabaseLocked)));
+    assert_eq!(reader1.collection("test").count().unwrap(), 1);
+    assert_eq!(reader2.collection("test").count().unwrap(), 1);
+}
+
+#[test]
+fn test_open_waits_for_lock_timeout() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.db");
+    let db_path_str = db_path.to_str().unwrap().to_string();
+
+    let writer = Database::open(&db_path_str).unwrap();
+    let waiting = DatabaseOptions {
+        lock_timeout_ms: 50,
+        ..Default::default()
+    };
+    let started = std::time::Instant::now();
+    assert!(matches!(
+        Database::open_with_options(&db_path_str, waiting),
+        Err(Error::DatabaseLocked)
+    ));
+    assert!(started.elapsed() >= std::time::Duration::from_millis(50));
+
+    // The lock is released while the second open is still waiting for it
+    let handle = thread::spawn(move || {
+        thread::sleep(std::time::Duration::from_millis(100));
+        writer.close().unwrap();
+    });
+    let waiting = DatabaseOptions {
+        lock_timeout_ms: 5_000,
+        ..Default::default()
+    };
+    let db = Database::open_with_options(&db_path_str, waiting).unwrap();
+    handle.join().unwrap();
+    db.close().unwrap();
+}
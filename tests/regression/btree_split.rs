@@ -176,3 +176,35 @@ fn test_crash_scenario_reproduction() {
         assert_eq!(count_after, 100, "All 100 documents should persist after reopen");
     }
 }
+
+#[test]
+fn test_scans_after_copied_leaves_in_later_transactions() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test_copied_leaves.db");
+    let db = Database::open(db_path.to_str().unwrap()).unwrap();
+
+    // Later transactions copy leaves without relinking the leaf before them,
+    // which scans used to follow into the stale copy
+    for batch in 0..20 {
+        let mut tx = db.begin().unwrap();
+        let mut coll = tx.collection("test").unwrap();
+        for i in 0..20 {
+            coll.insert(json!({"_id": format!("doc_{:03}_{:02}", batch, i), "value": i})).unwrap();
+        }
+        tx.commit().unwrap();
+
+        let expected = (batch + 1) * 20;
+        assert_eq!(db.collection("test").count().unwrap(), expected, "count after batch {}", batch);
+        assert_eq!(db.collection("test").find_all().unwrap().len(), expected, "scan after batch {}", batch);
+    }
+
+    let mut tx = db.begin().unwrap();
+    let mut coll = tx.collection("test").unwrap();
+    for batch in 0..20 {
+        coll.update_by_id(&format!("doc_{:03}_05", batch), json!({"value": 100})).unwrap();
+    }
+    tx.commit().unwrap();
+
+    let updated = db.collection("test").find("value is 100").unwrap();
+    assert_eq!(updated.len(), 20);
+}
@@ -737,3 +737,67 @@ fn run_combined_worker(db_path: &str, process_id: usize) {
         handle.join().unwrap();
     }
 }
+
+/// Many writers hammering one document through `run_transaction`, with and
+/// without jitter. Every conflict has to be retried or reported as
+/// `TxConflict`, never lost; how many run out of retries depends on timing, so
+/// the counts are printed rather than compared.
+#[test]
+fn test_conflict_retry_jitter() {
+    use jasonisnthappy::core::database::TransactionConfig;
+    use jasonisnthappy::Error;
+
+    fn run(jitter: bool) -> (u64, u64) {
+        let (_temp_dir, db) = setup_test_db();
+        db.collection("counters").insert(json!({"_id": "hits", "n": 0})).unwrap();
+        db.set_transaction_config(TransactionConfig {
+            max_retries: 6,
+            retry_backoff_base_ms: 2,
+            max_retry_backoff_ms: 50,
+            jitter,
+            ..TransactionConfig::default()
+        });
+
+        let db = Arc::new(db);
+        let committed = Arc::new(AtomicU64::new(0));
+        let exhausted = Arc::new(AtomicU64::new(0));
+
+        let handles: Vec<_> = (0..16)
+            .map(|_| {
+                let db = Arc::clone(&db);
+                let committed = Arc::clone(&committed);
+                let exhausted = Arc::clone(&exhausted);
+
+                thread::spawn(move || {
+                    for _ in 0..10 {
+                        let result = db.run_transaction(|tx| {
+                            tx.collection("counters")?.update_by_id("hits", json!({"$inc": {"n": 1}}))
+                        });
+                        match result {
+                            Ok(_) => { committed.fetch_add(1, Ordering::Relaxed); }
+                            Err(Error::TxConflict) => { exhausted.fetch_add(1, Ordering::Relaxed); }
+                            Err(e) => panic!("unexpected error: {}", e),
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let committed = committed.load(Ordering::Relaxed);
+        let exhausted = exhausted.load(Ordering::Relaxed);
+        let n = db.collection("counters").find_by_id("hits").unwrap()["n"].as_u64().unwrap();
+        assert_eq!(n, committed, "every commit should count exactly once");
+        assert_eq!(committed + exhausted, 160);
+        (committed, exhausted)
+    }
+
+    let (plain_committed, plain_exhausted) = run(false);
+    let (jitter_committed, jitter_exhausted) = run(true);
+    println!("without jitter: {} committed, {} ran out of retries", plain_committed, plain_exhausted);
+    println!("with jitter:    {} committed, {} ran out of retries", jitter_committed, jitter_exhausted);
+    assert!(jitter_committed > 0);
+}
@@ -1,4 +1,4 @@
-use jasonisnthappy::Database;
+use jasonisnthappy::{Database, OutMode};
 use serde_json::json;
 use std::fs;
 
@@ -342,3 +342,115 @@ fn test_aggregation_real_world_scenario() {
     let _ = fs::remove_file(format!("{}.lock", path));
     let _ = fs::remove_file(format!("{}-wal", path));
 }
+
+#[test]
+fn test_aggregation_out() {
+    let path = "/tmp/test_aggregation_out.db";
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+
+    let db = Database::open(path).unwrap();
+    let orders = db.collection("orders");
+    orders.insert(json!({"customer": "alice", "region": "north", "amount": 10})).unwrap();
+    orders.insert(json!({"customer": "alice", "region": "north", "amount": 15})).unwrap();
+    orders.insert(json!({"customer": "bob", "region": "south", "amount": 7})).unwrap();
+
+    // Replace mode clears what was there and creates the collection if needed
+    let totals = db.collection("totals");
+    totals.insert(json!({"_id": "stale", "total": 0})).unwrap();
+    let written = orders.aggregate()
+        .group_by("customer")
+        .sum("amount", "total")
+        .out("totals")
+        .unwrap();
+    assert_eq!(written, 2);
+    assert_eq!(totals.count().unwrap(), 2);
+    assert_eq!(totals.find_by_id("alice").unwrap()["total"], 25.0);
+    assert!(totals.find_by_id("stale").is_err());
+
+    // Merge mode updates matching ids and keeps the rest
+    db.collection("merged").insert(json!({"_id": "bob", "note": "vip", "total": 0})).unwrap();
+    db.collection("merged").insert(json!({"_id": "carol", "total": 3})).unwrap();
+    let written = orders.aggregate()
+        .group_by("customer")
+        .sum("amount", "total")
+        .out_with_mode("merged", OutMode::Merge)
+        .unwrap();
+    assert_eq!(written, 2);
+    let merged = db.collection("merged");
+    assert_eq!(merged.count().unwrap(), 3);
+    let bob = merged.find_by_id("bob").unwrap();
+    assert_eq!(bob["total"], 7.0);
+    assert_eq!(bob["note"], "vip");
+
+    // JSON stages, with a compound key stored as its JSON text
+    let stages = json!([
+        {"group_by": ["region", "customer"]},
+        {"count": "orders"},
+        {"out": "by_region", "mode": "replace"}
+    ]);
+    let results = orders.aggregate().stages(stages.as_array().unwrap()).unwrap().execute().unwrap();
+    assert_eq!(results, vec![json!({"out": "by_region", "written": 2})]);
+    let by_region = db.collection("by_region").find("region is 'north'").unwrap();
+    assert_eq!(by_region.len(), 1);
+    assert_eq!(by_region[0]["orders"], 2);
+    assert!(by_region[0]["_id"].is_string());
+
+    let err = orders.aggregate()
+        .stages(&[json!({"out": "x"}), json!({"limit": 1})])
+        .err()
+        .unwrap();
+    assert_eq!(err.to_string(), "'out' at index 0 must be the last stage");
+    let err = orders.aggregate().stages(&[json!({"out": "x", "mode": "append"})]).err().unwrap();
+    assert_eq!(err.to_string(), "'mode' at index 0 must be replace or merge");
+
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_aggregation_accumulator_after_match() {
+    let path = "/tmp/test_aggregation_accumulator_after_match.db";
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+
+    let db = Database::open(path).unwrap();
+    let orders = db.collection("orders");
+    for (city, amount) in [("paris", 600), ("paris", 700), ("rome", 100), ("oslo", 2000)] {
+        orders.insert(json!({"city": city, "amount": amount})).unwrap();
+    }
+
+    // An accumulator added after the match still attaches to the group_by
+    let mut results = orders.aggregate()
+        .group_by("city")
+        .match_("total > 1000")
+        .sum("amount", "total")
+        .execute()
+        .unwrap();
+    results.sort_by(|a, b| a["_id"].as_str().cmp(&b["_id"].as_str()));
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0]["_id"], "oslo");
+    assert_eq!(results[0]["total"], 2000.0);
+    assert_eq!(results[1]["_id"], "paris");
+    assert_eq!(results[1]["total"], 1300.0);
+
+    let stages = json!([
+        {"group_by": "city"},
+        {"match": "orders >= 2"},
+        {"count": "orders"}
+    ]);
+    let results = orders.aggregate().stages(stages.as_array().unwrap()).unwrap().execute().unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0]["_id"], "paris");
+
+    // Without any group_by the accumulator is an error rather than dropped
+    let err = orders.aggregate().match_("amount > 0").count("n").execute().err().unwrap();
+    assert_eq!(err.to_string(), "Accumulator 'n' has no group_by stage before it");
+
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
@@ -0,0 +1,81 @@
+#![cfg(feature = "tokio")]
+
+use jasonisnthappy::{AsyncDatabase, Database, Error};
+use serde_json::json;
+use std::fs;
+use std::sync::Arc;
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_async_crud_and_aggregate() {
+    let path = "/tmp/test_async_crud.db";
+    cleanup(path);
+
+    let db = AsyncDatabase::open(path).await.unwrap();
+    let users = db.collection("users");
+
+    let alice = users.insert(json!({"name": "Alice", "city": "Paris", "age": 30})).await.unwrap();
+    users.insert_many(vec![
+        json!({"name": "Bob", "city": "Paris", "age": 25}),
+        json!({"name": "Carol", "city": "Oslo", "age": 35}),
+    ]).await.unwrap();
+
+    assert_eq!(users.find_by_id(&alice).await.unwrap()["name"], "Alice");
+    assert_eq!(users.count().await.unwrap(), 3);
+    assert_eq!(users.find("city is 'Paris'").await.unwrap().len(), 2);
+
+    users.update_by_id(&alice, json!({"$set": {"age": 31}})).await.unwrap();
+    assert_eq!(users.find_one("name is 'Alice'").await.unwrap().unwrap()["age"], 31);
+    assert_eq!(users.delete("age > 32").await.unwrap(), 1);
+
+    let groups = users.aggregate(vec![json!({"group_by": "city"}), json!({"count": "users"})]).await.unwrap();
+    assert_eq!(groups.len(), 1);
+    assert_eq!(groups[0]["users"], 2);
+
+    users.delete_by_id(&alice).await.unwrap();
+    assert!(matches!(users.find_by_id(&alice).await, Err(Error::NotFound | Error::DocumentNotFound { .. })));
+    assert_eq!(db.list_collections().await.unwrap(), ["users"]);
+
+    db.close().await.unwrap();
+    cleanup(path);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_async_shares_database_with_blocking_code() {
+    let path = "/tmp/test_async_shared.db";
+    cleanup(path);
+
+    let sync_db = Arc::new(Database::open(path).unwrap());
+    let db = AsyncDatabase::from(sync_db.clone());
+
+    // Writes from a plain thread are visible to the async side
+    let writer = sync_db.clone();
+    std::thread::spawn(move || {
+        writer.collection("events").insert(json!({"_id": "e1"})).unwrap();
+    }).join().unwrap();
+    assert_eq!(db.collection("events").find_all().await.unwrap().len(), 1);
+
+    let tasks: Vec<_> = (0..8).map(|i| {
+        let events = db.collection("events");
+        tokio::spawn(async move { events.insert(json!({"n": i})).await.unwrap() })
+    }).collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+    assert_eq!(sync_db.collection("events").count().unwrap(), 9);
+
+    db.run(|db| {
+        let mut tx = db.begin()?;
+        tx.collection("events")?.delete_by_id("e1")?;
+        tx.commit()
+    }).await.unwrap();
+    assert_eq!(db.collection("events").run(|events| events.count()).await.unwrap(), 8);
+
+    sync_db.close().unwrap();
+    cleanup(path);
+}
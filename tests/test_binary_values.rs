@@ -0,0 +1,114 @@
+use jasonisnthappy::{binary_bytes, binary_value, Database, Error};
+use serde_json::json;
+use std::fs;
+
+fn open(path: &str) -> Database {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+    Database::open(path).unwrap()
+}
+
+fn cleanup(db: Database, path: &str) {
+    db.close().unwrap();
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_insert_and_get_binary() {
+    let path = "/tmp/test_binary_values_helpers.db";
+    let db = open(path);
+    let thumbs = db.collection("thumbs");
+
+    // Large enough to spill onto overflow pages
+    let image: Vec<u8> = (0..20_000u32).map(|i| (i * 7 % 251) as u8).collect();
+    thumbs.insert_binary("cat", &image).unwrap();
+    thumbs.insert_binary("empty", &[]).unwrap();
+
+    assert_eq!(thumbs.get_binary("cat").unwrap(), image);
+    assert_eq!(thumbs.get_binary("empty").unwrap(), Vec::<u8>::new());
+
+    // Through the JSON API the bytes read back as base64
+    let doc = thumbs.find_by_id("cat").unwrap();
+    assert_eq!(binary_bytes(&doc["data"]).unwrap(), image);
+
+    thumbs.insert(json!({"_id": "text", "data": "not binary"})).unwrap();
+    let err = thumbs.get_binary("text").unwrap_err();
+    assert!(matches!(err, Error::InvalidDocumentFormat { .. }), "{:?}", err);
+    assert!(thumbs.get_binary("dog").is_err());
+
+    // The bytes go into the file as they are, not as base64 text
+    db.checkpoint().unwrap();
+    let file = fs::read(path).unwrap();
+    let base64 = doc["data"]["$binary"].as_str().unwrap();
+    assert!(file.windows(64).any(|window| window == &image[..64]));
+    assert!(!file.windows(64).any(|window| window == &base64.as_bytes()[..64]));
+
+    cleanup(db, path);
+}
+
+#[test]
+fn test_binary_fields_in_documents() {
+    let path = "/tmp/test_binary_values_documents.db";
+    let db = open(path);
+    let files = db.collection("files");
+
+    files.insert(json!({
+        "_id": "a",
+        "name": "a.bin",
+        "thumb": binary_value(b"\x00\x01\x02"),
+        "chunks": [binary_value(b"one"), binary_value(b"two")],
+    })).unwrap();
+    files.insert(json!({"_id": "b", "name": "b.bin", "thumb": binary_value(b"other")})).unwrap();
+
+    let doc = files.find_by_id("a").unwrap();
+    assert_eq!(binary_bytes(&doc["thumb"]).unwrap(), b"\x00\x01\x02");
+    assert_eq!(binary_bytes(&doc["chunks"][1]).unwrap(), b"two");
+
+    // Equality is the one comparison binary values support
+    let found = files.find("thumb is binary('AAEC')").unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0]["_id"], "a");
+    assert_eq!(files.find("thumb is not binary('AAEC')").unwrap().len(), 1);
+    assert!(files.find("thumb > binary('AAEC')").is_err());
+
+    // Updates keep binary values that they don't touch
+    files.update_by_id("a", json!({"$set": {"name": "renamed.bin"}})).unwrap();
+    let doc = files.find_by_id("a").unwrap();
+    assert_eq!(doc["name"], "renamed.bin");
+    assert_eq!(binary_bytes(&doc["thumb"]).unwrap(), b"\x00\x01\x02");
+
+    let mut tx = db.begin().unwrap();
+    let mut tx_files = tx.collection("files").unwrap();
+    tx_files.insert(json!({"_id": "c", "thumb": binary_value(b"in tx")})).unwrap();
+    assert_eq!(binary_bytes(&tx_files.find_by_id("c").unwrap()["thumb"]).unwrap(), b"in tx");
+    tx.commit().unwrap();
+    assert_eq!(binary_bytes(&files.find_by_id("c").unwrap()["thumb"]).unwrap(), b"in tx");
+
+    for bad in [json!({"$binary": "%%"}), json!({"$binary": "AA==", "type": 0}), json!({"$binary": 3})] {
+        let err = files.insert(json!({"thumb": bad})).unwrap_err();
+        assert!(matches!(err, Error::InvalidDocumentFormat { .. }), "{:?}", err);
+    }
+
+    cleanup(db, path);
+}
+
+#[test]
+fn test_binary_schema_type() {
+    let path = "/tmp/test_binary_values_schema.db";
+    let db = open(path);
+
+    db.set_json_schema("thumbs", &json!({
+        "type": "object",
+        "properties": {"data": {"type": "binary", "maxLength": 8}}
+    })).unwrap();
+    let thumbs = db.collection("thumbs");
+
+    thumbs.insert_binary("small", b"12345678").unwrap();
+    assert!(thumbs.insert_binary("large", b"123456789").is_err());
+    assert!(thumbs.insert(json!({"_id": "text", "data": "12345678"})).is_err());
+
+    cleanup(db, path);
+}
@@ -1,4 +1,4 @@
-use jasonisnthappy::Database;
+use jasonisnthappy::{Database, IndexOptions};
 use serde_json::json;
 use std::fs;
 
@@ -292,3 +292,276 @@ fn test_bulk_write_performance_single_transaction() {
     let _ = fs::remove_file(format!("{}.lock", path));
     let _ = fs::remove_file(format!("{}-wal", path));
 }
+
+#[test]
+fn test_bulk_write_upserts() {
+    let path = "/tmp/test_bulk_upserts.db";
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+
+    let db = Database::open(path).unwrap();
+    let users = db.collection("users");
+
+    users.insert(json!({"_id": "alice", "name": "Alice", "age": 30})).unwrap();
+
+    let result = users.bulk_write()
+        // Matches Alice
+        .upsert_one("name is \"Alice\"", json!({"age": 31}))
+        // Matches nothing, so Zed is inserted...
+        .upsert_one("name is \"Zed\"", json!({"name": "Zed", "age": 40}))
+        // ...and later operations in the same batch see him
+        .upsert_one("name is \"Zed\"", json!({"age": 41}))
+        .update_one("name is \"Zed\"", json!({"visited": true}))
+        .upsert_by_id("alice", json!({"city": "NYC"}))
+        .upsert_by_id("bob", json!({"name": "Bob", "age": 25}))
+        .upsert_by_id("bob", json!({"age": 26}))
+        .execute()
+        .unwrap();
+
+    assert_eq!(result.inserted_count, 0);
+    assert_eq!(result.upserted_count, 2, "Zed and Bob should be inserted");
+    assert_eq!(result.updated_count, 5);
+    assert!(result.errors.is_empty());
+
+    let alice = users.find_by_id("alice").unwrap();
+    assert_eq!(alice["age"], 31);
+    assert_eq!(alice["city"], "NYC");
+
+    let zed = users.find_one("name is \"Zed\"").unwrap().unwrap();
+    assert_eq!(zed["age"], 41);
+    assert_eq!(zed["visited"], true);
+
+    let bob = users.find_by_id("bob").unwrap();
+    assert_eq!(bob["name"], "Bob");
+    assert_eq!(bob["age"], 26);
+
+    assert_eq!(users.count().unwrap(), 3);
+
+    // Non-object documents are rejected
+    let err = users.bulk_write().upsert_by_id("carol", json!(42)).execute();
+    assert!(err.is_err());
+
+    db.close().unwrap();
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_bulk_write_maintains_secondary_indexes() {
+    let path = "/tmp/test_bulk_indexes.db";
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+
+    let db = Database::open(path).unwrap();
+    let users = db.collection("users");
+
+    users.insert(json!({"_id": "alice", "email": "alice@example.com"})).unwrap();
+    db.create_index_with_options("users", "email_idx", &["email"], IndexOptions { unique: true, ..Default::default() }).unwrap();
+
+    let by_email = |email: &str| -> Vec<String> {
+        users.query()
+            .filter(&format!("email is \"{}\"", email))
+            .hint("email_idx")
+            .execute()
+            .unwrap()
+            .iter()
+            .map(|doc| doc["_id"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    // Inserts, updates and deletes all reach the index
+    let result = users.bulk_write()
+        .insert(json!({"_id": "bob", "email": "bob@example.com"}))
+        .insert(json!({"_id": "carol", "email": "carol@example.com"}))
+        .update_one("email is \"carol@example.com\"", json!({"email": "carol@example.org"}))
+        .delete_one("email is \"alice@example.com\"")
+        .execute()
+        .unwrap();
+    assert!(result.errors.is_empty());
+
+    assert_eq!(by_email("bob@example.com"), vec!["bob"]);
+    assert_eq!(by_email("carol@example.org"), vec!["carol"]);
+    assert!(by_email("carol@example.com").is_empty());
+    assert!(by_email("alice@example.com").is_empty());
+
+    // A duplicate within the batch fails an ordered write as a whole
+    let err = users.bulk_write()
+        .insert(json!({"_id": "dave", "email": "dave@example.com"}))
+        .insert(json!({"_id": "dave2", "email": "dave@example.com"}))
+        .execute()
+        .unwrap_err();
+    assert!(err.to_string().contains("operation 1"), "{}", err);
+    assert!(err.to_string().contains("unique constraint violation"), "{}", err);
+    assert!(by_email("dave@example.com").is_empty());
+
+    // So does a duplicate of a committed value, including from insert_many
+    assert!(users.bulk_write().insert(json!({"email": "bob@example.com"})).execute().is_err());
+    assert!(users.insert_many(vec![json!({"email": "bob@example.com"})]).is_err());
+    assert_eq!(users.count().unwrap(), 2);
+
+    // Unordered writes skip the duplicate and keep the rest. Freeing a value
+    // earlier in the batch lets a later document take it.
+    let result = users.bulk_write()
+        .ordered(false)
+        .update_one("email is \"bob@example.com\"", json!({"email": "bob@example.org"}))
+        .insert(json!({"_id": "erin", "email": "bob@example.com"}))
+        .insert(json!({"_id": "frank", "email": "carol@example.org"}))
+        .insert(json!({"_id": "gina", "email": "gina@example.com"}))
+        .execute()
+        .unwrap();
+    assert_eq!(result.inserted_count, 2);
+    assert_eq!(result.errors.len(), 1);
+    assert_eq!(result.errors[0].operation_index, 2);
+
+    assert_eq!(by_email("bob@example.org"), vec!["bob"]);
+    assert_eq!(by_email("bob@example.com"), vec!["erin"]);
+    assert_eq!(by_email("carol@example.org"), vec!["carol"]);
+    assert_eq!(by_email("gina@example.com"), vec!["gina"]);
+
+    // The index survives reopening
+    db.close().unwrap();
+    let db = Database::open(path).unwrap();
+    let users = db.collection("users");
+    let found = users.query().filter("email is \"gina@example.com\"").hint("email_idx").execute().unwrap();
+    assert_eq!(found.len(), 1);
+    assert!(users.bulk_write().insert(json!({"email": "gina@example.com"})).execute().is_err());
+
+    db.close().unwrap();
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_insert_many_chunked_commits_each_chunk() {
+    let path = "/tmp/test_insert_many_chunked.db";
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+
+    let db = Database::open(path).unwrap();
+    let events = db.collection("events");
+
+    let docs = (0..25).map(|i| json!({"_id": format!("e{:02}", i), "seq": i}));
+    let result = events.insert_many_chunked(docs, 10).unwrap();
+    assert!(result.failed_chunk.is_none());
+    assert_eq!(result.chunks_committed, 3);
+    assert_eq!(result.inserted_ids.len(), 25);
+    assert_eq!(result.inserted_ids[24], "e24");
+    assert_eq!(events.count().unwrap(), 25);
+
+    // A duplicate in the second chunk stops the load there; the first chunk stays
+    let docs = (25..45).map(|i| {
+        let id = if i == 37 { "e03".to_string() } else { format!("e{:02}", i) };
+        json!({"_id": id, "seq": i})
+    });
+    let result = events.insert_many_chunked(docs, 10).unwrap();
+    assert_eq!(result.chunks_committed, 1);
+    assert_eq!(result.inserted_ids.len(), 10);
+    let failure = result.failed_chunk.unwrap();
+    assert_eq!(failure.chunk_index, 1);
+    assert!(failure.error.to_string().contains("e03"), "{}", failure.error);
+    assert_eq!(events.count().unwrap(), 35);
+    assert!(events.find_by_id("e35").is_err());
+
+    assert!(events.insert_many_chunked(Vec::new(), 0).is_err());
+    assert!(events.insert_many_chunked(Vec::new(), db.max_bulk_operations() + 1).is_err());
+    assert_eq!(events.insert_many_chunked(Vec::new(), 10).unwrap().chunks_committed, 0);
+
+    db.close().unwrap();
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_validate_many_reports_every_failure_without_writing() {
+    let path = "/tmp/test_validate_many.db";
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+
+    let db = Database::open(path).unwrap();
+    db.set_json_schema("users", &json!({
+        "type": "object",
+        "required": ["name"],
+        "properties": {"name": {"type": "string"}}
+    })).unwrap();
+    db.create_index("users", "email_idx", "email", true).unwrap();
+    let users = db.collection("users");
+    users.insert(json!({"_id": "alice", "name": "Alice", "email": "alice@example.com"})).unwrap();
+
+    let docs = vec![
+        json!({"name": "Bob", "email": "bob@example.com"}),
+        json!({"name": 42}),
+        json!({"_id": "alice", "name": "Alice again"}),
+        json!({"name": "Carol", "email": "alice@example.com"}),
+        json!({"_id": "dan", "name": "Dan", "email": "dan@example.com"}),
+        json!({"_id": "dan", "name": "Dan twice"}),
+        json!({"name": "Dan's twin", "email": "dan@example.com"}),
+        json!("not an object"),
+    ];
+    let failures = users.validate_many(&docs).unwrap();
+    let indexes: Vec<usize> = failures.iter().map(|f| f.operation_index).collect();
+    assert_eq!(indexes, vec![1, 2, 3, 5, 6, 7], "{:?}", failures);
+    assert!(failures[0].message.contains("name"), "{}", failures[0].message);
+
+    // Nothing was written
+    assert_eq!(users.count().unwrap(), 1);
+
+    // What validates is what insert_many accepts
+    let valid: Vec<_> = docs.iter().enumerate()
+        .filter(|(i, _)| !indexes.contains(i))
+        .map(|(_, doc)| doc.clone())
+        .collect();
+    assert!(users.validate_many(&valid).unwrap().is_empty());
+    users.insert_many(valid).unwrap();
+    assert_eq!(users.count().unwrap(), 3);
+
+    assert!(users.insert_many(vec![json!({"name": 1})]).is_err());
+
+    db.close().unwrap();
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_bulk_write_dry_run() {
+    let path = "/tmp/test_bulk_dry_run.db";
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+
+    let db = Database::open(path).unwrap();
+    let users = db.collection("users");
+    users.insert(json!({"_id": "a", "name": "Alice", "age": 30})).unwrap();
+    users.insert(json!({"_id": "b", "name": "Bob", "age": 25})).unwrap();
+
+    let bulk = || users.bulk_write()
+        .insert(json!({"_id": "a", "name": "Duplicate"}))
+        .insert(json!({"name": "Carol", "age": 35}))
+        .update_many("age > 20", json!({"$inc": {"age": 1}}))
+        .delete_one("name is \"Bob\"")
+        .update_one("name is \"Alice\"", json!({"$bogus": 1}));
+
+    // Ordered mode would stop at the first error, the dry run lists them all
+    let result = bulk().dry_run().unwrap();
+    assert_eq!(result.inserted_count, 1);
+    assert_eq!(result.updated_count, 3);
+    assert_eq!(result.deleted_count, 1);
+    let failed: Vec<usize> = result.errors.iter().map(|e| e.operation_index).collect();
+    assert_eq!(failed, vec![0, 4]);
+
+    assert_eq!(users.count().unwrap(), 2);
+    assert_eq!(users.find_by_id("a").unwrap()["age"], 30);
+    assert!(bulk().execute().is_err());
+
+    db.close().unwrap();
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
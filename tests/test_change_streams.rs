@@ -285,3 +285,141 @@ fn test_watch_different_collections() {
     let _ = fs::remove_file(format!("{}.lock", path));
     let _ = fs::remove_file(format!("{}-wal", path));
 }
+
+#[test]
+fn test_watch_update_old_document() {
+    let path = "/tmp/test_watch_update_old_doc.db";
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+
+    let db = Arc::new(Database::open(path).unwrap());
+    let coll = db.collection("users");
+
+    let id = coll.insert(json!({"name": "Alice", "age": 30})).unwrap();
+
+    let (_handle, rx) = coll.watch()
+        .with_full_document_before_change()
+        .subscribe()
+        .unwrap();
+    let (_plain_handle, plain_rx) = coll.watch().subscribe().unwrap();
+
+    coll.update_by_id(&id, json!({"age": 31})).unwrap();
+
+    let event = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(event.operation, ChangeOperation::Update);
+    assert_eq!(event.document.as_ref().unwrap()["age"], 31);
+    assert_eq!(event.old_document.as_ref().unwrap()["age"], 30);
+
+    // Watchers that didn't ask for the before-image don't get it
+    let plain_event = plain_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(plain_event.operation, ChangeOperation::Update);
+    assert!(plain_event.old_document.is_none());
+
+    // Multiple updates in one transaction report the state before the transaction
+    {
+        let mut tx = db.begin().unwrap();
+        let mut users = tx.collection("users").unwrap();
+        users.update_by_id(&id, json!({"age": 32})).unwrap();
+        users.update_by_id(&id, json!({"age": 33})).unwrap();
+        tx.commit().unwrap();
+    }
+
+    let event = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(event.operation, ChangeOperation::Update);
+    assert_eq!(event.document.as_ref().unwrap()["age"], 33);
+    assert_eq!(event.old_document.as_ref().unwrap()["age"], 31);
+
+    db.close().unwrap();
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_watch_delete_old_document() {
+    let path = "/tmp/test_watch_delete_old_doc.db";
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+
+    let db = Arc::new(Database::open(path).unwrap());
+    let coll = db.collection("users");
+
+    let alice = coll.insert(json!({"name": "Alice"})).unwrap();
+    let bob = coll.insert(json!({"name": "Bob"})).unwrap();
+
+    let (_handle, rx) = coll.watch()
+        .with_full_document_before_change()
+        .subscribe()
+        .unwrap();
+
+    coll.delete_by_id(&alice).unwrap();
+
+    let event = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(event.operation, ChangeOperation::Delete);
+    assert_eq!(event.doc_id, alice);
+    assert!(event.document.is_none());
+    assert_eq!(event.old_document.as_ref().unwrap()["name"], "Alice");
+
+    {
+        let mut tx = db.begin().unwrap();
+        let mut users = tx.collection("users").unwrap();
+        users.delete_by_id(&bob).unwrap();
+        tx.commit().unwrap();
+    }
+
+    let event = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(event.operation, ChangeOperation::Delete);
+    assert_eq!(event.doc_id, bob);
+    assert_eq!(event.old_document.as_ref().unwrap()["name"], "Bob");
+
+    db.close().unwrap();
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_watch_operations() {
+    let path = "/tmp/test_watch_operations.db";
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+
+    let db = Arc::new(Database::open(path).unwrap());
+    let coll = db.collection("users");
+
+    let (_inserts, insert_rx) = coll.watch()
+        .operations(&[ChangeOperation::Insert])
+        .subscribe()
+        .unwrap();
+    let (_changes, change_rx) = coll.watch()
+        .operations(&[ChangeOperation::Update, ChangeOperation::Delete])
+        .filter("age > 28")
+        .subscribe()
+        .unwrap();
+
+    let alice = coll.insert(json!({"name": "Alice", "age": 30})).unwrap();
+    let bob = coll.insert(json!({"name": "Bob", "age": 25})).unwrap();
+    coll.update_by_id(&alice, json!({"$set": {"age": 31}})).unwrap();
+    coll.update_by_id(&bob, json!({"$set": {"age": 26}})).unwrap();
+    coll.delete_by_id(&bob).unwrap();
+
+    let event = insert_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!((event.operation, event.doc_id), (ChangeOperation::Insert, alice.clone()));
+    let event = insert_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!((event.operation, event.doc_id), (ChangeOperation::Insert, bob));
+    assert!(insert_rx.recv_timeout(Duration::from_millis(100)).is_err());
+
+    // Both the operation and the filter have to match: Bob's update is
+    // filtered out, and his delete has no document to filter
+    let event = change_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!((event.operation, event.doc_id), (ChangeOperation::Update, alice));
+    assert!(change_rx.recv_timeout(Duration::from_millis(100)).is_err());
+
+    db.close().unwrap();
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
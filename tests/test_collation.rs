@@ -0,0 +1,166 @@
+use jasonisnthappy::{AccessPath, Collation, Database, Error, IndexOptions, SortOrder};
+use serde_json::json;
+use std::fs;
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+fn names(docs: &[serde_json::Value]) -> Vec<&str> {
+    docs.iter().map(|doc| doc["name"].as_str().unwrap()).collect()
+}
+
+#[test]
+fn test_query_collation_filters_and_sorts() {
+    let path = "/tmp/test_collation_query.db";
+    cleanup(path);
+    let db = Database::open(path).unwrap();
+    let users = db.collection("users");
+    users.insert_many(vec![
+        json!({"name": "bob", "tags": ["Rust"]}),
+        json!({"name": "Alice", "tags": ["go"]}),
+        json!({"name": "carol", "tags": ["RUST", "go"]}),
+        json!({"name": "ALICE"}),
+        json!({"name": "Dave"}),
+        json!({"name": "Émile"}),
+    ]).unwrap();
+
+    let alices = users.query().filter("name is 'alice'").execute().unwrap();
+    assert!(alices.is_empty());
+    let alices = users.query()
+        .filter("name is 'alice'")
+        .collation(Collation::CaseInsensitive)
+        .execute()
+        .unwrap();
+    assert_eq!(alices.len(), 2);
+
+    let ci = |filter: &str, collation| users.query().filter(filter).collation(collation).count().unwrap();
+    assert_eq!(ci("name is not 'ALICE'", Collation::CaseInsensitive), 4);
+    assert_eq!(ci("name > 'c'", Collation::CaseInsensitive), 3);
+    assert_eq!(ci("tags has 'rust'", Collation::CaseInsensitive), 2);
+    assert_eq!(ci("tags has all ['RUST', 'GO']", Collation::CaseInsensitive), 1);
+    assert_eq!(ci("lower(name) is 'ALICE'", Collation::CaseInsensitive), 2);
+    // ASCII folding leaves other letters alone
+    assert_eq!(ci("name is 'émile'", Collation::AsciiCaseInsensitive), 0);
+    assert_eq!(ci("name is 'émile'", Collation::CaseInsensitive), 1);
+
+    let sorted = users.query().sort_by("name", SortOrder::Asc).execute().unwrap();
+    assert_eq!(names(&sorted), ["ALICE", "Alice", "Dave", "bob", "carol", "Émile"]);
+    let sorted = users.query()
+        .sort_by("name", SortOrder::Asc)
+        .collation(Collation::CaseInsensitive)
+        .execute()
+        .unwrap();
+    assert_eq!(names(&sorted), ["Alice", "ALICE", "bob", "carol", "Dave", "Émile"]);
+
+    // The collation also picks the document find_one_and_update changes
+    let claimed = users.query()
+        .filter("name is 'DAVE'")
+        .collation(Collation::CaseInsensitive)
+        .find_one_and_update(json!({"seen": true}), true)
+        .unwrap()
+        .unwrap();
+    assert_eq!(claimed["name"], "Dave");
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_case_insensitive_unique_index() {
+    let path = "/tmp/test_collation_index.db";
+    cleanup(path);
+    let db = Database::open(path).unwrap();
+    let users = db.collection("users");
+    users.insert(json!({"username": "Alice"})).unwrap();
+
+    let options = IndexOptions { unique: true, collation: Collation::CaseInsensitive, ..Default::default() };
+    db.create_index_with_options("users", "username_ci", &["username"], options).unwrap();
+
+    let result = users.insert(json!({"username": "ALICE"}));
+    assert!(matches!(result, Err(Error::DuplicateKey { .. })), "{:?}", result);
+    users.insert(json!({"username": "bob"})).unwrap();
+    users.insert(json!({"username": "Carol"})).unwrap();
+    let result = users.update("username is 'bob'", json!({"username": "alice"}));
+    assert!(result.is_err());
+
+    // Existing values differing only in case keep the index from being created
+    let dup = db.collection("dup");
+    dup.insert_many(vec![json!({"code": "ab"}), json!({"code": "AB"})]).unwrap();
+    assert!(db.create_index_with_options("dup", "code_ci", &["code"], options).is_err());
+    let binary = IndexOptions { unique: true, ..Default::default() };
+    db.create_index_with_options("dup", "code_bin", &["code"], binary).unwrap();
+
+    // Queries with the index's collation read their sort order from it
+    let explain = users.query()
+        .sort_by("username", SortOrder::Asc)
+        .collation(Collation::CaseInsensitive)
+        .explain()
+        .unwrap();
+    assert_eq!(explain.access_path, AccessPath::IndexScan);
+    assert!(!explain.in_memory_sort);
+    let explain = users.query().sort_by("username", SortOrder::Asc).explain().unwrap();
+    assert!(explain.in_memory_sort);
+
+    let sorted = users.query()
+        .sort_by("username", SortOrder::Asc)
+        .collation(Collation::CaseInsensitive)
+        .project(&["username"])
+        .execute()
+        .unwrap();
+    // Never covered, since the index holds folded strings
+    assert_eq!(
+        sorted.iter().map(|doc| doc["username"].as_str().unwrap()).collect::<Vec<_>>(),
+        ["Alice", "bob", "Carol"]
+    );
+    assert_eq!(users.distinct("username").unwrap(), [json!("Alice"), json!("bob"), json!("Carol")]);
+    db.close().unwrap();
+
+    let db = Database::open(path).unwrap();
+    let index = db.list_indexes("users").unwrap().into_iter().find(|idx| idx.name == "username_ci").unwrap();
+    assert_eq!(index.collation, Collation::CaseInsensitive);
+    assert!(db.collection("users").insert(json!({"username": "CAROL"})).is_err());
+    assert!(db.check_integrity().unwrap().is_ok());
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_case_insensitive_expression_index() {
+    let path = "/tmp/test_collation_expression.db";
+    cleanup(path);
+    let db = Database::open(path).unwrap();
+    let users = db.collection("users");
+    users.insert_many(vec![json!({"email": "Ann@Example.com"}), json!({"email": "bob@example.com"})]).unwrap();
+
+    let options = IndexOptions { collation: Collation::CaseInsensitive, ..Default::default() };
+    db.create_expression_index("users", "email_upper", "upper(email)", options).unwrap();
+
+    // A binary query reads candidates from the index and checks them exactly
+    let explain = users.query().filter("upper(email) is 'ANN@EXAMPLE.COM'").explain().unwrap();
+    assert_eq!(explain.access_path, AccessPath::ExpressionIndexScan);
+    assert_eq!(explain.docs_returned, 1);
+    assert_eq!(users.query().filter("upper(email) is 'ann@example.com'").count().unwrap(), 0);
+
+    let explain = users.query()
+        .filter("upper(email) is 'ann@example.com'")
+        .collation(Collation::CaseInsensitive)
+        .explain()
+        .unwrap();
+    assert_eq!(explain.access_path, AccessPath::ExpressionIndexScan);
+    assert_eq!(explain.docs_returned, 1);
+
+    // An index under another collation than the query's can't answer it
+    let explain = users.query()
+        .filter("upper(email) is 'ann@example.com'")
+        .collation(Collation::AsciiCaseInsensitive)
+        .explain()
+        .unwrap();
+    assert_eq!(explain.access_path, AccessPath::CollectionScan);
+    assert_eq!(explain.docs_returned, 1);
+
+    db.close().unwrap();
+    cleanup(path);
+}
@@ -1,4 +1,4 @@
-use jasonisnthappy::Database;
+use jasonisnthappy::{AccessPath, Database, Error, IndexOptions, SortOrder, TextIndexOptions};
 use serde_json::json;
 use std::fs;
 
@@ -448,3 +448,297 @@ fn test_multiple_compound_indexes_same_collection() {
 
     cleanup_test_db("multiple_indexes");
 }
+
+#[test]
+fn test_rebuild_index_reports_progress_and_keeps_definition() {
+    let db = setup_test_db("rebuild_progress");
+
+    let mut tx = db.begin().unwrap();
+    let mut events = tx.collection("events").unwrap();
+    for i in 0..2500 {
+        events.insert(json!({ "seq": i, "kind": if i % 2 == 0 { "a" } else { "b" } })).unwrap();
+    }
+    tx.commit().unwrap();
+
+    let options = IndexOptions { unique: true, descending: true, ..Default::default() };
+    db.create_index_with_options("events", "seq_desc", &["seq"], options).unwrap();
+
+    let mut calls = Vec::new();
+    db.rebuild_index_with_progress("events", "seq_desc", |done, total| calls.push((done, total))).unwrap();
+    assert_eq!(calls, vec![(1000, 2500), (2000, 2500), (2500, 2500)]);
+
+    let info = db.list_indexes("events").unwrap().into_iter().find(|i| i.name == "seq_desc").unwrap();
+    assert_eq!(info.fields, vec!["seq"]);
+    assert!(info.unique);
+    assert!(info.descending);
+
+    // The rebuilt index holds every document, so it can answer covered queries
+    let events = db.collection("events");
+    let query = || events.query().project(&["seq"]).sort_by("seq", SortOrder::Desc).limit(1);
+    assert_eq!(query().explain().unwrap().access_path, AccessPath::CoveringIndexScan);
+    assert_eq!(query().execute().unwrap()[0]["seq"], 2499);
+
+    // Still enforced after the rebuild
+    let mut tx = db.begin().unwrap();
+    let mut events = tx.collection("events").unwrap();
+    assert!(events.insert(json!({ "seq": 7 })).is_err());
+
+    cleanup_test_db("rebuild_progress");
+}
+
+#[test]
+fn test_rebuild_text_index_keeps_options() {
+    let db = setup_test_db("rebuild_text");
+
+    let mut tx = db.begin().unwrap();
+    let mut posts = tx.collection("posts").unwrap();
+    posts.insert(json!({ "title": "the quick brown fox" })).unwrap();
+    posts.insert(json!({ "title": "a lazy dog" })).unwrap();
+    tx.commit().unwrap();
+
+    let options = TextIndexOptions {
+        stopwords: vec!["the".to_string()],
+        ..Default::default()
+    };
+    db.create_text_index("posts", "title_text", &["title"], options.clone()).unwrap();
+
+    let mut calls = Vec::new();
+    db.rebuild_index_with_progress("posts", "title_text", |done, total| calls.push((done, total))).unwrap();
+    assert_eq!(calls, vec![(2, 2)]);
+
+    let info = db.list_indexes("posts").unwrap().into_iter().find(|i| i.name == "title_text").unwrap();
+    assert_eq!(info.text_options, Some(options));
+
+    let posts = db.collection("posts");
+    assert_eq!(posts.search("fox").unwrap().len(), 1);
+    assert!(posts.search("the").unwrap().is_empty());
+
+    cleanup_test_db("rebuild_text");
+}
+
+#[test]
+fn test_rebuild_index_frees_the_old_tree() {
+    let db = setup_test_db("rebuild_frees");
+
+    let mut tx = db.begin().unwrap();
+    let mut events = tx.collection("events").unwrap();
+    for i in 0..2000 {
+        events.insert(json!({ "seq": i, "title": format!("event{}", i) })).unwrap();
+    }
+    tx.commit().unwrap();
+    db.create_index("events", "seq_idx", "seq", false).unwrap();
+    db.create_text_index("events", "title_text", &["title"], TextIndexOptions::default()).unwrap();
+
+    db.rebuild_index("events", "seq_idx").unwrap();
+    let stats = db.space_stats().unwrap();
+    assert!(stats.free_pages > 0);
+
+    // Later rebuilds reuse the pages the earlier ones freed
+    for _ in 0..3 {
+        db.rebuild_index("events", "seq_idx").unwrap();
+    }
+    assert_eq!(db.space_stats().unwrap().total_pages, stats.total_pages);
+
+    // Freed pages of a text index include its posting lists
+    db.rebuild_index("events", "title_text").unwrap();
+    assert!(db.space_stats().unwrap().free_pages > stats.free_pages);
+    assert!(db.check_integrity().unwrap().is_ok());
+    assert_eq!(db.collection("events").search("event42").unwrap().len(), 1);
+
+    cleanup_test_db("rebuild_frees");
+}
+
+#[test]
+fn test_unique_index_build_names_conflicting_documents() {
+    let db = setup_test_db("unique_build_conflict");
+
+    let mut tx = db.begin().unwrap();
+    let mut users = tx.collection("users").unwrap();
+    users.insert(json!({ "_id": "alice", "email": "shared@example.com" })).unwrap();
+    users.insert(json!({ "_id": "bob", "email": "shared@example.com" })).unwrap();
+    tx.commit().unwrap();
+
+    let err = db.create_index("users", "email_idx", "email", true).unwrap_err().to_string();
+    assert!(err.contains("unique constraint"));
+    assert!(err.contains("alice") && err.contains("bob"));
+    assert!(db.list_indexes("users").unwrap().is_empty());
+
+    cleanup_test_db("unique_build_conflict");
+}
+
+#[test]
+fn test_rebuild_index_errors() {
+    let db = setup_test_db("rebuild_errors");
+
+    let mut tx = db.begin().unwrap();
+    tx.collection("users").unwrap().insert(json!({ "email": "a@example.com" })).unwrap();
+    tx.commit().unwrap();
+
+    assert!(db.rebuild_index("users", "missing_idx").is_err());
+    assert!(db.rebuild_index("missing", "email_idx").is_err());
+
+    db.create_index("users", "email_idx", "email", true).unwrap();
+    db.rebuild_index("users", "email_idx").unwrap();
+    db.rebuild_index("users", "email_idx").unwrap();
+
+    cleanup_test_db("rebuild_errors");
+}
+
+#[test]
+fn test_unique_index_enforced_on_single_document_writes() {
+    let db = setup_test_db("unique_single_writes");
+    let users = db.collection("users");
+
+    let alice = users.insert(json!({ "username": "alice" })).unwrap();
+    db.create_index("users", "username_idx", "username", true).unwrap();
+
+    let is_duplicate = |result: jasonisnthappy::Result<_>, value: &str| match result {
+        Err(Error::DuplicateKey { index, value: held }) => index == "username_idx" && held == value,
+        _ => false,
+    };
+    let owner_of = |username: &str| -> Vec<String> {
+        users.query()
+            .filter(&format!("username is \"{}\"", username))
+            .hint("username_idx")
+            .execute()
+            .unwrap()
+            .iter()
+            .map(|doc| doc["_id"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    // Insert of a taken value is rejected and leaves nothing behind
+    assert!(is_duplicate(users.insert(json!({ "username": "alice" })).map(|_| ()), "\"alice\""));
+    assert_eq!(users.count().unwrap(), 1);
+
+    // Update that would create a duplicate is rejected too
+    let bob = users.insert(json!({ "username": "bob" })).unwrap();
+    assert!(is_duplicate(users.update_by_id(&bob, json!({ "username": "alice" })), "\"alice\""));
+    assert_eq!(users.find_by_id(&bob).unwrap()["username"], "bob");
+    assert_eq!(owner_of("alice"), vec![alice.clone()]);
+
+    // Updating a document without touching its indexed value is fine
+    users.update_by_id(&bob, json!({ "age": 30 })).unwrap();
+
+    // Once a value is freed by an update, another document can take it
+    users.update_by_id(&alice, json!({ "username": "alicia" })).unwrap();
+    users.update_by_id(&bob, json!({ "username": "alice" })).unwrap();
+    assert_eq!(owner_of("alice"), vec![bob.clone()]);
+    assert_eq!(owner_of("alicia"), vec![alice.clone()]);
+    assert!(owner_of("bob").is_empty());
+
+    // ...or by a delete
+    users.delete_by_id(&alice).unwrap();
+    let alicia = users.insert(json!({ "username": "alicia" })).unwrap();
+    assert_eq!(owner_of("alicia"), vec![alicia]);
+
+    // Transactions enforce the index and keep it up to date on commit
+    let mut tx = db.begin().unwrap();
+    let mut tx_users = tx.collection("users").unwrap();
+    assert!(is_duplicate(tx_users.insert(json!({ "username": "alicia" })).map(|_| ()), "\"alicia\""));
+    let carol = tx_users.insert(json!({ "username": "carol" })).unwrap();
+    assert!(is_duplicate(tx_users.insert(json!({ "username": "carol" })).map(|_| ()), "\"carol\""));
+    tx_users.update_by_id(&bob, json!({ "username": "robert" })).unwrap();
+    tx.commit().unwrap();
+
+    assert_eq!(owner_of("carol"), vec![carol]);
+    assert_eq!(owner_of("robert"), vec![bob]);
+    assert!(owner_of("alice").is_empty());
+    assert_eq!(users.count().unwrap(), 3);
+
+    cleanup_test_db("unique_single_writes");
+}
+
+#[test]
+fn test_unique_index_checked_again_at_commit() {
+    let db = setup_test_db("unique_commit_check");
+    db.create_index("users", "username_idx", "username", true).unwrap();
+
+    // Both transactions pass the check when they write, but only the first
+    // to commit may keep the value
+    let mut first = db.begin().unwrap();
+    let mut second = db.begin().unwrap();
+    first.collection("users").unwrap().insert(json!({ "username": "dana" })).unwrap();
+    second.collection("users").unwrap().insert(json!({ "username": "dana" })).unwrap();
+
+    first.commit().unwrap();
+    assert!(matches!(second.commit(), Err(Error::DuplicateKey { .. })));
+
+    assert_eq!(db.collection("users").count().unwrap(), 1);
+
+    cleanup_test_db("unique_commit_check");
+}
+
+#[test]
+fn test_sparse_unique_index_ignores_missing_and_null_fields() {
+    let db = setup_test_db("sparse_unique");
+    let users = db.collection("users");
+
+    users.insert(json!({ "name": "a", "email": "a@example.com" })).unwrap();
+    users.insert(json!({ "name": "b" })).unwrap();
+    users.insert(json!({ "name": "c", "email": null })).unwrap();
+
+    // A regular unique index stores absent and null alike, so the build fails
+    let options = IndexOptions { unique: true, ..Default::default() };
+    assert!(db.create_index_with_options("users", "email_idx", &["email"], options).is_err());
+
+    let options = IndexOptions { unique: true, sparse: true, ..Default::default() };
+    db.create_index_with_options("users", "email_idx", &["email"], options).unwrap();
+
+    db.rebuild_index("users", "email_idx").unwrap();
+    let info = db.list_indexes("users").unwrap();
+    assert!(info[0].sparse && info[0].unique);
+
+    // Documents without an email never conflict
+    let d = users.insert(json!({ "name": "d" })).unwrap();
+    users.insert(json!({ "name": "e", "email": null })).unwrap();
+
+    // ...but present values are still unique, including once a document gains one
+    assert!(matches!(users.insert(json!({ "email": "a@example.com" })), Err(Error::DuplicateKey { .. })));
+    users.update_by_id(&d, json!({ "email": "d@example.com" })).unwrap();
+    assert!(matches!(users.insert(json!({ "email": "d@example.com" })), Err(Error::DuplicateKey { .. })));
+
+    // Dropping the value frees it again
+    users.update_by_id(&d, json!({ "email": null })).unwrap();
+    users.insert(json!({ "name": "f", "email": "d@example.com" })).unwrap();
+
+    let mut tx = db.begin().unwrap();
+    let mut tx_users = tx.collection("users").unwrap();
+    tx_users.insert(json!({ "name": "g" })).unwrap();
+    tx_users.insert(json!({ "name": "h" })).unwrap();
+    tx.commit().unwrap();
+
+    // Queries never scan the sparse index, so documents it skips are still found
+    let missing = users.query().filter("email is null").execute().unwrap();
+    assert_eq!(missing.len(), 6);
+    let sorted = users.query().sort_by("email", SortOrder::Asc).project(&["email"]).explain().unwrap();
+    assert_eq!(sorted.access_path, AccessPath::CollectionScan);
+    assert!(users.query().filter("email is 'a@example.com'").hint("email_idx").execute().is_err());
+
+    cleanup_test_db("sparse_unique");
+}
+
+#[test]
+fn test_sparse_compound_index_needs_every_field() {
+    let db = setup_test_db("sparse_compound");
+    let users = db.collection("users");
+
+    let options = IndexOptions { unique: true, sparse: true, ..Default::default() };
+    db.create_index_with_options("users", "tenant_email_idx", &["tenant", "email"], options).unwrap();
+
+    users.insert(json!({ "tenant": "t1", "email": "a@example.com" })).unwrap();
+    users.insert(json!({ "tenant": "t2", "email": "a@example.com" })).unwrap();
+    // Missing either field leaves the document out of the index
+    users.insert(json!({ "tenant": "t1" })).unwrap();
+    users.insert(json!({ "tenant": "t1" })).unwrap();
+    users.insert(json!({ "email": "a@example.com" })).unwrap();
+    users.insert(json!({ "email": "a@example.com" })).unwrap();
+
+    assert!(matches!(
+        users.insert(json!({ "tenant": "t1", "email": "a@example.com" })),
+        Err(Error::DuplicateKey { .. })
+    ));
+    assert_eq!(users.count().unwrap(), 6);
+
+    cleanup_test_db("sparse_compound");
+}
@@ -0,0 +1,111 @@
+use jasonisnthappy::{AccessPath, Database, IndexOptions, SortOrder};
+use serde_json::json;
+use std::fs;
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+fn timestamps(docs: &[serde_json::Value]) -> Vec<i64> {
+    docs.iter().map(|doc| doc["timestamp"].as_i64().unwrap()).collect()
+}
+
+#[test]
+fn test_sort_matching_an_index_reads_in_index_order() {
+    let path = "/tmp/test_sort_matching_index.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    let events = db.collection("events");
+    for ts in [5, 3, 9, 1, 7] {
+        events.insert(json!({"timestamp": ts, "kind": "click"})).unwrap();
+    }
+    db.create_index("events", "by_timestamp", "timestamp", false).unwrap();
+
+    let query = || events.query().filter("kind is 'click'").sort_by("timestamp", SortOrder::Asc);
+    let explain = query().explain().unwrap();
+    assert_eq!(explain.access_path, AccessPath::IndexScan);
+    assert_eq!(explain.index.as_deref(), Some("by_timestamp"));
+    assert!(!explain.in_memory_sort);
+    assert_eq!(timestamps(&query().execute().unwrap()), vec![1, 3, 5, 7, 9]);
+
+    // The index runs the other way, so this one is still sorted in memory
+    let explain = events.query().sort_by("timestamp", SortOrder::Desc).explain().unwrap();
+    assert_eq!(explain.access_path, AccessPath::CollectionScan);
+    assert!(explain.in_memory_sort);
+
+    drop(db);
+    cleanup(path);
+}
+
+#[test]
+fn test_default_sort() {
+    let path = "/tmp/test_default_sort.db";
+    cleanup(path);
+
+    {
+        let db = Database::open(path).unwrap();
+        let events = db.collection("events");
+        for ts in [5, 3, 9, 1, 7] {
+            events.insert(json!({"timestamp": ts, "kind": if ts > 2 { "click" } else { "view" }})).unwrap();
+        }
+
+        // Without an index the default sort is applied in memory
+        db.set_default_sort("events", &[("timestamp", SortOrder::Desc)]).unwrap();
+        assert_eq!(events.default_sort(), vec![("timestamp".to_string(), SortOrder::Desc)]);
+        assert_eq!(timestamps(&events.find("kind is 'click'").unwrap()), vec![9, 7, 5, 3]);
+        let explain = events.query().explain().unwrap();
+        assert_eq!(explain.access_path, AccessPath::CollectionScan);
+        assert!(explain.in_memory_sort);
+
+        // With one in that order, queries read it instead
+        let options = IndexOptions { descending: true, ..Default::default() };
+        db.create_index_with_options("events", "by_timestamp", &["timestamp"], options).unwrap();
+        let explain = events.query().filter("kind is 'click'").explain().unwrap();
+        assert_eq!(explain.access_path, AccessPath::IndexScan);
+        assert!(!explain.in_memory_sort);
+        assert_eq!(timestamps(&events.query().execute().unwrap()), vec![9, 7, 5, 3, 1]);
+
+        // An explicit sort replaces the default one
+        let ascending = events.query().sort_by("timestamp", SortOrder::Asc).execute().unwrap();
+        assert_eq!(timestamps(&ascending), vec![1, 3, 5, 7, 9]);
+    }
+
+    let db = Database::open(path).unwrap();
+    let events = db.collection("events");
+    assert_eq!(db.get_default_sort("events"), vec![("timestamp".to_string(), SortOrder::Desc)]);
+    assert_eq!(timestamps(&events.find("timestamp > 2").unwrap()), vec![9, 7, 5, 3]);
+
+    db.set_default_sort("events", &[]).unwrap();
+    assert!(events.default_sort().is_empty());
+    assert_eq!(events.find("timestamp > 0").unwrap().len(), 5);
+
+    drop(db);
+    cleanup(path);
+}
+
+#[test]
+fn test_default_sort_survives_reopen_without_later_writes() {
+    let path = "/tmp/test_default_sort_reopen.db";
+    cleanup(path);
+
+    {
+        let db = Database::open(path).unwrap();
+        let events = db.collection("events");
+        for ts in [5, 3, 9] {
+            events.insert(json!({"timestamp": ts})).unwrap();
+        }
+        // Nothing is committed after the setting, so it has to persist itself
+        db.set_default_sort("events", &[("timestamp", SortOrder::Desc)]).unwrap();
+        db.close().unwrap();
+    }
+
+    let db = Database::open(path).unwrap();
+    assert_eq!(db.get_default_sort("events"), vec![("timestamp".to_string(), SortOrder::Desc)]);
+    assert_eq!(timestamps(&db.collection("events").find("timestamp > 0").unwrap()), vec![9, 5, 3]);
+
+    db.close().unwrap();
+    cleanup(path);
+}
@@ -0,0 +1,147 @@
+use jasonisnthappy::core::database::{Database, DatabaseOptions};
+use jasonisnthappy::Error;
+use serde_json::json;
+use std::fs;
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+fn open_with_metadata(path: &str) -> Database {
+    let opts = DatabaseOptions {
+        document_metadata: true,
+        ..Default::default()
+    };
+    Database::open_with_options(path, opts).unwrap()
+}
+
+#[test]
+fn test_insert_stamps_metadata() {
+    let path = "/tmp/test_doc_metadata_insert.db";
+    cleanup(path);
+
+    let db = open_with_metadata(path);
+    let users = db.collection("users");
+
+    let id = users.insert(json!({"name": "Alice"})).unwrap();
+    let doc = users.find_by_id(&id).unwrap();
+
+    assert_eq!(doc["_rev"], 1);
+    assert!(doc["_created_at"].as_u64().unwrap() > 0);
+    assert_eq!(doc["_created_at"], doc["_updated_at"]);
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_update_bumps_rev() {
+    let path = "/tmp/test_doc_metadata_update.db";
+    cleanup(path);
+
+    let db = open_with_metadata(path);
+    let users = db.collection("users");
+
+    let id = users.insert(json!({"name": "Alice", "age": 30})).unwrap();
+    let created_at = users.find_by_id(&id).unwrap()["_created_at"].clone();
+
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    users.update_by_id(&id, json!({"age": 31})).unwrap();
+
+    let doc = users.find_by_id(&id).unwrap();
+    assert_eq!(doc["_rev"], 2);
+    assert_eq!(doc["_created_at"], created_at);
+    assert!(doc["_updated_at"].as_u64().unwrap() > created_at.as_u64().unwrap());
+
+    // Updates can't rewind the revision or rewrite the creation time
+    users.update(r#"name is "Alice""#, json!({"_rev": 0, "_created_at": 1})).unwrap();
+    let doc = users.find_by_id(&id).unwrap();
+    assert_eq!(doc["_rev"], 3);
+    assert_eq!(doc["_created_at"], created_at);
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_transaction_writes_stamp_metadata() {
+    let path = "/tmp/test_doc_metadata_tx.db";
+    cleanup(path);
+
+    let db = open_with_metadata(path);
+
+    let mut tx = db.begin().unwrap();
+    let id = {
+        let mut users = tx.collection("users").unwrap();
+        let id = users.insert(json!({"name": "Alice"})).unwrap();
+        users.update_by_id(&id, json!({"name": "Alicia"})).unwrap();
+        id
+    };
+    tx.commit().unwrap();
+
+    let doc = db.collection("users").find_by_id(&id).unwrap();
+    assert_eq!(doc["_rev"], 2);
+    assert_eq!(doc["name"], "Alicia");
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_update_by_id_if_rev() {
+    let path = "/tmp/test_doc_metadata_if_rev.db";
+    cleanup(path);
+
+    let db = open_with_metadata(path);
+    let users = db.collection("users");
+
+    let id = users.insert(json!({"name": "Alice", "age": 30})).unwrap();
+
+    users.update_by_id_if_rev(&id, 1, json!({"age": 31})).unwrap();
+
+    // A writer holding the old revision loses
+    let err = users.update_by_id_if_rev(&id, 1, json!({"age": 99})).unwrap_err();
+    match err {
+        Error::RevConflict { expected, actual, .. } => {
+            assert_eq!(expected, 1);
+            assert_eq!(actual, 2);
+        }
+        other => panic!("expected RevConflict, got {:?}", other),
+    }
+
+    let doc = users.find_by_id(&id).unwrap();
+    assert_eq!(doc["age"], 31);
+    assert_eq!(doc["_rev"], 2);
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_no_metadata_by_default() {
+    let path = "/tmp/test_doc_metadata_default.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    let users = db.collection("users");
+
+    let id = users.insert(json!({"name": "Alice"})).unwrap();
+    users.update_by_id(&id, json!({"age": 31})).unwrap();
+
+    let doc = users.find_by_id(&id).unwrap();
+    assert!(doc.get("_rev").is_none());
+    assert!(doc.get("_created_at").is_none());
+    assert!(doc.get("_updated_at").is_none());
+
+    // Documents without a _rev count as revision 0
+    users.update_by_id_if_rev(&id, 0, json!({"age": 32})).unwrap();
+    assert!(matches!(
+        users.update_by_id_if_rev(&id, 1, json!({"age": 33})),
+        Err(Error::RevConflict { .. })
+    ));
+
+    db.close().unwrap();
+    cleanup(path);
+}
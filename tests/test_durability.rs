@@ -0,0 +1,163 @@
+use jasonisnthappy::core::database::{Database, DatabaseOptions};
+use jasonisnthappy::Durability;
+use serde_json::json;
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_every_durability_level_keeps_commits_across_reopen() {
+    let levels = [
+        ("full", Durability::Full),
+        ("normal", Durability::Normal),
+        ("batched", Durability::Batched(Duration::from_millis(20))),
+    ];
+
+    for (name, durability) in levels {
+        let path = format!("/tmp/test_durability_{}.db", name);
+        cleanup(&path);
+
+        {
+            let opts = DatabaseOptions { durability, ..Default::default() };
+            let db = Arc::new(Database::open_with_options(&path, opts).unwrap());
+            assert_eq!(db.durability(), durability);
+
+            let handles: Vec<_> = (0..4)
+                .map(|t| {
+                    let db = db.clone();
+                    thread::spawn(move || {
+                        let items = db.collection("items");
+                        for n in 0..50 {
+                            items.insert(json!({"thread": t, "n": n})).unwrap();
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                handle.join().unwrap();
+            }
+
+            // Give the batched background fsync a chance to run
+            thread::sleep(Duration::from_millis(50));
+            assert_eq!(db.collection("items").count().unwrap(), 200);
+        }
+
+        let db = Database::open(&path).unwrap();
+        assert_eq!(db.collection("items").count().unwrap(), 200, "{} lost commits", name);
+        assert_eq!(db.durability(), Durability::Full);
+        assert!(db.check_integrity().unwrap().is_ok());
+        drop(db);
+
+        cleanup(&path);
+    }
+}
+
+#[test]
+fn test_sync_reports_and_clears_unsynced_commits() {
+    let path = "/tmp/test_durability_sync.db";
+    cleanup(path);
+
+    let opts = DatabaseOptions { durability: Durability::Normal, ..Default::default() };
+    let db = Database::open_with_options(path, opts).unwrap();
+    assert!(db.is_fully_synced());
+
+    db.collection("items").insert(json!({"n": 1})).unwrap();
+    assert!(!db.is_fully_synced());
+
+    let wal_frames = db.frame_count();
+    db.sync().unwrap();
+    assert!(db.is_fully_synced());
+    // Unlike a checkpoint, syncing leaves the WAL in place
+    assert_eq!(db.frame_count(), wal_frames);
+
+    db.collection("items").insert(json!({"n": 2})).unwrap();
+    assert!(!db.is_fully_synced());
+    db.close().unwrap();
+    assert!(db.is_fully_synced());
+    drop(db);
+
+    let db = Database::open(path).unwrap();
+    db.collection("items").insert(json!({"n": 3})).unwrap();
+    assert!(db.is_fully_synced(), "full durability fsyncs every commit");
+    assert_eq!(db.collection("items").count().unwrap(), 3);
+    drop(db);
+
+    cleanup(path);
+}
+
+#[test]
+fn test_durability_parse() {
+    let interval = Duration::from_millis(100);
+    assert_eq!(Durability::parse("full", interval), Some(Durability::Full));
+    assert_eq!(Durability::parse("normal", interval), Some(Durability::Normal));
+    assert_eq!(Durability::parse("batched", interval), Some(Durability::Batched(interval)));
+    assert_eq!(Durability::parse("sometimes", interval), None);
+    assert_eq!(Durability::Batched(interval).as_str(), "batched");
+}
+
+#[test]
+fn test_killed_before_sync_leaves_an_intact_database() {
+    // The child commits without syncing the database file, reports it and
+    // waits to be killed
+    if let Ok(db_path) = env::var("DURABILITY_CRASH_DB") {
+        let durability = Durability::parse(&env::var("DURABILITY_CRASH_LEVEL").unwrap(), Duration::from_secs(3600)).unwrap();
+        let opts = DatabaseOptions { durability, ..Default::default() };
+        let db = Database::open_with_options(&db_path, opts).unwrap();
+        db.create_index("items", "n_idx", "n", false).unwrap();
+        for batch in 0..5 {
+            let mut tx = db.begin().unwrap();
+            let mut items = tx.collection("items").unwrap();
+            for n in 0..40 {
+                items.insert(json!({"_id": format!("{}-{}", batch, n), "n": batch * 40 + n})).unwrap();
+            }
+            tx.commit().unwrap();
+        }
+        db.collection("items").update("n < 20", json!({"touched": true})).unwrap();
+        assert!(!db.is_fully_synced());
+        println!("COMMITTED");
+        thread::sleep(Duration::from_secs(60));
+        return;
+    }
+
+    for level in ["normal", "batched"] {
+        let path = format!("/tmp/test_durability_crash_{}.db", level);
+        cleanup(&path);
+
+        let mut child = Command::new(env::current_exe().unwrap())
+            .env("DURABILITY_CRASH_DB", &path)
+            .env("DURABILITY_CRASH_LEVEL", level)
+            .arg("test_killed_before_sync_leaves_an_intact_database")
+            .arg("--exact")
+            .arg("--nocapture")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let committed = stdout.lines().map_while(|line| line.ok()).any(|line| line.ends_with("COMMITTED"));
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert!(committed, "{} child exited before committing", level);
+
+        let db = Database::open(&path).unwrap();
+        let report = db.check_integrity().unwrap();
+        assert!(report.is_ok(), "{}: {:?}", level, report);
+        let items = db.collection("items");
+        assert_eq!(items.count().unwrap(), 200, "{} lost commits", level);
+        assert_eq!(items.find("n >= 190").unwrap().len(), 10);
+        assert_eq!(items.find("touched is true").unwrap().len(), 20);
+        drop(db);
+
+        cleanup(&path);
+    }
+}
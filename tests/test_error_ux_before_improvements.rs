@@ -255,3 +255,36 @@ fn test_what_good_errors_would_look_like() {
 
     println!("========================================\n");
 }
+
+#[test]
+fn test_autocommit_not_found_errors_are_typed() {
+    let dir = TempDir::new().unwrap();
+    let db_path = dir.path().join("test.db");
+    let db = Database::open(db_path.to_str().unwrap()).unwrap();
+
+    let users = db.collection("users");
+    users.insert(json!({"_id": "alice"})).unwrap();
+
+    match users.update_by_id("bob", json!({"age": 1})).unwrap_err() {
+        Error::DocumentNotFound { id, .. } => assert_eq!(id, "bob"),
+        other => panic!("Expected Error::DocumentNotFound, got {:?}", other),
+    }
+
+    users.delete_by_id("alice").unwrap();
+    assert!(matches!(
+        users.find_by_id("alice"),
+        Err(Error::NotFound | Error::DocumentNotFound { .. })
+    ));
+
+    // Missing collections read as empty and can still be upserted into
+    let missing = db.collection("missing");
+    assert!(matches!(
+        db.list_indexes("missing"),
+        Err(Error::CollectionDoesNotExist { .. })
+    ));
+    assert_eq!(missing.find_by_id_typed::<serde_json::Value>("x").unwrap(), None);
+    assert!(missing.distinct("name").unwrap().is_empty());
+    assert_eq!(missing.query().count().unwrap(), 0);
+    missing.upsert("name is \"x\"", json!({"name": "x"})).unwrap();
+    assert_eq!(missing.count().unwrap(), 1);
+}
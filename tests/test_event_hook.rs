@@ -0,0 +1,91 @@
+use jasonisnthappy::core::database::{Database, DatabaseOptions};
+use jasonisnthappy::{DbEvent, Error};
+use serde_json::json;
+use std::fs;
+use std::sync::{Arc, Mutex};
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+fn record(db: &Database) -> Arc<Mutex<Vec<DbEvent>>> {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let sink = seen.clone();
+    db.set_event_hook(Box::new(move |event| sink.lock().unwrap().push(event)));
+    seen
+}
+
+#[test]
+fn test_event_hook_sees_transactions_and_checkpoints() {
+    let path = "/tmp/test_event_hook.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    let users = db.collection("users");
+    let id = users.insert(json!({"name": "alice"})).unwrap();
+    let seen = record(&db);
+
+    let mut tx = db.begin().unwrap();
+    let tx_id = tx.tx_id;
+    tx.collection("users").unwrap().insert(json!({"name": "bob"})).unwrap();
+    tx.commit().unwrap();
+
+    let mut tx = db.begin().unwrap();
+    let rolled_back = tx.tx_id;
+    tx.rollback().unwrap();
+
+    db.checkpoint().unwrap();
+
+    let events = seen.lock().unwrap().clone();
+    assert_eq!(events[0], DbEvent::TransactionBegin { tx_id, read_only: false });
+    assert!(events.contains(&DbEvent::TransactionCommit { tx_id }));
+    assert!(events.contains(&DbEvent::TransactionRollback { tx_id: rolled_back }));
+    assert!(events.iter().any(|e| matches!(e, DbEvent::CheckpointStart { frames } if *frames > 0)));
+    assert!(events.iter().any(|e| matches!(e, DbEvent::CheckpointEnd { pages_written } if *pages_written > 0)));
+    assert!(events.iter().any(|e| matches!(e, DbEvent::WalRotated { .. })));
+
+    // A write that loses to one committed after its snapshot is reported
+    let mut first = db.begin().unwrap();
+    let mut second = db.begin().unwrap();
+    first.collection("users").unwrap().update_by_id(&id, json!({"$set": {"age": 1}})).unwrap();
+    first.commit().unwrap();
+    let loser = second.tx_id;
+    second.collection("users").unwrap().update_by_id(&id, json!({"$set": {"age": 2}})).unwrap();
+    assert!(matches!(second.commit(), Err(Error::TxConflict)));
+    assert!(seen.lock().unwrap().iter().any(|e| matches!(
+        e,
+        DbEvent::TransactionConflict { tx_id, collection, .. } if *tx_id == loser && collection == "users"
+    )));
+    assert!(!seen.lock().unwrap().contains(&DbEvent::TransactionCommit { tx_id: loser }));
+
+    db.clear_event_hook();
+    let before = seen.lock().unwrap().len();
+    users.insert(json!({"name": "carol"})).unwrap();
+    assert_eq!(seen.lock().unwrap().len(), before);
+
+    drop(db);
+    cleanup(path);
+}
+
+#[test]
+fn test_event_hook_sees_page_evictions() {
+    let path = "/tmp/test_event_hook_eviction.db";
+    cleanup(path);
+
+    let opts = DatabaseOptions { cache_size: 16, ..Default::default() };
+    let db = Database::open_with_options(path, opts).unwrap();
+    let seen = record(&db);
+
+    let items = db.collection("items");
+    let docs = (0..2000).map(|n| json!({"n": n, "pad": "x".repeat(100)})).collect();
+    items.insert_many(docs).unwrap();
+    db.checkpoint().unwrap();
+    assert_eq!(items.count().unwrap(), 2000);
+
+    assert!(seen.lock().unwrap().iter().any(|e| matches!(e, DbEvent::PageEvicted { .. })));
+
+    drop(db);
+    cleanup(path);
+}
@@ -0,0 +1,144 @@
+use jasonisnthappy::core::database::Database;
+use jasonisnthappy::{AccessPath, Error, IndexOptions};
+use serde_json::{json, Value};
+use std::fs;
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+fn ids(docs: &[Value]) -> Vec<String> {
+    docs.iter().map(|doc| doc["_id"].as_str().unwrap().to_string()).collect()
+}
+
+#[test]
+fn test_expression_queries_use_index() {
+    let path = "/tmp/test_expression_index_queries.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    let users = db.collection("users");
+    users.insert_many(vec![
+        json!({"_id": "ann", "email": "Ann@Example.com", "tags": ["a", "b"], "first": "Ann", "last": "Lee"}),
+        json!({"_id": "bob", "email": "bob@example.com", "tags": [], "first": "Bob", "last": "Ray"}),
+        json!({"_id": "cat", "email": "CAT@example.com", "tags": ["c"], "first": "Cat", "last": "Lee"}),
+        json!({"_id": "dan", "tags": ["d", "e"]}),
+    ]).unwrap();
+
+    let queries = [
+        "lower(email) is 'ann@example.com'",
+        "lower(email) is 'cat@example.com' and tags has 'c'",
+        "length(tags) is 2",
+        "lower(concat(first, ' ', last)) is 'bob ray'",
+        "lower(email) is null",
+    ];
+    let before: Vec<Vec<Value>> = queries.iter().map(|q| users.find(q).unwrap()).collect();
+    assert_eq!(ids(&before[0]), ["ann"]);
+    assert_eq!(ids(&before[2]), ["ann", "dan"]);
+    assert_eq!(ids(&before[4]), ["dan"]);
+    assert_eq!(users.explain(queries[0]).unwrap().access_path, AccessPath::CollectionScan);
+
+    db.create_expression_index("users", "email_ci", "lower( email )", IndexOptions::default()).unwrap();
+    db.create_expression_index("users", "tag_count", "length(tags)", IndexOptions::default()).unwrap();
+    db.create_expression_index("users", "full_name", "lower(concat(first,' ',last))", IndexOptions::default()).unwrap();
+
+    let indexes = db.list_indexes("users").unwrap();
+    let index = indexes.iter().find(|idx| idx.name == "email_ci").unwrap();
+    assert!(index.expression && !index.geo);
+    assert_eq!(index.fields, ["lower(email)"]);
+
+    for (query, expected) in queries.iter().zip(&before) {
+        assert_eq!(&users.find(query).unwrap(), expected, "query: {}", query);
+
+        let explain = users.explain(query).unwrap();
+        assert_eq!(explain.access_path, AccessPath::ExpressionIndexScan, "query: {}", query);
+        assert_eq!(explain.docs_returned, expected.len());
+        assert!(explain.docs_examined <= 2, "query: {}", query);
+    }
+    assert_eq!(users.explain("lower(email) is 'ann@example.com'").unwrap().index.as_deref(), Some("email_ci"));
+
+    // Other comparisons still work, by scanning
+    assert_eq!(ids(&users.find("length(tags) > 1").unwrap()), ["ann", "dan"]);
+    assert_eq!(ids(&users.find("upper(last) is 'LEE'").unwrap()), ["ann", "cat"]);
+    assert_eq!(users.explain("length(tags) > 1").unwrap().access_path, AccessPath::CollectionScan);
+
+    // Writes keep the index current
+    users.update_by_id("bob", json!({"$set": {"email": "Robert@Example.com"}})).unwrap();
+    users.delete_by_id("ann").unwrap();
+    assert!(users.find("lower(email) is 'bob@example.com'").unwrap().is_empty());
+    assert_eq!(ids(&users.find("lower(email) is 'robert@example.com'").unwrap()), ["bob"]);
+    assert!(users.find("lower(email) is 'ann@example.com'").unwrap().is_empty());
+    assert!(db.check_integrity().unwrap().is_ok());
+
+    drop(users);
+    drop(db);
+    let db = Database::open(path).unwrap();
+    let users = db.collection("users");
+    assert_eq!(ids(&users.find("lower(email) is 'robert@example.com'").unwrap()), ["bob"]);
+    assert_eq!(users.explain("length(tags) is 0").unwrap().access_path, AccessPath::ExpressionIndexScan);
+
+    drop(db);
+    cleanup(path);
+}
+
+#[test]
+fn test_unique_expression_index_rejects_case_variants() {
+    let path = "/tmp/test_expression_index_unique.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    let users = db.collection("users");
+    users.insert(json!({"email": "Ann@Example.com"})).unwrap();
+    users.insert(json!({"email": "ANN@example.com"})).unwrap();
+
+    // Existing duplicates stop the index from being built
+    let unique = IndexOptions { unique: true, sparse: true, ..Default::default() };
+    assert!(db.create_expression_index("users", "email_ci", "lower(email)", unique).is_err());
+    users.delete("email is 'ANN@example.com'").unwrap();
+    db.create_expression_index("users", "email_ci", "lower(email)", unique).unwrap();
+
+    assert!(matches!(
+        users.insert(json!({"email": "ann@EXAMPLE.com"})),
+        Err(Error::DuplicateKey { .. })
+    ));
+    users.insert(json!({"email": "bob@example.com"})).unwrap();
+    // Sparse, so documents without an email don't collide on null
+    users.insert(json!({"name": "no email"})).unwrap();
+    users.insert(json!({"name": "no email either"})).unwrap();
+
+    let mut tx = db.begin().unwrap();
+    let mut coll = tx.collection("users").unwrap();
+    coll.insert(json!({"email": "Cat@example.com"})).unwrap();
+    assert!(coll.insert(json!({"email": "cat@example.com"})).is_err());
+    drop(coll);
+    tx.rollback().unwrap();
+
+    assert_eq!(users.find("lower(email) is 'ann@example.com'").unwrap().len(), 1);
+    assert_eq!(users.count().unwrap(), 4);
+
+    drop(db);
+    cleanup(path);
+}
+
+#[test]
+fn test_invalid_expressions_are_rejected() {
+    let path = "/tmp/test_expression_index_invalid.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    db.collection("users").insert(json!({"email": "a@b.c"})).unwrap();
+
+    for bad in ["email", "md5(email)", "lower(email", "substr(email, -1)", "lower(a, b)"] {
+        assert!(
+            db.create_expression_index("users", "bad", bad, IndexOptions::default()).is_err(),
+            "{} should be rejected", bad
+        );
+    }
+    assert!(db.list_indexes("users").unwrap().is_empty());
+    assert!(db.collection("users").find("lower(email) is").is_err());
+
+    drop(db);
+    cleanup(path);
+}
@@ -0,0 +1,176 @@
+use jasonisnthappy::{Database, Error, Schema, SortOrder, ValueType};
+use serde_json::json;
+use std::collections::HashSet;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_find_one_and_update_returns_old_or_new() {
+    let path = "/tmp/test_find_and_modify_update.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    let jobs = db.collection("jobs");
+    jobs.insert_many(vec![
+        json!({"_id": "a", "status": "queued", "priority": 1}),
+        json!({"_id": "b", "status": "queued", "priority": 5}),
+        json!({"_id": "c", "status": "done", "priority": 9}),
+    ]).unwrap();
+
+    let old = jobs.query()
+        .filter("status is \"queued\"")
+        .sort_by("priority", SortOrder::Desc)
+        .find_one_and_update(json!({"status": "running"}), false)
+        .unwrap()
+        .unwrap();
+    assert_eq!(old["_id"], "b");
+    assert_eq!(old["status"], "queued");
+    assert_eq!(jobs.find_by_id("b").unwrap()["status"], "running");
+
+    let new = jobs.query()
+        .filter("status is \"queued\"")
+        .sort_by("priority", SortOrder::Desc)
+        .find_one_and_update(json!({"status": "running"}), true)
+        .unwrap()
+        .unwrap();
+    assert_eq!(new["_id"], "a");
+    assert_eq!(new["status"], "running");
+    assert_eq!(new["priority"], 1);
+
+    assert_eq!(jobs.find_one_and_update("status is \"queued\"", json!({"status": "running"}), true).unwrap(), None);
+    assert_eq!(jobs.count_with_query(Some("status is \"running\"")).unwrap(), 2);
+
+    let err = jobs.find_one_and_update("status is \"done\"", json!("nope"), true).unwrap_err();
+    assert!(err.to_string().contains("updates must be an object"));
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_find_one_and_delete_returns_removed_document() {
+    let path = "/tmp/test_find_and_modify_delete.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    let jobs = db.collection("jobs");
+
+    // A collection that doesn't exist yet has nothing to pop
+    assert_eq!(jobs.find_one_and_delete("priority > 0").unwrap(), None);
+    assert!(!jobs.exists());
+
+    jobs.insert_many(vec![
+        json!({"_id": "a", "priority": 3}),
+        json!({"_id": "b", "priority": 1}),
+        json!({"_id": "c", "priority": 2}),
+    ]).unwrap();
+
+    let mut popped = Vec::new();
+    while let Some(doc) = jobs.query()
+        .filter("priority > 0")
+        .sort_by("priority", SortOrder::Asc)
+        .find_one_and_delete()
+        .unwrap()
+    {
+        popped.push(doc["_id"].as_str().unwrap().to_string());
+    }
+
+    assert_eq!(popped, vec!["b", "c", "a"]);
+    assert_eq!(jobs.count().unwrap(), 0);
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_find_one_and_update_validates_schema() {
+    let path = "/tmp/test_find_and_modify_schema.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    let mut n_schema = Schema::new();
+    n_schema.value_type = Some(ValueType::Number);
+    let mut schema = Schema::new();
+    schema.value_type = Some(ValueType::Object);
+    schema.properties = Some([("n".to_string(), n_schema)].into_iter().collect());
+    db.set_schema("items", schema).unwrap();
+
+    let items = db.collection("items");
+    items.insert(json!({"_id": "x", "n": 1})).unwrap();
+
+    assert!(items.find_one_and_update("n is 1", json!({"n": "one"}), true).is_err());
+    assert_eq!(items.find_by_id("x").unwrap()["n"], 1);
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_concurrent_workers_never_pop_the_same_document() {
+    let path = "/tmp/test_find_and_modify_concurrent.db";
+    cleanup(path);
+
+    let db = Arc::new(Database::open(path).unwrap());
+    let mut config = db.get_transaction_config();
+    config.max_retries = 50;
+    db.set_transaction_config(config);
+
+    let docs: Vec<_> = (0..200)
+        .map(|i| json!({"_id": format!("job{:03}", i), "seq": i, "state": if i % 2 == 0 { "delete" } else { "claim" }}))
+        .collect();
+    db.collection("jobs").insert_many(docs).unwrap();
+
+    let popped = Arc::new(Mutex::new(Vec::new()));
+    let mut handles = Vec::new();
+
+    for worker in 0..4 {
+        let db = db.clone();
+        let popped = popped.clone();
+        handles.push(thread::spawn(move || {
+            let jobs = db.collection("jobs");
+            loop {
+                // Half the workers delete their jobs, the other half mark them claimed
+                let result = if worker % 2 == 0 {
+                    jobs.query()
+                        .filter("state is \"delete\"")
+                        .sort_by("seq", SortOrder::Asc)
+                        .find_one_and_delete()
+                } else {
+                    jobs.query()
+                        .filter("state is \"claim\"")
+                        .sort_by("seq", SortOrder::Asc)
+                        .find_one_and_update(json!({"state": "claimed", "worker": worker}), true)
+                };
+
+                match result {
+                    Ok(Some(doc)) => popped.lock().unwrap().push(doc["_id"].as_str().unwrap().to_string()),
+                    Ok(None) => break,
+                    Err(Error::TxConflict) => continue,
+                    Err(e) => panic!("worker {} failed: {}", worker, e),
+                }
+            }
+        }));
+    }
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    let popped = popped.lock().unwrap();
+    let unique: HashSet<_> = popped.iter().collect();
+    assert_eq!(unique.len(), popped.len(), "a document was popped twice");
+    assert_eq!(popped.len(), 200);
+
+    let jobs = db.collection("jobs");
+    assert_eq!(jobs.count().unwrap(), 100);
+    assert_eq!(jobs.count_with_query(Some("state is \"claimed\"")).unwrap(), 100);
+
+    cleanup(path);
+}
@@ -0,0 +1,45 @@
+use jasonisnthappy::core::database::Database;
+use jasonisnthappy::Error;
+use serde_json::json;
+use std::fs;
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_find_batches_streams_matching_documents() {
+    let path = "/tmp/test_find_batches.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    let items = db.collection("items");
+    items.insert_many((0..25).map(|n| json!({"n": n})).collect()).unwrap();
+
+    let batches: Vec<Vec<_>> = items.find_batches("n >= 5", 8).unwrap()
+        .map(|batch| batch.unwrap())
+        .collect();
+    assert_eq!(batches.iter().map(Vec::len).collect::<Vec<_>>(), vec![8, 8, 4]);
+    let mut found: Vec<i64> = batches.concat().iter().map(|doc| doc["n"].as_i64().unwrap()).collect();
+    found.sort();
+    assert_eq!(found, (5..25).collect::<Vec<_>>());
+
+    // Batches keep reading from the snapshot taken when the scan started
+    let mut batches = items.find_batches("n < 10", 5).unwrap();
+    assert_eq!(batches.next().unwrap().unwrap().len(), 5);
+    items.delete("n < 10").unwrap();
+    assert_eq!(batches.next().unwrap().unwrap().len(), 5);
+    assert!(batches.next().is_none());
+    drop(batches);
+
+    assert_eq!(items.find_batches("n < 10", 5).unwrap().count(), 0);
+    assert!(matches!(
+        db.collection("missing").find_batches("n > 1", 5).map(|_| ()),
+        Err(Error::CollectionDoesNotExist { .. })
+    ));
+
+    drop(db);
+    cleanup(path);
+}
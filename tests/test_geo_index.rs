@@ -0,0 +1,182 @@
+use jasonisnthappy::core::database::Database;
+use jasonisnthappy::{haversine_km, AccessPath, SortOrder};
+use serde_json::{json, Value};
+use std::fs;
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+fn ids(docs: &[Value]) -> Vec<String> {
+    docs.iter().map(|doc| doc["_id"].as_str().unwrap().to_string()).collect()
+}
+
+/// A grid of points a tenth of a degree apart around Paris, plus cities far
+/// away and documents without a usable location
+fn seed(db: &Database) {
+    let places = db.collection("places");
+    let mut docs = Vec::new();
+    for i in 0..20 {
+        for j in 0..20 {
+            docs.push(json!({
+                "_id": format!("grid-{:02}-{:02}", i, j),
+                "kind": if (i + j) % 2 == 0 { "cafe" } else { "shop" },
+                "location": {"lat": 47.9 + i as f64 * 0.1, "lng": 1.4 + j as f64 * 0.1},
+            }));
+        }
+    }
+    docs.push(json!({"_id": "london", "kind": "city", "location": {"lat": 51.5074, "lng": -0.1278}}));
+    docs.push(json!({"_id": "fiji", "kind": "city", "location": {"lat": -17.7134, "lng": 179.9}}));
+    docs.push(json!({"_id": "fiji-east", "kind": "city", "location": {"lat": -17.7, "lng": -179.95}}));
+    docs.push(json!({"_id": "nowhere", "kind": "cafe"}));
+    docs.push(json!({"_id": "broken", "kind": "cafe", "location": {"lat": "48.85", "lng": 2.35}}));
+    places.insert_many(docs).unwrap();
+}
+
+#[test]
+fn test_near_matches_collection_scan() {
+    let path = "/tmp/test_geo_near.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    seed(&db);
+    let places = db.collection("places");
+
+    let queries = [
+        "near(location, 48.8566, 2.3522, 15)",
+        "near(location, 48.8566, 2.3522, 40) and kind is 'cafe'",
+        "near(location, 50.0, 1.0, 300)",
+        "near(location, -17.71, 179.99, 20)",
+        "near(location, 48.8566, 2.3522, 0)",
+    ];
+    let before: Vec<Vec<Value>> = queries.iter().map(|q| places.find(q).unwrap()).collect();
+
+    db.create_geo_index("places", "location_geo", "location.lat", "location.lng").unwrap();
+    let indexes = db.list_indexes("places").unwrap();
+    let index = indexes.iter().find(|idx| idx.name == "location_geo").unwrap();
+    assert!(index.geo && index.sparse && !index.unique);
+
+    for (query, expected) in queries.iter().zip(&before) {
+        assert_eq!(&places.find(query).unwrap(), expected, "query: {}", query);
+
+        let explain = places.explain(query).unwrap();
+        assert_eq!(explain.access_path, AccessPath::GeoIndexScan, "query: {}", query);
+        assert_eq!(explain.index.as_deref(), Some("location_geo"));
+        assert_eq!(explain.docs_returned, expected.len());
+    }
+
+    // Exact distances decide, not the cells the candidates came from
+    let within = places.find("near(location, 48.8566, 2.3522, 15)").unwrap();
+    assert!(!within.is_empty());
+    for doc in &within {
+        let d = haversine_km(48.8566, 2.3522, doc["location"]["lat"].as_f64().unwrap(), doc["location"]["lng"].as_f64().unwrap());
+        assert!(d <= 15.0);
+    }
+    let explain = places.explain("near(location, 48.8566, 2.3522, 15)").unwrap();
+    assert!(explain.docs_examined < 405, "examined {}", explain.docs_examined);
+
+    // Both sides of the antimeridian
+    let pacific = ids(&places.find("near(location, -17.71, 179.99, 20)").unwrap());
+    assert_eq!(pacific, vec!["fiji", "fiji-east"]);
+
+    // Without near() the geo index is never used
+    let explain = places.explain("kind is 'city'").unwrap();
+    assert_eq!(explain.access_path, AccessPath::CollectionScan);
+    let explain = places.explain("near(location, 48.8566, 2.3522, 15) or kind is 'city'").unwrap();
+    assert_eq!(explain.access_path, AccessPath::CollectionScan);
+
+    cleanup(path);
+}
+
+#[test]
+fn test_geo_index_follows_writes() {
+    let path = "/tmp/test_geo_writes.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    db.create_geo_index("places", "location_geo", "location.lat", "location.lng").unwrap();
+    let places = db.collection("places");
+
+    places.insert(json!({"_id": "a", "location": {"lat": 48.8566, "lng": 2.3522}})).unwrap();
+    places.insert(json!({"_id": "b", "location": {"lat": 40.7128, "lng": -74.006}})).unwrap();
+    places.insert(json!({"_id": "c"})).unwrap();
+    let query = "near(location, 48.86, 2.35, 5)";
+    assert_eq!(ids(&places.find(query).unwrap()), vec!["a"]);
+
+    // Moving documents in and out of range
+    places.update_by_id("b", json!({"location": {"lat": 48.87, "lng": 2.36}})).unwrap();
+    places.update_by_id("a", json!({"location": {"lat": 10.0, "lng": 10.0}})).unwrap();
+    places.update_by_id("c", json!({"location": {"lat": 48.85, "lng": 2.34}})).unwrap();
+    assert_eq!(ids(&places.find(query).unwrap()), vec!["b", "c"]);
+
+    places.delete_by_id("b").unwrap();
+    assert_eq!(ids(&places.find(query).unwrap()), vec!["c"]);
+
+    // Updates and deletes by query use the index's candidates too
+    assert_eq!(places.update(query, json!({"$set": {"seen": true}})).unwrap(), 1);
+    assert_eq!(places.delete(query).unwrap(), 1);
+    assert!(places.find(query).unwrap().is_empty());
+
+    let report = db.check_integrity().unwrap();
+    assert!(report.is_ok(), "{:?}", report.problems);
+
+    drop(places);
+    db.close().unwrap();
+    let db = Database::open(path).unwrap();
+    let places = db.collection("places");
+    places.insert(json!({"_id": "d", "location": {"lat": 48.861, "lng": 2.351}})).unwrap();
+    assert_eq!(ids(&places.find(query).unwrap()), vec!["d"]);
+    assert!(db.list_indexes("places").unwrap().iter().any(|idx| idx.geo));
+
+    cleanup(path);
+}
+
+#[test]
+fn test_sort_by_distance() {
+    let path = "/tmp/test_geo_sort.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    seed(&db);
+    db.create_geo_index("places", "location_geo", "location.lat", "location.lng").unwrap();
+    let places = db.collection("places");
+
+    let nearest = places.query()
+        .filter("near(location, 48.8566, 2.3522, 25)")
+        .sort_by_distance("location", 48.8566, 2.3522)
+        .limit(5)
+        .execute()
+        .unwrap();
+    assert_eq!(nearest.len(), 5);
+    assert_eq!(nearest[0]["_id"], "grid-10-10");
+    let distances: Vec<f64> = nearest.iter()
+        .map(|doc| haversine_km(48.8566, 2.3522, doc["location"]["lat"].as_f64().unwrap(), doc["location"]["lng"].as_f64().unwrap()))
+        .collect();
+    assert!(distances.windows(2).all(|pair| pair[0] <= pair[1]), "{:?}", distances);
+
+    // Sort fields break ties; documents without a point come last
+    let cafes = places.query()
+        .filter("kind is 'cafe'")
+        .sort_by_distance("location", 51.5, -0.12)
+        .sort_by("_id", SortOrder::Desc)
+        .execute()
+        .unwrap();
+    let cafe_ids = ids(&cafes);
+    assert_eq!(&cafe_ids[cafe_ids.len() - 2..], ["nowhere", "broken"]);
+
+    let explain = places.query()
+        .filter("near(location, 48.8566, 2.3522, 25)")
+        .sort_by_distance("location", 48.8566, 2.3522)
+        .explain()
+        .unwrap();
+    assert_eq!(explain.access_path, AccessPath::GeoIndexScan);
+    assert!(explain.in_memory_sort);
+
+    // Geo indexes only answer near()
+    let err = places.query().filter("kind is 'cafe'").hint("location_geo").execute().unwrap_err();
+    assert!(err.to_string().contains("geo index"), "{}", err);
+
+    cleanup(path);
+}
@@ -0,0 +1,158 @@
+use jasonisnthappy::core::database::Database;
+use jasonisnthappy::IdStrategy;
+use serde_json::json;
+use std::collections::HashSet;
+use std::fs;
+use std::sync::Arc;
+use std::thread;
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_uuid_object_id_and_ulid_strategies() {
+    let path = "/tmp/test_id_strategies_formats.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    db.set_id_strategy("uuids", IdStrategy::Uuid).unwrap();
+    db.set_id_strategy("object_ids", IdStrategy::ObjectId).unwrap();
+    db.set_id_strategy("ulids", IdStrategy::Ulid).unwrap();
+
+    let uuid = db.collection("uuids").insert(json!({"n": 1})).unwrap();
+    assert_eq!(uuid.len(), 36);
+    assert_eq!(uuid.matches('-').count(), 4);
+
+    let object_id = db.collection("object_ids").insert(json!({"n": 1})).unwrap();
+    assert_eq!(object_id.len(), 24);
+    assert!(object_id.chars().all(|c| c.is_ascii_hexdigit()));
+
+    let ulids: Vec<String> = (0..20)
+        .map(|n| db.collection("ulids").insert(json!({"n": n})).unwrap())
+        .collect();
+    let mut sorted = ulids.clone();
+    sorted.sort();
+    assert_eq!(ulids, sorted, "ULIDs should sort in insertion order");
+
+    // An explicit _id always wins
+    let id = db.collection("uuids").insert(json!({"_id": "custom"})).unwrap();
+    assert_eq!(id, "custom");
+
+    assert_eq!(db.get_id_strategy("ulids"), Some(IdStrategy::Ulid));
+    assert_eq!(db.get_id_strategy("other"), None);
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_auto_increment_persists_across_reopen() {
+    let path = "/tmp/test_id_strategies_auto_increment.db";
+    cleanup(path);
+
+    {
+        let db = Database::open(path).unwrap();
+        db.set_id_strategy("orders", IdStrategy::AutoIncrement).unwrap();
+
+        let orders = db.collection("orders");
+        assert_eq!(orders.insert(json!({"item": "a"})).unwrap(), "1");
+        assert_eq!(orders.insert(json!({"item": "b"})).unwrap(), "2");
+
+        let mut tx = db.begin().unwrap();
+        let id = tx.collection("orders").unwrap().insert(json!({"item": "c"})).unwrap();
+        tx.commit().unwrap();
+        assert_eq!(id, "3");
+
+        db.close().unwrap();
+    }
+
+    {
+        let db = Database::open(path).unwrap();
+        assert_eq!(db.get_id_strategy("orders"), Some(IdStrategy::AutoIncrement));
+        assert_eq!(db.collection("orders").insert(json!({"item": "d"})).unwrap(), "4");
+        assert_eq!(db.collection("orders").find_by_id("1").unwrap()["item"], "a");
+        db.close().unwrap();
+    }
+
+    cleanup(path);
+}
+
+#[test]
+fn test_strategy_set_after_writes_survives_reopen() {
+    let path = "/tmp/test_id_strategies_set_late.db";
+    cleanup(path);
+
+    {
+        let db = Database::open(path).unwrap();
+        db.collection("users").insert(json!({"_id": "seed"})).unwrap();
+        // No commit follows the setting, so only its own write persists it
+        db.set_id_strategy("users", IdStrategy::AutoIncrement).unwrap();
+        db.close().unwrap();
+    }
+
+    {
+        let db = Database::open(path).unwrap();
+        assert_eq!(db.get_id_strategy("users"), Some(IdStrategy::AutoIncrement));
+        assert_eq!(db.collection("users").insert(json!({"n": 1})).unwrap(), "1");
+        db.close().unwrap();
+    }
+
+    cleanup(path);
+}
+
+#[test]
+fn test_auto_increment_concurrent_inserts() {
+    let path = "/tmp/test_id_strategies_concurrent.db";
+    cleanup(path);
+
+    let db = Arc::new(Database::open(path).unwrap());
+    db.set_id_strategy("orders", IdStrategy::AutoIncrement).unwrap();
+
+    let handles: Vec<_> = (0..4)
+        .map(|t| {
+            let db = Arc::clone(&db);
+            thread::spawn(move || {
+                (0..25)
+                    .map(|i| db.collection("orders").insert(json!({"t": t, "i": i})).unwrap())
+                    .collect::<Vec<String>>()
+            })
+        })
+        .collect();
+
+    let mut ids = HashSet::new();
+    for handle in handles {
+        for id in handle.join().unwrap() {
+            assert!(ids.insert(id.clone()), "duplicate id {}", id);
+        }
+    }
+
+    // Every number from 1 to 100 was handed out exactly once
+    let expected: HashSet<String> = (1..=100).map(|n| n.to_string()).collect();
+    assert_eq!(ids, expected);
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_reserve_auto_ids_skips_restored_ids() {
+    let path = "/tmp/test_id_strategies_reserve.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    db.set_id_strategy("orders", IdStrategy::AutoIncrement).unwrap();
+
+    let orders = db.collection("orders");
+    orders.insert(json!({"_id": "7", "restored": true})).unwrap();
+    db.reserve_auto_ids("orders", 7).unwrap();
+    // The counter never moves backwards
+    db.reserve_auto_ids("orders", 3).unwrap();
+
+    assert_eq!(orders.insert(json!({"n": 1})).unwrap(), "8");
+
+    db.close().unwrap();
+    cleanup(path);
+}
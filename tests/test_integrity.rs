@@ -0,0 +1,194 @@
+use jasonisnthappy::core::database::{Database, DatabaseOptions};
+use jasonisnthappy::TextIndexOptions;
+use serde_json::json;
+use std::fs;
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_check_integrity_after_writes() {
+    let path = "/tmp/test_integrity_healthy.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    let users = db.collection("users");
+    db.create_index("users", "by_age", "age", false).unwrap();
+    db.create_index("users", "by_email", "email", true).unwrap();
+    db.create_compound_index("users", "by_city_age", &["city", "age"], false).unwrap();
+
+    for i in 0..300 {
+        let city = ["Oslo", "Lima", "Pune"][i % 3];
+        users.insert(json!({
+            "_id": format!("user{:04}", i),
+            "age": i % 50,
+            "email": format!("user{}@example.com", i),
+            "city": city,
+            "bio": "x".repeat(if i % 10 == 0 { 10_000 } else { 20 }),
+        })).unwrap();
+    }
+    for i in (0..300).step_by(7) {
+        users.update_by_id(&format!("user{:04}", i), json!({"age": 99})).unwrap();
+    }
+    for i in (0..300).step_by(5) {
+        users.delete_by_id(&format!("user{:04}", i)).unwrap();
+    }
+
+    let posts = db.collection("posts");
+    for i in 0..20 {
+        posts.insert(json!({"title": format!("post number {}", i), "body": "hello world"})).unwrap();
+    }
+    db.create_text_index("posts", "search", &["title", "body"], TextIndexOptions::default()).unwrap();
+
+    let report = db.check_integrity().unwrap();
+    assert!(report.is_ok(), "unexpected problems: {:?}", report.problems);
+    assert!(report.warnings.is_empty(), "unexpected warnings: {:?}", report.warnings);
+    assert_eq!(report.collections_checked, 2);
+    assert_eq!(report.documents_checked, 240 + 20);
+    assert_eq!(report.indexes_checked, 4);
+    assert!(report.total_pages > report.free_pages + report.orphaned_pages);
+
+    // Vacuum rewrites the file without the superseded pages
+    db.vacuum().unwrap();
+    let report = db.check_integrity().unwrap();
+    assert!(report.is_ok(), "unexpected problems after vacuum: {:?}", report.problems);
+    assert_eq!(report.documents_checked, 260);
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_check_integrity_reports_stale_text_index() {
+    let path = "/tmp/test_integrity_text_index.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    let posts = db.collection("posts");
+    posts.insert(json!({"_id": "a", "title": "rust databases"})).unwrap();
+    db.create_text_index("posts", "search", &["title"], TextIndexOptions::default()).unwrap();
+
+    // Text indexes are only refreshed by rebuild_index
+    posts.insert(json!({"_id": "b", "title": "embedded storage"})).unwrap();
+    let report = db.check_integrity().unwrap();
+    assert!(report.is_ok(), "unexpected problems: {:?}", report.problems);
+    assert_eq!(report.warnings.len(), 1);
+    assert!(report.warnings[0].message.contains("text index 'search'"));
+
+    db.rebuild_index("posts", "search").unwrap();
+    let report = db.check_integrity().unwrap();
+    assert!(report.warnings.is_empty(), "unexpected warnings: {:?}", report.warnings);
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_check_integrity_detects_damaged_document() {
+    let path = "/tmp/test_integrity_damaged.db";
+    cleanup(path);
+
+    {
+        let db = Database::open(path).unwrap();
+        let coll = db.collection("notes");
+        coll.insert(json!({"_id": "keep", "text": "fine"})).unwrap();
+        coll.insert(json!({"_id": "victim", "text": "needle-marker"})).unwrap();
+        // Move the pages out of the WAL so replay on open doesn't undo the damage
+        db.checkpoint_truncate().unwrap();
+        db.close().unwrap();
+    }
+
+    // Overwrite the document body in place so it no longer parses as JSON.
+    // Superseded copies of the page may hold it too, so damage every copy.
+    let mut contents = fs::read(path).unwrap();
+    let marker = b"\"needle-marker\"";
+    let offsets: Vec<usize> = contents.windows(marker.len())
+        .enumerate()
+        .filter(|(_, w)| w == marker)
+        .map(|(offset, _)| offset)
+        .collect();
+    assert!(!offsets.is_empty(), "document body should be in the file");
+    for offset in offsets {
+        contents[offset..offset + 4].copy_from_slice(b"}}}}");
+    }
+    fs::write(path, &contents).unwrap();
+
+    let db = Database::open(path).unwrap();
+    let report = db.check_integrity().unwrap();
+    assert!(!report.is_ok());
+    assert_eq!(report.problems.len(), 1, "problems: {:?}", report.problems);
+    let problem = &report.problems[0];
+    assert_eq!(problem.collection.as_deref(), Some("notes"));
+    assert!(problem.page.is_some());
+    assert!(problem.message.contains("document 'victim'"), "{}", problem);
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_check_integrity_detects_checksum_mismatch() {
+    let path = "/tmp/test_integrity_checksum.db";
+    cleanup(path);
+
+    {
+        let db = Database::open(path).unwrap();
+        assert!(db.page_checksums());
+        let coll = db.collection("notes");
+        coll.insert(json!({"_id": "victim", "text": "needle-marker"})).unwrap();
+        db.checkpoint_truncate().unwrap();
+        db.close().unwrap();
+    }
+
+    // Flip one bit of the body, leaving it valid JSON of the same length
+    let mut contents = fs::read(path).unwrap();
+    let marker = b"needle-marker";
+    let offsets: Vec<usize> = contents.windows(marker.len())
+        .enumerate()
+        .filter(|(_, w)| w == marker)
+        .map(|(offset, _)| offset)
+        .collect();
+    assert!(!offsets.is_empty(), "document body should be in the file");
+    for offset in offsets {
+        contents[offset] ^= 0x20;
+    }
+    fs::write(path, &contents).unwrap();
+
+    let db = Database::open(path).unwrap();
+    let err = db.collection("notes").find_by_id("victim").unwrap_err();
+    assert!(matches!(err, jasonisnthappy::Error::PageChecksumMismatch { .. }), "{:?}", err);
+
+    let report = db.check_integrity().unwrap();
+    assert!(!report.is_ok());
+    assert!(report.problems.iter().any(|p| p.message.contains("checksum")), "problems: {:?}", report.problems);
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_database_without_page_checksums() {
+    let path = "/tmp/test_integrity_no_checksums.db";
+    cleanup(path);
+
+    {
+        let opts = DatabaseOptions { page_checksums: false, ..DatabaseOptions::default() };
+        let db = Database::open_with_options(path, opts).unwrap();
+        db.collection("notes").insert(json!({"_id": "a", "text": "plain"})).unwrap();
+        db.close().unwrap();
+    }
+
+    // The setting is kept from the header when reopened with the defaults
+    let db = Database::open(path).unwrap();
+    assert!(!db.page_checksums());
+    assert_eq!(db.collection("notes").find_by_id("a").unwrap()["text"], "plain");
+    let report = db.check_integrity().unwrap();
+    assert!(report.is_ok(), "unexpected problems: {:?}", report.problems);
+    assert_eq!(report.pages_verified, 0);
+
+    db.close().unwrap();
+    cleanup(path);
+}
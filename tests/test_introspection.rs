@@ -280,3 +280,55 @@ fn test_introspection_persistence() {
     let _ = fs::remove_file(format!("{}.lock", path));
     let _ = fs::remove_file(format!("{}-wal", path));
 }
+
+#[test]
+fn test_index_usage() {
+    use jasonisnthappy::{SortOrder, TextIndexOptions};
+
+    let path = "/tmp/test_index_usage.db";
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+
+    let db = Database::open(path).unwrap();
+    let users = db.collection("users");
+    for i in 0..10 {
+        users.insert(json!({"name": format!("user {}", i), "age": 20 + i, "email": format!("{}@example.com", i)})).unwrap();
+    }
+    db.create_index("users", "by_age", "age", false).unwrap();
+    db.create_index("users", "by_email", "email", true).unwrap();
+    db.create_text_index("users", "by_name", &["name"], TextIndexOptions::default()).unwrap();
+
+    let usage = db.index_usage("users").unwrap();
+    let names: Vec<&str> = usage.iter().map(|index| index.name.as_str()).collect();
+    assert_eq!(names, ["by_age", "by_email", "by_name"]);
+    assert!(usage.iter().all(|index| index.uses == 0 && index.last_used_millis.is_none()));
+    assert!(usage[1].unique);
+    assert!(usage[2].text);
+
+    // Sorting on an indexed field reads the index, a filter on a field
+    // without one doesn't
+    users.query().sort_by("age", SortOrder::Asc).execute().unwrap();
+    users.query().filter("age > 25").sort_by("age", SortOrder::Asc).limit(3).execute().unwrap();
+    users.find("name is 'user 1'").unwrap();
+    users.search("user").unwrap();
+
+    let usage = db.index_usage("users").unwrap();
+    assert_eq!(usage[0].uses, 2);
+    assert!(usage[0].last_used_millis.unwrap() > 0);
+    assert_eq!(usage[1].uses, 0);
+    assert_eq!(usage[1].last_used_millis, None);
+    assert_eq!(usage[2].uses, 1);
+
+    // Dropping an index forgets its usage
+    db.drop_index("users", "by_age").unwrap();
+    db.create_index("users", "by_age", "age", false).unwrap();
+    assert_eq!(db.index_usage("users").unwrap()[0].uses, 0);
+
+    assert!(db.index_usage("missing").is_err());
+
+    db.close().unwrap();
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
@@ -0,0 +1,81 @@
+use jasonisnthappy::{Database, Error};
+use serde_json::json;
+use std::fs;
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_kv_get_set_delete() {
+    let path = "/tmp/test_kv_basic.db";
+    cleanup(path);
+    let db = Database::open(path).unwrap();
+    let kv = db.kv("settings");
+
+    assert_eq!(kv.get("theme").unwrap(), None);
+    assert!(!kv.delete("theme").unwrap());
+
+    kv.set("theme", json!({"mode": "dark", "accent": "blue"})).unwrap();
+    assert_eq!(kv.get("theme").unwrap(), Some(json!({"mode": "dark", "accent": "blue"})));
+
+    // Setting again replaces the whole value
+    kv.set("theme", json!({"mode": "light"})).unwrap();
+    assert_eq!(kv.get("theme").unwrap(), Some(json!({"mode": "light"})));
+    assert_eq!(db.collection("settings").find_by_id("theme").unwrap(), json!({"_id": "theme", "mode": "light"}));
+
+    // A matching _id is fine, anything else isn't
+    kv.set("lang", json!({"_id": "lang", "code": "en"})).unwrap();
+    assert!(matches!(kv.set("lang", json!({"_id": "other"})), Err(Error::InvalidDocumentFormat { .. })));
+    assert!(matches!(kv.set("lang", json!("en")), Err(Error::InvalidDocumentFormat { .. })));
+
+    assert!(kv.delete("theme").unwrap());
+    assert_eq!(kv.get("theme").unwrap(), None);
+    assert_eq!(db.collection("settings").count().unwrap(), 1);
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_kv_mget_mset() {
+    let path = "/tmp/test_kv_batch.db";
+    cleanup(path);
+    let db = Database::open(path).unwrap();
+    let kv = db.kv("flags");
+
+    assert_eq!(kv.mget(&["a", "b"]).unwrap(), vec![None, None]);
+
+    kv.set("a", json!({"on": false, "note": "old"})).unwrap();
+    kv.mset(&[
+        ("a", json!({"on": true})),
+        ("b", json!({"on": false})),
+        ("c", json!({"on": false})),
+        ("c", json!({"on": true})),
+    ]).unwrap();
+    assert_eq!(
+        kv.mget(&["c", "missing", "a", "b"]).unwrap(),
+        vec![Some(json!({"on": true})), None, Some(json!({"on": true})), Some(json!({"on": false}))]
+    );
+
+    // One bad value and nothing is written
+    assert!(kv.mset(&[("d", json!({"on": true})), ("e", json!(1))]).is_err());
+    assert_eq!(kv.get("d").unwrap(), None);
+
+    kv.mset(&[]).unwrap();
+    assert_eq!(db.collection("flags").count().unwrap(), 3);
+
+    // Nor when a write fails partway through the transaction
+    let slots = db.kv("slots");
+    db.create_index("slots", "by_slot", "slot", true).unwrap();
+    slots.mset(&[("a", json!({"slot": 1})), ("b", json!({"slot": 2}))]).unwrap();
+    let result = slots.mset(&[("c", json!({"slot": 3})), ("b", json!({"slot": 1}))]);
+    assert!(result.is_err());
+    assert_eq!(slots.get("c").unwrap(), None);
+    assert_eq!(slots.get("b").unwrap(), Some(json!({"slot": 2})));
+
+    db.close().unwrap();
+    cleanup(path);
+}
@@ -0,0 +1,120 @@
+use jasonisnthappy::core::database::Database;
+use serde_json::json;
+use std::env;
+use std::fs;
+use std::process::{Command, Stdio};
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+fn setup(path: &str) -> Database {
+    cleanup(path);
+    let db = Database::open(path).unwrap();
+    db.collection("accounts").insert(json!({"_id": "alice", "balance": 100})).unwrap();
+    db.create_index("ledger", "by_from", "from", false).unwrap();
+    db
+}
+
+/// Decrement alice's balance and record the transfer in one transaction
+fn transfer(db: &Database, amount: i64) -> jasonisnthappy::Transaction {
+    let mut tx = db.begin().unwrap();
+    tx.collection("accounts").unwrap()
+        .update_by_id("alice", json!({"$inc": {"balance": -amount}})).unwrap();
+    tx.collection("ledger").unwrap()
+        .insert(json!({"from": "alice", "amount": amount})).unwrap();
+    tx
+}
+
+fn assert_state(db: &Database, balance: i64, ledger_entries: usize) {
+    assert_eq!(db.collection("accounts").find_by_id("alice").unwrap()["balance"], balance);
+    assert_eq!(db.collection("ledger").count().unwrap(), ledger_entries);
+    assert_eq!(db.collection("ledger").find("from is 'alice'").unwrap().len(), ledger_entries);
+    let stats = db.collection_stats("ledger").unwrap();
+    assert_eq!(stats.indexes[0].entry_count, ledger_entries as u64);
+}
+
+#[test]
+fn test_transfer_commits_both_collections() {
+    let path = "/tmp/test_multi_collection_commit.db";
+    let db = setup(path);
+
+    transfer(&db, 30).commit().unwrap();
+    assert_state(&db, 70, 1);
+
+    drop(db);
+    let db = Database::open(path).unwrap();
+    assert_state(&db, 70, 1);
+
+    drop(db);
+    cleanup(path);
+}
+
+#[test]
+fn test_transfer_rollback_applies_neither() {
+    let path = "/tmp/test_multi_collection_rollback.db";
+    let db = setup(path);
+
+    transfer(&db, 30).rollback().unwrap();
+    assert_state(&db, 100, 0);
+
+    // Dropping an uncommitted transaction rolls it back as well
+    drop(transfer(&db, 30));
+    assert_state(&db, 100, 0);
+
+    drop(db);
+    cleanup(path);
+}
+
+#[test]
+fn test_transfer_failed_commit_applies_neither() {
+    let path = "/tmp/test_multi_collection_conflict.db";
+    let db = setup(path);
+
+    let mut tx = transfer(&db, 30);
+    // A concurrent write to one of the two collections makes the whole commit fail
+    db.collection("accounts").update_by_id("alice", json!({"$set": {"owner": "alice"}})).unwrap();
+    assert!(tx.commit().is_err());
+    assert_state(&db, 100, 0);
+
+    drop(db);
+    cleanup(path);
+}
+
+#[test]
+fn test_transfer_crash_before_commit_applies_neither() {
+    let path = "/tmp/test_multi_collection_crash.db";
+
+    if env::var("MULTI_COLLECTION_CRASH_CHILD").is_ok() {
+        let db = Database::open(path).unwrap();
+        let tx = transfer(&db, 30);
+        std::mem::forget(tx);
+        // Exit without committing or closing anything, as a crash would
+        std::process::exit(0);
+    }
+
+    let db = setup(path);
+    transfer(&db, 10).commit().unwrap();
+    drop(db);
+
+    let status = Command::new(env::current_exe().unwrap())
+        .env("MULTI_COLLECTION_CRASH_CHILD", "1")
+        .arg("test_transfer_crash_before_commit_applies_neither")
+        .arg("--exact")
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let db = Database::open(path).unwrap();
+    assert_state(&db, 90, 1);
+
+    // The database stays writable after recovery
+    transfer(&db, 20).commit().unwrap();
+    assert_state(&db, 70, 2);
+
+    drop(db);
+    cleanup(path);
+}
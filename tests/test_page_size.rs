@@ -0,0 +1,140 @@
+use jasonisnthappy::core::database::{Database, DatabaseOptions};
+use jasonisnthappy::Error;
+use serde_json::json;
+use std::fs;
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+fn open_with_page_size(path: &str, page_size: usize) -> jasonisnthappy::Result<Database> {
+    let opts = DatabaseOptions {
+        page_size,
+        ..Default::default()
+    };
+    Database::open_with_options(path, opts)
+}
+
+#[test]
+fn test_page_size_is_fixed_at_creation() {
+    let path = "/tmp/test_page_size_fixed.db";
+    cleanup(path);
+
+    let db = open_with_page_size(path, 16384).unwrap();
+    assert_eq!(db.page_size(), 16384);
+    let items = db.collection("items");
+    items.insert(json!({"_id": "small", "n": 1})).unwrap();
+    // Fits in one 16KB page, but would need overflow pages at 4KB
+    items.insert(json!({"_id": "medium", "body": "m".repeat(10_000)})).unwrap();
+    // Spans several 16KB overflow pages
+    items.insert(json!({"_id": "large", "body": "l".repeat(100_000)})).unwrap();
+    db.close().unwrap();
+
+    assert_eq!(fs::metadata(path).unwrap().len() % 16384, 0);
+
+    // 0 takes the size from the header, and the same explicit size is accepted
+    for page_size in [0, 16384] {
+        let db = open_with_page_size(path, page_size).unwrap();
+        assert_eq!(db.page_size(), 16384);
+        let items = db.collection("items");
+        assert_eq!(items.count().unwrap(), 3);
+        assert_eq!(items.find_by_id("medium").unwrap()["body"].as_str().unwrap().len(), 10_000);
+        assert_eq!(items.find_by_id("large").unwrap()["body"].as_str().unwrap().len(), 100_000);
+        db.close().unwrap();
+    }
+
+    match open_with_page_size(path, 4096) {
+        Err(Error::PageSizeMismatch { requested, actual }) => {
+            assert_eq!((requested, actual), (4096, 16384));
+        }
+        Err(e) => panic!("expected PageSizeMismatch, got {:?}", e),
+        Ok(_) => panic!("opening with a different page size should fail"),
+    }
+
+    cleanup(path);
+}
+
+#[test]
+fn test_default_page_size() {
+    let path = "/tmp/test_page_size_default.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    assert_eq!(db.page_size(), 4096);
+    db.collection("items").insert(json!({"n": 1})).unwrap();
+    db.close().unwrap();
+
+    let db = open_with_page_size(path, 4096).unwrap();
+    assert_eq!(db.collection("items").count().unwrap(), 1);
+    db.close().unwrap();
+
+    let err = open_with_page_size(path, 8192).err().expect("mismatched page size should fail");
+    assert!(err.to_string().contains("4096-byte pages"), "unexpected error: {}", err);
+
+    cleanup(path);
+}
+
+#[test]
+fn test_unsupported_page_sizes_are_rejected() {
+    let path = "/tmp/test_page_size_unsupported.db";
+
+    for page_size in [1024, 2048, 5000, 12288, 131072] {
+        cleanup(path);
+        let err = open_with_page_size(path, page_size).err().expect("unsupported page size should fail");
+        assert!(err.to_string().contains("power of two"), "unexpected error: {}", err);
+        assert!(!std::path::Path::new(path).exists(), "no database file should be created");
+    }
+
+    cleanup(path);
+}
+
+#[test]
+fn test_large_pages_survive_wal_recovery_and_vacuum() {
+    let path = "/tmp/test_page_size_recovery.db";
+    let crashed = "/tmp/test_page_size_recovery_crashed.db";
+    cleanup(path);
+    cleanup(crashed);
+
+    let opts = DatabaseOptions {
+        page_size: 65536,
+        auto_checkpoint_threshold: 1_000_000,
+        ..Default::default()
+    };
+    let db = Database::open_with_options(path, opts).unwrap();
+    db.create_index("items", "by_n", "n", false).unwrap();
+    let items = db.collection("items");
+    let docs: Vec<_> = (0..2_000)
+        .map(|i| json!({"_id": format!("doc{:05}", i), "n": i, "pad": "x".repeat(i % 300)}))
+        .collect();
+    items.insert_many(docs).unwrap();
+    items.delete("n >= 1000").unwrap();
+
+    // Copy the files while the commits are still only in the WAL, as a crash would leave them
+    assert!(db.frame_count() > 0);
+    fs::copy(path, crashed).unwrap();
+    fs::copy(format!("{}-wal", path), format!("{}-wal", crashed)).unwrap();
+    db.close().unwrap();
+    cleanup(path);
+
+    let db = open_with_page_size(crashed, 0).unwrap();
+    assert_eq!(db.page_size(), 65536);
+    let items = db.collection("items");
+    assert_eq!(items.count().unwrap(), 1_000);
+
+    db.vacuum().unwrap();
+    assert_eq!(db.page_size(), 65536);
+    assert_eq!(fs::metadata(crashed).unwrap().len() % 65536, 0);
+    assert_eq!(items.count().unwrap(), 1_000);
+    assert_eq!(items.find("n < 10").unwrap().len(), 10);
+    assert_eq!(items.find_by_id("doc00999").unwrap()["n"], 999);
+    db.close().unwrap();
+
+    let db = open_with_page_size(crashed, 0).unwrap();
+    assert_eq!(db.page_size(), 65536);
+    assert_eq!(db.collection("items").count().unwrap(), 1_000);
+    db.close().unwrap();
+
+    cleanup(crashed);
+}
@@ -0,0 +1,149 @@
+use jasonisnthappy::core::database::{Database, DatabaseOptions};
+use jasonisnthappy::SortOrder;
+use serde_json::{json, Value};
+use std::fs;
+use std::time::Instant;
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+fn open_with_scan_threads(path: &str, scan_threads: usize) -> Database {
+    let opts = DatabaseOptions {
+        scan_threads,
+        ..Default::default()
+    };
+    Database::open_with_options(path, opts).unwrap()
+}
+
+fn populate(db: &Database, count: usize) {
+    let items = db.collection("items");
+    let docs: Vec<Value> = (0..count)
+        .map(|i| json!({
+            "n": i,
+            "group": format!("g{}", i % 7),
+            "score": (i * 37) % 1000,
+            "tags": if i % 3 == 0 { json!(["a", "b"]) } else { json!(["c"]) },
+        }))
+        .collect();
+    for chunk in docs.chunks(5_000) {
+        items.insert_many(chunk.to_vec()).unwrap();
+    }
+}
+
+/// Every read that scans the collection, so serial and parallel runs can be compared
+fn scan_results(db: &Database) -> Vec<Value> {
+    let items = db.collection("items");
+    let filtered = items.query()
+        .filter("score >= 500 and group is not 'g3'")
+        .sort_by("score", SortOrder::Desc)
+        .sort_by("n", SortOrder::Asc)
+        .execute()
+        .unwrap();
+    let grouped = items.aggregate()
+        .match_("tags has 'a'")
+        .group_by("group")
+        .count("total")
+        .sort("_id", true)
+        .execute()
+        .unwrap();
+    let explain = items.query().filter("score < 100").explain().unwrap();
+
+    vec![
+        json!(items.find_all().unwrap()),
+        json!(items.find("score < 250").unwrap()),
+        json!(filtered),
+        json!(items.query().filter("n > 100").count().unwrap()),
+        json!(items.count_with_query(Some("group is 'g1'")).unwrap()),
+        json!(grouped),
+        json!(items.distinct_with_query("tags", "n < 10").unwrap()),
+        json!([explain.docs_examined, explain.docs_returned]),
+    ]
+}
+
+#[test]
+fn test_parallel_scan_matches_serial_scan() {
+    let path = "/tmp/test_parallel_scan_parity.db";
+    cleanup(path);
+
+    let db = open_with_scan_threads(path, 1);
+    populate(&db, 12_000);
+
+    // Deleted and updated documents must stay invisible to every run
+    let items = db.collection("items");
+    assert_eq!(items.delete("n < 40").unwrap(), 40);
+    items.update("n >= 11990", json!({"score": 999})).unwrap();
+
+    let serial = scan_results(&db);
+    db.close().unwrap();
+
+    for scan_threads in [2, 3, 8] {
+        let db = open_with_scan_threads(path, scan_threads);
+        assert_eq!(db.scan_threads(), scan_threads);
+        assert_eq!(scan_results(&db), serial, "scan_threads = {}", scan_threads);
+        db.close().unwrap();
+    }
+
+    cleanup(path);
+}
+
+#[test]
+fn test_parallel_scan_of_small_and_empty_collections() {
+    let path = "/tmp/test_parallel_scan_small.db";
+    cleanup(path);
+
+    let db = open_with_scan_threads(path, 8);
+    let items = db.collection("items");
+    assert_eq!(items.query().filter("n > 1").count().unwrap(), 0);
+
+    populate(&db, 10);
+    assert_eq!(items.find("n > 1").unwrap().len(), 8);
+    assert_eq!(items.query().filter("n < 5").count().unwrap(), 5);
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+#[ignore] // Run with: cargo test --release --test test_parallel_scan -- --ignored --nocapture
+fn bench_parallel_scan_speedup() {
+    let path = "/tmp/bench_parallel_scan.db";
+    cleanup(path);
+
+    let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let db = open_with_scan_threads(path, 1);
+    populate(&db, 200_000);
+    db.close().unwrap();
+
+    let time_scan = |scan_threads: usize| {
+        let db = open_with_scan_threads(path, scan_threads);
+        let items = db.collection("items");
+        // Warm the page cache so both runs read from memory
+        items.find("score > 990").unwrap();
+
+        let started = Instant::now();
+        let results = items.find("score > 990 and group is 'g2'").unwrap();
+        let elapsed = started.elapsed();
+        db.close().unwrap();
+        (results, elapsed)
+    };
+
+    let (serial, serial_time) = time_scan(1);
+    let (parallel, parallel_time) = time_scan(threads);
+    println!(
+        "serial: {:?}, {} threads: {:?} ({:.2}x)",
+        serial_time,
+        threads,
+        parallel_time,
+        serial_time.as_secs_f64() / parallel_time.as_secs_f64()
+    );
+
+    assert_eq!(serial, parallel);
+    if threads >= 4 {
+        assert!(parallel_time < serial_time, "parallel scan was not faster");
+    }
+
+    cleanup(path);
+}
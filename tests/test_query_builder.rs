@@ -1,4 +1,4 @@
-use jasonisnthappy::{Database, SortOrder};
+use jasonisnthappy::{AccessPath, Database, IndexHint, IndexOptions, Query, SortOrder};
 use serde_json::json;
 use std::fs;
 
@@ -576,8 +576,440 @@ fn test_distinct_integration() {
     let countries = products.distinct("manufacturer.country").unwrap();
     assert_eq!(countries.len(), 2);
 
+    // Paths through arrays reach every element, for distinct and for filters
+    let orders = db.collection("orders");
+    orders.insert(json!({"_id": "o1", "items": [{"price": 5, "tags": ["a"]}, {"price": 12}]})).unwrap();
+    orders.insert(json!({"_id": "o2", "items": [{"price": 5, "tags": ["b", "c"]}]})).unwrap();
+    orders.insert(json!({"_id": "o3", "items": []})).unwrap();
+
+    assert_eq!(orders.distinct("items.price").unwrap(), vec![json!(5), json!(12), json!(null)]);
+    assert_eq!(orders.distinct("items.tags").unwrap(), vec![json!("a"), json!("b"), json!("c"), json!(null)]);
+    assert_eq!(orders.count_distinct("items.price").unwrap(), 3);
+
+    let expensive = orders.find("items.price > 10").unwrap();
+    assert_eq!(expensive.len(), 1);
+    assert_eq!(expensive[0]["_id"], "o1");
+    assert_eq!(orders.find("items.tags contains 'c'").unwrap().len(), 1);
+
+    db.close().unwrap();
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_query_explain() {
+    let path = "/tmp/test_query_explain.db";
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+
+    let db = Database::open(path).unwrap();
+    let users = db.collection("users");
+
+    for i in 0..20 {
+        users.insert(json!({"name": format!("User{}", i), "age": 20 + i, "city": "NYC"})).unwrap();
+    }
+    db.create_index("users", "age_idx", "age", false).unwrap();
+    db.create_index("users", "name_idx", "name", false).unwrap();
+
+    let explain = users.query()
+        .filter("age >= 30 and (city is \"NYC\" or age < 0)")
+        .sort_by("age", SortOrder::Desc)
+        .limit(5)
+        .explain()
+        .unwrap();
+
+    assert_eq!(explain.access_path, AccessPath::CollectionScan);
+    assert_eq!(explain.filter_fields, vec!["age", "city"]);
+    assert_eq!(explain.candidate_indexes, vec!["age_idx"]);
+    assert_eq!(explain.estimated_docs_examined, 20);
+    assert_eq!(explain.docs_examined, 20);
+    assert_eq!(explain.docs_returned, 5);
+    assert!(explain.in_memory_sort);
+
+    let explain = users.explain("name is \"User3\"").unwrap();
+    assert_eq!(explain.docs_returned, 1);
+    assert!(!explain.in_memory_sort);
+
+    let missing = db.collection("missing").explain("age > 1").unwrap();
+    assert_eq!(missing.docs_examined, 0);
+    assert!(missing.candidate_indexes.is_empty());
+
+    assert!(users.explain("age >").is_err());
+
+    db.close().unwrap();
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_covered_query_reads_no_documents() {
+    let path = "/tmp/test_query_covered.db";
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+
+    let db = Database::open(path).unwrap();
+    let users = db.collection("users");
+
+    for i in 0..30 {
+        users.insert(json!({
+            "name": format!("User{:02}", i),
+            "age": 20 + (i % 10),
+            "city": if i % 2 == 0 { "NYC" } else { "LA" },
+        })).unwrap();
+    }
+    db.create_compound_index("users", "city_age", &["city", "age"], false).unwrap();
+
+    let query = || users.query()
+        .filter("city is \"NYC\" and age >= 24")
+        .project(&["city", "age"])
+        .sort_by("city", SortOrder::Asc)
+        .sort_by("age", SortOrder::Asc);
+
+    let before = db.metrics().documents_read;
+    let covered = query().execute().unwrap();
+    assert_eq!(db.metrics().documents_read - before, 0);
+
+    let explain = query().explain().unwrap();
+    assert_eq!(explain.access_path, AccessPath::CoveringIndexScan);
+    assert_eq!(explain.index.as_deref(), Some("city_age"));
+    assert_eq!(explain.keys_examined, 30);
+    assert_eq!(explain.docs_examined, 0);
+    assert!(!explain.in_memory_sort);
+
+    // Same answer as reading every document
+    let scanned = users.query()
+        .filter("city is \"NYC\" and age >= 24")
+        .project(&["city", "age", "name"])
+        .sort_by("city", SortOrder::Asc)
+        .sort_by("age", SortOrder::Asc)
+        .execute()
+        .unwrap();
+    assert_eq!(covered.len(), scanned.len());
+    assert_eq!(covered.len(), 9);
+    let strip = |docs: &[serde_json::Value]| docs.iter()
+        .map(|d| (d["_id"].clone(), d["city"].clone(), d["age"].clone()))
+        .collect::<Vec<_>>();
+    let mut covered_rows = strip(&covered);
+    let mut scanned_rows = strip(&scanned);
+    assert!(covered_rows.windows(2).all(|w| w[0].2.as_i64() <= w[1].2.as_i64()));
+    covered_rows.sort_by_key(|r| r.0.to_string());
+    scanned_rows.sort_by_key(|r| r.0.to_string());
+    assert_eq!(covered_rows, scanned_rows);
+
+    // Descending index serves descending sorts without sorting in memory
+    let options = IndexOptions { descending: true, ..Default::default() };
+    db.create_index_with_options("users", "age_desc", &["age"], options).unwrap();
+    let info = db.list_indexes("users").unwrap().into_iter().find(|i| i.name == "age_desc").unwrap();
+    assert!(info.descending);
+
+    let before = db.metrics().documents_read;
+    let ages = users.query()
+        .project(&["age"])
+        .sort_by("age", SortOrder::Desc)
+        .limit(3)
+        .execute()
+        .unwrap();
+    assert_eq!(db.metrics().documents_read - before, 0);
+    let ages: Vec<_> = ages.iter().map(|d| d["age"].clone()).collect();
+    assert_eq!(ages, vec![json!(29), json!(29), json!(29)]);
+
+    let explain = users.query().project(&["age"]).sort_by("age", SortOrder::Desc).explain().unwrap();
+    assert_eq!(explain.index.as_deref(), Some("age_desc"));
+    assert!(!explain.in_memory_sort);
+
+    // A missing field indexes as null, so those entries are checked against the document
+    users.insert(json!({"name": "NoAge", "city": "SF"})).unwrap();
+    db.drop_index("users", "age_desc").unwrap();
+    db.create_index_with_options("users", "age_desc", &["age"], options).unwrap();
+    let explain = users.query().project(&["age"]).sort_by("age", SortOrder::Desc).explain().unwrap();
+    assert_eq!(explain.access_path, AccessPath::CoveringIndexScan);
+    assert_eq!(explain.docs_examined, 1);
+    assert_eq!(explain.docs_returned, 31);
+
+    // Fields outside the index fall back to a collection scan
+    let explain = users.query().project(&["age", "name"]).explain().unwrap();
+    assert_eq!(explain.access_path, AccessPath::CollectionScan);
+    assert!(explain.index.is_none());
+
+    db.close().unwrap();
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_query_hint() {
+    let path = "/tmp/test_query_hint.db";
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+
+    let db = Database::open(path).unwrap();
+    let users = db.collection("users");
+
+    for i in 0..20 {
+        users.insert(json!({"name": format!("User{:02}", i), "age": 40 - i, "city": "NYC"})).unwrap();
+    }
+    db.create_index("users", "age_idx", "age", false).unwrap();
+    db.create_index("users", "name_idx", "name", false).unwrap();
+
+    let query = || users.query()
+        .filter("age >= 30")
+        .sort_by("age", SortOrder::Asc);
+
+    let expected = query().hint_none().execute().unwrap();
+    let hinted = query().hint("age_idx").execute().unwrap();
+    assert_eq!(hinted, expected);
+    assert_eq!(hinted.len(), 11);
+
+    let explain = query().hint("age_idx").explain().unwrap();
+    assert_eq!(explain.access_path, AccessPath::IndexScan);
+    assert_eq!(explain.index.as_deref(), Some("age_idx"));
+    assert_eq!(explain.hint, Some(IndexHint::Index("age_idx".to_string())));
+    assert_eq!(explain.keys_examined, 20);
+    assert_eq!(explain.docs_examined, 20);
+    assert!(!explain.in_memory_sort);
+
+    // A hint on a covering index still skips the documents
+    let explain = query().project(&["age"]).hint("age_idx").explain().unwrap();
+    assert_eq!(explain.access_path, AccessPath::CoveringIndexScan);
+    assert_eq!(explain.docs_examined, 0);
+
+    // hint_none overrides a covering index the planner would pick
+    let explain = query().project(&["age"]).hint_none().explain().unwrap();
+    assert_eq!(explain.access_path, AccessPath::CollectionScan);
+    assert_eq!(explain.hint, Some(IndexHint::NoIndex));
+    assert!(explain.index.is_none());
+
+    let explain = query().explain().unwrap();
+    assert!(explain.hint.is_none());
+
+    // Hints that can't be honored fail instead of falling back
+    assert!(query().hint("missing_idx").execute().is_err());
+    assert!(query().hint("name_idx").execute().is_err());
+    assert!(db.collection("missing").query().hint("age_idx").execute().is_err());
+
+    db.close().unwrap();
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_mixed_type_sort_order() {
+    let path = "/tmp/test_query_mixed_sort.db";
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+
+    let db = Database::open(path).unwrap();
+    let items = db.collection("items");
+
+    let values = [
+        ("obj", json!({"a": 1})),
+        ("str10", json!("10")),
+        ("float", json!(9.5)),
+        ("arr", json!([1, 2])),
+        ("int10", json!(10)),
+        ("str9", json!("9")),
+        ("neg", json!(-3)),
+        ("big", json!(9_007_199_254_740_993_u64)),
+        ("bool", json!(false)),
+        ("int2", json!(2)),
+        ("null", json!(null)),
+        ("float2", json!(2.25)),
+        ("bigger", json!(1e300)),
+    ];
+    for (id, score) in &values {
+        items.insert(json!({"_id": id, "score": score})).unwrap();
+    }
+    db.create_index("items", "score_idx", "score", false).unwrap();
+
+    let ids = |docs: Vec<serde_json::Value>| docs.iter()
+        .map(|d| d["_id"].as_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    let query = || items.query().sort_by("score", SortOrder::Asc);
+
+    // null < bool < number < string < array < object, numbers by value
+    let expected = vec![
+        "null", "bool", "neg", "int2", "float2", "float", "int10", "big", "bigger",
+        "str10", "str9", "arr", "obj",
+    ];
+    let in_memory = ids(query().hint_none().execute().unwrap());
+    assert_eq!(in_memory, expected);
+
+    let from_index = ids(query().hint("score_idx").execute().unwrap());
+    assert_eq!(from_index, expected);
+    assert!(!query().hint("score_idx").explain().unwrap().in_memory_sort);
+
+    let aggregated = ids(items.aggregate().sort("score", true).execute().unwrap());
+    assert_eq!(aggregated, expected);
+
+    db.close().unwrap();
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_find_with_projection() {
+    let path = "/tmp/test_find_with_projection.db";
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+
+    let db = Database::open(path).unwrap();
+    let users = db.collection("users");
+    users.insert(json!({"_id": "u1", "name": "Alice", "age": 31, "bio": "x".repeat(500), "address": {"city": "Oslo", "zip": "0150"}})).unwrap();
+    users.insert(json!({"_id": "u2", "name": "Bob", "age": 25, "bio": "y".repeat(500), "address": {"city": "Lima", "zip": "15001"}})).unwrap();
+
+    let included = users.find_with_projection("age > 30", &["name", "address.city"], &[]).unwrap();
+    assert_eq!(included, vec![json!({"_id": "u1", "name": "Alice", "address": {"city": "Oslo"}})]);
+
+    let excluded = users.find_with_projection("age < 30", &[], &["bio", "address.zip"]).unwrap();
+    assert_eq!(excluded, vec![json!({"_id": "u2", "name": "Bob", "age": 25, "address": {"city": "Lima"}})]);
+
+    let without_id = users.find_with_projection("name is 'Bob'", &[], &["_id", "bio", "address"]).unwrap();
+    assert_eq!(without_id, vec![json!({"name": "Bob", "age": 25})]);
+
+    let one = users.find_one_with_projection("age > 20", &["age"], &[]).unwrap().unwrap();
+    assert_eq!(one.as_object().unwrap().len(), 2);
+    assert!(users.find_one_with_projection("age > 90", &["age"], &[]).unwrap().is_none());
+
+    // No projection returns whole documents; with both lists, exclusions
+    // outside the included fields are refused
+    assert_eq!(users.find_with_projection("age > 30", &[], &[]).unwrap(), users.find("age > 30").unwrap());
+    assert!(users.find_with_projection("age > 30", &["name"], &["bio"]).is_err());
+
+    db.close().unwrap();
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_nested_and_id_only_projections() {
+    let path = "/tmp/test_nested_projections.db";
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+
+    let db = Database::open(path).unwrap();
+    let orders = db.collection("orders");
+    orders.insert(json!({
+        "_id": "o1",
+        "customer": {"name": "Ann", "address": {"city": "Oslo", "zip": "0150"}},
+        "items": [{"sku": "a", "qty": 1}, {"sku": "b", "qty": 2}, "note"],
+        "total": 3,
+    })).unwrap();
+
+    // Nested includes, reaching into every object of an array
+    let doc = orders.query().project(&["customer.address.city", "items.sku"]).first().unwrap().unwrap();
+    assert_eq!(doc, json!({
+        "_id": "o1",
+        "customer": {"address": {"city": "Oslo"}},
+        "items": [{"sku": "a"}, {"sku": "b"}],
+    }));
+
+    // A field included whole swallows paths inside it
+    let doc = orders.query().project(&["customer", "customer.name"]).first().unwrap().unwrap();
+    assert_eq!(doc["customer"]["address"]["zip"], "0150");
+
+    // Nested excludes, inside arrays too
+    let doc = orders.query().exclude(&["customer.address.zip", "items.qty"]).first().unwrap().unwrap();
+    assert_eq!(doc, json!({
+        "_id": "o1",
+        "customer": {"name": "Ann", "address": {"city": "Oslo"}},
+        "items": [{"sku": "a"}, {"sku": "b"}, "note"],
+        "total": 3,
+    }));
+
+    // Everything but _id, and nothing at all
+    assert_eq!(orders.find_with_projection("total > 1", &["_id"], &[]).unwrap(), vec![json!({"_id": "o1"})]);
+    assert_eq!(orders.find_with_projection("total > 1", &["_id"], &["_id"]).unwrap(), vec![json!({})]);
+
+    // An included field without a child, or without _id
+    let doc = orders.query()
+        .project(&["customer", "total"])
+        .exclude(&["_id", "customer.address"])
+        .first().unwrap().unwrap();
+    assert_eq!(doc, json!({"customer": {"name": "Ann"}, "total": 3}));
+
+    // Excluding an included field or a parent of one conflicts
+    assert!(orders.query().project(&["customer"]).exclude(&["customer"]).execute().is_err());
+    assert!(orders.query().project(&["customer.name"]).exclude(&["customer"]).execute().is_err());
+    assert!(orders.query().project(&["total"]).exclude(&["items"]).first().is_err());
+
     db.close().unwrap();
     let _ = fs::remove_file(path);
     let _ = fs::remove_file(format!("{}.lock", path));
     let _ = fs::remove_file(format!("{}-wal", path));
 }
+
+#[test]
+fn test_find_with_built_query() {
+    let path = "/tmp/test_find_with_built_query.db";
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+
+    let db = Database::open(path).unwrap();
+    let users = db.collection("users");
+    users.insert(json!({"name": "Alice", "age": 30})).unwrap();
+    users.insert(json!({"name": "Bob\" or age > 0 or name is \"", "age": 25})).unwrap();
+    users.insert(json!({"name": "Carol", "age": 15})).unwrap();
+
+    // Input that would rewrite a query string only ever matches itself
+    let user_input = "Bob\" or age > 0 or name is \"";
+    let results = users.find(
+        Query::field("age").gt(json!(18)).and(Query::field("name").eq(json!(user_input)))
+    ).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0]["age"], 25);
+
+    let adults = users.find(Query::field("age").gte(json!(18)).and(Query::field("name").ne(json!("Alice")).not())).unwrap();
+    assert_eq!(adults.len(), 1);
+    assert_eq!(adults[0]["name"], "Alice");
+
+    let query = String::from("age < 18");
+    assert_eq!(users.find(&query).unwrap().len(), 1);
+    assert_eq!(users.find(query).unwrap().len(), 1);
+    assert!(users.find_one(Query::field("age").gt(json!(100))).unwrap().is_none());
+
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_find_params_binds_placeholders() {
+    let path = "/tmp/test_find_params.db";
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+
+    let db = Database::open(path).unwrap();
+    let users = db.collection("users");
+    users.insert(json!({"email": "alice@example.com", "age": 30})).unwrap();
+    users.insert(json!({"email": "bob' or age > 0 or email is '", "age": 12})).unwrap();
+
+    let found = users.find_params("email is ?1 and age > ?2", &[json!("alice@example.com"), json!(18)]).unwrap();
+    assert_eq!(found.len(), 1);
+
+    let sneaky = json!("bob' or age > 0 or email is '");
+    let found = users.find_params("email is ?1 and age > ?2", &[sneaky.clone(), json!(18)]).unwrap();
+    assert!(found.is_empty());
+    let bob = users.find_one_params("email is ?", &[sneaky]).unwrap().unwrap();
+    assert_eq!(bob["age"], 12);
+
+    let err = users.find_params("email is ?1 and age > ?2", &[json!("alice@example.com")]).unwrap_err();
+    assert!(err.to_string().contains("?2"), "{}", err);
+
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
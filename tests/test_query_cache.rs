@@ -0,0 +1,103 @@
+use jasonisnthappy::core::database::{Database, DatabaseOptions};
+use serde_json::json;
+use std::fs;
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+fn open(path: &str, query_cache_size: usize) -> Database {
+    let opts = DatabaseOptions { query_cache_size, ..Default::default() };
+    Database::open_with_options(path, opts).unwrap()
+}
+
+#[test]
+fn test_query_cache_serves_repeated_queries() {
+    let path = "/tmp/test_query_cache_hits.db";
+    cleanup(path);
+
+    let db = open(path, 16);
+    let users = db.collection("users");
+    users.insert(json!({"name": "ann", "age": 30})).unwrap();
+    users.insert(json!({"name": "bob", "age": 20})).unwrap();
+
+    assert_eq!(users.find("age > 25").unwrap().len(), 1);
+    assert_eq!(users.find("age > 25").unwrap().len(), 1);
+    assert_eq!(users.find_params("age > ?1", &[json!(25)]).unwrap().len(), 1);
+    assert_eq!(users.find_params("age > ?1", &[json!(10)]).unwrap().len(), 2);
+
+    let pipeline = || users.aggregate().match_("age > 10").sort("age", true);
+    assert_eq!(pipeline().execute().unwrap()[0]["name"], "bob");
+    assert_eq!(pipeline().execute().unwrap()[0]["name"], "bob");
+
+    let metrics = db.metrics();
+    assert_eq!(metrics.query_cache_hits, 2);
+    assert_eq!(metrics.query_cache_misses, 4);
+    assert!(db.metrics().to_prometheus().contains("jasonisnthappy_query_cache_hits_total 2"));
+
+    drop(db);
+    cleanup(path);
+}
+
+#[test]
+fn test_query_cache_never_returns_stale_results() {
+    let path = "/tmp/test_query_cache_stale.db";
+    cleanup(path);
+
+    let db = open(path, 16);
+    let users = db.collection("users");
+    let other = db.collection("other");
+    let id = users.insert(json!({"name": "ann", "age": 30})).unwrap();
+    other.insert(json!({"n": 1})).unwrap();
+    assert_eq!(users.find("age > 25").unwrap().len(), 1);
+    assert_eq!(other.find("n is 1").unwrap().len(), 1);
+
+    users.insert(json!({"name": "bob", "age": 40})).unwrap();
+    assert_eq!(users.find("age > 25").unwrap().len(), 2);
+
+    users.update_by_id(&id, json!({"$set": {"age": 20}})).unwrap();
+    assert_eq!(users.find("age > 25").unwrap().len(), 1);
+
+    users.delete("name is 'bob'").unwrap();
+    assert_eq!(users.find("age > 25").unwrap().len(), 0);
+
+    let mut tx = db.begin().unwrap();
+    tx.collection("users").unwrap().insert(json!({"name": "cat", "age": 50})).unwrap();
+    assert_eq!(users.find("age > 25").unwrap().len(), 0);
+    tx.commit().unwrap();
+    assert_eq!(users.find("age > 25").unwrap().len(), 1);
+
+    let mut tx = db.begin().unwrap();
+    tx.drop_collection("users").unwrap();
+    tx.commit().unwrap();
+    assert_eq!(users.find("age > 25").unwrap_or_default().len(), 0);
+
+    // Writes to one collection leave another's results cached
+    let hits = db.metrics().query_cache_hits;
+    assert_eq!(other.find("n is 1").unwrap().len(), 1);
+    assert_eq!(db.metrics().query_cache_hits, hits + 1);
+
+    drop(db);
+    cleanup(path);
+}
+
+#[test]
+fn test_query_cache_is_off_by_default() {
+    let path = "/tmp/test_query_cache_off.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    let users = db.collection("users");
+    users.insert(json!({"name": "ann"})).unwrap();
+    users.find("name is 'ann'").unwrap();
+    users.find("name is 'ann'").unwrap();
+
+    let metrics = db.metrics();
+    assert_eq!(metrics.query_cache_hits, 0);
+    assert_eq!(metrics.query_cache_misses, 0);
+
+    drop(db);
+    cleanup(path);
+}
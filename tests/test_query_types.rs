@@ -0,0 +1,59 @@
+use jasonisnthappy::core::database::{Database, DatabaseOptions};
+use serde_json::json;
+use std::fs;
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+fn open(path: &str, strict_query_types: bool) -> Database {
+    let opts = DatabaseOptions { strict_query_types, ..Default::default() };
+    Database::open_with_options(path, opts).unwrap()
+}
+
+#[test]
+fn test_strict_query_types_by_default() {
+    let path = "/tmp/test_strict_query_types.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    assert!(db.strict_query_types());
+    let people = db.collection("people");
+    people.insert(json!({"name": "ann", "age": "21"})).unwrap();
+    people.insert(json!({"name": "bob", "age": 30})).unwrap();
+
+    let found = people.find("age > 18").unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0]["name"], "bob");
+    assert_eq!(people.query().filter("age is not 21").count().unwrap(), 2);
+
+    drop(db);
+    cleanup(path);
+}
+
+#[test]
+fn test_lax_query_types_coerce_numeric_strings() {
+    let path = "/tmp/test_lax_query_types.db";
+    cleanup(path);
+
+    let db = open(path, false);
+    let people = db.collection("people");
+    people.insert(json!({"name": "ann", "age": "21"})).unwrap();
+    people.insert(json!({"name": "bob", "age": 30})).unwrap();
+    people.insert(json!({"name": "cat", "age": "unknown"})).unwrap();
+    people.insert(json!({"name": "dan", "age": " 12 "})).unwrap();
+
+    assert_eq!(people.find("age > 18").unwrap().len(), 2);
+    assert_eq!(people.query().filter("age <= 21").count().unwrap(), 2);
+    assert_eq!(people.find_one("age is '30'").unwrap().unwrap()["name"], "bob");
+
+    assert_eq!(people.aggregate().match_("age >= 18").execute().unwrap().len(), 2);
+
+    assert_eq!(people.update("age < 18", json!({"$set": {"minor": true}})).unwrap(), 1);
+    assert_eq!(people.find("minor is true").unwrap()[0]["name"], "dan");
+
+    drop(db);
+    cleanup(path);
+}
@@ -0,0 +1,109 @@
+use jasonisnthappy::{Database, Error};
+use serde_json::json;
+use std::fs;
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_read_only_transaction_rejects_writes() {
+    let path = "/tmp/test_ro_tx_writes.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    let id = db.collection("users").insert(json!({"name": "Alice"})).unwrap();
+
+    let mut tx = db.begin_read_only().unwrap();
+    assert!(tx.is_read_only());
+
+    let mut users = tx.collection("users").unwrap();
+    assert_eq!(users.find_by_id(&id).unwrap()["name"], "Alice");
+    assert!(matches!(users.insert(json!({"name": "Bob"})), Err(Error::ReadOnlyTransaction)));
+    assert!(matches!(users.update_by_id(&id, json!({"name": "Carol"})), Err(Error::ReadOnlyTransaction)));
+    assert!(matches!(users.delete_by_id(&id), Err(Error::ReadOnlyTransaction)));
+
+    assert!(matches!(tx.create_collection("posts"), Err(Error::ReadOnlyTransaction)));
+    assert!(matches!(tx.drop_collection("users"), Err(Error::ReadOnlyTransaction)));
+    assert!(matches!(tx.rename_collection("users", "people"), Err(Error::ReadOnlyTransaction)));
+    tx.commit().unwrap();
+
+    let all = db.collection("users").find_all().unwrap();
+    assert_eq!(all.len(), 1);
+    assert_eq!(all[0]["name"], "Alice");
+    assert!(!db.list_collections().unwrap().contains(&"posts".to_string()));
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_read_only_transaction_keeps_its_snapshot() {
+    let path = "/tmp/test_ro_tx_snapshot.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    let id = db.collection("users").insert(json!({"name": "Alice"})).unwrap();
+
+    let mut reader = db.begin_read_only().unwrap();
+    let other_reader = db.begin_read_only().unwrap();
+    assert_eq!(reader.snapshot_id, other_reader.snapshot_id);
+
+    // Writers commit freely while the readers are open
+    let mut writer = db.begin().unwrap();
+    writer.collection("users").unwrap().update_by_id(&id, json!({"name": "Alicia"})).unwrap();
+    writer.collection("users").unwrap().insert(json!({"name": "Bob"})).unwrap();
+    writer.commit().unwrap();
+
+    let users = reader.collection("users").unwrap();
+    assert_eq!(users.count().unwrap(), 1);
+    assert_eq!(users.find_by_id(&id).unwrap()["name"], "Alice");
+    reader.commit().unwrap();
+    drop(other_reader);
+
+    let mut later = db.begin_read_only().unwrap();
+    let users = later.collection("users").unwrap();
+    assert_eq!(users.count().unwrap(), 2);
+    assert_eq!(users.find_by_id(&id).unwrap()["name"], "Alicia");
+    later.rollback().unwrap();
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_read_only_transaction_sees_missing_collection_as_empty() {
+    let path = "/tmp/test_ro_tx_missing.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+
+    let mut tx = db.begin_read_only().unwrap();
+    assert!(tx.collection("ghosts").unwrap().find_all().unwrap().is_empty());
+    tx.commit().unwrap();
+
+    assert!(!db.list_collections().unwrap().contains(&"ghosts".to_string()));
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_open_read_only_transaction_blocks_vacuum() {
+    let path = "/tmp/test_ro_tx_vacuum.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    db.collection("users").insert(json!({"name": "Alice"})).unwrap();
+
+    let tx = db.begin_read_only().unwrap();
+    assert!(db.vacuum().is_err());
+    drop(tx);
+
+    db.vacuum().unwrap();
+
+    db.close().unwrap();
+    cleanup(path);
+}
@@ -0,0 +1,143 @@
+use jasonisnthappy::{Database, DatabaseOptions};
+use serde_json::{json, Value};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const PAGE_SIZE: usize = 4096;
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+/// Two collections with a few versions of each left behind by updates and
+/// deletes, checkpointed so everything is in the main file. Returns the
+/// documents each collection should end up with.
+fn create_db(path: &str, page_checksums: bool) -> (Vec<Value>, Vec<Value>) {
+    cleanup(path);
+    let opts = DatabaseOptions { page_checksums, ..DatabaseOptions::default() };
+    let db = Database::open_with_options(path, opts).unwrap();
+
+    let users = db.collection("users");
+    db.create_index("users", "by_email", "email", true).unwrap();
+    for chunk in (0..300).collect::<Vec<_>>().chunks(50) {
+        let docs = chunk.iter()
+            .map(|i| json!({"_id": format!("user{:03}", i), "email": format!("{}@example.com", i)}))
+            .collect();
+        users.insert_many(docs).unwrap();
+    }
+
+    let orders = db.collection("orders");
+    for i in 0..5 {
+        orders.insert(json!({"_id": format!("order{}", i), "total": i * 10})).unwrap();
+    }
+    for round in 0..3 {
+        orders.update_by_id("order1", json!({"$set": {"total": 100 + round}})).unwrap();
+    }
+    orders.delete_by_id("order3").unwrap();
+
+    let expected = (users.find_all().unwrap(), orders.find_all().unwrap());
+    drop(users);
+    drop(orders);
+    db.checkpoint().unwrap();
+    db.close().unwrap();
+    expected
+}
+
+/// Overwrite the metadata page the header points at
+fn overwrite_metadata(path: &str, contents: &[u8]) {
+    let mut header = vec![0u8; PAGE_SIZE];
+    File::open(path).unwrap().read_exact(&mut header).unwrap();
+    let metadata_page = u64::from_le_bytes(header[24..32].try_into().unwrap());
+
+    let mut page = vec![0u8; PAGE_SIZE];
+    page[..contents.len()].copy_from_slice(contents);
+    let mut file = OpenOptions::new().write(true).open(path).unwrap();
+    file.seek(SeekFrom::Start(metadata_page * PAGE_SIZE as u64)).unwrap();
+    file.write_all(&page).unwrap();
+    file.sync_all().unwrap();
+}
+
+fn sorted(mut docs: Vec<Value>) -> Vec<Value> {
+    docs.sort_by(|a, b| a["_id"].as_str().cmp(&b["_id"].as_str()));
+    docs
+}
+
+#[test]
+fn test_repair_recovers_collections_from_emptied_metadata() {
+    let path = "/tmp/test_repair_emptied.db";
+    // Without checksums, so the emptied metadata page still reads fine
+    let (users, orders) = create_db(path, false);
+    overwrite_metadata(path, br#"{"collections":{}}"#);
+
+    {
+        let db = Database::open(path).unwrap();
+        assert!(db.list_collections().unwrap().is_empty());
+        db.close().unwrap();
+    }
+
+    let report = Database::repair(path).unwrap();
+    assert_eq!(report.collections.len(), 2, "{:?}", report);
+    assert_eq!(report.documents_recovered, 304);
+    assert!(report.unrecoverable.is_empty(), "{:?}", report.unrecoverable);
+    assert!(report.superseded_trees > 0);
+    // The unique index on users
+    assert!(report.skipped_trees >= 1);
+    assert!(!Path::new(&format!("{}.repair", path)).exists());
+
+    let db = Database::open(path).unwrap();
+    let mut names = db.list_collections().unwrap();
+    names.sort();
+    assert_eq!(names, ["recovered_1", "recovered_2"]);
+
+    let by_size = |documents: u64| {
+        let found = report.collections.iter().find(|c| c.documents == documents).unwrap();
+        sorted(db.collection(&found.name).find_all().unwrap())
+    };
+    assert_eq!(by_size(300), users);
+    assert_eq!(by_size(4), orders);
+    assert!(db.check_integrity().unwrap().is_ok());
+
+    // The repaired file is an ordinary database
+    db.rename_collection(&report.collections[0].name, "restored").unwrap();
+    db.collection("restored").insert(json!({"_id": "new"})).unwrap();
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_repair_opens_files_with_unreadable_metadata() {
+    let path = "/tmp/test_repair_truncated.db";
+    let (users, orders) = create_db(path, true);
+    overwrite_metadata(path, br#"{"collections":{"users":{"btree_ro"#);
+    assert!(Database::open(path).is_err());
+
+    let report = Database::repair(path).unwrap();
+    assert_eq!(report.documents_recovered, 304);
+
+    let db = Database::open(path).unwrap();
+    let mut recovered: Vec<Vec<Value>> = db.list_collections().unwrap().iter()
+        .map(|name| sorted(db.collection(name).find_all().unwrap()))
+        .collect();
+    recovered.sort_by_key(|docs| docs.len());
+    assert_eq!(recovered, [orders, users]);
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_repair_of_empty_database() {
+    let path = "/tmp/test_repair_empty.db";
+    cleanup(path);
+    Database::open(path).unwrap().close().unwrap();
+
+    let report = Database::repair(path).unwrap();
+    assert!(report.collections.is_empty());
+    assert_eq!(report.documents_recovered, 0);
+    assert!(Database::open(path).unwrap().list_collections().unwrap().is_empty());
+
+    cleanup(path);
+}
@@ -0,0 +1,183 @@
+use jasonisnthappy::{ChangeOperation, Database, Error, ReplicationRecord};
+use serde_json::json;
+use std::fs;
+use std::sync::Arc;
+use std::thread;
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+fn records(db: &Database, from_sequence: u64) -> Vec<ReplicationRecord> {
+    db.replication_stream(from_sequence).unwrap().collect::<Result<_, _>>().unwrap()
+}
+
+#[test]
+fn test_replication_stream_and_apply() {
+    let leader_path = "/tmp/test_replication_leader.db";
+    let follower_path = "/tmp/test_replication_follower.db";
+    cleanup(leader_path);
+    cleanup(follower_path);
+
+    let leader = Database::open(leader_path).unwrap();
+    assert!(leader.replication_stream(0).is_err());
+    leader.enable_replication_log().unwrap();
+    assert!(leader.replication_log_enabled());
+
+    let users = leader.collection("users");
+    users.insert(json!({"_id": "u1", "name": "Alice"})).unwrap();
+    leader.run_transaction(|tx| {
+        let mut users = tx.collection("users")?;
+        users.insert(json!({"_id": "u2", "name": "Bob"}))?;
+        users.update_by_id("u1", json!({"age": 30}))?;
+        tx.collection("orders")?.insert(json!({"_id": "o1", "user": "u2"}))?;
+        Ok(())
+    }).unwrap();
+    users.delete_by_id("u2").unwrap();
+
+    let log = records(&leader, 0);
+    let summary: Vec<_> = log.iter()
+        .map(|r| (r.sequence, r.collection.as_str(), r.operation, r.doc_id.as_str()))
+        .collect();
+    assert_eq!(summary, [
+        (1, "users", ChangeOperation::Insert, "u1"),
+        (2, "orders", ChangeOperation::Insert, "o1"),
+        (3, "users", ChangeOperation::Update, "u1"),
+        (4, "users", ChangeOperation::Insert, "u2"),
+        (5, "users", ChangeOperation::Delete, "u2"),
+    ]);
+    assert_eq!(log[2].document, Some(json!({"_id": "u1", "name": "Alice", "age": 30})));
+    assert_eq!(log[4].document, None);
+    // The log is hidden like recycle bins
+    assert_eq!(leader.list_collections().unwrap(), ["orders", "users"]);
+
+    let follower = Database::open(follower_path).unwrap();
+    for record in &log[..3] {
+        follower.apply_replication(record).unwrap();
+    }
+    let applied = log[2].sequence;
+    drop(follower);
+
+    // The follower restarts, replays a record it already applied and
+    // catches up from its saved position
+    users.insert(json!({"_id": "u3", "name": "Carol"})).unwrap();
+    let follower = Database::open(follower_path).unwrap();
+    follower.apply_replication(&log[2]).unwrap();
+    let mut stream = leader.replication_stream(applied + 1).unwrap();
+    for record in stream.by_ref() {
+        follower.apply_replication(&record.unwrap()).unwrap();
+    }
+    assert_eq!(stream.next_sequence(), 7);
+
+    let mut leader_users = leader.collection("users").find_all().unwrap();
+    let mut follower_users = follower.collection("users").find_all().unwrap();
+    leader_users.sort_by_key(|doc| doc["_id"].as_str().unwrap().to_string());
+    follower_users.sort_by_key(|doc| doc["_id"].as_str().unwrap().to_string());
+    assert_eq!(leader_users, follower_users);
+    assert_eq!(follower.collection("orders").count().unwrap(), 1);
+    assert!(follower.check_integrity().unwrap().is_ok());
+
+    // Sequences carry on after the leader is reopened
+    leader.close().unwrap();
+    let leader = Database::open(leader_path).unwrap();
+    leader.collection("users").delete_by_id("u1").unwrap();
+    let tail = records(&leader, 7);
+    assert_eq!(tail.len(), 1);
+    assert_eq!((tail[0].sequence, tail[0].operation), (7, ChangeOperation::Delete));
+    assert!(leader.check_integrity().unwrap().is_ok());
+
+    leader.close().unwrap();
+    follower.close().unwrap();
+    cleanup(leader_path);
+    cleanup(follower_path);
+}
+
+#[test]
+fn test_log_enabled_after_writes_survives_reopen() {
+    let path = "/tmp/test_replication_reopen.db";
+    cleanup(path);
+    let db = Database::open(path).unwrap();
+    db.collection("items").insert(json!({"_id": "a"})).unwrap();
+    db.enable_replication_log().unwrap();
+    db.close().unwrap();
+
+    let db = Database::open(path).unwrap();
+    assert!(db.replication_log_enabled());
+    db.collection("items").insert(json!({"_id": "b"})).unwrap();
+    let log = records(&db, 0);
+    assert_eq!(log.iter().map(|r| (r.sequence, r.doc_id.as_str())).collect::<Vec<_>>(), [(1, "b")]);
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_trim_replication_log() {
+    let path = "/tmp/test_replication_trim.db";
+    cleanup(path);
+    let db = Database::open(path).unwrap();
+    db.enable_replication_log().unwrap();
+    let items = db.collection("items");
+    for i in 0..10 {
+        items.insert(json!({"n": i})).unwrap();
+    }
+
+    assert_eq!(db.trim_replication_log(4).unwrap(), 4);
+    assert_eq!(db.trim_replication_log(4).unwrap(), 0);
+    let result = db.replication_stream(3);
+    assert!(matches!(result, Err(Error::ReplicationLogTrimmed { requested: 3, oldest: 5 })));
+    assert_eq!(records(&db, 0).first().map(|r| r.sequence), Some(5));
+    assert_eq!(records(&db, 5).len(), 6);
+
+    // Vacuum keeps the log and where it was trimmed
+    db.vacuum().unwrap();
+    assert!(db.replication_stream(4).is_err());
+    items.insert(json!({"n": 10})).unwrap();
+    let log = records(&db, 5);
+    assert_eq!(log.iter().map(|r| r.sequence).collect::<Vec<_>>(), (5..=11).collect::<Vec<_>>());
+    assert!(db.check_integrity().unwrap().is_ok());
+    db.close().unwrap();
+
+    let db = Database::open(path).unwrap();
+    assert!(matches!(db.replication_stream(4), Err(Error::ReplicationLogTrimmed { oldest: 5, .. })));
+    assert_eq!(records(&db, 0).len(), 7);
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_replication_log_orders_concurrent_commits() {
+    let path = "/tmp/test_replication_concurrent.db";
+    cleanup(path);
+    let db = Arc::new(Database::open(path).unwrap());
+    db.enable_replication_log().unwrap();
+
+    let handles: Vec<_> = (0..4).map(|t| {
+        let db = db.clone();
+        thread::spawn(move || {
+            let events = db.collection("events");
+            for i in 0..50 {
+                events.insert(json!({"_id": format!("{}-{}", t, i)})).unwrap();
+            }
+        })
+    }).collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    let log = records(&db, 0);
+    assert_eq!(log.iter().map(|r| r.sequence).collect::<Vec<_>>(), (1..=200).collect::<Vec<_>>());
+
+    let follower_path = "/tmp/test_replication_concurrent_follower.db";
+    cleanup(follower_path);
+    let follower = Database::open(follower_path).unwrap();
+    for record in &log {
+        follower.apply_replication(record).unwrap();
+    }
+    assert_eq!(follower.collection("events").count().unwrap(), 200);
+
+    follower.close().unwrap();
+    cleanup(follower_path);
+    cleanup(path);
+}
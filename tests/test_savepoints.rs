@@ -0,0 +1,154 @@
+use jasonisnthappy::core::database::Database;
+use jasonisnthappy::Error;
+use serde_json::json;
+use std::fs;
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_rollback_to_savepoint_keeps_earlier_writes() {
+    let path = "/tmp/test_savepoint_rollback.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+
+    let mut tx = db.begin().unwrap();
+    let alice = tx.collection("users").unwrap().insert(json!({"name": "Alice"})).unwrap();
+
+    tx.savepoint("batch").unwrap();
+    let bob = tx.collection("users").unwrap().insert(json!({"name": "Bob"})).unwrap();
+    let carol = tx.collection("users").unwrap().insert(json!({"name": "Carol"})).unwrap();
+
+    tx.rollback_to_savepoint("batch").unwrap();
+
+    // Writes after the savepoint are gone inside the transaction too
+    {
+        let users = tx.collection("users").unwrap();
+        assert!(users.find_by_id(&alice).is_ok());
+        assert!(users.find_by_id(&bob).is_err());
+        assert!(users.find_by_id(&carol).is_err());
+    }
+
+    tx.commit().unwrap();
+
+    let users = db.collection("users");
+    assert_eq!(users.count().unwrap(), 1);
+    assert_eq!(users.find_by_id(&alice).unwrap()["name"], "Alice");
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_rollback_to_savepoint_undoes_update_and_delete() {
+    let path = "/tmp/test_savepoint_update_delete.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    let users = db.collection("users");
+    let alice = users.insert(json!({"name": "Alice", "age": 30})).unwrap();
+    let bob = users.insert(json!({"name": "Bob", "age": 25})).unwrap();
+
+    let mut tx = db.begin().unwrap();
+    tx.collection("users").unwrap().update_by_id(&alice, json!({"age": 31})).unwrap();
+
+    tx.savepoint("risky").unwrap();
+    tx.collection("users").unwrap().update_by_id(&alice, json!({"age": 99})).unwrap();
+    tx.collection("users").unwrap().delete_by_id(&bob).unwrap();
+    tx.rollback_to_savepoint("risky").unwrap();
+
+    tx.commit().unwrap();
+
+    assert_eq!(users.find_by_id(&alice).unwrap()["age"], 31);
+    assert_eq!(users.find_by_id(&bob).unwrap()["name"], "Bob");
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_savepoint_can_be_rolled_back_to_twice() {
+    let path = "/tmp/test_savepoint_twice.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+
+    let mut tx = db.begin().unwrap();
+    tx.savepoint("sp").unwrap();
+    tx.collection("users").unwrap().insert(json!({"name": "Alice"})).unwrap();
+    tx.rollback_to_savepoint("sp").unwrap();
+    tx.collection("users").unwrap().insert(json!({"name": "Bob"})).unwrap();
+    tx.rollback_to_savepoint("sp").unwrap();
+    tx.collection("users").unwrap().insert(json!({"name": "Carol"})).unwrap();
+    tx.commit().unwrap();
+
+    let docs = db.collection("users").find_all().unwrap();
+    assert_eq!(docs.len(), 1);
+    assert_eq!(docs[0]["name"], "Carol");
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_nested_savepoints() {
+    let path = "/tmp/test_savepoint_nested.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+
+    let mut tx = db.begin().unwrap();
+    tx.collection("users").unwrap().insert(json!({"name": "Zed"})).unwrap();
+    tx.savepoint("outer").unwrap();
+    tx.collection("users").unwrap().insert(json!({"name": "Alice"})).unwrap();
+    tx.savepoint("inner").unwrap();
+    tx.collection("users").unwrap().insert(json!({"name": "Bob"})).unwrap();
+
+    // Rolling back to the outer savepoint also drops the inner one
+    tx.rollback_to_savepoint("outer").unwrap();
+    assert!(matches!(
+        tx.rollback_to_savepoint("inner"),
+        Err(Error::SavepointNotFound { .. })
+    ));
+
+    tx.commit().unwrap();
+    assert_eq!(db.collection("users").count().unwrap(), 1);
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_release_savepoint_keeps_writes() {
+    let path = "/tmp/test_savepoint_release.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+
+    let mut tx = db.begin().unwrap();
+    tx.savepoint("sp").unwrap();
+    tx.collection("users").unwrap().insert(json!({"name": "Alice"})).unwrap();
+    tx.release_savepoint("sp").unwrap();
+
+    assert!(matches!(
+        tx.rollback_to_savepoint("sp"),
+        Err(Error::SavepointNotFound { .. })
+    ));
+    assert!(matches!(
+        tx.release_savepoint("missing"),
+        Err(Error::SavepointNotFound { .. })
+    ));
+
+    tx.commit().unwrap();
+    assert_eq!(db.collection("users").count().unwrap(), 1);
+
+    // Savepoints need an active transaction
+    assert!(matches!(tx.savepoint("late"), Err(Error::TxNotActive)));
+
+    db.close().unwrap();
+    cleanup(path);
+}
@@ -1,4 +1,4 @@
-use jasonisnthappy::{Database, Schema, ValueType};
+use jasonisnthappy::{Database, DatabaseOptions, Error, Schema, ValueType};
 use serde_json::json;
 use std::collections::HashMap;
 use std::fs;
@@ -393,3 +393,113 @@ fn test_integer_type_validation() {
     let _ = fs::remove_file(format!("{}.lock", path));
     let _ = fs::remove_file(format!("{}-wal", path));
 }
+
+#[test]
+fn test_set_json_schema() {
+    let path = "/tmp/test_set_json_schema.db";
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+
+    {
+        let db = Database::open(path).unwrap();
+
+        let warnings = db.set_json_schema("users", &json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "type": "object",
+            "required": ["email"],
+            "properties": {
+                "email": {"type": "string", "pattern": "^[^@\\s]+@[^@\\s]+$"},
+                "age": {"type": "integer", "minimum": 0, "exclusiveMaximum": 150}
+            }
+        })).unwrap();
+        assert_eq!(warnings, vec![
+            "#/properties/age: keyword 'exclusiveMaximum' is not supported and was ignored".to_string(),
+        ]);
+
+        let coll = db.collection("users");
+        assert!(coll.insert(json!({"email": "alice@example.com", "age": 30})).is_ok());
+        assert!(coll.insert(json!({"email": "not an email"})).is_err());
+        assert!(coll.insert(json!({"email": "bob@example.com", "age": -1})).is_err());
+        assert!(coll.insert(json!({"age": 30})).is_err());
+
+        // A native schema with a pattern that can't be compiled is rejected outright
+        let mut schema = Schema::new();
+        schema.pattern = Some("(?<=x)y".to_string());
+        assert!(db.set_schema("codes", schema).is_err());
+        assert!(db.get_schema("codes").is_none());
+
+        db.close().unwrap();
+    }
+
+    // The translated pattern is stored with the rest of the schema
+    let db = Database::open(path).unwrap();
+    let coll = db.collection("users");
+    assert!(coll.insert(json!({"email": "nope"})).is_err());
+    assert!(coll.insert(json!({"email": "carol@example.com"})).is_ok());
+
+    // Cleanup
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_collect_all_validation_errors_option() {
+    let path = "/tmp/test_collect_all_validation_errors.db";
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+
+    let schema = json!({
+        "type": "object",
+        "required": ["name"],
+        "properties": {
+            "age": {"type": "integer", "minimum": 0},
+            "email": {"type": "string", "pattern": "@"}
+        }
+    });
+    let bad = json!({"age": -5, "email": "nope"});
+
+    {
+        let db = Database::open(path).unwrap();
+        db.set_json_schema("users", &schema).unwrap();
+        let coll = db.collection("users");
+
+        // By default only the first problem is reported
+        match coll.insert(bad.clone()) {
+            Err(Error::SchemaValidation(msg)) => assert_eq!(msg, "Missing required field 'name' at ''"),
+            other => panic!("expected a schema validation error, got {:?}", other),
+        }
+        db.close().unwrap();
+    }
+
+    let opts = DatabaseOptions {
+        collect_all_validation_errors: true,
+        ..Default::default()
+    };
+    let db = Database::open_with_options(path, opts).unwrap();
+    let coll = db.collection("users");
+
+    match coll.insert(bad) {
+        Err(Error::SchemaValidation(msg)) => {
+            assert!(msg.starts_with("3 problems: "), "{}", msg);
+            assert!(msg.contains("Missing required field 'name'"), "{}", msg);
+            assert!(msg.contains("Number at 'age' (-5) is less than minimum 0"), "{}", msg);
+            assert!(msg.contains("String at 'email' does not match pattern '@'"), "{}", msg);
+        }
+        other => panic!("expected a schema validation error, got {:?}", other),
+    }
+
+    // Updates go through the same check
+    let id = coll.insert(json!({"name": "Alice", "age": 1})).unwrap();
+    match coll.update_by_id(&id, json!({"age": -1, "email": "x"})) {
+        Err(Error::SchemaValidation(msg)) => assert!(msg.starts_with("2 problems: "), "{}", msg),
+        other => panic!("expected a schema validation error, got {:?}", other),
+    }
+
+    // Cleanup
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
@@ -1,5 +1,5 @@
 /// Tests for bulk operation size limits
-use jasonisnthappy::{Database, DatabaseOptions};
+use jasonisnthappy::{Database, DatabaseOptions, Error};
 use serde_json::json;
 use tempfile::TempDir;
 
@@ -86,6 +86,8 @@ fn test_default_limits_are_generous() {
     assert_eq!(db.max_bulk_operations(), 100_000, "Default should be 100K");
     assert_eq!(db.max_document_size(), 67_108_864, "Default should be 64MB");
     assert_eq!(db.max_request_body_size(), 52_428_800, "Default should be 50MB");
+    assert_eq!(db.max_scan_documents(), 0, "Scans should be unlimited by default");
+    assert_eq!(db.max_result_documents(), 0, "Results should be unlimited by default");
 
     db.close().unwrap();
 }
@@ -154,3 +156,78 @@ fn test_large_but_within_limit() {
 
     db.close().unwrap();
 }
+
+#[test]
+fn test_query_scan_and_result_limits() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.db");
+
+    let opts = DatabaseOptions {
+        max_scan_documents: 100,
+        max_result_documents: 20,
+        ..Default::default()
+    };
+
+    let db = Database::open_with_options(db_path.to_str().unwrap(), opts).unwrap();
+    let coll = db.collection("test");
+    let docs: Vec<_> = (0..100).map(|i| json!({"value": i})).collect();
+    coll.insert_many(docs).unwrap();
+
+    // A full scan at the limit is fine as long as few documents match
+    assert_eq!(coll.find("value < 20").unwrap().len(), 20);
+    assert_eq!(db.metrics().queries_over_limit, 0);
+
+    // Too many matches
+    let err = coll.find("value >= 50").unwrap_err();
+    assert!(matches!(err, Error::QueryTooExpensive { examined: 21, limit: 20 }), "{:?}", err);
+    assert!(coll.find_all().is_err());
+    assert!(coll.query().filter("value > 10").limit(5).execute().is_err());
+    assert!(coll.aggregate().match_("value > 10").execute().is_err());
+
+    // Too many documents to examine
+    coll.insert(json!({"value": 1000})).unwrap();
+    let err = coll.find("value > 999").unwrap_err();
+    assert!(matches!(err, Error::QueryTooExpensive { examined: 101, limit: 100 }), "{:?}", err);
+
+    // Every refused query is counted
+    assert_eq!(db.metrics().queries_over_limit, 5);
+
+    // Lookups by ID and counts don't buffer documents
+    assert_eq!(coll.count().unwrap(), 101);
+    db.close().unwrap();
+}
+
+#[test]
+fn test_metadata_larger_than_a_page() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.db");
+    let path = db_path.to_str().unwrap();
+
+    // Enough collections that their metadata spans several pages
+    let db = Database::open(path).unwrap();
+    for i in 0..200 {
+        db.collection(&format!("collection_{}", i)).insert(json!({"value": i})).unwrap();
+    }
+    assert!(db.check_integrity().unwrap().is_ok());
+    db.close().unwrap();
+
+    let db = Database::open(path).unwrap();
+    assert_eq!(db.list_collections().unwrap().len(), 200);
+    assert_eq!(db.collection("collection_199").count().unwrap(), 1);
+
+    // Shrinking the metadata frees the pages it no longer needs
+    let mut tx = db.begin().unwrap();
+    for i in 10..200 {
+        tx.drop_collection(&format!("collection_{}", i)).unwrap();
+    }
+    tx.collection("collection_0").unwrap().insert(json!({"value": 200})).unwrap();
+    tx.commit().unwrap();
+    assert!(db.check_integrity().unwrap().is_ok());
+    db.close().unwrap();
+
+    let db = Database::open(path).unwrap();
+    assert_eq!(db.list_collections().unwrap().len(), 10);
+    assert_eq!(db.collection("collection_0").count().unwrap(), 2);
+    assert!(db.check_integrity().unwrap().is_ok());
+    db.close().unwrap();
+}
@@ -0,0 +1,68 @@
+use jasonisnthappy::core::database::{Database, DatabaseOptions};
+use jasonisnthappy::{SlowQuery, SlowQueryOperation};
+use serde_json::json;
+use std::fs;
+use std::sync::{Arc, Mutex};
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_slow_queries_are_logged() {
+    let path = "/tmp/test_slow_query_log.db";
+    cleanup(path);
+
+    let opts = DatabaseOptions { slow_query_log_size: 4, ..Default::default() };
+    let db = Database::open_with_options(path, opts).unwrap();
+    let items = db.collection("items");
+    let docs = (0..5000).map(|n| json!({"n": n, "group": n % 10})).collect();
+    items.insert_many(docs).unwrap();
+
+    // Off by default
+    items.find("n > 10").unwrap();
+    assert!(db.slow_queries().is_empty());
+
+    let seen: Arc<Mutex<Vec<SlowQuery>>> = Arc::new(Mutex::new(Vec::new()));
+    let seen_clone = seen.clone();
+    db.on_slow_query(move |query| seen_clone.lock().unwrap().push(query.clone()));
+    db.set_slow_query_threshold_ms(1);
+
+    items.find("n > 4990").unwrap();
+    items.aggregate().match_("group is 3").group_by("group").count("total").execute().unwrap();
+    items.update("n is 7", json!({"$set": {"seven": true}})).unwrap();
+    items.delete("n >= 4999").unwrap();
+
+    let logged = db.slow_queries();
+    let operations: Vec<SlowQueryOperation> = logged.iter().map(|q| q.operation).collect();
+    assert_eq!(operations, vec![
+        SlowQueryOperation::Find,
+        SlowQueryOperation::Aggregate,
+        SlowQueryOperation::Update,
+        SlowQueryOperation::Delete,
+    ]);
+    assert_eq!(logged[0].collection, "items");
+    assert_eq!(logged[0].query, "n > 4990");
+    assert_eq!(logged[0].docs_examined, 5000);
+    assert!(logged[0].elapsed_ms >= 1.0);
+    assert_eq!(logged[1].query, "match(group is 3) | group_by(group)");
+    assert_eq!(*seen.lock().unwrap(), logged);
+
+    // The log keeps the most recent entries
+    items.query().filter("group is 1").execute().unwrap();
+    let logged = db.slow_queries();
+    assert_eq!(logged.len(), 4);
+    assert_eq!(logged[0].operation, SlowQueryOperation::Aggregate);
+    assert_eq!(logged[3].query, "group is 1");
+
+    db.clear_slow_queries();
+    db.clear_slow_query_callback();
+    items.find("n > 0").unwrap();
+    assert_eq!(db.slow_queries().len(), 1);
+    assert_eq!(seen.lock().unwrap().len(), 5);
+
+    db.close().unwrap();
+    cleanup(path);
+}
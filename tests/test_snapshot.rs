@@ -0,0 +1,113 @@
+use jasonisnthappy::{Database, Error};
+use serde_json::json;
+use std::fs;
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_snapshot_reads_one_point_in_time() {
+    let path = "/tmp/test_snapshot_consistent.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    let orders = db.collection("orders");
+    db.create_index("orders", "by_status", "status", false).unwrap();
+    for i in 0..10 {
+        orders.insert(json!({"_id": format!("o{}", i), "status": "open", "total": i})).unwrap();
+    }
+
+    let snapshot = db.snapshot().unwrap();
+    let frozen = snapshot.collection("orders");
+
+    // Writers aren't blocked by the snapshot
+    orders.insert(json!({"_id": "o10", "status": "open", "total": 10})).unwrap();
+    orders.update_by_id("o0", json!({"status": "shipped"})).unwrap();
+    orders.delete_by_id("o1").unwrap();
+    db.collection("refunds").insert(json!({"order": "o2"})).unwrap();
+
+    assert_eq!(frozen.count().unwrap(), 10);
+    assert_eq!(frozen.estimated_count().unwrap(), 10);
+    assert_eq!(frozen.find("status is 'open'").unwrap().len(), 10);
+    assert_eq!(frozen.find_by_id("o0").unwrap()["status"], "open");
+    assert!(frozen.find_by_id("o1").is_ok());
+    assert!(frozen.find_by_id("o10").is_err());
+    assert_eq!(frozen.find_by_ids(&["o1", "o10"]).unwrap(), vec![Some(frozen.find_by_id("o1").unwrap()), None]);
+    assert_eq!(frozen.distinct("status").unwrap(), vec![json!("open")]);
+    assert_eq!(frozen.query().sort_by("status", jasonisnthappy::SortOrder::Asc).count().unwrap(), 10);
+
+    let totals = frozen.aggregate()
+        .group_by("status")
+        .sum("total", "sum")
+        .execute()
+        .unwrap();
+    assert_eq!(totals, vec![json!({"_id": "open", "sum": 45.0})]);
+
+    // Collections taken later from the same snapshot see the same point in time
+    assert!(!snapshot.collection("refunds").exists());
+    assert_eq!(snapshot.list_collections(), vec!["orders".to_string()]);
+
+    // The live collection sees every write
+    assert_eq!(orders.count().unwrap(), 10);
+    assert_eq!(orders.find("status is 'open'").unwrap().len(), 9);
+
+    drop(frozen);
+    drop(snapshot);
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_snapshot_collections_reject_writes() {
+    let path = "/tmp/test_snapshot_writes.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    let id = db.collection("users").insert(json!({"name": "Alice"})).unwrap();
+
+    let snapshot = db.snapshot().unwrap();
+    let users = snapshot.collection("users");
+    assert!(matches!(users.insert(json!({"name": "Bob"})), Err(Error::ReadOnlyTransaction)));
+    assert!(matches!(users.update_by_id(&id, json!({"name": "Carol"})), Err(Error::ReadOnlyTransaction)));
+    assert!(matches!(users.delete_by_id(&id), Err(Error::ReadOnlyTransaction)));
+    assert!(matches!(users.find_one_and_delete("name is 'Alice'"), Err(Error::ReadOnlyTransaction)));
+    assert!(matches!(
+        users.bulk_write().insert(json!({"name": "Dan"})).execute(),
+        Err(Error::ReadOnlyTransaction)
+    ));
+
+    assert_eq!(db.collection("users").count().unwrap(), 1);
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_dropping_snapshot_releases_it() {
+    let path = "/tmp/test_snapshot_release.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    let users = db.collection("users");
+    let id = users.insert(json!({"name": "Alice"})).unwrap();
+
+    let snapshot = db.snapshot().unwrap();
+    let frozen = snapshot.collection("users");
+    drop(snapshot);
+
+    users.update_by_id(&id, json!({"name": "Alicia"})).unwrap();
+
+    // The collection keeps the snapshot pinned, so the old version survives GC
+    db.garbage_collect().unwrap();
+    assert_eq!(frozen.find_by_id(&id).unwrap()["name"], "Alice");
+
+    drop(frozen);
+    let stats = db.garbage_collect().unwrap();
+    assert_eq!(stats.versions_removed, 1);
+
+    db.close().unwrap();
+    cleanup(path);
+}
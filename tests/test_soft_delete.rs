@@ -0,0 +1,216 @@
+use jasonisnthappy::core::database::Database;
+use jasonisnthappy::{Error, SortOrder};
+use serde_json::{json, Value};
+use std::fs;
+use std::time::Duration;
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+fn ids(docs: &[Value]) -> Vec<String> {
+    docs.iter().map(|doc| doc["_id"].as_str().unwrap().to_string()).collect()
+}
+
+fn setup(path: &str) -> Database {
+    cleanup(path);
+    let db = Database::open(path).unwrap();
+    db.create_index("orders", "by_ref", "ref", true).unwrap();
+    db.set_soft_delete("orders", true).unwrap();
+    db.collection("orders").insert_many(vec![
+        json!({"_id": "a", "ref": 1, "status": "open"}),
+        json!({"_id": "b", "ref": 2, "status": "open"}),
+        json!({"_id": "c", "ref": 3, "status": "closed"}),
+        json!({"_id": "d", "ref": 4, "status": "closed"}),
+    ]).unwrap();
+    db
+}
+
+#[test]
+fn test_deletes_move_documents_to_recycle_bin() {
+    let path = "/tmp/test_soft_delete_bin.db";
+    let db = setup(path);
+    let orders = db.collection("orders");
+    assert!(db.soft_delete("orders"));
+
+    orders.delete_by_id("a").unwrap();
+    assert_eq!(orders.delete("status is 'closed'").unwrap(), 2);
+
+    assert!(matches!(orders.find_by_id("a"), Err(Error::NotFound)));
+    assert_eq!(ids(&orders.find_all().unwrap()), ["b"]);
+    assert_eq!(orders.count().unwrap(), 1);
+    assert!(orders.find("ref is 1").unwrap().is_empty());
+
+    let deleted = orders.deleted().unwrap();
+    assert_eq!(ids(&deleted), ["a", "c", "d"]);
+    assert!(deleted.iter().all(|doc| doc["_deleted_at"].as_u64().is_some()));
+    assert_eq!(deleted[0]["ref"], 1);
+
+    // The unique index only holds live documents
+    orders.insert(json!({"_id": "e", "ref": 3})).unwrap();
+
+    // The bin is hidden, and survives a reopen
+    assert_eq!(db.list_collections().unwrap(), ["orders"]);
+    assert_eq!(db.info().unwrap().total_documents, 2);
+    assert!(db.check_integrity().unwrap().is_ok());
+    drop(orders);
+    drop(db);
+
+    let db = Database::open(path).unwrap();
+    let orders = db.collection("orders");
+    assert_eq!(ids(&orders.deleted().unwrap()), ["a", "c", "d"]);
+    assert_eq!(orders.count().unwrap(), 2);
+
+    drop(orders);
+    drop(db);
+    cleanup(path);
+}
+
+#[test]
+fn test_restore_and_purge() {
+    let path = "/tmp/test_soft_delete_restore.db";
+    let db = setup(path);
+    let orders = db.collection("orders");
+
+    orders.delete("status is 'open'").unwrap();
+    orders.restore("a").unwrap();
+    let restored = orders.find_by_id("a").unwrap();
+    assert_eq!(restored, json!({"_id": "a", "ref": 1, "status": "open"}));
+    assert_eq!(ids(&orders.find("ref is 1").unwrap()), ["a"]);
+    assert!(matches!(orders.restore("a"), Err(Error::DocumentNotFound { .. })));
+
+    // A document inserted under the same id since blocks the restore
+    orders.insert(json!({"_id": "b", "ref": 20})).unwrap();
+    assert!(matches!(orders.restore("b"), Err(Error::DocumentAlreadyExists { .. })));
+    assert_eq!(ids(&orders.deleted().unwrap()), ["b"]);
+
+    // Deleting it again replaces the earlier deleted version
+    orders.delete_by_id("b").unwrap();
+    let deleted = orders.deleted().unwrap();
+    assert_eq!(deleted.len(), 1);
+    assert_eq!(deleted[0]["ref"], 20);
+
+    orders.delete_by_id("c").unwrap();
+    assert_eq!(orders.purge_deleted(Duration::from_secs(3600)).unwrap(), 0);
+    assert_eq!(orders.purge_deleted(Duration::ZERO).unwrap(), 2);
+    assert!(orders.deleted().unwrap().is_empty());
+    assert!(matches!(orders.restore("c"), Err(Error::DocumentNotFound { .. })));
+    assert_eq!(ids(&orders.find_all().unwrap()), ["a", "d"]);
+
+    drop(orders);
+    drop(db);
+    cleanup(path);
+}
+
+#[test]
+fn test_setting_enabled_after_writes_survives_reopen() {
+    let path = "/tmp/test_soft_delete_reopen.db";
+    cleanup(path);
+    let db = Database::open(path).unwrap();
+    db.collection("orders").insert(json!({"_id": "a", "ref": 1})).unwrap();
+    db.set_soft_delete("orders", true).unwrap();
+    drop(db);
+
+    let db = Database::open(path).unwrap();
+    assert!(db.soft_delete("orders"));
+    let orders = db.collection("orders");
+    orders.delete_by_id("a").unwrap();
+    assert_eq!(ids(&orders.deleted().unwrap()), ["a"]);
+
+    drop(orders);
+    drop(db);
+    cleanup(path);
+}
+
+#[test]
+fn test_with_deleted_includes_recycle_bin() {
+    let path = "/tmp/test_soft_delete_with_deleted.db";
+    let db = setup(path);
+    let orders = db.collection("orders");
+    orders.delete_by_id("b").unwrap();
+    orders.delete_by_id("c").unwrap();
+
+    assert_eq!(ids(&orders.query().execute().unwrap()), ["a", "d"]);
+    assert_eq!(ids(&orders.query().with_deleted().execute().unwrap()), ["a", "b", "c", "d"]);
+    assert_eq!(ids(&orders.query().filter("status is 'closed'").with_deleted().execute().unwrap()), ["c", "d"]);
+    assert_eq!(
+        ids(&orders.query().with_deleted().sort_by("ref", SortOrder::Desc).limit(3).execute().unwrap()),
+        ["d", "c", "b"]
+    );
+
+    assert_eq!(orders.query().count().unwrap(), 2);
+    assert_eq!(orders.query().with_deleted().count().unwrap(), 4);
+    assert_eq!(orders.query().filter("ref > 1").with_deleted().count().unwrap(), 3);
+
+    let explain = orders.query().filter("ref > 1").with_deleted().explain().unwrap();
+    assert_eq!(explain.docs_returned, 3);
+    assert_eq!(explain.docs_examined, 4);
+
+    drop(orders);
+    drop(db);
+    cleanup(path);
+}
+
+#[test]
+fn test_soft_delete_in_transactions_and_bulk_writes() {
+    let path = "/tmp/test_soft_delete_tx.db";
+    let db = setup(path);
+    let orders = db.collection("orders");
+
+    let mut tx = db.begin().unwrap();
+    tx.collection("orders").unwrap().delete_by_id("a").unwrap();
+    tx.rollback().unwrap();
+    assert!(orders.deleted().unwrap().is_empty());
+
+    let mut tx = db.begin().unwrap();
+    tx.collection("orders").unwrap().delete_by_id("a").unwrap();
+    tx.commit().unwrap();
+
+    let result = orders.bulk_write()
+        .delete_one("ref is 2")
+        .delete_many("status is 'closed'")
+        .execute()
+        .unwrap();
+    assert_eq!(result.deleted_count, 3);
+    assert_eq!(orders.find_one_and_delete("ref is 99").unwrap(), None);
+
+    assert_eq!(orders.count().unwrap(), 0);
+    assert_eq!(ids(&orders.deleted().unwrap()), ["a", "b", "c", "d"]);
+
+    // Turning soft delete off makes deletes permanent again
+    db.set_soft_delete("orders", false).unwrap();
+    orders.restore("a").unwrap();
+    orders.delete_by_id("a").unwrap();
+    assert_eq!(ids(&orders.deleted().unwrap()), ["b", "c", "d"]);
+
+    drop(orders);
+    drop(db);
+    cleanup(path);
+}
+
+#[test]
+fn test_recycle_bin_follows_rename_and_drop() {
+    let path = "/tmp/test_soft_delete_rename.db";
+    let db = setup(path);
+    db.collection("orders").delete_by_id("a").unwrap();
+
+    db.rename_collection("orders", "archive").unwrap();
+    let archive = db.collection("archive");
+    assert!(db.soft_delete("archive"));
+    assert_eq!(ids(&archive.deleted().unwrap()), ["a"]);
+    archive.restore("a").unwrap();
+    archive.delete_by_id("b").unwrap();
+    assert!(db.collection("orders").deleted().unwrap().is_empty());
+
+    let mut tx = db.begin().unwrap();
+    tx.drop_collection("archive").unwrap();
+    tx.commit().unwrap();
+    assert!(db.list_collections().unwrap().is_empty());
+    assert!(archive.deleted().unwrap().is_empty());
+
+    drop(archive);
+    drop(db);
+    cleanup(path);
+}
@@ -0,0 +1,111 @@
+use jasonisnthappy::core::database::{Database, TransactionConfig};
+use jasonisnthappy::Error;
+use serde_json::json;
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+fn open_with_timeout(path: &str, max_transaction_duration_ms: u64) -> Database {
+    let db = Database::open(path).unwrap();
+    db.set_transaction_config(TransactionConfig {
+        max_transaction_duration_ms,
+        ..TransactionConfig::default()
+    });
+    db
+}
+
+#[test]
+fn test_transaction_times_out() {
+    let path = "/tmp/test_tx_timeout.db";
+    cleanup(path);
+
+    let db = open_with_timeout(path, 50);
+
+    let mut tx = db.begin().unwrap();
+    tx.collection("users").unwrap().insert(json!({"name": "Alice"})).unwrap();
+    assert!(tx.is_active());
+
+    thread::sleep(Duration::from_millis(100));
+
+    assert!(!tx.is_active());
+    assert!(matches!(tx.collection("users"), Err(Error::TxTimeout)));
+    assert!(matches!(tx.commit(), Err(Error::TxTimeout)));
+    assert!(matches!(tx.rollback(), Err(Error::TxAlreadyDone)));
+
+    assert_eq!(db.collection("users").count().unwrap_or(0), 0);
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_transaction_timeout_fails_commit() {
+    let path = "/tmp/test_tx_timeout_commit.db";
+    cleanup(path);
+
+    let db = open_with_timeout(path, 50);
+
+    let mut tx = db.begin().unwrap();
+    tx.collection("users").unwrap().insert(json!({"name": "Alice"})).unwrap();
+
+    thread::sleep(Duration::from_millis(100));
+
+    assert!(matches!(tx.commit(), Err(Error::TxTimeout)));
+    assert_eq!(db.collection("users").count().unwrap_or(0), 0);
+
+    // New transactions get a fresh deadline
+    let mut tx = db.begin().unwrap();
+    tx.collection("users").unwrap().insert(json!({"name": "Bob"})).unwrap();
+    tx.commit().unwrap();
+    assert_eq!(db.collection("users").count().unwrap(), 1);
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_transaction_timeout_fails_reads() {
+    let path = "/tmp/test_tx_timeout_reads.db";
+    cleanup(path);
+
+    let db = open_with_timeout(path, 50);
+    let id = db.collection("users").insert(json!({"name": "Alice"})).unwrap();
+
+    let mut tx = db.begin().unwrap();
+    let mut users = tx.collection("users").unwrap();
+    assert_eq!(users.find_by_id(&id).unwrap()["name"], "Alice");
+
+    thread::sleep(Duration::from_millis(100));
+
+    assert!(matches!(users.find_by_id(&id), Err(Error::TxTimeout)));
+    assert!(matches!(users.find_all(), Err(Error::TxTimeout)));
+    assert!(matches!(users.insert(json!({"name": "Bob"})), Err(Error::TxTimeout)));
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_no_timeout_by_default() {
+    let path = "/tmp/test_tx_timeout_default.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    assert_eq!(db.get_transaction_config().max_transaction_duration_ms, 0);
+
+    let mut tx = db.begin().unwrap();
+    tx.collection("users").unwrap().insert(json!({"name": "Alice"})).unwrap();
+    thread::sleep(Duration::from_millis(50));
+    tx.commit().unwrap();
+
+    assert_eq!(db.collection("users").count().unwrap(), 1);
+
+    db.close().unwrap();
+    cleanup(path);
+}
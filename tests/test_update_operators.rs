@@ -0,0 +1,155 @@
+use jasonisnthappy::{Database, Error, UpsertResult};
+use serde_json::json;
+use std::fs;
+
+fn open(path: &str) -> Database {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+    Database::open(path).unwrap()
+}
+
+fn cleanup(db: Database, path: &str) {
+    db.close().unwrap();
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_update_operators_on_collection() {
+    let path = "/tmp/test_update_operators_collection.db";
+    let db = open(path);
+    let players = db.collection("players");
+
+    players.insert(json!({
+        "_id": "p1",
+        "name": "Ann",
+        "best": 50,
+        "worst": 50,
+        "scores": [{"value": 10}, {"value": 20}],
+        "tags": ["new"],
+    })).unwrap();
+
+    players.update_by_id("p1", json!({
+        "$inc": {"games": 1, "scores.1.value": 5},
+        "$max": {"best": 70},
+        "$min": {"worst": 60},
+        "$rename": {"name": "profile.name"},
+        "$push": {"tags": {"$each": ["pro", "mvp"], "$slice": -2}},
+    })).unwrap();
+
+    let doc = players.find_by_id("p1").unwrap();
+    assert_eq!(doc["games"], 1);
+    assert_eq!(doc["scores"], json!([{"value": 10}, {"value": 25}]));
+    assert_eq!(doc["best"], 70);
+    assert_eq!(doc["worst"], 50);
+    assert_eq!(doc["profile"], json!({"name": "Ann"}));
+    assert!(doc.get("name").is_none());
+    assert_eq!(doc["tags"], json!(["pro", "mvp"]));
+
+    // Query updates apply the operators to every match
+    players.insert(json!({"_id": "p2", "games": 4})).unwrap();
+    let count = players.update("games > 0", json!({"$inc": {"games": 10}})).unwrap();
+    assert_eq!(count, 2);
+    assert_eq!(players.find_by_id("p2").unwrap()["games"], 14);
+
+    assert!(players.update_one("games < 12", json!({"$set": {"scores.0.value": 0}})).unwrap());
+    assert_eq!(players.find_by_id("p1").unwrap()["scores"][0]["value"], 0);
+
+    cleanup(db, path);
+}
+
+#[test]
+fn test_update_operator_type_errors_leave_document_unchanged() {
+    let path = "/tmp/test_update_operators_errors.db";
+    let db = open(path);
+    let players = db.collection("players");
+
+    players.insert(json!({"_id": "p1", "name": "Ann", "tags": "new"})).unwrap();
+    let before = players.find_by_id("p1").unwrap();
+
+    for updates in [
+        json!({"$push": {"tags": "pro"}}),
+        json!({"$inc": {"name": 1}}),
+        json!({"$set": {"name.first": "A"}}),
+        json!({"$set": {"games": 1}, "name": "Bo"}),
+        json!({"$pull": {"tags": "new"}}),
+    ] {
+        let err = players.update_by_id("p1", updates.clone()).unwrap_err();
+        assert!(matches!(err, Error::InvalidUpdate { .. }), "{} gave {:?}", updates, err);
+    }
+    let err = players.update_by_id("p1", json!({"$push": {"tags": "pro"}})).unwrap_err();
+    assert!(err.to_string().contains("'tags'"), "{}", err);
+
+    assert_eq!(players.find_by_id("p1").unwrap(), before);
+
+    cleanup(db, path);
+}
+
+#[test]
+fn test_update_operators_in_transactions_bulk_writes_and_upserts() {
+    let path = "/tmp/test_update_operators_tx.db";
+    let db = open(path);
+    let counters = db.collection("counters");
+    counters.insert(json!({"_id": "hits", "n": 1})).unwrap();
+
+    let mut tx = db.begin().unwrap();
+    tx.collection("counters").unwrap().update_by_id("hits", json!({"$inc": {"n": 1}})).unwrap();
+    tx.commit().unwrap();
+    assert_eq!(counters.find_by_id("hits").unwrap()["n"], 2);
+
+    counters.bulk_write()
+        .update_one("n < 3", json!({"$inc": {"n": 3}}))
+        .update_many("n > 0", json!({"$push": {"log": "bulk"}}))
+        .execute()
+        .unwrap();
+    let doc = counters.find_by_id("hits").unwrap();
+    assert_eq!(doc["n"], 5);
+    assert_eq!(doc["log"], json!(["bulk"]));
+
+    // An upsert that inserts builds the document from the operators
+    let result = counters.upsert_by_id("misses", json!({"$inc": {"n": 1}, "$set": {"kind": "miss"}})).unwrap();
+    assert!(matches!(result, UpsertResult::Inserted(_)));
+    assert_eq!(counters.find_by_id("misses").unwrap(), json!({"_id": "misses", "n": 1, "kind": "miss"}));
+    let result = counters.upsert("kind is \"miss\"", json!({"$inc": {"n": 1}})).unwrap();
+    assert!(matches!(result, UpsertResult::Updated(_)));
+    assert_eq!(counters.find_by_id("misses").unwrap()["n"], 2);
+
+    cleanup(db, path);
+}
+
+#[test]
+fn test_update_operators_are_checked_against_schema() {
+    let path = "/tmp/test_update_operators_schema.db";
+    let db = open(path);
+
+    db.set_json_schema("users", &json!({
+        "type": "object",
+        "required": ["name"],
+        "properties": {
+            "name": {"type": "string"},
+            "age": {"type": "integer", "minimum": 0},
+            "tags": {"type": "array", "items": {"type": "string"}, "maxItems": 3}
+        }
+    })).unwrap();
+    let users = db.collection("users");
+    users.insert(json!({"_id": "u1", "name": "Ann", "age": 30, "tags": ["a"]})).unwrap();
+
+    // The document as it would be after the operators is what gets validated
+    assert!(users.update_by_id("u1", json!({"$inc": {"age": -40}})).is_err());
+    assert!(users.update_by_id("u1", json!({"$unset": {"name": ""}})).is_err());
+    assert!(users.update_by_id("u1", json!({"$rename": {"name": "full_name"}})).is_err());
+    assert!(users.update_by_id("u1", json!({"$push": {"tags": 5}})).is_err());
+    assert!(users.update_by_id("u1", json!({"$push": {"tags": {"$each": ["b", "c", "d"]}}})).is_err());
+    assert!(users.find_one_and_update("name is \"Ann\"", json!({"$inc": {"age": -40}}), true).is_err());
+    assert_eq!(users.find_by_id("u1").unwrap()["age"], 30);
+
+    // $slice can bring the array back within the schema's limits
+    users.update_by_id("u1", json!({"$push": {"tags": {"$each": ["b", "c", "d"], "$slice": -3}}})).unwrap();
+    assert_eq!(users.find_by_id("u1").unwrap()["tags"], json!(["b", "c", "d"]));
+    users.update_by_id("u1", json!({"$inc": {"age": 1}})).unwrap();
+    assert_eq!(users.find_by_id("u1").unwrap()["age"], 31);
+
+    cleanup(db, path);
+}
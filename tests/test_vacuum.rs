@@ -0,0 +1,104 @@
+use jasonisnthappy::core::database::Database;
+use jasonisnthappy::IdStrategy;
+use serde_json::json;
+use std::fs;
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+    let _ = fs::remove_file(format!("{}.vacuum", path));
+}
+
+fn file_size(path: &str) -> u64 {
+    fs::metadata(path).unwrap().len()
+}
+
+#[test]
+fn test_vacuum_shrinks_file_after_deletes() {
+    let path = "/tmp/test_vacuum_shrink.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    let items = db.collection("items");
+
+    let docs: Vec<_> = (0..2000)
+        .map(|i| json!({"n": i, "payload": "x".repeat(200)}))
+        .collect();
+    items.insert_many(docs).unwrap();
+    items.delete("n >= 100").unwrap();
+    db.checkpoint().unwrap();
+
+    let before = file_size(path);
+    let reclaimed = db.vacuum().unwrap();
+    let after = file_size(path);
+
+    assert!(reclaimed > 0);
+    assert_eq!(before - after, reclaimed);
+    assert_eq!(items.count().unwrap(), 100);
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_vacuum_preserves_data_indexes_and_settings() {
+    let path = "/tmp/test_vacuum_preserve.db";
+    cleanup(path);
+
+    {
+        let db = Database::open(path).unwrap();
+        db.set_id_strategy("users", IdStrategy::AutoIncrement).unwrap();
+        let users = db.collection("users");
+        for i in 0..50 {
+            users.insert(json!({"email": format!("user{}@example.com", i), "age": i})).unwrap();
+        }
+        users.delete("age >= 25").unwrap();
+        db.create_index("users", "email_idx", "email", true).unwrap();
+
+        db.vacuum().unwrap();
+
+        assert_eq!(users.count().unwrap(), 25);
+        let found = users.find("age < 5").unwrap();
+        assert_eq!(found.len(), 5);
+
+        let by_email = users.find("email is \"user3@example.com\"").unwrap();
+        assert_eq!(by_email.len(), 1);
+
+        // Writes keep working and the id counter carries on where it left off
+        let id = users.insert(json!({"email": "new@example.com", "age": 100})).unwrap();
+        assert_eq!(id, "51");
+
+        db.close().unwrap();
+    }
+
+    let db = Database::open(path).unwrap();
+    let users = db.collection("users");
+    assert_eq!(users.count().unwrap(), 26);
+    assert!(users.find_by_id("51").is_ok());
+    assert_eq!(db.get_id_strategy("users"), Some(IdStrategy::AutoIncrement));
+    assert!(db.list_indexes("users").unwrap().iter().any(|idx| idx.name == "email_idx"));
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_vacuum_refuses_with_open_transaction() {
+    let path = "/tmp/test_vacuum_open_tx.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    db.collection("items").insert(json!({"n": 1})).unwrap();
+
+    let mut tx = db.begin().unwrap();
+    let err = db.vacuum().unwrap_err();
+    assert!(err.to_string().contains("transaction(s) still open"));
+
+    tx.rollback().unwrap();
+    db.vacuum().unwrap();
+    assert_eq!(db.collection("items").count().unwrap(), 1);
+
+    db.close().unwrap();
+    cleanup(path);
+}
@@ -1,6 +1,6 @@
 // Test to verify WAL is being checkpointed and not growing unbounded
 
-use jasonisnthappy::core::database::Database;
+use jasonisnthappy::core::database::{Database, DatabaseOptions};
 use std::fs;
 use std::thread;
 use std::time::Duration;
@@ -105,3 +105,107 @@ fn test_performance_with_manual_checkpoint() {
     let _ = fs::remove_file(format!("{}.lock", test_path));
     let _ = fs::remove_file(format!("{}-wal", test_path));
 }
+
+fn wal_file_len(path: &str) -> u64 {
+    fs::metadata(format!("{}-wal", path)).unwrap().len()
+}
+
+#[test]
+fn test_max_wal_bytes_caps_wal_size() {
+    let test_path = "/tmp/test_max_wal_bytes.db";
+    let _ = fs::remove_file(test_path);
+    let _ = fs::remove_file(format!("{}.lock", test_path));
+    let _ = fs::remove_file(format!("{}-wal", test_path));
+
+    let cap = 512 * 1024;
+    let opts = DatabaseOptions {
+        auto_checkpoint_threshold: 0, // only the cap checkpoints
+        max_wal_bytes: cap,
+        ..Default::default()
+    };
+    let db = Database::open_with_options(test_path, opts).unwrap();
+    assert_eq!(db.max_wal_bytes(), cap);
+
+    let mut largest_commit = 0;
+    let mut largest_wal = 0;
+    let mut checkpoints = 0;
+    for batch in 0..60 {
+        let before = db.wal_size();
+        let docs: Vec<_> = (0..20)
+            .map(|i| serde_json::json!({
+                "_id": format!("doc_{}_{}", batch, i),
+                "data": "x".repeat(1000),
+            }))
+            .collect();
+        db.collection("test").insert_many(docs).unwrap();
+
+        let after = db.wal_size();
+        assert_eq!(after, wal_file_len(test_path));
+        if before >= cap {
+            // The commit checkpointed first, so only its own frames are left
+            checkpoints += 1;
+            largest_commit = largest_commit.max(after);
+        } else {
+            largest_commit = largest_commit.max(after - before);
+        }
+        largest_wal = largest_wal.max(after);
+    }
+
+    assert!(checkpoints >= 2, "only {} blocking checkpoints", checkpoints);
+    assert!(largest_wal < cap + largest_commit, "WAL reached {} bytes", largest_wal);
+    assert_eq!(db.collection("test").count().unwrap(), 60 * 20);
+
+    db.close().unwrap();
+    let db = Database::open(test_path).unwrap();
+    assert_eq!(db.collection("test").count().unwrap(), 60 * 20);
+    db.close().unwrap();
+
+    let _ = fs::remove_file(test_path);
+    let _ = fs::remove_file(format!("{}.lock", test_path));
+    let _ = fs::remove_file(format!("{}-wal", test_path));
+}
+
+#[test]
+fn test_checkpoint_truncate_empties_wal_file() {
+    let test_path = "/tmp/test_checkpoint_truncate.db";
+    let _ = fs::remove_file(test_path);
+    let _ = fs::remove_file(format!("{}.lock", test_path));
+    let _ = fs::remove_file(format!("{}-wal", test_path));
+
+    let db = Database::open(test_path).unwrap();
+    db.set_auto_checkpoint_threshold(0);
+    let coll = db.collection("test");
+    for i in 0..50 {
+        coll.insert(serde_json::json!({"_id": format!("a{}", i), "n": i})).unwrap();
+    }
+    assert!(wal_file_len(test_path) > 0);
+
+    db.checkpoint_truncate().unwrap();
+    assert_eq!(wal_file_len(test_path), 0);
+    assert_eq!(db.frame_count(), 0);
+    assert_eq!(coll.count().unwrap(), 50);
+
+    // Writing again brings the WAL header back
+    coll.insert(serde_json::json!({"_id": "b", "n": 50})).unwrap();
+    assert!(db.frame_count() > 0);
+    assert_eq!(db.wal_size(), wal_file_len(test_path));
+
+    db.checkpoint_truncate().unwrap();
+    db.close().unwrap();
+    assert_eq!(wal_file_len(test_path), 0);
+
+    // An empty WAL left behind is reopened like a new one
+    let db = Database::open(test_path).unwrap();
+    let coll = db.collection("test");
+    assert_eq!(coll.count().unwrap(), 51);
+    coll.insert(serde_json::json!({"_id": "c", "n": 51})).unwrap();
+    db.close().unwrap();
+
+    let db = Database::open(test_path).unwrap();
+    assert_eq!(db.collection("test").count().unwrap(), 52);
+    db.close().unwrap();
+
+    let _ = fs::remove_file(test_path);
+    let _ = fs::remove_file(format!("{}.lock", test_path));
+    let _ = fs::remove_file(format!("{}-wal", test_path));
+}
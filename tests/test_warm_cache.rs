@@ -0,0 +1,67 @@
+use jasonisnthappy::core::database::{Database, DatabaseOptions};
+use jasonisnthappy::Error;
+use serde_json::json;
+use std::fs;
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+fn seed(path: &str) {
+    let db = Database::open(path).unwrap();
+    let items = db.collection("items");
+    let docs = (0..2000).map(|n| json!({"_id": format!("item-{:05}", n), "n": n})).collect();
+    items.insert_many(docs).unwrap();
+    db.create_index("items", "n_idx", "n", false).unwrap();
+    db.collection("tags").insert(json!({"name": "red"})).unwrap();
+    db.close().unwrap();
+}
+
+#[test]
+fn test_warm_cache_loads_trees_and_documents() {
+    let path = "/tmp/test_warm_cache.db";
+    cleanup(path);
+    seed(path);
+
+    let db = Database::open(path).unwrap();
+    let trees = db.warm_cache("items", false).unwrap();
+    assert!(trees.tree_pages > 1);
+    assert_eq!(trees.document_pages, 0);
+    assert!(!trees.cache_full);
+
+    let warmup = db.warm_cache("items", true).unwrap();
+    assert_eq!(warmup.tree_pages, trees.tree_pages);
+    assert!(warmup.document_pages >= 2000);
+    assert_eq!(warmup.pages(), warmup.tree_pages + warmup.document_pages);
+
+    // Everything a full scan needs is already cached
+    let misses = db.metrics().cache_misses;
+    assert_eq!(db.collection("items").find("n >= 0").unwrap().len(), 2000);
+    assert_eq!(db.metrics().cache_misses, misses);
+
+    let all = db.warm_cache_all(true).unwrap();
+    assert!(all.pages() > warmup.pages());
+
+    assert!(matches!(db.warm_cache("missing", true), Err(Error::CollectionDoesNotExist { .. })));
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_warm_cache_stops_when_cache_is_full() {
+    let path = "/tmp/test_warm_cache_full.db";
+    cleanup(path);
+    seed(path);
+
+    let opts = DatabaseOptions { cache_size: 64, ..Default::default() };
+    let db = Database::open_with_options(path, opts).unwrap();
+    let warmup = db.warm_cache_all(true).unwrap();
+    assert!(warmup.cache_full);
+    assert!(warmup.pages() <= 64 + 16, "read {} pages", warmup.pages());
+
+    db.close().unwrap();
+    cleanup(path);
+}
@@ -0,0 +1,451 @@
+#![cfg(feature = "web-ui")]
+
+use jasonisnthappy::core::database::{Database, DatabaseOptions};
+use jasonisnthappy::WebServerOptions;
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+/// Send a request and return the status code and parsed JSON body
+fn http(addr: &str, method: &str, path: &str, body: Option<&str>) -> (u16, Value) {
+    let (status, _, body) = http_with_headers(addr, method, path, &[], body);
+    (status, body)
+}
+
+/// Like `http`, with extra request headers, also returning the raw response headers
+fn http_with_headers(
+    addr: &str,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: Option<&str>,
+) -> (u16, String, Value) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let body = body.unwrap_or("");
+    let extra: String = headers.iter().map(|(k, v)| format!("{}: {}\r\n", k, v)).collect();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+        method, path, addr, body.len(), extra, body
+    ).unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    let status = response[9..12].parse().unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+    let body = if head.to_ascii_lowercase().contains("transfer-encoding: chunked") {
+        dechunk(body)
+    } else {
+        body.to_string()
+    };
+    (status, head.to_string(), serde_json::from_str(&body).unwrap_or(Value::Null))
+}
+
+/// Join the chunks of a chunked response body
+fn dechunk(mut body: &str) -> String {
+    let mut out = String::new();
+    while let Some((size, rest)) = body.split_once("\r\n") {
+        let size = usize::from_str_radix(size.trim(), 16).unwrap();
+        if size == 0 {
+            break;
+        }
+        out.push_str(&rest[..size]);
+        body = &rest[size + 2..];
+    }
+    out
+}
+
+/// The value of a response header, matched case-insensitively
+fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim())
+}
+
+#[test]
+fn test_rest_crud_and_queries() {
+    let path = "/tmp/test_web_api_crud.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    let users = db.collection("users");
+    for (name, age) in [("Alice", 30), ("Bob", 25), ("Carol", 35), ("Dan", 40)] {
+        users.insert(json!({"name": name, "age": age, "team": if age > 30 { "a" } else { "b" }})).unwrap();
+    }
+
+    let addr = "127.0.0.1:18731";
+    let server = db.start_web_ui(addr).unwrap();
+
+    let (status, body) = http(addr, "GET", "/api/collections", None);
+    assert_eq!(status, 200);
+    assert_eq!(body, json!(["users"]));
+
+    let (status, body) = http(addr, "GET", "/api/slow-queries", None);
+    assert_eq!(status, 200);
+    assert_eq!(body, json!([]));
+
+    let (status, body) = http(addr, "GET", "/api/collections/users?filter=age%20%3E%2028&sort=-age&limit=2", None);
+    assert_eq!(status, 200);
+    let names: Vec<&str> = body.as_array().unwrap().iter().map(|d| d["name"].as_str().unwrap()).collect();
+    assert_eq!(names, vec!["Dan", "Carol"]);
+
+    let (status, body) = http(addr, "GET", "/api/collections/users?sort=age&skip=1&limit=1", None);
+    assert_eq!(status, 200);
+    assert_eq!(body[0]["name"], "Alice");
+
+    let (status, body) = http(addr, "GET", "/api/collections/users?filter=age%20%3E", None);
+    assert_eq!(status, 400);
+    assert!(body["error"].is_string());
+
+    let (status, _) = http(addr, "GET", "/api/collections/missing", None);
+    assert_eq!(status, 404);
+
+    let (status, body) = http(addr, "POST", "/api/collections/users", Some(r#"{"name": "Eve", "age": 22}"#));
+    assert_eq!(status, 201);
+    let id = body["id"].as_str().unwrap().to_string();
+
+    let (status, body) = http(addr, "GET", &format!("/api/collections/users/{}", id), None);
+    assert_eq!(status, 200);
+    assert_eq!(body["name"], "Eve");
+
+    let (status, _) = http(addr, "PUT", &format!("/api/collections/users/{}", id), Some(r#"{"age": 23}"#));
+    assert_eq!(status, 200);
+    assert_eq!(users.find_by_id(&id).unwrap()["age"], 23);
+
+    let (status, _) = http(addr, "PUT", "/api/collections/users/nope", Some(r#"{"age": 1}"#));
+    assert_eq!(status, 404);
+
+    let (status, _) = http(addr, "POST", "/api/collections/users", Some("{not json"));
+    assert_eq!(status, 400);
+
+    let (status, _) = http(addr, "DELETE", &format!("/api/collections/users/{}", id), None);
+    assert_eq!(status, 200);
+    let (status, _) = http(addr, "GET", &format!("/api/collections/users/{}", id), None);
+    assert_eq!(status, 404);
+    let (status, _) = http(addr, "DELETE", &format!("/api/collections/users/{}", id), None);
+    assert_eq!(status, 404);
+
+    let pipeline = r#"[{"group_by": "team"}, {"count": "total"}, {"sort": {"field": "total", "asc": false}}]"#;
+    let (status, body) = http(addr, "POST", "/api/collections/users/aggregate", Some(pipeline));
+    assert_eq!(status, 200);
+    assert_eq!(body.as_array().unwrap().len(), 2);
+    assert_eq!(body[0]["total"], 2);
+
+    let (status, _) = http(addr, "POST", "/api/collections/users/aggregate", Some(r#"[{"bogus": 1}]"#));
+    assert_eq!(status, 400);
+
+    let pipeline = r#"[{"group_by_date": {"field": "joined", "granularity": "week"}}]"#;
+    let (status, _) = http(addr, "POST", "/api/collections/users/aggregate", Some(pipeline));
+    assert_eq!(status, 400);
+
+    server.shutdown();
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_document_listing_pages_and_streams() {
+    let path = "/tmp/test_web_api_paging.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    let items = db.collection("items");
+    let docs: Vec<Value> = (0..1050).map(|n| json!({"n": n})).collect();
+    items.insert_many(docs).unwrap();
+
+    let addr = "127.0.0.1:18738";
+    let server = db.start_web_ui(addr).unwrap();
+
+    // Without a limit a page holds the default 100 documents
+    let (status, head, body) = http_with_headers(addr, "GET", "/api/collections/items?sort=n", &[], None);
+    assert_eq!(status, 200);
+    assert!(head.to_ascii_lowercase().contains("transfer-encoding: chunked"), "{}", head);
+    assert_eq!(body.as_array().unwrap().len(), 100);
+    assert_eq!(header(&head, "X-Next-Cursor"), Some("100"));
+
+    // Limits past the maximum are clamped to it
+    let (_, head, body) = http_with_headers(addr, "GET", "/api/collections/items?sort=n&limit=5000", &[], None);
+    assert_eq!(body.as_array().unwrap().len(), 1000);
+    assert_eq!(body[999]["n"], 999);
+
+    // Following the cursor walks to the last page, which has no cursor
+    let cursor = header(&head, "X-Next-Cursor").unwrap();
+    let (_, head, body) = http_with_headers(
+        addr, "GET", &format!("/api/collections/items?sort=n&limit=1000&cursor={}", cursor), &[], None,
+    );
+    assert_eq!(body.as_array().unwrap().len(), 50);
+    assert_eq!(body[0]["n"], 1000);
+    assert_eq!(header(&head, "X-Next-Cursor"), None);
+
+    let (status, body) = http(addr, "GET", "/api/collections/items?filter=n%20%3C%200", None);
+    assert_eq!(status, 200);
+    assert_eq!(body, json!([]));
+
+    let (status, _) = http(addr, "GET", "/api/collections/items?cursor=abc", None);
+    assert_eq!(status, 400);
+
+    server.shutdown();
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_rest_rejects_oversized_body() {
+    let path = "/tmp/test_web_api_body_limit.db";
+    cleanup(path);
+
+    let opts = DatabaseOptions {
+        max_request_body_size: 64,
+        ..Default::default()
+    };
+    let db = Database::open_with_options(path, opts).unwrap();
+
+    let addr = "127.0.0.1:18732";
+    let server = db.start_web_ui(addr).unwrap();
+
+    let big = json!({"payload": "x".repeat(100)}).to_string();
+    let (status, body) = http(addr, "POST", "/api/collections/items", Some(&big));
+    assert_eq!(status, 413);
+    assert!(body["error"].as_str().unwrap().contains("64"));
+
+    let (status, _) = http(addr, "POST", "/api/collections/items", Some(r#"{"n": 1}"#));
+    assert_eq!(status, 201);
+
+    server.shutdown();
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_auth_token_and_read_only() {
+    let path = "/tmp/test_web_api_auth.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    db.collection("secrets").insert(json!({"_id": "s1", "value": "hunter2"})).unwrap();
+
+    let addr = "127.0.0.1:18733";
+    let options = WebServerOptions {
+        auth_token: Some("letmein".to_string()),
+        read_only: true,
+        ..Default::default()
+    };
+    let server = db.start_web_ui_with_options(addr, options).unwrap();
+
+    let (status, head, body) = http_with_headers(addr, "GET", "/api/collections/secrets/s1", &[], None);
+    assert_eq!(status, 401);
+    assert!(head.contains("WWW-Authenticate: Bearer"));
+    assert!(!body.to_string().contains("hunter2"));
+
+    let wrong = [("Authorization", "Bearer nope")];
+    let (status, _, _) = http_with_headers(addr, "GET", "/api/collections/secrets", &wrong, None);
+    assert_eq!(status, 401);
+    let (status, _, _) = http_with_headers(addr, "GET", "/metrics", &wrong, None);
+    assert_eq!(status, 401);
+
+    // Only change streams accept the token as a query parameter
+    let (status, _) = http(addr, "GET", "/api/collections/secrets?access_token=letmein", None);
+    assert_eq!(status, 401);
+    let (status, _) = http(addr, "GET", "/api/collections/secrets/watch?access_token=nope", None);
+    assert_eq!(status, 401);
+
+    // Health checks and the dashboard page stay reachable
+    let (status, _) = http(addr, "GET", "/health", None);
+    assert_eq!(status, 200);
+
+    let auth = [("Authorization", "Bearer letmein")];
+    let (status, _, body) = http_with_headers(addr, "GET", "/api/collections/secrets/s1", &auth, None);
+    assert_eq!(status, 200);
+    assert_eq!(body["value"], "hunter2");
+
+    let (status, _, _) = http_with_headers(addr, "POST", "/api/collections/secrets", &auth, Some(r#"{"value": "x"}"#));
+    assert_eq!(status, 403);
+    let (status, _, _) = http_with_headers(addr, "DELETE", "/api/collections/secrets/s1", &auth, None);
+    assert_eq!(status, 403);
+    assert_eq!(db.collection("secrets").count().unwrap(), 1);
+
+    // Aggregation only reads, so it is still allowed
+    let (status, _, _) = http_with_headers(addr, "POST", "/api/collections/secrets/aggregate", &auth, Some(r#"[{"group_by": "value"}, {"count": "n"}]"#));
+    assert_eq!(status, 200);
+
+    server.shutdown();
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_cors_headers() {
+    let path = "/tmp/test_web_api_cors.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    let addr = "127.0.0.1:18734";
+    let options = WebServerOptions {
+        auth_token: Some("letmein".to_string()),
+        allowed_origins: vec!["http://localhost:3000".to_string()],
+        ..Default::default()
+    };
+    let server = db.start_web_ui_with_options(addr, options).unwrap();
+
+    // Preflights succeed without credentials
+    let origin = [("Origin", "http://localhost:3000")];
+    let (status, head, _) = http_with_headers(addr, "OPTIONS", "/api/collections", &origin, None);
+    assert_eq!(status, 204);
+    assert!(head.contains("Access-Control-Allow-Origin: http://localhost:3000"));
+    assert!(head.contains("Access-Control-Allow-Headers: Authorization, Content-Type"));
+
+    // Rejections carry CORS headers too so the browser can surface the 401
+    let (status, head, _) = http_with_headers(addr, "GET", "/api/collections", &origin, None);
+    assert_eq!(status, 401);
+    assert!(head.contains("Access-Control-Allow-Origin: http://localhost:3000"));
+
+    let other = [("Origin", "http://evil.example"), ("Authorization", "Bearer letmein")];
+    let (status, head, _) = http_with_headers(addr, "GET", "/api/collections", &other, None);
+    assert_eq!(status, 200);
+    assert!(!head.contains("Access-Control-Allow-Origin"));
+
+    server.shutdown();
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_watch_streams_changes_and_cleans_up() {
+    let path = "/tmp/test_web_api_watch.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    let users = db.collection("users");
+    let addr = "127.0.0.1:18735";
+    let server = db.start_web_ui(addr).unwrap();
+
+    let (status, _) = http(addr, "GET", "/api/collections/users/watch?filter=age%20%3E", None);
+    assert_eq!(status, 400);
+
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    write!(stream, "GET /api/collections/users/watch?filter=age%20%3E%2018 HTTP/1.1\r\nHost: {}\r\n\r\n", addr).unwrap();
+
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    assert!(line.starts_with("HTTP/1.1 200"));
+    let mut content_type = String::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        if line == "\r\n" {
+            break;
+        }
+        if line.to_lowercase().starts_with("content-type") {
+            content_type = line.clone();
+        }
+    }
+    assert!(content_type.contains("text/event-stream"));
+    assert_eq!(server.active_streams(), 1);
+
+    users.insert(json!({"_id": "kid", "age": 10})).unwrap();
+    users.insert(json!({"_id": "adult", "age": 30})).unwrap();
+
+    // Only the event matching the filter comes through
+    let mut event = String::new();
+    reader.read_line(&mut event).unwrap();
+    assert_eq!(event, "event: insert\n");
+    let mut data = String::new();
+    reader.read_line(&mut data).unwrap();
+    let payload: Value = serde_json::from_str(data.trim_start_matches("data: ").trim()).unwrap();
+    assert_eq!(payload["doc_id"], "adult");
+    assert_eq!(payload["operation"], "insert");
+    assert_eq!(payload["document"]["age"], 30);
+
+    // Once the client goes away the next writes fail and the subscription is released
+    drop(reader);
+    drop(stream);
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut n = 0;
+    while server.active_streams() > 0 && Instant::now() < deadline {
+        n += 1;
+        users.insert(json!({"age": 40 + n})).unwrap();
+        thread::sleep(Duration::from_millis(50));
+    }
+    assert_eq!(server.active_streams(), 0);
+
+    server.shutdown();
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_shutdown_with_open_watch_stream() {
+    let path = "/tmp/test_web_api_watch_shutdown.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    let addr = "127.0.0.1:18736";
+    let server = db.start_web_ui(addr).unwrap();
+
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(stream, "GET /api/collections/users/watch HTTP/1.1\r\nHost: {}\r\n\r\n", addr).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while server.active_streams() == 0 && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(server.active_streams(), 1);
+
+    let start = Instant::now();
+    server.shutdown();
+    assert!(start.elapsed() < Duration::from_secs(2));
+
+    drop(stream);
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_prometheus_metrics_endpoint() {
+    let path = "/tmp/test_web_api_metrics.db";
+    cleanup(path);
+
+    let db = Database::open(path).unwrap();
+    let users = db.collection("users");
+    let id = users.insert(json!({"name": "Alice"})).unwrap();
+    users.find_by_id(&id).unwrap();
+
+    let addr = "127.0.0.1:18737";
+    let server = db.start_web_ui(addr).unwrap();
+
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(stream, "GET /metrics HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", addr).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, text) = response.split_once("\r\n\r\n").unwrap();
+    assert!(head.starts_with("HTTP/1.1 200"));
+    assert!(head.contains("text/plain; version=0.0.4"));
+    assert!(text.contains("jasonisnthappy_operation_duration_seconds_count{operation=\"insert\"} 1\n"));
+    assert!(text.contains("jasonisnthappy_operation_duration_seconds_count{operation=\"find\"} 1\n"));
+    assert!(text.contains("jasonisnthappy_operation_duration_seconds_count{operation=\"commit\"} 1\n"));
+    assert!(text.contains("jasonisnthappy_operation_duration_quantile_seconds{operation=\"insert\",quantile=\"0.99\"}"));
+
+    let (status, body) = http(addr, "GET", "/api/metrics", None);
+    assert_eq!(status, 200);
+    assert_eq!(body["insert_latency"]["count"], 1);
+    assert_eq!(body["insert_latency"]["p50_micros"], body["insert_latency"]["max_micros"]);
+
+    db.reset_latency_metrics();
+    assert_eq!(db.metrics().insert_latency.count, 0);
+
+    server.shutdown();
+    db.close().unwrap();
+    cleanup(path);
+}
@@ -0,0 +1,93 @@
+use jasonisnthappy::{Database, Error};
+use serde_json::json;
+use std::fs;
+
+fn cleanup(path: &str) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(format!("{}.lock", path));
+    let _ = fs::remove_file(format!("{}-wal", path));
+}
+
+#[test]
+fn test_with_batch_commits_every_n_writes() {
+    let path = "/tmp/test_write_batch_commits.db";
+    cleanup(path);
+    let db = Database::open(path).unwrap();
+    let events = db.collection("events");
+
+    let before = db.metrics().transactions_committed;
+    let committed = events.with_batch(1000, |batch| {
+        for i in 0..2500 {
+            batch.insert(json!({"_id": format!("ev{:05}", i), "seq": i}))?;
+        }
+        assert_eq!(batch.committed(), 2000);
+        assert_eq!(batch.pending(), 500);
+        Ok(batch.committed())
+    }).unwrap();
+    assert_eq!(committed, 2000);
+    assert_eq!(events.count().unwrap(), 2500);
+    assert_eq!(db.metrics().transactions_committed - before, 3);
+
+    events.with_batch(2, |batch| {
+        batch.update_by_id("ev00000", json!({"seen": true}))?;
+        batch.delete_by_id("ev00001")?;
+        batch.flush()?;
+        assert_eq!(batch.committed(), 2);
+        batch.insert(json!({"_id": "last"}))
+    }).unwrap();
+    assert_eq!(events.find_by_id("ev00000").unwrap()["seen"], true);
+    assert!(events.find_by_id("ev00001").is_err());
+    assert_eq!(events.count().unwrap(), 2500);
+
+    db.close().unwrap();
+    cleanup(path);
+}
+
+#[test]
+fn test_with_batch_failures_discard_the_current_batch() {
+    let path = "/tmp/test_write_batch_failures.db";
+    cleanup(path);
+    let db = Database::open(path).unwrap();
+    let events = db.collection("events");
+    events.insert(json!({"_id": "dup"})).unwrap();
+
+    // A failed write discards its batch; batches committed before it stay
+    let result = events.with_batch(10, |batch| {
+        for i in 0..25 {
+            batch.insert(json!({"_id": format!("a{:02}", i)}))?;
+        }
+        batch.insert(json!({"_id": "dup"}))?;
+        Ok(())
+    });
+    assert!(matches!(result, Err(Error::DocumentAlreadyExists { .. })), "{:?}", result);
+    assert_eq!(events.count().unwrap(), 21);
+    assert!(events.find_by_id("a19").is_ok());
+    assert!(events.find_by_id("a20").is_err());
+
+    // So does an error from the closure
+    let result: jasonisnthappy::Result<()> = events.with_batch(10, |batch| {
+        for i in 0..5 {
+            batch.insert(json!({"_id": format!("b{}", i)}))?;
+        }
+        Err(Error::Other("producer failed".to_string()))
+    });
+    assert!(result.is_err());
+    assert_eq!(events.count().unwrap(), 21);
+
+    // A write after a failed one starts a new batch
+    events.with_batch(10, |batch| {
+        assert!(batch.insert(json!({"_id": "dup"})).is_err());
+        batch.insert(json!({"_id": "c0"}))
+    }).unwrap();
+    assert_eq!(events.count().unwrap(), 22);
+
+    assert!(events.with_batch(0, |_| Ok(())).is_err());
+    let snapshot = db.snapshot().unwrap();
+    let frozen = snapshot.collection("events");
+    assert!(matches!(frozen.with_batch(10, |_| Ok(())), Err(Error::ReadOnlyTransaction)));
+    drop(frozen);
+    drop(snapshot);
+
+    db.close().unwrap();
+    cleanup(path);
+}
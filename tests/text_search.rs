@@ -3,7 +3,7 @@
 //! This file contains all text search and indexing tests for jasonisnthappy.
 //! Tests are organized into sections covering different aspects of text search functionality.
 
-use jasonisnthappy::Database;
+use jasonisnthappy::{Database, Language, TextIndexOptions};
 use serde_json::json;
 use tempfile::tempdir;
 
@@ -24,7 +24,7 @@ fn test_create_text_index() {
     })).unwrap();
 
     // Create text index
-    db.create_text_index("posts", "search_idx", &["title", "body"]).unwrap();
+    db.create_text_index("posts", "search_idx", &["title", "body"], TextIndexOptions::default()).unwrap();
 
     // Verify index was created (will test search separately)
 }
@@ -48,7 +48,7 @@ fn test_text_search_single_field() {
     })).unwrap();
 
     // Index only the title field
-    db.create_text_index("posts", "title_idx", &["title"]).unwrap();
+    db.create_text_index("posts", "title_idx", &["title"], TextIndexOptions::default()).unwrap();
 
     // Search for "rust" - should only find doc1 (in title)
     let results = posts.search("rust").unwrap();
@@ -76,7 +76,7 @@ fn test_text_search_existing_documents() {
     })).unwrap();
 
     // Create index - should index existing documents
-    db.create_text_index("posts", "search_idx", &["title", "body"]).unwrap();
+    db.create_text_index("posts", "search_idx", &["title", "body"], TextIndexOptions::default()).unwrap();
 
     // Search should find existing documents
     let results = posts.search("rust").unwrap();
@@ -113,7 +113,7 @@ fn test_text_search_basic() {
     })).unwrap();
 
     // Create text index
-    db.create_text_index("posts", "search_idx", &["title", "body"]).unwrap();
+    db.create_text_index("posts", "search_idx", &["title", "body"], TextIndexOptions::default()).unwrap();
 
     // Search for "rust"
     let results = posts.search("rust").unwrap();
@@ -139,7 +139,7 @@ fn test_text_search_no_results() {
         "body": "Learn Rust"
     })).unwrap();
 
-    db.create_text_index("posts", "search_idx", &["title", "body"]).unwrap();
+    db.create_text_index("posts", "search_idx", &["title", "body"], TextIndexOptions::default()).unwrap();
 
     // Search for term that doesn't exist
     let results = posts.search("python").unwrap();
@@ -159,7 +159,7 @@ fn test_text_search_empty_query() {
         "body": "Test content"
     })).unwrap();
 
-    db.create_text_index("posts", "search_idx", &["title", "body"]).unwrap();
+    db.create_text_index("posts", "search_idx", &["title", "body"], TextIndexOptions::default()).unwrap();
 
     // Empty query should return no results
     let results = posts.search("").unwrap();
@@ -209,7 +209,7 @@ fn test_text_search_ranking() {
     })).unwrap();
 
     // Create text index and search
-    db.create_text_index("posts", "search_idx", &["title", "body"]).unwrap();
+    db.create_text_index("posts", "search_idx", &["title", "body"], TextIndexOptions::default()).unwrap();
     let results = posts.search("database").unwrap();
 
     // doc1 should rank higher (mentioned more times)
@@ -241,7 +241,7 @@ fn test_text_search_multiple_terms() {
         "body": "Learn Python programming"
     })).unwrap();
 
-    db.create_text_index("posts", "search_idx", &["title", "body"]).unwrap();
+    db.create_text_index("posts", "search_idx", &["title", "body"], TextIndexOptions::default()).unwrap();
 
     // Search for multiple terms
     let results = posts.search("rust database").unwrap();
@@ -254,6 +254,68 @@ fn test_text_search_multiple_terms() {
     assert_eq!(results[1].doc_id, doc2);
 }
 
+#[test]
+fn test_text_search_keyword_stuffing() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let db = Database::open(db_path.to_str().unwrap()).unwrap();
+
+    let posts = db.collection("posts");
+
+    let _spam = posts.insert(json!({
+        "body": "rust rust rust rust rust rust rust rust rust rust"
+    })).unwrap();
+
+    let relevant = posts.insert(json!({
+        "body": "A practical rust borrow checker guide"
+    })).unwrap();
+
+    let _other = posts.insert(json!({
+        "body": "Python guide"
+    })).unwrap();
+
+    db.create_text_index("posts", "search_idx", &["body"], TextIndexOptions::default()).unwrap();
+
+    // Repeating a term saturates, so matching the rarer term wins
+    let results = posts.search("rust borrow").unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].doc_id, relevant);
+}
+
+#[test]
+fn test_text_search_explain() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let db = Database::open(db_path.to_str().unwrap()).unwrap();
+
+    let posts = db.collection("posts");
+    let doc1 = posts.insert(json!({
+        "title": "Rust Programming",
+        "body": "Building a database in Rust"
+    })).unwrap();
+
+    db.create_text_index("posts", "search_idx", &["title", "body"], TextIndexOptions::default()).unwrap();
+
+    // Plain search doesn't carry components
+    let results = posts.search("rust database").unwrap();
+    assert!(results[0].components.is_none());
+
+    let results = posts.search_explain("rust database").unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].doc_id, doc1);
+
+    let components = results[0].components.as_ref().unwrap();
+    let mut matched: Vec<(&str, &str)> = components.iter()
+        .map(|c| (c.term.as_str(), c.field.as_str()))
+        .collect();
+    matched.sort();
+    assert_eq!(matched, vec![("database", "body"), ("rust", "body"), ("rust", "title")]);
+
+    // Components add up to the total score
+    let total: f32 = components.iter().map(|c| c.score).sum();
+    assert!((total - results[0].score).abs() < 1e-5);
+}
+
 // ============================================================================
 // Case Sensitivity and Unicode Tests
 // ============================================================================
@@ -271,7 +333,7 @@ fn test_text_search_case_insensitive() {
         "body": "Learn RUST language"
     })).unwrap();
 
-    db.create_text_index("posts", "search_idx", &["title", "body"]).unwrap();
+    db.create_text_index("posts", "search_idx", &["title", "body"], TextIndexOptions::default()).unwrap();
 
     // Search should be case-insensitive
     let results1 = posts.search("rust").unwrap();
@@ -297,7 +359,7 @@ fn test_text_search_special_characters() {
         "body": "Testing special chars: @#$% & more!"
     })).unwrap();
 
-    db.create_text_index("posts", "search_idx", &["title", "body"]).unwrap();
+    db.create_text_index("posts", "search_idx", &["title", "body"], TextIndexOptions::default()).unwrap();
 
     // Search for words without punctuation
     let results = posts.search("hello world").unwrap();
@@ -318,7 +380,7 @@ fn test_text_search_unicode() {
         "body": "Unicode support is important"
     })).unwrap();
 
-    db.create_text_index("posts", "search_idx", &["title", "body"]).unwrap();
+    db.create_text_index("posts", "search_idx", &["title", "body"], TextIndexOptions::default()).unwrap();
 
     // Search should handle unicode properly
     let results = posts.search("amazing").unwrap();
@@ -344,7 +406,7 @@ fn test_text_index_persistence() {
             "body": "This should work after reopening"
         })).unwrap();
 
-        db.create_text_index("posts", "search_idx", &["title", "body"]).unwrap();
+        db.create_text_index("posts", "search_idx", &["title", "body"], TextIndexOptions::default()).unwrap();
         id
     };
 
@@ -373,7 +435,7 @@ fn test_simple_index_and_search() {
         })).unwrap();
 
         // Create index
-        db.create_text_index("posts", "idx", &["title"]).unwrap();
+        db.create_text_index("posts", "idx", &["title"], TextIndexOptions::default()).unwrap();
 
         // Force flush
         db.checkpoint().unwrap();
@@ -390,6 +452,105 @@ fn test_simple_index_and_search() {
     }
 }
 
+// ============================================================================
+// Tokenization Options Tests
+// ============================================================================
+
+fn english_options() -> TextIndexOptions {
+    TextIndexOptions {
+        stopwords: vec!["the".to_string(), "a".to_string(), "is".to_string()],
+        stemming: Some(Language::English),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_text_search_stemming() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let db = Database::open(db_path.to_str().unwrap()).unwrap();
+
+    let posts = db.collection("posts");
+    let doc1 = posts.insert(json!({
+        "title": "Running a database",
+        "body": "The engine is running"
+    })).unwrap();
+    posts.insert(json!({
+        "title": "Cooking pasta",
+        "body": "Boil the water"
+    })).unwrap();
+
+    db.create_text_index("posts", "search_idx", &["title", "body"], english_options()).unwrap();
+
+    // "run" matches "running" once both are stemmed
+    let results = posts.search("run").unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].doc_id, doc1);
+
+    // Query terms are stemmed too
+    let results = posts.search("runs").unwrap();
+    assert_eq!(results.len(), 1);
+}
+
+#[test]
+fn test_text_search_stopwords() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let db = Database::open(db_path.to_str().unwrap()).unwrap();
+
+    let posts = db.collection("posts");
+    posts.insert(json!({"title": "The Rust book", "body": "It is great"})).unwrap();
+
+    db.create_text_index("posts", "search_idx", &["title", "body"], english_options()).unwrap();
+
+    // Stopwords are never indexed, so searching for them finds nothing
+    assert!(posts.search("the").unwrap().is_empty());
+    assert!(posts.search("is").unwrap().is_empty());
+    assert_eq!(posts.search("the rust").unwrap().len(), 1);
+}
+
+#[test]
+fn test_text_index_options_in_list_indexes() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let path = db_path.to_str().unwrap();
+
+    {
+        let db = Database::open(path).unwrap();
+        db.collection("posts").insert(json!({"title": "Hello"})).unwrap();
+        db.create_text_index("posts", "search_idx", &["title"], english_options()).unwrap();
+        db.close().unwrap();
+    }
+
+    let db = Database::open(path).unwrap();
+    let indexes = db.list_indexes("posts").unwrap();
+    let text_idx = indexes.iter().find(|idx| idx.name == "search_idx").unwrap();
+    assert_eq!(text_idx.fields, vec!["title"]);
+    assert_eq!(text_idx.text_options, Some(english_options()));
+}
+
+#[test]
+fn test_text_index_options_change_requires_rebuild() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let db = Database::open(db_path.to_str().unwrap()).unwrap();
+
+    let posts = db.collection("posts");
+    posts.insert(json!({"title": "Running fast"})).unwrap();
+
+    db.create_text_index("posts", "search_idx", &["title"], TextIndexOptions::default()).unwrap();
+    assert!(posts.search("run").unwrap().is_empty());
+
+    // Different options on the same index are rejected
+    let err = db.create_text_index("posts", "search_idx", &["title"], english_options()).unwrap_err();
+    assert!(err.to_string().contains("different options"));
+
+    // Dropping and recreating rebuilds with the new options
+    db.drop_index("posts", "search_idx").unwrap();
+    db.create_text_index("posts", "search_idx", &["title"], english_options()).unwrap();
+    assert_eq!(posts.search("run").unwrap().len(), 1);
+}
+
 // ============================================================================
 // Debug and Diagnostic Tests
 // ============================================================================
@@ -411,7 +572,7 @@ fn test_debug_text_index() {
     println!("Inserted document: {}", doc_id);
 
     // Create text index
-    let result = db.create_text_index("posts", "search_idx", &["title", "body"]);
+    let result = db.create_text_index("posts", "search_idx", &["title", "body"], TextIndexOptions::default());
     println!("Create index result: {:?}", result);
     assert!(result.is_ok());
 